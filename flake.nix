@@ -14,14 +14,15 @@
 
       imports = [
         ./helper-proc-macros/build.nix
-        ./stupid-dbg/build.nix
+        ./stupid-dbg-core/build.nix
+        ./stupid-dbg-cli/build.nix
         ./pre-commit.nix
         ./settings.nix
       ];
 
       perSystem = { config, ... }:
         {
-          packages.default = config.packages.stupid-dbg-rust;
+          packages.default = config.packages.stupid-dbg-cli-rust;
           devShells.default = config.devShells.dev-pre-commit;
         };
     };
@@ -36,6 +36,13 @@ struct RId {
     id: usize,
 }
 
+#[derive(Debug)]
+struct XstateVector {
+    prefix: &'static str,
+    id: usize,
+    byte_width: usize,
+}
+
 #[derive(Debug)]
 enum RegDef {
     Gpr64(Gpr64),
@@ -45,6 +52,8 @@ enum RegDef {
     FpMM(RId),
     FpXMM(RId),
     Dr(RId),
+    // not in the user struct but in the XSAVE area
+    Xstate(XstateVector),
 }
 
 #[derive(Debug)]
@@ -169,6 +178,19 @@ fn parse_r_id(args: Punctuated<Expr, Comma>) -> Result<RId> {
     Ok(RId { id })
 }
 
+fn parse_xstate_vector(
+    prefix: &'static str,
+    byte_width: usize,
+    args: Punctuated<Expr, Comma>,
+) -> Result<XstateVector> {
+    let RId { id } = parse_r_id(args)?;
+    Ok(XstateVector {
+        prefix,
+        id,
+        byte_width,
+    })
+}
+
 // gpr_64(<name>, <dwarf id>?)
 // gpr_(8l|8h|16|32)(<name>, <base name>)
 // fpr(<name>, <name in fpregs struct>, <dwarf id>?)
@@ -176,6 +198,7 @@ fn parse_r_id(args: Punctuated<Expr, Comma>) -> Result<RId> {
 // fp_mm(<id>)
 // fp_xmm(<id>)
 // dr(<id>)
+// xstate_(xmm|ymm|zmm)(<id>)
 impl Parse for RegDef {
     fn parse(input: ParseStream<'_>) -> Result<Self> {
         let expr_call = ExprCall::parse(input)?;
@@ -208,6 +231,9 @@ impl Parse for RegDef {
             "fp_mm" => parse_r_id(args).map(RegDef::FpMM),
             "fp_xmm" => parse_r_id(args).map(RegDef::FpXMM),
             "dr" => parse_r_id(args).map(RegDef::Dr),
+            "xstate_xmm" => parse_xstate_vector("xmm", 16, args).map(RegDef::Xstate),
+            "xstate_ymm" => parse_xstate_vector("ymm", 32, args).map(RegDef::Xstate),
+            "xstate_zmm" => parse_xstate_vector("zmm", 64, args).map(RegDef::Xstate),
             _ => Err(Error::new(
                 directive.span(),
                 format!("unknown register directive: {}", directive),
@@ -234,6 +260,7 @@ impl RegDef {
             RegDef::FpMM(rid) => format!("mm{}", rid.id),
             RegDef::FpXMM(rid) => format!("xmm{}", rid.id),
             RegDef::Dr(rid) => format!("dr{}", rid.id),
+            RegDef::Xstate(XstateVector { prefix, id, .. }) => format!("{}{}", prefix, id),
         }
     }
 
@@ -263,6 +290,11 @@ impl RegDef {
             RegDef::FpMM(RId { id }) => parse_quote!(Some(41usize + #id)),
             RegDef::FpXMM(RId { id }) => parse_quote!(Some(17usize + #id)),
             RegDef::Dr(_) => parse_quote!(None),
+            // xmm16-31 are 67-82
+            RegDef::Xstate(XstateVector {
+                prefix: "xmm", id, ..
+            }) => parse_quote!(Some(51usize + #id)),
+            RegDef::Xstate(_) => parse_quote!(None),
         }
     }
 
@@ -275,6 +307,7 @@ impl RegDef {
                 parse_quote!(#id * 16usize)
             }
             RegDef::Dr(RId { id }) => parse_quote!(#id * 8usize),
+            RegDef::Xstate(_) => unreachable!("xstate registers are not in the user struct"),
         }
     }
 
@@ -285,7 +318,7 @@ impl RegDef {
                 Some(parse_quote!(libc::user_fpregs_struct))
             }
 
-            RegDef::Dr(_) => None,
+            RegDef::Dr(_) | RegDef::Xstate(_) => None,
         }
     }
 
@@ -296,7 +329,7 @@ impl RegDef {
             RegDef::Fpr(fpr) => Some(format_ident!("{}", fpr.name_in_fpregs_struct)),
             RegDef::FpSt(_) | RegDef::FpMM(_) => Some(format_ident!("st_space")),
             RegDef::FpXMM(_) => Some(format_ident!("xmm_space")),
-            RegDef::Dr(_) => None,
+            RegDef::Dr(_) | RegDef::Xstate(_) => None,
         }
     }
 
@@ -309,10 +342,16 @@ impl RegDef {
             RegDef::FpMM(_) => format_ident!("i387"),
             RegDef::FpXMM(_) => format_ident!("i387"),
             RegDef::Dr(_) => format_ident!("u_debugreg"),
+            RegDef::Xstate(_) => unreachable!("xstate registers are not in the user struct"),
         }
     }
 
     fn offset_in_user_struct_expr(&self) -> Expr {
+        if let RegDef::Xstate(_) = self {
+            let name = self.name();
+            return parse_quote!(unreachable!("register {} is not in the user struct", #name));
+        }
+
         let first_level_field = self.field_in_user_struct_ident();
         let first_level_offset_expr: Expr =
             parse_quote!(core::mem::offset_of!(libc::user, #first_level_field));
@@ -336,6 +375,7 @@ impl RegDef {
         SubGeneralPurpose,
         FloatingPoint,
         Debug,
+        ExtendedState,
     }
     */
     fn kind_expr(&self) -> Expr {
@@ -346,6 +386,7 @@ impl RegDef {
                 parse_quote!(RegisterKind::FloatingPoint)
             }
             RegDef::Dr(_) => parse_quote!(RegisterKind::Debug),
+            RegDef::Xstate(_) => parse_quote!(RegisterKind::ExtendedState),
         }
     }
 
@@ -361,7 +402,7 @@ impl RegDef {
             RegDef::Gpr64(_) | RegDef::GprSub(_) => parse_quote!(RegisterRepr::UInt),
             RegDef::Fpr(_) => parse_quote!(RegisterRepr::UInt),
             RegDef::FpSt(_) => parse_quote!(RegisterRepr::LongDouble),
-            RegDef::FpMM(_) | RegDef::FpXMM(_) => {
+            RegDef::FpMM(_) | RegDef::FpXMM(_) | RegDef::Xstate(_) => {
                 parse_quote!(RegisterRepr::Vector)
             }
             RegDef::Dr(_) => parse_quote!(RegisterRepr::UInt),
@@ -383,6 +424,16 @@ impl RegDef {
             RegDef::FpMM(_) => parse_quote!(8usize),
             RegDef::FpXMM(_) => parse_quote!(16usize),
             RegDef::Dr(_) => parse_quote!(8usize),
+            RegDef::Xstate(XstateVector { byte_width, .. }) => parse_quote!(#byte_width),
+        }
+    }
+
+    fn vector_index_expr(&self) -> Expr {
+        match self {
+            RegDef::FpXMM(RId { id }) | RegDef::Xstate(XstateVector { id, .. }) => {
+                parse_quote!(Some(#id))
+            }
+            _ => parse_quote!(None),
         }
     }
 }
@@ -534,6 +585,22 @@ impl RegDefs {
         )
     }
 
+    fn vector_index_fn_item(&self) -> ItemFn {
+        let arms = self.defs.iter().map(|def| -> Arm {
+            let variant = def.enum_variant_ident();
+            let expr = def.vector_index_expr();
+            parse_quote!(Self::#variant => #expr)
+        });
+
+        parse_quote!(
+            pub fn vector_index(&self) -> Option<usize> {
+                match self {
+                    #(#arms),*
+                }
+            }
+        )
+    }
+
     fn all_debug_registers_item_fn(&self) -> ItemFn {
         let exprs = self
             .defs
@@ -563,6 +630,7 @@ impl RegDefs {
         let repr_fn_item = self.repr_fn_item();
         let byte_width_fn_item = self.byte_width_fn_item();
         let all_debug_registers_item_fn = self.all_debug_registers_item_fn();
+        let vector_index_fn_item = self.vector_index_fn_item();
 
         parse_quote!(
             impl Register{
@@ -574,6 +642,7 @@ impl RegDefs {
                 #repr_fn_item
                 #byte_width_fn_item
                 #all_debug_registers_item_fn
+                #vector_index_fn_item
             }
         )
     }
@@ -606,6 +675,8 @@ fn playground() {
       fp_mm(0);
       fp_xmm(0);
       dr(0);
+      xstate_xmm(16);
+      xstate_zmm(0);
     };
 
     let output = impl_define_amd64_registers(input).unwrap();
@@ -0,0 +1,18 @@
+{ inputs, ... }: {
+  perSystem = { system, config, ... }:
+    let
+      rustFlake = inputs.flake-lang.lib.${system}.rustFlake {
+        src = ./.;
+        crateName = "stupid-dbg-cli";
+        devShellHook = config.settings.defaultShellHook;
+        extraSources = [
+          config.packages.helper-proc-macros-rust-src
+          config.packages.stupid-dbg-core-rust-src
+        ];
+        rustChannel = "nightly";
+      };
+    in
+    {
+      inherit (rustFlake) packages checks devShells;
+    };
+}
@@ -0,0 +1,160 @@
+use std::{path::PathBuf, process::exit, time::Duration};
+
+use anyhow::anyhow;
+use clap::Parser;
+use libc::pid_t;
+use tracing_subscriber::{
+    filter::{filter_fn, LevelFilter},
+    fmt::format::FmtSpan,
+    layer::SubscriberExt as _,
+    reload, EnvFilter, Layer as _,
+};
+
+use stupid_dbg_cli::{
+    debugger::{self, Debugger},
+    diagnostics::DiagnosticFilter,
+    interrupt,
+};
+
+#[derive(Debug, clap::Parser)]
+#[command(group(clap::ArgGroup::new("unattended").args(["batch", "verdict"])))]
+struct Cli {
+    #[arg(long, short = 'p')]
+    pid: Option<pid_t>,
+
+    #[arg(long, short = 'v')]
+    verbose: bool,
+
+    #[arg(long)]
+    history_file: Option<PathBuf>,
+
+    /// run the debuggee to completion without a prompt and exit with its status
+    #[arg(long)]
+    batch: bool,
+
+    /// kill the debuggee after this many seconds in batch or verdict mode
+    #[arg(long, value_name = "SECONDS", requires = "unattended")]
+    timeout: Option<u64>,
+
+    /// like batch mode, but write a JSON report of how the debuggee ended to PATH and exit with
+    /// 0 if it exited, 2 if it crashed or 3 if it timed out
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["batch", "replay"])]
+    verdict: Option<PathBuf>,
+
+    /// set a breakpoint before anything runs, may be repeated
+    #[arg(long = "break", value_name = "LOCATION")]
+    breakpoints: Vec<String>,
+
+    /// execute a command before anything runs, may be repeated
+    #[arg(long = "ex", value_name = "COMMAND")]
+    commands: Vec<String>,
+
+    /// replay a transcript without a prompt and fail if its output doesn't match
+    #[arg(long, value_name = "PATH", conflicts_with = "batch")]
+    replay: Option<PathBuf>,
+
+    #[cfg(feature = "dynamic-plugins")]
+    #[arg(long)]
+    plugins_dir: Option<PathBuf>,
+
+    #[command(flatten)]
+    launch: debugger::LaunchArgs,
+
+    child_args: Vec<String>,
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    let mut debugger = Debugger::new();
+    interrupt::install()?;
+
+    let default_level = if cli.verbose {
+        LevelFilter::DEBUG
+    } else {
+        LevelFilter::INFO
+    };
+    // swapped out by `set debug`
+    let (level_filter, level_filter_handle) = reload::Layer::new(
+        EnvFilter::builder()
+            .with_default_directive(default_level.into())
+            .parse_lossy(""),
+    );
+    debugger.set_diagnostic_filter(DiagnosticFilter::new(default_level, move |filter| {
+        level_filter_handle
+            .reload(filter)
+            .map_err(|err| anyhow!("unable to change diagnostics: {}", err))
+    }));
+
+    let output_log = debugger.output_log().clone();
+    let terminal = tracing_subscriber::fmt::layer()
+        .with_span_events(FmtSpan::CLOSE | FmtSpan::ENTER)
+        .with_filter(level_filter)
+        .with_filter(filter_fn(move |metadata| {
+            !output_log.is_redirected(metadata)
+        }));
+    let collector = tracing_subscriber::registry()
+        .with(terminal)
+        .with(debugger.output_log().layer());
+    tracing::subscriber::set_global_default(collector)
+        .map_err(|err| anyhow!("unable to setup logging subscriber: {}", err))?;
+
+    #[cfg(feature = "dynamic-plugins")]
+    if let Some(plugins_dir) = &cli.plugins_dir {
+        debugger.load_plugins(plugins_dir)?;
+    }
+
+    if let debugger::CommandExecutionResult::Quit(result) = match (cli.pid, cli.child_args.len()) {
+        (Some(pid), 0) => debugger.handle_command(debugger::Command::Attach { pid, steal: false }),
+        (None, len) => {
+            if len > 0 {
+                debugger.handle_command(debugger::Command::Run {
+                    launch: cli.launch,
+                    args: cli.child_args,
+                })
+            } else {
+                debugger::CommandExecutionResult::Continue(Ok(()))
+            }
+        }
+        (Some(_), _) => Err(anyhow!("ambiguous debuggee config"))?,
+    } {
+        return result;
+    }
+
+    let lines = cli
+        .breakpoints
+        .iter()
+        .map(|location| format!("break {}", location))
+        .chain(cli.commands);
+    for line in lines {
+        match debugger.repl_line(&line) {
+            debugger::CommandExecutionResult::Continue(result) => {
+                result.map_err(|err| anyhow!("`{}` failed: {}", line, err))?
+            }
+            debugger::CommandExecutionResult::Quit(result) => return result,
+        }
+    }
+
+    if let Some(path) = cli.replay {
+        return match debugger.handle_command(debugger::Command::Replay { path }) {
+            debugger::CommandExecutionResult::Continue(result)
+            | debugger::CommandExecutionResult::Quit(result) => result,
+        };
+    }
+
+    if let Some(path) = cli.verdict {
+        let report = debugger.verdict(cli.timeout.map(Duration::from_secs))?;
+        report.save(path)?;
+        exit(report.verdict.exit_code());
+    }
+
+    if cli.batch {
+        let outcome = debugger.run_to_completion(cli.timeout.map(Duration::from_secs))?;
+        // the debuggee is gone, nothing is left to clean up
+        exit(outcome.exit_status());
+    }
+
+    debugger.repl(cli.history_file)?;
+
+    return Ok(());
+}
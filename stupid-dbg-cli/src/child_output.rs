@@ -0,0 +1,118 @@
+use std::{
+    fmt,
+    fs::{File, OpenOptions},
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard},
+    thread::{self, JoinHandle},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+impl fmt::Display for OutputStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OutputStream::Stdout => write!(f, "stdout"),
+            OutputStream::Stderr => write!(f, "stderr"),
+        }
+    }
+}
+
+// Keeps everything the debuggee prints in a file, line by line with the time and the stream it
+// came from, apart from the output of the debugger. Clones share the same file.
+#[derive(Debug, Clone, Default)]
+pub struct ChildOutputLog {
+    file: Arc<Mutex<Option<(PathBuf, File)>>>,
+}
+
+impl ChildOutputLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn file(&self) -> MutexGuard<'_, Option<(PathBuf, File)>> {
+        self.file.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    // Appends to `path`, the output of debuggees already running included.
+    pub fn open<P: AsRef<Path>>(&self, path: P) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        *self.file() = Some((path.to_path_buf(), file));
+        Ok(())
+    }
+
+    pub fn close(&self) {
+        *self.file() = None;
+    }
+
+    pub fn path(&self) -> Option<PathBuf> {
+        self.file().as_ref().map(|(path, _)| path.clone())
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.file().is_some()
+    }
+
+    fn log_line(&self, stream: OutputStream, line: &[u8]) {
+        let Some((_, file)) = &mut *self.file() else {
+            return;
+        };
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64();
+        // a failing log file must not take the session down with it
+        _ = writeln!(
+            file,
+            "{:.3} {}: {}",
+            time,
+            stream,
+            String::from_utf8_lossy(line)
+        );
+    }
+
+    // Copies `output` to `terminal` as it comes and logs every line of it, until the debuggee
+    // closes its end.
+    pub fn tee<R, W>(&self, stream: OutputStream, mut output: R, mut terminal: W) -> JoinHandle<()>
+    where
+        R: Read + Send + 'static,
+        W: Write + Send + 'static,
+    {
+        let log = self.clone();
+        thread::spawn(move || {
+            let mut buffer = [0; 4096];
+            let mut line = Vec::new();
+            loop {
+                let len = match output.read(&mut buffer) {
+                    Ok(0) => break,
+                    Ok(len) => len,
+                    Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(_) => break,
+                };
+                _ = terminal.write_all(&buffer[..len]);
+                _ = terminal.flush();
+
+                for chunk in buffer[..len].split_inclusive(|byte| *byte == b'\n') {
+                    match chunk.strip_suffix(b"\n") {
+                        Some(rest) => {
+                            line.extend_from_slice(rest);
+                            log.log_line(stream, &line);
+                            line.clear();
+                        }
+                        None => line.extend_from_slice(chunk),
+                    }
+                }
+            }
+            // the last line may lack its newline
+            if !line.is_empty() {
+                log.log_line(stream, &line);
+            }
+        })
+    }
+}
@@ -0,0 +1,3876 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    env, io,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, UNIX_EPOCH},
+};
+
+use anyhow::anyhow;
+use clap::{CommandFactory as _, Parser as _};
+use libc::pid_t;
+use nix::{sys::signal::Signal, unistd::Pid};
+use nonempty::NonEmpty;
+use rustyline::error::ReadlineError;
+use tracing::{error, info, warn, Dispatch};
+use tracing_subscriber::{filter::LevelFilter, layer::SubscriberExt as _};
+
+use stupid_dbg_core::{
+    anti_debug::AntiDebugConfig,
+    checksec,
+    crash::CrashReport,
+    debug_register::WatchKind,
+    debuggee::{self, Debuggee, FollowFork, ModuleEvent, ProcessState, ThreadEvent, WaitOutcome},
+    elf::SymbolKind,
+    expression::{
+        self, ConvenienceVariables, EvalContext, Expr, NoDebuggee, Value, WithConvenienceVariables,
+    },
+    format::{self, Format, Letter},
+    fp_control,
+    instruction_trace::{InstructionTrace, DEFAULT_INSTRUCTION_TRACE_CAPACITY},
+    launch::{LaunchSpec, Stdio},
+    line_table::{self, SourceLocation},
+    memory_map::{MemoryMap, RegionKind},
+    pretty_printer::{StdLib, StdType},
+    provenance::Provenance,
+    register::{LaneFormat, Register, RegisterGroup, RegisterValue, Registers},
+    session_state::SessionState,
+    source_path::SourcePath,
+    stop_reason::{SignalInfo, StopReason},
+    symbol_cache::ModuleOffset,
+    symbol_index::SymbolIndex,
+    tracepoint::{Backpressure, TraceAction, TraceFile, DEFAULT_TRACE_FILE_TIMEOUT},
+    tracer::PtraceTracer,
+    verdict::{ThreadReport, Verdict, VerdictReport},
+    virt_addr::VirtAddr,
+};
+
+use crate::{
+    aux::{box_err, RlWithOpitonalHistoryFile},
+    child_output::{ChildOutputLog, OutputStream},
+    diagnostics::DiagnosticFilter,
+    interrupt,
+    output_log::OutputLog,
+    plugin::{Plugin, PluginRegistry, PrettyPrinter, StdPlugin, StdPrettyPrinter},
+    replay::{self, Mismatch, OutputCapture, Transcript},
+};
+
+#[derive(Debug, clap::Parser)]
+#[command(multicall = true)]
+struct CommandWrapper {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub enum Command {
+    /// Attach to a running process
+    Attach {
+        /// terminate the tracer the process has already, e.g. strace, and attach in its place
+        #[arg(long)]
+        steal: bool,
+        pid: pid_t,
+    },
+    /// Start the program under the debugger
+    Run {
+        #[command(flatten)]
+        launch: LaunchArgs,
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
+    /// Detach from the debuggee and let it run on
+    Detach,
+    /// Resume the debuggee and wait until it stops
+    Continue {
+        /// give up waiting after this many seconds, leaving the debuggee running
+        #[arg(long, value_name = "SECONDS")]
+        timeout: Option<u64>,
+    },
+    /// Execute a single instruction of the current thread
+    Stepi,
+    /// Run the current thread to the next source line, into calls
+    Step,
+    /// Run the current thread to the next source line, over calls
+    Next,
+    /// Run the current thread until the function it's in returns, and show what it returned
+    Finish,
+    /// Run the current thread to a location in the function it's in, or until that returns. A
+    /// bare number is a line of the file the pc is in.
+    Until {
+        #[command(flatten)]
+        location: AddressArg,
+    },
+    /// Run like `until`, stopping at the location in calls the function makes as well
+    Advance {
+        #[command(flatten)]
+        location: AddressArg,
+    },
+    /// Set a breakpoint
+    Break {
+        /// allow addresses outside of executable memory
+        #[arg(long)]
+        force: bool,
+        /// use a debug register if one is free
+        #[arg(long)]
+        fast: bool,
+        /// tag the breakpoint, can be given more than once
+        #[arg(long = "group", value_name = "GROUP")]
+        groups: Vec<String>,
+        /// only stop when it's true, e.g. `--condition '$rax == 5'`
+        #[arg(long, value_name = "EXPRESSION")]
+        condition: Option<String>,
+        #[command(flatten)]
+        address: AddressArg,
+    },
+    /// Manage breakpoints one by one or by group
+    Breakpoint {
+        #[command(subcommand)]
+        command: BreakpointCommand,
+    },
+    /// Set a breakpoint in a debug register
+    Hbreak {
+        #[command(flatten)]
+        address: AddressArg,
+    },
+    /// Delete breakpoints, the same as `breakpoint delete`
+    Delete {
+        #[command(flatten)]
+        selection: BreakpointSelection,
+    },
+    /// Stop when the memory at an address is written, using a debug register
+    Watch {
+        #[arg(long, default_value_t = 8)]
+        size: usize,
+        /// stop on reads as well as writes
+        #[arg(long)]
+        access: bool,
+        #[command(flatten)]
+        address: AddressArg,
+    },
+    /// Remove a watchpoint
+    Unwatch { id: usize },
+    /// Write protect the pages of a range, for buffers too large for debug registers; reads
+    /// into them stop too, other syscalls writing there fail with EFAULT
+    Mwatch { address: String, len: usize },
+    /// Move the pc of the current thread to another location and continue from there
+    Jump {
+        /// allow jumping out of the code the pc is in
+        #[arg(long)]
+        force: bool,
+        /// stay stopped at the target instead of continuing
+        #[arg(long)]
+        stop: bool,
+        /// an address, a function name or `file:line`
+        #[command(flatten)]
+        target: AddressArg,
+    },
+    /// Read or write the registers of the current thread
+    Register {
+        #[command(subcommand)]
+        command: RegisterCommand,
+    },
+    /// Evaluate an expression and print its value
+    Print {
+        /// print/c and print/s end up here
+        #[arg(long)]
+        format: Option<Format>,
+        /// show the object the expression is the address of with the pretty-printer of a type,
+        /// e.g. `print --type std::string $rdi`
+        #[arg(long = "type", value_name = "TYPE", conflicts_with = "format")]
+        ty: Option<String>,
+        #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+        expression: Vec<String>,
+    },
+    /// Examine memory, e.g. x/32bx $rsp, x/s 0x404000 or x/16i $rip
+    X {
+        #[arg(long)]
+        format: Option<Format>,
+        /// show the memory as values of a builtin C type, e.g. `x/4 --type 'unsigned short' $rsp`
+        #[arg(long = "type", value_name = "TYPE")]
+        ty: Option<String>,
+        #[command(flatten)]
+        address: AddressArg,
+    },
+    /// Read, search, dump or restore memory of the debuggee
+    Memory {
+        #[command(subcommand)]
+        command: MemoryCommand,
+    },
+    /// Show a C++ standard library object without debug info
+    PrettyPrint {
+        /// size of a vector element or a map key-value pair
+        #[arg(long, default_value_t = 8)]
+        element_size: usize,
+        /// detected from the mapped libraries by default
+        #[arg(long, value_enum)]
+        abi: Option<StdAbi>,
+        #[arg(value_enum)]
+        kind: StdKind,
+        #[command(flatten)]
+        address: AddressArg,
+    },
+    /// Write a core file of the debuggee, core.<pid> by default
+    Gcore { path: Option<PathBuf> },
+    /// Save the breakpoints and settings of the session to a file, or load them
+    Session {
+        #[command(subcommand)]
+        command: SessionCommand,
+    },
+    /// Add directories to search for source files
+    Directory {
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+    },
+    /// Show information about the debuggee
+    Info {
+        #[command(subcommand)]
+        command: InfoCommand,
+    },
+    /// Look up symbols of the loaded modules
+    Symbol {
+        #[command(subcommand)]
+        command: SymbolCommand,
+    },
+    /// Show settings of the debugger
+    Show {
+        #[command(subcommand)]
+        command: ShowCommand,
+    },
+    /// Change settings of the debugger, or a variable of the debuggee
+    Set {
+        #[command(subcommand)]
+        command: SetCommand,
+    },
+    /// Undo a setting of the debugger
+    Unset {
+        #[command(subcommand)]
+        command: UnsetCommand,
+    },
+    /// Switch to a child forked while following both, the current debuggee is kept as it is
+    Inferior { pid: pid_t },
+    /// Fork the stopped debuggee, to go back to where it is now with `restart`
+    Checkpoint {
+        #[command(subcommand)]
+        command: Option<CheckpointCommand>,
+    },
+    /// Show PIE, RELRO, stack canaries, NX and fortify of every loaded module
+    Checksec,
+    /// Go back to a checkpoint, debugging a new fork of it so it can be restarted again
+    Restart { id: usize },
+    /// Set a breakpoint that collects data into the trace buffer and lets the debuggee go on
+    #[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+    Trace {
+        #[command(subcommand)]
+        command: Option<TraceCommand>,
+        /// general purpose registers
+        #[arg(long)]
+        registers: bool,
+        #[arg(long, value_name = "EXPRESSION:LEN", value_parser = parse_trace_memory)]
+        memory: Vec<TraceAction>,
+        #[arg(long, value_name = "EXPRESSION")]
+        collect: Vec<String>,
+        #[command(flatten)]
+        address: AddressArg,
+    },
+    /// Show the tracepoints and how full the trace buffer is
+    Tstatus,
+    /// Select a trace frame, the one after the selected one by default
+    Tfind { frame: Option<usize> },
+    /// Show what the selected trace frame collected
+    Tdump,
+    /// Record the allocator calls of the debuggee
+    Heap {
+        #[command(subcommand)]
+        command: HeapCommand,
+    },
+    /// Record which functions of the debuggee run
+    Coverage {
+        #[command(subcommand)]
+        command: CoverageCommand,
+    },
+    /// Run the commands of a transcript and check their output against it
+    Replay { path: PathBuf },
+    /// Maintain the internal state of the debugger
+    Maintenance {
+        #[command(subcommand)]
+        command: MaintenanceCommand,
+    },
+    /// Quit the debugger
+    Quit,
+}
+
+// Launch options shared by the `run` command and the command line of the debugger.
+#[derive(Debug, Clone, Default, clap::Args)]
+pub struct LaunchArgs {
+    #[arg(long = "env", value_name = "KEY=VALUE", value_parser = parse_env_var)]
+    pub env: Vec<(String, String)>,
+    #[arg(long = "unset-env", value_name = "KEY")]
+    pub unset_env: Vec<String>,
+    #[arg(long)]
+    pub cwd: Option<PathBuf>,
+    #[arg(long, value_name = "PATH")]
+    pub stdin: Option<PathBuf>,
+    #[arg(long, value_name = "PATH")]
+    pub stdout: Option<PathBuf>,
+    #[arg(long, value_name = "PATH")]
+    pub stderr: Option<PathBuf>,
+    #[arg(long)]
+    pub disable_aslr: bool,
+    /// stop at the entry point of the executable instead of in the dynamic linker
+    #[arg(long)]
+    pub stop_at_entry: bool,
+}
+
+impl LaunchArgs {
+    // `environment` goes first, the variables given for this launch take precedence.
+    pub fn into_launch_spec(
+        self,
+        args: NonEmpty<String>,
+        environment: &BTreeMap<String, Option<String>>,
+    ) -> LaunchSpec {
+        let mut launch_spec = LaunchSpec::new(args)
+            .disable_aslr(self.disable_aslr)
+            .stop_at_entry(self.stop_at_entry);
+        for (key, value) in environment {
+            launch_spec = match value {
+                Some(value) => launch_spec.env(key, value),
+                None => launch_spec.env_remove(key),
+            };
+        }
+        for (key, value) in self.env {
+            launch_spec = launch_spec.env(key, value);
+        }
+        for key in self.unset_env {
+            launch_spec = launch_spec.env_remove(key);
+        }
+        if let Some(cwd) = self.cwd {
+            launch_spec = launch_spec.cwd(cwd);
+        }
+        if let Some(path) = self.stdin {
+            launch_spec = launch_spec.stdin(Stdio::File(path));
+        }
+        if let Some(path) = self.stdout {
+            launch_spec = launch_spec.stdout(Stdio::File(path));
+        }
+        if let Some(path) = self.stderr {
+            launch_spec = launch_spec.stderr(Stdio::File(path));
+        }
+        launch_spec
+    }
+}
+
+fn parse_trace_memory(s: &str) -> Result<TraceAction, String> {
+    s.rsplit_once(':')
+        .filter(|(address, _)| !address.is_empty())
+        .and_then(|(address, len)| {
+            Some(TraceAction::Memory {
+                address: address.to_string(),
+                len: len.parse().ok()?,
+            })
+        })
+        .ok_or_else(|| format!("invalid memory range {}, expected EXPRESSION:LEN", s))
+}
+
+// Pairs of hex digits, whitespace in between is ignored.
+fn parse_hex_bytes(s: &str) -> anyhow::Result<Vec<u8>> {
+    let digits = s.split_whitespace().collect::<String>();
+    if digits.len() % 2 != 0 {
+        Err(anyhow!("odd number of hex digits in {}", s))?;
+    }
+    (0..digits.len())
+        .step_by(2)
+        .map(|index| {
+            u8::from_str_radix(&digits[index..index + 2], 16)
+                .map_err(|_| anyhow!("invalid hex bytes {}", s))
+        })
+        .collect()
+}
+
+fn parse_env_var(s: &str) -> Result<(String, String), String> {
+    s.split_once('=')
+        .filter(|(key, _)| !key.is_empty())
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .ok_or_else(|| format!("invalid environment variable {}, expected KEY=VALUE", s))
+}
+
+// An expression for an address, `0x401000`, `main+0x20`, `$rip+8` or `*$rsp`. It takes the
+// rest of the command line so it doesn't have to be quoted.
+#[derive(Debug, Clone, clap::Args)]
+pub struct AddressArg {
+    #[arg(
+        value_name = "ADDRESS",
+        required = true,
+        trailing_var_arg = true,
+        allow_hyphen_values = true
+    )]
+    expression: Vec<String>,
+}
+
+impl AddressArg {
+    pub fn as_expression(&self) -> String {
+        self.expression.join(" ")
+    }
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub enum RegisterCommand {
+    Read {
+        name: Option<String>,
+        /// which registers to show without a name
+        #[arg(long, value_enum, default_value_t = RegisterSet::Gpr, conflicts_with = "name")]
+        group: RegisterSet,
+        /// lanes to split a vector register into
+        #[arg(long, value_enum, requires = "name")]
+        format: Option<VectorFormat>,
+    },
+    /// Registers that changed since the previous stop, old -> new
+    Changed,
+    /// Change named fields of fcw, fsw, ftw or mxcsr, e.g. `register write mxcsr rc=zero mask=none`
+    Write {
+        name: String,
+        #[arg(required = true, value_name = "FIELD=VALUE")]
+        fields: Vec<String>,
+    },
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub enum MemoryCommand {
+    /// Hexdump of `len` bytes at an address
+    Read { address: String, len: usize },
+    /// Every address between two others a string or byte pattern is at
+    Find {
+        start: String,
+        end: String,
+        /// the pattern is hex bytes, e.g. `7f454c46` or `"7f 45 4c 46"`
+        #[arg(long)]
+        hex: bool,
+        pattern: String,
+    },
+    /// Write `len` bytes at an address to a file
+    Dump {
+        address: String,
+        len: u64,
+        path: PathBuf,
+    },
+    /// Write the contents of a file to an address
+    Restore {
+        path: PathBuf,
+        address: String,
+        /// write read-only memory like code too
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub enum SessionCommand {
+    Save { path: PathBuf },
+    Load { path: PathBuf },
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub enum InfoCommand {
+    Auxv,
+    Environment {
+        /// only show this variable
+        name: Option<String>,
+    },
+    Proc,
+    Fds,
+    DebugRegisters,
+    #[command(alias = "maps")]
+    Mappings,
+    StopLog {
+        /// only show the most recent ones
+        #[arg(long)]
+        last: Option<usize>,
+    },
+    AntiDebug,
+    /// Mapped files, with where the debugger reads them for a process in a container
+    #[command(name = "sharedlibrary")]
+    SharedLibrary,
+    /// The symbol an address is in, as `function+offset (module)`
+    Symbol {
+        #[command(flatten)]
+        address: AddressArg,
+    },
+    /// The report of the latest fatal signal
+    Crash,
+    /// Checkpoints to `restart` from, with where the debuggee was
+    Checkpoints,
+    /// The current debuggee and the forked children kept while following both
+    Inferiors,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum RegisterSet {
+    Gpr,
+    Fpr,
+    Vector,
+    Debug,
+    All,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum VectorFormat {
+    F32,
+    F64,
+    U8,
+    U16,
+    U32,
+    U64,
+    Hex,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum FollowForkMode {
+    Parent,
+    Both,
+}
+
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum StdKind {
+    String,
+    Vector,
+    Map,
+    UniquePtr,
+    SharedPtr,
+}
+
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum StdAbi {
+    Libstdcxx,
+    Libcxx,
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub enum SymbolCommand {
+    /// Fuzzy search over function and variable names
+    Find {
+        query: String,
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+    },
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub enum MaintenanceCommand {
+    /// Remove the symbols indexed on disk, every file gets read again
+    FlushSymbolCache,
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub enum TraceCommand {
+    /// The frames in the trace buffer, a line each, oldest first
+    Show {
+        /// only the latest ones
+        #[arg(long)]
+        last: Option<usize>,
+        /// only the ones of this tracepoint
+        #[arg(long)]
+        tracepoint: Option<usize>,
+    },
+    /// How many frames the trace buffer keeps, 0 to keep none, e.g. when streaming to a file
+    Buffer { capacity: usize },
+    /// Stream trace frames to a file too, turned off without a path
+    File {
+        path: Option<PathBuf>,
+        /// drop frames while the file is behind instead of waiting for it
+        #[arg(long)]
+        drop: bool,
+        /// how long a frame waits for the file at most before it's dropped
+        #[arg(
+            long,
+            value_name = "MS",
+            default_value_t = DEFAULT_TRACE_FILE_TIMEOUT.as_millis() as u64,
+            conflicts_with = "drop"
+        )]
+        timeout: u64,
+    },
+    /// Single-step the debuggee on `continue`, recording every instruction it executes
+    Start {
+        /// recorded with every instruction besides the pc, e.g. rax,rsp
+        #[arg(long, value_delimiter = ',')]
+        registers: Vec<String>,
+        /// how many instructions are kept, the oldest ones are dropped first, 0 to keep none,
+        /// e.g. when streaming to the trace file
+        #[arg(long, default_value_t = DEFAULT_INSTRUCTION_TRACE_CAPACITY)]
+        capacity: usize,
+    },
+    /// Run at full speed again, the recording is kept until the next `trace start`
+    Stop,
+    /// The recorded instructions, a line each, oldest first, or appended to a file
+    Dump {
+        path: Option<PathBuf>,
+        /// only the latest ones
+        #[arg(long, conflicts_with = "path")]
+        last: Option<usize>,
+    },
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub enum HeapCommand {
+    /// Hook malloc, calloc, realloc, free, mmap and munmap of the loaded libraries
+    Trace {
+        #[arg(action = clap::ArgAction::Set, value_parser = clap::builder::BoolishValueParser::new())]
+        enabled: bool,
+    },
+    /// Totals and the call sites holding the most live bytes
+    Stats {
+        #[arg(long, default_value_t = 10)]
+        top: usize,
+    },
+    /// The allocation a pointer points into and where it was allocated from
+    Find {
+        #[command(flatten)]
+        address: AddressArg,
+    },
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub enum CoverageCommand {
+    /// Put a one-shot probe on the entry of every function of the loaded modules
+    Start {
+        /// only functions whose name contains this
+        pattern: Option<String>,
+        /// only functions of modules whose path contains this
+        #[arg(long)]
+        module: Option<String>,
+    },
+    Stop,
+    /// The functions that ran, in the order they first did
+    Report {
+        /// list the functions that didn't run instead
+        #[arg(long)]
+        uncovered: bool,
+        /// also write an lcov tracefile
+        #[arg(long, value_name = "PATH")]
+        lcov: Option<PathBuf>,
+    },
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub enum ShowCommand {
+    /// The environment `run` starts the debuggee with
+    Environment {
+        /// only show this variable
+        name: Option<String>,
+    },
+    Convenience,
+    Directories,
+    Logging,
+    Debug,
+    ChildOutputLog,
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub enum SetCommand {
+    /// <lvalue> = <expression>
+    Variable {
+        #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+        assignment: Vec<String>,
+    },
+    /// Watch the debuggee's syscalls and signals for checks whether it is being debugged
+    AntiDebug {
+        #[arg(action = clap::ArgAction::Set, value_parser = clap::builder::BoolishValueParser::new())]
+        enabled: bool,
+        /// fake success of ptrace(PTRACE_TRACEME)
+        #[arg(long)]
+        spoof_traceme: bool,
+    },
+    /// Diagnostics of a target down to a level, e.g. `set debug ptrace debug`
+    Debug {
+        /// `all`, `ptrace`, `core`, `cli`, `plugin` or a module path
+        target: String,
+        level: LevelFilter,
+    },
+    Breakpoint {
+        #[command(subcommand)]
+        command: BreakpointSetting,
+    },
+    /// Stop at the first instruction of a signal handler a step runs into, instead of stepping
+    /// the interrupted instruction once the handler returned
+    StepIntoHandlers {
+        #[arg(action = clap::ArgAction::Set, value_parser = clap::builder::BoolishValueParser::new())]
+        enabled: bool,
+    },
+    /// Keep forked children stopped with the breakpoints in, to switch to them with `inferior`
+    /// (`both`), or detach them (`parent`)
+    FollowFork { mode: FollowForkMode },
+    /// Copy command output to a file
+    Logging {
+        #[command(subcommand)]
+        command: LoggingCommand,
+    },
+    /// KEY=VALUE in the environment of debuggees started by `run` from now on
+    Environment {
+        #[arg(
+            required = true,
+            trailing_var_arg = true,
+            allow_hyphen_values = true,
+            value_name = "KEY=VALUE"
+        )]
+        assignment: Vec<String>,
+    },
+    /// Keep what debuggees started by `run` print in a file, turned off without a path. Their
+    /// stdout and stderr are captured unless redirected with `--stdout` or `--stderr`
+    ChildOutputLog { path: Option<PathBuf> },
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub enum UnsetCommand {
+    /// Leave a variable out of the environment of debuggees started by `run` from now on
+    Environment { name: String },
+}
+
+// Breakpoints to act on, by id or every one of a group.
+#[derive(Debug, Clone, clap::Args)]
+pub struct BreakpointSelection {
+    #[arg(required_unless_present = "group", conflicts_with = "group")]
+    ids: Vec<usize>,
+    #[arg(long)]
+    group: Option<String>,
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub enum CheckpointCommand {
+    /// Kill the fork kept for the checkpoint
+    Delete { id: usize },
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub enum BreakpointCommand {
+    Enable {
+        #[command(flatten)]
+        selection: BreakpointSelection,
+    },
+    Disable {
+        #[command(flatten)]
+        selection: BreakpointSelection,
+    },
+    Delete {
+        #[command(flatten)]
+        selection: BreakpointSelection,
+    },
+    Tag {
+        id: usize,
+        group: String,
+    },
+    Untag {
+        id: usize,
+        group: String,
+    },
+    /// Only stop when the expression is true, without one the breakpoint always stops again
+    Condition {
+        id: usize,
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        expression: Vec<String>,
+    },
+    List,
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub enum BreakpointSetting {
+    /// Put breakpoints worth it into free debug registers, e.g. hot ones or ones in read-only pages
+    AutoHw {
+        #[arg(action = clap::ArgAction::Set, value_parser = clap::builder::BoolishValueParser::new())]
+        enabled: bool,
+    },
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub enum LoggingCommand {
+    On,
+    Off,
+    File {
+        path: PathBuf,
+    },
+    /// Truncate the file instead of appending to it
+    Overwrite {
+        #[arg(action = clap::ArgAction::Set, value_parser = clap::builder::BoolishValueParser::new())]
+        enabled: bool,
+    },
+    /// Don't print to the terminal while logging
+    Redirect {
+        #[arg(action = clap::ArgAction::Set, value_parser = clap::builder::BoolishValueParser::new())]
+        enabled: bool,
+    },
+}
+
+pub enum CommandExecutionResult {
+    Continue(anyhow::Result<()>),
+    Quit(anyhow::Result<()>),
+}
+
+impl CommandExecutionResult {
+    pub fn should_quit(&self) -> bool {
+        if let Self::Quit(_) = self {
+            true
+        } else {
+            false
+        }
+    }
+}
+
+// How long a debuggee that ran out of time gets to stop before it's killed anyway.
+const INTERRUPT_TIMEOUT: Duration = Duration::from_secs(1);
+
+// How far `continue`, the step commands, `finish`, `until` and `advance` let the debuggee run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Motion {
+    Continue,
+    Instruction,
+    Line { over_calls: bool },
+    Return,
+    Location { target: VirtAddr, in_frame: bool },
+}
+
+// How a debuggee run by `Debugger::run_to_completion` ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BatchOutcome {
+    Exited(i32),
+    Terminated(Signal),
+    TimedOut,
+}
+
+impl BatchOutcome {
+    // What a shell would report for the debuggee itself, and timeout(1) for running out of time.
+    pub fn exit_status(&self) -> i32 {
+        match self {
+            BatchOutcome::Exited(status_code) => *status_code,
+            BatchOutcome::Terminated(signal) => 128 + *signal as i32,
+            BatchOutcome::TimedOut => 124,
+        }
+    }
+}
+
+pub struct Debugger {
+    debuggee: Option<Debuggee>,
+    plugins: PluginRegistry,
+    convenience_variables: ConvenienceVariables,
+    source_path: SourcePath,
+    output_log: OutputLog,
+    diagnostics: Option<DiagnosticFilter>,
+    // index of the trace frame `tdump` shows
+    trace_frame: Option<usize>,
+    // changes to the debugger's environment for debuggees started by `run`, None unsets
+    environment: BTreeMap<String, Option<String>>,
+    child_output_log: ChildOutputLog,
+    // taken at the latest fatal signal, for `info crash`
+    crash_report: Option<CrashReport>,
+    // file and line of the breakpoints set by them, echoed back when they're hit
+    breakpoint_sources: BTreeMap<usize, SourceLocation>,
+    // handed to every debuggee, None if there's nowhere to keep it
+    symbol_index: Option<SymbolIndex>,
+    follow_fork: FollowFork,
+    // forked children taken over while following both, besides the current debuggee
+    inferiors: Vec<Debuggee>,
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        let mut plugins = PluginRegistry::new();
+        // can't fail, it brings no commands
+        plugins.register(Box::new(StdPlugin), &[]).unwrap();
+
+        Self {
+            debuggee: None,
+            plugins,
+            convenience_variables: ConvenienceVariables::new(),
+            source_path: SourcePath::new(),
+            output_log: OutputLog::new(),
+            diagnostics: None,
+            trace_frame: None,
+            environment: BTreeMap::new(),
+            child_output_log: ChildOutputLog::new(),
+            crash_report: None,
+            breakpoint_sources: BTreeMap::new(),
+            symbol_index: SymbolIndex::open_default(),
+            follow_fork: FollowFork::default(),
+            inferiors: Vec::new(),
+        }
+    }
+
+    // Lets the debuggee run until it's gone, passing it every signal it gets and reporting where
+    // the fatal ones hit. Breakpoint hits are reported and carried on from. The debuggee is
+    // killed once `timeout` passes.
+    pub fn run_to_completion(&mut self, timeout: Option<Duration>) -> anyhow::Result<BatchOutcome> {
+        self.run_until_gone(timeout, |_, _| ())
+    }
+
+    // Like `run_to_completion`, with a JSON report of how it went instead of a log. Backtraces of
+    // every thread are taken when a fatal signal hits and before the debuggee is killed for
+    // running out of time, and kept if that's how it ended.
+    pub fn verdict(&mut self, timeout: Option<Duration>) -> anyhow::Result<VerdictReport> {
+        let started = Instant::now();
+        let pid = self.debuggee.as_ref().ok_or(anyhow!("no debuggee"))?.pid();
+        let mut snapshot = None;
+        let outcome = self.run_until_gone(timeout, |debuggee, info| {
+            let memory_map = debuggee.memory_map().ok();
+            let threads = debuggee
+                .threads()
+                .iter()
+                .map(|tid| {
+                    ThreadReport::new(*tid, debuggee.backtrace(*tid), |address| {
+                        debuggee.lookup_symbol(memory_map.as_ref()?, address)
+                    })
+                })
+                .collect::<Vec<_>>();
+            snapshot = Some((
+                debuggee.current_thread(),
+                info.and_then(|info| info.fault_address),
+                threads,
+            ));
+        })?;
+
+        let verdict = match outcome {
+            BatchOutcome::Exited(_) => Verdict::Exited,
+            BatchOutcome::Terminated(_) => Verdict::Crashed,
+            BatchOutcome::TimedOut => Verdict::TimedOut,
+        };
+        let mut report = VerdictReport::new(verdict, pid, started.elapsed());
+        match outcome {
+            BatchOutcome::Exited(status_code) => report.exit_code = Some(status_code),
+            BatchOutcome::Terminated(signal) => report.signal = Some(signal.as_str().to_string()),
+            BatchOutcome::TimedOut => (),
+        }
+        // a fatal signal the debuggee handled and lived on from doesn't count
+        if let (Verdict::Crashed | Verdict::TimedOut, Some((thread, fault_address, threads))) =
+            (verdict, snapshot)
+        {
+            if verdict == Verdict::Crashed {
+                report.crashed_thread = Some(thread.as_raw());
+                report.fault_address = fault_address.map(|address| address.to_string());
+            }
+            report.threads = threads;
+        }
+        Ok(report)
+    }
+
+    // Calls `snapshot` whenever the debuggee stops at a fatal signal, and once it's interrupted
+    // for running out of time.
+    fn run_until_gone(
+        &mut self,
+        timeout: Option<Duration>,
+        mut snapshot: impl FnMut(&Debuggee, Option<&SignalInfo>),
+    ) -> anyhow::Result<BatchOutcome> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let debuggee = self.debuggee.as_mut().ok_or(anyhow!("no debuggee"))?;
+        debuggee.cancellation_token().take();
+
+        loop {
+            match debuggee.process_state() {
+                ProcessState::Stopped(StopReason::Signal(info)) => {
+                    if is_fatal_signal(info.signal) {
+                        match CrashReport::capture(debuggee, info) {
+                            Ok(report) => {
+                                pp_crash_report(&report);
+                                self.crash_report = Some(report);
+                            }
+                            Err(err) => {
+                                warn!(error = box_err(err), "unable to capture crash report");
+                                report_crash(debuggee, &info);
+                            }
+                        }
+                        snapshot(debuggee, Some(&info));
+                    } else {
+                        info!(signal = %info.signal, thread = %debuggee.current_thread(), "signal");
+                    }
+                    debuggee.resume_with_signal(Some(info.signal))?;
+                }
+                ProcessState::Stopped(reason) => {
+                    if let Some(breakpoint) = debuggee.hit_breakpoint() {
+                        info!(
+                            breakpoint = breakpoint.id(),
+                            address = %breakpoint.address(),
+                            thread = %debuggee.current_thread(),
+                            "breakpoint hit",
+                        );
+                    } else if reason != StopReason::Initial {
+                        info!(reason = %reason, "stopped");
+                    }
+                    debuggee.resume()?;
+                }
+                ProcessState::Running => (),
+                ProcessState::Exited(status_code) => {
+                    let status_code =
+                        status_code.ok_or(anyhow!("exit status of the debuggee is unknown"))?;
+                    info!(status_code, "debuggee exited");
+                    return Ok(BatchOutcome::Exited(status_code));
+                }
+                ProcessState::Terminated(signal) => {
+                    error!(signal = %signal, "debuggee terminated");
+                    return Ok(BatchOutcome::Terminated(signal));
+                }
+            }
+
+            let remaining =
+                deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+            match debuggee.wait_for_stop(remaining)? {
+                WaitOutcome::StateChanged(_) => (),
+                WaitOutcome::TimedOut | WaitOutcome::Cancelled => {
+                    warn!("debuggee is still running, killing it");
+                    let stopped = debuggee
+                        .interrupt()
+                        .and_then(|()| debuggee.wait_for_stop(Some(INTERRUPT_TIMEOUT)));
+                    match stopped {
+                        Ok(WaitOutcome::StateChanged(ProcessState::Stopped(_))) => {
+                            snapshot(debuggee, None)
+                        }
+                        Ok(_) => (),
+                        Err(err) => warn!(error = box_err(err), "unable to interrupt the debuggee"),
+                    }
+                    self.handle_detach();
+                    return Ok(BatchOutcome::TimedOut);
+                }
+            }
+        }
+    }
+
+    fn builtin_command_names() -> Vec<String> {
+        CommandWrapper::command()
+            .get_subcommands()
+            .map(|command| command.get_name().to_string())
+            .collect()
+    }
+
+    pub fn register_plugin(&mut self, plugin: Box<dyn Plugin>) -> anyhow::Result<()> {
+        let reserved = Self::builtin_command_names();
+        let reserved = reserved.iter().map(String::as_str).collect::<Vec<_>>();
+        self.plugins.register(plugin, &reserved)
+    }
+
+    #[cfg(feature = "dynamic-plugins")]
+    pub fn load_plugins<P: AsRef<Path>>(&mut self, dir: P) -> anyhow::Result<()> {
+        let reserved = Self::builtin_command_names();
+        let reserved = reserved.iter().map(String::as_str).collect::<Vec<_>>();
+        self.plugins.load_directory(dir, &reserved)
+    }
+
+    pub fn plugins(&self) -> &PluginRegistry {
+        &self.plugins
+    }
+
+    pub fn handle_command(&mut self, command: Command) -> CommandExecutionResult {
+        match command {
+            Command::Attach { pid, steal } => self.handle_attach(pid, steal),
+            Command::Run { launch, args } => self.handle_run(launch, args),
+            Command::Detach => self.handle_detach(),
+            Command::Continue { timeout } => self.handle_continue(timeout, Motion::Continue),
+            Command::Stepi => self.handle_continue(None, Motion::Instruction),
+            Command::Step => self.handle_continue(None, Motion::Line { over_calls: false }),
+            Command::Next => self.handle_continue(None, Motion::Line { over_calls: true }),
+            Command::Finish => self.handle_continue(None, Motion::Return),
+            Command::Until { location } => self.handle_until(&location, true),
+            Command::Advance { location } => self.handle_until(&location, false),
+            Command::Break {
+                force,
+                fast,
+                groups,
+                condition,
+                address,
+            } => self.handle_break(&address, force, fast, &groups, condition),
+            Command::Breakpoint { command } => self.handle_breakpoint_command(command),
+            Command::Hbreak { address } => self.handle_hbreak(&address),
+            Command::Delete { selection } => {
+                self.handle_breakpoint_command(BreakpointCommand::Delete { selection })
+            }
+            Command::Watch {
+                size,
+                access,
+                address,
+            } => self.handle_watch(&address, size, access),
+            Command::Unwatch { id } => self.handle_unwatch(id),
+            Command::Mwatch { address, len } => self.handle_mwatch(&address, len),
+            Command::Jump {
+                force,
+                stop,
+                target,
+            } => self.handle_jump(&target, force, stop),
+            Command::Register { command } => self.handle_register_command(command),
+            Command::Memory { command } => self.handle_memory_command(command),
+            Command::Print {
+                ty: Some(ty),
+                expression,
+                ..
+            } => self.handle_print_pretty(&ty, &expression.join(" ")),
+            Command::Print {
+                format,
+                ty: None,
+                expression,
+            } => self.handle_print(format, &expression.join(" ")),
+            Command::X {
+                format,
+                ty: Some(ty),
+                address,
+            } => self.handle_examine_typed(format, &ty, &address),
+            Command::X {
+                format,
+                ty: None,
+                address,
+            } => self.handle_examine(format, &address),
+            Command::PrettyPrint {
+                element_size,
+                abi,
+                kind,
+                address,
+            } => self.handle_pretty_print(kind, &address, element_size, abi),
+            Command::Gcore { path } => self.handle_gcore(path),
+            Command::Session { command } => self.handle_session_command(command),
+            Command::Directory { paths } => self.handle_directory(paths),
+            Command::Info { command } => self.handle_info_command(command),
+            Command::Symbol { command } => self.handle_symbol_command(command),
+            Command::Show { command } => self.handle_show_command(command),
+            Command::Set { command } => self.handle_set_command(command),
+            Command::Unset {
+                command: UnsetCommand::Environment { name },
+            } => self.handle_unset_environment(name),
+            Command::Inferior { pid } => self.handle_inferior(pid),
+            Command::Checkpoint { command: None } => self.handle_checkpoint(),
+            Command::Checkpoint {
+                command: Some(CheckpointCommand::Delete { id }),
+            } => self.handle_delete_checkpoint(id),
+            Command::Checksec => self.handle_checksec(),
+            Command::Restart { id } => self.handle_restart(id),
+            Command::Trace {
+                command: Some(command),
+                ..
+            } => self.handle_trace_command(command),
+            Command::Trace {
+                command: None,
+                registers,
+                memory,
+                collect,
+                address,
+            } => self.handle_trace(&address, registers, memory, collect),
+            Command::Tstatus => self.handle_tstatus(),
+            Command::Tfind { frame } => self.handle_tfind(frame),
+            Command::Tdump => self.handle_tdump(),
+            Command::Heap { command } => self.handle_heap_command(command),
+            Command::Coverage { command } => self.handle_coverage_command(command),
+            Command::Replay { path } => self.handle_replay(&path),
+            Command::Maintenance {
+                command: MaintenanceCommand::FlushSymbolCache,
+            } => self.handle_flush_symbol_cache(),
+            Command::Quit => self.handle_quit(),
+        }
+    }
+
+    pub fn handle_register_command(&mut self, command: RegisterCommand) -> CommandExecutionResult {
+        match command {
+            RegisterCommand::Read {
+                name,
+                group,
+                format,
+            } => self.handle_register_read(name.as_deref(), group, format),
+            RegisterCommand::Write { name, fields } => self.handle_register_write(&name, &fields),
+            RegisterCommand::Changed => self.handle_register_changed(),
+        }
+    }
+
+    pub fn handle_memory_command(&mut self, command: MemoryCommand) -> CommandExecutionResult {
+        match command {
+            MemoryCommand::Read { address, len } => self.handle_memory_read(&address, len),
+            MemoryCommand::Find {
+                start,
+                end,
+                hex,
+                pattern,
+            } => self.handle_memory_find(&start, &end, hex, &pattern),
+            MemoryCommand::Dump { address, len, path } => {
+                self.handle_memory_dump(&address, len, &path)
+            }
+            MemoryCommand::Restore {
+                path,
+                address,
+                force,
+            } => self.handle_memory_restore(&path, &address, force),
+        }
+    }
+
+    pub fn handle_session_command(&mut self, command: SessionCommand) -> CommandExecutionResult {
+        match command {
+            SessionCommand::Save { path } => self.handle_session_save(path),
+            SessionCommand::Load { path } => self.handle_session_load(path),
+        }
+    }
+
+    pub fn handle_symbol_command(&mut self, command: SymbolCommand) -> CommandExecutionResult {
+        match command {
+            SymbolCommand::Find { query, limit } => self.handle_symbol_find(&query, limit),
+        }
+    }
+
+    pub fn handle_heap_command(&mut self, command: HeapCommand) -> CommandExecutionResult {
+        match command {
+            HeapCommand::Trace { enabled } => self.handle_heap_trace(enabled),
+            HeapCommand::Stats { top } => self.handle_heap_stats(top),
+            HeapCommand::Find { address } => self.handle_heap_find(&address),
+        }
+    }
+
+    pub fn handle_coverage_command(&mut self, command: CoverageCommand) -> CommandExecutionResult {
+        match command {
+            CoverageCommand::Start { pattern, module } => {
+                self.handle_coverage_start(pattern.as_deref(), module.as_deref())
+            }
+            CoverageCommand::Stop => self.handle_with_debuggee_mut(&mut |debuggee| {
+                CommandExecutionResult::Continue(debuggee.stop_coverage())
+            }),
+            CoverageCommand::Report { uncovered, lcov } => {
+                self.handle_coverage_report(uncovered, lcov.as_deref())
+            }
+        }
+    }
+
+    pub fn handle_info_command(&mut self, command: InfoCommand) -> CommandExecutionResult {
+        match command {
+            InfoCommand::Auxv => self.handle_info_auxv(),
+            InfoCommand::Environment { name } => self.handle_info_environment(name.as_deref()),
+            InfoCommand::Proc => self.handle_info_proc(),
+            InfoCommand::Fds => self.handle_info_fds(),
+            InfoCommand::DebugRegisters => self.handle_info_debug_registers(),
+            InfoCommand::Mappings => self.handle_info_mappings(),
+            InfoCommand::StopLog { last } => self.handle_info_stop_log(last),
+            InfoCommand::AntiDebug => self.handle_info_anti_debug(),
+            InfoCommand::SharedLibrary => self.handle_info_shared_library(),
+            InfoCommand::Symbol { address } => self.handle_info_symbol(&address),
+            InfoCommand::Crash => self.handle_info_crash(),
+            InfoCommand::Checkpoints => self.handle_info_checkpoints(),
+            InfoCommand::Inferiors => self.handle_info_inferiors(),
+        }
+    }
+
+    fn handle_with_debuggee_mut<F>(&mut self, action: &mut F) -> CommandExecutionResult
+    where
+        F: FnMut(&mut Debuggee) -> CommandExecutionResult,
+    {
+        match &mut self.debuggee {
+            Some(debuggee) => action(debuggee),
+            None => {
+                warn!("no debuggee, do nothing");
+                CommandExecutionResult::Continue(Ok(()))
+            }
+        }
+    }
+
+    fn handle_with_debuggee<F>(&self, action: F) -> CommandExecutionResult
+    where
+        F: FnOnce(&Debuggee) -> CommandExecutionResult,
+    {
+        match &self.debuggee {
+            Some(debuggee) => action(debuggee),
+            None => {
+                warn!("no debuggee, do nothing");
+                CommandExecutionResult::Continue(Ok(()))
+            }
+        }
+    }
+
+    fn handle_attach(&mut self, pid: pid_t, steal: bool) -> CommandExecutionResult {
+        CommandExecutionResult::Continue(if self.debuggee.is_some() {
+            warn!("use `detach` to detach from the current debuggee first");
+            Ok(())
+        } else {
+            let pid = Pid::from_raw(pid);
+            let config = if steal {
+                debuggee::Config::Steal(pid)
+            } else {
+                debuggee::Config::Existing(pid)
+            };
+            Debuggee::new(config).map(move |mut debuggee| {
+                self.configure_debuggee(&mut debuggee);
+                self.debuggee = Some(debuggee);
+            })
+        })
+    }
+
+    fn handle_run(&mut self, launch: LaunchArgs, args: Vec<String>) -> CommandExecutionResult {
+        CommandExecutionResult::Continue(if self.debuggee.is_some() {
+            warn!("use `detach` to detach from the current debuggee first");
+            Ok(())
+        } else {
+            let inner = move || -> anyhow::Result<()> {
+                let args = NonEmpty::from_vec(args).ok_or(anyhow!("no child argument provided"))?;
+                let capture = self.child_output_log.is_open();
+                let (capture_stdout, capture_stderr) = (
+                    capture && launch.stdout.is_none(),
+                    capture && launch.stderr.is_none(),
+                );
+                let mut launch_spec = launch.into_launch_spec(args, &self.environment);
+                if capture_stdout {
+                    launch_spec = launch_spec.stdout(Stdio::Capture);
+                }
+                if capture_stderr {
+                    launch_spec = launch_spec.stderr(Stdio::Capture);
+                }
+
+                let mut debuggee = Debuggee::new(debuggee::Config::SpawnChild(launch_spec))?;
+                self.configure_debuggee(&mut debuggee);
+                let captured_output = debuggee.take_captured_output();
+                // the forwarding threads finish on their own once the debuggee is gone
+                if let Some(stdout) = captured_output.stdout {
+                    self.child_output_log
+                        .tee(OutputStream::Stdout, stdout, io::stdout());
+                }
+                if let Some(stderr) = captured_output.stderr {
+                    self.child_output_log
+                        .tee(OutputStream::Stderr, stderr, io::stderr());
+                }
+                self.debuggee = Some(debuggee);
+                Ok(())
+            };
+
+            inner()
+        })
+    }
+
+    // What every debuggee gets from the debugger, however it came to be.
+    fn configure_debuggee(&self, debuggee: &mut Debuggee) {
+        debuggee.set_symbol_index(self.symbol_index.clone());
+        debuggee.set_follow_fork(self.follow_fork);
+        if let Some(token) = interrupt::token() {
+            debuggee.set_cancellation_token(token);
+        }
+    }
+
+    // Gives every child forked while following both a debuggee of its own.
+    fn take_over_fork_children(&mut self) {
+        let children = self
+            .debuggee
+            .iter_mut()
+            .chain(self.inferiors.iter_mut())
+            .flat_map(Debuggee::take_fork_children)
+            .collect::<Vec<_>>();
+        for child in children {
+            let pid = child.pid();
+            match Debuggee::from_fork_child(PtraceTracer, child) {
+                Ok(mut inferior) => {
+                    self.configure_debuggee(&mut inferior);
+                    self.inferiors.push(inferior);
+                }
+                Err(err) => {
+                    warn!(child = %pid, error = box_err(err), "unable to take over forked child")
+                }
+            }
+        }
+    }
+
+    fn handle_inferior(&mut self, pid: pid_t) -> CommandExecutionResult {
+        self.take_over_fork_children();
+        let pid = Pid::from_raw(pid);
+        let Some(index) = self
+            .inferiors
+            .iter()
+            .position(|inferior| inferior.pid() == pid)
+        else {
+            return CommandExecutionResult::Continue(Err(anyhow!("no inferior with pid {}", pid)));
+        };
+
+        let inferior = self.inferiors.remove(index);
+        if let Some(current) = self.debuggee.replace(inferior) {
+            if current.process_state().is_alive() {
+                self.inferiors.push(current);
+            }
+        }
+        self.trace_frame = None;
+        info!(pid = %pid, "switched to inferior");
+        CommandExecutionResult::Continue(Ok(()))
+    }
+
+    fn handle_info_inferiors(&mut self) -> CommandExecutionResult {
+        self.take_over_fork_children();
+        let inferiors = self
+            .debuggee
+            .iter()
+            .map(|debuggee| (debuggee, true))
+            .chain(self.inferiors.iter().map(|inferior| (inferior, false)));
+        for (debuggee, current) in inferiors {
+            let state = debuggee.process_state();
+            match current_pc(debuggee).filter(|_| matches!(state, ProcessState::Stopped(_))) {
+                Some(pc) => {
+                    let memory_map = debuggee.memory_map().ok();
+                    info!(
+                        pid = %debuggee.pid(),
+                        current,
+                        pc = %pc,
+                        location = %describe_location(debuggee, memory_map.as_ref(), pc),
+                    )
+                }
+                None => info!(pid = %debuggee.pid(), current, state = ?state),
+            }
+        }
+        CommandExecutionResult::Continue(Ok(()))
+    }
+
+    fn handle_set_follow_fork(&mut self, mode: FollowForkMode) -> CommandExecutionResult {
+        self.follow_fork = match mode {
+            FollowForkMode::Parent => FollowFork::Parent,
+            FollowForkMode::Both => FollowFork::Both,
+        };
+        for debuggee in self.debuggee.iter_mut().chain(self.inferiors.iter_mut()) {
+            debuggee.set_follow_fork(self.follow_fork);
+        }
+        info!(mode = ?self.follow_fork, "following forks");
+        CommandExecutionResult::Continue(Ok(()))
+    }
+
+    fn handle_detach(&mut self) -> CommandExecutionResult {
+        if self.debuggee.is_none() {
+            warn!("no debuggee, do nothing")
+        }
+        self.debuggee = None;
+        self.trace_frame = None;
+        CommandExecutionResult::Continue(Ok(()))
+    }
+
+    // Resumes the debuggee, or steps it by an instruction or a line, and reports where it
+    // stopped.
+    fn handle_continue(&mut self, timeout: Option<u64>, motion: Motion) -> CommandExecutionResult {
+        // TODO: move this to debuggee module
+        fn pp_process_state(state: &ProcessState, symbol: Option<String>) {
+            match state {
+                ProcessState::Running => info!(process_state = %"running"),
+                ProcessState::Stopped(reason) => match symbol {
+                    Some(symbol) => {
+                        info!(process_state = %"stopped", reason = %reason, symbol = %symbol)
+                    }
+                    None => info!(process_state = %"stopped", reason = %reason),
+                },
+                ProcessState::Exited(status_code) => {
+                    if let Some(status_code) = status_code {
+                        info!(process_state = %"exited", status_code = status_code)
+                    } else {
+                        info!("exited")
+                    }
+                }
+                ProcessState::Terminated(signal) => {
+                    info!(process_state = %"terminated", signal = %signal)
+                }
+            }
+        }
+
+        fn pp_thread_event(event: &ThreadEvent) {
+            match event {
+                ThreadEvent::Created(tid) => info!(thread = %tid, "thread created"),
+                ThreadEvent::Exited(tid) => info!(thread = %tid, "thread exited"),
+            }
+        }
+
+        fn pp_module_event(event: &ModuleEvent, selected_pc: Option<VirtAddr>) {
+            match event {
+                ModuleEvent::Loaded {
+                    module,
+                    base,
+                    resumed,
+                } => {
+                    info!(module = %module, base = %base, "library loaded");
+                    for id in resumed {
+                        info!(breakpoint = id, "breakpoint armed again");
+                    }
+                }
+                ModuleEvent::Unloaded {
+                    module,
+                    start,
+                    end,
+                    suspended,
+                } => {
+                    info!(module = %module, "library unloaded");
+                    for id in suspended {
+                        warn!(
+                            breakpoint = id,
+                            module = %module,
+                            "breakpoint is pending until the library is loaded again"
+                        );
+                    }
+                    if selected_pc.is_some_and(|pc| (*start..*end).contains(&pc)) {
+                        warn!(
+                            module = %module,
+                            "the selected trace frame is in the unloaded library, its addresses \
+                             may refer to something else by now"
+                        );
+                    }
+                }
+            }
+        }
+
+        let trace_frame = self.trace_frame;
+        let breakpoint_sources = self.breakpoint_sources.clone();
+        let result = self.handle_with_debuggee_mut(&mut move |debuggee| {
+            let mut inner = || -> anyhow::Result<()> {
+                let timeout = timeout.map(Duration::from_secs);
+                // a ^C that came while no wait was going on isn't meant for this one
+                debuggee.cancellation_token().take();
+                // a previous wait timed out, keep waiting for the same stop
+                let outcome = if matches!(debuggee.process_state(), ProcessState::Running) {
+                    if motion != Motion::Continue {
+                        Err(anyhow!(
+                            "debuggee is still running, use `continue` to keep waiting"
+                        ))?;
+                    }
+                    debuggee.wait_for_stop(timeout)?
+                } else {
+                    debuggee.remember_registers();
+                    match motion {
+                        Motion::Continue if debuggee.is_recording_instructions() => {
+                            debuggee.run_recording()?
+                        }
+                        Motion::Continue => {
+                            debuggee.resume()?;
+                            debuggee.wait_for_stop(timeout)?
+                        }
+                        Motion::Instruction => {
+                            debuggee.step_instruction()?;
+                            debuggee.wait_for_stop(timeout)?
+                        }
+                        Motion::Line { over_calls } => {
+                            let lines = debuggee.line_table()?;
+                            debuggee.step_line(&lines, &debuggee.trampolines()?, over_calls)?
+                        }
+                        Motion::Return => debuggee.finish()?,
+                        Motion::Location { target, in_frame } => {
+                            debuggee.run_until(target, in_frame)?
+                        }
+                    }
+                };
+                match outcome {
+                    WaitOutcome::StateChanged(_) => (),
+                    WaitOutcome::TimedOut | WaitOutcome::Cancelled => {
+                        warn!("debuggee is still running, use `continue` to keep waiting");
+                        return Ok(());
+                    }
+                }
+                debuggee
+                    .take_thread_events()
+                    .iter()
+                    .for_each(pp_thread_event);
+                let selected_pc = trace_frame
+                    .and_then(|index| debuggee.trace_buffer().frame(index))
+                    .map(|frame| frame.pc);
+                for event in debuggee.take_module_events() {
+                    pp_module_event(&event, selected_pc);
+                }
+                for id in debuggee.take_expired_watchpoints() {
+                    info!(
+                        watchpoint = id,
+                        "watchpoint deleted, the frame it watched returned"
+                    );
+                }
+                if let Some(breakpoint) = debuggee.hit_breakpoint() {
+                    match breakpoint_sources.get(&breakpoint.id()) {
+                        Some(source) => info!(
+                            breakpoint = breakpoint.id(),
+                            location = %source,
+                            address = %breakpoint.address(),
+                            thread = %debuggee.current_thread(),
+                            "breakpoint hit",
+                        ),
+                        None => info!(
+                            breakpoint = breakpoint.id(),
+                            address = %breakpoint.address(),
+                            symbol = %symbolize(debuggee, breakpoint.address()),
+                            thread = %debuggee.current_thread(),
+                            "breakpoint hit",
+                        ),
+                    }
+                } else {
+                    let symbol = current_pc(debuggee).map(|pc| symbolize(debuggee, pc));
+                    pp_process_state(&debuggee.process_state(), symbol);
+                }
+                if matches!(
+                    motion,
+                    Motion::Line { .. } | Motion::Return | Motion::Location { .. }
+                ) && matches!(
+                    debuggee.process_state(),
+                    ProcessState::Stopped(StopReason::StepComplete)
+                ) {
+                    let memory_map = debuggee.memory_map().ok();
+                    let pc = current_pc(debuggee);
+                    if let Some((pc, row)) = pc
+                        .and_then(|pc| Some((pc, debuggee.lookup_line(memory_map.as_ref()?, pc)?)))
+                    {
+                        info!(location = %row.location(), address = %pc, "stepped");
+                    }
+                }
+                if motion == Motion::Return
+                    && matches!(
+                        debuggee.process_state(),
+                        ProcessState::Stopped(StopReason::StepComplete)
+                    )
+                {
+                    let value = debuggee.call_return_value()?;
+                    info!(value, hex = %format_args!("{:#x}", value), "returned");
+                }
+                if let ProcessState::Stopped(_) = debuggee.process_state() {
+                    check_stack_pointer(debuggee);
+                    check_breakpoints(debuggee);
+                }
+                Ok(())
+            };
+
+            CommandExecutionResult::Continue(inner())
+        });
+
+        if let Some(debuggee) = &mut self.debuggee {
+            if let ProcessState::Stopped(_) = debuggee.process_state() {
+                self.plugins.notify_stop(debuggee);
+            }
+        }
+        self.triage_crash();
+
+        result
+    }
+
+    // Reports a fatal signal the debuggee stopped at in full, before it's delivered and the
+    // state is gone, and keeps the report for `info crash`.
+    fn triage_crash(&mut self) {
+        let Some(debuggee) = &self.debuggee else {
+            return;
+        };
+        let ProcessState::Stopped(StopReason::Signal(info)) = debuggee.process_state() else {
+            return;
+        };
+        if !is_fatal_signal(info.signal) {
+            return;
+        }
+        match CrashReport::capture(debuggee, info) {
+            Ok(report) => {
+                pp_crash_report(&report);
+                self.crash_report = Some(report);
+            }
+            Err(err) => warn!(error = box_err(err), "unable to capture crash report"),
+        }
+    }
+
+    fn handle_break(
+        &mut self,
+        address: &AddressArg,
+        force: bool,
+        fast: bool,
+        groups: &[String],
+        condition: Option<String>,
+    ) -> CommandExecutionResult {
+        // rejected before there's a breakpoint to take back
+        if let Some(Err(err)) = condition.as_deref().map(Expr::parse) {
+            return CommandExecutionResult::Continue(Err(err));
+        }
+        let (address, source) = match self.resolve_location(address) {
+            Ok(location) => location,
+            Err(err) => return CommandExecutionResult::Continue(Err(err)),
+        };
+        let mut set = None;
+        let result = self.handle_with_debuggee_mut(&mut |debuggee| {
+            if let Err(err) = debuggee.check_breakpoint_address(address) {
+                if !force {
+                    return CommandExecutionResult::Continue(Err(err));
+                }
+                warn!(error = box_err(err), "setting breakpoint anyway");
+            }
+            let mut inner = || -> anyhow::Result<()> {
+                let (id, mechanism) = debuggee.set_breakpoint_auto(address, fast)?;
+                set = Some(id);
+                match &source {
+                    Some(source) => info!(
+                        breakpoint = id,
+                        location = %source,
+                        address = %address,
+                        mechanism = %mechanism,
+                        "breakpoint set"
+                    ),
+                    None => info!(
+                        breakpoint = id,
+                        address = %address,
+                        mechanism = %mechanism,
+                        "breakpoint set"
+                    ),
+                }
+                for group in groups {
+                    debuggee.add_breakpoint_to_group(id, group)?;
+                }
+                if condition.is_some() {
+                    debuggee.set_breakpoint_condition(id, condition.clone())?;
+                }
+                Ok(())
+            };
+            CommandExecutionResult::Continue(inner())
+        });
+        if let (Some(id), Some(source)) = (set, source) {
+            self.breakpoint_sources.insert(id, source);
+        }
+        result
+    }
+
+    fn handle_trace(
+        &mut self,
+        address: &AddressArg,
+        registers: bool,
+        memory: Vec<TraceAction>,
+        collect: Vec<String>,
+    ) -> CommandExecutionResult {
+        let address = match self.resolve_location(address) {
+            Ok((address, _)) => address,
+            Err(err) => return CommandExecutionResult::Continue(Err(err)),
+        };
+        let actions = registers
+            .then_some(TraceAction::Registers)
+            .into_iter()
+            .chain(memory)
+            .chain(collect.into_iter().map(TraceAction::Expression))
+            .collect::<Vec<_>>();
+        if actions.is_empty() {
+            return CommandExecutionResult::Continue(Err(anyhow!(
+                "nothing to collect, use --registers, --memory or --collect"
+            )));
+        }
+
+        self.handle_with_debuggee_mut(&mut |debuggee| {
+            let mut inner = || -> anyhow::Result<()> {
+                debuggee.check_breakpoint_address(address)?;
+                let (id, mechanism) = debuggee.set_breakpoint_auto(address, false)?;
+                if let Err(err) = debuggee.set_tracepoint(id, actions.clone()) {
+                    _ = debuggee.remove_breakpoint(id);
+                    return Err(err);
+                }
+                info!(
+                    tracepoint = id,
+                    address = %address,
+                    mechanism = %mechanism,
+                    "tracepoint set"
+                );
+                Ok(())
+            };
+            CommandExecutionResult::Continue(inner())
+        })
+    }
+
+    fn handle_tstatus(&self) -> CommandExecutionResult {
+        self.handle_with_debuggee(|debuggee| {
+            for (id, actions) in debuggee.tracepoints() {
+                let actions = actions
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                info!(tracepoint = id, collects = %actions);
+            }
+            let trace_buffer = debuggee.trace_buffer();
+            info!(
+                frames = trace_buffer.len(),
+                dropped = trace_buffer.dropped(),
+                capacity = trace_buffer.capacity(),
+                "trace buffer"
+            );
+            if let Some(trace_file) = debuggee.trace_file() {
+                info!(
+                    path = %trace_file.path().display(),
+                    written = trace_file.written(),
+                    dropped = trace_file.dropped(),
+                    backpressure = %trace_file.backpressure(),
+                    "trace file"
+                );
+                if let Some(err) = trace_file.error() {
+                    warn!(error = %err, "trace file stopped being written");
+                }
+            }
+            CommandExecutionResult::Continue(Ok(()))
+        })
+    }
+
+    fn handle_trace_command(&mut self, command: TraceCommand) -> CommandExecutionResult {
+        match command {
+            TraceCommand::Show { last, tracepoint } => self.handle_trace_show(last, tracepoint),
+            TraceCommand::Buffer { capacity } => self.handle_with_debuggee_mut(&mut |debuggee| {
+                debuggee.trace_buffer_mut().set_capacity(capacity);
+                info!(capacity, "trace buffer resized");
+                CommandExecutionResult::Continue(Ok(()))
+            }),
+            TraceCommand::File {
+                path,
+                drop,
+                timeout,
+            } => {
+                let backpressure = if drop {
+                    Backpressure::Drop
+                } else {
+                    Backpressure::Block(Duration::from_millis(timeout))
+                };
+                self.handle_with_debuggee_mut(&mut |debuggee| {
+                    let Some(path) = &path else {
+                        return CommandExecutionResult::Continue(debuggee.close_trace_file());
+                    };
+                    let result = debuggee.open_trace_file(path, backpressure);
+                    if result.is_ok() {
+                        info!(path = %path.display(), backpressure = %backpressure, "streaming trace frames");
+                    }
+                    CommandExecutionResult::Continue(result)
+                })
+            }
+            TraceCommand::Start {
+                registers,
+                capacity,
+            } => self.handle_with_debuggee_mut(&mut |debuggee| {
+                let mut inner = || -> anyhow::Result<()> {
+                    let registers = registers
+                        .iter()
+                        .map(|name| {
+                            Register::lookup_by_name(name)
+                                .ok_or(anyhow!("unable to find register with name: {}", name))
+                        })
+                        .collect::<anyhow::Result<Vec<_>>>()?;
+                    debuggee.start_recording(InstructionTrace::new(capacity, registers));
+                    info!(capacity, "recording instructions, `continue` to run");
+                    Ok(())
+                };
+                CommandExecutionResult::Continue(inner())
+            }),
+            TraceCommand::Stop => self.handle_with_debuggee_mut(&mut |debuggee| {
+                if !debuggee.is_recording_instructions() {
+                    warn!("not recording instructions, do nothing");
+                    return CommandExecutionResult::Continue(Ok(()));
+                }
+                debuggee.stop_recording();
+                let records = debuggee.instruction_trace().records();
+                info!(
+                    recorded = records.next_index(),
+                    kept = records.len(),
+                    "stopped recording instructions"
+                );
+                CommandExecutionResult::Continue(Ok(()))
+            }),
+            TraceCommand::Dump { path, last } => self.handle_with_debuggee(|debuggee| {
+                let records = debuggee.instruction_trace().records();
+                if let Some(path) = &path {
+                    let write = || -> anyhow::Result<()> {
+                        // with room for every record nothing is dropped
+                        let mut trace_file =
+                            TraceFile::create(path, records.len().max(1), Backpressure::Drop)?;
+                        for record in records.frames() {
+                            trace_file.write(record);
+                        }
+                        trace_file.close()
+                    };
+                    let result = write();
+                    if result.is_ok() {
+                        info!(path = %path.display(), records = records.len(), "instruction trace written");
+                    }
+                    return CommandExecutionResult::Continue(result);
+                }
+                let skipped = last.map_or(0, |last| records.len().saturating_sub(last));
+                for record in records.frames().skip(skipped) {
+                    info!("{}", record);
+                }
+                if records.dropped() > 0 {
+                    info!(dropped = records.dropped(), "older instructions didn't fit");
+                }
+                CommandExecutionResult::Continue(Ok(()))
+            }),
+        }
+    }
+
+    fn handle_trace_show(
+        &self,
+        last: Option<usize>,
+        tracepoint: Option<usize>,
+    ) -> CommandExecutionResult {
+        self.handle_with_debuggee(|debuggee| {
+            let trace_buffer = debuggee.trace_buffer();
+            let frames = trace_buffer
+                .frames()
+                .filter(|frame| tracepoint.is_none_or(|id| frame.tracepoint == id))
+                .collect::<Vec<_>>();
+            let skipped = last.map_or(0, |last| frames.len().saturating_sub(last));
+            for frame in &frames[skipped..] {
+                info!("{}", frame);
+            }
+            if trace_buffer.dropped() > 0 {
+                info!(dropped = trace_buffer.dropped(), "older frames didn't fit");
+            }
+            CommandExecutionResult::Continue(Ok(()))
+        })
+    }
+
+    fn handle_tfind(&mut self, frame: Option<usize>) -> CommandExecutionResult {
+        let Some(debuggee) = &self.debuggee else {
+            warn!("no debuggee, do nothing");
+            return CommandExecutionResult::Continue(Ok(()));
+        };
+        let trace_buffer = debuggee.trace_buffer();
+        let found = match (frame, self.trace_frame) {
+            (Some(index), _) => trace_buffer.frame(index),
+            (None, Some(selected)) => trace_buffer.frames().find(|frame| frame.index > selected),
+            (None, None) => trace_buffer.frames().next(),
+        };
+        let Some(found) = found else {
+            return CommandExecutionResult::Continue(Err(anyhow!("no such trace frame")));
+        };
+
+        info!(
+            frame = found.index,
+            tracepoint = found.tracepoint,
+            thread = %found.thread,
+            pc = %found.pc,
+            "trace frame selected"
+        );
+        self.trace_frame = Some(found.index);
+        CommandExecutionResult::Continue(Ok(()))
+    }
+
+    fn handle_tdump(&self) -> CommandExecutionResult {
+        self.handle_with_debuggee(|debuggee| {
+            let frame = self
+                .trace_frame
+                .and_then(|index| debuggee.trace_buffer().frame(index));
+            let Some(frame) = frame else {
+                return CommandExecutionResult::Continue(Err(anyhow!(
+                    "no trace frame selected, use `tfind`"
+                )));
+            };
+
+            let time = frame
+                .time
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs_f64();
+            info!(
+                frame = frame.index,
+                time = %format_args!("{:.3}", time),
+                tracepoint = frame.tracepoint,
+                thread = %frame.thread,
+                pc = %frame.pc,
+            );
+            for (register, value) in &frame.registers {
+                info!(register = %register.name(), register_value = %value);
+            }
+            for memory in &frame.memory {
+                match &memory.result {
+                    Ok((address, bytes)) => info!(
+                        expression = %memory.expression,
+                        address = %address,
+                        bytes = %bytes.iter().map(|byte| format!("{:02x}", byte)).collect::<Vec<_>>().join(" "),
+                    ),
+                    Err(err) => warn!(expression = %memory.expression, error = %err, "not collected"),
+                }
+            }
+            for value in &frame.values {
+                match value.result {
+                    Ok(Value::Int(x)) => {
+                        info!(expression = %value.expression, value = x, hex = %format_args!("{:#x}", x))
+                    }
+                    Ok(Value::Float(x)) => info!(expression = %value.expression, value = x),
+                    Err(ref err) => {
+                        warn!(expression = %value.expression, error = %err, "not collected")
+                    }
+                }
+            }
+            CommandExecutionResult::Continue(Ok(()))
+        })
+    }
+
+    fn handle_heap_trace(&mut self, enabled: bool) -> CommandExecutionResult {
+        self.handle_with_debuggee_mut(&mut |debuggee| {
+            let mut inner = || -> anyhow::Result<()> {
+                if !enabled {
+                    return debuggee.stop_heap_trace();
+                }
+                let symbol_table = debuggee.symbol_table()?;
+                for (function, address) in debuggee.start_heap_trace(&symbol_table)? {
+                    let module = symbol_table
+                        .lookup(address)
+                        .map_or("??", |(symbol, _)| symbol.module.as_str());
+                    info!(function = %function, address = %address, module = %module, "hooked");
+                }
+                Ok(())
+            };
+            CommandExecutionResult::Continue(inner())
+        })
+    }
+
+    fn handle_coverage_start(
+        &mut self,
+        pattern: Option<&str>,
+        module: Option<&str>,
+    ) -> CommandExecutionResult {
+        self.handle_with_debuggee_mut(&mut |debuggee| {
+            let mut inner = || -> anyhow::Result<()> {
+                let symbol_table = debuggee.symbol_table()?;
+                let entries = symbol_table
+                    .symbols()
+                    .iter()
+                    .filter(|symbol| symbol.kind == SymbolKind::Function)
+                    .filter(|symbol| {
+                        pattern.is_none_or(|pattern| symbol.display_name().contains(pattern))
+                    })
+                    .filter(|symbol| module.is_none_or(|module| symbol.module.contains(module)))
+                    .map(|symbol| symbol.address)
+                    .collect::<Vec<_>>();
+                debuggee.start_coverage(entries)?;
+                Ok(())
+            };
+            CommandExecutionResult::Continue(inner())
+        })
+    }
+
+    fn handle_coverage_report(
+        &self,
+        uncovered: bool,
+        lcov: Option<&Path>,
+    ) -> CommandExecutionResult {
+        self.handle_with_debuggee(|debuggee| {
+            let inner = || -> anyhow::Result<()> {
+                let coverage = debuggee
+                    .coverage()
+                    .ok_or(anyhow!("no coverage collected, use `coverage start` first"))?;
+                let symbol_table = debuggee.symbol_table().unwrap_or_default();
+                let memory_map = debuggee.memory_map().ok();
+
+                if uncovered {
+                    for address in coverage.uncovered() {
+                        let location = describe_location(debuggee, memory_map.as_ref(), address);
+                        info!(address = %address, function = %location, "not run");
+                    }
+                } else {
+                    for (address, hit) in coverage.covered() {
+                        let location = describe_location(debuggee, memory_map.as_ref(), address);
+                        let time = hit
+                            .time
+                            .duration_since(UNIX_EPOCH)
+                            .unwrap_or_default()
+                            .as_secs_f64();
+                        info!(
+                            order = hit.order,
+                            time = %format_args!("{:.3}", time),
+                            thread = %hit.thread,
+                            address = %address,
+                            function = %location,
+                        );
+                    }
+                }
+                info!(
+                    collecting = debuggee.is_collecting_coverage(),
+                    probes = coverage.len(),
+                    covered = coverage.covered_count(),
+                    "coverage"
+                );
+
+                if let Some(path) = lcov {
+                    std::fs::write(path, coverage.to_lcov(&symbol_table))?;
+                    info!(path = %path.display(), "lcov tracefile written");
+                }
+                Ok(())
+            };
+            CommandExecutionResult::Continue(inner())
+        })
+    }
+
+    fn handle_heap_stats(&self, top: usize) -> CommandExecutionResult {
+        self.handle_with_debuggee(|debuggee| {
+            let heap_trace = debuggee.heap_trace();
+            let stats = heap_trace.stats();
+            info!(
+                tracing = debuggee.is_heap_tracing(),
+                live_allocations = stats.live_allocations,
+                live_bytes = stats.live_bytes,
+                peak_bytes = stats.peak_bytes,
+                allocations = stats.allocations,
+                releases = stats.releases,
+                unknown_releases = stats.unknown_releases,
+                "heap"
+            );
+            for (function, calls) in &stats.calls {
+                info!(function = %function, calls);
+            }
+
+            let memory_map = debuggee.memory_map().ok();
+            for (call_site, allocations, bytes) in heap_trace.call_sites().into_iter().take(top) {
+                let location = call_site.map_or("??".to_string(), |call_site| {
+                    describe_location(debuggee, memory_map.as_ref(), call_site)
+                });
+                info!(call_site = %location, allocations, bytes, "live");
+            }
+
+            CommandExecutionResult::Continue(Ok(()))
+        })
+    }
+
+    fn handle_heap_find(&self, address: &AddressArg) -> CommandExecutionResult {
+        let address = match self.resolve_address(address) {
+            Ok(address) => address,
+            Err(err) => return CommandExecutionResult::Continue(Err(err)),
+        };
+
+        self.handle_with_debuggee(|debuggee| {
+            let Some(allocation) = debuggee.heap_trace().find(address) else {
+                return CommandExecutionResult::Continue(Err(anyhow!(
+                    "{} is in no allocation recorded by heap tracing",
+                    address
+                )));
+            };
+
+            let memory_map = debuggee.memory_map().ok();
+            let location = |pc: VirtAddr| describe_location(debuggee, memory_map.as_ref(), pc);
+
+            info!(
+                allocation = allocation.index,
+                address = %allocation.address,
+                offset = address.offset_from(allocation.address).unwrap_or_default(),
+                size = allocation.size,
+                function = %allocation.function,
+                thread = %allocation.thread,
+            );
+            if let Some(released_at) = allocation.released_at {
+                warn!(call_site = %location(released_at), "released already");
+            }
+            for (frame, pc) in allocation.backtrace.iter().enumerate() {
+                info!(frame, pc = %pc, location = %location(*pc));
+            }
+
+            CommandExecutionResult::Continue(Ok(()))
+        })
+    }
+
+    fn handle_breakpoint_command(&mut self, command: BreakpointCommand) -> CommandExecutionResult {
+        self.handle_with_debuggee_mut(&mut |debuggee| {
+            let selected =
+                |debuggee: &Debuggee, selection: &BreakpointSelection| match &selection.group {
+                    Some(group) => debuggee.breakpoint_group(group),
+                    None => Ok(selection.ids.clone()),
+                };
+            let mut inner = || -> anyhow::Result<()> {
+                match &command {
+                    BreakpointCommand::Enable { selection } => {
+                        for id in selected(debuggee, selection)? {
+                            debuggee.set_breakpoint_enabled(id, true)?;
+                            info!(breakpoint = id, "breakpoint enabled");
+                        }
+                    }
+                    BreakpointCommand::Disable { selection } => {
+                        for id in selected(debuggee, selection)? {
+                            debuggee.set_breakpoint_enabled(id, false)?;
+                            info!(breakpoint = id, "breakpoint disabled");
+                        }
+                    }
+                    BreakpointCommand::Delete { selection } => {
+                        for id in selected(debuggee, selection)? {
+                            debuggee.remove_breakpoint(id)?;
+                            info!(breakpoint = id, "breakpoint deleted");
+                        }
+                    }
+                    BreakpointCommand::Tag { id, group } => {
+                        debuggee.add_breakpoint_to_group(*id, group)?
+                    }
+                    BreakpointCommand::Untag { id, group } => {
+                        debuggee.remove_breakpoint_from_group(*id, group)?
+                    }
+                    BreakpointCommand::Condition { id, expression } => {
+                        let condition = (!expression.is_empty()).then(|| expression.join(" "));
+                        debuggee.set_breakpoint_condition(*id, condition.clone())?;
+                        match condition {
+                            Some(condition) => {
+                                info!(breakpoint = id, condition = %condition, "condition set")
+                            }
+                            None => info!(breakpoint = id, "breakpoint is unconditional"),
+                        }
+                    }
+                    BreakpointCommand::List => list_breakpoints(debuggee),
+                }
+                Ok(())
+            };
+            CommandExecutionResult::Continue(inner())
+        })
+    }
+
+    fn handle_hbreak(&mut self, address: &AddressArg) -> CommandExecutionResult {
+        let address = match self.resolve_location(address) {
+            Ok((address, _)) => address,
+            Err(err) => return CommandExecutionResult::Continue(Err(err)),
+        };
+        self.handle_with_debuggee_mut(&mut |debuggee| {
+            CommandExecutionResult::Continue(debuggee.set_hardware_breakpoint(address).map(|id| {
+                info!(
+                    breakpoint = id,
+                    address = %address,
+                    "hardware breakpoint set"
+                )
+            }))
+        })
+    }
+
+    fn handle_watch(
+        &mut self,
+        address: &AddressArg,
+        size: usize,
+        access: bool,
+    ) -> CommandExecutionResult {
+        let address = match self.resolve_address(address) {
+            Ok(address) => address,
+            Err(err) => return CommandExecutionResult::Continue(Err(err)),
+        };
+        let kind = if access {
+            WatchKind::ReadWrite
+        } else {
+            WatchKind::Write
+        };
+
+        self.handle_with_debuggee_mut(&mut |debuggee| {
+            CommandExecutionResult::Continue(debuggee.set_watchpoint(address, size, kind).map(
+                |id| {
+                    info!(
+                        watchpoint = id,
+                        address = %address,
+                        size = size,
+                        kind = %kind,
+                        strategy = %debuggee.watchpoints()[&id].strategy(),
+                        "watchpoint set"
+                    );
+                    pp_watch_scope(debuggee, id);
+                },
+            ))
+        })
+    }
+
+    fn handle_memory_read(&self, address: &str, len: usize) -> CommandExecutionResult {
+        let address = match self
+            .with_eval_context(|context| expression::evaluate_address(address, context))
+        {
+            Ok(address) => address,
+            Err(err) => return CommandExecutionResult::Continue(Err(err)),
+        };
+
+        self.handle_with_debuggee(|debuggee| {
+            CommandExecutionResult::Continue(debuggee.read_memory(address, len).map(|bytes| {
+                for (index, line) in bytes.chunks(format::HEXDUMP_WIDTH).enumerate() {
+                    let address = address + (index * format::HEXDUMP_WIDTH) as u64;
+                    info!(address = %address, "{}", format::hexdump_line(line));
+                }
+            }))
+        })
+    }
+
+    fn handle_memory_find(
+        &self,
+        start: &str,
+        end: &str,
+        hex: bool,
+        pattern: &str,
+    ) -> CommandExecutionResult {
+        let range = self.with_eval_context(|context| {
+            Ok((
+                expression::evaluate_address(start, context)?,
+                expression::evaluate_address(end, context)?,
+            ))
+        });
+        let pattern = if hex {
+            parse_hex_bytes(pattern)
+        } else {
+            Ok(pattern.as_bytes().to_vec())
+        };
+        let ((start, end), pattern) = match range.and_then(|range| Ok((range, pattern?))) {
+            Ok(search) => search,
+            Err(err) => return CommandExecutionResult::Continue(Err(err)),
+        };
+
+        self.handle_with_debuggee(|debuggee| {
+            CommandExecutionResult::Continue(debuggee.find_memory(start, end, &pattern).map(
+                |found| {
+                    for address in &found {
+                        info!(address = %address, "pattern found");
+                    }
+                    info!(matches = found.len(), "search done");
+                },
+            ))
+        })
+    }
+
+    fn handle_memory_dump(&self, address: &str, len: u64, path: &Path) -> CommandExecutionResult {
+        let address = match self
+            .with_eval_context(|context| expression::evaluate_address(address, context))
+        {
+            Ok(address) => address,
+            Err(err) => return CommandExecutionResult::Continue(Err(err)),
+        };
+
+        self.handle_with_debuggee(|debuggee| {
+            CommandExecutionResult::Continue(debuggee.dump_memory(address, len, path).map(|()| {
+                info!(address = %address, size = len, path = %path.display(), "memory dumped");
+            }))
+        })
+    }
+
+    fn handle_memory_restore(
+        &mut self,
+        path: &Path,
+        address: &str,
+        force: bool,
+    ) -> CommandExecutionResult {
+        let address = match self
+            .with_eval_context(|context| expression::evaluate_address(address, context))
+        {
+            Ok(address) => address,
+            Err(err) => return CommandExecutionResult::Continue(Err(err)),
+        };
+
+        self.handle_with_debuggee_mut(&mut |debuggee| {
+            CommandExecutionResult::Continue(debuggee.restore_memory(path, address, force).map(
+                |size| {
+                    info!(address = %address, size, path = %path.display(), "memory restored");
+                },
+            ))
+        })
+    }
+
+    fn handle_mwatch(&mut self, address: &str, len: usize) -> CommandExecutionResult {
+        let address = match self
+            .with_eval_context(|context| expression::evaluate_address(address, context))
+        {
+            Ok(address) => address,
+            Err(err) => return CommandExecutionResult::Continue(Err(err)),
+        };
+
+        self.handle_with_debuggee_mut(&mut |debuggee| {
+            CommandExecutionResult::Continue(debuggee.set_page_watchpoint(address, len).map(|id| {
+                info!(
+                    watchpoint = id,
+                    address = %address,
+                    size = len,
+                    strategy = %debuggee.watchpoints()[&id].strategy(),
+                    "watchpoint set"
+                );
+                pp_watch_scope(debuggee, id);
+            }))
+        })
+    }
+
+    fn handle_unwatch(&mut self, id: usize) -> CommandExecutionResult {
+        self.handle_with_debuggee_mut(&mut |debuggee| {
+            CommandExecutionResult::Continue(
+                debuggee
+                    .remove_watchpoint(id)
+                    .map(|()| info!(watchpoint = id, "watchpoint deleted")),
+            )
+        })
+    }
+
+    fn handle_register_read(
+        &self,
+        name: Option<&str>,
+        set: RegisterSet,
+        format: Option<VectorFormat>,
+    ) -> CommandExecutionResult {
+        // TODO: move all these to register module
+        // what a pointer sized register points into
+        type PointsTo<'a> = dyn Fn(u64) -> Option<Provenance> + 'a;
+
+        fn pp_register(
+            registers: &Registers,
+            register: Register,
+            points_to: &PointsTo,
+        ) -> anyhow::Result<()> {
+            let register_value = registers.read_register(register)?;
+
+            let strings = match register_value {
+                RegisterValue::Byte64(bytes) => format::strings_in(&bytes),
+                RegisterValue::Byte128(bytes) => format::strings_in(&bytes),
+                RegisterValue::Byte256(bytes) => format::strings_in(&bytes),
+                RegisterValue::Byte512(bytes) => format::strings_in(&bytes),
+                _ => Vec::new(),
+            };
+            let region = match register_value {
+                RegisterValue::U64(value) => points_to(value),
+                _ => None,
+            };
+            let fields = register.decode(register_value);
+            match (fields, strings.is_empty(), region) {
+                (Some(fields), _, _) => info!(
+                    register = %register.name(),
+                    register_value = %register_value,
+                    fields = %fields,
+                ),
+                (None, false, _) => info!(
+                    register = %register.name(),
+                    register_value = %register_value,
+                    strings = %strings.join(" "),
+                ),
+                (None, true, Some(region)) => info!(
+                    register = %register.name(),
+                    register_value = %register_value,
+                    points_to = %region,
+                ),
+                (None, true, None) => {
+                    info!(register = %register.name(), register_value = %register_value)
+                }
+            }
+
+            Ok(())
+        }
+
+        fn pp_register_with_name(
+            registers: &Registers,
+            name: &str,
+            format: Option<VectorFormat>,
+            points_to: &PointsTo,
+        ) -> anyhow::Result<()> {
+            let register = Register::lookup_by_name(name)
+                .ok_or(anyhow!("unable to find register with name: {}", name))?;
+            let Some(format) = format else {
+                return pp_register(registers, register, points_to);
+            };
+
+            let format = match format {
+                VectorFormat::F32 => LaneFormat::F32,
+                VectorFormat::F64 => LaneFormat::F64,
+                VectorFormat::U8 => LaneFormat::U8,
+                VectorFormat::U16 => LaneFormat::U16,
+                VectorFormat::U32 => LaneFormat::U32,
+                VectorFormat::U64 => LaneFormat::U64,
+                VectorFormat::Hex => LaneFormat::Hex,
+            };
+            let lanes = registers
+                .read_register(register)?
+                .format_lanes(format)
+                .ok_or(anyhow!("{} is not a vector register", name))?;
+            info!(register = %register.name(), register_value = %lanes);
+            Ok(())
+        }
+
+        fn pp_all_registers(
+            registers: &Registers,
+            set: RegisterSet,
+            points_to: &PointsTo,
+        ) -> anyhow::Result<()> {
+            let group = match set {
+                RegisterSet::Gpr => Some(RegisterGroup::GeneralPurpose),
+                RegisterSet::Fpr => Some(RegisterGroup::FloatingPoint),
+                RegisterSet::Vector => Some(RegisterGroup::Vector),
+                RegisterSet::Debug => Some(RegisterGroup::Debug),
+                RegisterSet::All => None,
+            };
+            Register::all_registers()
+                .into_iter()
+                .filter(|reg| group.is_none() || reg.group() == group)
+                .filter(|reg| registers.is_available(*reg))
+                .try_for_each(|reg| pp_register(registers, reg, points_to))?;
+
+            Ok(())
+        }
+
+        self.handle_with_debuggee(|debuggee| {
+            // registers are still worth showing without annotations
+            let memory_map = match debuggee.memory_map() {
+                Ok(memory_map) => Some(memory_map),
+                Err(err) => {
+                    warn!(error = box_err(err), "unable to classify pointers");
+                    None
+                }
+            };
+            let stack_pointers = debuggee.stack_pointers();
+            let symbols = match &memory_map {
+                Some(_) => debuggee.symbol_table().unwrap_or_default(),
+                None => Default::default(),
+            };
+            let points_to = |value: u64| {
+                Provenance::of(
+                    VirtAddr::new(value),
+                    memory_map.as_ref()?,
+                    &stack_pointers,
+                    &symbols,
+                    |address, len| debuggee.read_memory(address, len),
+                )
+            };
+
+            CommandExecutionResult::Continue(match debuggee.registers() {
+                Some(registers) => match name {
+                    Some(name) => pp_register_with_name(registers, name, format, &points_to),
+                    None => pp_all_registers(registers, set, &points_to),
+                },
+                None => {
+                    warn!("no register info available");
+                    Ok(())
+                }
+            })
+        })
+    }
+
+    fn handle_register_changed(&self) -> CommandExecutionResult {
+        self.handle_with_debuggee(|debuggee| {
+            let inner = || -> anyhow::Result<()> {
+                let registers = debuggee
+                    .registers()
+                    .ok_or(anyhow!("no register info available"))?;
+                let previous = debuggee
+                    .previous_registers()
+                    .ok_or(anyhow!("no previous stop of this thread to compare with"))?;
+
+                let changes = registers.changes_since(previous);
+                if changes.is_empty() {
+                    info!("no registers changed");
+                }
+                for (register, old, new) in changes {
+                    info!(register = %register.name(), "{} -> {}", old, new);
+                }
+                Ok(())
+            };
+            CommandExecutionResult::Continue(inner())
+        })
+    }
+
+    fn handle_register_write(&mut self, name: &str, fields: &[String]) -> CommandExecutionResult {
+        self.handle_with_debuggee_mut(&mut |debuggee| {
+            let mut inner = || -> anyhow::Result<()> {
+                let register = Register::lookup_by_name(name)
+                    .ok_or(anyhow!("unable to find register with name: {}", name))?;
+                if !fp_control::is_decodable(register.name()) {
+                    Err(anyhow!(
+                        "${} has no named fields, assign to it with `set variable` instead",
+                        name
+                    ))?;
+                }
+                let current = debuggee
+                    .registers()
+                    .ok_or(anyhow!("no register info available"))?
+                    .read_register(register)?;
+                let bits = current
+                    .as_u64()
+                    .ok_or(anyhow!("${} is not an integer register", name))?;
+                let bits = fp_control::encode(register.name(), bits, fields)?;
+                let value = RegisterValue::from_bits(bits, current.byte_width())
+                    .ok_or(anyhow!("${} is not an integer register", name))?;
+                debuggee.write_register(register, value)?;
+                if let Some(fields) = register.decode(value) {
+                    info!(register = %register.name(), register_value = %value, fields = %fields);
+                }
+                Ok(())
+            };
+            CommandExecutionResult::Continue(inner())
+        })
+    }
+
+    // Convenience variables and literals work without a debuggee.
+    fn with_eval_context<R>(&self, action: impl FnOnce(&dyn EvalContext) -> R) -> R {
+        let variables = &self.convenience_variables;
+        match &self.debuggee {
+            Some(debuggee) => action(&WithConvenienceVariables {
+                context: debuggee,
+                variables,
+            }),
+            None => action(&WithConvenienceVariables {
+                context: &NoDebuggee,
+                variables,
+            }),
+        }
+    }
+
+    fn evaluate(&self, expression: &str) -> anyhow::Result<Value> {
+        self.with_eval_context(|context| expression::evaluate(expression, context))
+    }
+
+    // What an integer points to in the debuggee, if it looks like a pointer.
+    fn points_to(&self, value: Value) -> Option<Provenance> {
+        let (Some(debuggee), Value::Int(x)) = (&self.debuggee, value) else {
+            return None;
+        };
+        let address = VirtAddr::new(x as u64);
+        let memory_map = debuggee.memory_map().ok()?;
+        // loading symbols isn't worth it for plain integers
+        memory_map.region_containing(address)?;
+
+        Provenance::of(
+            address,
+            &memory_map,
+            &debuggee.stack_pointers(),
+            &debuggee.symbol_table().unwrap_or_default(),
+            |address, len| debuggee.read_memory(address, len),
+        )
+    }
+
+    fn resolve_address(&self, address: &AddressArg) -> anyhow::Result<VirtAddr> {
+        self.with_eval_context(|context| {
+            expression::evaluate_address(&address.as_expression(), context)
+        })
+    }
+
+    // Where a breakpoint or a jump goes: `file:line`, a function name like `main` or
+    // `my_crate::foo`, or an address expression. The source location is there for `file:line`.
+    // Where `until` and `advance` run to, a bare number being a line of the file the pc is in.
+    fn resolve_until_target(&self, location: &AddressArg) -> anyhow::Result<VirtAddr> {
+        let Ok(line) = location.as_expression().parse::<u64>() else {
+            return self.resolve_location(location).map(|(address, _)| address);
+        };
+        let debuggee = self.debuggee.as_ref().ok_or(anyhow!("no debuggee"))?;
+        let registers = debuggee.registers().ok_or(anyhow!(
+            "debuggee must be stopped to tell which file it's in"
+        ))?;
+        let pc = VirtAddr::new(debuggee.arch().pc(registers.user_regs()));
+
+        let line_table = debuggee.line_table()?;
+        let file = &line_table
+            .lookup(pc)
+            .ok_or(anyhow!("no line information for {}", pc))?
+            .file;
+        let row = line_table.find_line(&file.to_string_lossy(), line)?;
+        Ok(row.address)
+    }
+
+    fn handle_until(&mut self, location: &AddressArg, in_frame: bool) -> CommandExecutionResult {
+        let target = match self.resolve_until_target(location) {
+            Ok(target) => target,
+            Err(err) => return CommandExecutionResult::Continue(Err(err)),
+        };
+        info!(target = %target, "running until");
+        self.handle_continue(None, Motion::Location { target, in_frame })
+    }
+
+    fn resolve_location(
+        &self,
+        address: &AddressArg,
+    ) -> anyhow::Result<(VirtAddr, Option<SourceLocation>)> {
+        let location = address.as_expression();
+        if let Some((file, line)) = line_table::parse_file_line(&location) {
+            let debuggee = self.debuggee.as_ref().ok_or(anyhow!("no debuggee"))?;
+            let line_table = debuggee.line_table()?;
+            let row = line_table.find_line(file, line)?;
+            return Ok((row.address, Some(row.location())));
+        }
+        if !is_symbol_name(&location) {
+            return self.resolve_address(address).map(|address| (address, None));
+        }
+
+        let debuggee = self.debuggee.as_ref().ok_or(anyhow!("no debuggee"))?;
+        let symbol = debuggee.resolve_symbol(&location)?;
+        info!(
+            symbol = %symbol.display_name(),
+            module = %symbol.module,
+            address = %symbol.address,
+            "symbol resolved"
+        );
+        Ok((symbol.address, None))
+    }
+
+    fn handle_print(&mut self, format: Option<Format>, expression: &str) -> CommandExecutionResult {
+        if let Some((name, expression)) = expression::parse_assignment(expression) {
+            return self.handle_assignment(name, expression);
+        }
+
+        let Some(letter) = format.and_then(|format| format.letter) else {
+            let result =
+                self.evaluate(expression)
+                    .map(|value| match (value, self.points_to(value)) {
+                        (Value::Int(x), Some(provenance)) => info!(
+                            value = x,
+                            hex = %format_args!("{:#x}", x),
+                            points_to = %provenance,
+                        ),
+                        (value, _) => log_value(value),
+                    });
+            return CommandExecutionResult::Continue(result);
+        };
+        let unit = format.and_then(|format| format.unit).unwrap_or(8);
+
+        CommandExecutionResult::Continue(self.with_eval_context(|context| {
+            let value = expression::evaluate(expression, context)?;
+            let formatted = match letter {
+                Letter::Char => format::format_char(value.as_i64()),
+                // the value is the address of the string
+                Letter::String => {
+                    format::format_c_string(&format::read_c_string(context, value.as_u64())?)
+                }
+                Letter::Instruction => Err(anyhow!("format letter i is meaningless in print"))?,
+                letter => format::format_unit(&value.as_i64().to_le_bytes()[..unit], letter),
+            };
+            info!("{}", formatted);
+            Ok(())
+        }))
+    }
+
+    fn handle_examine(
+        &self,
+        format: Option<Format>,
+        address: &AddressArg,
+    ) -> CommandExecutionResult {
+        let letter = format
+            .and_then(|format| format.letter)
+            .unwrap_or(Letter::Hex);
+        let count = format.and_then(|format| format.count).unwrap_or(1);
+        let unit = match letter {
+            Letter::Char => 1,
+            _ => format.and_then(|format| format.unit).unwrap_or(4),
+        };
+
+        CommandExecutionResult::Continue(self.with_eval_context(|context| {
+            let mut address =
+                expression::evaluate_address(&address.as_expression(), context)?.as_u64();
+
+            if letter == Letter::Instruction {
+                let debuggee = self.debuggee.as_ref().ok_or(anyhow!("no debuggee"))?;
+                for instruction in debuggee.disassemble(VirtAddr::new(address), count)? {
+                    info!(address = %instruction.address, "{}", instruction.text);
+                }
+                return Ok(());
+            }
+
+            if letter == Letter::String {
+                for _ in 0..count {
+                    let bytes = format::read_c_string(context, address)?;
+                    info!(address = %VirtAddr::new(address), "{}", format::format_c_string(&bytes));
+                    address += bytes.len() as u64 + 1;
+                }
+                return Ok(());
+            }
+
+            // as many units as fit in a line of gdb's output
+            let per_line = match unit {
+                1 | 2 => 8,
+                4 => 4,
+                _ => 2,
+            };
+            let bytes = context.read_memory(address, count * unit)?;
+            for line in bytes.chunks(per_line * unit) {
+                let units = line
+                    .chunks(unit)
+                    .map(|bytes| format::format_unit(bytes, letter))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                let strings = format::strings_in(line);
+                if strings.is_empty() {
+                    info!(address = %VirtAddr::new(address), "{}", units);
+                } else {
+                    info!(address = %VirtAddr::new(address), strings = %strings.join(" "), "{}", units);
+                }
+                address += line.len() as u64;
+            }
+            Ok(())
+        }))
+    }
+
+    fn handle_examine_typed(
+        &self,
+        format: Option<Format>,
+        ty: &str,
+        address: &AddressArg,
+    ) -> CommandExecutionResult {
+        let count = format.and_then(|format| format.count).unwrap_or(1);
+        CommandExecutionResult::Continue(self.with_eval_context(|context| {
+            let ty = expression::parse_type(ty)?;
+            let size = ty.size(context.pointer_size())?;
+            let address = expression::evaluate_address(&address.as_expression(), context)?;
+            let bytes = context.read_memory(address.as_u64(), count * size)?;
+            for (index, bytes) in bytes.chunks(size).enumerate() {
+                let address = address + (index * size) as u64;
+                match ty.decode(bytes)? {
+                    Value::Int(x) if ty.is_pointer() => {
+                        info!(address = %address, "{}", VirtAddr::new(x as u64))
+                    }
+                    Value::Int(x) => {
+                        info!(address = %address, value = x, hex = %format_args!("{:#x}", x))
+                    }
+                    Value::Float(x) => info!(address = %address, value = x),
+                }
+            }
+            Ok(())
+        }))
+    }
+
+    fn handle_assignment(&mut self, name: &str, expression: &str) -> CommandExecutionResult {
+        if Register::lookup_by_name(name).is_some() {
+            return self.handle_set_variable(&format!("${} = {}", name, expression));
+        }
+
+        let result = self
+            .evaluate(expression)
+            .and_then(|value| self.convenience_variables.set(name, value).map(|()| value));
+        CommandExecutionResult::Continue(result.map(log_value))
+    }
+
+    pub fn handle_set_command(&mut self, command: SetCommand) -> CommandExecutionResult {
+        match command {
+            SetCommand::Variable { assignment } => self.handle_set_variable(&assignment.join(" ")),
+            SetCommand::AntiDebug {
+                enabled,
+                spoof_traceme,
+            } => self.handle_set_anti_debug(enabled, spoof_traceme),
+            SetCommand::Logging { command } => self.handle_set_logging(command),
+            SetCommand::FollowFork { mode } => self.handle_set_follow_fork(mode),
+            SetCommand::Debug { target, level } => self.handle_set_debug(&target, level),
+            SetCommand::Breakpoint {
+                command: BreakpointSetting::AutoHw { enabled },
+            } => self.handle_with_debuggee_mut(&mut |debuggee| {
+                debuggee.set_auto_hardware_breakpoints(enabled);
+                info!(enabled, "automatic hardware breakpoints");
+                CommandExecutionResult::Continue(Ok(()))
+            }),
+            SetCommand::Environment { assignment } => {
+                self.handle_set_environment(&assignment.join(" "))
+            }
+            SetCommand::ChildOutputLog { path } => self.handle_set_child_output_log(path),
+            SetCommand::StepIntoHandlers { enabled } => {
+                self.handle_with_debuggee_mut(&mut |debuggee| {
+                    debuggee.set_step_into_handlers(enabled);
+                    info!(enabled, "stepping into signal handlers");
+                    CommandExecutionResult::Continue(Ok(()))
+                })
+            }
+        }
+    }
+
+    // Lets `set debug` change what the subscriber lets through.
+    pub fn set_diagnostic_filter(&mut self, diagnostics: DiagnosticFilter) {
+        self.diagnostics = Some(diagnostics);
+    }
+
+    fn handle_set_debug(&mut self, target: &str, level: LevelFilter) -> CommandExecutionResult {
+        let Some(diagnostics) = &mut self.diagnostics else {
+            return CommandExecutionResult::Continue(Err(anyhow!(
+                "diagnostics can't be changed in this session"
+            )));
+        };
+        CommandExecutionResult::Continue(
+            diagnostics
+                .set(target, level)
+                .map(|()| info!(target = %target, level = %level, "diagnostics changed")),
+        )
+    }
+
+    fn handle_show_debug(&self) -> CommandExecutionResult {
+        let Some(diagnostics) = &self.diagnostics else {
+            info!("diagnostics can't be changed in this session");
+            return CommandExecutionResult::Continue(Ok(()));
+        };
+        info!(target = "all", level = %diagnostics.default_level());
+        for (target, level) in diagnostics.directives() {
+            info!(target = %target, level = %level);
+        }
+        CommandExecutionResult::Continue(Ok(()))
+    }
+
+    // Shared with the tracing layers, see `OutputLog::layer` and `OutputLog::is_redirected`.
+    pub fn output_log(&self) -> &OutputLog {
+        &self.output_log
+    }
+
+    fn handle_set_logging(&mut self, command: LoggingCommand) -> CommandExecutionResult {
+        let result = match command {
+            LoggingCommand::On => self.output_log.enable(),
+            LoggingCommand::Off => {
+                self.output_log.disable();
+                Ok(())
+            }
+            LoggingCommand::File { path } => {
+                if self.output_log.is_enabled() {
+                    warn!("logging is on already, the new file is used from the next `set logging on`");
+                }
+                self.output_log.set_path(path);
+                Ok(())
+            }
+            LoggingCommand::Overwrite { enabled } => {
+                self.output_log.set_overwrite(enabled);
+                Ok(())
+            }
+            LoggingCommand::Redirect { enabled } => {
+                self.output_log.set_redirect(enabled);
+                Ok(())
+            }
+        };
+        if result.is_err() {
+            return CommandExecutionResult::Continue(result);
+        }
+        self.handle_show_logging()
+    }
+
+    fn handle_show_logging(&self) -> CommandExecutionResult {
+        info!("{}", self.output_log.describe());
+        CommandExecutionResult::Continue(Ok(()))
+    }
+
+    pub fn child_output_log(&self) -> &ChildOutputLog {
+        &self.child_output_log
+    }
+
+    // Debuggees already running keep logging into the new file, or stop logging.
+    fn handle_set_child_output_log(&mut self, path: Option<PathBuf>) -> CommandExecutionResult {
+        let result = match path {
+            Some(path) => self.child_output_log.open(path),
+            None => {
+                self.child_output_log.close();
+                Ok(())
+            }
+        };
+        CommandExecutionResult::Continue(result.map(|()| self.log_child_output_log()))
+    }
+
+    fn handle_show_child_output_log(&self) -> CommandExecutionResult {
+        self.log_child_output_log();
+        CommandExecutionResult::Continue(Ok(()))
+    }
+
+    fn log_child_output_log(&self) {
+        match self.child_output_log.path() {
+            Some(path) => info!(path = %path.display(), "logging debuggee output"),
+            None => info!("not logging debuggee output"),
+        }
+    }
+
+    fn handle_set_anti_debug(
+        &mut self,
+        enabled: bool,
+        spoof_traceme: bool,
+    ) -> CommandExecutionResult {
+        if spoof_traceme && !enabled {
+            return CommandExecutionResult::Continue(Err(anyhow!(
+                "spoofing ptrace(PTRACE_TRACEME) needs anti-debugging detection"
+            )));
+        }
+        self.handle_with_debuggee_mut(&mut |debuggee| {
+            let config = AntiDebugConfig {
+                detect: enabled,
+                spoof_traceme,
+            };
+            CommandExecutionResult::Continue(
+                debuggee
+                    .set_anti_debug(config)
+                    .map(|()| info!(detect = enabled, spoof_traceme, "anti-debugging detection")),
+            )
+        })
+    }
+
+    fn handle_set_variable(&mut self, assignment: &str) -> CommandExecutionResult {
+        let Some((lhs, rhs)) = expression::split_assignment(assignment) else {
+            return CommandExecutionResult::Continue(Err(anyhow!(
+                "expected <lvalue> = <expression>"
+            )));
+        };
+        let Some(debuggee) = &mut self.debuggee else {
+            warn!("no debuggee, do nothing");
+            return CommandExecutionResult::Continue(Ok(()));
+        };
+
+        let mut inner = || -> anyhow::Result<()> {
+            let context = WithConvenienceVariables {
+                context: &*debuggee,
+                variables: &self.convenience_variables,
+            };
+            let lvalue = Expr::parse(lhs)?.lvalue(&context)?;
+            let value = expression::evaluate(rhs, &context)?;
+            expression::assign(debuggee, &lvalue, value)?;
+            info!(lvalue = %lvalue, "assigned");
+            log_value(value);
+            Ok(())
+        };
+        CommandExecutionResult::Continue(inner())
+    }
+
+    pub fn handle_show_command(&mut self, command: ShowCommand) -> CommandExecutionResult {
+        match command {
+            ShowCommand::Convenience => self.handle_show_convenience(),
+            ShowCommand::Directories => self.handle_show_directories(),
+            ShowCommand::Logging => self.handle_show_logging(),
+            ShowCommand::Debug => self.handle_show_debug(),
+            ShowCommand::ChildOutputLog => self.handle_show_child_output_log(),
+            ShowCommand::Environment { name } => self.handle_show_environment(name.as_deref()),
+        }
+    }
+
+    fn handle_set_environment(&mut self, assignment: &str) -> CommandExecutionResult {
+        CommandExecutionResult::Continue(parse_env_var(assignment).map_err(|err| anyhow!(err)).map(
+            |(key, value)| {
+                info!(name = %key, value = %value, "environment of the next run");
+                self.environment.insert(key, Some(value));
+            },
+        ))
+    }
+
+    fn handle_unset_environment(&mut self, name: String) -> CommandExecutionResult {
+        info!(name = %name, "unset in the environment of the next run");
+        self.environment.insert(name, None);
+        CommandExecutionResult::Continue(Ok(()))
+    }
+
+    // The debugger's own environment with the changes made by `set environment` and `unset
+    // environment` applied.
+    fn handle_show_environment(&self, name: Option<&str>) -> CommandExecutionResult {
+        let mut environment = env::vars_os()
+            .map(|(key, value)| {
+                (
+                    key.to_string_lossy().into_owned(),
+                    value.to_string_lossy().into_owned(),
+                )
+            })
+            .collect::<BTreeMap<_, _>>();
+        for (key, value) in &self.environment {
+            match value {
+                Some(value) => environment.insert(key.clone(), value.clone()),
+                None => environment.remove(key),
+            };
+        }
+
+        CommandExecutionResult::Continue(match name {
+            Some(name) => environment
+                .get(name)
+                .map(|value| info!(name = %name, value = %value))
+                .ok_or(anyhow!("environment variable {} is not set", name)),
+            None => {
+                environment
+                    .iter()
+                    .for_each(|(key, value)| info!(name = %key, value = %value));
+                Ok(())
+            }
+        })
+    }
+
+    fn handle_show_convenience(&self) -> CommandExecutionResult {
+        let mut variables = self.convenience_variables.iter().peekable();
+        if variables.peek().is_none() {
+            info!("no convenience variables");
+        }
+        for (name, value) in variables {
+            match value {
+                Value::Int(x) => {
+                    info!(name = %format_args!("${}", name), value = x, hex = %format_args!("{:#x}", x))
+                }
+                Value::Float(x) => info!(name = %format_args!("${}", name), value = x),
+            }
+        }
+        CommandExecutionResult::Continue(Ok(()))
+    }
+
+    fn handle_jump(
+        &mut self,
+        target: &AddressArg,
+        force: bool,
+        stop: bool,
+    ) -> CommandExecutionResult {
+        let address = match self.resolve_location(target) {
+            Ok((address, _)) => address,
+            Err(err) => return CommandExecutionResult::Continue(Err(err)),
+        };
+        let result = self.handle_with_debuggee_mut(&mut |debuggee| {
+            CommandExecutionResult::Continue(
+                debuggee
+                    .jump(address, force)
+                    .map(|()| info!(address = %address, "jumped")),
+            )
+        });
+        match result {
+            CommandExecutionResult::Continue(Ok(())) if !stop => {
+                self.handle_continue(None, Motion::Continue)
+            }
+            result => result,
+        }
+    }
+
+    fn handle_pretty_print(
+        &self,
+        kind: StdKind,
+        address: &AddressArg,
+        element_size: usize,
+        abi: Option<StdAbi>,
+    ) -> CommandExecutionResult {
+        let address = match self.resolve_address(address) {
+            Ok(address) => address,
+            Err(err) => return CommandExecutionResult::Continue(Err(err)),
+        };
+        let ty = match kind {
+            StdKind::String => StdType::String,
+            StdKind::Vector => StdType::Vector { element_size },
+            StdKind::Map => StdType::Map { element_size },
+            StdKind::UniquePtr => StdType::UniquePtr,
+            StdKind::SharedPtr => StdType::SharedPtr,
+        };
+
+        let lib = abi.map(|abi| match abi {
+            StdAbi::Libstdcxx => StdLib::LibStdCxx,
+            StdAbi::Libcxx => StdLib::LibCxx,
+        });
+
+        self.pretty_print(&StdPrettyPrinter::new(ty, lib), address)
+    }
+
+    // `print --type`, with the pretty-printers of the plugins.
+    fn handle_print_pretty(&self, type_name: &str, expression: &str) -> CommandExecutionResult {
+        let Some(pretty_printer) = self.plugins.pretty_printer(type_name) else {
+            return CommandExecutionResult::Continue(Err(anyhow!(
+                "no pretty-printer for type {}",
+                type_name
+            )));
+        };
+        let address = match self
+            .with_eval_context(|context| expression::evaluate_address(expression, context))
+        {
+            Ok(address) => address,
+            Err(err) => return CommandExecutionResult::Continue(Err(err)),
+        };
+
+        self.pretty_print(pretty_printer, address)
+    }
+
+    fn pretty_print(
+        &self,
+        pretty_printer: &dyn PrettyPrinter,
+        address: VirtAddr,
+    ) -> CommandExecutionResult {
+        self.handle_with_debuggee(|debuggee| {
+            CommandExecutionResult::Continue(
+                pretty_printer
+                    .format(debuggee, address)
+                    .map(|value| info!("{}", value)),
+            )
+        })
+    }
+
+    fn handle_gcore(&mut self, path: Option<PathBuf>) -> CommandExecutionResult {
+        self.handle_with_debuggee_mut(&mut |debuggee| {
+            let path = path
+                .clone()
+                .unwrap_or_else(|| PathBuf::from(format!("core.{}", debuggee.pid())));
+
+            CommandExecutionResult::Continue(
+                debuggee
+                    .generate_core_dump(&path)
+                    .map(|()| info!(path = %path.display(), "core file written")),
+            )
+        })
+    }
+
+    fn handle_session_save(&self, path: PathBuf) -> CommandExecutionResult {
+        self.handle_with_debuggee(|debuggee| {
+            CommandExecutionResult::Continue(
+                SessionState::capture(debuggee)
+                    .and_then(|mut state| {
+                        state.source_directories = self.source_path.directories().to_vec();
+                        state.environment = self.environment.clone();
+                        state.save(&path)
+                    })
+                    .map(|()| info!(path = %path.display(), "session saved")),
+            )
+        })
+    }
+
+    fn handle_session_load(&mut self, path: PathBuf) -> CommandExecutionResult {
+        let Some(debuggee) = &mut self.debuggee else {
+            warn!("no debuggee, do nothing");
+            return CommandExecutionResult::Continue(Ok(()));
+        };
+
+        let source_path = &mut self.source_path;
+        let environment = &mut self.environment;
+        CommandExecutionResult::Continue(
+            SessionState::load(&path)
+                .and_then(|state| {
+                    state
+                        .source_directories
+                        .iter()
+                        .for_each(|directory| source_path.add(directory));
+                    environment.extend(state.environment.clone());
+                    state.apply(debuggee)
+                })
+                .map(|()| info!(path = %path.display(), "session loaded")),
+        )
+    }
+
+    fn handle_directory(&mut self, paths: Vec<PathBuf>) -> CommandExecutionResult {
+        for path in paths {
+            if !path.is_dir() {
+                warn!(path = %path.display(), "not a directory, adding it anyway");
+            }
+            self.source_path.add(path);
+        }
+        self.handle_show_directories()
+    }
+
+    fn handle_show_directories(&self) -> CommandExecutionResult {
+        if self.source_path.directories().is_empty() {
+            info!("no source directories");
+        }
+        for directory in self.source_path.directories() {
+            info!(directory = %directory.display());
+        }
+        CommandExecutionResult::Continue(Ok(()))
+    }
+
+    fn handle_symbol_find(&self, query: &str, limit: usize) -> CommandExecutionResult {
+        self.handle_with_debuggee(|debuggee| {
+            CommandExecutionResult::Continue(debuggee.symbol_table().map(|symbol_table| {
+                let matches = symbol_table.find_fuzzy(query, limit);
+                if matches.is_empty() {
+                    info!(query = %query, "no matching symbols");
+                }
+                for (symbol, _) in matches {
+                    info!(
+                        address = %symbol.address,
+                        kind = %symbol.kind,
+                        module = %symbol.module,
+                        "{}",
+                        symbol.display_name()
+                    );
+                }
+            }))
+        })
+    }
+
+    fn handle_flush_symbol_cache(&self) -> CommandExecutionResult {
+        CommandExecutionResult::Continue(match &self.symbol_index {
+            Some(index) => index.flush().map(|removed| {
+                info!(
+                    removed,
+                    directory = %index.directory().display(),
+                    "symbol cache flushed"
+                )
+            }),
+            None => Err(anyhow!("there's no symbol cache")),
+        })
+    }
+
+    fn handle_info_auxv(&self) -> CommandExecutionResult {
+        self.handle_with_debuggee(|debuggee| {
+            CommandExecutionResult::Continue(debuggee.auxv().map(|auxv| {
+                for entry in auxv.entries() {
+                    info!(
+                        key = %entry.name().map_or_else(|| entry.key.to_string(), str::to_string),
+                        value = %format_args!("{:#x}", entry.value),
+                    )
+                }
+            }))
+        })
+    }
+
+    fn handle_info_environment(&self, name: Option<&str>) -> CommandExecutionResult {
+        self.handle_with_debuggee(|debuggee| {
+            let inner = || -> anyhow::Result<()> {
+                let environment = debuggee.environment()?;
+                match name {
+                    Some(name) => {
+                        let (_, value) = environment
+                            .iter()
+                            .find(|(key, _)| key == name)
+                            .ok_or(anyhow!("environment variable {} is not set", name))?;
+                        info!(name = %name, value = %value);
+                    }
+                    None => environment
+                        .iter()
+                        .for_each(|(key, value)| info!(name = %key, value = %value)),
+                }
+                Ok(())
+            };
+
+            CommandExecutionResult::Continue(inner())
+        })
+    }
+
+    fn handle_info_mappings(&self) -> CommandExecutionResult {
+        self.handle_with_debuggee(|debuggee| {
+            CommandExecutionResult::Continue(debuggee.memory_map().map(|memory_map| {
+                for (region, kind) in memory_map.classify(&debuggee.stack_pointers()) {
+                    info!(
+                        start = %region.start,
+                        end = %region.end,
+                        permissions = %region.permissions,
+                        offset = %format_args!("{:#x}", region.offset),
+                        kind = %kind,
+                    );
+                }
+            }))
+        })
+    }
+
+    fn handle_info_shared_library(&self) -> CommandExecutionResult {
+        self.handle_with_debuggee(|debuggee| {
+            CommandExecutionResult::Continue(debuggee.memory_map().map(|memory_map| {
+                let root = debuggee.root();
+                let mut modules = memory_map.modules().into_iter().collect::<Vec<_>>();
+                modules.sort_by_key(|(_, base)| *base);
+                for (module, base) in modules {
+                    if root.is_foreign() {
+                        info!(
+                            base = %base,
+                            module = %module,
+                            host_path = %root.module_path(&memory_map, module).display(),
+                        );
+                    } else {
+                        info!(base = %base, module = %module);
+                    }
+                }
+            }))
+        })
+    }
+
+    fn handle_info_symbol(&self, address: &AddressArg) -> CommandExecutionResult {
+        let inner = || -> anyhow::Result<()> {
+            let (address, _) = self.resolve_location(address)?;
+            let debuggee = self.debuggee.as_ref().ok_or(anyhow!("no debuggee"))?;
+            info!(address = %address, symbol = %symbolize(debuggee, address));
+            Ok(())
+        };
+        CommandExecutionResult::Continue(inner())
+    }
+
+    fn handle_info_crash(&self) -> CommandExecutionResult {
+        match &self.crash_report {
+            Some(report) => pp_crash_report(report),
+            None => info!("no crash so far"),
+        }
+        CommandExecutionResult::Continue(Ok(()))
+    }
+
+    fn handle_info_stop_log(&self, last: Option<usize>) -> CommandExecutionResult {
+        self.handle_with_debuggee(|debuggee| {
+            let stop_log = debuggee.stop_log();
+            if stop_log.dropped() > 0 {
+                info!(
+                    dropped = stop_log.dropped(),
+                    capacity = stop_log.capacity(),
+                    "older stops no longer fit the log"
+                );
+            }
+
+            // locations are resolved against what is loaded now
+            let memory_map = debuggee.memory_map().ok();
+            let location = |pc: VirtAddr| describe_location(debuggee, memory_map.as_ref(), pc);
+
+            let skip = last.map_or(0, |last| stop_log.len().saturating_sub(last));
+            for record in stop_log.records().skip(skip) {
+                let time = record
+                    .time
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs_f64();
+                info!(
+                    stop = record.index,
+                    time = %format_args!("{:.3}", time),
+                    thread = %record.thread,
+                    reason = %record.reason,
+                    pc = %record.pc,
+                    location = %location(record.pc),
+                );
+            }
+
+            CommandExecutionResult::Continue(Ok(()))
+        })
+    }
+
+    fn handle_info_anti_debug(&self) -> CommandExecutionResult {
+        self.handle_with_debuggee(|debuggee| {
+            let config = debuggee.anti_debug();
+            info!(
+                detect = config.detect,
+                spoof_traceme = config.spoof_traceme,
+                "anti-debugging detection"
+            );
+            let attempts = debuggee.anti_debug_attempts();
+            if attempts.is_empty() {
+                info!("no anti-debugging attempts seen");
+            }
+            for (index, attempt) in attempts.iter().enumerate() {
+                info!(index, attempt = %attempt);
+            }
+            CommandExecutionResult::Continue(Ok(()))
+        })
+    }
+
+    fn handle_info_proc(&self) -> CommandExecutionResult {
+        self.handle_with_debuggee(|debuggee| {
+            let inner = || -> anyhow::Result<()> {
+                let pid = debuggee.pid();
+                let exe = std::fs::read_link(format!("/proc/{}/exe", pid))?;
+                let cwd = std::fs::read_link(format!("/proc/{}/cwd", pid))?;
+                let command_line =
+                    shlex::try_join(debuggee.command_line()?.iter().map(String::as_str))?;
+                let environment = debuggee.environment()?;
+
+                info!(
+                    pid = %pid,
+                    exe = %exe.display(),
+                    cwd = %cwd.display(),
+                    arch = %debuggee.arch().name(),
+                );
+                info!(cmdline = %command_line);
+                info!(
+                    variables = environment.len(),
+                    "environment, use `info environment` to list it"
+                );
+                Ok(())
+            };
+
+            CommandExecutionResult::Continue(inner())
+        })
+    }
+
+    fn handle_info_fds(&self) -> CommandExecutionResult {
+        self.handle_with_debuggee(|debuggee| {
+            CommandExecutionResult::Continue(debuggee.file_descriptors().map(|fds| {
+                for fd in fds {
+                    match fd.info {
+                        Some(fd_info) => info!(
+                            fd = fd.fd,
+                            target = %fd.target,
+                            offset = fd_info.position,
+                            flags = %fd_info.describe_flags(),
+                        ),
+                        None => info!(fd = fd.fd, target = %fd.target),
+                    }
+                }
+            }))
+        })
+    }
+
+    fn handle_info_debug_registers(&self) -> CommandExecutionResult {
+        self.handle_with_debuggee(|debuggee| {
+            for (index, slot) in debuggee
+                .debug_register_allocator()
+                .slots()
+                .iter()
+                .enumerate()
+            {
+                match slot {
+                    Some(slot) => info!(register = %format_args!("dr{}", index), slot = %slot),
+                    None => info!(register = %format_args!("dr{}", index), "free"),
+                }
+            }
+            CommandExecutionResult::Continue(Ok(()))
+        })
+    }
+
+    fn handle_checksec(&self) -> CommandExecutionResult {
+        self.handle_with_debuggee(|debuggee| {
+            CommandExecutionResult::Continue(debuggee.memory_map().map(|memory_map| {
+                for (module, hardening) in checksec::check_modules(&memory_map, &debuggee.root()) {
+                    match hardening {
+                        Ok(hardening) => info!(
+                            module = %module,
+                            pie = %hardening.pie,
+                            relro = %hardening.relro,
+                            canary = hardening.stack_canary,
+                            nx = hardening.nx,
+                            fortified = hardening.fortified.len(),
+                        ),
+                        Err(err) => {
+                            warn!(error = box_err(err), module = %module, "unable to check")
+                        }
+                    }
+                }
+            }))
+        })
+    }
+
+    fn handle_checkpoint(&mut self) -> CommandExecutionResult {
+        self.handle_with_debuggee_mut(&mut |debuggee| {
+            CommandExecutionResult::Continue(debuggee.checkpoint().map(|_| ()))
+        })
+    }
+
+    fn handle_delete_checkpoint(&mut self, id: usize) -> CommandExecutionResult {
+        self.handle_with_debuggee_mut(&mut |debuggee| {
+            CommandExecutionResult::Continue(
+                debuggee
+                    .delete_checkpoint(id)
+                    .map(|()| info!(checkpoint = id, "checkpoint deleted")),
+            )
+        })
+    }
+
+    fn handle_info_checkpoints(&self) -> CommandExecutionResult {
+        self.handle_with_debuggee(|debuggee| {
+            if debuggee.checkpoints().is_empty() {
+                info!("no checkpoints");
+            }
+            let memory_map = debuggee.memory_map().ok();
+            for (id, checkpoint_pid) in debuggee.checkpoints() {
+                match debuggee.checkpoint_pc(*id) {
+                    Ok(pc) => info!(
+                        checkpoint = id,
+                        checkpoint_pid = %checkpoint_pid,
+                        pc = %pc,
+                        location = %describe_location(debuggee, memory_map.as_ref(), pc),
+                    ),
+                    Err(err) => warn!(
+                        checkpoint = id,
+                        checkpoint_pid = %checkpoint_pid,
+                        error = box_err(err),
+                        "unable to read registers of checkpoint"
+                    ),
+                }
+            }
+            CommandExecutionResult::Continue(Ok(()))
+        })
+    }
+
+    fn handle_restart(&mut self, id: usize) -> CommandExecutionResult {
+        self.handle_with_debuggee_mut(&mut |debuggee| {
+            CommandExecutionResult::Continue(debuggee.restart_checkpoint(id))
+        })
+    }
+
+    fn handle_quit(&self) -> CommandExecutionResult {
+        CommandExecutionResult::Quit(Ok(()))
+    }
+
+    // print/x and x/32bx are passed as `--format` to the command before the slash.
+    fn split_format_suffix(mut args: Vec<String>) -> Vec<String> {
+        let Some((name, format)) = args.first().and_then(|first| first.split_once('/')) else {
+            return args;
+        };
+        if !Self::builtin_command_names()
+            .iter()
+            .any(|builtin| builtin == name)
+        {
+            return args;
+        }
+
+        let (name, format) = (name.to_string(), format.to_string());
+        args.splice(0..1, [name, "--format".to_string(), format]);
+        args
+    }
+
+    pub fn repl_line(&mut self, line: &str) -> CommandExecutionResult {
+        if let Some((name, expression)) = expression::parse_assignment(line) {
+            return self.handle_assignment(name, expression);
+        }
+
+        let args = match shlex::split(line).ok_or(anyhow!("invalid quoting in command")) {
+            Ok(args) => args,
+            Err(err) => return CommandExecutionResult::Continue(Err(err)),
+        };
+
+        let args = Self::split_format_suffix(args);
+
+        if let Some(command) = args.first().and_then(|name| self.plugins.command_mut(name)) {
+            return CommandExecutionResult::Continue(
+                command.execute(self.debuggee.as_mut(), &args[1..]),
+            );
+        }
+
+        match CommandWrapper::try_parse_from(args) {
+            Err(err) => CommandExecutionResult::Continue(Err(err.into())),
+            Ok(command_wrapped) => self.handle_command(command_wrapped.command),
+        }
+    }
+
+    // Runs the commands of `transcript` one after the other and checks what each of them
+    // prints, which is captured instead of shown. Stops after a command that quits.
+    pub fn replay(&mut self, transcript: &Transcript) -> Vec<Mismatch> {
+        let capture = OutputCapture::new();
+        let dispatch = Dispatch::new(tracing_subscriber::registry().with(capture.clone()));
+
+        let mut mismatches = Vec::new();
+        for step in &transcript.steps {
+            let result =
+                tracing::dispatcher::with_default(&dispatch, || self.repl_line(&step.command));
+            let should_quit = result.should_quit();
+            match result {
+                CommandExecutionResult::Continue(Err(err))
+                | CommandExecutionResult::Quit(Err(err)) => capture.push(format!("ERROR: {}", err)),
+                _ => (),
+            }
+            mismatches.extend(replay::check(step, &capture.take()));
+            if should_quit {
+                break;
+            }
+        }
+        mismatches
+    }
+
+    fn handle_replay(&mut self, path: &Path) -> CommandExecutionResult {
+        let transcript = match Transcript::read(path) {
+            Ok(transcript) => transcript,
+            Err(err) => return CommandExecutionResult::Continue(Err(err)),
+        };
+        let mismatches = self.replay(&transcript);
+
+        let mut last_step = None;
+        for mismatch in &mismatches {
+            warn!("{}", mismatch);
+            if last_step != Some(mismatch.line_number) {
+                for line in &mismatch.output {
+                    info!("  | {}", line);
+                }
+                last_step = Some(mismatch.line_number);
+            }
+        }
+
+        let failed_steps = mismatches
+            .iter()
+            .map(|mismatch| mismatch.line_number)
+            .collect::<BTreeSet<_>>()
+            .len();
+        CommandExecutionResult::Continue(if failed_steps == 0 {
+            info!(steps = transcript.steps.len(), "transcript replayed");
+            Ok(())
+        } else {
+            Err(anyhow!(
+                "{} of {} steps didn't match",
+                failed_steps,
+                transcript.steps.len()
+            ))
+        })
+    }
+
+    pub fn repl<T>(&mut self, history_file: Option<T>) -> anyhow::Result<()>
+    where
+        T: AsRef<Path>,
+    {
+        let mut rl = RlWithOpitonalHistoryFile::new(history_file)?;
+
+        loop {
+            match rl.readline("dbg> ") {
+                Ok(line) => {
+                    if line.is_empty() {
+                        continue;
+                    }
+                    _ = rl.add_history_entry(&line);
+                    let result = self.repl_line(&line);
+                    let should_quit = result.should_quit();
+                    match result {
+                        CommandExecutionResult::Continue(Err(err))
+                        | CommandExecutionResult::Quit(Err(err)) => {
+                            error!(error = box_err(err), "failed to execute command")
+                        }
+                        _ => (),
+                    }
+                    if should_quit {
+                        break;
+                    }
+                }
+                Err(ReadlineError::Interrupted) => {
+                    info!("use `quit` or ctrl+d to exit");
+                }
+                Err(ReadlineError::Eof) => {
+                    info!("ctrl-d");
+                    break;
+                }
+                Err(err) => {
+                    error!(error = box_err(err), "unknown readline error");
+                    break;
+                }
+            };
+        }
+
+        Ok(())
+    }
+}
+
+// The pc of the current thread, None while it's running.
+fn current_pc(debuggee: &Debuggee) -> Option<VirtAddr> {
+    let registers = debuggee.registers()?;
+    Some(VirtAddr::new(debuggee.arch().pc(registers.user_regs())))
+}
+
+// `function+offset (module)` for an address in a symbol, like `describe_location` otherwise.
+fn symbolize(debuggee: &Debuggee, address: VirtAddr) -> String {
+    let memory_map = debuggee.memory_map().ok();
+    match memory_map
+        .as_ref()
+        .and_then(|memory_map| debuggee.lookup_symbol(memory_map, address))
+    {
+        Some((symbol, 0)) => format!("{} ({})", symbol.display_name(), symbol.module),
+        Some((symbol, offset)) => {
+            format!(
+                "{}+{:#x} ({})",
+                symbol.display_name(),
+                offset,
+                symbol.module
+            )
+        }
+        None => describe_location(debuggee, memory_map.as_ref(), address),
+    }
+}
+
+// `symbol+offset`, `module+offset` outside of every symbol, `??` if not even that.
+fn describe_location(debuggee: &Debuggee, memory_map: Option<&MemoryMap>, pc: VirtAddr) -> String {
+    let Some(memory_map) = memory_map else {
+        return "??".to_string();
+    };
+    match debuggee.lookup_symbol(memory_map, pc) {
+        Some((symbol, offset)) => format!("{}+{:#x}", symbol.display_name(), offset),
+        None => ModuleOffset::from_address(memory_map, pc)
+            .map_or("??".to_string(), |module_offset| {
+                format!("{}+{:#x}", module_offset.module, module_offset.offset)
+            }),
+    }
+}
+
+// Where the current thread is about to die from a signal.
+fn report_crash(debuggee: &Debuggee, info: &SignalInfo) {
+    let thread = debuggee.current_thread();
+    let pc = current_pc(debuggee);
+    let memory_map = debuggee.memory_map().ok();
+    error!(
+        signal = %info.signal,
+        code = info.code,
+        fault_address = ?info.fault_address.map(|address| address.to_string()),
+        thread = %thread,
+        pc = ?pc.map(|pc| pc.to_string()),
+        location = ?pc.map(|pc| describe_location(debuggee, memory_map.as_ref(), pc)),
+        stack_pointer = ?debuggee.stack_pointers().get(&thread).map(|sp| sp.to_string()),
+        "fatal signal"
+    );
+}
+
+fn pp_crash_report(report: &CrashReport) {
+    let location = report
+        .threads
+        .first()
+        .and_then(|thread| thread.frames.first())
+        .and_then(|frame| frame.function.clone().or(frame.module.clone()));
+    error!(
+        signal = %report.signal.signal,
+        code = report.signal.code,
+        fault_address = ?report.signal.fault_address.map(|address| address.to_string()),
+        thread = %report.thread,
+        pc = %report.pc,
+        location = ?location,
+        "crash"
+    );
+    match &report.instruction {
+        Some(instruction) => info!(
+            pc = %report.pc,
+            bytes = %instruction.bytes.iter().map(|byte| format!("{:02x}", byte)).collect::<String>(),
+            instruction = %instruction.text,
+            "faulting instruction"
+        ),
+        None => warn!(pc = %report.pc, "faulting instruction is unreadable"),
+    }
+    match (&report.fault_region, report.signal.fault_address) {
+        (Some(region), _) => info!(
+            start = %region.start,
+            end = %region.end,
+            permissions = %region.permissions,
+            path = ?region.path,
+            "fault address is mapped"
+        ),
+        (None, Some(address)) => info!(fault_address = %address, "fault address is unmapped"),
+        (None, None) => (),
+    }
+    for (register, value) in &report.registers {
+        info!(register = %register.name(), register_value = %value);
+    }
+    for thread in &report.threads {
+        if let Some(error) = &thread.error {
+            warn!(thread = thread.tid, error = %error, "no backtrace");
+        }
+        for (index, frame) in thread.frames.iter().enumerate() {
+            info!(
+                thread = thread.tid,
+                frame = index,
+                address = %frame.address,
+                function = ?frame.function,
+                module = ?frame.module,
+            );
+        }
+    }
+}
+
+// Names of functions and variables, mangled or not, as opposed to expressions: registers and
+// convenience variables start with `$`, numbers with a digit.
+fn is_symbol_name(s: &str) -> bool {
+    s.starts_with(|c: char| c.is_alphabetic() || c == '_')
+        && s.chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '_' | ':' | '.' | '$'))
+}
+
+fn log_value(value: Value) {
+    match value {
+        Value::Int(x) => info!(value = x, hex = %format_args!("{:#x}", x)),
+        Value::Float(x) => info!(value = x),
+    }
+}
+
+fn list_breakpoints(debuggee: &Debuggee) {
+    let software = debuggee
+        .breakpoints()
+        .values()
+        .map(|breakpoint| (breakpoint.id(), breakpoint.address(), "software"));
+    let hardware = debuggee
+        .hardware_breakpoints()
+        .iter()
+        .map(|(id, address)| (*id, *address, "hardware"));
+    let mut breakpoints = software.chain(hardware).collect::<Vec<_>>();
+    breakpoints.sort();
+    if breakpoints.is_empty() {
+        info!("no breakpoints");
+    }
+
+    for (id, address, mechanism) in breakpoints {
+        let groups = debuggee
+            .breakpoint_groups()
+            .iter()
+            .filter(|(_, ids)| ids.contains(&id))
+            .map(|(group, _)| group.as_str())
+            .collect::<Vec<_>>()
+            .join(",");
+        info!(
+            breakpoint = id,
+            address = %address,
+            mechanism,
+            enabled = debuggee.is_breakpoint_enabled(id),
+            groups = %groups,
+        );
+        if let Some(condition) = debuggee.breakpoint_conditions().get(&id) {
+            info!(breakpoint = id, condition = %condition, "stops only if");
+        }
+        if let Some(location) = debuggee.pending_breakpoints().get(&id) {
+            info!(
+                breakpoint = id,
+                location = %format_args!("{}+{:#x}", location.module, location.offset),
+                "pending until its library is loaded again"
+            );
+        }
+    }
+}
+
+fn pp_watch_scope(debuggee: &Debuggee, id: usize) {
+    if let Some(scope) = debuggee.watchpoints()[&id].scope() {
+        info!(
+            watchpoint = id,
+            scope = %scope,
+            "watchpoint will be deleted once the frame returns"
+        );
+    }
+}
+
+// Signals whose default action kills the process with a core dump, i.e. crashes.
+fn is_fatal_signal(signal: Signal) -> bool {
+    matches!(
+        signal,
+        Signal::SIGSEGV
+            | Signal::SIGBUS
+            | Signal::SIGILL
+            | Signal::SIGFPE
+            | Signal::SIGABRT
+            | Signal::SIGTRAP
+            | Signal::SIGSYS
+    )
+}
+
+fn check_breakpoints(debuggee: &mut Debuggee) {
+    match debuggee.revalidate_breakpoints() {
+        Ok(misplaced) => {
+            for id in misplaced {
+                if let Some(breakpoint) = debuggee.breakpoints().get(&id) {
+                    warn!(
+                        breakpoint = id,
+                        address = %breakpoint.address(),
+                        "breakpoint is not in executable memory anymore, consider deleting it"
+                    );
+                }
+            }
+        }
+        Err(err) => warn!(error = box_err(err), "unable to check breakpoints"),
+    }
+}
+
+// A stack pointer outside every stack means the stack is smashed or switched, so frames read
+// from it can't be trusted.
+fn check_stack_pointer(debuggee: &Debuggee) {
+    let stack_pointers = debuggee.stack_pointers();
+    let Some(&stack_pointer) = stack_pointers.get(&debuggee.current_thread()) else {
+        return;
+    };
+    match debuggee.memory_map() {
+        Ok(memory_map) => match memory_map.kind_of(stack_pointer, &stack_pointers) {
+            Some(RegionKind::Stack(_)) => (),
+            Some(kind) => warn!(
+                stack_pointer = %stack_pointer,
+                region = %kind,
+                "stack pointer is outside the stack"
+            ),
+            None => warn!(stack_pointer = %stack_pointer, "stack pointer is unmapped"),
+        },
+        Err(err) => warn!(error = box_err(err), "unable to check the stack pointer"),
+    }
+}
@@ -0,0 +1,90 @@
+use std::collections::BTreeMap;
+
+use anyhow::anyhow;
+use tracing_subscriber::{filter::LevelFilter, EnvFilter};
+
+// Short names for the targets worth tuning, anything else is taken as a module path.
+const TARGET_ALIASES: &[(&str, &[&str])] = &[
+    (
+        "ptrace",
+        &["stupid_dbg_core::tracer", "stupid_dbg_core::debuggee"],
+    ),
+    ("core", &["stupid_dbg_core"]),
+    ("cli", &["stupid_dbg_cli"]),
+    ("plugin", &["stupid_dbg_cli::plugin"]),
+];
+
+// The filter deciding which diagnostics reach the terminal, changeable while the session is
+// running. Every change rebuilds the whole filter and hands it to `reload`, which swaps it into
+// the subscriber.
+pub struct DiagnosticFilter {
+    default: LevelFilter,
+    directives: BTreeMap<String, LevelFilter>,
+    reload: Box<dyn Fn(EnvFilter) -> anyhow::Result<()>>,
+}
+
+impl DiagnosticFilter {
+    pub fn new<F>(default: LevelFilter, reload: F) -> Self
+    where
+        F: Fn(EnvFilter) -> anyhow::Result<()> + 'static,
+    {
+        Self {
+            default,
+            directives: BTreeMap::new(),
+            reload: Box::new(reload),
+        }
+    }
+
+    pub fn default_level(&self) -> LevelFilter {
+        self.default
+    }
+
+    pub fn directives(&self) -> &BTreeMap<String, LevelFilter> {
+        &self.directives
+    }
+
+    pub fn env_filter(&self) -> anyhow::Result<EnvFilter> {
+        // the default directive of the builder only applies when there are no others
+        let directives = std::iter::once(self.default.to_string())
+            .chain(
+                self.directives
+                    .iter()
+                    .map(|(target, level)| format!("{}={}", target, level)),
+            )
+            .collect::<Vec<_>>()
+            .join(",");
+        Ok(EnvFilter::builder().parse(directives)?)
+    }
+
+    // `all` changes the level of every target without a level of its own.
+    pub fn set(&mut self, target: &str, level: LevelFilter) -> anyhow::Result<()> {
+        let previous = (self.default, self.directives.clone());
+        if target == "all" {
+            self.default = level;
+        } else {
+            for target in resolve_target(target)? {
+                self.directives.insert(target, level);
+            }
+        }
+
+        let result = self.env_filter().and_then(|filter| (self.reload)(filter));
+        if result.is_err() {
+            (self.default, self.directives) = previous;
+        }
+        result
+    }
+}
+
+fn resolve_target(target: &str) -> anyhow::Result<Vec<String>> {
+    if let Some((_, targets)) = TARGET_ALIASES.iter().find(|(alias, _)| *alias == target) {
+        return Ok(targets.iter().map(|target| target.to_string()).collect());
+    }
+    if target.is_empty()
+        || !target
+            .split("::")
+            .all(|part| !part.is_empty() && part.chars().all(|c| c.is_alphanumeric() || c == '_'))
+    {
+        Err(anyhow!("invalid target {:?}", target))?;
+    }
+    Ok(vec![target.to_string()])
+}
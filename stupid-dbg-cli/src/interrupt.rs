@@ -0,0 +1,40 @@
+use std::sync::OnceLock;
+
+use anyhow::anyhow;
+use libc::c_int;
+use nix::sys::signal::{self, SaFlags, SigAction, SigHandler, SigSet, Signal};
+use stupid_dbg_core::cancel::CancellationToken;
+
+// cancelled on SIGINT, handed to every debuggee so ^C stops waiting for whichever one runs
+static TOKEN: OnceLock<CancellationToken> = OnceLock::new();
+
+extern "C" fn cancel_on_sigint(_: c_int) {
+    if let Some(token) = TOKEN.get() {
+        token.cancel();
+    }
+}
+
+// Makes SIGINT cancel the wait on the debuggee instead of killing the debugger. There is no
+// SA_RESTART, so a wait blocked in a syscall is woken up as well.
+pub fn install() -> anyhow::Result<()> {
+    if TOKEN.get().is_some() {
+        return Ok(());
+    }
+    let token =
+        CancellationToken::new().map_err(|err| anyhow!("unable to create token: {}", err))?;
+    _ = TOKEN.set(token);
+
+    let action = SigAction::new(
+        SigHandler::Handler(cancel_on_sigint),
+        SaFlags::empty(),
+        SigSet::empty(),
+    );
+    unsafe { signal::sigaction(Signal::SIGINT, &action) }
+        .map_err(|err| anyhow!("unable to handle SIGINT: {}", err))?;
+    Ok(())
+}
+
+// The token SIGINT cancels, None until `install` is called.
+pub fn token() -> Option<CancellationToken> {
+    TOKEN.get().cloned()
+}
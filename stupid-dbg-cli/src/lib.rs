@@ -0,0 +1,8 @@
+pub(crate) mod aux;
+pub mod child_output;
+pub mod debugger;
+pub mod diagnostics;
+pub mod interrupt;
+pub mod output_log;
+pub mod plugin;
+pub mod replay;
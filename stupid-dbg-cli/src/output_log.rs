@@ -0,0 +1,194 @@
+use std::{
+    fmt::{self, Write as _},
+    fs::{File, OpenOptions},
+    io::Write,
+    path::PathBuf,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use tracing::{
+    field::{Field, Visit},
+    Event, Level, Metadata, Subscriber,
+};
+use tracing_subscriber::{layer::Context, Layer};
+
+pub const DEFAULT_OUTPUT_LOG_FILE: &str = "stupid-dbg.txt";
+
+#[derive(Debug)]
+struct State {
+    path: PathBuf,
+    overwrite: bool,
+    redirect: bool,
+    file: Option<File>,
+}
+
+// Copies what commands print to a file, like `set logging` of gdb. Only the output of the
+// debugger itself is logged, not the diagnostics of the libraries underneath. Clones share the
+// same file.
+#[derive(Debug, Clone)]
+pub struct OutputLog {
+    state: Arc<Mutex<State>>,
+}
+
+impl Default for OutputLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OutputLog {
+    pub fn new() -> Self {
+        Self {
+            state: Arc::new(Mutex::new(State {
+                path: PathBuf::from(DEFAULT_OUTPUT_LOG_FILE),
+                overwrite: false,
+                redirect: false,
+                file: None,
+            })),
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        // the state stays consistent even if a writer panicked
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    pub fn path(&self) -> PathBuf {
+        self.state().path.clone()
+    }
+
+    // Takes effect the next time logging is turned on.
+    pub fn set_path<P: Into<PathBuf>>(&self, path: P) {
+        self.state().path = path.into();
+    }
+
+    pub fn overwrite(&self) -> bool {
+        self.state().overwrite
+    }
+
+    // Truncate the file when logging is turned on instead of appending to it.
+    pub fn set_overwrite(&self, overwrite: bool) {
+        self.state().overwrite = overwrite;
+    }
+
+    pub fn redirect(&self) -> bool {
+        self.state().redirect
+    }
+
+    // Only write to the file while logging, nothing to the terminal.
+    pub fn set_redirect(&self, redirect: bool) {
+        self.state().redirect = redirect;
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.state().file.is_some()
+    }
+
+    pub fn enable(&self) -> anyhow::Result<()> {
+        let mut state = self.state();
+        let file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(!state.overwrite)
+            .truncate(state.overwrite)
+            .open(&state.path)?;
+        state.file = Some(file);
+        Ok(())
+    }
+
+    pub fn disable(&self) {
+        self.state().file = None;
+    }
+
+    // Whether `metadata` belongs to output that must not reach the terminal right now.
+    pub fn is_redirected(&self, metadata: &Metadata<'_>) -> bool {
+        let state = self.state();
+        state.redirect && state.file.is_some() && is_output(metadata)
+    }
+
+    // A layer writing into the file, to be installed next to the one printing to the terminal.
+    pub fn layer(&self) -> OutputLogLayer {
+        OutputLogLayer { log: self.clone() }
+    }
+
+    pub fn describe(&self) -> String {
+        let state = self.state();
+        format!(
+            "logging {} to {} ({}{})",
+            if state.file.is_some() { "on" } else { "off" },
+            state.path.display(),
+            if state.overwrite {
+                "overwrite"
+            } else {
+                "append"
+            },
+            if state.redirect { ", redirected" } else { "" },
+        )
+    }
+}
+
+pub(crate) fn is_output(metadata: &Metadata<'_>) -> bool {
+    metadata.is_event()
+        && *metadata.level() <= Level::INFO
+        && metadata.target().starts_with(env!("CARGO_CRATE_NAME"))
+}
+
+pub struct OutputLogLayer {
+    log: OutputLog,
+}
+
+impl<S: Subscriber> Layer<S> for OutputLogLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        if !is_output(event.metadata()) {
+            return;
+        }
+        let mut state = self.log.state();
+        let Some(file) = &mut state.file else {
+            return;
+        };
+
+        // a failing log file must not take the session down with it
+        _ = writeln!(file, "{}", format_output(event));
+    }
+}
+
+// The message of an event followed by its fields, prefixed with the level unless it's INFO.
+pub(crate) fn format_output(event: &Event<'_>) -> String {
+    let mut line = LineVisitor::default();
+    event.record(&mut line);
+    let mut text = line.message;
+    if *event.metadata().level() != Level::INFO {
+        text.insert_str(0, &format!("{}: ", event.metadata().level()));
+    }
+    for field in line.fields {
+        if !text.is_empty() {
+            text.push(' ');
+        }
+        text.push_str(&field);
+    }
+    text
+}
+
+#[derive(Default)]
+struct LineVisitor {
+    message: String,
+    fields: Vec<String>,
+}
+
+impl Visit for LineVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = value.to_string();
+        } else {
+            self.fields.push(format!("{}={}", field.name(), value));
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            _ = write!(self.message, "{:?}", value);
+        } else {
+            self.fields.push(format!("{}={:?}", field.name(), value));
+        }
+    }
+}
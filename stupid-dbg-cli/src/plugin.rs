@@ -0,0 +1,241 @@
+use std::collections::BTreeMap;
+#[cfg(feature = "dynamic-plugins")]
+use std::{fs, path::Path};
+
+use anyhow::anyhow;
+use stupid_dbg_core::{
+    debuggee::Debuggee,
+    pretty_printer::{self, StdLib, StdType},
+    virt_addr::VirtAddr,
+};
+use tracing::{info, warn};
+
+#[cfg(feature = "dynamic-plugins")]
+use crate::aux::box_err;
+
+// Name of the function a dynamic plugin exports, see `declare_plugin!`.
+pub const PLUGIN_ENTRY_SYMBOL: &str = "stupid_dbg_plugin_create";
+
+// Extension point for external crates. A plugin contributes REPL commands and pretty-printers
+// once when it's registered, and gets notified every time the debuggee stops.
+pub trait Plugin {
+    fn name(&self) -> &str;
+
+    fn commands(&mut self) -> Vec<Box<dyn PluginCommand>> {
+        Vec::new()
+    }
+
+    fn pretty_printers(&mut self) -> Vec<Box<dyn PrettyPrinter>> {
+        Vec::new()
+    }
+
+    fn on_stop(&mut self, _debuggee: &mut Debuggee) {}
+}
+
+pub trait PluginCommand {
+    fn name(&self) -> &str;
+
+    // `args` doesn't include the command name.
+    fn execute(&mut self, debuggee: Option<&mut Debuggee>, args: &[String]) -> anyhow::Result<()>;
+}
+
+pub trait PrettyPrinter {
+    fn type_name(&self) -> &str;
+
+    // `address` is where the object is in the debuggee.
+    fn format(&self, debuggee: &Debuggee, address: VirtAddr) -> anyhow::Result<String>;
+}
+
+// The C++ standard library types, registered by every debugger. Containers are taken to hold 8
+// byte elements, `pretty-print` takes other sizes.
+pub struct StdPlugin;
+
+impl Plugin for StdPlugin {
+    fn name(&self) -> &str {
+        "std"
+    }
+
+    fn pretty_printers(&mut self) -> Vec<Box<dyn PrettyPrinter>> {
+        [
+            StdType::String,
+            StdType::Vector { element_size: 8 },
+            StdType::Map { element_size: 8 },
+            StdType::UniquePtr,
+            StdType::SharedPtr,
+        ]
+        .into_iter()
+        .map(|ty| Box::new(StdPrettyPrinter::new(ty, None)) as Box<dyn PrettyPrinter>)
+        .collect()
+    }
+}
+
+pub struct StdPrettyPrinter {
+    ty: StdType,
+    // detected from the mapped libraries if None
+    lib: Option<StdLib>,
+}
+
+impl StdPrettyPrinter {
+    pub fn new(ty: StdType, lib: Option<StdLib>) -> Self {
+        Self { ty, lib }
+    }
+}
+
+impl PrettyPrinter for StdPrettyPrinter {
+    fn type_name(&self) -> &str {
+        self.ty.type_name()
+    }
+
+    fn format(&self, debuggee: &Debuggee, address: VirtAddr) -> anyhow::Result<String> {
+        let lib = match self.lib {
+            Some(lib) => lib,
+            None => StdLib::detect(&debuggee.memory_map()?).ok_or(anyhow!(
+                "unable to tell the C++ standard library, use `pretty-print --abi`"
+            ))?,
+        };
+        let value = pretty_printer::pretty_print_or_raw(debuggee, lib, self.ty, address.as_u64())?;
+        Ok(format!("{} ({})", value, lib))
+    }
+}
+
+// Exports `$constructor` as the entry point of a plugin built as a cdylib. The plugin has to be
+// built with the same compiler as the debugger, trait objects have no stable ABI.
+#[macro_export]
+macro_rules! declare_plugin {
+    ($constructor:expr) => {
+        #[no_mangle]
+        #[allow(improper_ctypes_definitions)]
+        pub extern "C" fn stupid_dbg_plugin_create() -> *mut Box<dyn $crate::plugin::Plugin> {
+            let plugin: Box<dyn $crate::plugin::Plugin> = Box::new($constructor);
+            Box::into_raw(Box::new(plugin))
+        }
+    };
+}
+
+#[derive(Default)]
+pub struct PluginRegistry {
+    plugins: Vec<Box<dyn Plugin>>,
+    commands: BTreeMap<String, Box<dyn PluginCommand>>,
+    pretty_printers: BTreeMap<String, Box<dyn PrettyPrinter>>,
+    // must be dropped after everything that came out of them
+    #[cfg(feature = "dynamic-plugins")]
+    libraries: Vec<libloading::Library>,
+}
+
+impl PluginRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // `reserved` are names that plugin commands must not shadow, like the builtin commands.
+    pub fn register(
+        &mut self,
+        mut plugin: Box<dyn Plugin>,
+        reserved: &[&str],
+    ) -> anyhow::Result<()> {
+        let commands = plugin.commands();
+        for command in &commands {
+            let name = command.name();
+            if reserved.contains(&name) || self.commands.contains_key(name) {
+                Err(anyhow!(
+                    "plugin {} registers command {} which already exists",
+                    plugin.name(),
+                    name
+                ))?;
+            }
+        }
+
+        info!(plugin = plugin.name(), "registering plugin");
+
+        self.commands.extend(
+            commands
+                .into_iter()
+                .map(|command| (command.name().to_string(), command)),
+        );
+        for pretty_printer in plugin.pretty_printers() {
+            let type_name = pretty_printer.type_name().to_string();
+            if self
+                .pretty_printers
+                .insert(type_name.clone(), pretty_printer)
+                .is_some()
+            {
+                warn!(
+                    plugin = plugin.name(),
+                    type_name = type_name,
+                    "pretty-printer replaced"
+                );
+            }
+        }
+        self.plugins.push(plugin);
+
+        Ok(())
+    }
+
+    // Loads a plugin from a shared library exporting the symbol defined by `declare_plugin!`.
+    #[cfg(feature = "dynamic-plugins")]
+    pub fn load_library<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        reserved: &[&str],
+    ) -> anyhow::Result<()> {
+        let path = path.as_ref();
+
+        let plugin = unsafe {
+            let library = libloading::Library::new(path)
+                .map_err(|err| anyhow!("unable to load plugin {}: {}", path.display(), err))?;
+            let create = library
+                .get::<unsafe extern "C" fn() -> *mut Box<dyn Plugin>>(
+                    PLUGIN_ENTRY_SYMBOL.as_bytes(),
+                )
+                .map_err(|err| anyhow!("{} is not a plugin: {}", path.display(), err))?;
+            let plugin = *Box::from_raw(create());
+            self.libraries.push(library);
+            plugin
+        };
+
+        self.register(plugin, reserved)
+    }
+
+    // Loads every shared library in `dir`. Failing plugins are skipped.
+    #[cfg(feature = "dynamic-plugins")]
+    pub fn load_directory<P: AsRef<Path>>(
+        &mut self,
+        dir: P,
+        reserved: &[&str],
+    ) -> anyhow::Result<()> {
+        let dir = dir.as_ref();
+        let entries = fs::read_dir(dir)
+            .map_err(|err| anyhow!("unable to read plugin directory {}: {}", dir.display(), err))?;
+
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_some_and(|extension| extension == "so") {
+                if let Err(err) = self.load_library(&path, reserved) {
+                    warn!(error = box_err(err), path = %path.display(), "unable to load plugin");
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn plugins(&self) -> impl Iterator<Item = &dyn Plugin> {
+        self.plugins.iter().map(|plugin| plugin.as_ref())
+    }
+
+    pub fn command_mut(&mut self, name: &str) -> Option<&mut Box<dyn PluginCommand>> {
+        self.commands.get_mut(name)
+    }
+
+    pub fn pretty_printer(&self, type_name: &str) -> Option<&dyn PrettyPrinter> {
+        self.pretty_printers
+            .get(type_name)
+            .map(|pretty_printer| pretty_printer.as_ref())
+    }
+
+    pub fn notify_stop(&mut self, debuggee: &mut Debuggee) {
+        for plugin in &mut self.plugins {
+            plugin.on_stop(debuggee);
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use std::{
+    fmt,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::anyhow;
+use tracing::{Event, Subscriber};
+use tracing_subscriber::{layer::Context, Layer};
+
+use crate::output_log::{format_output, is_output};
+
+// What the output of a command has to contain, or must not.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expectation {
+    // a line matching the pattern, after the line the previous one matched
+    Present(String),
+    // no line matching the pattern anywhere
+    Absent(String),
+}
+
+impl fmt::Display for Expectation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expectation::Present(pattern) => write!(f, "{}", pattern),
+            Expectation::Absent(pattern) => write!(f, "! {}", pattern),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Step {
+    // where the command is in the transcript, starting at 1
+    pub line_number: usize,
+    pub command: String,
+    pub expectations: Vec<Expectation>,
+}
+
+// A recorded session: commands starting with `> `, each followed by patterns its output has to
+// match, `*` matching any run of characters. A pattern starting with `! ` must not match. Lines
+// starting with `#` and empty ones are skipped. A failing command prints `ERROR: <error>`.
+//
+//     > break main
+//     breakpoint set *
+//     > info registers rip
+//     ! ERROR: *
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Transcript {
+    pub steps: Vec<Step>,
+}
+
+impl Transcript {
+    pub fn parse(text: &str) -> anyhow::Result<Self> {
+        let mut steps = Vec::<Step>::new();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim_end();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(command) = line.strip_prefix("> ") {
+                steps.push(Step {
+                    line_number: index + 1,
+                    command: command.trim().to_string(),
+                    expectations: Vec::new(),
+                });
+                continue;
+            }
+
+            let step = steps.last_mut().ok_or(anyhow!(
+                "line {}: expected output before the first command",
+                index + 1
+            ))?;
+            step.expectations.push(match line.strip_prefix("! ") {
+                Some(pattern) => Expectation::Absent(pattern.to_string()),
+                None => Expectation::Present(line.to_string()),
+            });
+        }
+        Ok(Self { steps })
+    }
+
+    pub fn read<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .map_err(|err| anyhow!("unable to read {}: {}", path.display(), err))?;
+        Self::parse(&text)
+    }
+}
+
+// A step whose output didn't live up to one of its expectations.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+    pub line_number: usize,
+    pub command: String,
+    pub expectation: Expectation,
+    pub output: Vec<String>,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let verb = match self.expectation {
+            Expectation::Present(_) => "expected",
+            Expectation::Absent(_) => "unexpected",
+        };
+        write!(
+            f,
+            "line {}: `{}`: {} `{}`",
+            self.line_number, self.command, verb, self.expectation
+        )
+    }
+}
+
+// Checks the output of a step, the mismatches in the order of the expectations.
+pub fn check(step: &Step, output: &[String]) -> Vec<Mismatch> {
+    let mut mismatches = Vec::new();
+    let mut next_line = 0;
+    for expectation in &step.expectations {
+        let matched = match expectation {
+            Expectation::Present(pattern) => {
+                match output[next_line..]
+                    .iter()
+                    .position(|line| matches_pattern(pattern, line))
+                {
+                    Some(position) => {
+                        next_line += position + 1;
+                        true
+                    }
+                    None => false,
+                }
+            }
+            Expectation::Absent(pattern) => {
+                !output.iter().any(|line| matches_pattern(pattern, line))
+            }
+        };
+        if !matched {
+            mismatches.push(Mismatch {
+                line_number: step.line_number,
+                command: step.command.clone(),
+                expectation: expectation.clone(),
+                output: output.to_vec(),
+            });
+        }
+    }
+    mismatches
+}
+
+// Whether all of `line` matches `pattern`, where `*` stands for any run of characters.
+pub fn matches_pattern(pattern: &str, line: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = line.strip_prefix(first) else {
+        return false;
+    };
+    let mut parts = parts.collect::<Vec<_>>();
+    let Some(last) = parts.pop() else {
+        // no `*` at all
+        return rest.is_empty();
+    };
+    for part in parts {
+        match rest.find(part) {
+            Some(at) => rest = &rest[at + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+// A layer collecting the lines the debugger prints while a step runs.
+#[derive(Debug, Clone, Default)]
+pub struct OutputCapture {
+    lines: Arc<Mutex<Vec<String>>>,
+}
+
+impl OutputCapture {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&self, line: String) {
+        self.lines
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .push(line);
+    }
+
+    pub fn take(&self) -> Vec<String> {
+        std::mem::take(&mut *self.lines.lock().unwrap_or_else(|err| err.into_inner()))
+    }
+}
+
+impl<S: Subscriber> Layer<S> for OutputCapture {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        if is_output(event.metadata()) {
+            self.push(format_output(event));
+        }
+    }
+}
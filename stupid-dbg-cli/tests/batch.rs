@@ -0,0 +1,160 @@
+use std::{fs, time::Duration};
+
+use nix::sys::signal::Signal;
+use stupid_dbg_cli::debugger::{BatchOutcome, CommandExecutionResult, Debugger};
+use stupid_dbg_core::{
+    elf::ElfFile,
+    verdict::{Verdict, VerdictReport},
+};
+use tracing_subscriber::layer::SubscriberExt as _;
+
+fn run_script(script: &str, timeout: Duration) -> BatchOutcome {
+    run_script_with(&[], script, timeout)
+}
+
+fn start_script(commands: &[&str], script: &str) -> Debugger {
+    let mut debugger = Debugger::new();
+    for command in commands {
+        match debugger.repl_line(command) {
+            CommandExecutionResult::Continue(result) => result.unwrap(),
+            CommandExecutionResult::Quit(_) => panic!("unexpected quit"),
+        }
+    }
+    match debugger.repl_line(&format!("run sh -c '{}'", script)) {
+        CommandExecutionResult::Continue(result) => result.unwrap(),
+        CommandExecutionResult::Quit(_) => panic!("unexpected quit"),
+    }
+    debugger
+}
+
+// Runs `commands` before starting the script.
+fn run_script_with(commands: &[&str], script: &str, timeout: Duration) -> BatchOutcome {
+    start_script(commands, script)
+        .run_to_completion(Some(timeout))
+        .unwrap()
+}
+
+fn verdict_of(script: &str, timeout: Duration) -> VerdictReport {
+    start_script(&[], script).verdict(Some(timeout)).unwrap()
+}
+
+#[test]
+fn outcomes_become_exit_statuses() {
+    let outcome = run_script("exit 3", Duration::from_secs(10));
+    assert_eq!(outcome, BatchOutcome::Exited(3));
+    assert_eq!(outcome.exit_status(), 3);
+
+    let outcome = run_script("kill -SEGV $$", Duration::from_secs(10));
+    assert_eq!(outcome, BatchOutcome::Terminated(Signal::SIGSEGV));
+    assert_eq!(outcome.exit_status(), 139);
+
+    let outcome = run_script("sleep 10", Duration::from_millis(100));
+    assert_eq!(outcome, BatchOutcome::TimedOut);
+    assert_eq!(outcome.exit_status(), 124);
+}
+
+#[test]
+fn environment_applies_to_the_next_run() {
+    let outcome = run_script_with(
+        &["set environment STUPID_DBG_STATUS=7"],
+        "exit $STUPID_DBG_STATUS",
+        Duration::from_secs(10),
+    );
+    assert_eq!(outcome, BatchOutcome::Exited(7));
+
+    let outcome = run_script_with(
+        &[
+            "set environment STUPID_DBG_STATUS=7",
+            "unset environment STUPID_DBG_STATUS",
+        ],
+        "exit ${STUPID_DBG_STATUS:-5}",
+        Duration::from_secs(10),
+    );
+    assert_eq!(outcome, BatchOutcome::Exited(5));
+}
+
+#[test]
+fn stopping_at_entry_runs_to_completion_from_there() {
+    let path = std::env::temp_dir().join(format!("stupid-dbg-entry-{}.txt", std::process::id()));
+    _ = fs::remove_file(&path);
+
+    let mut debugger = Debugger::new();
+    let subscriber = tracing_subscriber::registry().with(debugger.output_log().layer());
+    tracing::subscriber::with_default(subscriber, || {
+        for line in [
+            "run --stop-at-entry sh -c 'exit 4'",
+            &format!("set logging file {}", path.to_str().unwrap()),
+            "set logging on",
+            "info proc",
+            "info mappings",
+            "register read rip",
+            "set logging off",
+        ] {
+            match debugger.repl_line(line) {
+                CommandExecutionResult::Continue(result) => result.unwrap(),
+                CommandExecutionResult::Quit(_) => panic!("unexpected quit"),
+            }
+        }
+    });
+    let logged = fs::read_to_string(&path).unwrap();
+    _ = fs::remove_file(&path);
+
+    let field = |name: &str| {
+        logged
+            .split_whitespace()
+            .find_map(|field| field.strip_prefix(name)?.strip_prefix('='))
+            .unwrap()
+    };
+    let hex = |value: &str| u64::from_str_radix(value.trim_start_matches("0x"), 16).unwrap();
+    let exe = field("exe");
+    // the first mapping of the executable is its link base plus the load bias
+    let first_mapping = logged
+        .lines()
+        .find(|line| line.contains(" offset=0x0 ") && line.ends_with(&format!(" of {}", exe)))
+        .unwrap();
+    let start = hex(first_mapping
+        .split_whitespace()
+        .find_map(|field| field.strip_prefix("start="))
+        .unwrap());
+    let elf = ElfFile::read(exe).unwrap();
+    let bytes = fs::read(exe).unwrap();
+    let e_entry = u64::from_le_bytes(bytes[24..32].try_into().unwrap());
+    assert_eq!(
+        hex(field("register_value")),
+        e_entry + (start - elf.link_base())
+    );
+
+    let outcome = debugger
+        .run_to_completion(Some(Duration::from_secs(10)))
+        .unwrap();
+    assert_eq!(outcome, BatchOutcome::Exited(4));
+}
+
+#[test]
+fn verdicts_have_exit_codes_of_their_own() {
+    let report = verdict_of("exit 3", Duration::from_secs(10));
+    assert_eq!(report.verdict, Verdict::Exited);
+    assert_eq!(report.verdict.exit_code(), 0);
+    assert_eq!(report.exit_code, Some(3));
+    assert!(report.threads.is_empty());
+
+    let report = verdict_of("kill -SEGV $$", Duration::from_secs(10));
+    assert_eq!(report.verdict, Verdict::Crashed);
+    assert_eq!(report.verdict.exit_code(), 2);
+    assert_eq!(report.signal.as_deref(), Some("SIGSEGV"));
+    assert_eq!(report.crashed_thread, Some(report.pid));
+    let crashed = report
+        .threads
+        .iter()
+        .find(|thread| thread.tid == report.pid)
+        .unwrap();
+    assert!(!crashed.frames.is_empty());
+
+    let report = verdict_of("sleep 10", Duration::from_millis(100));
+    assert_eq!(report.verdict, Verdict::TimedOut);
+    assert_eq!(report.verdict.exit_code(), 3);
+    assert!(!report.threads.is_empty());
+
+    let json = report.to_json().unwrap();
+    assert!(json.contains("\"verdict\": \"timed_out\""));
+}
@@ -0,0 +1,75 @@
+use std::{fs, io};
+
+use stupid_dbg_cli::{
+    child_output::{ChildOutputLog, OutputStream},
+    debugger::{CommandExecutionResult, Debugger},
+};
+
+fn run(debugger: &mut Debugger, line: &str) {
+    match debugger.repl_line(line) {
+        CommandExecutionResult::Continue(result) => result.unwrap(),
+        CommandExecutionResult::Quit(_) => panic!("unexpected quit"),
+    }
+}
+
+fn log_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!(
+        "stupid-dbg-child-{}-{}.txt",
+        name,
+        std::process::id()
+    ))
+}
+
+#[test]
+fn output_is_logged_per_line_with_its_stream() {
+    let path = log_path("tee");
+    _ = fs::remove_file(&path);
+
+    let log = ChildOutputLog::new();
+    log.open(&path).unwrap();
+    log.tee(
+        OutputStream::Stdout,
+        &b"first\nsecond\nno newline"[..],
+        io::sink(),
+    )
+    .join()
+    .unwrap();
+    log.tee(OutputStream::Stderr, &b"oops\n"[..], io::sink())
+        .join()
+        .unwrap();
+    log.close();
+    log.tee(OutputStream::Stdout, &b"not logged\n"[..], io::sink())
+        .join()
+        .unwrap();
+
+    let logged = fs::read_to_string(&path).unwrap();
+    _ = fs::remove_file(&path);
+    let lines = logged
+        .lines()
+        .map(|line| line.split_once(' ').unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        lines.iter().map(|(_, line)| *line).collect::<Vec<_>>(),
+        [
+            "stdout: first",
+            "stdout: second",
+            "stdout: no newline",
+            "stderr: oops"
+        ]
+    );
+    assert!(lines.iter().all(|(time, _)| time.parse::<f64>().is_ok()));
+}
+
+#[test]
+fn set_child_output_log_without_path_turns_it_off() {
+    let path = log_path("set");
+    let mut debugger = Debugger::new();
+    run(
+        &mut debugger,
+        &format!("set child-output-log {}", path.to_str().unwrap()),
+    );
+    assert_eq!(debugger.child_output_log().path(), Some(path.clone()));
+    run(&mut debugger, "set child-output-log");
+    assert!(!debugger.child_output_log().is_open());
+    _ = fs::remove_file(&path);
+}
@@ -0,0 +1,40 @@
+use std::{cell::RefCell, rc::Rc};
+
+use stupid_dbg_cli::diagnostics::DiagnosticFilter;
+use tracing_subscriber::filter::LevelFilter;
+
+#[test]
+fn changes_are_reloaded() {
+    let reloaded = Rc::new(RefCell::new(Vec::new()));
+    let mut diagnostics = DiagnosticFilter::new(LevelFilter::INFO, {
+        let reloaded = reloaded.clone();
+        move |filter| {
+            reloaded.borrow_mut().push(filter.to_string());
+            Ok(())
+        }
+    });
+
+    diagnostics.set("ptrace", LevelFilter::DEBUG).unwrap();
+    let filter = reloaded.borrow().last().unwrap().clone();
+    assert!(filter.contains("stupid_dbg_core::tracer=debug"));
+    assert!(filter.contains("stupid_dbg_core::debuggee=debug"));
+    assert!(filter.contains("info"));
+    assert!(filter.split(',').any(|directive| directive == "info"));
+
+    diagnostics.set("all", LevelFilter::WARN).unwrap();
+    assert_eq!(diagnostics.default_level(), LevelFilter::WARN);
+    assert_eq!(diagnostics.directives().len(), 2);
+
+    assert!(diagnostics
+        .set("no such target", LevelFilter::DEBUG)
+        .is_err());
+    assert_eq!(reloaded.borrow().len(), 2);
+}
+
+#[test]
+fn failed_reloads_are_rolled_back() {
+    let mut diagnostics =
+        DiagnosticFilter::new(LevelFilter::INFO, |_| Err(anyhow::anyhow!("gone")));
+    assert!(diagnostics.set("core", LevelFilter::TRACE).is_err());
+    assert!(diagnostics.directives().is_empty());
+}
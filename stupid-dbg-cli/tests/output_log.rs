@@ -0,0 +1,37 @@
+use std::fs;
+
+use stupid_dbg_cli::debugger::{CommandExecutionResult, Debugger};
+use tracing_subscriber::layer::SubscriberExt as _;
+
+fn run(debugger: &mut Debugger, line: &str) {
+    match debugger.repl_line(line) {
+        CommandExecutionResult::Continue(result) => result.unwrap(),
+        CommandExecutionResult::Quit(_) => panic!("unexpected quit"),
+    }
+}
+
+#[test]
+fn command_output_is_logged() {
+    let path = std::env::temp_dir().join(format!("stupid-dbg-output-{}.txt", std::process::id()));
+    _ = fs::remove_file(&path);
+
+    let mut debugger = Debugger::new();
+    let subscriber = tracing_subscriber::registry().with(debugger.output_log().layer());
+    tracing::subscriber::with_default(subscriber, || {
+        run(&mut debugger, "show directories");
+        run(
+            &mut debugger,
+            &format!("set logging file {}", path.to_str().unwrap()),
+        );
+        run(&mut debugger, "set logging on");
+        run(&mut debugger, "show directories");
+        run(&mut debugger, "set logging off");
+        run(&mut debugger, "show directories");
+    });
+
+    let logged = fs::read_to_string(&path).unwrap();
+    _ = fs::remove_file(&path);
+    assert!(logged.starts_with("logging on to "));
+    assert_eq!(logged.matches("no source directories").count(), 1);
+    assert!(!debugger.output_log().is_enabled());
+}
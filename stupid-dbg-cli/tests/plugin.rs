@@ -0,0 +1,127 @@
+use std::{cell::RefCell, rc::Rc};
+
+use nonempty::NonEmpty;
+use stupid_dbg_cli::{
+    debugger::{CommandExecutionResult, Debugger},
+    plugin::{Plugin, PluginCommand, PrettyPrinter},
+};
+use stupid_dbg_core::{
+    debuggee::{self, Debuggee},
+    launch::LaunchSpec,
+    virt_addr::VirtAddr,
+};
+
+struct Echo {
+    name: &'static str,
+    calls: Rc<RefCell<Vec<Vec<String>>>>,
+}
+
+impl PluginCommand for Echo {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn execute(&mut self, debuggee: Option<&mut Debuggee>, args: &[String]) -> anyhow::Result<()> {
+        assert!(debuggee.is_none());
+        self.calls.borrow_mut().push(args.to_vec());
+        Ok(())
+    }
+}
+
+struct Hex;
+
+impl PrettyPrinter for Hex {
+    fn type_name(&self) -> &str {
+        "bytes"
+    }
+
+    fn format(&self, debuggee: &Debuggee, address: VirtAddr) -> anyhow::Result<String> {
+        let bytes = debuggee.read_memory(address, 2)?;
+        Ok(bytes.iter().map(|byte| format!("{:02x}", byte)).collect())
+    }
+}
+
+struct TestPlugin {
+    command: &'static str,
+    calls: Rc<RefCell<Vec<Vec<String>>>>,
+}
+
+impl Plugin for TestPlugin {
+    fn name(&self) -> &str {
+        "test"
+    }
+
+    fn commands(&mut self) -> Vec<Box<dyn PluginCommand>> {
+        vec![Box::new(Echo {
+            name: self.command,
+            calls: self.calls.clone(),
+        })]
+    }
+
+    fn pretty_printers(&mut self) -> Vec<Box<dyn PrettyPrinter>> {
+        vec![Box::new(Hex)]
+    }
+}
+
+#[test]
+fn plugin_commands_are_dispatched() {
+    let calls = Rc::new(RefCell::new(Vec::new()));
+    let mut debugger = Debugger::new();
+    debugger
+        .register_plugin(Box::new(TestPlugin {
+            command: "echo",
+            calls: calls.clone(),
+        }))
+        .unwrap();
+
+    let CommandExecutionResult::Continue(result) = debugger.repl_line("echo a 'b c'") else {
+        panic!("plugin command must not quit");
+    };
+    result.unwrap();
+    assert_eq!(
+        *calls.borrow(),
+        vec![vec!["a".to_string(), "b c".to_string()]]
+    );
+}
+
+#[test]
+fn print_uses_plugin_pretty_printers() {
+    let mut debugger = Debugger::new();
+    debugger
+        .register_plugin(Box::new(TestPlugin {
+            command: "echo",
+            calls: Rc::default(),
+        }))
+        .unwrap();
+    assert!(debugger.plugins().pretty_printer("std::string").is_some());
+
+    let debuggee = Debuggee::new(debuggee::Config::SpawnChild(LaunchSpec::new(
+        NonEmpty::new("true".to_string()),
+    )))
+    .unwrap();
+    let rsp = VirtAddr::new(debuggee.registers().unwrap().user_regs().rsp);
+    let pretty_printer = debugger.plugins().pretty_printer("bytes").unwrap();
+    // argc
+    assert_eq!(pretty_printer.format(&debuggee, rsp).unwrap(), "0100");
+
+    let CommandExecutionResult::Continue(result) = debugger.repl_line("print --type bytes $rsp")
+    else {
+        panic!("print must not quit");
+    };
+    assert!(result.is_err(), "there's no debuggee");
+    let CommandExecutionResult::Continue(result) = debugger.repl_line("print --type none 0") else {
+        panic!("print must not quit");
+    };
+    assert!(result.is_err());
+}
+
+#[test]
+fn plugin_commands_cannot_shadow_builtins() {
+    let mut debugger = Debugger::new();
+    assert!(debugger
+        .register_plugin(Box::new(TestPlugin {
+            command: "continue",
+            calls: Rc::default(),
+        }))
+        .is_err());
+}
@@ -0,0 +1,59 @@
+use stupid_dbg_cli::{
+    debugger::Debugger,
+    replay::{matches_pattern, Expectation, Transcript},
+};
+
+#[test]
+fn patterns_match_whole_lines() {
+    assert!(matches_pattern("breakpoint 1", "breakpoint 1"));
+    assert!(!matches_pattern("breakpoint 1", "breakpoint 12"));
+    assert!(matches_pattern(
+        "breakpoint * at 0x*",
+        "breakpoint 3 at 0x401000"
+    ));
+    assert!(matches_pattern("*", ""));
+    assert!(matches_pattern("a*a", "aa"));
+    assert!(!matches_pattern("a*a", "a"));
+    assert!(!matches_pattern("*at 0x*", "breakpoint 3"));
+}
+
+#[test]
+fn transcripts_are_parsed() {
+    let transcript =
+        Transcript::parse("# a comment\n\n> break main\nbreakpoint *\n> continue\n! ERROR: *\n")
+            .unwrap();
+    assert_eq!(transcript.steps.len(), 2);
+    assert_eq!(transcript.steps[0].line_number, 3);
+    assert_eq!(transcript.steps[0].command, "break main");
+    assert_eq!(
+        transcript.steps[1].expectations,
+        vec![Expectation::Absent("ERROR: *".to_string())]
+    );
+
+    assert!(Transcript::parse("output before any command\n").is_err());
+}
+
+#[test]
+fn replay_reports_mismatches() {
+    let transcript = Transcript::parse(
+        "> show directories\n\
+         no source directories\n\
+         > show directories\n\
+         ! *source*\n\
+         > no-such-command\n\
+         ERROR: *\n",
+    )
+    .unwrap();
+
+    let mismatches = Debugger::new().replay(&transcript);
+    assert_eq!(mismatches.len(), 1);
+    assert_eq!(mismatches[0].line_number, 3);
+    assert_eq!(
+        mismatches[0].expectation,
+        Expectation::Absent("*source*".to_string())
+    );
+    assert!(mismatches[0]
+        .output
+        .iter()
+        .any(|line| line.contains("no source directories")));
+}
@@ -3,7 +3,7 @@
     let
       rustFlake = inputs.flake-lang.lib.${system}.rustFlake {
         src = ./.;
-        crateName = "stupid-dbg";
+        crateName = "stupid-dbg-core";
         devShellHook = config.settings.defaultShellHook;
         extraSources = [
           config.packages.helper-proc-macros-rust-src
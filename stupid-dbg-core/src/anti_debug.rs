@@ -0,0 +1,67 @@
+use std::fmt;
+
+use nix::unistd::Pid;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AntiDebugConfig {
+    // stop at every syscall of the debuggee to look for checks
+    pub detect: bool,
+    // have ptrace(PTRACE_TRACEME) succeed as if nothing were tracing the debuggee
+    pub spoof_traceme: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AntiDebugAttempt {
+    // fails for a process that is traced already
+    TraceMe { thread: Pid, spoofed: bool },
+    // status files of /proc have a TracerPid line
+    StatusRead { thread: Pid, path: String },
+    // a SIGTRAP the debugger didn't cause, debuggers tend to swallow them
+    SelfTrap { thread: Pid, sender: Option<Pid> },
+}
+
+impl fmt::Display for AntiDebugAttempt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AntiDebugAttempt::TraceMe {
+                thread,
+                spoofed: false,
+            } => write!(f, "thread {} called ptrace(PTRACE_TRACEME)", thread),
+            AntiDebugAttempt::TraceMe {
+                thread,
+                spoofed: true,
+            } => write!(
+                f,
+                "thread {} called ptrace(PTRACE_TRACEME), faked success",
+                thread
+            ),
+            AntiDebugAttempt::StatusRead { thread, path } => {
+                write!(f, "thread {} opened {}", thread, path)
+            }
+            AntiDebugAttempt::SelfTrap {
+                thread,
+                sender: Some(sender),
+            } => write!(f, "thread {} got a SIGTRAP from pid {}", thread, sender),
+            AntiDebugAttempt::SelfTrap {
+                thread,
+                sender: None,
+            } => write!(f, "thread {} trapped outside any breakpoint", thread),
+        }
+    }
+}
+
+// /proc/self/status, /proc/<pid>/status, /proc/thread-self/status and the ones of tasks.
+pub fn is_status_path(path: &str) -> bool {
+    let is_id =
+        |component: &str| !component.is_empty() && component.chars().all(|c| c.is_ascii_digit());
+
+    let Some(rest) = path.strip_prefix("/proc/") else {
+        return false;
+    };
+    match rest.split('/').collect::<Vec<_>>()[..] {
+        [process, "status"] => process == "self" || process == "thread-self" || is_id(process),
+        [process, "task", task, "status"] => (process == "self" || is_id(process)) && is_id(task),
+        _ => false,
+    }
+}
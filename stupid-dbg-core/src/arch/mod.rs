@@ -0,0 +1,147 @@
+use std::{fmt, fs::File, io::Read};
+
+use anyhow::anyhow;
+use nix::unistd::Pid;
+
+#[cfg(target_arch = "x86_64")]
+mod x86_64;
+
+#[cfg(target_arch = "x86_64")]
+pub(crate) use x86_64::{regs_from_i386, I386_REGS_SIZE};
+#[cfg(target_arch = "x86_64")]
+pub use x86_64::{I386, X86_64};
+
+const ELFCLASS32: u8 = 1;
+const ELFCLASS64: u8 = 2;
+const EM_386: u16 = 3;
+const EM_X86_64: u16 = 62;
+const EM_AARCH64: u16 = 183;
+const EM_RISCV: u16 = 243;
+
+// No instruction is longer than this on any of the architectures.
+pub const MAX_INSTRUCTION_LENGTH: usize = 15;
+
+// Width of pointers in the debuggee, which differs from the debugger for 32-bit processes
+// running in compat mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PointerWidth {
+    Bits32,
+    Bits64,
+}
+
+impl PointerWidth {
+    pub fn size(self) -> usize {
+        match self {
+            PointerWidth::Bits32 => 4,
+            PointerWidth::Bits64 => 8,
+        }
+    }
+
+    // `bytes` must be exactly `size()` bytes long.
+    pub fn read_pointer(self, bytes: &[u8]) -> u64 {
+        match self {
+            PointerWidth::Bits32 => u32::from_ne_bytes(bytes.try_into().unwrap()) as u64,
+            PointerWidth::Bits64 => u64::from_ne_bytes(bytes.try_into().unwrap()),
+        }
+    }
+}
+
+// Where a function finds its return address right at its entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReturnAddress {
+    Register(u64),
+    // pushed by the call, the address it was pushed to
+    Stack(u64),
+}
+
+// Everything the engine needs to know about the instruction set and ABI of the debuggee.
+// Registers are always handled in the layout PTRACE_GETREGS has for the debugger, which for a
+// 32-bit process on a 64-bit kernel is the 64-bit one.
+pub trait Arch: fmt::Debug + Sync {
+    fn name(&self) -> &'static str;
+    fn pointer_width(&self) -> PointerWidth;
+
+    fn breakpoint_instruction(&self) -> &'static [u8];
+    // How far past the breakpoint the pc is when the trap is reported.
+    fn breakpoint_pc_offset(&self) -> u64;
+    fn syscall_instruction(&self) -> &'static [u8];
+
+    fn pc(&self, regs: &libc::user_regs_struct) -> u64;
+    fn set_pc(&self, regs: &mut libc::user_regs_struct, pc: u64);
+    fn stack_pointer(&self, regs: &libc::user_regs_struct) -> u64;
+
+    // Syscall numbers are the ones of the debuggee's ABI.
+    fn syscall_number(&self, regs: &libc::user_regs_struct) -> u64;
+    fn syscall_return_value(&self, regs: &libc::user_regs_struct) -> i64;
+    fn set_syscall_return_value(&self, regs: &mut libc::user_regs_struct, value: i64);
+    // Only meaningful at a syscall entry stop, some of them are clobbered by the return value.
+    fn syscall_args(&self, regs: &libc::user_regs_struct) -> [u64; 6];
+    // Tells a syscall entry stop from an exit stop.
+    fn is_syscall_entry(&self, regs: &libc::user_regs_struct) -> bool;
+    fn prepare_syscall(&self, regs: &mut libc::user_regs_struct, number: u64, args: &[u64]);
+    // Has the syscall of an entry stop skipped, and run again by its instruction once resumed.
+    fn rewind_syscall(&self, regs: &mut libc::user_regs_struct);
+
+    // Integer arguments of a function call at its first instruction, None for ABIs passing them
+    // on the stack.
+    fn call_args(&self, regs: &libc::user_regs_struct) -> Option<[u64; 6]>;
+    fn call_return_value(&self, regs: &libc::user_regs_struct) -> u64;
+    fn return_address(&self, regs: &libc::user_regs_struct) -> ReturnAddress;
+    fn frame_pointer(&self, regs: &libc::user_regs_struct) -> u64;
+    // Where the frame record `frame_pointer` points into keeps the caller's frame pointer and the
+    // return address.
+    fn frame_record(&self, frame_pointer: u64) -> (u64, u64);
+    // Length of the call `insn` starts with, None if it's anything else or cut short.
+    fn call_length(&self, insn: &[u8]) -> Option<usize>;
+}
+
+// The architecture the debugger itself runs on.
+pub fn native() -> &'static dyn Arch {
+    #[cfg(target_arch = "x86_64")]
+    return &X86_64;
+}
+
+// Picks the architecture from the ELF header of the executable a process is running. Only
+// architectures the debugger can trace from its own one are known.
+pub fn from_elf_header(header: &[u8]) -> anyhow::Result<&'static dyn Arch> {
+    if header.len() < 20 || header[..4] != *b"\x7fELF" {
+        Err(anyhow!("not an ELF header"))?;
+    }
+    let class = header[4];
+    let machine = u16::from_ne_bytes([header[18], header[19]]);
+
+    let arch: Option<&'static dyn Arch> = match (class, machine) {
+        #[cfg(target_arch = "x86_64")]
+        (ELFCLASS64, EM_X86_64) => Some(&X86_64),
+        #[cfg(target_arch = "x86_64")]
+        (ELFCLASS32, EM_386) => Some(&I386),
+        _ => None,
+    };
+
+    arch.ok_or_else(|| {
+        let name = match machine {
+            EM_386 => "i386",
+            EM_X86_64 => "x86_64",
+            EM_AARCH64 => "aarch64",
+            EM_RISCV => "riscv",
+            _ => "unknown",
+        };
+        let bits = if class == ELFCLASS32 { 32 } else { 64 };
+        anyhow!(
+            "unable to debug {}-bit {} (machine {}) processes from a {} debugger",
+            bits,
+            name,
+            machine,
+            native().name()
+        )
+    })
+}
+
+pub fn detect(pid: Pid) -> anyhow::Result<&'static dyn Arch> {
+    let mut header = [0u8; 20];
+    File::open(format!("/proc/{}/exe", pid))
+        .and_then(|mut exe| exe.read_exact(&mut header))
+        .map_err(|err| anyhow!("unable to read executable of {}: {}", pid, err))?;
+
+    from_elf_header(&header)
+}
@@ -0,0 +1,306 @@
+use super::{Arch, PointerWidth, ReturnAddress};
+
+#[derive(Debug)]
+pub struct X86_64;
+
+// 32-bit processes in compat mode.
+#[derive(Debug)]
+pub struct I386;
+
+// int3
+const BREAKPOINT_INSTRUCTION: [u8; 1] = [0xcc];
+// syscall
+const SYSCALL_INSTRUCTION: [u8; 2] = [0x0f, 0x05];
+// int 0x80
+const I386_SYSCALL_INSTRUCTION: [u8; 2] = [0xcd, 0x80];
+
+impl Arch for X86_64 {
+    fn name(&self) -> &'static str {
+        "x86_64"
+    }
+
+    fn pointer_width(&self) -> PointerWidth {
+        PointerWidth::Bits64
+    }
+
+    fn breakpoint_instruction(&self) -> &'static [u8] {
+        &BREAKPOINT_INSTRUCTION
+    }
+
+    // rip is right after the int3
+    fn breakpoint_pc_offset(&self) -> u64 {
+        1
+    }
+
+    fn syscall_instruction(&self) -> &'static [u8] {
+        &SYSCALL_INSTRUCTION
+    }
+
+    fn pc(&self, regs: &libc::user_regs_struct) -> u64 {
+        regs.rip
+    }
+
+    fn set_pc(&self, regs: &mut libc::user_regs_struct, pc: u64) {
+        regs.rip = pc;
+    }
+
+    fn stack_pointer(&self, regs: &libc::user_regs_struct) -> u64 {
+        regs.rsp
+    }
+
+    fn syscall_number(&self, regs: &libc::user_regs_struct) -> u64 {
+        regs.orig_rax
+    }
+
+    fn syscall_return_value(&self, regs: &libc::user_regs_struct) -> i64 {
+        regs.rax as i64
+    }
+
+    fn set_syscall_return_value(&self, regs: &mut libc::user_regs_struct, value: i64) {
+        regs.rax = value as u64;
+    }
+
+    fn syscall_args(&self, regs: &libc::user_regs_struct) -> [u64; 6] {
+        [regs.rdi, regs.rsi, regs.rdx, regs.r10, regs.r8, regs.r9]
+    }
+
+    // Syscall entry and exit stops look the same, but the kernel sets rax to -ENOSYS on entry.
+    fn is_syscall_entry(&self, regs: &libc::user_regs_struct) -> bool {
+        regs.rax as i64 == -(libc::ENOSYS as i64)
+    }
+
+    fn prepare_syscall(&self, regs: &mut libc::user_regs_struct, number: u64, args: &[u64]) {
+        assert!(args.len() <= 6, "syscalls take at most 6 arguments");
+
+        regs.rax = number;
+        // keep the kernel from treating this as a restarted syscall
+        regs.orig_rax = u64::MAX;
+        [
+            &mut regs.rdi,
+            &mut regs.rsi,
+            &mut regs.rdx,
+            &mut regs.r10,
+            &mut regs.r8,
+            &mut regs.r9,
+        ]
+        .into_iter()
+        .zip(args)
+        .for_each(|(reg, arg)| *reg = *arg);
+    }
+
+    // The kernel skips syscall -1, rax is set back to the number the instruction left there.
+    fn rewind_syscall(&self, regs: &mut libc::user_regs_struct) {
+        regs.rax = regs.orig_rax;
+        regs.orig_rax = u64::MAX;
+        regs.rip -= SYSCALL_INSTRUCTION.len() as u64;
+    }
+
+    fn call_args(&self, regs: &libc::user_regs_struct) -> Option<[u64; 6]> {
+        Some([regs.rdi, regs.rsi, regs.rdx, regs.rcx, regs.r8, regs.r9])
+    }
+
+    fn call_return_value(&self, regs: &libc::user_regs_struct) -> u64 {
+        regs.rax
+    }
+
+    fn return_address(&self, regs: &libc::user_regs_struct) -> ReturnAddress {
+        ReturnAddress::Stack(regs.rsp)
+    }
+
+    fn frame_pointer(&self, regs: &libc::user_regs_struct) -> u64 {
+        regs.rbp
+    }
+
+    // push rbp; mov rbp, rsp
+    fn frame_record(&self, frame_pointer: u64) -> (u64, u64) {
+        (frame_pointer, frame_pointer.wrapping_add(8))
+    }
+
+    fn call_length(&self, insn: &[u8]) -> Option<usize> {
+        call_length(insn, true)
+    }
+}
+
+impl Arch for I386 {
+    fn name(&self) -> &'static str {
+        "i386"
+    }
+
+    fn pointer_width(&self) -> PointerWidth {
+        PointerWidth::Bits32
+    }
+
+    fn breakpoint_instruction(&self) -> &'static [u8] {
+        &BREAKPOINT_INSTRUCTION
+    }
+
+    fn breakpoint_pc_offset(&self) -> u64 {
+        1
+    }
+
+    fn syscall_instruction(&self) -> &'static [u8] {
+        &I386_SYSCALL_INSTRUCTION
+    }
+
+    fn pc(&self, regs: &libc::user_regs_struct) -> u64 {
+        X86_64.pc(regs)
+    }
+
+    fn set_pc(&self, regs: &mut libc::user_regs_struct, pc: u64) {
+        X86_64.set_pc(regs, pc)
+    }
+
+    fn stack_pointer(&self, regs: &libc::user_regs_struct) -> u64 {
+        X86_64.stack_pointer(regs)
+    }
+
+    fn syscall_number(&self, regs: &libc::user_regs_struct) -> u64 {
+        X86_64.syscall_number(regs)
+    }
+
+    fn syscall_return_value(&self, regs: &libc::user_regs_struct) -> i64 {
+        regs.rax as i32 as i64
+    }
+
+    fn set_syscall_return_value(&self, regs: &mut libc::user_regs_struct, value: i64) {
+        regs.rax = value as i32 as u32 as u64;
+    }
+
+    fn syscall_args(&self, regs: &libc::user_regs_struct) -> [u64; 6] {
+        [regs.rbx, regs.rcx, regs.rdx, regs.rsi, regs.rdi, regs.rbp].map(|arg| arg as u32 as u64)
+    }
+
+    fn is_syscall_entry(&self, regs: &libc::user_regs_struct) -> bool {
+        X86_64.is_syscall_entry(regs)
+    }
+
+    fn prepare_syscall(&self, regs: &mut libc::user_regs_struct, number: u64, args: &[u64]) {
+        assert!(args.len() <= 6, "syscalls take at most 6 arguments");
+
+        regs.rax = number;
+        regs.orig_rax = u64::MAX;
+        [
+            &mut regs.rbx,
+            &mut regs.rcx,
+            &mut regs.rdx,
+            &mut regs.rsi,
+            &mut regs.rdi,
+            &mut regs.rbp,
+        ]
+        .into_iter()
+        .zip(args)
+        .for_each(|(reg, arg)| *reg = *arg);
+    }
+
+    fn rewind_syscall(&self, regs: &mut libc::user_regs_struct) {
+        regs.rax = regs.orig_rax;
+        regs.orig_rax = u64::MAX;
+        regs.rip -= I386_SYSCALL_INSTRUCTION.len() as u64;
+    }
+
+    // cdecl passes everything on the stack
+    fn call_args(&self, _regs: &libc::user_regs_struct) -> Option<[u64; 6]> {
+        None
+    }
+
+    fn call_return_value(&self, regs: &libc::user_regs_struct) -> u64 {
+        regs.rax as u32 as u64
+    }
+
+    fn return_address(&self, regs: &libc::user_regs_struct) -> ReturnAddress {
+        X86_64.return_address(regs)
+    }
+
+    fn frame_pointer(&self, regs: &libc::user_regs_struct) -> u64 {
+        regs.rbp as u32 as u64
+    }
+
+    fn frame_record(&self, frame_pointer: u64) -> (u64, u64) {
+        (frame_pointer, frame_pointer.wrapping_add(4))
+    }
+
+    // 0x40 to 0x4f are inc and dec, not REX prefixes
+    fn call_length(&self, insn: &[u8]) -> Option<usize> {
+        call_length(insn, false)
+    }
+}
+
+// Near calls, e8 with a 32-bit displacement and ff /2 with any operand, after legacy and REX
+// prefixes. Addressing is assumed to be 32 or 64-bit.
+fn call_length(insn: &[u8], rex: bool) -> Option<usize> {
+    let mut length = insn
+        .iter()
+        .take_while(|byte| {
+            matches!(
+                byte,
+                0x26 | 0x2e | 0x36 | 0x3e | 0x64 | 0x65 | 0x66 | 0x67 | 0xf2 | 0xf3
+            )
+        })
+        .count();
+    if rex && insn.get(length).is_some_and(|byte| byte & 0xf0 == 0x40) {
+        length += 1;
+    }
+
+    let length = match insn.get(length)? {
+        0xe8 => length + 5,
+        0xff => {
+            let modrm = *insn.get(length + 1)?;
+            if (modrm >> 3) & 0b111 != 2 {
+                return None;
+            }
+            length + 2 + operand_length(modrm, insn.get(length + 2).copied())?
+        }
+        _ => return None,
+    };
+    (length <= insn.len()).then_some(length)
+}
+
+// The SIB byte and displacement following a ModRM byte.
+fn operand_length(modrm: u8, sib: Option<u8>) -> Option<usize> {
+    let (mode, rm) = (modrm >> 6, modrm & 0b111);
+    let sib_length = usize::from(mode != 0b11 && rm == 0b100);
+    let displacement = match mode {
+        // rip-relative, or absolute in 32-bit mode
+        0b00 if rm == 0b101 => 4,
+        // no base register
+        0b00 if rm == 0b100 && sib? & 0b111 == 0b101 => 4,
+        0b01 => 1,
+        0b10 => 4,
+        _ => 0,
+    };
+    Some(sib_length + displacement)
+}
+
+// struct user_regs_struct32 in arch/x86/include/asm/user32.h
+pub(crate) const I386_REGS_SIZE: usize = 17 * 4;
+
+// PTRACE_GETREGSET(NT_PRSTATUS) hands out the register layout of the tracee, which for a 32-bit
+// process is the i386 one. Widens it to the layout PTRACE_GETREGS uses for the same process.
+pub(crate) fn regs_from_i386(buf: &[u8]) -> libc::user_regs_struct {
+    assert_eq!(buf.len(), I386_REGS_SIZE, "not an i386 register set");
+
+    let word = |idx: usize| u32::from_ne_bytes(buf[idx * 4..idx * 4 + 4].try_into().unwrap());
+    let zero_extend = |idx: usize| word(idx) as u64;
+    let sign_extend = |idx: usize| word(idx) as i32 as i64 as u64;
+    let segment = |idx: usize| word(idx) as u64 & 0xffff;
+
+    let mut regs: libc::user_regs_struct = unsafe { std::mem::zeroed() };
+    regs.rbx = zero_extend(0);
+    regs.rcx = zero_extend(1);
+    regs.rdx = zero_extend(2);
+    regs.rsi = zero_extend(3);
+    regs.rdi = zero_extend(4);
+    regs.rbp = zero_extend(5);
+    regs.rax = sign_extend(6);
+    regs.ds = segment(7);
+    regs.es = segment(8);
+    regs.fs = segment(9);
+    regs.gs = segment(10);
+    regs.orig_rax = sign_extend(11);
+    regs.rip = zero_extend(12);
+    regs.cs = segment(13);
+    regs.eflags = zero_extend(14);
+    regs.rsp = zero_extend(15);
+    regs.ss = segment(16);
+    regs
+}
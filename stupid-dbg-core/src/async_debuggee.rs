@@ -0,0 +1,92 @@
+use futures_util::{stream, Stream};
+use tokio::signal::unix::{signal, Signal, SignalKind};
+
+use crate::{
+    debuggee::{self, Debuggee, ProcessState},
+    session::{DebugEvent, DebugSession},
+};
+
+// Async counterpart of DebugSession. Instead of blocking in waitpid, stops are picked up with
+// non-blocking waits whenever SIGCHLD is delivered, so a single runtime thread can drive many
+// debuggees alongside other work. Must be created from within a tokio runtime.
+pub struct AsyncDebuggee {
+    session: DebugSession,
+    sigchld: Signal,
+}
+
+impl AsyncDebuggee {
+    pub fn new(config: debuggee::Config) -> anyhow::Result<Self> {
+        Self::from_debuggee(Debuggee::new(config)?)
+    }
+
+    pub fn from_debuggee(debuggee: Debuggee) -> anyhow::Result<Self> {
+        Ok(Self {
+            session: DebugSession::from_debuggee(debuggee),
+            sigchld: signal(SignalKind::child())?,
+        })
+    }
+
+    pub fn debuggee(&self) -> &Debuggee {
+        self.session.debuggee()
+    }
+
+    pub fn debuggee_mut(&mut self) -> &mut Debuggee {
+        self.session.debuggee_mut()
+    }
+
+    pub fn into_debuggee(self) -> Debuggee {
+        self.session.into_debuggee()
+    }
+
+    // Resolves once the debuggee is no longer running.
+    pub async fn wait_for_stop(&mut self) -> anyhow::Result<ProcessState> {
+        loop {
+            // SIGCHLD received between the check and the await is buffered by the signal stream,
+            // so no stop can be missed
+            let debuggee = self.session.debuggee_mut();
+            debuggee.update_process_state(false)?;
+            if !debuggee.process_stop()? {
+                debuggee.resume()?;
+                continue;
+            }
+
+            let process_state = debuggee.process_state();
+            if !matches!(process_state, ProcessState::Running) {
+                return Ok(process_state);
+            }
+
+            self.sigchld.recv().await;
+        }
+    }
+
+    // The signal the debuggee stopped for is delivered, like `DebugSession::next_event` does.
+    pub async fn resume_and_wait(&mut self) -> anyhow::Result<ProcessState> {
+        let debuggee = self.session.debuggee_mut();
+        debuggee.resume_with_signal(debuggee.stop_signal())?;
+        self.wait_for_stop().await
+    }
+
+    pub async fn next_event(&mut self) -> anyhow::Result<Option<DebugEvent>> {
+        loop {
+            if let Some(event) = self.session.pop_pending_event() {
+                return Ok(Some(event));
+            }
+
+            if !self.debuggee().process_state().is_alive() {
+                return Ok(None);
+            }
+
+            self.resume_and_wait().await?;
+            self.session.collect_events();
+        }
+    }
+
+    pub fn events(&mut self) -> impl Stream<Item = anyhow::Result<DebugEvent>> + '_ {
+        stream::unfold(self, |this| async move {
+            this.next_event()
+                .await
+                .transpose()
+                .map(|event| (event, this))
+        })
+    }
+}
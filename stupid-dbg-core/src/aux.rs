@@ -0,0 +1,101 @@
+use std::{mem::MaybeUninit, ptr, slice};
+
+use nix::{errno::Errno, sys::ptrace, unistd::Pid};
+
+pub fn box_err<E>(err: E) -> Box<dyn std::error::Error + 'static>
+where
+    E: Into<Box<dyn std::error::Error + 'static>>,
+{
+    err.into()
+}
+
+pub unsafe fn read_any_from_u8_pointer<T>(from_ptr: *const u8, size: usize) -> T {
+    assert!(size_of::<T>() >= size);
+    let mut ret = MaybeUninit::<T>::zeroed();
+    let ptr = ret.as_mut_ptr().cast::<u8>();
+    from_ptr.copy_to(ptr, size);
+    let ret = ret.assume_init();
+    return ret;
+}
+
+pub unsafe fn as_u8_slice<T: Sized>(value: &T) -> &[u8] {
+    slice::from_raw_parts((value as *const T).cast::<u8>(), size_of::<T>())
+}
+
+pub fn ptrace_get_data<T>(request: ptrace::Request, pid: Pid) -> nix::Result<T> {
+    let mut data = MaybeUninit::<T>::uninit();
+    let res = unsafe {
+        libc::ptrace(
+            request as libc::c_uint,
+            libc::pid_t::from(pid),
+            ptr::null_mut::<T>(),
+            data.as_mut_ptr(),
+        )
+    };
+    Errno::result(res)?;
+    Ok(unsafe { data.assume_init() })
+}
+
+pub fn ptrace_getfpregs(pid: Pid) -> nix::Result<libc::user_fpregs_struct> {
+    ptrace_get_data(ptrace::Request::PTRACE_GETFPREGS, pid)
+}
+
+pub fn ptrace_setfpregs(pid: Pid, fpregs: &libc::user_fpregs_struct) -> nix::Result<()> {
+    let res = unsafe {
+        libc::ptrace(
+            libc::PTRACE_SETFPREGS,
+            libc::pid_t::from(pid),
+            ptr::null_mut::<libc::c_void>(),
+            fpregs as *const libc::user_fpregs_struct,
+        )
+    };
+    Errno::result(res).map(drop)
+}
+
+// Fills `buf` with the register set `note_type` of `pid` and returns how many bytes the kernel
+// wrote.
+pub fn ptrace_getregset(pid: Pid, note_type: i32, buf: &mut [u8]) -> nix::Result<usize> {
+    let mut iov = libc::iovec {
+        iov_base: buf.as_mut_ptr().cast(),
+        iov_len: buf.len(),
+    };
+    let res = unsafe {
+        libc::ptrace(
+            libc::PTRACE_GETREGSET,
+            libc::pid_t::from(pid),
+            note_type as usize as *mut libc::c_void,
+            &mut iov as *mut libc::iovec,
+        )
+    };
+    Errno::result(res)?;
+    Ok(iov.iov_len)
+}
+
+// Sets the register set `note_type` of `pid` to `data`.
+pub fn ptrace_setregset(pid: Pid, note_type: i32, data: &[u8]) -> nix::Result<()> {
+    let mut iov = libc::iovec {
+        iov_base: data.as_ptr() as *mut libc::c_void,
+        iov_len: data.len(),
+    };
+    let res = unsafe {
+        libc::ptrace(
+            libc::PTRACE_SETREGSET,
+            libc::pid_t::from(pid),
+            note_type as usize as *mut libc::c_void,
+            &mut iov as *mut libc::iovec,
+        )
+    };
+    Errno::result(res).map(drop)
+}
+
+pub fn ptrace_listen(pid: Pid) -> nix::Result<()> {
+    let res = unsafe {
+        libc::ptrace(
+            libc::PTRACE_LISTEN,
+            libc::pid_t::from(pid),
+            ptr::null_mut::<libc::c_void>(),
+            ptr::null_mut::<libc::c_void>(),
+        )
+    };
+    Errno::result(res).map(drop)
+}
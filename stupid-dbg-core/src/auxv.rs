@@ -0,0 +1,208 @@
+use std::fs;
+
+use anyhow::anyhow;
+use nix::unistd::Pid;
+
+use crate::arch::PointerWidth;
+
+pub const AT_NULL: u64 = 0;
+pub const AT_PHDR: u64 = 3;
+pub const AT_PHENT: u64 = 4;
+pub const AT_PHNUM: u64 = 5;
+pub const AT_BASE: u64 = 7;
+pub const AT_ENTRY: u64 = 9;
+
+const PT_DYNAMIC: u32 = 2;
+const PT_PHDR: u32 = 6;
+
+const DT_NULL: u64 = 0;
+const DT_DEBUG: u64 = 21;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AuxvEntry {
+    pub key: u64,
+    pub value: u64,
+}
+
+impl AuxvEntry {
+    pub fn name(&self) -> Option<&'static str> {
+        let name = match self.key {
+            0 => "AT_NULL",
+            1 => "AT_IGNORE",
+            2 => "AT_EXECFD",
+            3 => "AT_PHDR",
+            4 => "AT_PHENT",
+            5 => "AT_PHNUM",
+            6 => "AT_PAGESZ",
+            7 => "AT_BASE",
+            8 => "AT_FLAGS",
+            9 => "AT_ENTRY",
+            10 => "AT_NOTELF",
+            11 => "AT_UID",
+            12 => "AT_EUID",
+            13 => "AT_GID",
+            14 => "AT_EGID",
+            15 => "AT_PLATFORM",
+            16 => "AT_HWCAP",
+            17 => "AT_CLKTCK",
+            23 => "AT_SECURE",
+            24 => "AT_BASE_PLATFORM",
+            25 => "AT_RANDOM",
+            26 => "AT_HWCAP2",
+            27 => "AT_RSEQ_FEATURE_SIZE",
+            28 => "AT_RSEQ_ALIGN",
+            29 => "AT_HWCAP3",
+            30 => "AT_HWCAP4",
+            31 => "AT_EXECFN",
+            32 => "AT_SYSINFO",
+            33 => "AT_SYSINFO_EHDR",
+            51 => "AT_MINSIGSTKSZ",
+            _ => return None,
+        };
+        Some(name)
+    }
+}
+
+// The auxiliary vector the kernel passed to the debuggee, terminated by AT_NULL.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Auxv {
+    entries: Vec<AuxvEntry>,
+}
+
+impl Auxv {
+    // Entries are pairs of words as wide as a pointer of the debuggee.
+    pub fn parse(bytes: &[u8], pointer_width: PointerWidth) -> anyhow::Result<Self> {
+        let word = pointer_width.size();
+        if !bytes.len().is_multiple_of(2 * word) {
+            Err(anyhow!("malformed auxv of {} bytes", bytes.len()))?;
+        }
+
+        let entries = bytes
+            .chunks_exact(2 * word)
+            .map(|entry| AuxvEntry {
+                key: pointer_width.read_pointer(&entry[..word]),
+                value: pointer_width.read_pointer(&entry[word..]),
+            })
+            .take_while(|entry| entry.key != AT_NULL)
+            .collect();
+
+        Ok(Self { entries })
+    }
+
+    pub fn read_from_procfs(pid: Pid, pointer_width: PointerWidth) -> anyhow::Result<Self> {
+        let bytes = fs::read(format!("/proc/{}/auxv", pid))
+            .map_err(|err| anyhow!("unable to read auxv of {}: {}", pid, err))?;
+        Self::parse(&bytes, pointer_width)
+    }
+
+    pub fn entries(&self) -> &[AuxvEntry] {
+        &self.entries
+    }
+
+    pub fn get(&self, key: u64) -> Option<u64> {
+        self.entries
+            .iter()
+            .find(|entry| entry.key == key)
+            .map(|entry| entry.value)
+    }
+
+    pub fn entry_point(&self) -> Option<u64> {
+        self.get(AT_ENTRY)
+    }
+
+    // Where the dynamic loader is mapped, zero or missing for static executables.
+    pub fn interpreter_base(&self) -> Option<u64> {
+        self.get(AT_BASE).filter(|base| *base != 0)
+    }
+
+    // Address, entry size and count of the program headers of the executable.
+    pub fn program_headers(&self) -> Option<(u64, u64, u64)> {
+        Some((self.get(AT_PHDR)?, self.get(AT_PHENT)?, self.get(AT_PHNUM)?))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ProgramHeader {
+    pub p_type: u32,
+    pub flags: u32,
+    pub vaddr: u64,
+    pub memsz: u64,
+}
+
+// Only the fields needed to find the load bias and the dynamic section, and the flags, are
+// decoded.
+pub(crate) fn parse_program_headers(
+    bytes: &[u8],
+    entry_size: usize,
+    pointer_width: PointerWidth,
+) -> Vec<ProgramHeader> {
+    let u32_at = |header: &[u8], offset: usize| {
+        u32::from_ne_bytes(header[offset..offset + 4].try_into().unwrap())
+    };
+    let word_at = |header: &[u8], offset: usize| {
+        pointer_width.read_pointer(&header[offset..offset + pointer_width.size()])
+    };
+
+    bytes
+        .chunks_exact(entry_size)
+        .map(|header| match pointer_width {
+            // p_type, p_offset, p_vaddr, p_paddr, p_filesz, p_memsz, p_flags, ...
+            PointerWidth::Bits32 => ProgramHeader {
+                p_type: u32_at(header, 0),
+                flags: u32_at(header, 24),
+                vaddr: word_at(header, 8),
+                memsz: word_at(header, 20),
+            },
+            // p_type, p_flags, p_offset, p_vaddr, p_paddr, p_filesz, p_memsz, ...
+            PointerWidth::Bits64 => ProgramHeader {
+                p_type: u32_at(header, 0),
+                flags: u32_at(header, 4),
+                vaddr: word_at(header, 16),
+                memsz: word_at(header, 40),
+            },
+        })
+        .collect()
+}
+
+// The difference between where the executable was linked and where it got loaded. PT_PHDR is
+// what the kernel put at AT_PHDR, executables without one aren't position independent.
+pub(crate) fn load_bias(phdr: u64, headers: &[ProgramHeader]) -> u64 {
+    headers
+        .iter()
+        .find(|header| header.p_type == PT_PHDR)
+        .map(|header| phdr.wrapping_sub(header.vaddr))
+        .unwrap_or(0)
+}
+
+// Link time address and size of the dynamic section.
+pub(crate) fn dynamic_section(headers: &[ProgramHeader]) -> Option<(u64, u64)> {
+    headers
+        .iter()
+        .find(|header| header.p_type == PT_DYNAMIC)
+        .map(|header| (header.vaddr, header.memsz))
+}
+
+// Finds DT_DEBUG in a dynamic section, which the loader points at its r_debug rendezvous
+// structure once it's done loading. Zero until then.
+pub(crate) fn find_debug_entry(bytes: &[u8], pointer_width: PointerWidth) -> Option<u64> {
+    dynamic_entries(bytes, pointer_width)
+        .find(|(tag, _)| *tag == DT_DEBUG)
+        .map(|(_, value)| value)
+}
+
+// Tag and value of every entry of a dynamic section up to DT_NULL.
+pub(crate) fn dynamic_entries(
+    bytes: &[u8],
+    pointer_width: PointerWidth,
+) -> impl Iterator<Item = (u64, u64)> + '_ {
+    let word = pointer_width.size();
+    bytes
+        .chunks_exact(2 * word)
+        .map(move |entry| {
+            (
+                pointer_width.read_pointer(&entry[..word]),
+                pointer_width.read_pointer(&entry[word..]),
+            )
+        })
+        .take_while(|(tag, _)| *tag != DT_NULL)
+}
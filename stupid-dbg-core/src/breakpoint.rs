@@ -0,0 +1,218 @@
+use std::fmt;
+
+use nix::unistd::Pid;
+use tracing::warn;
+
+use crate::{tracer::Tracer, virt_addr::VirtAddr};
+
+const PAGE_SIZE: u64 = 4096;
+
+// How a breakpoint stops the debuggee.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakpointMechanism {
+    // a trap instruction patched into the code
+    Software,
+    // a debug register, the code is left alone
+    Hardware,
+}
+
+impl fmt::Display for BreakpointMechanism {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BreakpointMechanism::Software => write!(f, "software"),
+            BreakpointMechanism::Hardware => write!(f, "hardware"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Breakpoint {
+    id: usize,
+    address: VirtAddr,
+    // as many bytes as the breakpoint instruction takes
+    saved_code: Option<Vec<u8>>,
+}
+
+impl Breakpoint {
+    pub(crate) fn new(id: usize, address: VirtAddr) -> Self {
+        Self {
+            id,
+            address,
+            saved_code: None,
+        }
+    }
+
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    pub fn address(&self) -> VirtAddr {
+        self.address
+    }
+
+    pub fn is_armed(&self) -> bool {
+        self.saved_code.is_some()
+    }
+
+    pub(crate) fn saved_code(&self) -> Option<&[u8]> {
+        self.saved_code.as_deref()
+    }
+
+    pub(crate) fn saved_code_mut(&mut self) -> Option<&mut [u8]> {
+        self.saved_code.as_deref_mut()
+    }
+
+    pub(crate) fn arm<T: Tracer>(
+        &mut self,
+        tracer: &T,
+        pid: Pid,
+        instruction: &[u8],
+    ) -> anyhow::Result<()> {
+        if self.is_armed() {
+            return Ok(());
+        }
+
+        let mut code = vec![0u8; instruction.len()];
+        tracer.read_memory(pid, self.address.as_u64(), &mut code)?;
+        tracer.write_memory(pid, self.address.as_u64(), instruction)?;
+        self.saved_code = Some(code);
+
+        Ok(())
+    }
+
+    pub(crate) fn disarm<T: Tracer>(&mut self, tracer: &T, pid: Pid) -> anyhow::Result<()> {
+        if let Some(code) = &self.saved_code {
+            tracer.write_memory(pid, self.address.as_u64(), code)?;
+            self.saved_code = None;
+        }
+
+        Ok(())
+    }
+}
+
+// Arms every breakpoint with one read and one write per page instead of one pair per breakpoint.
+pub(crate) fn arm_all<'a, T: Tracer>(
+    tracer: &T,
+    pid: Pid,
+    instruction: &[u8],
+    breakpoints: impl IntoIterator<Item = &'a mut Breakpoint>,
+) -> anyhow::Result<()> {
+    let mut breakpoints = breakpoints
+        .into_iter()
+        .filter(|breakpoint| !breakpoint.is_armed())
+        .collect::<Vec<_>>();
+
+    patch_by_page(
+        tracer,
+        pid,
+        &mut breakpoints,
+        instruction.len(),
+        |_| instruction.to_vec(),
+        |breakpoint, original| breakpoint.saved_code = Some(original.to_vec()),
+    )
+}
+
+pub(crate) fn disarm_all<'a, T: Tracer>(
+    tracer: &T,
+    pid: Pid,
+    breakpoints: impl IntoIterator<Item = &'a mut Breakpoint>,
+) -> anyhow::Result<()> {
+    let mut breakpoints = breakpoints
+        .into_iter()
+        .filter(|breakpoint| breakpoint.is_armed())
+        .collect::<Vec<_>>();
+    let Some(size) = breakpoints
+        .first()
+        .and_then(|breakpoint| breakpoint.saved_code())
+        .map(<[u8]>::len)
+    else {
+        return Ok(());
+    };
+
+    patch_by_page(
+        tracer,
+        pid,
+        &mut breakpoints,
+        size,
+        |breakpoint| breakpoint.saved_code.clone().expect("breakpoint is armed"),
+        |breakpoint, _| breakpoint.saved_code = None,
+    )
+}
+
+// Goes through /proc/pid/mem like the single breakpoint path: process_vm_writev refuses to write
+// to read-only mappings, which is where code lives. The bytes between two breakpoints on the same
+// page are written back unchanged, the debuggee is stopped so they can't change in between.
+// Either every page is patched or none: when one fails, the pages before it are written back and
+// no breakpoint is committed.
+fn patch_by_page<T, P, C>(
+    tracer: &T,
+    pid: Pid,
+    breakpoints: &mut [&mut Breakpoint],
+    size: usize,
+    patch: P,
+    mut commit: C,
+) -> anyhow::Result<()>
+where
+    T: Tracer,
+    P: Fn(&Breakpoint) -> Vec<u8>,
+    C: FnMut(&mut Breakpoint, &[u8]),
+{
+    breakpoints.sort_by_key(|breakpoint| breakpoint.address);
+    let same_page = |a: &&mut Breakpoint, b: &&mut Breakpoint| {
+        a.address.align_down(PAGE_SIZE) == b.address.align_down(PAGE_SIZE)
+    };
+
+    // the start of every patched page and what was there before
+    let mut patched_pages: Vec<(VirtAddr, Vec<u8>)> = Vec::new();
+    for page in breakpoints.chunk_by(same_page) {
+        match patch_page(tracer, pid, page, size, &patch) {
+            Ok(original) => patched_pages.push((page[0].address, original)),
+            Err(err) => {
+                for (start, original) in patched_pages.iter().rev() {
+                    if let Err(restore_err) = tracer.write_memory(pid, start.as_u64(), original) {
+                        warn!(address = %start, error = %restore_err, "unable to restore code");
+                    }
+                }
+                return Err(err);
+            }
+        }
+    }
+
+    for (page, (start, original)) in breakpoints.chunk_by_mut(same_page).zip(&patched_pages) {
+        for breakpoint in page.iter_mut() {
+            let offset = breakpoint.address.offset_from(*start).unwrap_or(0) as usize;
+            commit(breakpoint, &original[offset..offset + size]);
+        }
+    }
+
+    Ok(())
+}
+
+// Patches the breakpoints on one page with a single read and write, returning the bytes it
+// replaced.
+fn patch_page<T, P>(
+    tracer: &T,
+    pid: Pid,
+    page: &[&mut Breakpoint],
+    size: usize,
+    patch: P,
+) -> anyhow::Result<Vec<u8>>
+where
+    T: Tracer,
+    P: Fn(&Breakpoint) -> Vec<u8>,
+{
+    let start = page[0].address;
+    let len = page[page.len() - 1].address.offset_from(start).unwrap_or(0) + size as u64;
+
+    let mut original = vec![0u8; len as usize];
+    tracer.read_memory(pid, start.as_u64(), &mut original)?;
+
+    let mut patched = original.clone();
+    for breakpoint in page.iter() {
+        let offset = breakpoint.address.offset_from(start).unwrap_or(0) as usize;
+        patched[offset..offset + size].copy_from_slice(&patch(breakpoint));
+    }
+    tracer.write_memory(pid, start.as_u64(), &patched)?;
+
+    Ok(original)
+}
@@ -0,0 +1,308 @@
+use std::fmt;
+
+use anyhow::anyhow;
+
+use crate::expression::Value;
+
+// What a C type comes down to without debug info.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BaseType {
+    Void,
+    Bool,
+    Int { size: usize, signed: bool },
+    // float or double
+    Float { size: usize },
+    // a struct, union or enum, e.g. `struct Foo`, whose layout needs debug info
+    Named(String),
+}
+
+// A type expressions can cast to, e.g. `unsigned short` or `struct Foo **`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CType {
+    pub base: BaseType,
+    pub pointers: usize,
+}
+
+// Single word names of fixed size, on x86_64.
+const FIXED: [(&str, BaseType); 17] = [
+    (
+        "int8_t",
+        BaseType::Int {
+            size: 1,
+            signed: true,
+        },
+    ),
+    (
+        "uint8_t",
+        BaseType::Int {
+            size: 1,
+            signed: false,
+        },
+    ),
+    (
+        "int16_t",
+        BaseType::Int {
+            size: 2,
+            signed: true,
+        },
+    ),
+    (
+        "uint16_t",
+        BaseType::Int {
+            size: 2,
+            signed: false,
+        },
+    ),
+    (
+        "int32_t",
+        BaseType::Int {
+            size: 4,
+            signed: true,
+        },
+    ),
+    (
+        "uint32_t",
+        BaseType::Int {
+            size: 4,
+            signed: false,
+        },
+    ),
+    (
+        "int64_t",
+        BaseType::Int {
+            size: 8,
+            signed: true,
+        },
+    ),
+    (
+        "uint64_t",
+        BaseType::Int {
+            size: 8,
+            signed: false,
+        },
+    ),
+    (
+        "size_t",
+        BaseType::Int {
+            size: 8,
+            signed: false,
+        },
+    ),
+    (
+        "ssize_t",
+        BaseType::Int {
+            size: 8,
+            signed: true,
+        },
+    ),
+    (
+        "intptr_t",
+        BaseType::Int {
+            size: 8,
+            signed: true,
+        },
+    ),
+    (
+        "uintptr_t",
+        BaseType::Int {
+            size: 8,
+            signed: false,
+        },
+    ),
+    (
+        "ptrdiff_t",
+        BaseType::Int {
+            size: 8,
+            signed: true,
+        },
+    ),
+    ("float", BaseType::Float { size: 4 }),
+    ("double", BaseType::Float { size: 8 }),
+    ("bool", BaseType::Bool),
+    ("_Bool", BaseType::Bool),
+];
+
+// The words an integer type can be spelled with, in any order.
+const INTEGER_WORDS: [&str; 6] = ["signed", "unsigned", "char", "short", "int", "long"];
+
+// Whether a type name can start with `word`, which tells a cast from a parenthesized
+// expression.
+pub fn starts_type(word: &str) -> bool {
+    matches!(
+        word,
+        "struct" | "union" | "enum" | "const" | "volatile" | "void"
+    ) || INTEGER_WORDS.contains(&word)
+        || FIXED.iter().any(|(name, _)| *name == word)
+}
+
+impl BaseType {
+    // `words` without the pointers, `const` and `volatile` are ignored.
+    pub fn from_words<S: AsRef<str>>(words: &[S]) -> anyhow::Result<Self> {
+        let words = words
+            .iter()
+            .map(AsRef::as_ref)
+            .filter(|word| !matches!(*word, "const" | "volatile"))
+            .collect::<Vec<_>>();
+        let spelled = words.join(" ");
+
+        match words.as_slice() {
+            [] => Err(anyhow!("expected a type name"))?,
+            ["void"] => return Ok(BaseType::Void),
+            [kind @ ("struct" | "union" | "enum"), name] => {
+                return Ok(BaseType::Named(format!("{} {}", kind, name)))
+            }
+            [name] => {
+                if let Some((_, base)) = FIXED.iter().find(|(fixed, _)| fixed == name) {
+                    return Ok(base.clone());
+                }
+            }
+            _ => (),
+        }
+
+        if words.iter().any(|word| !INTEGER_WORDS.contains(word)) {
+            Err(anyhow!(
+                "unknown type {}, only builtin types can be used without debug info",
+                spelled
+            ))?;
+        }
+        let count = |word: &str| words.iter().filter(|w| **w == word).count();
+        let (signed, unsigned) = (count("signed"), count("unsigned"));
+        let (chars, shorts, ints, longs) =
+            (count("char"), count("short"), count("int"), count("long"));
+        if signed + unsigned > 1
+            || chars + shorts > 1
+            || ints > 1
+            || longs > 2
+            || (chars + shorts > 0 && longs > 0)
+            || (chars > 0 && ints > 0)
+        {
+            Err(anyhow!("invalid type {}", spelled))?;
+        }
+
+        let size = match (chars, shorts, longs) {
+            (1, _, _) => 1,
+            (_, 1, _) => 2,
+            (_, _, 0) => 4,
+            _ => 8,
+        };
+        // plain char is signed on x86_64
+        Ok(BaseType::Int {
+            size,
+            signed: unsigned == 0,
+        })
+    }
+}
+
+impl CType {
+    pub fn pointee(&self) -> Option<CType> {
+        Some(CType {
+            base: self.base.clone(),
+            pointers: self.pointers.checked_sub(1)?,
+        })
+    }
+
+    pub fn is_pointer(&self) -> bool {
+        self.pointers > 0
+    }
+
+    pub fn size(&self, pointer_size: usize) -> anyhow::Result<usize> {
+        if self.is_pointer() {
+            return Ok(pointer_size);
+        }
+        match &self.base {
+            BaseType::Void => Err(anyhow!("void has no size")),
+            BaseType::Bool => Ok(1),
+            BaseType::Int { size, .. } | BaseType::Float { size } => Ok(*size),
+            BaseType::Named(name) => Err(anyhow!("no debug info for {}", name)),
+        }
+    }
+
+    // What `(type) value` evaluates to.
+    pub fn convert(&self, value: Value, pointer_size: usize) -> anyhow::Result<Value> {
+        if self.is_pointer() {
+            let Value::Int(x) = value else {
+                return Err(anyhow!("can't cast a floating point value to {}", self));
+            };
+            return Ok(Value::Int(extend(x as u64, pointer_size, false)));
+        }
+        match &self.base {
+            BaseType::Void => Err(anyhow!("can't use a void value")),
+            BaseType::Bool => Ok(Value::Int(value.is_true() as i64)),
+            BaseType::Int { size, signed } => {
+                Ok(Value::Int(extend(value.as_i64() as u64, *size, *signed)))
+            }
+            BaseType::Float { size: 4 } => Ok(Value::Float(value.as_f64() as f32 as f64)),
+            BaseType::Float { .. } => Ok(Value::Float(value.as_f64())),
+            BaseType::Named(name) => Err(anyhow!("no debug info for {}", name)),
+        }
+    }
+
+    // Reads a value of this type from its bytes in memory.
+    pub fn decode(&self, bytes: &[u8]) -> anyhow::Result<Value> {
+        let mut word = [0u8; 8];
+        let len = bytes.len().min(8);
+        word[..len].copy_from_slice(&bytes[..len]);
+        let x = u64::from_le_bytes(word);
+
+        if self.is_pointer() {
+            return Ok(Value::Int(x as i64));
+        }
+        match &self.base {
+            BaseType::Float { size: 4 } => Ok(Value::Float(f32::from_bits(x as u32) as f64)),
+            BaseType::Float { .. } => Ok(Value::Float(f64::from_bits(x))),
+            BaseType::Int { signed, .. } => Ok(Value::Int(extend(x, len, *signed))),
+            BaseType::Bool => Ok(Value::Int((x != 0) as i64)),
+            BaseType::Void => Err(anyhow!("can't dereference a void pointer")),
+            BaseType::Named(name) => Err(anyhow!("no debug info for {}", name)),
+        }
+    }
+}
+
+// The low `size` bytes of `x`, sign extended if `signed`.
+fn extend(x: u64, size: usize, signed: bool) -> i64 {
+    if size >= 8 {
+        return x as i64;
+    }
+    let bits = size as u32 * 8;
+    let x = x & ((1 << bits) - 1);
+    if signed && x >> (bits - 1) != 0 {
+        (x | !((1 << bits) - 1)) as i64
+    } else {
+        x as i64
+    }
+}
+
+impl fmt::Display for BaseType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BaseType::Void => write!(f, "void"),
+            BaseType::Bool => write!(f, "bool"),
+            BaseType::Int { size, signed } => {
+                let name = match size {
+                    1 => "char",
+                    2 => "short",
+                    4 => "int",
+                    _ => "long",
+                };
+                match (signed, *size) {
+                    (true, 1) => write!(f, "signed char"),
+                    (true, _) => write!(f, "{}", name),
+                    (false, _) => write!(f, "unsigned {}", name),
+                }
+            }
+            BaseType::Float { size: 4 } => write!(f, "float"),
+            BaseType::Float { .. } => write!(f, "double"),
+            BaseType::Named(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+impl fmt::Display for CType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.base)?;
+        if self.is_pointer() {
+            write!(f, " {}", "*".repeat(self.pointers))?;
+        }
+        Ok(())
+    }
+}
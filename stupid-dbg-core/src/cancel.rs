@@ -0,0 +1,50 @@
+use std::{
+    os::fd::{AsFd, AsRawFd, BorrowedFd, OwnedFd},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use nix::{fcntl::OFlag, unistd};
+
+// Shared flag used to abort a wait on the debuggee from another thread or a signal handler.
+// Cancelling also writes to a pipe, which wakes up a wait blocked in poll.
+#[derive(Debug, Clone)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+    // read and write end
+    wakeup: Arc<(OwnedFd, OwnedFd)>,
+}
+
+impl CancellationToken {
+    pub fn new() -> nix::Result<Self> {
+        Ok(Self {
+            cancelled: Arc::new(AtomicBool::new(false)),
+            wakeup: Arc::new(unistd::pipe2(OFlag::O_CLOEXEC | OFlag::O_NONBLOCK)?),
+        })
+    }
+
+    // Only an atomic store and a write, so it's safe to call from a signal handler.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        // a full pipe wakes the waiter up just as well
+        _ = unistd::write(&self.wakeup.1, &[0]);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    // Clears the flag, returning whether it was set, so a cancellation only aborts one wait.
+    pub fn take(&self) -> bool {
+        let mut buf = [0; 64];
+        while unistd::read(self.wakeup.0.as_raw_fd(), &mut buf).is_ok_and(|len| len > 0) {}
+        self.cancelled.swap(false, Ordering::SeqCst)
+    }
+
+    // Readable once the token is cancelled, until it's taken.
+    pub(crate) fn wakeup_fd(&self) -> BorrowedFd<'_> {
+        self.wakeup.0.as_fd()
+    }
+}
@@ -0,0 +1,84 @@
+use anyhow::anyhow;
+use nix::{
+    errno::Errno,
+    sys::{
+        ptrace::{self, Options},
+        signal::{kill, Signal},
+        wait::{waitpid, WaitStatus},
+    },
+    unistd::Pid,
+};
+use tracing::{debug, debug_span, warn};
+
+use crate::{aux::box_err, inject::SyscallInjector, tracer::PtraceTracer};
+
+fn wait_for_fork_child(child: Pid) -> anyhow::Result<()> {
+    match waitpid(child, None)? {
+        WaitStatus::Stopped(_, Signal::SIGSTOP)
+        | WaitStatus::PtraceEvent(_, Signal::SIGSTOP, _) => Ok(()),
+        status => Err(anyhow!(
+            "unexpected initial state of forked process: {:?}",
+            status
+        )),
+    }
+}
+
+// Forks a stopped tracee. The fork is traced and left stopped with the exact state of the
+// original at the moment of the call. Only the calling thread is carried over into the fork.
+pub(crate) fn fork_stopped_process(pid: Pid, options: Options) -> anyhow::Result<Pid> {
+    let span = debug_span!(
+        "forking stopped process",
+        pid = tracing::field::display(&pid),
+    );
+    let _entered = span.enter();
+
+    debug!("enabling fork tracing");
+    ptrace::setoptions(pid, options | Options::PTRACE_O_TRACEFORK)?;
+
+    let fork = || -> anyhow::Result<Pid> {
+        let injector = SyscallInjector::new(&PtraceTracer, pid)?;
+        let ret = injector.call(&PtraceTracer, libc::SYS_fork, &[]);
+        injector.restore(&PtraceTracer, pid)?;
+
+        let ret = ret?;
+        if ret < 0 {
+            Err(anyhow!(
+                "injected fork failed: {}",
+                Errno::from_raw(-ret as i32)
+            ))?;
+        }
+        let child = Pid::from_raw(ret as libc::pid_t);
+        debug!(child = %child, "forked");
+
+        wait_for_fork_child(child)?;
+        injector.restore(&PtraceTracer, child)?;
+        ptrace::setoptions(child, options)?;
+
+        Ok(child)
+    };
+    let result = fork();
+
+    debug!("disabling fork tracing");
+    ptrace::setoptions(pid, options)?;
+
+    result
+}
+
+pub(crate) fn kill_traced_process(pid: Pid) {
+    if let Err(err) = kill(pid, Signal::SIGKILL) {
+        warn!(
+            error = box_err(err),
+            pid = %pid,
+            "unable to kill traced process",
+        );
+        return;
+    }
+
+    if let Err(err) = waitpid(pid, None) {
+        warn!(
+            error = box_err(err),
+            pid = %pid,
+            "unable to wait for traced process to exit",
+        )
+    }
+}
@@ -0,0 +1,149 @@
+use std::fmt;
+
+use tracing::debug;
+
+use crate::{
+    aux::box_err,
+    elf::{ElfFile, ET_DYN, ET_EXEC, PF_X, PT_GNU_RELRO, PT_GNU_STACK, PT_INTERP},
+    memory_map::MemoryMap,
+    namespace::ProcessRoot,
+    virt_addr::VirtAddr,
+};
+
+const DT_FLAGS: u64 = 30;
+const DT_BIND_NOW: u64 = 24;
+const DT_FLAGS_1: u64 = 0x6ffffffb;
+const DF_BIND_NOW: u64 = 0x8;
+const DF_1_NOW: u64 = 0x1;
+const DF_1_PIE: u64 = 0x08000000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pie {
+    Enabled,
+    Disabled,
+    // libraries are always position independent
+    SharedObject,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Relro {
+    None,
+    // the GOT stays writable for lazy binding
+    Partial,
+    Full,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hardening {
+    pub pie: Pie,
+    pub relro: Relro,
+    pub stack_canary: bool,
+    pub nx: bool,
+    // fortified libc functions it calls, like __printf_chk
+    pub fortified: Vec<String>,
+}
+
+impl fmt::Display for Pie {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Pie::Enabled => write!(f, "enabled"),
+            Pie::Disabled => write!(f, "disabled"),
+            Pie::SharedObject => write!(f, "shared object"),
+        }
+    }
+}
+
+impl fmt::Display for Relro {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Relro::None => write!(f, "none"),
+            Relro::Partial => write!(f, "partial"),
+            Relro::Full => write!(f, "full"),
+        }
+    }
+}
+
+impl Hardening {
+    pub fn of(elf: &ElfFile) -> anyhow::Result<Self> {
+        let dynamic = elf.dynamic_entries();
+        let dynamic_flags = |tag: u64| {
+            dynamic
+                .iter()
+                .filter(|(entry_tag, _)| *entry_tag == tag)
+                .fold(0, |flags, (_, value)| flags | value)
+        };
+        let has_segment = |p_type: u32| {
+            elf.program_headers()
+                .iter()
+                .any(|header| header.p_type == p_type)
+        };
+
+        let pie = match elf.e_type() {
+            ET_EXEC => Pie::Disabled,
+            ET_DYN if has_segment(PT_INTERP) || dynamic_flags(DT_FLAGS_1) & DF_1_PIE != 0 => {
+                Pie::Enabled
+            }
+            _ => Pie::SharedObject,
+        };
+
+        let bind_now = dynamic.iter().any(|(tag, _)| *tag == DT_BIND_NOW)
+            || dynamic_flags(DT_FLAGS) & DF_BIND_NOW != 0
+            || dynamic_flags(DT_FLAGS_1) & DF_1_NOW != 0;
+        let relro = match (has_segment(PT_GNU_RELRO), bind_now) {
+            (false, _) => Relro::None,
+            (true, false) => Relro::Partial,
+            (true, true) => Relro::Full,
+        };
+
+        // without PT_GNU_STACK the kernel assumes the stack has to be executable
+        let nx = elf
+            .program_headers()
+            .iter()
+            .find(|header| header.p_type == PT_GNU_STACK)
+            .is_some_and(|header| header.flags & PF_X == 0);
+
+        let names = elf.symbol_names()?;
+        let stack_canary = names.iter().any(|name| name.starts_with("__stack_chk_"));
+        let fortified = names
+            .into_iter()
+            .filter(|name| {
+                name.starts_with("__") && name.ends_with("_chk") && !name.starts_with("__stack_chk")
+            })
+            .collect();
+
+        Ok(Self {
+            pie,
+            relro,
+            stack_canary,
+            nx,
+            fortified,
+        })
+    }
+}
+
+// The hardening of every ELF file mapped into the debuggee, lowest mapping first. Mapped files
+// that aren't ELF files, like locale archives, are left out.
+pub fn check_modules(
+    memory_map: &MemoryMap,
+    root: &ProcessRoot,
+) -> Vec<(String, anyhow::Result<Hardening>)> {
+    let mut modules = memory_map
+        .modules()
+        .into_iter()
+        .collect::<Vec<(&str, VirtAddr)>>();
+    modules.sort_by_key(|(_, base)| *base);
+
+    modules
+        .into_iter()
+        .filter_map(|(module, _)| {
+            let elf = match ElfFile::read(root.module_path(memory_map, module)) {
+                Ok(elf) => elf,
+                Err(err) => {
+                    debug!(error = box_err(err), module = %module, "skipping module");
+                    return None;
+                }
+            };
+            Some((module.to_string(), Hardening::of(&elf)))
+        })
+        .collect()
+}
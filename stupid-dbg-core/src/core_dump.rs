@@ -0,0 +1,413 @@
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Seek, SeekFrom, Write},
+    os::unix::fs::MetadataExt,
+    path::Path,
+};
+
+use anyhow::anyhow;
+use nix::unistd::Pid;
+use tracing::{debug, debug_span, warn};
+
+use crate::{
+    aux::{as_u8_slice, box_err},
+    debuggee::{Debuggee, ProcessState},
+    memory_map::MemoryRegion,
+    tracer::Tracer,
+};
+
+const ELF_HEADER_SIZE: u16 = 64;
+const PROGRAM_HEADER_SIZE: u16 = 56;
+const PAGE_SIZE: u64 = 4096;
+const COPY_CHUNK_SIZE: u64 = 64 * 1024;
+
+const ET_CORE: u16 = 4;
+const EM_X86_64: u16 = 62;
+const EV_CURRENT: u8 = 1;
+
+const PT_LOAD: u32 = 1;
+const PT_NOTE: u32 = 4;
+
+const PF_X: u32 = 1;
+const PF_W: u32 = 2;
+const PF_R: u32 = 4;
+
+const NT_PRSTATUS: u32 = 1;
+const NT_PRFPREG: u32 = 2;
+const NT_PRPSINFO: u32 = 3;
+const NT_AUXV: u32 = 6;
+
+struct ThreadState {
+    tid: Pid,
+    regs: libc::user_regs_struct,
+    fpregs: libc::user_fpregs_struct,
+}
+
+struct ProcStat {
+    state: u8,
+    ppid: i32,
+    pgrp: i32,
+    sid: i32,
+}
+
+struct ProgramHeader {
+    p_type: u32,
+    p_flags: u32,
+    p_offset: u64,
+    p_vaddr: u64,
+    p_filesz: u64,
+    p_memsz: u64,
+    p_align: u64,
+}
+
+struct LoadSegment<'a> {
+    region: &'a MemoryRegion,
+    file_offset: u64,
+    file_size: u64,
+}
+
+fn align_up(value: u64, align: u64) -> u64 {
+    value.div_ceil(align) * align
+}
+
+fn pad_to_4(buf: &mut Vec<u8>) {
+    buf.resize(align_up(buf.len() as u64, 4) as usize, 0)
+}
+
+fn fixed_c_string(bytes: &[u8], len: usize) -> Vec<u8> {
+    let mut ret = bytes.iter().copied().take(len - 1).collect::<Vec<_>>();
+    ret.resize(len, 0);
+    ret
+}
+
+fn read_proc_stat(pid: Pid) -> anyhow::Result<ProcStat> {
+    let stat = fs::read_to_string(format!("/proc/{}/stat", pid))?;
+
+    // comm may contain spaces and parentheses, the fields we want follow the last ')'
+    let fields = stat
+        .rsplit_once(')')
+        .ok_or(anyhow!("malformed stat file of {}", pid))?
+        .1
+        .split_whitespace()
+        .collect::<Vec<_>>();
+    if fields.len() < 4 {
+        return Err(anyhow!("malformed stat file of {}", pid));
+    }
+
+    Ok(ProcStat {
+        state: fields[0].as_bytes()[0],
+        ppid: fields[1].parse()?,
+        pgrp: fields[2].parse()?,
+        sid: fields[3].parse()?,
+    })
+}
+
+fn read_thread_ids(pid: Pid) -> anyhow::Result<Vec<Pid>> {
+    fs::read_dir(format!("/proc/{}/task", pid))?
+        .map(|entry| {
+            let tid = entry?.file_name().to_string_lossy().parse()?;
+            Ok(Pid::from_raw(tid))
+        })
+        .collect()
+}
+
+fn collect_thread_states(debuggee: &Debuggee) -> anyhow::Result<Vec<ThreadState>> {
+    let registers = debuggee
+        .registers()
+        .ok_or(anyhow!("no register info available"))?;
+
+    let mut threads = vec![ThreadState {
+        tid: debuggee.current_thread(),
+        regs: *registers.user_regs(),
+        fpregs: *registers.user_fpregs(),
+    }];
+
+    // every thread is stopped, see Debuggee::generate_core_dump
+    let tracer = debuggee.tracer();
+    for tid in read_thread_ids(debuggee.pid())? {
+        if tid == debuggee.current_thread() {
+            continue;
+        }
+
+        let (regs, fpregs) = tracer
+            .get_regs(tid)
+            .and_then(|regs| Ok((regs, tracer.get_fpregs(tid)?)))
+            .map_err(|err| anyhow!("unable to read registers of thread {}: {}", tid, err))?;
+        threads.push(ThreadState { tid, regs, fpregs });
+    }
+
+    Ok(threads)
+}
+
+fn push_note(buf: &mut Vec<u8>, note_type: u32, desc: &[u8]) {
+    const NAME: &[u8] = b"CORE\0";
+
+    buf.extend_from_slice(&(NAME.len() as u32).to_le_bytes());
+    buf.extend_from_slice(&(desc.len() as u32).to_le_bytes());
+    buf.extend_from_slice(&note_type.to_le_bytes());
+    buf.extend_from_slice(NAME);
+    pad_to_4(buf);
+    buf.extend_from_slice(desc);
+    pad_to_4(buf);
+}
+
+// struct elf_prstatus
+fn prstatus_desc(thread: &ThreadState, signal: i32, stat: &ProcStat) -> Vec<u8> {
+    let mut desc = Vec::new();
+
+    // pr_info.si_signo, pr_info.si_code, pr_info.si_errno
+    desc.extend_from_slice(&signal.to_le_bytes());
+    desc.extend_from_slice(&0i32.to_le_bytes());
+    desc.extend_from_slice(&0i32.to_le_bytes());
+    // pr_cursig
+    desc.extend_from_slice(&(signal as i16).to_le_bytes());
+    desc.extend_from_slice(&[0u8; 2]);
+    // pr_sigpend, pr_sighold
+    desc.extend_from_slice(&0u64.to_le_bytes());
+    desc.extend_from_slice(&0u64.to_le_bytes());
+    // pr_pid, pr_ppid, pr_pgrp, pr_sid
+    desc.extend_from_slice(&thread.tid.as_raw().to_le_bytes());
+    desc.extend_from_slice(&stat.ppid.to_le_bytes());
+    desc.extend_from_slice(&stat.pgrp.to_le_bytes());
+    desc.extend_from_slice(&stat.sid.to_le_bytes());
+    // pr_utime, pr_stime, pr_cutime, pr_cstime
+    desc.extend_from_slice(&[0u8; 64]);
+    // pr_reg
+    desc.extend_from_slice(unsafe { as_u8_slice(&thread.regs) });
+    // pr_fpvalid
+    desc.extend_from_slice(&1i32.to_le_bytes());
+    desc.extend_from_slice(&[0u8; 4]);
+
+    desc
+}
+
+// struct elf_prpsinfo
+fn prpsinfo_desc(pid: Pid, stat: &ProcStat) -> anyhow::Result<Vec<u8>> {
+    let metadata = fs::metadata(format!("/proc/{}", pid))?;
+    let comm = fs::read_to_string(format!("/proc/{}/comm", pid))?;
+    let cmdline = fs::read(format!("/proc/{}/cmdline", pid))?
+        .into_iter()
+        .map(|c| if c == 0 { b' ' } else { c })
+        .collect::<Vec<_>>();
+
+    let mut desc = Vec::new();
+
+    // pr_state, pr_sname, pr_zomb, pr_nice
+    let state = b"RSDTZW".iter().position(|c| *c == stat.state).unwrap_or(0);
+    desc.push(state as u8);
+    desc.push(stat.state);
+    desc.push((stat.state == b'Z') as u8);
+    desc.push(0);
+    desc.extend_from_slice(&[0u8; 4]);
+    // pr_flag
+    desc.extend_from_slice(&0u64.to_le_bytes());
+    // pr_uid, pr_gid
+    desc.extend_from_slice(&metadata.uid().to_le_bytes());
+    desc.extend_from_slice(&metadata.gid().to_le_bytes());
+    // pr_pid, pr_ppid, pr_pgrp, pr_sid
+    desc.extend_from_slice(&pid.as_raw().to_le_bytes());
+    desc.extend_from_slice(&stat.ppid.to_le_bytes());
+    desc.extend_from_slice(&stat.pgrp.to_le_bytes());
+    desc.extend_from_slice(&stat.sid.to_le_bytes());
+    // pr_fname, pr_psargs
+    desc.extend(fixed_c_string(comm.trim_end().as_bytes(), 16));
+    desc.extend(fixed_c_string(cmdline.trim_ascii_end(), 80));
+
+    Ok(desc)
+}
+
+fn build_notes(debuggee: &Debuggee, signal: i32) -> anyhow::Result<Vec<u8>> {
+    let pid = debuggee.pid();
+    let stat = read_proc_stat(pid)?;
+    let threads = collect_thread_states(debuggee)?;
+
+    let mut notes = Vec::new();
+
+    push_note(&mut notes, NT_PRPSINFO, &prpsinfo_desc(pid, &stat)?);
+
+    match fs::read(format!("/proc/{}/auxv", pid)) {
+        Ok(auxv) => push_note(&mut notes, NT_AUXV, &auxv),
+        Err(err) => warn!(error = box_err(err), "unable to read auxv, skipping"),
+    }
+
+    // gdb expects NT_PRSTATUS to be immediately followed by the other notes of the same thread
+    for thread in &threads {
+        push_note(
+            &mut notes,
+            NT_PRSTATUS,
+            &prstatus_desc(thread, signal, &stat),
+        );
+        push_note(&mut notes, NT_PRFPREG, unsafe {
+            as_u8_slice(&thread.fpregs)
+        });
+    }
+
+    Ok(notes)
+}
+
+impl ProgramHeader {
+    fn write_to(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&self.p_type.to_le_bytes());
+        buf.extend_from_slice(&self.p_flags.to_le_bytes());
+        buf.extend_from_slice(&self.p_offset.to_le_bytes());
+        buf.extend_from_slice(&self.p_vaddr.to_le_bytes());
+        // p_paddr
+        buf.extend_from_slice(&0u64.to_le_bytes());
+        buf.extend_from_slice(&self.p_filesz.to_le_bytes());
+        buf.extend_from_slice(&self.p_memsz.to_le_bytes());
+        buf.extend_from_slice(&self.p_align.to_le_bytes());
+    }
+}
+
+fn elf_header(phnum: u16) -> Vec<u8> {
+    let mut buf = Vec::new();
+
+    // e_ident: magic, ELFCLASS64, ELFDATA2LSB, EV_CURRENT, ELFOSABI_NONE
+    buf.extend_from_slice(b"\x7fELF");
+    buf.extend_from_slice(&[2, 1, EV_CURRENT, 0]);
+    buf.resize(16, 0);
+    buf.extend_from_slice(&ET_CORE.to_le_bytes());
+    buf.extend_from_slice(&EM_X86_64.to_le_bytes());
+    buf.extend_from_slice(&(EV_CURRENT as u32).to_le_bytes());
+    // e_entry
+    buf.extend_from_slice(&0u64.to_le_bytes());
+    // e_phoff
+    buf.extend_from_slice(&(ELF_HEADER_SIZE as u64).to_le_bytes());
+    // e_shoff
+    buf.extend_from_slice(&0u64.to_le_bytes());
+    // e_flags
+    buf.extend_from_slice(&0u32.to_le_bytes());
+    buf.extend_from_slice(&ELF_HEADER_SIZE.to_le_bytes());
+    buf.extend_from_slice(&PROGRAM_HEADER_SIZE.to_le_bytes());
+    buf.extend_from_slice(&phnum.to_le_bytes());
+    // e_shentsize, e_shnum, e_shstrndx
+    buf.extend_from_slice(&[0u8; 6]);
+
+    buf
+}
+
+fn should_dump_region(region: &MemoryRegion) -> bool {
+    region.permissions.read && region.path.as_deref() != Some("[vvar]")
+}
+
+fn segment_flags(region: &MemoryRegion) -> u32 {
+    let mut flags = 0;
+    if region.permissions.read {
+        flags |= PF_R;
+    }
+    if region.permissions.write {
+        flags |= PF_W;
+    }
+    if region.permissions.execute {
+        flags |= PF_X;
+    }
+    flags
+}
+
+fn copy_region<W: Write>(
+    debuggee: &Debuggee,
+    region: &MemoryRegion,
+    out: &mut W,
+) -> anyhow::Result<()> {
+    let mut addr = region.start;
+    while addr < region.end {
+        let len = COPY_CHUNK_SIZE.min(region.end.offset_from(addr).unwrap_or(0)) as usize;
+        let chunk = debuggee.read_memory(addr, len).unwrap_or_else(|err| {
+            debug!(
+                error = box_err(err),
+                addr = %addr,
+                "unable to read memory chunk, filling with zeros",
+            );
+            vec![0u8; len]
+        });
+        out.write_all(&chunk)?;
+        addr = addr + len as u64;
+    }
+
+    Ok(())
+}
+
+pub(crate) fn write_core_dump(debuggee: &Debuggee, path: &Path) -> anyhow::Result<()> {
+    let span = debug_span!(
+        "writing core dump",
+        pid = tracing::field::display(&debuggee.pid()),
+    );
+    let _entered = span.enter();
+
+    let signal = match debuggee.process_state() {
+        ProcessState::Stopped(reason) => reason.signal().map(|signal| signal as i32).unwrap_or(0),
+        _ => return Err(anyhow!("debuggee must be stopped to generate a core dump")),
+    };
+
+    debug!("reading memory map");
+    let memory_map = debuggee.memory_map()?;
+
+    debug!("building notes");
+    let notes = build_notes(debuggee, signal)?;
+
+    let phnum = u16::try_from(memory_map.regions().len() + 1)
+        .map_err(|_| anyhow!("too many memory regions to fit in a core file"))?;
+    let notes_offset = ELF_HEADER_SIZE as u64 + PROGRAM_HEADER_SIZE as u64 * phnum as u64;
+
+    let mut next_offset = align_up(notes_offset + notes.len() as u64, PAGE_SIZE);
+    let segments = memory_map
+        .regions()
+        .iter()
+        .map(|region| {
+            let file_size = if should_dump_region(region) {
+                region.size()
+            } else {
+                0
+            };
+            let segment = LoadSegment {
+                region,
+                file_offset: next_offset,
+                file_size,
+            };
+            next_offset += file_size;
+            segment
+        })
+        .collect::<Vec<_>>();
+
+    let mut headers = elf_header(phnum);
+    ProgramHeader {
+        p_type: PT_NOTE,
+        p_flags: 0,
+        p_offset: notes_offset,
+        p_vaddr: 0,
+        p_filesz: notes.len() as u64,
+        p_memsz: 0,
+        p_align: 0,
+    }
+    .write_to(&mut headers);
+    for segment in &segments {
+        ProgramHeader {
+            p_type: PT_LOAD,
+            p_flags: segment_flags(segment.region),
+            p_offset: segment.file_offset,
+            p_vaddr: segment.region.start.as_u64(),
+            p_filesz: segment.file_size,
+            p_memsz: segment.region.size(),
+            p_align: PAGE_SIZE,
+        }
+        .write_to(&mut headers);
+    }
+
+    let mut out = BufWriter::new(
+        File::create(path).map_err(|err| anyhow!("unable to create core file: {}", err))?,
+    );
+
+    debug!("writing headers and notes");
+    out.write_all(&headers)?;
+    out.write_all(&notes)?;
+
+    debug!("writing memory contents");
+    for segment in segments.iter().filter(|segment| segment.file_size > 0) {
+        out.seek(SeekFrom::Start(segment.file_offset))?;
+        copy_region(debuggee, segment.region, &mut out)?;
+    }
+
+    out.flush()?;
+
+    Ok(())
+}
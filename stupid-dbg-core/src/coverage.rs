@@ -0,0 +1,132 @@
+use std::{collections::BTreeMap, fmt::Write as _, time::SystemTime};
+
+use nix::unistd::Pid;
+
+use crate::{symbols::SymbolTable, virt_addr::VirtAddr};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CoverageHit {
+    // counts the probes hit so far, the first one is 0
+    pub order: usize,
+    pub thread: Pid,
+    pub time: SystemTime,
+}
+
+// Which of the probed addresses the debuggee got to. A probe only records the first time,
+// telling whether code ran at all but not how often.
+#[derive(Debug, Clone, Default)]
+pub struct Coverage {
+    probes: BTreeMap<VirtAddr, Option<CoverageHit>>,
+    hits: usize,
+}
+
+impl Coverage {
+    pub fn new<I: IntoIterator<Item = VirtAddr>>(addresses: I) -> Self {
+        Self {
+            probes: addresses
+                .into_iter()
+                .map(|address| (address, None))
+                .collect(),
+            hits: 0,
+        }
+    }
+
+    // Returns whether it's the first time `address` is reached.
+    pub fn record(&mut self, address: VirtAddr, thread: Pid) -> bool {
+        let Some(hit) = self.probes.get_mut(&address).filter(|hit| hit.is_none()) else {
+            return false;
+        };
+        *hit = Some(CoverageHit {
+            order: self.hits,
+            thread,
+            time: SystemTime::now(),
+        });
+        self.hits += 1;
+        true
+    }
+
+    pub fn is_probed(&self, address: VirtAddr) -> bool {
+        self.probes.contains_key(&address)
+    }
+
+    pub fn is_covered(&self, address: VirtAddr) -> bool {
+        self.probes.get(&address).is_some_and(Option::is_some)
+    }
+
+    // Lowest address first.
+    pub fn probes(&self) -> impl Iterator<Item = (VirtAddr, Option<&CoverageHit>)> {
+        self.probes
+            .iter()
+            .map(|(address, hit)| (*address, hit.as_ref()))
+    }
+
+    // In the order they were first reached.
+    pub fn covered(&self) -> Vec<(VirtAddr, &CoverageHit)> {
+        let mut covered = self
+            .probes
+            .iter()
+            .filter_map(|(address, hit)| Some((*address, hit.as_ref()?)))
+            .collect::<Vec<_>>();
+        covered.sort_by_key(|(_, hit)| hit.order);
+        covered
+    }
+
+    pub fn uncovered(&self) -> impl Iterator<Item = VirtAddr> + '_ {
+        self.probes
+            .iter()
+            .filter(|(_, hit)| hit.is_none())
+            .map(|(address, _)| *address)
+    }
+
+    pub fn len(&self) -> usize {
+        self.probes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.probes.is_empty()
+    }
+
+    pub fn covered_count(&self) -> usize {
+        self.hits
+    }
+
+    // An lcov tracefile with a record per module and the probes as functions. Without line
+    // information every function is put on line 0 and there are no line records.
+    pub fn to_lcov(&self, symbols: &SymbolTable) -> String {
+        let mut modules = BTreeMap::<&str, Vec<(String, bool)>>::new();
+        for (address, hit) in self.probes() {
+            let (module, name) = match symbols.lookup(address) {
+                Some((symbol, 0)) => (symbol.module.as_str(), symbol.name.clone()),
+                Some((symbol, offset)) => (
+                    symbol.module.as_str(),
+                    format!("{}+{:#x}", symbol.name, offset),
+                ),
+                None => ("??", address.to_string()),
+            };
+            modules
+                .entry(module)
+                .or_default()
+                .push((name, hit.is_some()));
+        }
+
+        let mut lcov = String::new();
+        for (module, functions) in modules {
+            _ = writeln!(lcov, "TN:");
+            _ = writeln!(lcov, "SF:{}", module);
+            for (name, _) in &functions {
+                _ = writeln!(lcov, "FN:0,{}", name);
+            }
+            for (name, covered) in &functions {
+                _ = writeln!(lcov, "FNDA:{},{}", u8::from(*covered), name);
+            }
+            _ = writeln!(lcov, "FNF:{}", functions.len());
+            _ = writeln!(
+                lcov,
+                "FNH:{}",
+                functions.iter().filter(|(_, covered)| *covered).count()
+            );
+            _ = writeln!(lcov, "end_of_record");
+        }
+        lcov
+    }
+}
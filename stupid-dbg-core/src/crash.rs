@@ -0,0 +1,91 @@
+use anyhow::anyhow;
+use nix::unistd::Pid;
+
+use crate::{
+    arch,
+    debuggee::Debuggee,
+    disassemble::{disassemble, Instruction},
+    memory_map::MemoryRegion,
+    register::{Register, RegisterKind, RegisterValue},
+    stop_reason::SignalInfo,
+    tracer::Tracer,
+    verdict::ThreadReport,
+    virt_addr::VirtAddr,
+};
+
+// Everything worth knowing about a fatal signal, taken when the debuggee stops at it, before
+// the signal is delivered and the state is gone.
+#[derive(Debug, Clone)]
+pub struct CrashReport {
+    pub signal: SignalInfo,
+    pub thread: Pid,
+    pub pc: VirtAddr,
+    // the faulting instruction, None if the code at the pc is unreadable or the arch has no
+    // disassembler
+    pub instruction: Option<Instruction>,
+    // the general purpose registers of the crashed thread
+    pub registers: Vec<(Register, RegisterValue)>,
+    // the crashed thread first
+    pub threads: Vec<ThreadReport>,
+    // the mapping containing the fault address, None if it's unmapped or there's none
+    pub fault_region: Option<MemoryRegion>,
+}
+
+impl CrashReport {
+    pub fn capture<T: Tracer>(debuggee: &Debuggee<T>, signal: SignalInfo) -> anyhow::Result<Self> {
+        let thread = debuggee.current_thread();
+        let registers = debuggee
+            .registers()
+            .ok_or(anyhow!("no register info available"))?;
+        let pc = VirtAddr::new(debuggee.arch().pc(registers.user_regs()));
+
+        // a byte at a time, the instruction may be right before the end of the mapping
+        let code = (0..arch::MAX_INSTRUCTION_LENGTH as u64)
+            .map_while(|offset| {
+                debuggee
+                    .read_memory(pc + offset, 1)
+                    .ok()
+                    .map(|byte| byte[0])
+            })
+            .collect::<Vec<_>>();
+        let instruction = disassemble(debuggee.arch(), pc, &code, 1)?
+            .into_iter()
+            .next();
+        let registers = Register::all_registers()
+            .into_iter()
+            .filter(|register| register.kind() == RegisterKind::GeneralPurpose)
+            .filter_map(|register| Some((register, registers.read_register(register).ok()?)))
+            .collect();
+
+        let memory_map = debuggee.memory_map().ok();
+        let threads = [thread]
+            .into_iter()
+            .chain(
+                debuggee
+                    .threads()
+                    .iter()
+                    .copied()
+                    .filter(|tid| *tid != thread),
+            )
+            .map(|tid| {
+                ThreadReport::new(tid, debuggee.backtrace(tid), |address| {
+                    debuggee.lookup_symbol(memory_map.as_ref()?, address)
+                })
+            })
+            .collect();
+
+        let fault_region = signal
+            .fault_address
+            .and_then(|address| memory_map.as_ref()?.region_containing(address).cloned());
+
+        Ok(Self {
+            signal,
+            thread,
+            pc,
+            instruction,
+            registers,
+            threads,
+            fault_region,
+        })
+    }
+}
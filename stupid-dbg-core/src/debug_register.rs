@@ -0,0 +1,237 @@
+use std::fmt;
+
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+
+use crate::virt_addr::VirtAddr;
+
+// DR0 to DR3, DR4 and DR5 are aliases of DR6 and DR7
+pub const DEBUG_REGISTER_SLOTS: usize = 4;
+pub(crate) const DR6: usize = 6;
+pub(crate) const DR7: usize = 7;
+
+// RF in eflags, suppresses instruction breakpoints for one instruction
+pub(crate) const RESUME_FLAG: u64 = 1 << 16;
+
+// Offset of DRn in the user area, for PTRACE_PEEKUSER and PTRACE_POKEUSER.
+pub(crate) fn offset_in_user_struct(index: usize) -> usize {
+    std::mem::offset_of!(libc::user, u_debugreg) + index * size_of::<u64>()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WatchKind {
+    Execute,
+    Write,
+    ReadWrite,
+}
+
+impl WatchKind {
+    // R/W bits in DR7, 0b10 is I/O which user space can't use
+    fn encode(self) -> u64 {
+        match self {
+            WatchKind::Execute => 0b00,
+            WatchKind::Write => 0b01,
+            WatchKind::ReadWrite => 0b11,
+        }
+    }
+}
+
+impl fmt::Display for WatchKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WatchKind::Execute => write!(f, "execute"),
+            WatchKind::Write => write!(f, "write"),
+            WatchKind::ReadWrite => write!(f, "read/write"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlotOwner {
+    Breakpoint(usize),
+    Watchpoint(usize),
+}
+
+impl fmt::Display for SlotOwner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SlotOwner::Breakpoint(id) => write!(f, "breakpoint {}", id),
+            SlotOwner::Watchpoint(id) => write!(f, "watchpoint {}", id),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DebugRegisterSlot {
+    pub owner: SlotOwner,
+    pub address: VirtAddr,
+    pub size: usize,
+    pub kind: WatchKind,
+}
+
+impl DebugRegisterSlot {
+    // Sizes the hardware can watch, each has to be naturally aligned. Instructions are matched
+    // by their first byte.
+    fn validate(&self) -> anyhow::Result<()> {
+        if self.kind == WatchKind::Execute && self.size != 1 {
+            Err(anyhow!("hardware breakpoints cover exactly one byte"))?;
+        }
+        if ![1, 2, 4, 8].contains(&self.size) {
+            Err(anyhow!(
+                "debug registers watch 1, 2, 4 or 8 bytes, not {}",
+                self.size
+            ))?;
+        }
+        if !self.address.as_u64().is_multiple_of(self.size as u64) {
+            Err(anyhow!(
+                "{} is not aligned to {} bytes",
+                self.address,
+                self.size
+            ))?;
+        }
+        Ok(())
+    }
+
+    // LEN bits in DR7
+    fn encode_size(&self) -> u64 {
+        match self.size {
+            1 => 0b00,
+            2 => 0b01,
+            8 => 0b10,
+            _ => 0b11,
+        }
+    }
+}
+
+impl fmt::Display for DebugRegisterSlot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            WatchKind::Execute => write!(f, "{} at {}", self.owner, self.address),
+            kind => write!(
+                f,
+                "{} at {} ({} bytes, {})",
+                self.owner, self.address, self.size, kind
+            ),
+        }
+    }
+}
+
+// Hands out DR0 to DR3 to hardware breakpoints and watchpoints. The same assignment is written
+// to every thread of the debuggee, so it's kept once per thread group.
+#[derive(Debug, Clone, Default)]
+pub struct DebugRegisterAllocator {
+    slots: [Option<DebugRegisterSlot>; DEBUG_REGISTER_SLOTS],
+}
+
+impl DebugRegisterAllocator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn slots(&self) -> &[Option<DebugRegisterSlot>; DEBUG_REGISTER_SLOTS] {
+        &self.slots
+    }
+
+    pub fn slot(&self, index: usize) -> Option<&DebugRegisterSlot> {
+        self.slots.get(index)?.as_ref()
+    }
+
+    pub fn free_slots(&self) -> usize {
+        self.slots.iter().filter(|slot| slot.is_none()).count()
+    }
+
+    // Returns the index of the debug register the slot was given.
+    pub fn allocate(&mut self, slot: DebugRegisterSlot) -> anyhow::Result<usize> {
+        Ok(self.allocate_all(&[slot])?[0])
+    }
+
+    // Either every slot gets a debug register or none does.
+    pub fn allocate_all(&mut self, slots: &[DebugRegisterSlot]) -> anyhow::Result<Vec<usize>> {
+        slots.iter().try_for_each(DebugRegisterSlot::validate)?;
+
+        let free = self.free_slots();
+        if slots.len() > free {
+            let occupants = self
+                .slots
+                .iter()
+                .flatten()
+                .map(ToString::to_string)
+                .intersperse(", ".to_string())
+                .collect::<String>();
+            if free == 0 {
+                Err(anyhow!("no free debug registers, in use by {}", occupants))?;
+            }
+            Err(anyhow!(
+                "{} debug registers needed but only {} free, in use by {}",
+                slots.len(),
+                free,
+                occupants
+            ))?;
+        }
+
+        let indices = self
+            .slots
+            .iter()
+            .enumerate()
+            .filter(|(_, slot)| slot.is_none())
+            .map(|(index, _)| index)
+            .take(slots.len())
+            .collect::<Vec<_>>();
+        for (index, slot) in indices.iter().zip(slots) {
+            self.slots[*index] = Some(*slot);
+        }
+        Ok(indices)
+    }
+
+    // Frees every slot held by `owner`, returning their indices.
+    pub fn release(&mut self, owner: SlotOwner) -> Vec<usize> {
+        let mut released = Vec::new();
+        for (index, slot) in self.slots.iter_mut().enumerate() {
+            if slot.is_some_and(|slot| slot.owner == owner) {
+                *slot = None;
+                released.push(index);
+            }
+        }
+        released
+    }
+
+    // Values for DR0 to DR3 and DR7. Slots are enabled locally, the kernel switches them with
+    // the thread anyway.
+    pub fn encode(&self) -> ([u64; DEBUG_REGISTER_SLOTS], u64) {
+        let mut addresses = [0; DEBUG_REGISTER_SLOTS];
+        let mut dr7 = 0;
+        for (index, slot) in self.slots.iter().enumerate() {
+            let Some(slot) = slot else {
+                continue;
+            };
+            addresses[index] = slot.address.as_u64();
+            dr7 |= 1 << (2 * index);
+            dr7 |= slot.kind.encode() << (16 + 4 * index);
+            dr7 |= slot.encode_size() << (18 + 4 * index);
+        }
+        (addresses, dr7)
+    }
+}
+
+// Slots DR6 reports as the cause of a debug exception.
+pub fn triggered_slots(dr6: u64) -> impl Iterator<Item = usize> {
+    (0..DEBUG_REGISTER_SLOTS).filter(move |index| dr6 & (1 << index) != 0)
+}
+
+// Covers a range with as few naturally aligned 1, 2, 4 or 8 byte pieces as possible, the
+// shapes a single debug register can watch.
+pub fn split_range(address: VirtAddr, size: usize) -> Vec<(VirtAddr, usize)> {
+    let end = address.as_u64() + size as u64;
+    let mut pieces = Vec::new();
+    let mut start = address.as_u64();
+    while start < end {
+        let piece = [8, 4, 2, 1]
+            .into_iter()
+            .find(|piece| start.is_multiple_of(*piece) && start + piece <= end)
+            .expect("a single byte always fits");
+        pieces.push((VirtAddr::new(start), piece as usize));
+        start += piece;
+    }
+    pieces
+}
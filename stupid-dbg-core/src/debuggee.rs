@@ -0,0 +1,5006 @@
+use std::{
+    any::type_name,
+    cell::RefCell,
+    collections::{BTreeMap, BTreeSet},
+    convert::Infallible,
+    ffi::CString,
+    fmt,
+    fs::{self, File},
+    io::{read_to_string, Write},
+    mem,
+    os::fd::{AsFd, AsRawFd, OwnedFd, RawFd},
+    path::Path,
+    process::exit,
+    sync::Arc,
+    thread::sleep,
+    time::{Duration, Instant, SystemTime},
+};
+
+use anyhow::anyhow;
+use libc::EXIT_FAILURE;
+use nix::{
+    errno::Errno,
+    fcntl::OFlag,
+    poll::{poll, PollFd, PollFlags, PollTimeout},
+    sys::{
+        personality::{self, Persona},
+        ptrace::{self, Options},
+        signal::{self, SigSet, SigmaskHow, Signal},
+        signalfd::{SfdFlags, SignalFd},
+        wait::{wait, WaitPidFlag, WaitStatus},
+    },
+    unistd::{chdir, dup2, execvpe, fork, pipe2, ForkResult, Pid},
+};
+use tracing::{debug, debug_span, error, info, warn};
+
+use crate::{
+    anti_debug::{self, AntiDebugAttempt, AntiDebugConfig},
+    arch::{self, Arch, PointerWidth, ReturnAddress},
+    aux::{as_u8_slice, box_err, read_any_from_u8_pointer},
+    auxv::{self, Auxv},
+    breakpoint::{self, Breakpoint, BreakpointMechanism},
+    cancel::CancellationToken,
+    checkpoint::{fork_stopped_process, kill_traced_process},
+    core_dump,
+    coverage::Coverage,
+    debug_register::{
+        self, DebugRegisterAllocator, DebugRegisterSlot, SlotOwner, WatchKind, DR6, DR7,
+    },
+    disassemble::{self, Instruction},
+    elf::SymbolKind,
+    expression,
+    file_descriptor::FileDescriptor,
+    format,
+    heap::{self, AllocFunction, HeapCall, HeapTrace},
+    inject::SyscallInjector,
+    instruction_trace::{InstructionRecord, InstructionTrace},
+    launch::{CapturedOutput, LaunchSpec},
+    line_table::{LineRow, LineTable},
+    memory_cache::MemoryCache,
+    memory_map::{MemoryMap, RegionKind},
+    namespace::{self, ProcessRoot},
+    plain_data::{FromBytes, ToBytes},
+    register::{Register, RegisterKind, RegisterValue, Registers},
+    stop_log::StopLog,
+    stop_reason::{self, SignalInfo, StopReason},
+    symbol_cache::{ModuleOffset, SymbolCache},
+    symbol_index::SymbolIndex,
+    symbols::{Symbol, SymbolTable},
+    tracepoint::{
+        Backpressure, CollectedMemory, CollectedValue, TraceAction, TraceBuffer, TraceFile,
+        TraceFrame, DEFAULT_TRACE_FILE_QUEUE,
+    },
+    tracer::{PtraceTracer, Tracer},
+    trampoline::Trampolines,
+    virt_addr::VirtAddr,
+    watchpoint::{self, SyscallBuffer, WatchScope, WatchStrategy, Watchpoint},
+};
+
+// syscall stops are only asked for while looking for anti-debugging checks, TRACESYSGOOD tells
+// them apart from breakpoints then
+// Forks are traced only to take the breakpoints out of the child before letting it go.
+pub(crate) const DEFAULT_PTRACE_OPTIONS: Options = Options::PTRACE_O_TRACECLONE
+    .union(Options::PTRACE_O_TRACESYSGOOD)
+    .union(Options::PTRACE_O_TRACEFORK);
+
+// where every mapped module starts, by path
+type ModuleBases = BTreeMap<String, VirtAddr>;
+// something loaded from every mapped module, and the modules it was loaded for
+type ModuleCache<V> = RefCell<Option<(ModuleBases, Arc<V>)>>;
+
+const THREAD_POLL_INTERVAL: Duration = Duration::from_millis(1);
+// SIGCHLD can be taken by another thread that doesn't block it, so a wait never sleeps longer
+// than this without checking the debuggee again
+const WAIT_RECHECK_INTERVAL: Duration = Duration::from_millis(100);
+// how long a tracer being stolen from gets to exit
+const STEAL_TIMEOUT: Duration = Duration::from_secs(5);
+
+const PAGE_SIZE: u64 = 4096;
+// si_code of a SIGSEGV for a mapped page without the needed permission
+const SEGV_ACCERR: i32 = 2;
+// bit of CAP_SYS_PTRACE in the capability sets of /proc/<pid>/status
+const CAP_SYS_PTRACE: u32 = 19;
+// stops at an address before a breakpoint there counts as hot
+const HOT_BREAKPOINT_STOPS: usize = 2;
+// how much memory searches and dumps read at once
+const MEMORY_CHUNK_SIZE: u64 = 1 << 20;
+
+#[derive(Debug, Clone)]
+pub enum ProcessState {
+    Running,
+    Stopped(StopReason),
+    Exited(Option<i32>),
+    Terminated(Signal),
+}
+
+impl ProcessState {
+    pub fn is_alive(&self) -> bool {
+        match self {
+            ProcessState::Running | ProcessState::Stopped(_) => true,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum WaitOutcome {
+    StateChanged(ProcessState),
+    TimedOut,
+    Cancelled,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ThreadEvent {
+    Created(Pid),
+    Exited(Pid),
+}
+
+// What becomes of a child the debuggee forks.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FollowFork {
+    // detached, with the breakpoints taken out of its memory
+    #[default]
+    Parent,
+    // kept stopped with the breakpoints left in, for a debuggee of its own to take over
+    Both,
+}
+
+// A child forked while following both, stopped since the fork. Its memory is a copy of the
+// parent's, so the parent's breakpoints are in it at the same addresses and only their
+// bookkeeping is carried over. Children nobody takes over are released once the parent is
+// dropped.
+#[derive(Debug)]
+pub struct ForkChild {
+    pid: Pid,
+    arch: &'static dyn Arch,
+    should_terminate: bool,
+    ptrace_options: Options,
+    breakpoints: BTreeMap<usize, Breakpoint>,
+    hardware_breakpoints: BTreeMap<usize, VirtAddr>,
+    next_breakpoint_id: usize,
+    disabled_breakpoints: BTreeSet<usize>,
+    breakpoint_groups: BTreeMap<String, BTreeSet<usize>>,
+    breakpoint_conditions: BTreeMap<usize, String>,
+    tracepoints: BTreeMap<usize, Vec<TraceAction>>,
+}
+
+impl ForkChild {
+    pub fn pid(&self) -> Pid {
+        self.pid
+    }
+}
+
+// A shared library the dynamic loader mapped or unmapped, as seen once its list of libraries is
+// consistent again.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ModuleEvent {
+    // with the pending breakpoints in it, which are armed again
+    Loaded {
+        module: String,
+        base: VirtAddr,
+        resumed: Vec<usize>,
+    },
+    // where it was mapped, with the breakpoints in it, which are pending until it's loaded again
+    Unloaded {
+        module: String,
+        start: VirtAddr,
+        end: VirtAddr,
+        suspended: Vec<usize>,
+    },
+}
+
+// r_state of the loader's r_debug once it's done mapping or unmapping libraries.
+const RT_CONSISTENT: i32 = 0;
+
+// A breakpoint on r_brk, the function the dynamic loader calls before and after it changes its
+// list of libraries, so that debuggers can follow.
+#[derive(Debug, Clone)]
+struct LoaderHook {
+    r_debug: VirtAddr,
+    breakpoint: Breakpoint,
+}
+
+// A single instruction step that hasn't completed yet. Signals delivered to the thread meanwhile
+// run their handler first, the step is retried once the handler returns to `pc`.
+#[derive(Debug, Clone)]
+struct PendingStep {
+    thread: Pid,
+    pc: VirtAddr,
+    stack_pointer: VirtAddr,
+    // set while a signal handler runs, armed unless a breakpoint of the user is at `pc` already
+    handler_return: Option<Breakpoint>,
+}
+
+// A syscall writing into pages protected for watchpoints, which are writable until it returns.
+#[derive(Debug, Clone)]
+struct SyscallWrite {
+    buffers: Vec<(VirtAddr, u64)>,
+    pages: Vec<VirtAddr>,
+}
+
+// A hooked allocator call waiting for its return, which is where it's recorded.
+#[derive(Debug, Clone)]
+struct PendingHeapCall {
+    stack_pointer: VirtAddr,
+    return_address: VirtAddr,
+    call: HeapCall,
+}
+
+#[derive(Debug)]
+pub struct Debuggee<T: Tracer = PtraceTracer> {
+    // reads of pages go through a cache flushed whenever the debuggee runs or memory is written
+    tracer: MemoryCache<T>,
+    pid: Pid,
+    current_thread: Pid,
+    threads: BTreeSet<Pid>,
+    thread_events: Vec<ThreadEvent>,
+    process_state: ProcessState,
+    arch: &'static dyn Arch,
+    should_terminate: bool,
+    ptrace_options: Options,
+    registers: Option<Registers>,
+    // the registers of a thread when it was last resumed, to tell what running it changed
+    previous_registers: Option<(Pid, Registers)>,
+    // debug registers per thread, read once since only the debugger changes them
+    debug_registers: BTreeMap<Pid, [u64; 8]>,
+    breakpoints: BTreeMap<usize, Breakpoint>,
+    // share ids with the software ones
+    hardware_breakpoints: BTreeMap<usize, VirtAddr>,
+    next_breakpoint_id: usize,
+    watchpoints: BTreeMap<usize, Watchpoint>,
+    next_watchpoint_id: usize,
+    // deleted because the frame they watched in returned, not reported yet
+    expired_watchpoints: Vec<usize>,
+    debug_register_allocator: DebugRegisterAllocator,
+    // the current thread is being single stepped for software watchpoints
+    watch_stepping: bool,
+    // original protection of every page write protected for page protection watchpoints
+    page_protections: BTreeMap<VirtAddr, i32>,
+    // by thread
+    syscall_writes: BTreeMap<Pid, SyscallWrite>,
+    pending_wait_status: Option<WaitStatus>,
+    checkpoints: BTreeMap<usize, Pid>,
+    next_checkpoint_id: usize,
+    cancellation_token: CancellationToken,
+    stop_log: StopLog,
+    instruction_trace: InstructionTrace,
+    // whether `run_recording` is what continuing the debuggee should do
+    recording_instructions: bool,
+    anti_debug: AntiDebugConfig,
+    anti_debug_attempts: Vec<AntiDebugAttempt>,
+    // threads in a ptrace(PTRACE_TRACEME) whose result gets faked on exit
+    spoofed_syscalls: BTreeSet<Pid>,
+    // executable mappings the breakpoints were last checked against
+    executable_ranges: Vec<(VirtAddr, VirtAddr)>,
+    auto_hardware_breakpoints: bool,
+    // software and hardware ones alike, disabled ones are kept but don't stop the debuggee
+    disabled_breakpoints: BTreeSet<usize>,
+    breakpoint_groups: BTreeMap<String, BTreeSet<usize>>,
+    // expressions a breakpoint only stops the debuggee for if they're true
+    breakpoint_conditions: BTreeMap<usize, String>,
+    // breakpoints that collect data and let the debuggee go on instead of stopping it
+    tracepoints: BTreeMap<usize, Vec<TraceAction>>,
+    trace_buffer: TraceBuffer,
+    // where trace frames are streamed to besides the trace buffer
+    trace_file: Option<TraceFile>,
+    pending_step: Option<PendingStep>,
+    // threads in group-stop, and whether they're seized and can wait for SIGCONT under ptrace
+    group_stopped: BTreeMap<Pid, bool>,
+    // stop at the first instruction of a signal handler a step runs into
+    step_into_handlers: bool,
+    heap_trace: HeapTrace,
+    // entry points of the allocator functions, empty while heap tracing is off
+    heap_entries: BTreeMap<VirtAddr, AllocFunction>,
+    // breakpoints on the entries and the return addresses of pending calls, only armed while the
+    // debuggee runs so they never show up in its memory or get in the way of the user's
+    heap_hooks: BTreeMap<VirtAddr, Breakpoint>,
+    heap_calls: Vec<PendingHeapCall>,
+    // what the latest coverage collection found, kept once it's stopped
+    coverage: Option<Coverage>,
+    // one-shot breakpoints on every probed address while collecting coverage, armed like the
+    // heap hooks and disarmed for good once hit
+    coverage_probes: BTreeMap<VirtAddr, Breakpoint>,
+    // set once there are breakpoints to follow library unloads for, armed like the heap hooks
+    loader_hook: Option<LoaderHook>,
+    // the memory map as of the last time the loader's list of libraries was consistent
+    loaded_modules: Option<MemoryMap>,
+    // breakpoints whose library got unloaded, disarmed without touching the memory it was in
+    pending_breakpoints: BTreeMap<usize, ModuleOffset>,
+    module_events: Vec<ModuleEvent>,
+    // where symbols of files that haven't changed are taken from, None to read every file
+    symbol_index: Option<SymbolIndex>,
+    follow_fork: FollowFork,
+    // forked while following both, not taken over yet
+    fork_children: Vec<ForkChild>,
+    // reloaded once a module is loaded, unloaded or moved
+    symbol_table: ModuleCache<SymbolTable>,
+    line_table: ModuleCache<LineTable>,
+    // what `lookup_symbol` and `lookup_line` found, misses included
+    symbol_lookups: RefCell<SymbolCache<Option<(Symbol, u64)>>>,
+    line_lookups: RefCell<SymbolCache<Option<LineRow>>>,
+    captured_output: CapturedOutput,
+}
+
+#[derive(Debug)]
+pub enum Config {
+    Existing(Pid),
+    // like Existing, but terminates whatever traces the process already first
+    Steal(Pid),
+    SpawnChild(LaunchSpec),
+}
+
+// A process tracing the target already, as told by TracerPid in its /proc/<pid>/status.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompetingTracer {
+    pub pid: Pid,
+    // None if /proc/<pid>/comm of the tracer can't be read
+    pub name: Option<String>,
+}
+
+impl CompetingTracer {
+    pub fn find(target: Pid) -> Option<Self> {
+        let status = fs::read_to_string(format!("/proc/{}/status", target)).ok()?;
+        let pid = status_field(&status, "TracerPid")?
+            .parse()
+            .ok()
+            .filter(|tracer| *tracer != 0)
+            .map(Pid::from_raw)?;
+        let name = fs::read_to_string(format!("/proc/{}/comm", pid))
+            .ok()
+            .map(|comm| comm.trim_end().to_string());
+        Some(Self { pid, name })
+    }
+
+    // TracerPid is the thread that attached, which need not be the main one.
+    pub fn is_debugger(&self) -> bool {
+        Path::new(&format!("/proc/self/task/{}", self.pid)).exists()
+    }
+
+    // Stealing needs the debugger to be allowed to signal the tracer.
+    pub fn can_be_stolen_from(&self) -> bool {
+        !self.is_debugger() && signal::kill(self.pid, None).is_ok()
+    }
+
+    // Asks the tracer to terminate, which makes the kernel detach it from `target` if it doesn't
+    // detach by itself like strace does. Waits until `target` isn't traced by it anymore.
+    pub fn steal(&self, target: Pid, timeout: Duration) -> anyhow::Result<()> {
+        if self.is_debugger() {
+            Err(anyhow!("{} is traced by this debugger already", target))?;
+        }
+
+        info!(tracer = %self, "terminating competing tracer");
+        signal::kill(self.pid, Signal::SIGTERM)
+            .map_err(|err| anyhow!("unable to terminate {}: {}", self, err))?;
+
+        let deadline = Instant::now() + timeout;
+        while Self::find(target).is_some_and(|tracer| tracer.pid == self.pid) {
+            if Instant::now() >= deadline {
+                Err(anyhow!(
+                    "{} still traces {} after {}s, it may have to be killed with SIGKILL",
+                    self,
+                    target,
+                    timeout.as_secs()
+                ))?;
+            }
+            sleep(THREAD_POLL_INTERVAL);
+        }
+        Ok(())
+    }
+
+    // What to tell someone whose attach it's in the way of.
+    pub fn describe_conflict(&self, target: Pid) -> String {
+        if self.is_debugger() {
+            format!("{} is traced by this debugger already", target)
+        } else if self.can_be_stolen_from() {
+            format!(
+                "{} is traced by {} already; detach that tracer first, or use `attach --steal` \
+                 to terminate it and attach in its place",
+                target, self
+            )
+        } else {
+            format!(
+                "{} is traced by {} already; detach that tracer first",
+                target, self
+            )
+        }
+    }
+}
+
+impl fmt::Display for CompetingTracer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "pid {} ({})", self.pid, name),
+            None => write!(f, "pid {}", self.pid),
+        }
+    }
+}
+
+// What decides whether the debugger may attach to a process, as far as it can be told from
+// /proc. Security modules like SELinux or AppArmor have a say as well but can't be seen here.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PtracePermissions {
+    // None if Yama isn't built into the kernel
+    pub ptrace_scope: Option<u32>,
+    pub has_cap_sys_ptrace: bool,
+    pub uid: u32,
+    pub gid: u32,
+    // real, effective, saved and file system ids of the target
+    pub target_uids: Option<[u32; 4]>,
+    pub target_gids: Option<[u32; 4]>,
+    pub target_tracer: Option<CompetingTracer>,
+    pub target_is_descendant: bool,
+}
+
+impl PtracePermissions {
+    pub fn read(pid: Pid) -> Self {
+        let own_status = fs::read_to_string("/proc/self/status").unwrap_or_default();
+        let target_status = fs::read_to_string(format!("/proc/{}/status", pid)).ok();
+        let target_field = |name: &str| {
+            target_status
+                .as_deref()
+                .and_then(|status| status_field(status, name))
+        };
+        let ids = |value: &str| -> Option<[u32; 4]> {
+            value
+                .split_whitespace()
+                .map(|id| id.parse().ok())
+                .collect::<Option<Vec<u32>>>()?
+                .try_into()
+                .ok()
+        };
+
+        Self {
+            ptrace_scope: fs::read_to_string("/proc/sys/kernel/yama/ptrace_scope")
+                .ok()
+                .and_then(|scope| scope.trim().parse().ok()),
+            has_cap_sys_ptrace: status_field(&own_status, "CapEff")
+                .and_then(|caps| u64::from_str_radix(caps, 16).ok())
+                .is_some_and(|caps| caps & (1 << CAP_SYS_PTRACE) != 0),
+            uid: nix::unistd::geteuid().as_raw(),
+            gid: nix::unistd::getegid().as_raw(),
+            target_uids: target_field("Uid").and_then(ids),
+            target_gids: target_field("Gid").and_then(ids),
+            target_tracer: CompetingTracer::find(pid),
+            target_is_descendant: is_descendant(pid),
+        }
+    }
+
+    // Every reason the attach may have been refused for, with what can be done about it.
+    pub fn diagnose(&self) -> Vec<String> {
+        let mut reasons = Vec::new();
+
+        if let Some(tracer) = &self.target_tracer {
+            reasons.push(format!(
+                "the process is traced by {} already, detach that tracer first",
+                tracer
+            ));
+        }
+
+        if !self.has_cap_sys_ptrace {
+            if let Some(uids) = self
+                .target_uids
+                .filter(|uids| uids.iter().any(|uid| *uid != self.uid))
+            {
+                reasons.push(format!(
+                    "the process runs as uid {} and the debugger as uid {}, run the debugger as \
+                     that user or as root, or grant it CAP_SYS_PTRACE",
+                    uids[1], self.uid
+                ));
+            } else if let Some(gids) = self
+                .target_gids
+                .filter(|gids| gids.iter().any(|gid| *gid != self.gid))
+            {
+                reasons.push(format!(
+                    "the process runs as gid {} and the debugger as gid {}, e.g. after a setgid \
+                     exec, run the debugger as root or grant it CAP_SYS_PTRACE",
+                    gids[1], self.gid
+                ));
+            }
+        }
+
+        match self.ptrace_scope {
+            Some(1) if !self.has_cap_sys_ptrace && !self.target_is_descendant => reasons.push(
+                "Yama ptrace_scope=1 blocks attaching to non-children; run as root, grant \
+                 CAP_SYS_PTRACE, launch the program from the debugger with `run`, or set \
+                 /proc/sys/kernel/yama/ptrace_scope to 0"
+                    .to_string(),
+            ),
+            Some(2) if !self.has_cap_sys_ptrace => reasons.push(
+                "Yama ptrace_scope=2 only lets processes with CAP_SYS_PTRACE attach; run as root \
+                 or grant CAP_SYS_PTRACE"
+                    .to_string(),
+            ),
+            Some(3) => reasons.push(
+                "Yama ptrace_scope=3 disables attaching altogether until the next reboot"
+                    .to_string(),
+            ),
+            _ => {}
+        }
+
+        if reasons.is_empty() {
+            reasons.push(
+                "nothing visible forbids it, the process may not be dumpable or a security \
+                 module like SELinux or AppArmor may deny it"
+                    .to_string(),
+            );
+        }
+        reasons
+    }
+}
+
+fn status_field<'a>(status: &'a str, name: &str) -> Option<&'a str> {
+    status.lines().find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        (key == name).then_some(value.trim())
+    })
+}
+
+// Whether the debugger is an ancestor of `pid`, which is what Yama ptrace_scope=1 asks for.
+fn is_descendant(pid: Pid) -> bool {
+    let own = nix::unistd::getpid();
+    let mut current = pid;
+    // bounded in case the process tree changes under us
+    for _ in 0..4096 {
+        let Some(parent) = fs::read_to_string(format!("/proc/{}/status", current))
+            .ok()
+            .and_then(|status| status_field(&status, "PPid").and_then(|ppid| ppid.parse().ok()))
+            .map(Pid::from_raw)
+        else {
+            return false;
+        };
+        if parent == own {
+            return true;
+        }
+        if parent.as_raw() <= 1 {
+            return false;
+        }
+        current = parent;
+    }
+    false
+}
+
+impl Debuggee {
+    pub fn new(config: Config) -> anyhow::Result<Self> {
+        let span = debug_span!("creating debuggee");
+        let _entered = span.enter();
+
+        info!("initializing debuggee");
+
+        let mut captured_output = CapturedOutput::default();
+        let (pid, should_terminate, ptrace_options, stop_at_entry) = match config {
+            Config::Existing(pid) => {
+                if let Some(tracer) = CompetingTracer::find(pid) {
+                    Err(anyhow!("{}", tracer.describe_conflict(pid)))?;
+                }
+                Self::attach(pid)?;
+                (pid, false, DEFAULT_PTRACE_OPTIONS, false)
+            }
+            Config::Steal(pid) => {
+                if let Some(tracer) = CompetingTracer::find(pid) {
+                    tracer.steal(pid, STEAL_TIMEOUT)?;
+                }
+                Self::attach(pid)?;
+                (pid, false, DEFAULT_PTRACE_OPTIONS, false)
+            }
+            Config::SpawnChild(launch_spec) => {
+                let pid;
+                (pid, captured_output) = Self::launch(&launch_spec)?;
+                (
+                    pid,
+                    true,
+                    DEFAULT_PTRACE_OPTIONS | launch_spec.extra_ptrace_options(),
+                    launch_spec.should_stop_at_entry(),
+                )
+            }
+        };
+
+        info!(pid = tracing::field::display(&pid));
+
+        let mut debuggee = Self::with_tracer(PtraceTracer, pid, should_terminate, ptrace_options)?;
+        debuggee.captured_output = captured_output;
+        debuggee.arch = arch::detect(pid)?;
+        info!(arch = debuggee.arch.name());
+
+        if debuggee.root().is_foreign() {
+            match namespace::namespace_pid(pid) {
+                Ok(namespace_pid) => info!(
+                    namespace_pid = %namespace_pid,
+                    "debuggee lives in another mount namespace, its files are read through /proc"
+                ),
+                Err(err) => warn!(error = box_err(err), "unable to read pid in namespace"),
+            }
+        }
+
+        if !should_terminate {
+            debuggee.attach_other_threads()?;
+        }
+        if debuggee.is_group_stopped() {
+            info!("debuggee is stopped by job control, it stays stopped until it gets SIGCONT");
+        }
+
+        if stop_at_entry {
+            debuggee.run_to_entry()?;
+        }
+
+        Ok(debuggee)
+    }
+
+    // Runs a freshly launched debuggee until it reaches the entry point of the executable,
+    // skipping the dynamic loader.
+    fn run_to_entry(&mut self) -> anyhow::Result<()> {
+        let entry = self
+            .auxv()?
+            .entry_point()
+            .ok_or(anyhow!("no entry point in auxv of debuggee"))?;
+
+        debug!(
+            entry = format_args!("{:#x}", entry),
+            "running to entry point"
+        );
+
+        let id = self.set_breakpoint(VirtAddr::new(entry))?;
+        self.resume()?;
+        self.update_process_state(true)?;
+        self.remove_breakpoint(id)?;
+        if let ProcessState::Stopped(StopReason::Breakpoint { id: hit }) = self.process_state {
+            if hit == id {
+                self.process_state = ProcessState::Stopped(StopReason::Initial);
+            }
+        }
+        // the temporary breakpoint shouldn't take an id away from the user
+        self.next_breakpoint_id = id;
+
+        Ok(())
+    }
+
+    fn attach(pid: Pid) -> anyhow::Result<()> {
+        let span = debug_span!(
+            "attaching to child with pid",
+            pid = tracing::field::display(&pid)
+        );
+        let _entered = span.enter();
+
+        info!("attaching to debuggee");
+
+        // seizing leaves a process someone else stopped in its group-stop, the interrupt stops it
+        // for the debugger either way
+        debug!("calling ptrace::seize");
+        ptrace::seize(pid, DEFAULT_PTRACE_OPTIONS)
+            .and_then(|()| ptrace::interrupt(pid))
+            .map_err(|err| match err {
+                Errno::EPERM => anyhow!(
+                    "unable to attach to debuggee process: {}: {}",
+                    err,
+                    PtracePermissions::read(pid).diagnose().join("; ")
+                ),
+                _ => anyhow!("unable to attach to debuggee process: {}", err),
+            })?;
+        Ok(())
+    }
+
+    fn attach_other_threads(&mut self) -> anyhow::Result<()> {
+        let task_dir = fs::read_dir(format!("/proc/{}/task", self.pid))
+            .map_err(|err| anyhow!("unable to list threads of debuggee: {}", err))?;
+
+        for entry in task_dir {
+            let tid = Pid::from_raw(entry?.file_name().to_string_lossy().parse()?);
+            if self.threads.contains(&tid) {
+                continue;
+            }
+
+            debug!(tid = %tid, "attaching to thread");
+            if let Err(err) =
+                ptrace::seize(tid, self.ptrace_options).and_then(|()| ptrace::interrupt(tid))
+            {
+                warn!(error = box_err(err), tid = %tid, "unable to attach to thread");
+                continue;
+            }
+            self.start_thread(tid)?;
+        }
+
+        Ok(())
+    }
+
+    fn launch(launch_spec: &LaunchSpec) -> anyhow::Result<(Pid, CapturedOutput)> {
+        let span = debug_span!("launching child");
+        let _entered = span.enter();
+
+        info!("launching child process as debuggee");
+
+        // everything that allocates or may fail is prepared before forking
+        let child_args = launch_spec.c_args()?;
+        let child_env = launch_spec.c_env()?;
+        let (stdio, captured_output) = launch_spec.open_stdio()?;
+
+        let (error_reporting_pipe_read_end, error_reporting_pipe_write_end) =
+            pipe2(OFlag::O_CLOEXEC)?;
+
+        match unsafe { fork() }? {
+            ForkResult::Parent { child: pid } => {
+                drop(error_reporting_pipe_write_end);
+
+                let maybe_error_message = read_to_string(File::from(error_reporting_pipe_read_end))
+                    .map_err(|err| anyhow!("unable to read from error reporting pipe: {}", err))?;
+                if !maybe_error_message.is_empty() {
+                    return Err(anyhow!(
+                        "failed to launch debuggee: {}",
+                        maybe_error_message
+                    ));
+                }
+
+                Ok((pid, captured_output))
+            }
+            ForkResult::Child => {
+                drop(error_reporting_pipe_read_end);
+                Self::exec_traceme(
+                    launch_spec,
+                    &child_args,
+                    &child_env,
+                    stdio,
+                    error_reporting_pipe_write_end,
+                )
+            }
+        }
+    }
+
+    fn exec_traceme(
+        launch_spec: &LaunchSpec,
+        child_args: &[CString],
+        child_env: &[CString],
+        stdio: [Option<OwnedFd>; 3],
+        error_reporting_pipe_write_end: OwnedFd,
+    ) -> ! {
+        let span = debug_span!("child exec_traceme");
+        let _entered = span.entered();
+
+        let internal = || -> anyhow::Result<Infallible> {
+            debug!(?child_args);
+
+            for (target_fd, fd) in stdio.iter().enumerate() {
+                if let Some(fd) = fd {
+                    dup2(fd.as_raw_fd(), target_fd as RawFd)
+                        .map_err(|err| anyhow!("unable to redirect fd {}: {}", target_fd, err))?;
+                }
+            }
+
+            if let Some(cwd) = launch_spec.cwd_path() {
+                chdir(cwd).map_err(|err| {
+                    anyhow!("unable to change directory to {}: {}", cwd.display(), err)
+                })?;
+            }
+
+            if launch_spec.is_aslr_disabled() {
+                debug!("disabling aslr");
+                personality::set(personality::get()? | Persona::ADDR_NO_RANDOMIZE)
+                    .map_err(|err| anyhow!("unable to disable aslr: {}", err))?;
+            }
+
+            debug!("calling ptrace::traceme");
+            ptrace::traceme().map_err(|err| anyhow!("unable to set traceme: {}", err))?;
+
+            debug!("launching");
+            execvpe(&child_args[0], child_args, child_env)?;
+
+            unreachable!("POST EXEC")
+        };
+
+        let Err(err) = internal();
+        _ = File::from(error_reporting_pipe_write_end).write_all(err.to_string().as_bytes());
+
+        error!(
+            error = box_err(err),
+            child_args = ?launch_spec.args(),
+            "unable to spawn child",
+        );
+
+        exit(EXIT_FAILURE)
+    }
+    // Every thread is in the dump, the ones still running are stopped while it's written.
+    pub fn generate_core_dump<P: AsRef<Path>>(&mut self, path: P) -> anyhow::Result<()> {
+        info!(path = %path.as_ref().display(), "generating core dump");
+
+        if self.arch.name() != arch::native().name() {
+            Err(anyhow!(
+                "core dumps of {} processes are not supported",
+                self.arch.name()
+            ))?;
+        }
+        if !matches!(self.process_state, ProcessState::Stopped(_)) {
+            Err(anyhow!("debuggee must be stopped to generate a core dump"))?;
+        }
+
+        let mut stopped = Vec::new();
+        let result = self
+            .stop_running_threads(&mut stopped)
+            .and_then(|()| core_dump::write_core_dump(self, path.as_ref()));
+        let restarted = self.restart_threads(&stopped);
+        result.and(restarted)
+    }
+
+    pub fn checkpoint(&mut self) -> anyhow::Result<usize> {
+        let span = debug_span!(
+            "creating checkpoint",
+            pid = tracing::field::display(&self.pid),
+        );
+        let _entered = span.entered();
+
+        if !matches!(self.process_state, ProcessState::Stopped(_)) {
+            Err(anyhow!("debuggee must be stopped to create a checkpoint"))?;
+        }
+        // the fork is injected with the syscall numbers of the debugger's own architecture
+        if self.arch.name() != arch::native().name() {
+            Err(anyhow!(
+                "checkpoints of {} processes are not supported",
+                self.arch.name()
+            ))?;
+        }
+
+        let checkpoint_pid = fork_stopped_process(self.current_thread, self.ptrace_options)?;
+        // the fork is a copy of the debuggee, breakpoints and watched pages included. It's kept
+        // without them, restarting it puts in the ones there are by then.
+        if let Err(err) = self
+            .restore_code(
+                checkpoint_pid,
+                self.breakpoints.values().chain(self.internal_breakpoints()),
+            )
+            .and_then(|()| self.protect_pages(checkpoint_pid, &self.original_protections()))
+        {
+            kill_traced_process(checkpoint_pid);
+            return Err(err);
+        }
+
+        let id = self.next_checkpoint_id;
+        self.next_checkpoint_id += 1;
+        self.checkpoints.insert(id, checkpoint_pid);
+
+        info!(checkpoint = id, checkpoint_pid = %checkpoint_pid, "checkpoint created");
+
+        Ok(id)
+    }
+
+    pub fn restart_checkpoint(&mut self, id: usize) -> anyhow::Result<()> {
+        let span = debug_span!(
+            "restarting checkpoint",
+            pid = tracing::field::display(&self.pid),
+            checkpoint = id,
+        );
+        let _entered = span.entered();
+
+        let checkpoint_pid = *self
+            .checkpoints
+            .get(&id)
+            .ok_or(anyhow!("no checkpoint with id {}", id))?;
+
+        // fork the checkpoint again so it stays restorable
+        let new_pid = fork_stopped_process(checkpoint_pid, self.ptrace_options)?;
+
+        if self.process_state.is_alive() {
+            if self.should_terminate {
+                debug!("terminating current process");
+                kill_traced_process(self.pid);
+            } else {
+                debug!("detaching from current process");
+                // it goes on without the debugger, which would leave it to trap on them
+                if let Err(err) = self.restore_code(
+                    self.pid,
+                    self.breakpoints.values().chain(self.internal_breakpoints()),
+                ) {
+                    warn!(error = box_err(err), "unable to remove breakpoints");
+                }
+                if matches!(self.process_state, ProcessState::Stopped(_)) {
+                    if let Err(err) =
+                        self.protect_pages(self.current_thread, &self.original_protections())
+                    {
+                        warn!(error = box_err(err), "unable to restore page protections");
+                    }
+                    for tid in &self.threads {
+                        if let Err(err) = self.tracer.write_user(
+                            *tid,
+                            debug_register::offset_in_user_struct(DR7),
+                            0,
+                        ) {
+                            warn!(error = box_err(err), tid = %tid, "unable to clear debug registers");
+                        }
+                    }
+                }
+                if let Err(err) = ptrace::detach(self.pid, None) {
+                    warn!(
+                        error = box_err(err),
+                        "unable to detach from the debuggee process",
+                    )
+                }
+            }
+        }
+
+        self.pid = new_pid;
+        self.current_thread = new_pid;
+        self.threads = BTreeSet::from([new_pid]);
+        self.debug_registers.clear();
+        self.should_terminate = true;
+        self.watch_stepping = false;
+        self.syscall_writes.clear();
+        self.process_state = ProcessState::Stopped(StopReason::Initial);
+        self.forget_internal_breakpoints();
+        // debug registers aren't inherited over fork
+        self.sync_debug_registers()?;
+        // and the checkpoint has no breakpoints or watched pages, see checkpoint
+        for breakpoint in self
+            .breakpoints
+            .values_mut()
+            .filter(|breakpoint| breakpoint.is_armed())
+        {
+            *breakpoint = Breakpoint::new(breakpoint.id(), breakpoint.address());
+            if let Err(err) =
+                breakpoint.arm(&self.tracer, self.pid, self.arch.breakpoint_instruction())
+            {
+                warn!(
+                    breakpoint = breakpoint.id(),
+                    error = box_err(err),
+                    "unable to arm breakpoint"
+                );
+            }
+        }
+        let protected = self
+            .page_protections
+            .iter()
+            .map(|(page, prot)| (*page, prot & !libc::PROT_WRITE))
+            .collect::<Vec<_>>();
+        self.protect_pages(self.pid, &protected)?;
+        self.read_registers()?;
+
+        info!(checkpoint = id, pid = %self.pid, "restarted from checkpoint");
+
+        Ok(())
+    }
+}
+
+impl<T: Tracer> Debuggee<T> {
+    // Takes over a process that is already traced by `tracer` and about to report a stop.
+    pub fn from_tracer(tracer: T, pid: Pid) -> anyhow::Result<Self> {
+        Self::with_tracer(tracer, pid, false, DEFAULT_PTRACE_OPTIONS)
+    }
+
+    // Takes over a child forked while following both, stopped where it forked with the
+    // breakpoints of its parent.
+    pub fn from_fork_child(tracer: T, child: ForkChild) -> anyhow::Result<Self> {
+        let span = debug_span!("taking over forked child", pid = %child.pid);
+        let _entered = span.enter();
+
+        let mut debuggee = Self::stopped_with_tracer(
+            tracer,
+            child.pid,
+            child.should_terminate,
+            child.ptrace_options,
+        )?;
+        debuggee.arch = child.arch;
+        debuggee.breakpoints = child.breakpoints;
+        debuggee.next_breakpoint_id = child.next_breakpoint_id;
+        debuggee.disabled_breakpoints = child.disabled_breakpoints;
+        debuggee.breakpoint_groups = child.breakpoint_groups;
+        debuggee.breakpoint_conditions = child.breakpoint_conditions;
+        debuggee.tracepoints = child.tracepoints;
+
+        // debug registers aren't inherited over fork
+        for (id, address) in child.hardware_breakpoints {
+            let slot = DebugRegisterSlot {
+                owner: SlotOwner::Breakpoint(id),
+                address,
+                size: 1,
+                kind: WatchKind::Execute,
+            };
+            match debuggee.allocate_debug_registers(&[slot]) {
+                Ok(()) => {
+                    debuggee.hardware_breakpoints.insert(id, address);
+                }
+                Err(err) => warn!(
+                    breakpoint = id,
+                    error = box_err(err),
+                    "unable to set hardware breakpoint in forked child"
+                ),
+            }
+        }
+        debuggee.read_registers()?;
+
+        info!(pid = %debuggee.pid, "took over forked child");
+        Ok(debuggee)
+    }
+
+    fn with_tracer(
+        tracer: T,
+        pid: Pid,
+        should_terminate: bool,
+        ptrace_options: Options,
+    ) -> anyhow::Result<Self> {
+        let mut debuggee =
+            Self::stopped_with_tracer(tracer, pid, should_terminate, ptrace_options)?;
+
+        debuggee.update_process_state(true)?;
+        if let ProcessState::Stopped(_) = debuggee.process_state {
+            debuggee.process_state = ProcessState::Stopped(StopReason::Initial);
+        }
+
+        debug!("setting ptrace options");
+        debuggee
+            .tracer
+            .set_options(debuggee.pid, debuggee.ptrace_options)?;
+
+        Ok(debuggee)
+    }
+
+    // A debuggee for `pid` as if it were in its initial stop already.
+    fn stopped_with_tracer(
+        tracer: T,
+        pid: Pid,
+        should_terminate: bool,
+        ptrace_options: Options,
+    ) -> anyhow::Result<Self> {
+        Ok(Self {
+            tracer: MemoryCache::new(tracer),
+            pid,
+            current_thread: pid,
+            threads: BTreeSet::from([pid]),
+            thread_events: Vec::new(),
+            process_state: ProcessState::Stopped(StopReason::Initial),
+            arch: arch::native(),
+            should_terminate,
+            ptrace_options,
+            registers: None,
+            previous_registers: None,
+            debug_registers: BTreeMap::new(),
+            breakpoints: BTreeMap::new(),
+            hardware_breakpoints: BTreeMap::new(),
+            next_breakpoint_id: 1,
+            watchpoints: BTreeMap::new(),
+            next_watchpoint_id: 1,
+            expired_watchpoints: Vec::new(),
+            debug_register_allocator: DebugRegisterAllocator::new(),
+            watch_stepping: false,
+            page_protections: BTreeMap::new(),
+            syscall_writes: BTreeMap::new(),
+            pending_wait_status: None,
+            checkpoints: BTreeMap::new(),
+            next_checkpoint_id: 1,
+            cancellation_token: CancellationToken::new()?,
+            stop_log: StopLog::default(),
+            instruction_trace: InstructionTrace::default(),
+            recording_instructions: false,
+            anti_debug: AntiDebugConfig::default(),
+            anti_debug_attempts: Vec::new(),
+            spoofed_syscalls: BTreeSet::new(),
+            executable_ranges: Vec::new(),
+            auto_hardware_breakpoints: false,
+            disabled_breakpoints: BTreeSet::new(),
+            breakpoint_groups: BTreeMap::new(),
+            breakpoint_conditions: BTreeMap::new(),
+            tracepoints: BTreeMap::new(),
+            trace_buffer: TraceBuffer::default(),
+            trace_file: None,
+            pending_step: None,
+            group_stopped: BTreeMap::new(),
+            step_into_handlers: false,
+            heap_trace: HeapTrace::default(),
+            heap_entries: BTreeMap::new(),
+            heap_hooks: BTreeMap::new(),
+            heap_calls: Vec::new(),
+            coverage: None,
+            coverage_probes: BTreeMap::new(),
+            loader_hook: None,
+            loaded_modules: None,
+            pending_breakpoints: BTreeMap::new(),
+            module_events: Vec::new(),
+            symbol_index: None,
+            follow_fork: FollowFork::default(),
+            fork_children: Vec::new(),
+            symbol_table: RefCell::new(None),
+            line_table: RefCell::new(None),
+            symbol_lookups: RefCell::new(SymbolCache::default()),
+            line_lookups: RefCell::new(SymbolCache::default()),
+            captured_output: CapturedOutput::default(),
+        })
+    }
+
+    pub fn tracer(&self) -> &T {
+        self.tracer.inner()
+    }
+
+    // The output captured with `Stdio::Capture`, taken once by whoever forwards it.
+    pub fn take_captured_output(&mut self) -> CapturedOutput {
+        mem::take(&mut self.captured_output)
+    }
+
+    // Takes over a freshly traced thread: consumes its initial stop and lets it run.
+    fn start_thread(&mut self, tid: Pid) -> anyhow::Result<()> {
+        self.tracer.wait(tid, WaitPidFlag::__WALL)?;
+        self.tracer.set_options(tid, self.ptrace_options)?;
+        // nor over clone
+        self.sync_thread_debug_registers(tid)?;
+        self.continue_thread(tid, None)?;
+
+        self.threads.insert(tid);
+        self.thread_events.push(ThreadEvent::Created(tid));
+
+        Ok(())
+    }
+
+    // The child of a fork has a copy of every armed breakpoint. Following the parent only, it
+    // would die of SIGTRAP on the first one it runs into, so their code is restored in its memory
+    // before it's detached. Following both, the user's breakpoints are left in for whoever takes
+    // it over and only the debugger's own are taken out. Debug registers aren't inherited.
+    fn handle_fork(&mut self, tid: Pid, child: Pid) -> anyhow::Result<()> {
+        let shares_memory = self.fork_shares_memory(tid)?;
+        self.tracer.wait(child, WaitPidFlag::__WALL)?;
+
+        if shares_memory {
+            // restoring the code would take the breakpoints out of the debuggee as well
+            warn!(
+                child = %child,
+                "forked child shares the memory of the debuggee, detaching with the breakpoints in"
+            );
+            self.tracer.detach(child, None)?;
+            return Ok(());
+        }
+
+        match self.follow_fork {
+            FollowFork::Parent => {
+                self.restore_code(
+                    child,
+                    self.breakpoints.values().chain(self.internal_breakpoints()),
+                )?;
+                self.tracer.detach(child, None)?;
+                info!(child = %child, "detached from forked child");
+            }
+            FollowFork::Both => {
+                self.restore_code(child, self.internal_breakpoints())?;
+                self.fork_children.push(ForkChild {
+                    pid: child,
+                    arch: self.arch,
+                    should_terminate: self.should_terminate,
+                    ptrace_options: self.ptrace_options,
+                    breakpoints: self.breakpoints.clone(),
+                    hardware_breakpoints: self.hardware_breakpoints.clone(),
+                    next_breakpoint_id: self.next_breakpoint_id,
+                    disabled_breakpoints: self.disabled_breakpoints.clone(),
+                    breakpoint_groups: self.breakpoint_groups.clone(),
+                    breakpoint_conditions: self.breakpoint_conditions.clone(),
+                    tracepoints: self.tracepoints.clone(),
+                });
+                info!(child = %child, "keeping forked child");
+            }
+        }
+        Ok(())
+    }
+
+    // Whether the child of the fork `tid` is in shares the debuggee's memory. clone(CLONE_VM)
+    // with SIGCHLD as the exit signal is reported as a fork too.
+    fn fork_shares_memory(&self, tid: Pid) -> anyhow::Result<bool> {
+        let regs = self.tracer.get_regs(tid)?;
+        let args = self.arch.syscall_args(&regs);
+        let flags = match self.arch.syscall_number(&regs) as i64 {
+            libc::SYS_clone => args[0],
+            // the flags are the first field of struct clone_args
+            libc::SYS_clone3 => {
+                let mut flags = [0u8; 8];
+                self.tracer.read_memory(self.pid, args[0], &mut flags)?;
+                u64::from_ne_bytes(flags)
+            }
+            _ => 0,
+        };
+        Ok(flags & libc::CLONE_VM as u64 != 0)
+    }
+
+    // Breakpoints the debugger sets for itself, which no other debuggee knows about.
+    fn internal_breakpoints(&self) -> impl Iterator<Item = &Breakpoint> {
+        self.heap_hooks
+            .values()
+            .chain(self.coverage_probes.values())
+            .chain(self.loader_hook.iter().map(|hook| &hook.breakpoint))
+            .chain(
+                self.pending_step
+                    .iter()
+                    .filter_map(|step| step.handler_return.as_ref()),
+            )
+    }
+
+    // For a process whose memory doesn't have them, the hooks are armed again on the next resume.
+    fn forget_internal_breakpoints(&mut self) {
+        self.pending_step = None;
+        self.heap_calls.clear();
+        for hook in self.heap_hooks.values_mut() {
+            *hook = Breakpoint::new(0, hook.address());
+        }
+        for probe in self.coverage_probes.values_mut() {
+            *probe = Breakpoint::new(0, probe.address());
+        }
+        self.loader_hook = None;
+        self.loaded_modules = None;
+    }
+
+    // Writes the code of armed `breakpoints` back into the memory of `pid`, leaving their
+    // bookkeeping alone.
+    fn restore_code<'a>(
+        &self,
+        pid: Pid,
+        breakpoints: impl IntoIterator<Item = &'a Breakpoint>,
+    ) -> anyhow::Result<()> {
+        for breakpoint in breakpoints {
+            if let Some(saved_code) = breakpoint.saved_code() {
+                self.tracer
+                    .write_memory(pid, breakpoint.address().as_u64(), saved_code)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn follow_fork(&self) -> FollowFork {
+        self.follow_fork
+    }
+
+    pub fn set_follow_fork(&mut self, follow_fork: FollowFork) {
+        self.follow_fork = follow_fork;
+    }
+
+    // Children forked while following both since the last call, for `from_fork_child`.
+    pub fn take_fork_children(&mut self) -> Vec<ForkChild> {
+        mem::take(&mut self.fork_children)
+    }
+
+    pub fn pid(&self) -> Pid {
+        self.pid
+    }
+
+    pub fn current_thread(&self) -> Pid {
+        self.current_thread
+    }
+
+    pub fn threads(&self) -> &BTreeSet<Pid> {
+        &self.threads
+    }
+
+    // Stops the threads still running while the current one is stopped, adding them to `stopped`.
+    // A thread that stops for something else first keeps that stop to be reported on the next
+    // wait, there's only room for one.
+    fn stop_running_threads(&mut self, stopped: &mut Vec<Pid>) -> anyhow::Result<()> {
+        let running = self
+            .threads
+            .iter()
+            .copied()
+            .filter(|tid| *tid != self.current_thread && self.tracer.get_regs(*tid).is_err())
+            .collect::<Vec<_>>();
+        for tid in running {
+            if self.pending_wait_status.is_some() {
+                Err(anyhow!(
+                    "unable to stop thread {} while another stop is waiting to be reported",
+                    tid
+                ))?;
+            }
+            debug!(tid = %tid, "stopping thread");
+            // PTRACE_INTERRUPT only works on seized threads
+            if self.tracer.interrupt(tid).is_err() {
+                Errno::result(unsafe {
+                    libc::syscall(
+                        libc::SYS_tgkill,
+                        self.pid.as_raw(),
+                        tid.as_raw(),
+                        libc::SIGSTOP,
+                    )
+                })?;
+            }
+            match self.tracer.wait(tid, WaitPidFlag::__WALL)? {
+                WaitStatus::PtraceEvent(_, _, libc::PTRACE_EVENT_STOP)
+                | WaitStatus::Stopped(_, Signal::SIGSTOP) => stopped.push(tid),
+                wait_status => {
+                    debug!(tid = %tid, "thread stopped for something else");
+                    self.pending_wait_status = Some(wait_status);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Lets threads stopped by `stop_running_threads` go on as they were.
+    fn restart_threads(&self, threads: &[Pid]) -> anyhow::Result<()> {
+        for tid in threads {
+            if self.group_stopped.get(tid) == Some(&true) {
+                self.tracer.listen(*tid)?;
+            } else {
+                self.continue_thread(*tid, None)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Stops a running debuggee, the stop still has to be waited for.
+    pub fn interrupt(&self) -> anyhow::Result<()> {
+        if !matches!(self.process_state, ProcessState::Running) {
+            return Ok(());
+        }
+        // PTRACE_INTERRUPT only works on seized threads
+        self.tracer
+            .interrupt(self.pid)
+            .or_else(|_| self.tracer.kill(self.pid, Signal::SIGSTOP))?;
+        Ok(())
+    }
+
+    // Where a stopped thread is, then the return addresses the frame pointers lead to.
+    pub fn backtrace(&self, thread: Pid) -> anyhow::Result<Vec<VirtAddr>> {
+        let regs = self.tracer.get_regs(thread)?;
+        Ok(self.frame_pointer_backtrace(&regs, VirtAddr::new(self.arch.pc(&regs))))
+    }
+
+    pub fn take_thread_events(&mut self) -> Vec<ThreadEvent> {
+        mem::take(&mut self.thread_events)
+    }
+
+    pub fn take_module_events(&mut self) -> Vec<ModuleEvent> {
+        mem::take(&mut self.module_events)
+    }
+
+    pub fn process_state(&self) -> ProcessState {
+        self.process_state.clone()
+    }
+
+    pub fn arch(&self) -> &'static dyn Arch {
+        self.arch
+    }
+
+    pub fn pointer_width(&self) -> PointerWidth {
+        self.arch.pointer_width()
+    }
+
+    pub fn registers(&self) -> Option<&Registers> {
+        self.registers.as_ref()
+    }
+
+    pub fn registers_mut(&mut self) -> Option<&mut Registers> {
+        self.registers.as_mut()
+    }
+
+    // Keeps the registers of the current thread as they are now, for `previous_registers` after
+    // the next stop. Callers do it right before they resume the debuggee, once for a step made
+    // of many.
+    pub fn remember_registers(&mut self) {
+        self.previous_registers = self
+            .registers
+            .clone()
+            .map(|registers| (self.current_thread, registers));
+    }
+
+    // What `remember_registers` kept, None if it was for another thread than the current one.
+    pub fn previous_registers(&self) -> Option<&Registers> {
+        self.previous_registers
+            .as_ref()
+            .filter(|(tid, _)| *tid == self.current_thread)
+            .map(|(_, registers)| registers)
+    }
+
+    // Writes a general purpose or floating point register of the current thread. Debug
+    // registers are managed by the breakpoints and watchpoints using them.
+    pub fn write_register(
+        &mut self,
+        register: Register,
+        value: RegisterValue,
+    ) -> anyhow::Result<()> {
+        if register.kind() == RegisterKind::Debug {
+            Err(anyhow!("writing ${} is not supported", register.name()))?;
+        }
+
+        self.registers
+            .as_mut()
+            .ok_or(anyhow!("no register info available"))?
+            .write_register(register, value)?;
+        self.flush_registers()
+    }
+
+    // Writes back what was changed through `registers_mut` since the registers were read. The
+    // debuggee does it before it runs again. Debug registers written this way hold until the
+    // breakpoints and watchpoints using them change.
+    pub fn flush_registers(&mut self) -> anyhow::Result<()> {
+        let Some(registers) = self.registers.as_mut() else {
+            return Ok(());
+        };
+        if registers.dirty().is_empty() {
+            return Ok(());
+        }
+
+        registers.flush(&self.tracer, self.current_thread)?;
+        self.read_registers()
+    }
+
+    // Moves the pc of the current thread without executing anything. Without symbols the
+    // mapping stands in for the function, jumping out of it takes `force`.
+    pub fn jump(&mut self, address: VirtAddr, force: bool) -> anyhow::Result<()> {
+        if !matches!(self.process_state, ProcessState::Stopped(_)) {
+            Err(anyhow!("debuggee must be stopped to jump"))?;
+        }
+
+        let mut regs = self.tracer.get_regs(self.current_thread)?;
+        let pc = VirtAddr::new(self.arch.pc(&regs));
+        if !force {
+            let memory_map = self.memory_map()?;
+            let target = memory_map
+                .region_containing(address)
+                .filter(|region| region.permissions.execute)
+                .ok_or(anyhow!("{} is not in executable memory", address))?;
+            if !(target.start..target.end).contains(&pc) {
+                Err(anyhow!(
+                    "{} is outside of the code {} is in, use --force to jump anyway",
+                    address,
+                    pc
+                ))?;
+            }
+        }
+
+        debug!(from = %pc, to = %address, "jumping");
+        self.arch.set_pc(&mut regs, address.as_u64());
+        self.tracer.set_regs(self.current_thread, regs)?;
+        self.read_registers()
+    }
+
+    fn wait_for_any_thread(&mut self, blocking: bool) -> nix::Result<WaitStatus> {
+        let flags = if blocking {
+            WaitPidFlag::__WALL
+        } else {
+            WaitPidFlag::__WALL | WaitPidFlag::WNOHANG
+        };
+
+        if let Some(wait_status) = self.pending_wait_status.take() {
+            return Ok(wait_status);
+        }
+
+        if self.threads.len() == 1 {
+            return self.tracer.wait(self.pid, flags);
+        }
+
+        // there is no way to wait for a specific set of threads, and waiting for all children
+        // would steal events from processes that aren't ours, so poll the known ones instead
+        loop {
+            for tid in &self.threads {
+                match self
+                    .tracer
+                    .wait(*tid, WaitPidFlag::__WALL | WaitPidFlag::WNOHANG)
+                {
+                    Ok(WaitStatus::StillAlive) => continue,
+                    Err(Errno::ECHILD) if *tid != self.pid => {
+                        return Ok(WaitStatus::Exited(*tid, 0))
+                    }
+                    result => return result,
+                }
+            }
+
+            if !blocking {
+                return Ok(WaitStatus::StillAlive);
+            }
+
+            sleep(THREAD_POLL_INTERVAL);
+        }
+    }
+
+    // Token that aborts the current or next `wait_for_stop` when cancelled.
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.cancellation_token.clone()
+    }
+
+    // Replaces the token, so that one token (e.g. cancelled on SIGINT) serves every debuggee.
+    pub fn set_cancellation_token(&mut self, token: CancellationToken) {
+        self.cancellation_token = token;
+    }
+
+    // Waits until the debuggee is no longer running. Unlike a blocking `update_process_state`,
+    // this gives up after `timeout` or once the cancellation token is cancelled, leaving the
+    // debuggee running.
+    pub fn wait_for_stop(&mut self, timeout: Option<Duration>) -> anyhow::Result<WaitOutcome> {
+        let span = debug_span!(
+            "waiting for debuggee to stop",
+            pid = tracing::field::display(&self.pid),
+            timeout = ?timeout,
+        );
+        let _entered = span.entered();
+
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+
+        // SIGCHLD is blocked and read from a signalfd, so one arriving between checking the
+        // debuggee and going to sleep still wakes the wait up
+        let mut sigchld = SigSet::empty();
+        sigchld.add(Signal::SIGCHLD);
+        let previous_mask = sigchld.thread_swap_mask(SigmaskHow::SIG_BLOCK)?;
+        let outcome =
+            SignalFd::with_flags(&sigchld, SfdFlags::SFD_NONBLOCK | SfdFlags::SFD_CLOEXEC)
+                .map_err(anyhow::Error::from)
+                .and_then(|sigchld| self.wait_for_stop_until(&sigchld, deadline));
+        previous_mask.thread_set_mask()?;
+        outcome
+    }
+
+    fn wait_for_stop_until(
+        &mut self,
+        sigchld: &SignalFd,
+        deadline: Option<Instant>,
+    ) -> anyhow::Result<WaitOutcome> {
+        loop {
+            self.update_process_state(false)?;
+            if !self.process_stop()? {
+                self.resume()?;
+                continue;
+            }
+            if !matches!(self.process_state, ProcessState::Running) {
+                return Ok(WaitOutcome::StateChanged(self.process_state.clone()));
+            }
+
+            if self.cancellation_token.take() {
+                debug!("wait cancelled");
+                return Ok(WaitOutcome::Cancelled);
+            }
+            let now = Instant::now();
+            if deadline.is_some_and(|deadline| now >= deadline) {
+                debug!("wait timed out");
+                return Ok(WaitOutcome::TimedOut);
+            }
+
+            let sleep_for = deadline.map_or(WAIT_RECHECK_INTERVAL, |deadline| {
+                (deadline - now).min(WAIT_RECHECK_INTERVAL)
+            });
+            let mut fds = [
+                PollFd::new(sigchld.as_fd(), PollFlags::POLLIN),
+                PollFd::new(self.cancellation_token.wakeup_fd(), PollFlags::POLLIN),
+            ];
+            // rounded up, a zero timeout would spin until the deadline
+            let timeout = PollTimeout::from(sleep_for.as_millis().max(1) as u16);
+            match poll(&mut fds, timeout) {
+                // a signal handler (e.g. one cancelling the token) ran
+                Ok(_) | Err(Errno::EINTR) => {}
+                Err(err) => Err(err)?,
+            }
+            while let Ok(Some(_)) = sigchld.read_signal() {}
+        }
+    }
+
+    pub fn update_process_state(&mut self, blocking: bool) -> anyhow::Result<()> {
+        let span = debug_span!(
+            "waiting for debuggee state change",
+            pid = tracing::field::display(&self.pid),
+        );
+        let _entered = span.entered();
+
+        let (tid, process_state) = loop {
+            let wait_status = self.wait_for_any_thread(blocking);
+
+            break match wait_status {
+                Ok(WaitStatus::PtraceEvent(tid, _, libc::PTRACE_EVENT_CLONE)) => {
+                    let new_tid = Pid::from_raw(self.tracer.get_event(tid)? as libc::pid_t);
+                    debug!(tid = %tid, new_tid = %new_tid, "thread created");
+                    self.start_thread(new_tid)?;
+                    self.continue_thread(tid, None)?;
+                    continue;
+                }
+                Ok(WaitStatus::PtraceEvent(tid, _, libc::PTRACE_EVENT_FORK)) => {
+                    let child = Pid::from_raw(self.tracer.get_event(tid)? as libc::pid_t);
+                    debug!(tid = %tid, child = %child, "process forked");
+                    self.handle_fork(tid, child)?;
+                    self.continue_thread(tid, None)?;
+                    continue;
+                }
+                Ok(WaitStatus::PtraceEvent(tid, signal, libc::PTRACE_EVENT_STOP))
+                    if stop_reason::is_stop_signal(signal) =>
+                {
+                    if self.note_group_stop(tid, true)? {
+                        continue;
+                    }
+                    (tid, ProcessState::Stopped(StopReason::GroupStop { signal }))
+                }
+                Ok(WaitStatus::PtraceEvent(tid, Signal::SIGTRAP, libc::PTRACE_EVENT_STOP))
+                    if self.group_stopped.contains_key(&tid) =>
+                {
+                    // SIGCONT ended the group-stop the thread was listening in
+                    debug!(tid = %tid, "group stop ended");
+                    self.group_stopped.remove(&tid);
+                    if self.is_stepping(tid) {
+                        self.start_step(tid)?;
+                    } else {
+                        self.continue_thread(tid, None)?;
+                    }
+                    continue;
+                }
+                Ok(WaitStatus::PtraceEvent(tid, _, event)) => (
+                    tid,
+                    ProcessState::Stopped(StopReason::PtraceEvent(event.into())),
+                ),
+                Ok(WaitStatus::PtraceSyscall(tid)) if self.follows_syscalls() => {
+                    if self.anti_debug.detect {
+                        self.inspect_syscall(tid)?;
+                    }
+                    match self.syscall_watchpoint_hit(tid)? {
+                        Some(reason) => (tid, ProcessState::Stopped(reason)),
+                        None => continue,
+                    }
+                }
+                Ok(WaitStatus::PtraceSyscall(tid)) => {
+                    (tid, ProcessState::Stopped(self.syscall_stop_reason(tid)?))
+                }
+                Ok(WaitStatus::Stopped(tid, signal))
+                    if stop_reason::is_stop_signal(signal)
+                        && matches!(self.tracer.get_siginfo(tid), Err(Errno::EINVAL)) =>
+                {
+                    // without PTRACE_SEIZE a group-stop looks like the delivery of its signal,
+                    // except that there's no siginfo to get
+                    if self.note_group_stop(tid, false)? {
+                        continue;
+                    }
+                    (tid, ProcessState::Stopped(StopReason::GroupStop { signal }))
+                }
+                Ok(WaitStatus::Exited(tid, _)) | Ok(WaitStatus::Signaled(tid, _, _))
+                    if tid != self.pid =>
+                {
+                    debug!(tid = %tid, "thread exited");
+                    self.threads.remove(&tid);
+                    self.debug_registers.remove(&tid);
+                    self.group_stopped.remove(&tid);
+                    self.forget_heap_calls(tid)?;
+                    self.syscall_writes.remove(&tid);
+                    self.thread_events.push(ThreadEvent::Exited(tid));
+                    if self.current_thread == tid {
+                        self.current_thread = self.pid;
+                    }
+                    continue;
+                }
+                Ok(WaitStatus::Exited(tid, status_code)) => {
+                    (tid, ProcessState::Exited(Some(status_code)))
+                }
+                Ok(WaitStatus::Signaled(tid, signal, _)) => (tid, ProcessState::Terminated(signal)),
+                Ok(WaitStatus::Stopped(tid, Signal::SIGTRAP))
+                    if self.is_loader_hook_stop(tid)? =>
+                {
+                    if self.loader_hook_hit(tid)? {
+                        continue;
+                    }
+                    (
+                        tid,
+                        ProcessState::Stopped(StopReason::Signal(
+                            self.signal_info(tid, Signal::SIGTRAP),
+                        )),
+                    )
+                }
+                Ok(WaitStatus::Stopped(tid, Signal::SIGTRAP)) if self.is_heap_hook_stop(tid)? => {
+                    if self.heap_hook_hit(tid)? {
+                        continue;
+                    }
+                    // a breakpoint of the user is there as well
+                    (
+                        tid,
+                        ProcessState::Stopped(StopReason::Signal(
+                            self.signal_info(tid, Signal::SIGTRAP),
+                        )),
+                    )
+                }
+                Ok(WaitStatus::Stopped(tid, Signal::SIGTRAP))
+                    if self.is_coverage_probe_stop(tid)? =>
+                {
+                    self.coverage_probe_hit(tid)?;
+                    continue;
+                }
+                Ok(WaitStatus::Stopped(tid, Signal::SIGTRAP))
+                    if self.watch_stepping && tid == self.current_thread =>
+                {
+                    let info = self.signal_info(tid, Signal::SIGTRAP);
+                    if !info.is_single_step() {
+                        (tid, ProcessState::Stopped(StopReason::Signal(info)))
+                    } else if let Some(reason) = self.software_watchpoint_hit()? {
+                        (tid, ProcessState::Stopped(reason))
+                    } else {
+                        self.tracer.step(tid, None)?;
+                        continue;
+                    }
+                }
+                Ok(WaitStatus::Stopped(tid, Signal::SIGSEGV))
+                    if !self.page_protections.is_empty() =>
+                {
+                    let info = self.signal_info(tid, Signal::SIGSEGV);
+                    match info.fault_address.filter(|_| info.code == SEGV_ACCERR) {
+                        Some(address)
+                            if self
+                                .page_protections
+                                .contains_key(&address.align_down(PAGE_SIZE)) =>
+                        {
+                            match self.step_over_page_fault(tid, address)? {
+                                Some(reason) => (tid, ProcessState::Stopped(reason)),
+                                None => continue,
+                            }
+                        }
+                        _ => (tid, ProcessState::Stopped(StopReason::Signal(info))),
+                    }
+                }
+                Ok(WaitStatus::Stopped(tid, signal))
+                    if self.is_pending_step_stop(tid, signal)? =>
+                {
+                    match self.continue_step(tid, signal)? {
+                        Some(reason) => (tid, ProcessState::Stopped(reason)),
+                        None => continue,
+                    }
+                }
+                Ok(WaitStatus::Stopped(tid, signal)) => (
+                    tid,
+                    ProcessState::Stopped(StopReason::Signal(self.signal_info(tid, signal))),
+                ),
+                Ok(WaitStatus::Continued(tid)) => (tid, ProcessState::Running),
+                // nothing happened since the last wait
+                Ok(WaitStatus::StillAlive) => return Ok(()),
+                Err(Errno::ECHILD) => (self.pid, ProcessState::Exited(None)),
+                Err(err) => Err(err)?,
+            };
+        };
+
+        self.process_state = process_state;
+        if !matches!(self.process_state, ProcessState::Running) {
+            self.watch_stepping = false;
+        }
+
+        match self.process_state {
+            ProcessState::Stopped(_) => {
+                self.current_thread = tid;
+                self.disarm_heap_hooks()?;
+                self.disarm_coverage_probes()?;
+                self.disarm_loader_hook()?;
+                self.sync_thread_debug_registers(tid)?;
+                self.read_registers()?;
+                self.classify_trap()?;
+                self.expire_scoped_watchpoints()?;
+                self.record_stop()?;
+            }
+            ProcessState::Exited(_) | ProcessState::Terminated(_) => {
+                self.threads.clear();
+                self.current_thread = self.pid;
+                self.group_stopped.clear();
+                self.forget_internal_breakpoints();
+            }
+            ProcessState::Running => (),
+        }
+
+        Ok(())
+    }
+
+    fn record_stop(&mut self) -> anyhow::Result<()> {
+        let ProcessState::Stopped(reason) = &self.process_state else {
+            return Ok(());
+        };
+        let pc = self.arch.pc(&self.tracer.get_regs(self.current_thread)?);
+        self.stop_log
+            .record(self.current_thread, reason.clone(), VirtAddr::new(pc));
+        Ok(())
+    }
+
+    pub fn stop_log(&self) -> &StopLog {
+        &self.stop_log
+    }
+
+    pub fn stop_log_mut(&mut self) -> &mut StopLog {
+        &mut self.stop_log
+    }
+
+    pub fn instruction_trace(&self) -> &InstructionTrace {
+        &self.instruction_trace
+    }
+
+    pub fn is_recording_instructions(&self) -> bool {
+        self.recording_instructions
+    }
+
+    // Starts a new recording in place of the last one, which `run_recording` adds to.
+    pub fn start_recording(&mut self, trace: InstructionTrace) {
+        self.instruction_trace = trace;
+        self.recording_instructions = true;
+    }
+
+    // The recording is kept until the next one starts.
+    pub fn stop_recording(&mut self) {
+        self.recording_instructions = false;
+    }
+
+    pub fn anti_debug(&self) -> AntiDebugConfig {
+        self.anti_debug
+    }
+
+    // Takes effect the next time the debuggee is resumed.
+    pub fn set_anti_debug(&mut self, config: AntiDebugConfig) -> anyhow::Result<()> {
+        // syscalls are recognized by the numbers of the debugger's own architecture
+        if config.detect && self.arch.name() != arch::native().name() {
+            Err(anyhow!(
+                "detecting anti-debugging in {} processes is not supported",
+                self.arch.name()
+            ))?;
+        }
+        self.anti_debug = config;
+        if !config.spoof_traceme {
+            self.spoofed_syscalls.clear();
+        }
+        Ok(())
+    }
+
+    pub fn anti_debug_attempts(&self) -> &[AntiDebugAttempt] {
+        &self.anti_debug_attempts
+    }
+
+    fn report_anti_debug_attempt(&mut self, attempt: AntiDebugAttempt) {
+        warn!(attempt = %attempt, "anti-debugging attempt");
+        self.anti_debug_attempts.push(attempt);
+    }
+
+    // Lets a stopped thread run, through syscall stops while looking for anti-debugging checks.
+    // Syscalls are stopped at while looking for anti-debugging checks, and while there are pages
+    // protected for watchpoints for the kernel to write to.
+    fn follows_syscalls(&self) -> bool {
+        self.anti_debug.detect || !self.page_protections.is_empty()
+    }
+
+    fn continue_thread(&self, tid: Pid, signal: Option<Signal>) -> nix::Result<()> {
+        if self.follows_syscalls() {
+            self.tracer.syscall(tid, signal)
+        } else {
+            self.tracer.cont(tid, signal)
+        }
+    }
+
+    // A stop signal the debuggee stopped for is delivered when resuming it, so the process still
+    // stops as job control demands instead of the signal being swallowed.
+    fn job_control_signal(&self) -> Option<Signal> {
+        match &self.process_state {
+            ProcessState::Stopped(StopReason::Signal(info))
+                if stop_reason::is_stop_signal(info.signal) =>
+            {
+                Some(info.signal)
+            }
+            _ => None,
+        }
+    }
+
+    // The signal the debuggee stopped for, to hand to `resume_with_signal` so the debuggee gets
+    // it as if it wasn't traced.
+    pub fn stop_signal(&self) -> Option<Signal> {
+        match &self.process_state {
+            ProcessState::Stopped(StopReason::Signal(info)) => Some(info.signal),
+            _ => None,
+        }
+    }
+
+    pub fn is_group_stopped(&self) -> bool {
+        !self.group_stopped.is_empty()
+    }
+
+    // Remembers that `tid` is in group-stop. Returns true if the group-stop has been reported
+    // already, for another thread of the group or an earlier trap of the same one. Such a thread
+    // is left waiting for SIGCONT if it's seized, and let go otherwise.
+    fn note_group_stop(&mut self, tid: Pid, seized: bool) -> anyhow::Result<bool> {
+        if self.group_stopped.is_empty() {
+            debug!(tid = %tid, seized, "group stop");
+            self.group_stopped.insert(tid, seized);
+            return Ok(false);
+        }
+
+        if seized {
+            self.group_stopped.insert(tid, true);
+            self.tracer.listen(tid)?;
+        } else {
+            self.continue_thread(tid, None)?;
+        }
+        Ok(true)
+    }
+
+    // Looks at a syscall stop for ptrace(PTRACE_TRACEME) and opens of TracerPid files.
+    fn inspect_syscall(&mut self, tid: Pid) -> anyhow::Result<()> {
+        let mut regs = self.tracer.get_regs(tid)?;
+        if !self.arch.is_syscall_entry(&regs) {
+            if self.spoofed_syscalls.remove(&tid) {
+                debug!(tid = %tid, "faking ptrace(PTRACE_TRACEME) success");
+                self.arch.set_syscall_return_value(&mut regs, 0);
+                self.tracer.set_regs(tid, regs)?;
+            }
+            return Ok(());
+        }
+
+        let number = self.arch.syscall_number(&regs) as libc::c_long;
+        let args = self.arch.syscall_args(&regs);
+        let path = match number {
+            libc::SYS_ptrace if args[0] == libc::PTRACE_TRACEME as u64 => {
+                let spoofed = self.anti_debug.spoof_traceme;
+                if spoofed {
+                    self.spoofed_syscalls.insert(tid);
+                }
+                self.report_anti_debug_attempt(AntiDebugAttempt::TraceMe {
+                    thread: tid,
+                    spoofed,
+                });
+                return Ok(());
+            }
+            libc::SYS_openat => args[1],
+            #[cfg(target_arch = "x86_64")]
+            libc::SYS_open => args[0],
+            _ => return Ok(()),
+        };
+
+        match format::read_c_string(self, path) {
+            Ok(path) => {
+                let path = String::from_utf8_lossy(&path);
+                if anti_debug::is_status_path(&path) {
+                    self.report_anti_debug_attempt(AntiDebugAttempt::StatusRead {
+                        thread: tid,
+                        path: path.into_owned(),
+                    });
+                }
+            }
+            Err(err) => debug!(error = box_err(err), "unable to read opened path"),
+        }
+
+        Ok(())
+    }
+
+    fn signal_info(&self, tid: Pid, signal: Signal) -> SignalInfo {
+        match self.tracer.get_siginfo(tid) {
+            Ok(info) => SignalInfo::from_siginfo(signal, &info),
+            Err(err) => {
+                warn!(error = box_err(err), tid = %tid, "unable to get siginfo");
+                SignalInfo::from_signal(signal)
+            }
+        }
+    }
+
+    fn syscall_stop_reason(&self, tid: Pid) -> anyhow::Result<StopReason> {
+        let regs = self.tracer.get_regs(tid)?;
+        let number = self.arch.syscall_number(&regs);
+
+        Ok(if self.arch.is_syscall_entry(&regs) {
+            StopReason::SyscallEntry { number }
+        } else {
+            StopReason::SyscallExit {
+                number,
+                return_value: self.arch.syscall_return_value(&regs),
+            }
+        })
+    }
+
+    // Turns a SIGTRAP caused by the debugger itself into a breakpoint or step stop.
+    fn classify_trap(&mut self) -> anyhow::Result<()> {
+        let ProcessState::Stopped(StopReason::Signal(info)) = self.process_state else {
+            return Ok(());
+        };
+        if info.signal != Signal::SIGTRAP {
+            return Ok(());
+        }
+
+        if let Some(reason) = self.debug_register_stop_reason()? {
+            self.process_state = ProcessState::Stopped(reason);
+            return Ok(());
+        }
+
+        let mut regs = self.tracer.get_regs(self.current_thread)?;
+        let address =
+            VirtAddr::new(self.arch.pc(&regs)).wrapping_sub(self.arch.breakpoint_pc_offset());
+
+        let Some(id) = self
+            .breakpoints
+            .values()
+            .find(|breakpoint| breakpoint.address() == address && breakpoint.is_armed())
+            .map(Breakpoint::id)
+        else {
+            if info.is_single_step() {
+                self.process_state = ProcessState::Stopped(StopReason::StepComplete);
+            } else if self.anti_debug.detect {
+                self.report_anti_debug_attempt(AntiDebugAttempt::SelfTrap {
+                    thread: self.current_thread,
+                    sender: info.sender,
+                });
+            }
+            return Ok(());
+        };
+
+        debug!(breakpoint = id, "breakpoint hit");
+        self.note_coverage_hit(self.current_thread, address)?;
+
+        if self.arch.breakpoint_pc_offset() != 0 {
+            // rewind to the start of the replaced instruction
+            self.arch.set_pc(&mut regs, address.as_u64());
+            self.tracer.set_regs(self.current_thread, regs)?;
+            self.read_registers()?;
+        }
+
+        self.process_state = ProcessState::Stopped(StopReason::Breakpoint { id });
+
+        Ok(())
+    }
+
+    // Looks up the hardware breakpoint or watchpoint DR6 reports as hit, if any.
+    fn debug_register_stop_reason(&mut self) -> anyhow::Result<Option<StopReason>> {
+        if self.debug_register_allocator.free_slots() == debug_register::DEBUG_REGISTER_SLOTS {
+            return Ok(None);
+        }
+
+        let tid = self.current_thread;
+        let dr6 = self
+            .tracer
+            .read_user(tid, debug_register::offset_in_user_struct(DR6))? as u64;
+        let Some(slot) = debug_register::triggered_slots(dr6)
+            .find_map(|index| self.debug_register_allocator.slot(index).copied())
+        else {
+            return Ok(None);
+        };
+        // the processor never clears DR6
+        self.tracer
+            .write_user(tid, debug_register::offset_in_user_struct(DR6), 0)?;
+
+        debug!(slot = %slot, "debug register hit");
+
+        Ok(Some(match slot.owner {
+            SlotOwner::Breakpoint(id) => StopReason::Breakpoint { id },
+            SlotOwner::Watchpoint(id) => StopReason::Watchpoint {
+                id,
+                address: self
+                    .watchpoints
+                    .get(&id)
+                    .map_or(slot.address, Watchpoint::address),
+            },
+        }))
+    }
+
+    // Instruction breakpoints are faults, so the pc still points at the instruction. The resume
+    // flag lets it execute once without hitting the breakpoint again.
+    fn step_over_hardware_breakpoint(&mut self) -> anyhow::Result<()> {
+        let ProcessState::Stopped(StopReason::Breakpoint { id }) = self.process_state else {
+            return Ok(());
+        };
+        if !self.hardware_breakpoints.contains_key(&id) {
+            return Ok(());
+        }
+
+        let mut regs = self.tracer.get_regs(self.current_thread)?;
+        regs.eflags |= debug_register::RESUME_FLAG;
+        self.tracer.set_regs(self.current_thread, regs)?;
+
+        Ok(())
+    }
+
+    // Returns false if the debuggee stopped for another reason while stepping over the breakpoint,
+    // in which case the stop is left for the next update_process_state to pick up.
+    fn step_over_breakpoint(&mut self) -> anyhow::Result<bool> {
+        let pc = VirtAddr::new(self.arch.pc(&self.tracer.get_regs(self.current_thread)?));
+
+        let Some(breakpoint) = self
+            .breakpoints
+            .values_mut()
+            .find(|breakpoint| breakpoint.address() == pc && breakpoint.is_armed())
+        else {
+            return Ok(true);
+        };
+
+        debug!(breakpoint = breakpoint.id(), "stepping over breakpoint");
+
+        let id = breakpoint.id();
+        breakpoint.disarm(&self.tracer, self.pid)?;
+        let wait_status = self.single_step(self.current_thread)?;
+
+        if let WaitStatus::Stopped(..) = wait_status {
+            if let Some(breakpoint) = self.breakpoints.get_mut(&id) {
+                breakpoint.arm(&self.tracer, self.pid, self.arch.breakpoint_instruction())?;
+            }
+        }
+
+        if let WaitStatus::Stopped(_, Signal::SIGTRAP) = wait_status {
+            Ok(true)
+        } else {
+            self.pending_wait_status = Some(wait_status);
+            Ok(false)
+        }
+    }
+
+    fn single_step(&mut self, tid: Pid) -> anyhow::Result<WaitStatus> {
+        self.tracer.step(tid, None)?;
+        Ok(self.tracer.wait(tid, WaitPidFlag::__WALL)?)
+    }
+
+    pub fn step_into_handlers(&self) -> bool {
+        self.step_into_handlers
+    }
+
+    // Whether a step interrupted by a signal stops at the first instruction of its handler, instead
+    // of running the handler and then stepping the interrupted instruction.
+    pub fn set_step_into_handlers(&mut self, enabled: bool) {
+        self.step_into_handlers = enabled;
+    }
+
+    // Executes a single instruction of the current thread, the stop is waited for like after
+    // `resume`.
+    pub fn step_instruction(&mut self) -> anyhow::Result<()> {
+        if !matches!(self.process_state, ProcessState::Stopped(_)) {
+            Err(anyhow!("debuggee must be stopped to step"))?;
+        }
+        self.flush_registers()?;
+        // a step left unfinished in a signal handler is superseded
+        self.cancel_pending_step()?;
+
+        let tid = self.current_thread;
+        // stepping a thread in group-stop runs it regardless
+        self.group_stopped.remove(&tid);
+        self.step_over_hardware_breakpoint()?;
+        let regs = self.tracer.get_regs(tid)?;
+        self.pending_step = Some(PendingStep {
+            thread: tid,
+            pc: VirtAddr::new(self.arch.pc(&regs)),
+            stack_pointer: VirtAddr::new(self.arch.stack_pointer(&regs)),
+            handler_return: None,
+        });
+        self.start_step(tid)?;
+        self.process_state = ProcessState::Running;
+
+        debug!(tid = %tid, "stepping instruction");
+
+        Ok(())
+    }
+
+    // Steps instructions of the current thread until its pc is on another line of `lines` than
+    // the one it started on, running calls to their return with `over_calls` instead of
+    // stepping into them. A call stepped into `trampolines` is run to its return either way.
+    // Stops early for anything `wait_for_stop` would stop for, e.g. a breakpoint, and where it
+    // is when the wait is cancelled.
+    pub fn step_line(
+        &mut self,
+        lines: &LineTable,
+        trampolines: &Trampolines,
+        over_calls: bool,
+    ) -> anyhow::Result<WaitOutcome> {
+        let pc = VirtAddr::new(self.arch.pc(&self.tracer.get_regs(self.current_thread)?));
+        let start = lines
+            .lookup(pc)
+            .map(|row| row.location())
+            .ok_or(anyhow!("no line information for {}, use `stepi`", pc))?;
+        debug!(from = %start, "stepping line");
+
+        loop {
+            let outcome = self.step_past_trampolines(trampolines, over_calls)?;
+            if !matches!(
+                outcome,
+                WaitOutcome::StateChanged(ProcessState::Stopped(StopReason::StepComplete))
+            ) {
+                return Ok(outcome);
+            }
+            if self.cancellation_token.take() {
+                debug!("line step cancelled");
+                return Ok(outcome);
+            }
+
+            let pc = VirtAddr::new(self.arch.pc(&self.tracer.get_regs(self.current_thread)?));
+            // line 0 is code the compiler made up, it belongs to no line
+            if lines
+                .lookup(pc)
+                .is_some_and(|row| row.line != 0 && row.location() != start)
+            {
+                return Ok(outcome);
+            }
+        }
+    }
+
+    // A step of `step_line`. Trampolines are left as soon as they're entered, so it's at the
+    // first instruction of one, where the call left the return address.
+    fn step_past_trampolines(
+        &mut self,
+        trampolines: &Trampolines,
+        over_calls: bool,
+    ) -> anyhow::Result<WaitOutcome> {
+        let outcome = if over_calls {
+            self.step_over_call()?
+        } else {
+            self.step_instruction()?;
+            self.wait_for_stop(None)?
+        };
+        if !matches!(
+            outcome,
+            WaitOutcome::StateChanged(ProcessState::Stopped(StopReason::StepComplete))
+        ) {
+            return Ok(outcome);
+        }
+        let regs = self.tracer.get_regs(self.current_thread)?;
+        let pc = VirtAddr::new(self.arch.pc(&regs));
+        if !trampolines.contains(pc) {
+            return Ok(outcome);
+        }
+
+        let return_address = match self.arch.return_address(&regs) {
+            ReturnAddress::Register(address) => VirtAddr::new(address),
+            ReturnAddress::Stack(at) => self.read_pointer(VirtAddr::new(at))?,
+        };
+        debug!(pc = %pc, return_address = %return_address, "running through trampoline");
+        self.run_to_any(&[(return_address, Some(self.arch.stack_pointer(&regs)))])
+    }
+
+    // Runs the debuggee an instruction of the current thread at a time, recording each into the
+    // instruction trace and the trace file before it executes, until it stops for anything else than a completed
+    // step or the wait is cancelled. Breakpoints a step stops in front of are hit as if it had
+    // run into them.
+    pub fn run_recording(&mut self) -> anyhow::Result<WaitOutcome> {
+        loop {
+            let registers = self
+                .registers
+                .as_ref()
+                .ok_or(anyhow!("no register info available"))?;
+            let pc = VirtAddr::new(self.arch.pc(registers.user_regs()));
+            let record = InstructionRecord {
+                index: self.instruction_trace.records().next_index(),
+                thread: self.current_thread,
+                pc,
+                registers: self
+                    .instruction_trace
+                    .registers()
+                    .iter()
+                    .filter_map(|register| {
+                        Some((*register, registers.read_register(*register).ok()?))
+                    })
+                    .collect(),
+            };
+            if let Some(trace_file) = &mut self.trace_file {
+                if !trace_file.write(&record) {
+                    debug!(
+                        insn = record.index,
+                        "trace file is behind, instruction dropped"
+                    );
+                }
+            }
+            self.instruction_trace.record(record);
+
+            self.step_instruction()?;
+            let outcome = self.wait_for_stop(None)?;
+            if !matches!(
+                outcome,
+                WaitOutcome::StateChanged(ProcessState::Stopped(StopReason::StepComplete))
+            ) {
+                return Ok(outcome);
+            }
+            if self.cancellation_token.take() {
+                debug!("recording cancelled");
+                return Ok(outcome);
+            }
+
+            let pc = VirtAddr::new(self.arch.pc(&self.tracer.get_regs(self.current_thread)?));
+            let Some(id) = self
+                .breakpoints
+                .values()
+                .find(|breakpoint| breakpoint.address() == pc && breakpoint.is_armed())
+                .map(|breakpoint| breakpoint.id())
+            else {
+                continue;
+            };
+            self.process_state = ProcessState::Stopped(StopReason::Breakpoint { id });
+            if self.process_stop()? {
+                return Ok(WaitOutcome::StateChanged(self.process_state.clone()));
+            }
+            self.process_state = ProcessState::Stopped(StopReason::StepComplete);
+        }
+    }
+
+    // Executes the instruction at the pc of the current thread and waits for the stop. A call is
+    // run until it returns, which counts as a completed step.
+    pub fn step_over_call(&mut self) -> anyhow::Result<WaitOutcome> {
+        if !matches!(self.process_state, ProcessState::Stopped(_)) {
+            Err(anyhow!("debuggee must be stopped to step"))?;
+        }
+        let regs = self.tracer.get_regs(self.current_thread)?;
+        let pc = VirtAddr::new(self.arch.pc(&regs));
+        // the instruction may end right before an unmapped page
+        let len = arch::MAX_INSTRUCTION_LENGTH.min((PAGE_SIZE - pc.as_u64() % PAGE_SIZE) as usize);
+        let insn = self.read_memory(pc, len)?;
+
+        match self.arch.call_length(&insn) {
+            Some(length) => {
+                debug!(pc = %pc, "stepping over call");
+                let return_address = pc + length as u64;
+                self.run_to_any(&[(return_address, Some(self.arch.stack_pointer(&regs)))])
+            }
+            None => {
+                self.step_instruction()?;
+                self.wait_for_stop(None)
+            }
+        }
+    }
+
+    // Runs the current thread until the function it's in returns, which counts as a completed
+    // step. Stops early for anything else `wait_for_stop` stops for.
+    pub fn finish(&mut self) -> anyhow::Result<WaitOutcome> {
+        if !matches!(self.process_state, ProcessState::Stopped(_)) {
+            Err(anyhow!("debuggee must be stopped to finish"))?;
+        }
+        let regs = self.tracer.get_regs(self.current_thread)?;
+        let return_address = self.frame_return_address(&regs)?;
+
+        debug!(return_address = %return_address, "running to return");
+        self.run_to_any(&[(return_address, Some(self.arch.stack_pointer(&regs)))])
+    }
+
+    // Runs the current thread until it gets to `target`, or the function it's in returns, which
+    // counts as a completed step. With `in_frame` only the function it's in now stops at
+    // `target`, not a call it makes. Stops early for anything else `wait_for_stop` stops for.
+    pub fn run_until(&mut self, target: VirtAddr, in_frame: bool) -> anyhow::Result<WaitOutcome> {
+        if !matches!(self.process_state, ProcessState::Stopped(_)) {
+            Err(anyhow!("debuggee must be stopped to run to {}", target))?;
+        }
+        let regs = self.tracer.get_regs(self.current_thread)?;
+        let stack_pointer = self.arch.stack_pointer(&regs);
+
+        let mut targets = vec![(target, in_frame.then_some(stack_pointer))];
+        match self.frame_return_address(&regs) {
+            Ok(return_address) => targets.push((return_address, Some(stack_pointer))),
+            Err(err) => debug!(error = box_err(err), "running to target only"),
+        }
+        debug!(target = %target, in_frame, "running until");
+        self.run_to_any(&targets)
+    }
+
+    // What the function the current thread returned from last returned, as far as it fits in a
+    // register.
+    pub fn call_return_value(&self) -> anyhow::Result<u64> {
+        let regs = self.tracer.get_regs(self.current_thread)?;
+        Ok(self.arch.call_return_value(&regs))
+    }
+
+    // Where the function the pc is in returns to. That's where the call left it at the first
+    // instruction of a function, anywhere else it's taken from the frame record, which takes
+    // frame pointers.
+    fn frame_return_address(&self, regs: &libc::user_regs_struct) -> anyhow::Result<VirtAddr> {
+        let pc = VirtAddr::new(self.arch.pc(regs));
+        let at_entry = self
+            .symbol_table()?
+            .lookup(pc)
+            .is_some_and(|(_, offset)| offset == 0);
+        let return_address = if at_entry {
+            match self.arch.return_address(regs) {
+                ReturnAddress::Register(address) => VirtAddr::new(address),
+                ReturnAddress::Stack(at) => self.read_pointer(VirtAddr::new(at))?,
+            }
+        } else {
+            let frame_pointer = self.arch.frame_pointer(regs);
+            if frame_pointer == 0 || frame_pointer < self.arch.stack_pointer(regs) {
+                Err(anyhow!("no frame record to find the caller of {} in", pc))?;
+            }
+            let (_, saved_return_address) = self.arch.frame_record(frame_pointer);
+            self.read_pointer(VirtAddr::new(saved_return_address))?
+        };
+        if return_address.as_u64() == 0 {
+            Err(anyhow!("{} is in the outermost frame", pc))?;
+        }
+        Ok(return_address)
+    }
+
+    // Runs the debuggee until the current thread gets to one of `targets` through temporary
+    // breakpoints, with its stack pointer at or above the one given with it, which e.g. a
+    // recursive call of the same function doesn't have. Getting there counts as a completed
+    // step. Stops early for anything else `wait_for_stop` stops for.
+    fn run_to_any(&mut self, targets: &[(VirtAddr, Option<u64>)]) -> anyhow::Result<WaitOutcome> {
+        let tid = self.current_thread;
+        // no stack pointer stops at any depth
+        let mut merged = BTreeMap::<VirtAddr, Option<u64>>::new();
+        for (address, stack_pointer) in targets {
+            merged
+                .entry(*address)
+                .and_modify(|lowest| *lowest = lowest.zip(*stack_pointer).map(|(a, b)| a.min(b)))
+                .or_insert(*stack_pointer);
+        }
+
+        // by id, the lowest stack pointer it stops at and whether it's a breakpoint of the user
+        let mut stops = BTreeMap::<usize, (Option<u64>, bool)>::new();
+        let result = merged.into_iter().try_for_each(|(address, lowest)| {
+            let existing = self
+                .breakpoints
+                .values()
+                .find(|breakpoint| breakpoint.address() == address && breakpoint.is_armed())
+                .map(|breakpoint| breakpoint.id());
+            let id = match existing {
+                Some(id) => id,
+                None => self.set_breakpoint(address)?,
+            };
+            stops.insert(id, (lowest, existing.is_some()));
+            anyhow::Ok(())
+        });
+
+        let outcome = result.and_then(|()| loop {
+            self.resume()?;
+            let outcome = self.wait_for_stop(None)?;
+            let ProcessState::Stopped(StopReason::Breakpoint { id: hit }) = self.process_state
+            else {
+                break Ok(outcome);
+            };
+            let arrived = match stops.get(&hit) {
+                // a breakpoint of the user stops any thread at any depth
+                None | Some((_, true)) => true,
+                Some((_, false)) if self.current_thread != tid => false,
+                Some((Some(lowest), false)) => {
+                    self.arch.stack_pointer(&self.tracer.get_regs(tid)?) >= *lowest
+                }
+                Some((None, false)) => true,
+            };
+            if arrived {
+                break Ok(outcome);
+            }
+        });
+
+        let temporary = stops
+            .iter()
+            .filter(|(_, (_, existing))| !existing)
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+        for id in temporary.iter().rev() {
+            if self.process_state.is_alive() {
+                self.remove_breakpoint(*id)?;
+            } else {
+                self.breakpoints.remove(id);
+                self.forget_breakpoint(*id);
+            }
+        }
+        // the temporary breakpoints shouldn't take ids away from the user
+        if let (Some(first), Some(last)) = (temporary.first(), temporary.last()) {
+            if self.next_breakpoint_id == last + 1 {
+                self.next_breakpoint_id = *first;
+            }
+        }
+
+        let outcome = outcome?;
+        if let ProcessState::Stopped(StopReason::Breakpoint { id: hit }) = self.process_state {
+            if temporary.contains(&hit) {
+                self.process_state = ProcessState::Stopped(StopReason::StepComplete);
+                return Ok(WaitOutcome::StateChanged(self.process_state.clone()));
+            }
+        }
+        Ok(outcome)
+    }
+
+    // Steps `tid` over the instruction at its pc, the stop is picked up by update_process_state.
+    fn start_step(&mut self, tid: Pid) -> anyhow::Result<()> {
+        self.current_thread = tid;
+        let pc = self.arch.pc(&self.tracer.get_regs(tid)?);
+        if !self.step_over_breakpoint()? {
+            return Ok(());
+        }
+
+        if self.arch.pc(&self.tracer.get_regs(tid)?) != pc {
+            // the instruction under the breakpoint has been stepped already
+            self.pending_wait_status = Some(WaitStatus::Stopped(tid, Signal::SIGTRAP));
+        } else {
+            self.tracer.step(tid, None)?;
+        }
+        Ok(())
+    }
+
+    fn is_stepping(&self, tid: Pid) -> bool {
+        self.pending_step
+            .as_ref()
+            .is_some_and(|step| step.thread == tid)
+    }
+
+    // Whether a stop of `tid` belongs to its pending step: anything while the step is under way,
+    // only the return to the stepped instruction while a signal handler runs.
+    fn is_pending_step_stop(&self, tid: Pid, signal: Signal) -> anyhow::Result<bool> {
+        let Some(step) = self.pending_step.as_ref().filter(|step| step.thread == tid) else {
+            return Ok(false);
+        };
+        let Some(marker) = &step.handler_return else {
+            return Ok(true);
+        };
+        if signal != Signal::SIGTRAP {
+            return Ok(false);
+        }
+
+        let regs = self.tracer.get_regs(tid)?;
+        let address =
+            VirtAddr::new(self.arch.pc(&regs)).wrapping_sub(self.arch.breakpoint_pc_offset());
+        let stack_pointer = VirtAddr::new(self.arch.stack_pointer(&regs));
+        // a breakpoint of the user there is only taken over once the handler has returned
+        Ok(address == step.pc && (marker.is_armed() || stack_pointer == step.stack_pointer))
+    }
+
+    // Moves the pending step of `tid` on after a stop belonging to it. Returns why the debuggee
+    // stopped once the step completed, None while it's still under way.
+    fn continue_step(&mut self, tid: Pid, signal: Signal) -> anyhow::Result<Option<StopReason>> {
+        let Some(mut step) = self.pending_step.take() else {
+            return Ok(None);
+        };
+        self.current_thread = tid;
+
+        match step.handler_return.take() {
+            None if signal == Signal::SIGTRAP => {
+                // a hardware watchpoint the instruction triggered takes precedence
+                Ok(Some(
+                    self.debug_register_stop_reason()?
+                        .unwrap_or(StopReason::StepComplete),
+                ))
+            }
+            None if self.step_into_handlers => {
+                // the thread stops again at the first instruction of the handler
+                debug!(tid = %tid, signal = %signal, "stepping into signal handler");
+                self.tracer.step(tid, Some(signal))?;
+                self.pending_step = Some(step);
+                Ok(None)
+            }
+            None => {
+                // the signal is taken before the instruction executes, the pc is still at it
+                debug!(tid = %tid, signal = %signal, "running signal handler before stepping");
+                let mut marker = Breakpoint::new(0, step.pc);
+                if !self
+                    .breakpoints
+                    .values()
+                    .any(|breakpoint| breakpoint.address() == step.pc && breakpoint.is_armed())
+                {
+                    marker.arm(&self.tracer, self.pid, self.arch.breakpoint_instruction())?;
+                }
+                self.tracer.cont(tid, Some(signal))?;
+                step.handler_return = Some(marker);
+                self.pending_step = Some(step);
+                Ok(None)
+            }
+            Some(mut marker) => {
+                let mut regs = self.tracer.get_regs(tid)?;
+                self.arch.set_pc(&mut regs, step.pc.as_u64());
+                self.tracer.set_regs(tid, regs)?;
+                let returned = VirtAddr::new(self.arch.stack_pointer(&regs)) == step.stack_pointer;
+
+                if returned {
+                    debug!(tid = %tid, "signal handler returned, stepping again");
+                    if marker.is_armed() {
+                        marker.disarm(&self.tracer, self.pid)?;
+                    }
+                    self.pending_step = Some(step);
+                    self.start_step(tid)?;
+                    return Ok(None);
+                }
+
+                // the handler itself runs the stepped instruction, carry on over the marker
+                marker.disarm(&self.tracer, self.pid)?;
+                let wait_status = self.single_step(tid)?;
+                if matches!(wait_status, WaitStatus::Stopped(..)) {
+                    marker.arm(&self.tracer, self.pid, self.arch.breakpoint_instruction())?;
+                }
+                if matches!(wait_status, WaitStatus::Stopped(_, Signal::SIGTRAP)) {
+                    self.tracer.cont(tid, None)?;
+                } else {
+                    self.pending_wait_status = Some(wait_status);
+                }
+                step.handler_return = Some(marker);
+                self.pending_step = Some(step);
+                Ok(None)
+            }
+        }
+    }
+
+    // Drops the pending step, if any, along with the breakpoint waiting for its signal handler.
+    fn cancel_pending_step(&mut self) -> anyhow::Result<()> {
+        let Some(step) = self.pending_step.take() else {
+            return Ok(());
+        };
+        if let Some(mut marker) = step.handler_return.filter(Breakpoint::is_armed) {
+            marker.disarm(&self.tracer, self.pid)?;
+        }
+        debug!(tid = %step.thread, "pending step cancelled");
+        Ok(())
+    }
+
+    // Signals the debuggee stopped for are dropped, other than stop signals.
+    pub fn resume(&mut self) -> anyhow::Result<()> {
+        self.resume_with_signal(self.job_control_signal())
+    }
+
+    // Resumes the current thread delivering `signal` to it, e.g. the one it stopped for.
+    pub fn resume_with_signal(&mut self, signal: Option<Signal>) -> anyhow::Result<()> {
+        let span = debug_span!(
+            "resuming debuggee",
+            pid = tracing::field::display(&self.pid),
+        );
+        let _entered = span.entered();
+
+        if matches!(self.process_state, ProcessState::Stopped(_)) {
+            self.flush_registers()?;
+            self.arm_heap_hooks()?;
+            self.arm_coverage_probes()?;
+            self.arm_loader_hook()?;
+        }
+
+        match self.process_state {
+            ProcessState::Stopped(_)
+                if self.group_stopped.get(&self.current_thread) == Some(&true) =>
+            {
+                // it keeps waiting for SIGCONT like any stopped process, events are still reported
+                self.tracer.listen(self.current_thread)?;
+                self.process_state = ProcessState::Running;
+            }
+            ProcessState::Stopped(_) => {
+                if !self.group_stopped.is_empty() {
+                    // only seized threads can wait for SIGCONT under ptrace
+                    debug!("resuming the debuggee out of its group stop");
+                    self.group_stopped.clear();
+                }
+                self.step_over_hardware_breakpoint()?;
+                if self.watchpoints.values().any(Watchpoint::is_software) {
+                    self.resume_watch_stepping()?;
+                } else if self.step_over_breakpoint()? {
+                    self.continue_thread(self.current_thread, signal)?;
+                }
+                self.process_state = ProcessState::Running;
+            }
+            ProcessState::Running => {
+                self.continue_thread(self.current_thread, None)?;
+            }
+            ProcessState::Exited(_) | ProcessState::Terminated(_) => {
+                Err(anyhow!("unable to resume an exited or terminated process"))?;
+            }
+        }
+
+        info!("debuggee process resumed");
+
+        return Ok(());
+    }
+
+    // Single steps the current thread instead of continuing it, checking the software
+    // watchpoints after every instruction. Writes by other threads are only noticed once the
+    // current thread executes its next instruction.
+    fn resume_watch_stepping(&mut self) -> anyhow::Result<()> {
+        let tid = self.current_thread;
+        for watchpoint in self.watchpoints.values_mut().filter(|w| w.is_software()) {
+            let mut value = vec![0u8; watchpoint.size()];
+            self.tracer
+                .read_memory(self.pid, watchpoint.address().as_u64(), &mut value)?;
+            watchpoint.update_value(value);
+        }
+
+        let pc = self.arch.pc(&self.tracer.get_regs(tid)?);
+        if self.step_over_breakpoint()? {
+            if self.arch.pc(&self.tracer.get_regs(tid)?) == pc {
+                self.tracer.step(tid, None)?;
+            } else {
+                // the instruction under the breakpoint has been stepped already, have its step
+                // checked like any other
+                self.pending_wait_status = Some(WaitStatus::Stopped(tid, Signal::SIGTRAP));
+            }
+        }
+        self.watch_stepping = true;
+
+        Ok(())
+    }
+
+    // The first software watchpoint whose contents changed with the last step.
+    fn software_watchpoint_hit(&mut self) -> anyhow::Result<Option<StopReason>> {
+        if let Some(id) = self.popped_watchpoints().into_iter().next() {
+            debug!(watchpoint = id, "watched frame returned");
+            self.remove_watchpoint(id)?;
+            return Ok(Some(StopReason::WatchpointScope { id }));
+        }
+        for watchpoint in self.watchpoints.values_mut().filter(|w| w.is_software()) {
+            let mut value = vec![0u8; watchpoint.size()];
+            self.tracer
+                .read_memory(self.pid, watchpoint.address().as_u64(), &mut value)?;
+            if watchpoint.update_value(value) {
+                debug!(watchpoint = watchpoint.id(), "software watchpoint hit");
+                return Ok(Some(StopReason::Watchpoint {
+                    id: watchpoint.id(),
+                    address: watchpoint.address(),
+                }));
+            }
+        }
+        Ok(None)
+    }
+
+    // A write to a page protected for page protection watchpoints. The page, and the next one in
+    // case the write straddles them, is made writable for just the faulting instruction. Returns
+    // the watchpoint hit, None if the write missed every watched range and the debuggee carries
+    // on. Other threads writing while the page is writable go unnoticed.
+    fn step_over_page_fault(
+        &mut self,
+        tid: Pid,
+        address: VirtAddr,
+    ) -> anyhow::Result<Option<StopReason>> {
+        let page = address.align_down(PAGE_SIZE);
+        let pages = [page, page.saturating_add(PAGE_SIZE)]
+            .into_iter()
+            .filter_map(|page| Some((page, *self.page_protections.get(&page)?)))
+            .collect::<Vec<_>>();
+        debug!(address = %address, "stepping over write to protected page");
+
+        self.protect_pages(tid, &pages)?;
+        let wait_status = self.single_step(tid)?;
+        if matches!(
+            wait_status,
+            WaitStatus::Stopped(..) | WaitStatus::PtraceEvent(..)
+        ) {
+            let protected = pages
+                .iter()
+                .map(|(page, prot)| (*page, prot & !libc::PROT_WRITE))
+                .collect::<Vec<_>>();
+            self.protect_pages(tid, &protected)?;
+        }
+        if !matches!(wait_status, WaitStatus::Stopped(_, Signal::SIGTRAP)) {
+            // stopped for something else before the write went through
+            self.pending_wait_status = Some(wait_status);
+            return Ok(None);
+        }
+
+        if let Some(watchpoint) = self
+            .watchpoints
+            .values()
+            .find(|watchpoint| watchpoint.is_page_protected() && watchpoint.contains(address))
+        {
+            debug!(
+                watchpoint = watchpoint.id(),
+                "page protection watchpoint hit"
+            );
+            return Ok(Some(StopReason::Watchpoint {
+                id: watchpoint.id(),
+                address,
+            }));
+        }
+
+        if self.watch_stepping || self.is_stepping(tid) {
+            // have the step checked like any other
+            self.pending_wait_status = Some(WaitStatus::Stopped(tid, Signal::SIGTRAP));
+        } else {
+            self.continue_thread(tid, None)?;
+        }
+        Ok(None)
+    }
+
+    // A syscall that would fail with EFAULT writing into pages protected for watchpoints. It's
+    // skipped at its entry and run again once they're writable, mprotect can't be injected before
+    // it otherwise. They're protected again when it returns, with the watchpoint it wrote into
+    // hit. Other threads writing while the pages are writable go unnoticed.
+    fn syscall_watchpoint_hit(&mut self, tid: Pid) -> anyhow::Result<Option<StopReason>> {
+        let regs = self.tracer.get_regs(tid)?;
+        if !self.arch.is_syscall_entry(&regs) {
+            if let Some(write) = self.syscall_writes.remove(&tid) {
+                let protected = write
+                    .pages
+                    .iter()
+                    .filter_map(|page| {
+                        Some((*page, self.page_protections.get(page)? & !libc::PROT_WRITE))
+                    })
+                    .collect::<Vec<_>>();
+                self.protect_pages(tid, &protected)?;
+                let written = self.arch.syscall_return_value(&regs).max(0) as u64;
+                if let Some(reason) = self.written_watchpoint(&write.buffers, written) {
+                    return Ok(Some(reason));
+                }
+            }
+            self.continue_thread(tid, None)?;
+            return Ok(None);
+        }
+
+        // an entry while the pages are writable is the syscall being run again
+        let write = if self.syscall_writes.contains_key(&tid) {
+            None
+        } else {
+            self.syscall_write(&regs)
+        };
+        if let Some(write) = write {
+            debug!(tid = %tid, pages = write.pages.len(), "syscall writes to protected pages");
+            let mut rewound = regs;
+            self.arch.rewind_syscall(&mut rewound);
+            self.tracer.set_regs(tid, rewound)?;
+            self.tracer.syscall(tid, None)?;
+            let wait_status = self.tracer.wait(tid, WaitPidFlag::__WALL)?;
+            if !matches!(wait_status, WaitStatus::PtraceSyscall(_)) {
+                // the syscall is still run again, stopping at its entry once more
+                self.pending_wait_status = Some(wait_status);
+                return Ok(None);
+            }
+            let writable = write
+                .pages
+                .iter()
+                .map(|page| (*page, self.page_protections[page]))
+                .collect::<Vec<_>>();
+            self.protect_pages(tid, &writable)?;
+            self.syscall_writes.insert(tid, write);
+        }
+        self.continue_thread(tid, None)?;
+        Ok(None)
+    }
+
+    // What the syscall at the entry stop `regs` are of writes to protected pages, if anything.
+    fn syscall_write(&self, regs: &libc::user_regs_struct) -> Option<SyscallWrite> {
+        let number = self.arch.syscall_number(regs) as libc::c_long;
+        let buffers = match watchpoint::syscall_buffer(number, &self.arch.syscall_args(regs))? {
+            SyscallBuffer::Flat { address, len } => vec![(address, len)],
+            SyscallBuffer::Vectored { iov, count } => match self.read_iovecs(iov, count) {
+                Ok(buffers) => buffers,
+                Err(err) => {
+                    // the syscall fails with EFAULT itself
+                    debug!(error = box_err(err), "unable to read syscall buffers");
+                    return None;
+                }
+            },
+        };
+        let pages = self
+            .page_protections
+            .keys()
+            .filter(|page| {
+                let page = page.range(PAGE_SIZE);
+                buffers.iter().any(|(address, len)| {
+                    let buffer = address.range(*len);
+                    buffer.start < page.end && page.start < buffer.end
+                })
+            })
+            .copied()
+            .collect::<Vec<_>>();
+        (!pages.is_empty()).then_some(SyscallWrite { buffers, pages })
+    }
+
+    fn read_iovecs(&self, iov: VirtAddr, count: u64) -> anyhow::Result<Vec<(VirtAddr, u64)>> {
+        // more than IOV_MAX fails with EINVAL before anything is written
+        let mut iovecs = vec![0u8; count.min(libc::UIO_MAXIOV as u64) as usize * 16];
+        self.tracer
+            .read_memory(self.pid, iov.as_u64(), &mut iovecs)?;
+        Ok(iovecs
+            .chunks_exact(16)
+            .map(|iovec| {
+                let field = |offset: usize| {
+                    u64::from_ne_bytes(iovec[offset..offset + 8].try_into().expect("8 bytes"))
+                };
+                (VirtAddr::new(field(0)), field(8))
+            })
+            .collect())
+    }
+
+    // The first page protection watchpoint in the `written` bytes a syscall filled `buffers` with,
+    // in order.
+    fn written_watchpoint(&self, buffers: &[(VirtAddr, u64)], written: u64) -> Option<StopReason> {
+        let mut left = written;
+        for (address, len) in buffers {
+            let buffer = address.range((*len).min(left));
+            left -= buffer.end.offset_from(buffer.start).unwrap_or(0);
+            if let Some(watchpoint) = self.watchpoints.values().find(|watchpoint| {
+                let watched = watchpoint.address().range(watchpoint.size() as u64);
+                watchpoint.is_page_protected()
+                    && buffer.start < watched.end
+                    && watched.start < buffer.end
+            }) {
+                debug!(
+                    watchpoint = watchpoint.id(),
+                    "page protection watchpoint hit by syscall"
+                );
+                return Some(StopReason::Watchpoint {
+                    id: watchpoint.id(),
+                    address: buffer.start.max(watchpoint.address()),
+                });
+            }
+        }
+        None
+    }
+
+    // What the pages protected for watchpoints are changed back to once they aren't watched.
+    fn original_protections(&self) -> Vec<(VirtAddr, i32)> {
+        self.page_protections
+            .iter()
+            .map(|(page, prot)| (*page, *prot))
+            .collect()
+    }
+
+    // Changes the protection of whole pages with mprotect injected into `tid`, which has to be
+    // stopped. Neighbouring pages with the same protection are changed in one call.
+    fn protect_pages(&self, tid: Pid, pages: &[(VirtAddr, i32)]) -> anyhow::Result<()> {
+        if pages.is_empty() {
+            return Ok(());
+        }
+
+        let mut ranges = Vec::<(VirtAddr, u64, i32)>::new();
+        for (page, prot) in pages {
+            match ranges.last_mut() {
+                Some((start, len, last_prot))
+                    if *last_prot == *prot && start.saturating_add(*len) == *page =>
+                {
+                    *len += PAGE_SIZE;
+                }
+                _ => ranges.push((*page, PAGE_SIZE, *prot)),
+            }
+        }
+
+        let injector = SyscallInjector::new(&self.tracer, tid)?;
+        let result = ranges.iter().try_for_each(|(start, len, prot)| {
+            let ret = injector.call(
+                &self.tracer,
+                libc::SYS_mprotect,
+                &[start.as_u64(), *len, *prot as u64],
+            )?;
+            if ret < 0 {
+                Err(anyhow!(
+                    "injected mprotect of {} bytes at {} failed: {}",
+                    len,
+                    start,
+                    Errno::from_raw(-ret as i32)
+                ))?;
+            }
+            Ok(())
+        });
+        injector.restore(&self.tracer, tid)?;
+
+        result
+    }
+
+    fn ensure_alive(&self) -> anyhow::Result<()> {
+        if self.process_state.is_alive() {
+            Ok(())
+        } else {
+            Err(anyhow!("debuggee has exited or been terminated"))
+        }
+    }
+
+    /// Reads `len` bytes of debuggee memory starting at `addr`.
+    ///
+    /// The debuggee doesn't have to be stopped. Reading fails as a whole if any byte of the range
+    /// is unmapped or not readable. Bytes replaced by breakpoints read as their original values.
+    /// While the debuggee is stopped, the pages read are kept until it resumes or memory is
+    /// written, so reading the same memory again is cheap.
+    pub fn read_memory(&self, addr: VirtAddr, len: usize) -> anyhow::Result<Vec<u8>> {
+        self.ensure_alive()?;
+
+        let mut buf = vec![0u8; len];
+        if matches!(self.process_state, ProcessState::Running) {
+            self.tracer
+                .read_memory_uncached(self.pid, addr.as_u64(), &mut buf)?;
+        } else {
+            self.tracer.read_memory(self.pid, addr.as_u64(), &mut buf)?;
+        }
+
+        let range = addr.range(len as u64);
+        for breakpoint in self.breakpoints.values() {
+            let Some(saved_code) = breakpoint.saved_code() else {
+                continue;
+            };
+            for (offset, byte) in saved_code.iter().enumerate() {
+                let address = breakpoint.address() + offset as u64;
+                if range.contains(&address) {
+                    buf[address.offset_from(addr).unwrap() as usize] = *byte;
+                }
+            }
+        }
+
+        Ok(buf)
+    }
+
+    // Up to `count` instructions starting at `addr`, fewer if unreadable memory cuts them short.
+    // Breakpoints don't show up in them.
+    pub fn disassemble(&self, addr: VirtAddr, count: usize) -> anyhow::Result<Vec<Instruction>> {
+        let wanted = count * arch::MAX_INSTRUCTION_LENGTH;
+        let mut bytes = Vec::new();
+        while bytes.len() < wanted {
+            let next = addr + bytes.len() as u64;
+            let len = ((PAGE_SIZE - next.as_u64() % PAGE_SIZE) as usize).min(wanted - bytes.len());
+            match self.read_memory(next, len) {
+                Ok(chunk) => bytes.extend(chunk),
+                Err(err) if bytes.is_empty() => Err(err)?,
+                Err(_) => break,
+            }
+        }
+        disassemble::disassemble(self.arch, addr, &bytes, count)
+    }
+
+    // Addresses of every occurrence of `pattern` between `start` and `end`. Only mapped memory
+    // is searched, unreadable regions are skipped, and a match doesn't span two regions.
+    pub fn find_memory(
+        &self,
+        start: VirtAddr,
+        end: VirtAddr,
+        pattern: &[u8],
+    ) -> anyhow::Result<Vec<VirtAddr>> {
+        if pattern.is_empty() {
+            Err(anyhow!("nothing to search for"))?;
+        }
+        if start >= end {
+            Err(anyhow!("{} must be below {}", start, end))?;
+        }
+
+        let overlap = pattern.len() as u64 - 1;
+        let mut found = Vec::new();
+        for region in self.memory_map()?.regions() {
+            let (from, to) = (region.start.max(start), region.end.min(end));
+            if !region.permissions.read || from >= to {
+                continue;
+            }
+            // chunks overlap so matches at their ends are found, by the next one
+            let mut chunk = from;
+            while to.offset_from(chunk).unwrap() > overlap {
+                let len = to.offset_from(chunk).unwrap().min(MEMORY_CHUNK_SIZE);
+                let bytes = match self.read_memory(chunk, len as usize) {
+                    Ok(bytes) => bytes,
+                    Err(err) => {
+                        debug!(error = box_err(err), start = %region.start, "skipping region");
+                        break;
+                    }
+                };
+                found.extend(
+                    bytes
+                        .windows(pattern.len())
+                        .enumerate()
+                        .filter(|(_, window)| *window == pattern)
+                        .map(|(offset, _)| chunk + offset as u64),
+                );
+                chunk = chunk + (len - overlap);
+            }
+        }
+
+        Ok(found)
+    }
+
+    // Writes `len` bytes at `addr` to a file at `path`, a chunk at a time, so large regions
+    // don't have to fit in memory.
+    pub fn dump_memory<P: AsRef<Path>>(
+        &self,
+        addr: VirtAddr,
+        len: u64,
+        path: P,
+    ) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        let mut file = File::create(path)
+            .map_err(|err| anyhow!("unable to create {}: {}", path.display(), err))?;
+        let mut done = 0;
+        while done < len {
+            let chunk = (len - done).min(MEMORY_CHUNK_SIZE);
+            let bytes = self.read_memory(addr + done, chunk as usize)?;
+            file.write_all(&bytes)
+                .map_err(|err| anyhow!("unable to write {}: {}", path.display(), err))?;
+            done += chunk;
+        }
+        Ok(())
+    }
+
+    // Writes the contents of the file at `path` to `addr`, where it has to be mapped and, unless
+    // `force`, writable. Returns how many bytes that was.
+    pub fn restore_memory<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        addr: VirtAddr,
+        force: bool,
+    ) -> anyhow::Result<usize> {
+        let path = path.as_ref();
+        let data =
+            fs::read(path).map_err(|err| anyhow!("unable to read {}: {}", path.display(), err))?;
+
+        let memory_map = self.memory_map()?;
+        let regions = memory_map
+            .regions_spanning(addr, data.len() as u64)
+            .ok_or(anyhow!(
+                "{} bytes at {} aren't all mapped",
+                data.len(),
+                addr
+            ))?;
+        if let Some(region) = regions.iter().find(|region| !region.permissions.write) {
+            if !force {
+                Err(anyhow!(
+                    "{} to {} isn't writable, use --force to write anyway",
+                    region.start,
+                    region.end
+                ))?;
+            }
+        }
+
+        self.write_memory(addr, &data)?;
+        Ok(data.len())
+    }
+
+    /// Writes `data` into debuggee memory starting at `addr`.
+    ///
+    /// Like `PTRACE_POKEDATA`, this ignores page protections, so read-only mappings such as text
+    /// can be patched. Writing over a breakpoint updates the instruction it restores instead of
+    /// removing it.
+    pub fn write_memory(&mut self, addr: VirtAddr, data: &[u8]) -> anyhow::Result<()> {
+        self.ensure_alive()?;
+
+        let mut data = data.to_vec();
+        let range = addr.range(data.len() as u64);
+        for breakpoint in self.breakpoints.values_mut() {
+            let breakpoint_address = breakpoint.address();
+            let Some(saved_code) = breakpoint.saved_code_mut() else {
+                continue;
+            };
+            for (offset, byte) in saved_code.iter_mut().enumerate() {
+                let address = breakpoint_address + offset as u64;
+                if range.contains(&address) {
+                    let idx = address.offset_from(addr).unwrap() as usize;
+                    *byte = data[idx];
+                    data[idx] = self.arch.breakpoint_instruction()[offset];
+                }
+            }
+        }
+
+        self.tracer.write_memory(self.pid, addr.as_u64(), &data)
+    }
+
+    /// Reads a pointer of the width the debuggee uses.
+    pub fn read_pointer(&self, addr: VirtAddr) -> anyhow::Result<VirtAddr> {
+        let pointer_width = self.arch.pointer_width();
+        let buf = self.read_memory(addr, pointer_width.size())?;
+        Ok(VirtAddr::new(pointer_width.read_pointer(&buf)))
+    }
+
+    /// Reads a `V` from debuggee memory.
+    ///
+    /// # Safety
+    ///
+    /// Any bit pattern read from the debuggee must be a valid `V`.
+    pub unsafe fn read_value<V: Copy>(&self, addr: VirtAddr) -> anyhow::Result<V> {
+        let buf = self.read_memory(addr, size_of::<V>())?;
+        Ok(read_any_from_u8_pointer(buf.as_ptr(), buf.len()))
+    }
+
+    /// Writes the in-memory representation of `value` into debuggee memory.
+    ///
+    /// # Safety
+    ///
+    /// `V` must not contain padding bytes.
+    pub unsafe fn write_value<V: Copy>(&mut self, addr: VirtAddr, value: &V) -> anyhow::Result<()> {
+        self.write_memory(addr, as_u8_slice(value))
+    }
+
+    // A `V` read from debuggee memory, without the promises `read_value` needs.
+    pub fn read_object<V: FromBytes>(&self, addr: VirtAddr) -> anyhow::Result<V> {
+        let bytes = self.read_memory(addr, V::SIZE)?;
+        V::from_bytes(&bytes).ok_or(anyhow!(
+            "{} bytes at {} don't make a {}",
+            bytes.len(),
+            addr,
+            type_name::<V>()
+        ))
+    }
+
+    pub fn write_object<V: ToBytes>(&mut self, addr: VirtAddr, value: &V) -> anyhow::Result<()> {
+        self.write_memory(addr, &value.to_bytes())
+    }
+
+    pub fn command_line(&self) -> anyhow::Result<Vec<String>> {
+        self.read_procfs_strings("cmdline")
+    }
+
+    // The environment of the running debuggee, which may have changed since it was launched.
+    // Entries without `=` are kept with an empty value.
+    pub fn environment(&self) -> anyhow::Result<Vec<(String, String)>> {
+        Ok(self
+            .read_procfs_strings("environ")?
+            .into_iter()
+            .map(|var| match var.split_once('=') {
+                Some((key, value)) => (key.to_string(), value.to_string()),
+                None => (var, String::new()),
+            })
+            .collect())
+    }
+
+    // Reads a NUL separated list of strings from /proc/pid.
+    fn read_procfs_strings(&self, name: &str) -> anyhow::Result<Vec<String>> {
+        let bytes = fs::read(format!("/proc/{}/{}", self.pid, name))
+            .map_err(|err| anyhow!("unable to read {} of debuggee: {}", name, err))?;
+
+        Ok(bytes
+            .split(|byte| *byte == 0)
+            .filter(|string| !string.is_empty())
+            .map(|string| String::from_utf8_lossy(string).into_owned())
+            .collect())
+    }
+
+    // Stack pointers of the threads whose registers can be read, running ones are left out.
+    pub fn stack_pointers(&self) -> BTreeMap<Pid, VirtAddr> {
+        self.threads
+            .iter()
+            .filter_map(|tid| {
+                let regs = self.tracer.get_regs(*tid).ok()?;
+                Some((*tid, VirtAddr::new(self.arch.stack_pointer(&regs))))
+            })
+            .collect()
+    }
+
+    // What's mapped where in the debuggee right now, read fresh every time.
+    pub fn memory_map(&self) -> anyhow::Result<MemoryMap> {
+        MemoryMap::read_from_procfs(self.pid)
+    }
+
+    // Symbols of the executable and every library mapped right now. The table is loaded once
+    // and kept until a module is loaded, unloaded or moved.
+    pub fn symbol_table(&self) -> anyhow::Result<Arc<SymbolTable>> {
+        Ok(self.symbol_table_of(&self.memory_map()?))
+    }
+
+    fn symbol_table_of(&self, memory_map: &MemoryMap) -> Arc<SymbolTable> {
+        self.load_for_modules(memory_map, &self.symbol_table, |memory_map| {
+            SymbolTable::load_indexed(memory_map, &self.root(), self.symbol_index.as_ref())
+        })
+    }
+
+    // The symbol called `name` in the executable or a library, see `SymbolTable::resolve`.
+    pub fn resolve_symbol(&self, name: &str) -> anyhow::Result<Symbol> {
+        Ok(self.symbol_table()?.resolve(name)?.clone())
+    }
+
+    // Line tables of the executable and every library mapped right now, kept like the symbol
+    // table. Each module's is only parsed once it's needed.
+    pub fn line_table(&self) -> anyhow::Result<Arc<LineTable>> {
+        Ok(self.line_table_of(&self.memory_map()?))
+    }
+
+    fn line_table_of(&self, memory_map: &MemoryMap) -> Arc<LineTable> {
+        self.load_for_modules(memory_map, &self.line_table, |memory_map| {
+            LineTable::load(memory_map, &self.root())
+        })
+    }
+
+    // The symbol `address` is in and the offset into it, see `SymbolTable::lookup`. Backtraces
+    // and traces go over the same few addresses again and again, so what's found is remembered by
+    // module offset until the module is unloaded or moved. `memory_map` is the current one, read
+    // once by the caller for all of its lookups.
+    pub fn lookup_symbol(
+        &self,
+        memory_map: &MemoryMap,
+        address: VirtAddr,
+    ) -> Option<(Symbol, u64)> {
+        let lookup = || {
+            let symbol_table = self.symbol_table_of(memory_map);
+            let (symbol, offset) = symbol_table.lookup(address)?;
+            Some((symbol.clone(), offset))
+        };
+        Self::lookup_cached(&self.symbol_lookups, memory_map, address, lookup)
+    }
+
+    // The line table row `address` is in, remembered like `lookup_symbol`.
+    pub fn lookup_line(&self, memory_map: &MemoryMap, address: VirtAddr) -> Option<LineRow> {
+        let lookup = || self.line_table_of(memory_map).lookup(address).cloned();
+        Self::lookup_cached(&self.line_lookups, memory_map, address, lookup)
+    }
+
+    fn lookup_cached<V: Clone>(
+        cache: &RefCell<SymbolCache<Option<V>>>,
+        memory_map: &MemoryMap,
+        address: VirtAddr,
+        lookup: impl FnOnce() -> Option<V>,
+    ) -> Option<V> {
+        // outside of every module, e.g. in code generated at runtime, nothing stays put
+        let Some(key) = ModuleOffset::from_address(memory_map, address) else {
+            return lookup();
+        };
+        let mut cache = cache.borrow_mut();
+        cache.sync_modules(memory_map);
+        cache.get_or_insert_with(key, |_| lookup()).clone()
+    }
+
+    // What's in `cache`, unless the modules have changed since it was loaded.
+    fn load_for_modules<V>(
+        &self,
+        memory_map: &MemoryMap,
+        cache: &ModuleCache<V>,
+        load: impl FnOnce(&MemoryMap) -> V,
+    ) -> Arc<V> {
+        let modules = memory_map
+            .modules()
+            .into_iter()
+            .map(|(module, base)| (module.to_string(), base))
+            .collect::<ModuleBases>();
+
+        let mut cached = cache.borrow_mut();
+        if let Some((loaded_for, value)) = cached.as_ref() {
+            if *loaded_for == modules {
+                return value.clone();
+            }
+        }
+
+        debug!(kind = type_name::<V>(), "loading from modules");
+        let value = Arc::new(load(memory_map));
+        *cached = Some((modules, value.clone()));
+        value
+    }
+
+    // PLT stubs of the executable and every library mapped right now, and the dynamic linker.
+    pub fn trampolines(&self) -> anyhow::Result<Trampolines> {
+        Ok(Trampolines::load(
+            &self.memory_map()?,
+            &self.root(),
+            self.auxv()?.interpreter_base().map(VirtAddr::new),
+        ))
+    }
+
+    pub fn symbol_index(&self) -> Option<&SymbolIndex> {
+        self.symbol_index.as_ref()
+    }
+
+    pub fn set_symbol_index(&mut self, index: Option<SymbolIndex>) {
+        self.symbol_index = index;
+        self.symbol_table.take();
+        self.symbol_lookups.borrow_mut().clear();
+    }
+
+    // Checked on every call, a checkpoint restart brings a new process along.
+    pub fn root(&self) -> ProcessRoot {
+        ProcessRoot::of(self.pid)
+    }
+
+    pub fn file_descriptors(&self) -> anyhow::Result<Vec<FileDescriptor>> {
+        FileDescriptor::read_from_procfs(self.pid)
+    }
+
+    pub fn auxv(&self) -> anyhow::Result<Auxv> {
+        Auxv::read_from_procfs(self.pid, self.arch.pointer_width())
+    }
+
+    // How far the executable got moved from the addresses it was linked at.
+    pub fn load_bias(&self) -> anyhow::Result<u64> {
+        let (phdr, headers) = self.program_headers()?;
+        Ok(auxv::load_bias(phdr, &headers))
+    }
+
+    // Address of the r_debug structure of the dynamic loader, which lists the loaded shared
+    // objects. None for static executables and before the loader has filled it in.
+    pub fn loader_rendezvous(&self) -> anyhow::Result<Option<VirtAddr>> {
+        let (phdr, headers) = self.program_headers()?;
+        let Some((dynamic, size)) = auxv::dynamic_section(&headers) else {
+            return Ok(None);
+        };
+        let dynamic = VirtAddr::new(dynamic.wrapping_add(auxv::load_bias(phdr, &headers)));
+        let bytes = self.read_memory(dynamic, size as usize)?;
+
+        Ok(auxv::find_debug_entry(&bytes, self.arch.pointer_width())
+            .filter(|address| *address != 0)
+            .map(VirtAddr::new))
+    }
+
+    fn program_headers(&self) -> anyhow::Result<(u64, Vec<auxv::ProgramHeader>)> {
+        let (phdr, entry_size, count) = self
+            .auxv()?
+            .program_headers()
+            .filter(|(_, entry_size, _)| *entry_size != 0)
+            .ok_or(anyhow!("no program headers in auxv of debuggee"))?;
+        let bytes = self.read_memory(VirtAddr::new(phdr), (entry_size * count) as usize)?;
+
+        Ok((
+            phdr,
+            auxv::parse_program_headers(&bytes, entry_size as usize, self.arch.pointer_width()),
+        ))
+    }
+
+    pub fn breakpoints(&self) -> &BTreeMap<usize, Breakpoint> {
+        &self.breakpoints
+    }
+
+    pub fn hit_breakpoint(&self) -> Option<&Breakpoint> {
+        match self.process_state {
+            ProcessState::Stopped(StopReason::Breakpoint { id }) => self.breakpoints.get(&id),
+            _ => None,
+        }
+    }
+
+    pub fn set_breakpoint(&mut self, address: VirtAddr) -> anyhow::Result<usize> {
+        if !self.process_state.is_alive() {
+            Err(anyhow!(
+                "unable to set breakpoint in an exited or terminated process"
+            ))?;
+        }
+        if let Some(existing) = self
+            .breakpoints
+            .values()
+            .find(|breakpoint| breakpoint.address() == address)
+        {
+            Err(anyhow!(
+                "breakpoint {} already exists at {}",
+                existing.id(),
+                address
+            ))?;
+        }
+
+        let id = self.next_breakpoint_id;
+        let mut breakpoint = Breakpoint::new(id, address);
+        breakpoint.arm(&self.tracer, self.pid, self.arch.breakpoint_instruction())?;
+
+        self.next_breakpoint_id += 1;
+        self.breakpoints.insert(id, breakpoint);
+
+        Ok(id)
+    }
+
+    // Sets a breakpoint at every address, patching the code once per page. Either all of them are
+    // set or none.
+    pub fn set_breakpoints(&mut self, addresses: &[VirtAddr]) -> anyhow::Result<Vec<usize>> {
+        if !self.process_state.is_alive() {
+            Err(anyhow!(
+                "unable to set breakpoint in an exited or terminated process"
+            ))?;
+        }
+        let mut seen = BTreeSet::new();
+        for address in addresses {
+            if let Some(existing) = self
+                .breakpoints
+                .values()
+                .find(|breakpoint| breakpoint.address() == *address)
+            {
+                Err(anyhow!(
+                    "breakpoint {} already exists at {}",
+                    existing.id(),
+                    address
+                ))?;
+            }
+            if !seen.insert(*address) {
+                Err(anyhow!("duplicate breakpoint address {}", address))?;
+            }
+        }
+
+        let first_id = self.next_breakpoint_id;
+        let mut breakpoints = addresses
+            .iter()
+            .zip(first_id..)
+            .map(|(address, id)| Breakpoint::new(id, *address))
+            .collect::<Vec<_>>();
+        breakpoint::arm_all(
+            &self.tracer,
+            self.pid,
+            self.arch.breakpoint_instruction(),
+            &mut breakpoints,
+        )?;
+
+        self.next_breakpoint_id += breakpoints.len();
+        let ids = breakpoints.iter().map(Breakpoint::id).collect();
+        self.breakpoints.extend(
+            breakpoints
+                .into_iter()
+                .map(|breakpoint| (breakpoint.id(), breakpoint)),
+        );
+
+        Ok(ids)
+    }
+
+    // A software breakpoint overwrites the byte at its address, which corrupts anything but code.
+    pub fn check_breakpoint_address(&self, address: VirtAddr) -> anyhow::Result<()> {
+        let memory_map = self.memory_map()?;
+        match memory_map.region_containing(address) {
+            Some(region) if region.permissions.execute => Ok(()),
+            Some(region) => Err(anyhow!(
+                "{} is in non-executable memory ({}), use --force to set a breakpoint anyway",
+                address,
+                region.permissions
+            )),
+            None => Err(anyhow!(
+                "{} is unmapped, use --force to set a breakpoint anyway",
+                address
+            )),
+        }
+    }
+
+    // Software breakpoints that aren't in executable memory anymore, e.g. because the library
+    // they are in was unloaded. Only looked for when the executable mappings changed since the
+    // last call, so each change is reported once.
+    pub fn revalidate_breakpoints(&mut self) -> anyhow::Result<Vec<usize>> {
+        let memory_map = self.memory_map()?;
+        let executable_ranges = memory_map
+            .regions()
+            .iter()
+            .filter(|region| region.permissions.execute)
+            .map(|region| (region.start, region.end))
+            .collect::<Vec<_>>();
+        if executable_ranges == self.executable_ranges {
+            return Ok(Vec::new());
+        }
+        self.executable_ranges = executable_ranges;
+
+        Ok(self
+            .breakpoints
+            .values()
+            .filter(|breakpoint| !self.pending_breakpoints.contains_key(&breakpoint.id()))
+            .filter(|breakpoint| {
+                !self
+                    .executable_ranges
+                    .iter()
+                    .any(|(start, end)| (*start..*end).contains(&breakpoint.address()))
+            })
+            .map(Breakpoint::id)
+            .collect())
+    }
+
+    // Breakpoints whose library got unloaded, with where they're armed again once it's back.
+    pub fn pending_breakpoints(&self) -> &BTreeMap<usize, ModuleOffset> {
+        &self.pending_breakpoints
+    }
+
+    pub fn remove_breakpoint(&mut self, id: usize) -> anyhow::Result<()> {
+        if self.hardware_breakpoints.remove(&id).is_some() {
+            self.forget_breakpoint(id);
+            self.debug_register_allocator
+                .release(SlotOwner::Breakpoint(id));
+            return self.sync_debug_registers();
+        }
+
+        let mut breakpoint = self
+            .breakpoints
+            .remove(&id)
+            .ok_or(anyhow!("no breakpoint with id {}", id))?;
+        self.forget_breakpoint(id);
+
+        if self.process_state.is_alive() {
+            breakpoint.disarm(&self.tracer, self.pid)?;
+        }
+
+        Ok(())
+    }
+
+    fn forget_breakpoint(&mut self, id: usize) {
+        self.disabled_breakpoints.remove(&id);
+        self.pending_breakpoints.remove(&id);
+        self.tracepoints.remove(&id);
+        self.breakpoint_conditions.remove(&id);
+        self.breakpoint_groups.retain(|_, ids| {
+            ids.remove(&id);
+            !ids.is_empty()
+        });
+    }
+
+    fn has_breakpoint(&self, id: usize) -> bool {
+        self.breakpoints.contains_key(&id) || self.hardware_breakpoints.contains_key(&id)
+    }
+
+    pub fn is_breakpoint_enabled(&self, id: usize) -> bool {
+        self.has_breakpoint(id) && !self.disabled_breakpoints.contains(&id)
+    }
+
+    // A disabled software breakpoint has its code restored and a disabled hardware one gives up
+    // its debug register, so enabling it again may fail for lack of one.
+    pub fn set_breakpoint_enabled(&mut self, id: usize, enabled: bool) -> anyhow::Result<()> {
+        if !self.has_breakpoint(id) {
+            Err(anyhow!("no breakpoint with id {}", id))?;
+        }
+        if self.is_breakpoint_enabled(id) == enabled {
+            return Ok(());
+        }
+
+        if let Some(&address) = self.hardware_breakpoints.get(&id) {
+            if enabled {
+                self.allocate_debug_registers(&[DebugRegisterSlot {
+                    owner: SlotOwner::Breakpoint(id),
+                    address,
+                    size: 1,
+                    kind: WatchKind::Execute,
+                }])?;
+            } else {
+                self.debug_register_allocator
+                    .release(SlotOwner::Breakpoint(id));
+                self.sync_debug_registers()?;
+            }
+        } else if self.pending_breakpoints.contains_key(&id) {
+            // armed once its library is back, unless it's disabled by then
+        } else if let Some(breakpoint) = self.breakpoints.get_mut(&id) {
+            if enabled {
+                breakpoint.arm(&self.tracer, self.pid, self.arch.breakpoint_instruction())?;
+            } else {
+                breakpoint.disarm(&self.tracer, self.pid)?;
+            }
+        }
+
+        if enabled {
+            self.disabled_breakpoints.remove(&id);
+        } else {
+            self.disabled_breakpoints.insert(id);
+        }
+        Ok(())
+    }
+
+    pub fn breakpoint_conditions(&self) -> &BTreeMap<usize, String> {
+        &self.breakpoint_conditions
+    }
+
+    // The breakpoint only stops the debuggee when `condition` evaluates to non-zero, None makes
+    // it unconditional again. The condition is parsed right away, so typos show up here rather
+    // than at the first hit.
+    pub fn set_breakpoint_condition(
+        &mut self,
+        id: usize,
+        condition: Option<String>,
+    ) -> anyhow::Result<()> {
+        if !self.has_breakpoint(id) {
+            Err(anyhow!("no breakpoint with id {}", id))?;
+        }
+        match condition {
+            Some(condition) => {
+                expression::Expr::parse(&condition)?;
+                self.breakpoint_conditions.insert(id, condition);
+            }
+            None => {
+                self.breakpoint_conditions.remove(&id);
+            }
+        }
+        Ok(())
+    }
+
+    // Does what the stop the debuggee is in calls for: collects the trace frame of a tracepoint
+    // and evaluates the condition of a breakpoint. Returns whether the stop is to be reported,
+    // false means the caller resumes the debuggee. Every wait on the debuggee, `wait_for_stop`,
+    // DebugSession and AsyncDebuggee alike, goes through here.
+    pub(crate) fn process_stop(&mut self) -> anyhow::Result<bool> {
+        Ok(!self.collect_trace_frame()? && self.breakpoint_condition_holds())
+    }
+
+    // False only if the debuggee stopped at a breakpoint whose condition evaluated to zero. A
+    // condition that can't be evaluated stops the debuggee, it may well be what's looked for.
+    fn breakpoint_condition_holds(&self) -> bool {
+        let ProcessState::Stopped(StopReason::Breakpoint { id }) = self.process_state else {
+            return true;
+        };
+        let Some(condition) = self.breakpoint_conditions.get(&id) else {
+            return true;
+        };
+
+        match expression::evaluate(condition, self) {
+            Ok(value) => {
+                debug!(
+                    breakpoint = id,
+                    condition = %condition,
+                    value = %value,
+                    "breakpoint condition evaluated"
+                );
+                value.is_true()
+            }
+            Err(err) => {
+                warn!(
+                    breakpoint = id,
+                    condition = %condition,
+                    error = box_err(err),
+                    "unable to evaluate breakpoint condition"
+                );
+                true
+            }
+        }
+    }
+
+    pub fn tracepoints(&self) -> &BTreeMap<usize, Vec<TraceAction>> {
+        &self.tracepoints
+    }
+
+    // Turns a breakpoint into a tracepoint: `actions` are collected into the trace buffer
+    // whenever it's hit and the debuggee goes on.
+    pub fn set_tracepoint(&mut self, id: usize, actions: Vec<TraceAction>) -> anyhow::Result<()> {
+        if !self.has_breakpoint(id) {
+            Err(anyhow!("no breakpoint with id {}", id))?;
+        }
+        self.tracepoints.insert(id, actions);
+        Ok(())
+    }
+
+    pub fn trace_buffer(&self) -> &TraceBuffer {
+        &self.trace_buffer
+    }
+
+    pub fn trace_buffer_mut(&mut self) -> &mut TraceBuffer {
+        &mut self.trace_buffer
+    }
+
+    pub fn trace_file(&self) -> Option<&TraceFile> {
+        self.trace_file.as_ref()
+    }
+
+    // Streams every trace frame collected from now on to `path` as well, in place of the trace
+    // file there's already.
+    pub fn open_trace_file<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        backpressure: Backpressure,
+    ) -> anyhow::Result<()> {
+        let trace_file = TraceFile::create(path, DEFAULT_TRACE_FILE_QUEUE, backpressure)?;
+        self.close_trace_file()?;
+        self.trace_file = Some(trace_file);
+        Ok(())
+    }
+
+    // Returns once the frames still queued for it are written.
+    pub fn close_trace_file(&mut self) -> anyhow::Result<()> {
+        match self.trace_file.take() {
+            Some(trace_file) => trace_file.close(),
+            None => Ok(()),
+        }
+    }
+
+    // Returns whether the debuggee stopped at a tracepoint, which has been collected then.
+    // What can't be collected, like memory at an unmapped address, is kept as an error in the
+    // frame instead of failing the whole collection.
+    fn collect_trace_frame(&mut self) -> anyhow::Result<bool> {
+        let ProcessState::Stopped(StopReason::Breakpoint { id }) = self.process_state else {
+            return Ok(false);
+        };
+        let Some(actions) = self.tracepoints.get(&id).cloned() else {
+            return Ok(false);
+        };
+
+        let pc = VirtAddr::new(self.arch.pc(&self.tracer.get_regs(self.current_thread)?));
+        let mut frame = TraceFrame {
+            index: 0,
+            time: SystemTime::now(),
+            tracepoint: id,
+            thread: self.current_thread,
+            pc,
+            registers: Vec::new(),
+            memory: Vec::new(),
+            values: Vec::new(),
+        };
+        for action in actions {
+            match action {
+                TraceAction::Registers => {
+                    let registers = self
+                        .registers
+                        .as_ref()
+                        .ok_or(anyhow!("no register info available"))?;
+                    frame.registers = Register::all_registers()
+                        .into_iter()
+                        .filter(|register| register.kind() == RegisterKind::GeneralPurpose)
+                        .filter_map(|register| {
+                            Some((register, registers.read_register(register).ok()?))
+                        })
+                        .collect();
+                }
+                TraceAction::Memory { address, len } => {
+                    let result = expression::evaluate_address(&address, &*self)
+                        .and_then(|at| Ok((at, self.read_memory(at, len)?)))
+                        .map_err(|err| err.to_string());
+                    frame.memory.push(CollectedMemory {
+                        expression: address,
+                        result,
+                    });
+                }
+                TraceAction::Expression(expression) => {
+                    let result =
+                        expression::evaluate(&expression, &*self).map_err(|err| err.to_string());
+                    frame.values.push(CollectedValue { expression, result });
+                }
+            }
+        }
+
+        debug!(tracepoint = id, pc = %pc, "trace frame collected");
+        if let Some(trace_file) = &mut self.trace_file {
+            frame.index = self.trace_buffer.next_index();
+            if !trace_file.write(&frame) {
+                debug!(frame = frame.index, "trace file is behind, frame dropped");
+            }
+        }
+        self.trace_buffer.record(frame);
+        Ok(true)
+    }
+
+    pub fn heap_trace(&self) -> &HeapTrace {
+        &self.heap_trace
+    }
+
+    pub fn heap_trace_mut(&mut self) -> &mut HeapTrace {
+        &mut self.heap_trace
+    }
+
+    pub fn is_heap_tracing(&self) -> bool {
+        !self.heap_entries.is_empty()
+    }
+
+    // Hooks the allocator functions in `symbols`, their calls are recorded into the heap trace
+    // while the debuggee runs. Modules loaded later aren't hooked.
+    pub fn start_heap_trace(
+        &mut self,
+        symbols: &SymbolTable,
+    ) -> anyhow::Result<Vec<(AllocFunction, VirtAddr)>> {
+        if !matches!(self.process_state, ProcessState::Stopped(_)) {
+            Err(anyhow!("debuggee must be stopped to start heap tracing"))?;
+        }
+        if self.is_heap_tracing() {
+            Err(anyhow!("heap tracing is on already"))?;
+        }
+        let regs = self.tracer.get_regs(self.current_thread)?;
+        if self.arch.call_args(&regs).is_none() {
+            Err(anyhow!(
+                "heap tracing in {} processes is not supported",
+                self.arch.name()
+            ))?;
+        }
+
+        let entries = symbols
+            .symbols()
+            .iter()
+            .filter(|symbol| symbol.kind == SymbolKind::Function)
+            .filter_map(|symbol| Some((symbol.address, AllocFunction::from_symbol(&symbol.name)?)))
+            .collect::<BTreeMap<_, _>>();
+        if entries.is_empty() {
+            Err(anyhow!(
+                "no allocator functions found, the C library may not be loaded yet"
+            ))?;
+        }
+
+        self.heap_hooks = entries
+            .keys()
+            .map(|address| (*address, Breakpoint::new(0, *address)))
+            .collect();
+        self.heap_entries = entries;
+        info!(hooks = self.heap_entries.len(), "heap tracing started");
+
+        Ok(self
+            .heap_entries
+            .iter()
+            .map(|(address, function)| (*function, *address))
+            .collect())
+    }
+
+    // Calls that haven't returned yet are dropped, the recorded ones are kept.
+    pub fn stop_heap_trace(&mut self) -> anyhow::Result<()> {
+        if self.process_state.is_alive() {
+            self.disarm_heap_hooks()?;
+        }
+        self.heap_entries.clear();
+        self.heap_hooks.clear();
+        self.heap_calls.clear();
+        info!("heap tracing stopped");
+        Ok(())
+    }
+
+    // Hooks at a breakpoint of the user stay disarmed, its trap is taken for both.
+    fn arm_heap_hooks(&mut self) -> anyhow::Result<()> {
+        let breakpoints = &self.breakpoints;
+        breakpoint::arm_all(
+            &self.tracer,
+            self.pid,
+            self.arch.breakpoint_instruction(),
+            self.heap_hooks.values_mut().filter(|hook| {
+                !breakpoints.values().any(|breakpoint| {
+                    breakpoint.address() == hook.address() && breakpoint.is_armed()
+                })
+            }),
+        )
+    }
+
+    fn disarm_heap_hooks(&mut self) -> anyhow::Result<()> {
+        breakpoint::disarm_all(&self.tracer, self.pid, self.heap_hooks.values_mut())
+    }
+
+    // Disarmed hooks count as well: another thread may have trapped on one before the stop that
+    // disarmed it was reported.
+    fn is_heap_hook_stop(&self, tid: Pid) -> anyhow::Result<bool> {
+        if self.heap_hooks.is_empty() {
+            return Ok(false);
+        }
+        let address = VirtAddr::new(self.arch.pc(&self.tracer.get_regs(tid)?))
+            .wrapping_sub(self.arch.breakpoint_pc_offset());
+        Ok(self.heap_hooks.contains_key(&address)
+            && !self.signal_info(tid, Signal::SIGTRAP).is_single_step())
+    }
+
+    // Records what a heap tracing hook caught and lets the thread go on. Returns false if a
+    // breakpoint of the user is at the same address, whose stop is reported then.
+    fn heap_hook_hit(&mut self, tid: Pid) -> anyhow::Result<bool> {
+        let mut regs = self.tracer.get_regs(tid)?;
+        let address =
+            VirtAddr::new(self.arch.pc(&regs)).wrapping_sub(self.arch.breakpoint_pc_offset());
+        let user_breakpoint = self
+            .breakpoints
+            .values()
+            .any(|breakpoint| breakpoint.address() == address && breakpoint.is_armed());
+        if !user_breakpoint && self.arch.breakpoint_pc_offset() != 0 {
+            self.arch.set_pc(&mut regs, address.as_u64());
+            self.tracer.set_regs(tid, regs)?;
+        }
+
+        self.note_coverage_hit(tid, address)?;
+        self.record_heap_call(tid, address, &regs)?;
+        if user_breakpoint {
+            return Ok(false);
+        }
+
+        if self
+            .heap_hooks
+            .get(&address)
+            .is_some_and(Breakpoint::is_armed)
+        {
+            if let Some(hook) = self.heap_hooks.get_mut(&address) {
+                hook.disarm(&self.tracer, self.pid)?;
+            }
+            let wait_status = self.single_step(tid)?;
+            if matches!(wait_status, WaitStatus::Stopped(..)) {
+                if let Some(hook) = self.heap_hooks.get_mut(&address) {
+                    hook.arm(&self.tracer, self.pid, self.arch.breakpoint_instruction())?;
+                }
+            }
+            if !matches!(wait_status, WaitStatus::Stopped(_, Signal::SIGTRAP)) {
+                self.pending_wait_status = Some(wait_status);
+                return Ok(true);
+            }
+        }
+
+        if self.watch_stepping && tid == self.current_thread {
+            self.tracer.step(tid, None)?;
+        } else {
+            self.continue_thread(tid, None)?;
+        }
+        Ok(true)
+    }
+
+    // A hook at `address` is either the return of a pending call or the entry of a new one.
+    // Calls the allocator makes itself, like malloc mapping memory for a large block, are part
+    // of the outer call and not recorded.
+    fn record_heap_call(
+        &mut self,
+        tid: Pid,
+        address: VirtAddr,
+        regs: &libc::user_regs_struct,
+    ) -> anyhow::Result<()> {
+        let stack_pointer = VirtAddr::new(self.arch.stack_pointer(regs));
+        if let Some(index) = self.heap_calls.iter().position(|pending| {
+            pending.call.thread == tid
+                && pending.return_address == address
+                && stack_pointer >= pending.stack_pointer
+        }) {
+            let mut pending = self.heap_calls.remove(index);
+            pending.call.result = self.arch.call_return_value(regs);
+            debug!(
+                tid = %tid,
+                function = %pending.call.function,
+                result = pending.call.result,
+                "allocator call returned"
+            );
+            self.heap_trace.record(pending.call);
+            return self.drop_heap_return_hook(address);
+        }
+
+        let Some(&function) = self.heap_entries.get(&address) else {
+            return Ok(());
+        };
+        if self
+            .heap_calls
+            .iter()
+            .any(|pending| pending.call.thread == tid)
+        {
+            return Ok(());
+        }
+
+        let return_address = match self.arch.return_address(regs) {
+            ReturnAddress::Register(address) => VirtAddr::new(address),
+            ReturnAddress::Stack(at) => self.read_pointer(VirtAddr::new(at))?,
+        };
+        let call = HeapCall {
+            function,
+            thread: tid,
+            args: self.arch.call_args(regs).unwrap_or_default(),
+            result: 0,
+            backtrace: self.frame_pointer_backtrace(regs, return_address),
+        };
+        self.heap_calls.push(PendingHeapCall {
+            stack_pointer,
+            return_address,
+            call,
+        });
+
+        if !self.heap_hooks.contains_key(&return_address) {
+            let mut hook = Breakpoint::new(0, return_address);
+            // a coverage probe there is recorded and disarmed by the hook from now on
+            if let Some(probe) = self.coverage_probes.get_mut(&return_address) {
+                probe.disarm(&self.tracer, self.pid)?;
+            }
+            if !self
+                .breakpoints
+                .values()
+                .any(|breakpoint| breakpoint.address() == return_address && breakpoint.is_armed())
+            {
+                hook.arm(&self.tracer, self.pid, self.arch.breakpoint_instruction())?;
+            }
+            self.heap_hooks.insert(return_address, hook);
+        }
+
+        Ok(())
+    }
+
+    // Removes the hook at a return address once no pending call returns there anymore.
+    fn drop_heap_return_hook(&mut self, address: VirtAddr) -> anyhow::Result<()> {
+        if self.heap_entries.contains_key(&address)
+            || self
+                .heap_calls
+                .iter()
+                .any(|pending| pending.return_address == address)
+        {
+            return Ok(());
+        }
+        if let Some(mut hook) = self.heap_hooks.remove(&address) {
+            hook.disarm(&self.tracer, self.pid)?;
+        }
+        Ok(())
+    }
+
+    fn forget_heap_calls(&mut self, tid: Pid) -> anyhow::Result<()> {
+        let (gone, kept): (Vec<_>, Vec<_>) = mem::take(&mut self.heap_calls)
+            .into_iter()
+            .partition(|pending| pending.call.thread == tid);
+        self.heap_calls = kept;
+        for pending in gone {
+            self.drop_heap_return_hook(pending.return_address)?;
+        }
+        Ok(())
+    }
+
+    pub fn coverage(&self) -> Option<&Coverage> {
+        self.coverage.as_ref()
+    }
+
+    pub fn is_collecting_coverage(&self) -> bool {
+        !self.coverage_probes.is_empty()
+    }
+
+    // Puts a one-shot probe on every address, e.g. the entries of functions, recording which of
+    // them the debuggee gets to from now on. What a previous collection found is dropped.
+    pub fn start_coverage<I: IntoIterator<Item = VirtAddr>>(
+        &mut self,
+        addresses: I,
+    ) -> anyhow::Result<usize> {
+        if !matches!(self.process_state, ProcessState::Stopped(_)) {
+            Err(anyhow!(
+                "debuggee must be stopped to start collecting coverage"
+            ))?;
+        }
+        if self.is_collecting_coverage() {
+            Err(anyhow!("coverage is being collected already"))?;
+        }
+
+        let coverage = Coverage::new(addresses);
+        if coverage.is_empty() {
+            Err(anyhow!("nothing to collect coverage of"))?;
+        }
+        self.coverage_probes = coverage
+            .probes()
+            .map(|(address, _)| (address, Breakpoint::new(0, address)))
+            .collect();
+        info!(probes = coverage.len(), "collecting coverage");
+        let probes = coverage.len();
+        self.coverage = Some(coverage);
+        Ok(probes)
+    }
+
+    // The probes are removed, what they recorded is kept.
+    pub fn stop_coverage(&mut self) -> anyhow::Result<()> {
+        if self.process_state.is_alive() {
+            self.disarm_coverage_probes()?;
+        }
+        self.coverage_probes.clear();
+        info!("stopped collecting coverage");
+        Ok(())
+    }
+
+    // Probes already hit, or where a breakpoint of the user or a heap tracing hook is, stay
+    // disarmed. The latter two record coverage themselves.
+    fn arm_coverage_probes(&mut self) -> anyhow::Result<()> {
+        let (breakpoints, heap_hooks) = (&self.breakpoints, &self.heap_hooks);
+        let Some(coverage) = &self.coverage else {
+            return Ok(());
+        };
+        breakpoint::arm_all(
+            &self.tracer,
+            self.pid,
+            self.arch.breakpoint_instruction(),
+            self.coverage_probes.values_mut().filter(|probe| {
+                !coverage.is_covered(probe.address())
+                    && !heap_hooks.contains_key(&probe.address())
+                    && !breakpoints.values().any(|breakpoint| {
+                        breakpoint.address() == probe.address() && breakpoint.is_armed()
+                    })
+            }),
+        )
+    }
+
+    fn disarm_coverage_probes(&mut self) -> anyhow::Result<()> {
+        breakpoint::disarm_all(&self.tracer, self.pid, self.coverage_probes.values_mut())
+    }
+
+    // Probes hit already count as well, another thread may have trapped on one before it was
+    // disarmed.
+    fn is_coverage_probe_stop(&self, tid: Pid) -> anyhow::Result<bool> {
+        if self.coverage_probes.is_empty() {
+            return Ok(false);
+        }
+        let address = VirtAddr::new(self.arch.pc(&self.tracer.get_regs(tid)?))
+            .wrapping_sub(self.arch.breakpoint_pc_offset());
+        Ok(self.coverage_probes.contains_key(&address)
+            && !self
+                .breakpoints
+                .values()
+                .any(|breakpoint| breakpoint.address() == address && breakpoint.is_armed())
+            && !self.signal_info(tid, Signal::SIGTRAP).is_single_step())
+    }
+
+    // Records the probe and lets the thread go on without it.
+    fn coverage_probe_hit(&mut self, tid: Pid) -> anyhow::Result<()> {
+        let mut regs = self.tracer.get_regs(tid)?;
+        let address =
+            VirtAddr::new(self.arch.pc(&regs)).wrapping_sub(self.arch.breakpoint_pc_offset());
+        if self.arch.breakpoint_pc_offset() != 0 {
+            self.arch.set_pc(&mut regs, address.as_u64());
+            self.tracer.set_regs(tid, regs)?;
+        }
+        self.note_coverage_hit(tid, address)?;
+
+        if self.watch_stepping && tid == self.current_thread {
+            self.tracer.step(tid, None)?;
+        } else {
+            self.continue_thread(tid, None)?;
+        }
+        Ok(())
+    }
+
+    fn note_coverage_hit(&mut self, tid: Pid, address: VirtAddr) -> anyhow::Result<()> {
+        let Some(probe) = self.coverage_probes.get_mut(&address) else {
+            return Ok(());
+        };
+        probe.disarm(&self.tracer, self.pid)?;
+        if let Some(coverage) = &mut self.coverage {
+            if coverage.record(address, tid) {
+                debug!(tid = %tid, address = %address, "coverage probe hit");
+            }
+        }
+        Ok(())
+    }
+
+    // Library unloads are only followed while there are software breakpoints to suspend. r_debug
+    // is looked for again on every resume until the loader has filled it in.
+    fn arm_loader_hook(&mut self) -> anyhow::Result<()> {
+        if self.loader_hook.is_none() && !self.breakpoints.is_empty() {
+            if let Err(err) = self.install_loader_hook() {
+                debug!(error = box_err(err), "unable to follow library unloads");
+            }
+        }
+
+        let breakpoints = &self.breakpoints;
+        let Some(hook) = &mut self.loader_hook else {
+            return Ok(());
+        };
+        // a breakpoint of the user there traps for both
+        if breakpoints.values().any(|breakpoint| {
+            breakpoint.address() == hook.breakpoint.address() && breakpoint.is_armed()
+        }) {
+            return Ok(());
+        }
+        hook.breakpoint
+            .arm(&self.tracer, self.pid, self.arch.breakpoint_instruction())
+    }
+
+    fn disarm_loader_hook(&mut self) -> anyhow::Result<()> {
+        match &mut self.loader_hook {
+            Some(hook) => hook.breakpoint.disarm(&self.tracer, self.pid),
+            None => Ok(()),
+        }
+    }
+
+    fn install_loader_hook(&mut self) -> anyhow::Result<()> {
+        let Some(r_debug) = self.loader_rendezvous()? else {
+            return Ok(());
+        };
+        // r_brk follows r_version and r_map, each padded to a pointer
+        let pointer_size = self.arch.pointer_width().size() as u64;
+        let r_brk = self.read_pointer(r_debug + 2 * pointer_size)?;
+        if r_brk.as_u64() == 0 {
+            return Ok(());
+        }
+
+        self.loaded_modules = Some(self.memory_map()?);
+        self.loader_hook = Some(LoaderHook {
+            r_debug,
+            breakpoint: Breakpoint::new(0, r_brk),
+        });
+        debug!(r_brk = %r_brk, "following library unloads");
+        Ok(())
+    }
+
+    // A disarmed hook counts as well, another thread may have trapped on it before the stop that
+    // disarmed it was reported.
+    fn is_loader_hook_stop(&self, tid: Pid) -> anyhow::Result<bool> {
+        let Some(hook) = &self.loader_hook else {
+            return Ok(false);
+        };
+        let address = VirtAddr::new(self.arch.pc(&self.tracer.get_regs(tid)?))
+            .wrapping_sub(self.arch.breakpoint_pc_offset());
+        Ok(address == hook.breakpoint.address()
+            && !self.signal_info(tid, Signal::SIGTRAP).is_single_step())
+    }
+
+    // Catches up with the loader once its list of libraries is consistent and lets the thread go
+    // on. Returns false if a breakpoint of the user is at r_brk as well, whose stop is reported
+    // then.
+    fn loader_hook_hit(&mut self, tid: Pid) -> anyhow::Result<bool> {
+        let Some(hook) = &self.loader_hook else {
+            return Ok(true);
+        };
+        let (r_debug, address) = (hook.r_debug, hook.breakpoint.address());
+
+        let mut regs = self.tracer.get_regs(tid)?;
+        let user_breakpoint = self
+            .breakpoints
+            .values()
+            .any(|breakpoint| breakpoint.address() == address && breakpoint.is_armed());
+        if !user_breakpoint && self.arch.breakpoint_pc_offset() != 0 {
+            self.arch.set_pc(&mut regs, address.as_u64());
+            self.tracer.set_regs(tid, regs)?;
+        }
+
+        // r_state follows r_brk
+        let pointer_size = self.arch.pointer_width().size() as u64;
+        let state = self.read_memory(r_debug + 3 * pointer_size, 4)?;
+        if i32::from_le_bytes([state[0], state[1], state[2], state[3]]) == RT_CONSISTENT {
+            self.sync_modules()?;
+        }
+        if user_breakpoint {
+            return Ok(false);
+        }
+
+        if self
+            .loader_hook
+            .as_ref()
+            .is_some_and(|hook| hook.breakpoint.is_armed())
+        {
+            self.disarm_loader_hook()?;
+            let wait_status = self.single_step(tid)?;
+            if matches!(wait_status, WaitStatus::Stopped(..)) {
+                self.arm_loader_hook()?;
+            }
+            if !matches!(wait_status, WaitStatus::Stopped(_, Signal::SIGTRAP)) {
+                self.pending_wait_status = Some(wait_status);
+                return Ok(true);
+            }
+        }
+
+        if self.watch_stepping && tid == self.current_thread {
+            self.tracer.step(tid, None)?;
+        } else {
+            self.continue_thread(tid, None)?;
+        }
+        Ok(true)
+    }
+
+    // Suspends the breakpoints in the libraries unloaded or moved since the loader's list was
+    // last consistent, and arms the pending ones whose library is loaded again, wherever it is.
+    fn sync_modules(&mut self) -> anyhow::Result<()> {
+        let memory_map = self.memory_map()?;
+        let Some(previous) = self.loaded_modules.replace(memory_map.clone()) else {
+            return Ok(());
+        };
+        let (modules, previous_modules) = (memory_map.modules(), previous.modules());
+
+        for (module, base) in &previous_modules {
+            if modules.get(module) == Some(base) {
+                continue;
+            }
+            let end = previous
+                .regions()
+                .iter()
+                .filter(|region| region.path.as_deref() == Some(*module))
+                .map(|region| region.end)
+                .max()
+                .unwrap_or(*base);
+            let suspended = self.suspend_breakpoints(&previous, module);
+            debug!(module = %module, suspended = suspended.len(), "library unloaded");
+            self.module_events.push(ModuleEvent::Unloaded {
+                module: module.to_string(),
+                start: *base,
+                end,
+                suspended,
+            });
+        }
+
+        for (module, base) in &modules {
+            if previous_modules.get(module) == Some(base) {
+                continue;
+            }
+            let resumed = self.resume_breakpoints(module, *base);
+            debug!(module = %module, resumed = resumed.len(), "library loaded");
+            self.module_events.push(ModuleEvent::Loaded {
+                module: module.to_string(),
+                base: *base,
+                resumed,
+            });
+        }
+        Ok(())
+    }
+
+    // The library's mappings are gone or hold something else by now, so its breakpoints are
+    // disarmed without writing their code back.
+    fn suspend_breakpoints(&mut self, memory_map: &MemoryMap, module: &str) -> Vec<usize> {
+        let mut suspended = Vec::new();
+        for breakpoint in self.breakpoints.values_mut() {
+            if self.pending_breakpoints.contains_key(&breakpoint.id()) {
+                continue;
+            }
+            let Some(location) = ModuleOffset::from_address(memory_map, breakpoint.address())
+                .filter(|location| location.module == module)
+            else {
+                continue;
+            };
+            *breakpoint = Breakpoint::new(breakpoint.id(), breakpoint.address());
+            self.pending_breakpoints.insert(breakpoint.id(), location);
+            suspended.push(breakpoint.id());
+        }
+        suspended
+    }
+
+    // Breakpoints that can't be armed stay pending.
+    fn resume_breakpoints(&mut self, module: &str, base: VirtAddr) -> Vec<usize> {
+        let ids = self
+            .pending_breakpoints
+            .iter()
+            .filter(|(_, location)| location.module == module)
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+
+        let mut resumed = Vec::new();
+        for id in ids {
+            let mut breakpoint = Breakpoint::new(id, base + self.pending_breakpoints[&id].offset);
+            if !self.disabled_breakpoints.contains(&id) {
+                if let Err(err) =
+                    breakpoint.arm(&self.tracer, self.pid, self.arch.breakpoint_instruction())
+                {
+                    warn!(
+                        breakpoint = id,
+                        error = box_err(err),
+                        "unable to arm breakpoint"
+                    );
+                    continue;
+                }
+            }
+            self.pending_breakpoints.remove(&id);
+            self.breakpoints.insert(id, breakpoint);
+            resumed.push(id);
+        }
+        resumed
+    }
+
+    // Follows the frame pointers from a function's first instruction, where they still belong
+    // to its caller. Code built without them ends the walk early or adds bogus frames.
+    fn frame_pointer_backtrace(
+        &self,
+        regs: &libc::user_regs_struct,
+        call_site: VirtAddr,
+    ) -> Vec<VirtAddr> {
+        let mut backtrace = vec![call_site];
+        let mut frame_pointer = self.arch.frame_pointer(regs);
+        if frame_pointer < self.arch.stack_pointer(regs) {
+            return backtrace;
+        }
+
+        while backtrace.len() < heap::MAX_BACKTRACE_DEPTH && frame_pointer != 0 {
+            let (saved_frame_pointer, saved_return_address) = self.arch.frame_record(frame_pointer);
+            let (Ok(next), Ok(return_address)) = (
+                self.read_pointer(VirtAddr::new(saved_frame_pointer)),
+                self.read_pointer(VirtAddr::new(saved_return_address)),
+            ) else {
+                break;
+            };
+            if return_address.as_u64() == 0 {
+                break;
+            }
+            backtrace.push(return_address);
+            // callers are always further up the stack
+            if next.as_u64() <= frame_pointer {
+                break;
+            }
+            frame_pointer = next.as_u64();
+        }
+        backtrace
+    }
+
+    pub fn breakpoint_groups(&self) -> &BTreeMap<String, BTreeSet<usize>> {
+        &self.breakpoint_groups
+    }
+
+    // The breakpoints tagged with `group`, an unknown group is an error rather than an empty one
+    // so typos don't go unnoticed.
+    pub fn breakpoint_group(&self, group: &str) -> anyhow::Result<Vec<usize>> {
+        self.breakpoint_groups
+            .get(group)
+            .map(|ids| ids.iter().copied().collect())
+            .ok_or(anyhow!("no breakpoint group named {}", group))
+    }
+
+    // A breakpoint can be in any number of groups, a group exists as long as it has breakpoints.
+    pub fn add_breakpoint_to_group(&mut self, id: usize, group: &str) -> anyhow::Result<()> {
+        if !self.has_breakpoint(id) {
+            Err(anyhow!("no breakpoint with id {}", id))?;
+        }
+        if group.is_empty() {
+            Err(anyhow!("empty breakpoint group name"))?;
+        }
+        self.breakpoint_groups
+            .entry(group.to_string())
+            .or_default()
+            .insert(id);
+        Ok(())
+    }
+
+    pub fn remove_breakpoint_from_group(&mut self, id: usize, group: &str) -> anyhow::Result<()> {
+        let ids = self
+            .breakpoint_groups
+            .get_mut(group)
+            .ok_or(anyhow!("no breakpoint group named {}", group))?;
+        if !ids.remove(&id) {
+            Err(anyhow!("breakpoint {} is not in group {}", id, group))?;
+        }
+        if ids.is_empty() {
+            self.breakpoint_groups.remove(group);
+        }
+        Ok(())
+    }
+
+    pub fn hardware_breakpoints(&self) -> &BTreeMap<usize, VirtAddr> {
+        &self.hardware_breakpoints
+    }
+
+    // Breaks at `address` with a debug register instead of patching the code, which works on
+    // code that is mapped later or checksummed.
+    pub fn set_hardware_breakpoint(&mut self, address: VirtAddr) -> anyhow::Result<usize> {
+        if !self.process_state.is_alive() {
+            Err(anyhow!(
+                "unable to set breakpoint in an exited or terminated process"
+            ))?;
+        }
+        if let Some((existing, _)) = self
+            .hardware_breakpoints
+            .iter()
+            .find(|(_, existing)| **existing == address)
+        {
+            Err(anyhow!(
+                "breakpoint {} already exists at {}",
+                existing,
+                address
+            ))?;
+        }
+
+        let id = self.next_breakpoint_id;
+        self.allocate_debug_registers(&[DebugRegisterSlot {
+            owner: SlotOwner::Breakpoint(id),
+            address,
+            size: 1,
+            kind: WatchKind::Execute,
+        }])?;
+
+        self.next_breakpoint_id += 1;
+        self.hardware_breakpoints.insert(id, address);
+
+        Ok(id)
+    }
+
+    pub fn auto_hardware_breakpoints(&self) -> bool {
+        self.auto_hardware_breakpoints
+    }
+
+    // Lets `set_breakpoint_auto` pick debug registers for breakpoints worth one.
+    pub fn set_auto_hardware_breakpoints(&mut self, enabled: bool) {
+        self.auto_hardware_breakpoints = enabled;
+    }
+
+    // Sets a breakpoint in a debug register if it's `fast` or, with automatic hardware
+    // breakpoints on, worth one and a slot is free. Every other one gets a trap instruction.
+    pub fn set_breakpoint_auto(
+        &mut self,
+        address: VirtAddr,
+        fast: bool,
+    ) -> anyhow::Result<(usize, BreakpointMechanism)> {
+        if let Some(existing) = self
+            .hardware_breakpoints
+            .iter()
+            .find(|(_, existing)| **existing == address)
+            .map(|(id, _)| *id)
+            .or_else(|| {
+                self.breakpoints
+                    .values()
+                    .find(|breakpoint| breakpoint.address() == address)
+                    .map(Breakpoint::id)
+            })
+        {
+            Err(anyhow!(
+                "breakpoint {} already exists at {}",
+                existing,
+                address
+            ))?;
+        }
+
+        let wants_hardware =
+            fast || (self.auto_hardware_breakpoints && self.is_worth_debug_register(address));
+        if wants_hardware && self.debug_register_allocator.free_slots() > 0 {
+            match self.set_hardware_breakpoint(address) {
+                Ok(id) => return Ok((id, BreakpointMechanism::Hardware)),
+                Err(err) => debug!(
+                    error = box_err(err),
+                    address = %address,
+                    "falling back to a software breakpoint"
+                ),
+            }
+        }
+        self.set_breakpoint(address)
+            .map(|id| (id, BreakpointMechanism::Software))
+    }
+
+    fn is_worth_debug_register(&self, address: VirtAddr) -> bool {
+        // every hit of a software breakpoint costs a step over it
+        let hot = self
+            .stop_log
+            .records()
+            .filter(|record| record.pc == address)
+            .count()
+            >= HOT_BREAKPOINT_STOPS;
+        // patching a read-only mapping makes the kernel copy the page into the process
+        let read_only = self
+            .memory_map()
+            .ok()
+            .and_then(|memory_map| {
+                memory_map
+                    .region_containing(address)
+                    .map(|region| !region.permissions.write)
+            })
+            .unwrap_or(false);
+        hot || read_only
+    }
+
+    pub fn watchpoints(&self) -> &BTreeMap<usize, Watchpoint> {
+        &self.watchpoints
+    }
+
+    // Watches `size` bytes at `address`. Ranges that don't fit a single debug register are split
+    // across several, if there aren't enough free write watchpoints fall back to single stepping.
+    pub fn set_watchpoint(
+        &mut self,
+        address: VirtAddr,
+        size: usize,
+        kind: WatchKind,
+    ) -> anyhow::Result<usize> {
+        if !self.process_state.is_alive() {
+            Err(anyhow!(
+                "unable to set watchpoint in an exited or terminated process"
+            ))?;
+        }
+        if kind == WatchKind::Execute {
+            Err(anyhow!("use a hardware breakpoint to watch execution"))?;
+        }
+        if size == 0 {
+            Err(anyhow!("unable to watch an empty range"))?;
+        }
+
+        let id = self.next_watchpoint_id;
+        let slots = debug_register::split_range(address, size)
+            .into_iter()
+            .map(|(address, size)| DebugRegisterSlot {
+                owner: SlotOwner::Watchpoint(id),
+                address,
+                size,
+                kind,
+            })
+            .collect::<Vec<_>>();
+
+        let strategy = if slots.len() <= self.debug_register_allocator.free_slots() {
+            self.allocate_debug_registers(&slots)?;
+            WatchStrategy::Hardware { slots: slots.len() }
+        } else if kind == WatchKind::Write {
+            // make sure the range can be read before stepping through anything
+            self.read_memory(address, size)?;
+            WatchStrategy::Software
+        } else {
+            return Err(anyhow!(
+                "watching reads of {} bytes at {} takes {} debug registers, {} are free",
+                size,
+                address,
+                slots.len(),
+                self.debug_register_allocator.free_slots()
+            ));
+        };
+        debug!(watchpoint = id, strategy = %strategy, "watchpoint set");
+
+        self.next_watchpoint_id += 1;
+        let scope = match self.memory_map() {
+            Ok(memory_map) => self.watch_scope(&memory_map, address),
+            Err(err) => {
+                debug!(
+                    error = box_err(err),
+                    "unable to tell if watchpoint is on a stack"
+                );
+                None
+            }
+        };
+        self.watchpoints.insert(
+            id,
+            Watchpoint::new(id, address, size, kind, strategy, scope),
+        );
+
+        Ok(id)
+    }
+
+    // Watches writes to `size` bytes at `address` by write protecting the pages they are on, for
+    // ranges too large for debug registers. Every write to those pages costs a fault and a
+    // single step, writes elsewhere on them included. Syscalls reading into a buffer there, read(2)
+    // say, are stopped at and let through with the pages writable. Any other syscall the kernel
+    // writes into the pages for fails with EFAULT, and the watchpoint never sees it.
+    pub fn set_page_watchpoint(&mut self, address: VirtAddr, size: usize) -> anyhow::Result<usize> {
+        if !matches!(self.process_state, ProcessState::Stopped(_)) {
+            Err(anyhow!(
+                "debuggee must be stopped to set a page protection watchpoint"
+            ))?;
+        }
+        // mprotect is injected with the syscall numbers of the debugger's own architecture
+        if self.arch.name() != arch::native().name() {
+            Err(anyhow!(
+                "page protection watchpoints in {} processes are not supported",
+                self.arch.name()
+            ))?;
+        }
+        if size == 0 {
+            Err(anyhow!("unable to watch an empty range"))?;
+        }
+
+        let pages = watchpoint::watched_pages(address, size, PAGE_SIZE);
+        let memory_map = self.memory_map()?;
+        let mut protections = Vec::new();
+        for page in (pages.start.as_u64()..pages.end.as_u64())
+            .step_by(PAGE_SIZE as usize)
+            .map(VirtAddr::new)
+        {
+            // protected for another watchpoint already
+            if self.page_protections.contains_key(&page) {
+                continue;
+            }
+            let region = memory_map
+                .region_containing(page)
+                .ok_or(anyhow!("{} is not mapped", page))?;
+            if !region.permissions.write {
+                Err(anyhow!("{} is not writable", page))?;
+            }
+            protections.push((page, region.permissions.prot()));
+        }
+
+        let protected = protections
+            .iter()
+            .map(|(page, prot)| (*page, prot & !libc::PROT_WRITE))
+            .collect::<Vec<_>>();
+        self.protect_pages(self.current_thread, &protected)?;
+        self.page_protections.extend(protections);
+
+        let id = self.next_watchpoint_id;
+        let page_count = (pages.end.offset_from(pages.start).unwrap_or(0) / PAGE_SIZE) as usize;
+        let strategy = WatchStrategy::PageProtection { pages: page_count };
+        debug!(watchpoint = id, strategy = %strategy, "watchpoint set");
+
+        self.next_watchpoint_id += 1;
+        let scope = self.watch_scope(&memory_map, address);
+        self.watchpoints.insert(
+            id,
+            Watchpoint::new(id, address, size, WatchKind::Write, strategy, scope),
+        );
+
+        Ok(id)
+    }
+
+    // The frame a watchpoint at `address` belongs to, if it's on the stack of a thread.
+    fn watch_scope(&self, memory_map: &MemoryMap, address: VirtAddr) -> Option<WatchScope> {
+        let stack_pointers = self.stack_pointers();
+        let Some(RegionKind::Stack(Some(thread))) = memory_map.kind_of(address, &stack_pointers)
+        else {
+            return None;
+        };
+        Some(WatchScope {
+            thread,
+            stack_pointer: stack_pointers[&thread],
+        })
+    }
+
+    // Scoped watchpoints whose frame has returned, or whose thread is gone. Threads whose
+    // registers can't be read are assumed to still be in the frame.
+    fn popped_watchpoints(&self) -> Vec<usize> {
+        self.watchpoints
+            .values()
+            .filter(|watchpoint| {
+                watchpoint.scope().is_some_and(|scope| {
+                    if !self.threads.contains(&scope.thread) {
+                        return true;
+                    }
+                    self.tracer.get_regs(scope.thread).is_ok_and(|regs| {
+                        let stack_pointer = VirtAddr::new(self.arch.stack_pointer(&regs));
+                        scope.is_popped(watchpoint.address(), watchpoint.size(), stack_pointer)
+                    })
+                })
+            })
+            .map(Watchpoint::id)
+            .collect()
+    }
+
+    // Deletes the scoped watchpoints whose frame has returned. A hit of one of them is stack
+    // memory reused by another call, and is reported as the watchpoint going out of scope.
+    fn expire_scoped_watchpoints(&mut self) -> anyhow::Result<()> {
+        for id in self.popped_watchpoints() {
+            debug!(watchpoint = id, "watched frame returned");
+            self.remove_watchpoint(id)?;
+            match self.process_state {
+                ProcessState::Stopped(StopReason::Watchpoint { id: hit, .. }) if hit == id => {
+                    self.process_state = ProcessState::Stopped(StopReason::WatchpointScope { id });
+                }
+                _ => self.expired_watchpoints.push(id),
+            }
+        }
+        Ok(())
+    }
+
+    // Scoped watchpoints deleted since the last call, other than the one stopped for.
+    pub fn take_expired_watchpoints(&mut self) -> Vec<usize> {
+        mem::take(&mut self.expired_watchpoints)
+    }
+
+    pub fn remove_watchpoint(&mut self, id: usize) -> anyhow::Result<()> {
+        let watchpoint = self
+            .watchpoints
+            .get(&id)
+            .ok_or(anyhow!("no watchpoint with id {}", id))?;
+        if watchpoint.is_page_protected() {
+            self.release_watched_pages(id)?;
+        }
+        self.watchpoints.remove(&id);
+        self.debug_register_allocator
+            .release(SlotOwner::Watchpoint(id));
+
+        self.sync_debug_registers()
+    }
+
+    // Restores the protection of the pages only watchpoint `id` needs protected.
+    fn release_watched_pages(&mut self, id: usize) -> anyhow::Result<()> {
+        if !matches!(self.process_state, ProcessState::Stopped(_)) {
+            Err(anyhow!(
+                "debuggee must be stopped to remove a page protection watchpoint"
+            ))?;
+        }
+
+        let pages = self.watchpoints[&id].pages(PAGE_SIZE);
+        let released = self
+            .page_protections
+            .range(pages)
+            .filter(|(page, _)| {
+                !self.watchpoints.values().any(|watchpoint| {
+                    watchpoint.id() != id
+                        && watchpoint.is_page_protected()
+                        && watchpoint.pages(PAGE_SIZE).contains(*page)
+                })
+            })
+            .map(|(page, prot)| (*page, *prot))
+            .collect::<Vec<_>>();
+
+        self.protect_pages(self.current_thread, &released)?;
+        for (page, _) in released {
+            self.page_protections.remove(&page);
+        }
+        if self.page_protections.is_empty() {
+            // syscalls aren't stopped at anymore to protect the pages again when they return
+            self.syscall_writes.clear();
+        }
+
+        Ok(())
+    }
+
+    pub fn debug_register_allocator(&self) -> &DebugRegisterAllocator {
+        &self.debug_register_allocator
+    }
+
+    fn allocate_debug_registers(&mut self, slots: &[DebugRegisterSlot]) -> anyhow::Result<()> {
+        self.debug_register_allocator.allocate_all(slots)?;
+        if let Err(err) = self.sync_debug_registers() {
+            self.debug_register_allocator.release(slots[0].owner);
+            if let Err(err) = self.sync_debug_registers() {
+                warn!(error = box_err(err), "unable to restore debug registers");
+            }
+            return Err(err);
+        }
+        Ok(())
+    }
+
+    // Writes the debug registers of every thread. Running threads can't be written to, they
+    // catch up when they stop next.
+    fn sync_debug_registers(&mut self) -> anyhow::Result<()> {
+        let threads = self.threads.iter().copied().collect::<Vec<_>>();
+        for tid in threads {
+            match self.sync_thread_debug_registers(tid) {
+                Err(Errno::ESRCH) if tid != self.current_thread => {
+                    debug!(tid = %tid, "thread is running, deferring debug register update")
+                }
+                result => result?,
+            }
+        }
+
+        if let ProcessState::Stopped(_) = self.process_state {
+            self.read_registers()?;
+        }
+
+        Ok(())
+    }
+
+    fn sync_thread_debug_registers(&mut self, tid: Pid) -> nix::Result<()> {
+        let (addresses, dr7) = self.debug_register_allocator.encode();
+        let up_to_date = match self.debug_registers.get(&tid) {
+            Some(current) => current[..addresses.len()] == addresses && current[DR7] == dr7,
+            None => dr7 == 0,
+        };
+        if up_to_date {
+            return Ok(());
+        }
+
+        let offset = debug_register::offset_in_user_struct;
+        // disable every slot first, the kernel validates each address against the enabled ones
+        self.tracer.write_user(tid, offset(DR7), 0)?;
+        for (index, address) in addresses.iter().enumerate() {
+            self.tracer
+                .write_user(tid, offset(index), *address as libc::c_long)?;
+        }
+        self.tracer
+            .write_user(tid, offset(DR7), dr7 as libc::c_long)?;
+
+        let debug_registers = self.debug_registers.entry(tid).or_default();
+        debug_registers[..addresses.len()].copy_from_slice(&addresses);
+        debug_registers[DR7] = dr7;
+
+        Ok(())
+    }
+
+    pub fn checkpoints(&self) -> &BTreeMap<usize, Pid> {
+        &self.checkpoints
+    }
+
+    // Where the debuggee was when the checkpoint was taken, the fork hasn't run since.
+    pub fn checkpoint_pc(&self, id: usize) -> anyhow::Result<VirtAddr> {
+        let checkpoint_pid = self
+            .checkpoints
+            .get(&id)
+            .ok_or(anyhow!("no checkpoint with id {}", id))?;
+        Ok(VirtAddr::new(
+            self.arch.pc(&self.tracer.get_regs(*checkpoint_pid)?),
+        ))
+    }
+
+    pub fn delete_checkpoint(&mut self, id: usize) -> anyhow::Result<()> {
+        let checkpoint_pid = self
+            .checkpoints
+            .remove(&id)
+            .ok_or(anyhow!("no checkpoint with id {}", id))?;
+        kill_traced_process(checkpoint_pid);
+        debug!(checkpoint = id, checkpoint_pid = %checkpoint_pid, "checkpoint deleted");
+        Ok(())
+    }
+
+    fn read_registers(&mut self) -> anyhow::Result<()> {
+        let span = debug_span!(
+            "read registers of debuggee",
+            pid = tracing::field::display(&self.pid),
+        );
+        let _entered = span.entered();
+
+        debug!("reading registers");
+        let regs = match self.debug_registers.get(&self.current_thread) {
+            Some(debug_registers) => Registers::read_with_debug_registers(
+                &self.tracer,
+                self.current_thread,
+                *debug_registers,
+            )?,
+            None => {
+                let regs = Registers::read_with_tracer(&self.tracer, self.current_thread)?;
+                self.debug_registers
+                    .insert(self.current_thread, regs.debug_registers());
+                regs
+            }
+        };
+
+        self.registers = Some(regs);
+
+        Ok(())
+    }
+}
+
+impl<T: Tracer> Drop for Debuggee<T> {
+    fn drop(&mut self) {
+        let span = debug_span!(
+            "dropping debuggee",
+            pid = (tracing::field::display(&self.pid))
+        );
+        let _entered = span.enter();
+
+        for (id, checkpoint_pid) in &self.checkpoints {
+            debug!(checkpoint = id, "discarding checkpoint");
+            kill_traced_process(*checkpoint_pid);
+        }
+
+        for child in mem::take(&mut self.fork_children) {
+            debug!(child = %child.pid, "releasing forked child");
+            if let Err(err) = self
+                .restore_code(child.pid, child.breakpoints.values())
+                .and_then(|()| Ok(self.tracer.detach(child.pid, None)?))
+            {
+                warn!(error = box_err(err), child = %child.pid, "unable to release forked child");
+            }
+        }
+
+        info!("detaching from debuggee");
+
+        let _ = self.update_process_state(false);
+
+        if self.process_state.is_alive() {
+            if let Err(err) =
+                breakpoint::disarm_all(&self.tracer, self.pid, self.breakpoints.values_mut())
+            {
+                warn!(error = box_err(err), "unable to remove breakpoints");
+            }
+            if let Err(err) = self.cancel_pending_step() {
+                warn!(error = box_err(err), "unable to remove step breakpoint");
+            }
+            if let Err(err) = self.disarm_heap_hooks() {
+                warn!(
+                    error = box_err(err),
+                    "unable to remove heap tracing breakpoints"
+                );
+            }
+            if let Err(err) = self.disarm_coverage_probes() {
+                warn!(error = box_err(err), "unable to remove coverage probes");
+            }
+            if let Err(err) = self.disarm_loader_hook() {
+                warn!(
+                    error = box_err(err),
+                    "unable to remove the library unload hook"
+                );
+            }
+
+            if matches!(self.process_state, ProcessState::Stopped(_)) {
+                let protections = mem::take(&mut self.page_protections)
+                    .into_iter()
+                    .collect::<Vec<_>>();
+                if let Err(err) = self.protect_pages(self.current_thread, &protections) {
+                    warn!(error = box_err(err), "unable to restore page protections");
+                }
+            }
+
+            self.debug_register_allocator = DebugRegisterAllocator::new();
+            if let Err(err) = self.sync_debug_registers() {
+                warn!(error = box_err(err), "unable to clear debug registers");
+            }
+
+            for tid in self.threads.iter().filter(|tid| **tid != self.pid) {
+                if let Err(err) = self.tracer.detach(*tid, None) {
+                    warn!(error = box_err(err), tid = %tid, "unable to detach from thread");
+                }
+            }
+
+            if self.is_group_stopped() {
+                // left in its group-stop for whoever stopped it to continue
+                if matches!(self.process_state, ProcessState::Running)
+                    && self.group_stopped.get(&self.pid) == Some(&true)
+                {
+                    // a listening thread has to be back in a ptrace stop to be detached
+                    if let Err(err) = self
+                        .tracer
+                        .interrupt(self.pid)
+                        .and_then(|()| self.tracer.wait(self.pid, WaitPidFlag::__WALL))
+                    {
+                        warn!(error = box_err(err), "unable to interrupt the debuggee");
+                    }
+                }
+                if let Err(err) = self.tracer.detach(self.pid, None) {
+                    warn!(
+                        error = box_err(err),
+                        "unable to detach from the debuggee process",
+                    )
+                }
+            } else {
+                if let Err(err) = self.tracer.kill(self.pid, Signal::SIGSTOP) {
+                    warn!(error = box_err(err), "unable to stop the debuggee process");
+
+                    return;
+                };
+
+                if let Err(err) = self.tracer.detach(self.pid, Some(Signal::SIGCONT)) {
+                    warn!(
+                        error = box_err(err),
+                        "unable to detach from the debuggee process",
+                    )
+                }
+
+                if let Err(err) = self.tracer.kill(self.pid, Signal::SIGCONT) {
+                    warn!(
+                        error = box_err(err),
+                        "unable to resume the debuggee process",
+                    )
+                }
+            }
+
+            if self.should_terminate {
+                info!("terminating debuggee");
+
+                if let Err(err) = self.tracer.kill(self.pid, Signal::SIGKILL) {
+                    warn!(error = box_err(err), "unable to kill the debuggee");
+
+                    return;
+                }
+
+                if let Err(err) = self.tracer.wait(self.pid, WaitPidFlag::empty()) {
+                    warn!(error = box_err(err), "unable to wait for debuggee to exit")
+                }
+            }
+        }
+
+        if self.should_terminate {
+            _ = wait();
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use anyhow::anyhow;
+use iced_x86::{Decoder, DecoderError, DecoderOptions, Formatter as _, GasFormatter};
+
+use crate::{arch::Arch, virt_addr::VirtAddr};
+
+// A decoded instruction in AT&T syntax, the way gdb shows it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instruction {
+    pub address: VirtAddr,
+    pub bytes: Vec<u8>,
+    // `(bad)` for bytes that don't make an instruction
+    pub text: String,
+}
+
+// Decodes up to `count` instructions from `bytes`, which are at `address` in the debuggee. Stops
+// early at an instruction cut short by the end of `bytes`.
+pub fn disassemble(
+    arch: &dyn Arch,
+    address: VirtAddr,
+    bytes: &[u8],
+    count: usize,
+) -> anyhow::Result<Vec<Instruction>> {
+    let bitness = match arch.name() {
+        "x86_64" => 64,
+        "i386" => 32,
+        name => Err(anyhow!("unable to disassemble {} code", name))?,
+    };
+
+    let mut decoder = Decoder::with_ip(bitness, bytes, address.as_u64(), DecoderOptions::NONE);
+    let mut formatter = GasFormatter::new();
+    let mut instructions = Vec::new();
+    while instructions.len() < count && decoder.can_decode() {
+        let offset = decoder.position();
+        let instruction = decoder.decode();
+        let text = match decoder.last_error() {
+            DecoderError::None => {
+                let mut text = String::new();
+                formatter.format(&instruction, &mut text);
+                text
+            }
+            DecoderError::NoMoreBytes => break,
+            _ => "(bad)".to_string(),
+        };
+        instructions.push(Instruction {
+            address: address + offset as u64,
+            bytes: bytes[offset..offset + instruction.len()].to_vec(),
+            text,
+        });
+    }
+    Ok(instructions)
+}
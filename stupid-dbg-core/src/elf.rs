@@ -0,0 +1,388 @@
+use std::{fmt, ops::Deref, path::Path, sync::Arc};
+
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    arch::PointerWidth,
+    auxv::{self, ProgramHeader},
+    mapped_file::MappedFile,
+};
+
+const ELFCLASS32: u8 = 1;
+const ELFCLASS64: u8 = 2;
+const ELFDATA2LSB: u8 = 1;
+
+pub const ET_EXEC: u16 = 2;
+pub const ET_DYN: u16 = 3;
+
+pub(crate) const PT_LOAD: u32 = 1;
+pub(crate) const PT_INTERP: u32 = 3;
+pub(crate) const PT_GNU_STACK: u32 = 0x6474e551;
+pub(crate) const PT_GNU_RELRO: u32 = 0x6474e552;
+pub(crate) const PF_X: u32 = 1;
+
+const SHT_SYMTAB: u32 = 2;
+const SHT_DYNAMIC: u32 = 6;
+const SHT_DYNSYM: u32 = 11;
+const SHN_UNDEF: u16 = 0;
+
+const STT_OBJECT: u8 = 1;
+const STT_FUNC: u8 = 2;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Section {
+    pub name: String,
+    pub sh_type: u32,
+    pub flags: u64,
+    pub address: u64,
+    pub offset: u64,
+    pub size: u64,
+    pub link: u32,
+    pub entry_size: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SymbolKind {
+    Function,
+    Object,
+}
+
+impl fmt::Display for SymbolKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SymbolKind::Function => write!(f, "function"),
+            SymbolKind::Object => write!(f, "variable"),
+        }
+    }
+}
+
+// A defined function or variable, at its link time address.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ElfSymbol {
+    pub name: String,
+    pub address: u64,
+    pub size: u64,
+    pub kind: SymbolKind,
+}
+
+struct SymbolEntry {
+    name: String,
+    info: u8,
+    shndx: u16,
+    address: u64,
+    size: u64,
+}
+
+// Contents of an ELF file, mapped when it's read from disk so that only the parts looked at take
+// up memory, and shared by the clones.
+#[derive(Debug, Clone)]
+enum ElfBytes {
+    Owned(Vec<u8>),
+    Mapped(Arc<MappedFile>),
+}
+
+impl Deref for ElfBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            ElfBytes::Owned(bytes) => bytes,
+            ElfBytes::Mapped(file) => file.bytes(),
+        }
+    }
+}
+
+// The parts of a little endian ELF file the debugger looks at. The whole file is kept, section
+// contents are sliced out of it on demand. Only the section headers, the symbol tables and the
+// program headers are needed, and the latter are parsed by auxv already, for the ones in memory,
+// so this is written by hand rather than pulling in object or goblin.
+#[derive(Debug, Clone)]
+pub struct ElfFile {
+    bytes: ElfBytes,
+    pointer_width: PointerWidth,
+    e_type: u16,
+    sections: Vec<Section>,
+    program_headers: Vec<ProgramHeader>,
+}
+
+fn u16_at(bytes: &[u8], offset: usize) -> anyhow::Result<u16> {
+    Ok(u16::from_le_bytes(
+        bytes
+            .get(offset..offset + 2)
+            .ok_or(anyhow!("truncated ELF file"))?
+            .try_into()
+            .unwrap(),
+    ))
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> anyhow::Result<u32> {
+    Ok(u32::from_le_bytes(
+        bytes
+            .get(offset..offset + 4)
+            .ok_or(anyhow!("truncated ELF file"))?
+            .try_into()
+            .unwrap(),
+    ))
+}
+
+// A word of the file's class, 4 or 8 bytes.
+fn word_at(bytes: &[u8], offset: usize, pointer_width: PointerWidth) -> anyhow::Result<u64> {
+    match pointer_width {
+        PointerWidth::Bits32 => u32_at(bytes, offset).map(u64::from),
+        PointerWidth::Bits64 => Ok(u64::from_le_bytes(
+            bytes
+                .get(offset..offset + 8)
+                .ok_or(anyhow!("truncated ELF file"))?
+                .try_into()
+                .unwrap(),
+        )),
+    }
+}
+
+fn c_string_at(bytes: &[u8], offset: usize) -> String {
+    let bytes = bytes.get(offset..).unwrap_or_default();
+    let end = bytes
+        .iter()
+        .position(|byte| *byte == 0)
+        .unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).into_owned()
+}
+
+impl ElfFile {
+    pub fn read<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        Self::parse_bytes(ElfBytes::Mapped(Arc::new(MappedFile::open(path)?)))
+    }
+
+    pub fn parse(bytes: Vec<u8>) -> anyhow::Result<Self> {
+        Self::parse_bytes(ElfBytes::Owned(bytes))
+    }
+
+    fn parse_bytes(bytes: ElfBytes) -> anyhow::Result<Self> {
+        if bytes.len() < 52 || bytes[..4] != *b"\x7fELF" {
+            Err(anyhow!("not an ELF file"))?;
+        }
+        if bytes[5] != ELFDATA2LSB {
+            Err(anyhow!("big endian ELF files are not supported"))?;
+        }
+        let pointer_width = match bytes[4] {
+            ELFCLASS32 => PointerWidth::Bits32,
+            ELFCLASS64 => PointerWidth::Bits64,
+            class => Err(anyhow!("unknown ELF class {}", class))?,
+        };
+        let word = pointer_width.size();
+
+        // e_type, e_machine and e_version come before e_entry, e_phoff and e_shoff, then
+        // e_flags, e_ehsize and the table sizes
+        let e_type = u16_at(&bytes, 16)?;
+        let e_phoff = word_at(&bytes, 24 + word, pointer_width)? as usize;
+        let e_shoff = word_at(&bytes, 24 + 2 * word, pointer_width)? as usize;
+        let sizes = 24 + 3 * word + 6;
+        let e_phentsize = u16_at(&bytes, sizes)? as usize;
+        let e_phnum = u16_at(&bytes, sizes + 2)? as usize;
+        let e_shentsize = u16_at(&bytes, sizes + 4)? as usize;
+        let e_shnum = u16_at(&bytes, sizes + 6)? as usize;
+        let e_shstrndx = u16_at(&bytes, sizes + 8)? as usize;
+
+        let program_headers = match e_phentsize {
+            0 => Vec::new(),
+            _ => auxv::parse_program_headers(
+                bytes
+                    .get(e_phoff..e_phoff + e_phentsize * e_phnum)
+                    .ok_or(anyhow!("truncated program headers"))?,
+                e_phentsize,
+                pointer_width,
+            ),
+        };
+
+        let mut sections = (0..e_shnum)
+            .map(|index| {
+                let header = e_shoff + index * e_shentsize;
+                // sh_name, sh_type, then words for flags, addr, offset and size, sh_link and
+                // sh_info, and words again for addralign and entsize
+                let word_field =
+                    |field: usize| word_at(&bytes, header + 8 + field * word, pointer_width);
+                Ok(Section {
+                    name: String::new(),
+                    sh_type: u32_at(&bytes, header + 4)?,
+                    flags: word_field(0)?,
+                    address: word_field(1)?,
+                    offset: word_field(2)?,
+                    size: word_field(3)?,
+                    link: u32_at(&bytes, header + 8 + 4 * word)?,
+                    entry_size: word_at(&bytes, header + 16 + 5 * word, pointer_width)?,
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        if let Some(names) = sections
+            .get(e_shstrndx)
+            .map(|section| section.offset as usize)
+        {
+            for (index, section) in sections.iter_mut().enumerate() {
+                let name = u32_at(&bytes, e_shoff + index * e_shentsize)? as usize;
+                section.name = c_string_at(&bytes, names + name);
+            }
+        }
+
+        Ok(Self {
+            bytes,
+            pointer_width,
+            e_type,
+            sections,
+            program_headers,
+        })
+    }
+
+    pub fn pointer_width(&self) -> PointerWidth {
+        self.pointer_width
+    }
+
+    pub fn e_type(&self) -> u16 {
+        self.e_type
+    }
+
+    pub fn is_position_independent(&self) -> bool {
+        self.e_type == ET_DYN
+    }
+
+    pub fn sections(&self) -> &[Section] {
+        &self.sections
+    }
+
+    pub fn section(&self, name: &str) -> Option<&Section> {
+        self.sections.iter().find(|section| section.name == name)
+    }
+
+    pub fn section_data(&self, section: &Section) -> Option<&[u8]> {
+        let start = section.offset as usize;
+        self.bytes.get(start..start + section.size as usize)
+    }
+
+    pub(crate) fn program_headers(&self) -> &[ProgramHeader] {
+        &self.program_headers
+    }
+
+    // The lowest address a PT_LOAD segment is linked at, rounded down to a page. The module's
+    // first mapping is there plus the load bias.
+    pub fn link_base(&self) -> u64 {
+        self.program_headers
+            .iter()
+            .filter(|header| header.p_type == PT_LOAD)
+            .map(|header| header.vaddr & !0xfff)
+            .min()
+            .unwrap_or(0)
+    }
+
+    // Tag and value of the entries of the dynamic section, empty for static executables.
+    pub fn dynamic_entries(&self) -> Vec<(u64, u64)> {
+        self.sections
+            .iter()
+            .find(|section| section.sh_type == SHT_DYNAMIC)
+            .and_then(|section| self.section_data(section))
+            .map(|data| auxv::dynamic_entries(data, self.pointer_width).collect())
+            .unwrap_or_default()
+    }
+
+    // Defined functions and variables of .symtab, or of .dynsym for stripped files.
+    pub fn symbols(&self) -> anyhow::Result<Vec<ElfSymbol>> {
+        let table = self
+            .sections
+            .iter()
+            .find(|section| section.sh_type == SHT_SYMTAB)
+            .or_else(|| {
+                self.sections
+                    .iter()
+                    .find(|section| section.sh_type == SHT_DYNSYM)
+            });
+        let Some(table) = table else {
+            return Ok(Vec::new());
+        };
+
+        let mut symbols = Vec::new();
+        for entry in self.symbol_entries(table)? {
+            let kind = match entry.info & 0xf {
+                STT_FUNC => SymbolKind::Function,
+                STT_OBJECT => SymbolKind::Object,
+                _ => continue,
+            };
+            if entry.shndx == SHN_UNDEF || entry.name.is_empty() {
+                continue;
+            }
+
+            symbols.push(ElfSymbol {
+                name: entry.name,
+                address: entry.address,
+                size: entry.size,
+                kind,
+            });
+        }
+
+        Ok(symbols)
+    }
+
+    // Names in both symbol tables, the ones imported from other modules included.
+    pub fn symbol_names(&self) -> anyhow::Result<Vec<String>> {
+        let mut names = Vec::new();
+        for table in self
+            .sections
+            .iter()
+            .filter(|section| matches!(section.sh_type, SHT_SYMTAB | SHT_DYNSYM))
+        {
+            names.extend(
+                self.symbol_entries(table)?
+                    .into_iter()
+                    .map(|entry| entry.name)
+                    .filter(|name| !name.is_empty()),
+            );
+        }
+        names.sort();
+        names.dedup();
+        Ok(names)
+    }
+
+    fn symbol_entries(&self, table: &Section) -> anyhow::Result<Vec<SymbolEntry>> {
+        let data = self
+            .section_data(table)
+            .ok_or(anyhow!("truncated symbol table"))?;
+        let names = self
+            .sections
+            .get(table.link as usize)
+            .and_then(|section| self.section_data(section))
+            .ok_or(anyhow!("symbol table without string table"))?;
+        let entry_size = match (table.entry_size, self.pointer_width) {
+            (0, PointerWidth::Bits32) => 16,
+            (0, PointerWidth::Bits64) => 24,
+            (entry_size, _) => entry_size as usize,
+        };
+
+        let mut entries = Vec::new();
+        for entry in data.chunks_exact(entry_size) {
+            // st_name, st_value, st_size, st_info, st_other and st_shndx for 32-bit files,
+            // st_info, st_other and st_shndx come before the value and size in 64-bit ones
+            let (info, shndx, address, size) = match self.pointer_width {
+                PointerWidth::Bits32 => (
+                    entry[12],
+                    u16_at(entry, 14)?,
+                    u32_at(entry, 4)? as u64,
+                    u32_at(entry, 8)? as u64,
+                ),
+                PointerWidth::Bits64 => (
+                    entry[4],
+                    u16_at(entry, 6)?,
+                    word_at(entry, 8, PointerWidth::Bits64)?,
+                    word_at(entry, 16, PointerWidth::Bits64)?,
+                ),
+            };
+            entries.push(SymbolEntry {
+                name: c_string_at(names, u32_at(entry, 0)? as usize),
+                info,
+                shndx,
+                address,
+                size,
+            });
+        }
+
+        Ok(entries)
+    }
+}
@@ -0,0 +1,835 @@
+use std::{collections::BTreeMap, fmt, iter::Peekable, str::Chars};
+
+use anyhow::anyhow;
+use tracing::warn;
+
+use crate::{
+    aux::as_u8_slice,
+    c_type::{self, BaseType, CType},
+    debuggee::Debuggee,
+    register::{Register, RegisterValue},
+    tracer::Tracer,
+    virt_addr::VirtAddr,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value {
+    Int(i64),
+    Float(f64),
+}
+
+impl Value {
+    pub fn as_i64(self) -> i64 {
+        match self {
+            Value::Int(x) => x,
+            Value::Float(x) => x as i64,
+        }
+    }
+
+    // Addresses and other unsigned quantities are kept in the bits of an i64.
+    pub fn as_u64(self) -> u64 {
+        self.as_i64() as u64
+    }
+
+    pub fn as_f64(self) -> f64 {
+        match self {
+            Value::Int(x) => x as f64,
+            Value::Float(x) => x,
+        }
+    }
+
+    pub fn is_true(self) -> bool {
+        match self {
+            Value::Int(x) => x != 0,
+            Value::Float(x) => x != 0.0,
+        }
+    }
+
+    fn from_bool(b: bool) -> Self {
+        Value::Int(b as i64)
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Int(x) => write!(f, "{}", x),
+            Value::Float(x) => write!(f, "{}", x),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOp {
+    Neg,
+    Not,
+    BitNot,
+    Deref,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Mul,
+    Div,
+    Rem,
+    Add,
+    Sub,
+    Shl,
+    Shr,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+    BitAnd,
+    BitXor,
+    BitOr,
+    And,
+    Or,
+}
+
+impl BinaryOp {
+    // Binding power, the same order as in C.
+    fn precedence(self) -> u8 {
+        match self {
+            BinaryOp::Or => 1,
+            BinaryOp::And => 2,
+            BinaryOp::BitOr => 3,
+            BinaryOp::BitXor => 4,
+            BinaryOp::BitAnd => 5,
+            BinaryOp::Eq | BinaryOp::Ne => 6,
+            BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge => 7,
+            BinaryOp::Shl | BinaryOp::Shr => 8,
+            BinaryOp::Add | BinaryOp::Sub => 9,
+            BinaryOp::Mul | BinaryOp::Div | BinaryOp::Rem => 10,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Literal(Value),
+    // $rip, without the dollar sign
+    Register(String),
+    Variable(String),
+    Member(Box<Expr>, String),
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    Cast(CType, Box<Expr>),
+}
+
+// What an expression can refer to in the debuggee.
+pub trait EvalContext {
+    fn register(&self, name: &str) -> anyhow::Result<Value>;
+    fn read_memory(&self, address: u64, len: usize) -> anyhow::Result<Vec<u8>>;
+
+    fn pointer_size(&self) -> usize {
+        8
+    }
+
+    fn variable(&self, name: &str) -> anyhow::Result<Value> {
+        Err(anyhow!("no symbol \"{}\" in current context", name))
+    }
+
+    fn member(&self, _base: &Expr, member: &str) -> anyhow::Result<Value> {
+        Err(anyhow!("no debug info to look up member \"{}\"", member))
+    }
+
+    // Where a variable lives, for assigning to it.
+    fn variable_location(&self, name: &str) -> anyhow::Result<Lvalue> {
+        Err(anyhow!("no symbol \"{}\" in current context", name))
+    }
+}
+
+// Something a value can be assigned to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Lvalue {
+    // without the dollar sign
+    Register(String),
+    Memory { address: u64, size: usize },
+    // a float or a double in memory, what's stored there is converted to it
+    FloatMemory { address: u64, size: usize },
+}
+
+impl fmt::Display for Lvalue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Lvalue::Register(name) => write!(f, "${}", name),
+            Lvalue::Memory { address, size } => write!(f, "{} bytes at {:#x}", size, address),
+            Lvalue::FloatMemory { address, size } => {
+                write!(f, "{} byte float at {:#x}", size, address)
+            }
+        }
+    }
+}
+
+// Variables set with `$name = expr`. They belong to the debugger rather than the debuggee, so
+// they survive restarting or switching it.
+#[derive(Debug, Clone, Default)]
+pub struct ConvenienceVariables {
+    variables: BTreeMap<String, Value>,
+}
+
+impl ConvenienceVariables {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, name: &str) -> Option<Value> {
+        self.variables.get(name).copied()
+    }
+
+    pub fn set(&mut self, name: &str, value: Value) -> anyhow::Result<()> {
+        if Register::lookup_by_name(name).is_some() {
+            Err(anyhow!(
+                "${} is a register, not a convenience variable",
+                name
+            ))?;
+        }
+        self.variables.insert(name.to_string(), value);
+        Ok(())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, Value)> {
+        self.variables
+            .iter()
+            .map(|(name, value)| (name.as_str(), *value))
+    }
+}
+
+// Resolves `$name` to a register of `context` if there is one by that name, to a convenience
+// variable otherwise.
+pub struct WithConvenienceVariables<'a, C: ?Sized> {
+    pub context: &'a C,
+    pub variables: &'a ConvenienceVariables,
+}
+
+impl<C: EvalContext + ?Sized> EvalContext for WithConvenienceVariables<'_, C> {
+    fn register(&self, name: &str) -> anyhow::Result<Value> {
+        if Register::lookup_by_name(name).is_some() {
+            return self.context.register(name);
+        }
+        self.variables
+            .get(name)
+            .ok_or(anyhow!("convenience variable ${} is not set", name))
+    }
+
+    fn read_memory(&self, address: u64, len: usize) -> anyhow::Result<Vec<u8>> {
+        self.context.read_memory(address, len)
+    }
+
+    fn pointer_size(&self) -> usize {
+        self.context.pointer_size()
+    }
+
+    fn variable(&self, name: &str) -> anyhow::Result<Value> {
+        self.context.variable(name)
+    }
+
+    fn member(&self, base: &Expr, member: &str) -> anyhow::Result<Value> {
+        self.context.member(base, member)
+    }
+}
+
+// Context without a debuggee, only literals and convenience variables can be evaluated.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoDebuggee;
+
+impl EvalContext for NoDebuggee {
+    fn register(&self, name: &str) -> anyhow::Result<Value> {
+        Err(anyhow!("no debuggee to read ${} from", name))
+    }
+
+    fn read_memory(&self, address: u64, _len: usize) -> anyhow::Result<Vec<u8>> {
+        Err(anyhow!("no debuggee to read {:#x} from", address))
+    }
+}
+
+// Splits `lhs = rhs` at the first `=` that isn't part of a comparison.
+pub fn split_assignment(input: &str) -> Option<(&str, &str)> {
+    let bytes = input.as_bytes();
+    let index = (0..bytes.len()).find(|&index| {
+        bytes[index] == b'='
+            && bytes.get(index + 1) != Some(&b'=')
+            && !(index > 0 && b"=!<>".contains(&bytes[index - 1]))
+    })?;
+    Some((input[..index].trim(), &input[index + 1..]))
+}
+
+// Splits `$name = expr` into the name and the expression.
+pub fn parse_assignment(input: &str) -> Option<(&str, &str)> {
+    let (lhs, expression) = split_assignment(input)?;
+    let name = lhs.strip_prefix('$')?;
+    if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+        return None;
+    }
+    Some((name, expression))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Int(i64),
+    Float(f64),
+    Register(String),
+    Ident(String),
+    Punct(&'static str),
+}
+
+// Longest first, so that `<<` isn't lexed as two `<`.
+const PUNCTS: [&str; 24] = [
+    "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "->", "+", "-", "*", "/", "%", "<", ">", "&",
+    "^", "|", "!", "~", "(", ")", ".",
+];
+
+fn lex(input: &str) -> anyhow::Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_digit() {
+            tokens.push(lex_number(&mut chars)?);
+        } else if c == '$' {
+            chars.next();
+            let name = take_ident(&mut chars);
+            if name.is_empty() {
+                Err(anyhow!("expected a register name after $"))?;
+            }
+            tokens.push(Token::Register(name));
+        } else if c.is_alphabetic() || c == '_' {
+            tokens.push(Token::Ident(take_ident(&mut chars)));
+        } else {
+            let rest = chars.clone().collect::<String>();
+            let punct = PUNCTS
+                .iter()
+                .find(|punct| rest.starts_with(**punct))
+                .ok_or(anyhow!("unexpected character '{}' in expression", c))?;
+            for _ in 0..punct.len() {
+                chars.next();
+            }
+            tokens.push(Token::Punct(punct));
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn take_ident(chars: &mut Peekable<Chars>) -> String {
+    let mut ident = String::new();
+    while let Some(&c) = chars.peek() {
+        if !(c.is_alphanumeric() || c == '_') {
+            break;
+        }
+        ident.push(c);
+        chars.next();
+    }
+    ident
+}
+
+fn lex_number(chars: &mut Peekable<Chars>) -> anyhow::Result<Token> {
+    let mut text = String::new();
+    while let Some(&c) = chars.peek() {
+        let exponent_sign = (c == '+' || c == '-')
+            && text.ends_with(['e', 'E'])
+            && !text.to_ascii_lowercase().starts_with("0x");
+        if !(c.is_ascii_alphanumeric() || c == '.' || c == '_' || exponent_sign) {
+            break;
+        }
+        text.push(c);
+        chars.next();
+    }
+    let digits = text.replace('_', "");
+    let invalid = || anyhow!("invalid number {}", text);
+
+    let radix = match digits.get(..2) {
+        Some("0x" | "0X") => Some(16),
+        Some("0b" | "0B") => Some(2),
+        Some("0o" | "0O") => Some(8),
+        _ => None,
+    };
+    if let Some(radix) = radix {
+        // wrap around like C does for constants that don't fit an i64
+        return u64::from_str_radix(&digits[2..], radix)
+            .map(|x| Token::Int(x as i64))
+            .map_err(|_| invalid());
+    }
+
+    if digits.contains(['.', 'e', 'E']) {
+        digits.parse().map(Token::Float).map_err(|_| invalid())
+    } else {
+        digits
+            .parse::<u64>()
+            .map(|x| Token::Int(x as i64))
+            .map_err(|_| invalid())
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn eat(&mut self, punct: &str) -> bool {
+        let matches = matches!(self.peek(), Some(Token::Punct(p)) if *p == punct);
+        if matches {
+            self.position += 1;
+        }
+        matches
+    }
+
+    fn binary_op(&self) -> Option<BinaryOp> {
+        let Some(Token::Punct(punct)) = self.peek() else {
+            return None;
+        };
+        Some(match *punct {
+            "*" => BinaryOp::Mul,
+            "/" => BinaryOp::Div,
+            "%" => BinaryOp::Rem,
+            "+" => BinaryOp::Add,
+            "-" => BinaryOp::Sub,
+            "<<" => BinaryOp::Shl,
+            ">>" => BinaryOp::Shr,
+            "<" => BinaryOp::Lt,
+            "<=" => BinaryOp::Le,
+            ">" => BinaryOp::Gt,
+            ">=" => BinaryOp::Ge,
+            "==" => BinaryOp::Eq,
+            "!=" => BinaryOp::Ne,
+            "&" => BinaryOp::BitAnd,
+            "^" => BinaryOp::BitXor,
+            "|" => BinaryOp::BitOr,
+            "&&" => BinaryOp::And,
+            "||" => BinaryOp::Or,
+            _ => return None,
+        })
+    }
+
+    // Precedence climbing, every operator is left associative.
+    fn expression(&mut self, min_precedence: u8) -> anyhow::Result<Expr> {
+        let mut lhs = self.unary()?;
+        while let Some(op) = self
+            .binary_op()
+            .filter(|op| op.precedence() >= min_precedence)
+        {
+            self.position += 1;
+            let rhs = self.expression(op.precedence() + 1)?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> anyhow::Result<Expr> {
+        if self.at_cast() {
+            self.position += 1;
+            let ty = self.type_name()?;
+            if !self.eat(")") {
+                Err(anyhow!("expected ) after type {}", ty))?;
+            }
+            return Ok(Expr::Cast(ty, Box::new(self.unary()?)));
+        }
+        let op = match self.peek() {
+            Some(Token::Punct("-")) => UnaryOp::Neg,
+            Some(Token::Punct("!")) => UnaryOp::Not,
+            Some(Token::Punct("~")) => UnaryOp::BitNot,
+            Some(Token::Punct("*")) => UnaryOp::Deref,
+            Some(Token::Punct("+")) => {
+                self.position += 1;
+                return self.unary();
+            }
+            _ => return self.postfix(),
+        };
+        self.position += 1;
+        Ok(Expr::Unary(op, Box::new(self.unary()?)))
+    }
+
+    // `(` followed by a type name, which has to start with a keyword or a builtin type since
+    // typedefs aren't known without debug info.
+    fn at_cast(&self) -> bool {
+        matches!(self.peek(), Some(Token::Punct("(")))
+            && matches!(self.tokens.get(self.position + 1), Some(Token::Ident(word)) if c_type::starts_type(word))
+    }
+
+    // The words of a type, then its pointers.
+    fn type_name(&mut self) -> anyhow::Result<CType> {
+        let mut words = Vec::new();
+        while let Some(Token::Ident(word)) = self.peek() {
+            words.push(word.clone());
+            self.position += 1;
+        }
+        let base = BaseType::from_words(&words)?;
+        let mut pointers = 0;
+        while self.eat("*") {
+            pointers += 1;
+        }
+        Ok(CType { base, pointers })
+    }
+
+    fn postfix(&mut self) -> anyhow::Result<Expr> {
+        let mut expr = self.primary()?;
+        loop {
+            let arrow = if self.eat(".") {
+                false
+            } else if self.eat("->") {
+                true
+            } else {
+                return Ok(expr);
+            };
+            let Some(Token::Ident(member)) = self.next() else {
+                return Err(anyhow!("expected a member name"));
+            };
+            if arrow {
+                expr = Expr::Unary(UnaryOp::Deref, Box::new(expr));
+            }
+            expr = Expr::Member(Box::new(expr), member);
+        }
+    }
+
+    fn primary(&mut self) -> anyhow::Result<Expr> {
+        match self.next() {
+            Some(Token::Int(x)) => Ok(Expr::Literal(Value::Int(x))),
+            Some(Token::Float(x)) => Ok(Expr::Literal(Value::Float(x))),
+            Some(Token::Register(name)) => Ok(Expr::Register(name)),
+            Some(Token::Ident(name)) => Ok(Expr::Variable(name)),
+            Some(Token::Punct("(")) => {
+                let expr = self.expression(0)?;
+                if !self.eat(")") {
+                    Err(anyhow!("expected )"))?;
+                }
+                Ok(expr)
+            }
+            Some(Token::Punct(punct)) => Err(anyhow!("unexpected {} in expression", punct)),
+            None => Err(anyhow!("unexpected end of expression")),
+        }
+    }
+}
+
+impl Expr {
+    pub fn parse(input: &str) -> anyhow::Result<Self> {
+        let mut parser = Parser {
+            tokens: lex(input)?,
+            position: 0,
+        };
+        let expr = parser.expression(0)?;
+        if let Some(token) = parser.peek() {
+            Err(anyhow!("unexpected {:?} after expression", token))?;
+        }
+        Ok(expr)
+    }
+
+    pub fn evaluate<C: EvalContext + ?Sized>(&self, context: &C) -> anyhow::Result<Value> {
+        match self {
+            Expr::Literal(value) => Ok(*value),
+            Expr::Register(name) => context.register(name),
+            Expr::Variable(name) => context.variable(name),
+            Expr::Member(base, member) => context.member(base, member),
+            Expr::Unary(UnaryOp::Deref, operand) if operand.pointee().is_some() => {
+                let (address, size, pointee) = operand.typed_pointer(context)?;
+                pointee.decode(&context.read_memory(address, size)?)
+            }
+            Expr::Unary(op, operand) => {
+                let value = operand.evaluate(context)?;
+                evaluate_unary(*op, value, context)
+            }
+            Expr::Cast(ty, operand) => {
+                ty.convert(operand.evaluate(context)?, context.pointer_size())
+            }
+            // evaluated lazily like in C
+            Expr::Binary(BinaryOp::And, lhs, rhs) => Ok(Value::from_bool(
+                lhs.evaluate(context)?.is_true() && rhs.evaluate(context)?.is_true(),
+            )),
+            Expr::Binary(BinaryOp::Or, lhs, rhs) => Ok(Value::from_bool(
+                lhs.evaluate(context)?.is_true() || rhs.evaluate(context)?.is_true(),
+            )),
+            Expr::Binary(op, lhs, rhs) => {
+                evaluate_binary(*op, lhs.evaluate(context)?, rhs.evaluate(context)?)
+            }
+        }
+    }
+}
+
+impl Expr {
+    // The type a pointer points to, if it has been cast to one. Types go no further than the
+    // cast, `(int *)p + 1` is an untyped address one byte on.
+    fn pointee(&self) -> Option<CType> {
+        match self {
+            Expr::Cast(ty, _) => ty.pointee(),
+            _ => None,
+        }
+    }
+
+    // The address a typed pointer holds, the size and type of what it points to.
+    fn typed_pointer<C: EvalContext + ?Sized>(
+        &self,
+        context: &C,
+    ) -> anyhow::Result<(u64, usize, CType)> {
+        let pointee = self
+            .pointee()
+            .ok_or(anyhow!("expression is not a typed pointer"))?;
+        let size = pointee.size(context.pointer_size())?;
+        let address = self.evaluate(context)?.as_u64();
+        Ok((address, size, pointee))
+    }
+
+    // Registers, dereferenced pointers and variables can be assigned to. Without a cast a
+    // dereference covers a pointer sized word, like it does when reading.
+    pub fn lvalue<C: EvalContext + ?Sized>(&self, context: &C) -> anyhow::Result<Lvalue> {
+        match self {
+            Expr::Register(name) => Ok(Lvalue::Register(name.clone())),
+            Expr::Variable(name) => context.variable_location(name),
+            Expr::Unary(UnaryOp::Deref, operand) if operand.pointee().is_some() => {
+                let (address, size, pointee) = operand.typed_pointer(context)?;
+                match pointee.base {
+                    BaseType::Float { .. } if !pointee.is_pointer() => {
+                        Ok(Lvalue::FloatMemory { address, size })
+                    }
+                    _ => Ok(Lvalue::Memory { address, size }),
+                }
+            }
+            Expr::Unary(UnaryOp::Deref, operand) => match operand.evaluate(context)? {
+                Value::Int(address) => Ok(Lvalue::Memory {
+                    address: address as u64,
+                    size: context.pointer_size(),
+                }),
+                Value::Float(_) => Err(anyhow!("can't dereference a floating point value")),
+            },
+            _ => Err(anyhow!("left operand of assignment is not an lvalue")),
+        }
+    }
+}
+
+// Parses and evaluates in one go.
+pub fn evaluate<C: EvalContext + ?Sized>(input: &str, context: &C) -> anyhow::Result<Value> {
+    Expr::parse(input)?.evaluate(context)
+}
+
+// Parses a type on its own, like the one of `x --type`.
+pub fn parse_type(input: &str) -> anyhow::Result<CType> {
+    let mut parser = Parser {
+        tokens: lex(input)?,
+        position: 0,
+    };
+    let ty = parser.type_name()?;
+    if let Some(token) = parser.peek() {
+        Err(anyhow!("unexpected {:?} after type", token))?;
+    }
+    Ok(ty)
+}
+
+// Evaluates something that has to be an address, like the argument of break or x.
+pub fn evaluate_address<C: EvalContext + ?Sized>(
+    input: &str,
+    context: &C,
+) -> anyhow::Result<VirtAddr> {
+    match evaluate(input, context)? {
+        Value::Int(address) => Ok(VirtAddr::new(address as u64)),
+        Value::Float(_) => Err(anyhow!("{} is not an address", input.trim())),
+    }
+}
+
+fn evaluate_unary<C: EvalContext + ?Sized>(
+    op: UnaryOp,
+    value: Value,
+    context: &C,
+) -> anyhow::Result<Value> {
+    Ok(match (op, value) {
+        (UnaryOp::Neg, Value::Int(x)) => Value::Int(x.wrapping_neg()),
+        (UnaryOp::Neg, Value::Float(x)) => Value::Float(-x),
+        (UnaryOp::Not, value) => Value::from_bool(!value.is_true()),
+        (UnaryOp::BitNot, Value::Int(x)) => Value::Int(!x),
+        (UnaryOp::Deref, Value::Int(address)) => {
+            let size = context.pointer_size();
+            let bytes = context.read_memory(address as u64, size)?;
+            let mut word = [0u8; 8];
+            word[..size].copy_from_slice(&bytes);
+            Value::Int(u64::from_le_bytes(word) as i64)
+        }
+        (op, Value::Float(_)) => Err(anyhow!("{:?} needs an integer operand", op))?,
+    })
+}
+
+fn evaluate_binary(op: BinaryOp, lhs: Value, rhs: Value) -> anyhow::Result<Value> {
+    if let (Value::Int(lhs), Value::Int(rhs)) = (lhs, rhs) {
+        return Ok(Value::Int(match op {
+            BinaryOp::Mul => lhs.wrapping_mul(rhs),
+            BinaryOp::Div | BinaryOp::Rem if rhs == 0 => Err(anyhow!("division by zero"))?,
+            BinaryOp::Div => lhs.wrapping_div(rhs),
+            BinaryOp::Rem => lhs.wrapping_rem(rhs),
+            BinaryOp::Add => lhs.wrapping_add(rhs),
+            BinaryOp::Sub => lhs.wrapping_sub(rhs),
+            BinaryOp::Shl => lhs.wrapping_shl(rhs as u32),
+            BinaryOp::Shr => lhs.wrapping_shr(rhs as u32),
+            BinaryOp::Lt => (lhs < rhs) as i64,
+            BinaryOp::Le => (lhs <= rhs) as i64,
+            BinaryOp::Gt => (lhs > rhs) as i64,
+            BinaryOp::Ge => (lhs >= rhs) as i64,
+            BinaryOp::Eq => (lhs == rhs) as i64,
+            BinaryOp::Ne => (lhs != rhs) as i64,
+            BinaryOp::BitAnd => lhs & rhs,
+            BinaryOp::BitXor => lhs ^ rhs,
+            BinaryOp::BitOr => lhs | rhs,
+            BinaryOp::And | BinaryOp::Or => unreachable!("evaluated lazily"),
+        }));
+    }
+
+    let (lhs, rhs) = (lhs.as_f64(), rhs.as_f64());
+    Ok(match op {
+        BinaryOp::Mul => Value::Float(lhs * rhs),
+        BinaryOp::Div => Value::Float(lhs / rhs),
+        BinaryOp::Rem => Value::Float(lhs % rhs),
+        BinaryOp::Add => Value::Float(lhs + rhs),
+        BinaryOp::Sub => Value::Float(lhs - rhs),
+        BinaryOp::Lt => Value::from_bool(lhs < rhs),
+        BinaryOp::Le => Value::from_bool(lhs <= rhs),
+        BinaryOp::Gt => Value::from_bool(lhs > rhs),
+        BinaryOp::Ge => Value::from_bool(lhs >= rhs),
+        BinaryOp::Eq => Value::from_bool(lhs == rhs),
+        BinaryOp::Ne => Value::from_bool(lhs != rhs),
+        op => Err(anyhow!("{:?} needs integer operands", op))?,
+    })
+}
+
+impl<T: Tracer> EvalContext for Debuggee<T> {
+    fn register(&self, name: &str) -> anyhow::Result<Value> {
+        let register =
+            Register::lookup_by_name(name).ok_or(anyhow!("no register named ${}", name))?;
+        let registers = self
+            .registers()
+            .ok_or(anyhow!("no register info available"))?;
+
+        let value = registers.read_register(register)?;
+        if let Some(x) = value.as_i64() {
+            return Ok(Value::Int(x));
+        }
+        match value {
+            RegisterValue::F128(x) => Ok(Value::Float(x87_to_f64(unsafe { as_u8_slice(&x) }))),
+            _ => Err(anyhow!(
+                "${} is a vector register, which expressions can't use",
+                name
+            )),
+        }
+    }
+
+    fn read_memory(&self, address: u64, len: usize) -> anyhow::Result<Vec<u8>> {
+        Debuggee::read_memory(self, VirtAddr::new(address), len)
+    }
+
+    fn pointer_size(&self) -> usize {
+        self.pointer_width().size()
+    }
+}
+
+// Stores `value` into `lvalue`, converted to its width like a C assignment would. Lossy
+// conversions and writes to read-only memory go through with a warning.
+pub fn assign<T: Tracer>(
+    debuggee: &mut Debuggee<T>,
+    lvalue: &Lvalue,
+    value: Value,
+) -> anyhow::Result<()> {
+    match lvalue {
+        Lvalue::Register(name) => {
+            let register =
+                Register::lookup_by_name(name).ok_or(anyhow!("no register named ${}", name))?;
+            let current = debuggee
+                .registers()
+                .ok_or(anyhow!("no register info available"))?
+                .read_register(register)?;
+
+            let x = integer_for_assignment(lvalue, value);
+            let width = current.byte_width();
+            let converted = current
+                .as_u64()
+                .and_then(|_| RegisterValue::from_bits(truncate(lvalue, x, width), width))
+                .ok_or(anyhow!("assigning to ${} is not supported", name))?;
+            debuggee.write_register(register, converted)
+        }
+        Lvalue::Memory { address, size } | Lvalue::FloatMemory { address, size } => {
+            let bytes = match (lvalue, value, size) {
+                (Lvalue::FloatMemory { .. }, value, 4) => {
+                    (value.as_f64() as f32).to_le_bytes().to_vec()
+                }
+                (Lvalue::FloatMemory { .. }, value, _) => value.as_f64().to_le_bytes().to_vec(),
+                (_, Value::Float(x), 8) => x.to_le_bytes().to_vec(),
+                (_, Value::Float(x), 4) => (x as f32).to_le_bytes().to_vec(),
+                (_, value, size) => {
+                    let x = integer_for_assignment(lvalue, value);
+                    truncate(lvalue, x, *size).to_le_bytes()[..*size].to_vec()
+                }
+            };
+
+            let read_only = debuggee
+                .memory_map()
+                .ok()
+                .and_then(|memory_map| {
+                    memory_map
+                        .region_containing(VirtAddr::new(*address))
+                        .map(|region| !region.permissions.write)
+                })
+                .unwrap_or(false);
+            if read_only {
+                warn!(address = %format_args!("{:#x}", address), "writing to read-only memory");
+            }
+
+            debuggee.write_memory(VirtAddr::new(*address), &bytes)
+        }
+    }
+}
+
+fn integer_for_assignment(lvalue: &Lvalue, value: Value) -> i64 {
+    if let Value::Float(x) = value {
+        warn!(
+            value = x,
+            "converting floating point value to an integer for {}", lvalue
+        );
+    }
+    value.as_i64()
+}
+
+// Keeps the low `size` bytes, warning if the value fits neither signed nor unsigned.
+fn truncate(lvalue: &Lvalue, x: i64, size: usize) -> u64 {
+    if size >= 8 {
+        return x as u64;
+    }
+    let bits = size as u32 * 8;
+    if x < -(1 << (bits - 1)) || x >= 1 << bits {
+        warn!(
+            value = x,
+            "value truncated to fit {} bytes of {}", size, lvalue
+        );
+    }
+    x as u64 & ((1 << bits) - 1)
+}
+
+// x87 registers hold the raw 80-bit extended precision bytes: 64-bit mantissa with an explicit
+// integer bit, then sign and 15-bit exponent.
+fn x87_to_f64(bytes: &[u8]) -> f64 {
+    let mantissa = u64::from_le_bytes(bytes[..8].try_into().unwrap());
+    let sign_exponent = u16::from_le_bytes([bytes[8], bytes[9]]);
+    let sign = if sign_exponent & 0x8000 != 0 {
+        -1.0
+    } else {
+        1.0
+    };
+    let exponent = (sign_exponent & 0x7fff) as i32;
+
+    if exponent == 0x7fff {
+        return if mantissa << 1 == 0 {
+            sign * f64::INFINITY
+        } else {
+            f64::NAN
+        };
+    }
+    sign * mantissa as f64 * 2f64.powi(exponent - 16383 - 63)
+}
@@ -0,0 +1,140 @@
+use std::{fmt, fs, path::PathBuf, str::FromStr};
+
+use anyhow::anyhow;
+use nix::{fcntl::OFlag, unistd::Pid};
+
+// What a descriptor refers to, from the /proc/pid/fd symlink.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FdTarget {
+    File(PathBuf),
+    Socket(u64),
+    Pipe(u64),
+    // anon_inode:[eventfd], anon_inode:[eventpoll], ...
+    AnonInode(String),
+    Other(String),
+}
+
+impl FdTarget {
+    pub fn from_link(link: &str) -> Self {
+        let inode = |prefix: &str| {
+            link.strip_prefix(prefix)?
+                .strip_prefix('[')?
+                .strip_suffix(']')?
+                .parse()
+                .ok()
+        };
+
+        if let Some(inode) = inode("socket:") {
+            FdTarget::Socket(inode)
+        } else if let Some(inode) = inode("pipe:") {
+            FdTarget::Pipe(inode)
+        } else if let Some(kind) = link.strip_prefix("anon_inode:") {
+            FdTarget::AnonInode(kind.to_string())
+        } else if link.starts_with('/') {
+            FdTarget::File(PathBuf::from(link))
+        } else {
+            FdTarget::Other(link.to_string())
+        }
+    }
+}
+
+impl fmt::Display for FdTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FdTarget::File(path) => write!(f, "{}", path.display()),
+            FdTarget::Socket(inode) => write!(f, "socket:[{}]", inode),
+            FdTarget::Pipe(inode) => write!(f, "pipe:[{}]", inode),
+            FdTarget::AnonInode(kind) => write!(f, "anon_inode:{}", kind),
+            FdTarget::Other(link) => write!(f, "{}", link),
+        }
+    }
+}
+
+// The parts of /proc/pid/fdinfo/<fd> every kind of descriptor has.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FdInfo {
+    pub position: u64,
+    pub flags: OFlag,
+}
+
+impl FromStr for FdInfo {
+    type Err = anyhow::Error;
+
+    // pos:	<decimal>
+    // flags:	<octal>
+    // ...
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let field = |name: &str| {
+            s.lines()
+                .filter_map(|line| line.split_once(':'))
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| value.trim())
+                .ok_or_else(|| anyhow!("no {} in fdinfo", name))
+        };
+
+        Ok(Self {
+            position: field("pos")?.parse()?,
+            flags: OFlag::from_bits_retain(i32::from_str_radix(field("flags")?, 8)?),
+        })
+    }
+}
+
+impl FdInfo {
+    // Access mode followed by the status flags worth knowing about when looking at I/O.
+    pub fn describe_flags(&self) -> String {
+        let access_mode = match self.flags & OFlag::O_ACCMODE {
+            OFlag::O_WRONLY => "O_WRONLY",
+            OFlag::O_RDWR => "O_RDWR",
+            _ => "O_RDONLY",
+        };
+
+        [
+            (OFlag::O_APPEND, "O_APPEND"),
+            (OFlag::O_NONBLOCK, "O_NONBLOCK"),
+            (OFlag::O_SYNC, "O_SYNC"),
+            (OFlag::O_DIRECT, "O_DIRECT"),
+            (OFlag::O_CLOEXEC, "O_CLOEXEC"),
+        ]
+        .into_iter()
+        .filter(|(flag, _)| self.flags.contains(*flag))
+        .map(|(_, name)| name)
+        .fold(access_mode.to_string(), |flags, name| flags + "|" + name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileDescriptor {
+    pub fd: i32,
+    pub target: FdTarget,
+    // None if the descriptor got closed while it was being read
+    pub info: Option<FdInfo>,
+}
+
+impl FileDescriptor {
+    // Descriptors are sorted by number. The debuggee is usually stopped, but one closed in
+    // between listing and reading it is skipped instead of failing the whole listing.
+    pub fn read_from_procfs(pid: Pid) -> anyhow::Result<Vec<Self>> {
+        let entries = fs::read_dir(format!("/proc/{}/fd", pid))
+            .map_err(|err| anyhow!("unable to read file descriptors of {}: {}", pid, err))?;
+
+        let mut fds = entries
+            .filter_map(|entry| {
+                let entry = entry.ok()?;
+                let fd = entry.file_name().to_str()?.parse().ok()?;
+                let link = fs::read_link(entry.path()).ok()?;
+                let info = fs::read_to_string(format!("/proc/{}/fdinfo/{}", pid, fd))
+                    .ok()
+                    .and_then(|info| info.parse().ok());
+
+                Some(Self {
+                    fd,
+                    target: FdTarget::from_link(&link.to_string_lossy()),
+                    info,
+                })
+            })
+            .collect::<Vec<_>>();
+        fds.sort_by_key(|fd| fd.fd);
+
+        Ok(fds)
+    }
+}
@@ -0,0 +1,239 @@
+use std::{ops::Range, str::FromStr};
+
+use anyhow::anyhow;
+
+use crate::expression::EvalContext;
+
+// Runs shorter than this in byte dumps are more likely numbers than text.
+pub const MIN_STRING_RUN: usize = 4;
+const MAX_STRING_LENGTH: usize = 4096;
+
+// C escape of a single byte, printable ASCII stays as is and the rest is octal like gdb shows it.
+pub fn escape_byte(byte: u8, quote: char) -> String {
+    match byte {
+        b'\0' => "\\0".to_string(),
+        0x07 => "\\a".to_string(),
+        0x08 => "\\b".to_string(),
+        b'\t' => "\\t".to_string(),
+        b'\n' => "\\n".to_string(),
+        0x0b => "\\v".to_string(),
+        0x0c => "\\f".to_string(),
+        b'\r' => "\\r".to_string(),
+        b'\\' => "\\\\".to_string(),
+        byte if byte as char == quote => format!("\\{}", quote),
+        0x20..=0x7e => (byte as char).to_string(),
+        byte => format!("\\{:o}", byte),
+    }
+}
+
+// The value followed by the character, e.g. `65 'A'`.
+pub fn format_char(value: i64) -> String {
+    format!("{} '{}'", value as i8, escape_byte(value as u8, '\''))
+}
+
+// A double quoted C string. Valid UTF-8 sequences are kept, anything else is escaped.
+pub fn format_c_string(bytes: &[u8]) -> String {
+    let mut formatted = String::from("\"");
+    for chunk in bytes.utf8_chunks() {
+        for c in chunk.valid().chars() {
+            if c.is_ascii() {
+                formatted.push_str(&escape_byte(c as u8, '"'));
+            } else if c.is_control() {
+                let mut buf = [0; 4];
+                for byte in c.encode_utf8(&mut buf).bytes() {
+                    formatted.push_str(&escape_byte(byte, '"'));
+                }
+            } else {
+                formatted.push(c);
+            }
+        }
+        for byte in chunk.invalid() {
+            formatted.push_str(&escape_byte(*byte, '"'));
+        }
+    }
+    formatted.push('"');
+    formatted
+}
+
+// Ranges of at least `min_len` bytes of printable ASCII or UTF-8 text.
+pub fn printable_runs(bytes: &[u8], min_len: usize) -> Vec<Range<usize>> {
+    let mut runs = Vec::new();
+    let mut start = None;
+    let mut offset = 0;
+
+    let mut end_run = |start: &mut Option<usize>, end: usize| {
+        if let Some(start) = start.take() {
+            if end - start >= min_len {
+                runs.push(start..end);
+            }
+        }
+    };
+
+    for chunk in bytes.utf8_chunks() {
+        for c in chunk.valid().chars() {
+            let printable = if c.is_ascii() {
+                c.is_ascii_graphic() || c == ' '
+            } else {
+                !c.is_control()
+            };
+            if printable {
+                start.get_or_insert(offset);
+            } else {
+                end_run(&mut start, offset);
+            }
+            offset += c.len_utf8();
+        }
+        if !chunk.invalid().is_empty() {
+            end_run(&mut start, offset);
+            offset += chunk.invalid().len();
+        }
+    }
+    end_run(&mut start, offset);
+
+    runs
+}
+
+// The printable runs of a byte vector as strings, for annotating register and memory dumps.
+pub fn strings_in(bytes: &[u8]) -> Vec<String> {
+    printable_runs(bytes, MIN_STRING_RUN)
+        .into_iter()
+        .map(|run| format_c_string(&bytes[run]))
+        .collect()
+}
+
+// Reads a NUL terminated string, stopping early at unreadable memory or the length limit.
+pub fn read_c_string<C: EvalContext + ?Sized>(
+    context: &C,
+    address: u64,
+) -> anyhow::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    while bytes.len() < MAX_STRING_LENGTH {
+        let byte = match context.read_memory(address + bytes.len() as u64, 1) {
+            Ok(byte) => byte[0],
+            Err(err) if bytes.is_empty() => Err(err)?,
+            Err(_) => break,
+        };
+        if byte == 0 {
+            break;
+        }
+        bytes.push(byte);
+    }
+    Ok(bytes)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Letter {
+    Hex,
+    Decimal,
+    Unsigned,
+    Char,
+    String,
+    Instruction,
+}
+
+impl Letter {
+    fn from_char(c: char) -> Option<Self> {
+        Some(match c {
+            'x' => Letter::Hex,
+            'd' => Letter::Decimal,
+            'u' => Letter::Unsigned,
+            'c' => Letter::Char,
+            's' => Letter::String,
+            'i' => Letter::Instruction,
+            _ => return None,
+        })
+    }
+}
+
+// The `/fmt` suffix of print and x: an optional count, then a unit and a format letter in
+// any order, e.g. 32bx or s.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Format {
+    pub count: Option<usize>,
+    // b, h, w or g as 1, 2, 4 or 8 bytes
+    pub unit: Option<usize>,
+    pub letter: Option<Letter>,
+}
+
+impl FromStr for Format {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let digits = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+        let mut format = Format {
+            count: (digits > 0).then(|| s[..digits].parse()).transpose()?,
+            unit: None,
+            letter: None,
+        };
+
+        for c in s[digits..].chars() {
+            let unit = match c {
+                'b' => 1,
+                'h' => 2,
+                'w' => 4,
+                'g' => 8,
+                c => {
+                    let letter =
+                        Letter::from_char(c).ok_or(anyhow!("unknown format letter {}", c))?;
+                    if format.letter.replace(letter).is_some() {
+                        Err(anyhow!("more than one format letter in {}", s))?;
+                    }
+                    continue;
+                }
+            };
+            if format.unit.replace(unit).is_some() {
+                Err(anyhow!("more than one unit size in {}", s))?;
+            }
+        }
+
+        Ok(format)
+    }
+}
+
+// One unit of memory as `letter` shows it.
+pub fn format_unit(bytes: &[u8], letter: Letter) -> String {
+    let mut word = [0u8; 8];
+    word[..bytes.len()].copy_from_slice(bytes);
+    let unsigned = u64::from_le_bytes(word);
+    let bits = bytes.len() as u32 * 8;
+    let signed = ((unsigned << (64 - bits)) as i64) >> (64 - bits);
+
+    match letter {
+        // a unit is too little to decode an instruction from
+        Letter::Hex | Letter::Instruction => {
+            format!("{:#0width$x}", unsigned, width = 2 + 2 * bytes.len())
+        }
+        Letter::Decimal => signed.to_string(),
+        Letter::Unsigned => unsigned.to_string(),
+        Letter::Char => format_char(signed),
+        Letter::String => format_c_string(bytes),
+    }
+}
+
+// Bytes in a line of a hexdump.
+pub const HEXDUMP_WIDTH: usize = 16;
+
+// Up to HEXDUMP_WIDTH bytes the way `hexdump -C` shows them, in two groups of 8 with the ASCII
+// next to them and anything unprintable as a dot:
+//
+//     7f 45 4c 46 02 01 01 00  00 00 00 00 00 00 00 00  |.ELF............|
+pub fn hexdump_line(bytes: &[u8]) -> String {
+    let mut line = String::new();
+    for index in 0..HEXDUMP_WIDTH {
+        if index == HEXDUMP_WIDTH / 2 {
+            line.push(' ');
+        }
+        match bytes.get(index) {
+            Some(byte) => line.push_str(&format!("{:02x} ", byte)),
+            None => line.push_str("   "),
+        }
+    }
+    let ascii = bytes
+        .iter()
+        .map(|byte| match byte {
+            0x20..=0x7e => *byte as char,
+            _ => '.',
+        })
+        .collect::<String>();
+    format!("{} |{}|", line, ascii)
+}
@@ -0,0 +1,243 @@
+use std::fmt;
+
+use anyhow::anyhow;
+
+// The x87 and SSE floating point exceptions, in the order of their bits in fcw, fsw and mxcsr.
+pub const EXCEPTIONS: [(&str, &str); 6] = [
+    ("IE", "invalid operation"),
+    ("DE", "denormal operand"),
+    ("ZE", "divide by zero"),
+    ("OE", "overflow"),
+    ("UE", "underflow"),
+    ("PE", "precision"),
+];
+
+const ROUNDING_MODES: [&str; 4] = ["nearest", "down", "up", "zero"];
+// 1 is reserved
+const PRECISIONS: [&str; 4] = ["single", "reserved", "double", "extended"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FieldKind {
+    // a set of exceptions, shown as `IE|ZE` or `none`
+    Exceptions,
+    Rounding,
+    Precision,
+    // an x87 register tag of the abridged tag word
+    Tag,
+    Bit,
+    Number,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Field {
+    name: &'static str,
+    shift: u32,
+    width: u32,
+    kind: FieldKind,
+}
+
+const fn field(name: &'static str, shift: u32, width: u32, kind: FieldKind) -> Field {
+    Field {
+        name,
+        shift,
+        width,
+        kind,
+    }
+}
+
+const FCW: &[Field] = &[
+    field("mask", 0, 6, FieldKind::Exceptions),
+    field("pc", 8, 2, FieldKind::Precision),
+    field("rc", 10, 2, FieldKind::Rounding),
+];
+
+const FSW: &[Field] = &[
+    field("flags", 0, 6, FieldKind::Exceptions),
+    field("sf", 6, 1, FieldKind::Bit),
+    field("es", 7, 1, FieldKind::Bit),
+    field("c0", 8, 1, FieldKind::Bit),
+    field("c1", 9, 1, FieldKind::Bit),
+    field("c2", 10, 1, FieldKind::Bit),
+    field("top", 11, 3, FieldKind::Number),
+    field("c3", 14, 1, FieldKind::Bit),
+    field("b", 15, 1, FieldKind::Bit),
+];
+
+// The FXSAVE layout keeps a bit per physical register, set if it isn't empty.
+const FTW: &[Field] = &[
+    field("r0", 0, 1, FieldKind::Tag),
+    field("r1", 1, 1, FieldKind::Tag),
+    field("r2", 2, 1, FieldKind::Tag),
+    field("r3", 3, 1, FieldKind::Tag),
+    field("r4", 4, 1, FieldKind::Tag),
+    field("r5", 5, 1, FieldKind::Tag),
+    field("r6", 6, 1, FieldKind::Tag),
+    field("r7", 7, 1, FieldKind::Tag),
+];
+
+const MXCSR: &[Field] = &[
+    field("flags", 0, 6, FieldKind::Exceptions),
+    field("daz", 6, 1, FieldKind::Bit),
+    field("mask", 7, 6, FieldKind::Exceptions),
+    field("rc", 13, 2, FieldKind::Rounding),
+    field("fz", 15, 1, FieldKind::Bit),
+];
+
+fn fields_of(register: &str) -> Option<&'static [Field]> {
+    match register {
+        "fcw" => Some(FCW),
+        "fsw" => Some(FSW),
+        "ftw" => Some(FTW),
+        "mxcsr" => Some(MXCSR),
+        _ => None,
+    }
+}
+
+// Whether `register` has fields `decode` and `encode` know about.
+pub fn is_decodable(register: &str) -> bool {
+    fields_of(register).is_some()
+}
+
+impl Field {
+    fn mask(&self) -> u64 {
+        ((1 << self.width) - 1) << self.shift
+    }
+
+    fn get(&self, value: u64) -> u64 {
+        (value & self.mask()) >> self.shift
+    }
+
+    fn set(&self, value: u64, x: u64) -> u64 {
+        (value & !self.mask()) | ((x << self.shift) & self.mask())
+    }
+
+    fn format(&self, x: u64) -> String {
+        match self.kind {
+            FieldKind::Exceptions => format_exceptions(x),
+            FieldKind::Rounding => ROUNDING_MODES[x as usize].to_string(),
+            FieldKind::Precision => PRECISIONS[x as usize].to_string(),
+            FieldKind::Tag if x == 0 => "empty".to_string(),
+            FieldKind::Tag => "valid".to_string(),
+            FieldKind::Bit | FieldKind::Number => x.to_string(),
+        }
+    }
+
+    fn parse(&self, s: &str) -> anyhow::Result<u64> {
+        let position = |names: &[&str]| {
+            names
+                .iter()
+                .position(|name| name.eq_ignore_ascii_case(s))
+                .map(|x| x as u64)
+                .ok_or(anyhow!(
+                    "invalid value {} for {}, expected one of {}",
+                    s,
+                    self.name,
+                    names.join(", ")
+                ))
+        };
+        match self.kind {
+            FieldKind::Exceptions => parse_exceptions(s),
+            FieldKind::Rounding => position(&ROUNDING_MODES),
+            FieldKind::Precision => position(&PRECISIONS),
+            FieldKind::Tag => position(&["empty", "valid"]),
+            FieldKind::Bit | FieldKind::Number => {
+                let x = s
+                    .parse::<u64>()
+                    .map_err(|_| anyhow!("invalid value {} for {}", s, self.name))?;
+                if x >= 1 << self.width {
+                    Err(anyhow!("{} is too large for {}", x, self.name))?;
+                }
+                Ok(x)
+            }
+        }
+    }
+}
+
+fn format_exceptions(bits: u64) -> String {
+    let names = EXCEPTIONS
+        .iter()
+        .enumerate()
+        .filter(|(bit, _)| bits & (1 << bit) != 0)
+        .map(|(_, (name, _))| *name)
+        .collect::<Vec<_>>();
+    if names.is_empty() {
+        "none".to_string()
+    } else {
+        names.join("|")
+    }
+}
+
+// `IE|ZE`, `none` or `all`.
+fn parse_exceptions(s: &str) -> anyhow::Result<u64> {
+    match s.to_ascii_lowercase().as_str() {
+        "none" => return Ok(0),
+        "all" => return Ok(0x3f),
+        _ => (),
+    }
+    s.split('|')
+        .try_fold(0, |bits, name| -> anyhow::Result<u64> {
+            let bit = EXCEPTIONS
+                .iter()
+                .position(|(exception, _)| exception.eq_ignore_ascii_case(name.trim()))
+                .ok_or(anyhow!(
+                    "unknown exception {}, expected IE, DE, ZE, OE, UE or PE",
+                    name
+                ))?;
+            Ok(bits | 1 << bit)
+        })
+}
+
+// The named fields of fcw, fsw, ftw or mxcsr, None for other registers.
+pub fn decode(register: &str, value: u64) -> Option<Decoded> {
+    Some(Decoded(
+        fields_of(register)?
+            .iter()
+            .map(|field| (field.name, field.format(field.get(value))))
+            .collect(),
+    ))
+}
+
+// Shown as `mask=IE|DE|ZE|OE|UE|PE pc=extended rc=nearest` and so on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Decoded(pub Vec<(&'static str, String)>);
+
+impl fmt::Display for Decoded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, (name, value)) in self.0.iter().enumerate() {
+            if index > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{}={}", name, value)?;
+        }
+        Ok(())
+    }
+}
+
+// Applies `field=value` assignments like `rc=zero` or `mask=IE|ZE` to `value`, the fields
+// that aren't mentioned are kept.
+pub fn encode<S: AsRef<str>>(register: &str, value: u64, assignments: &[S]) -> anyhow::Result<u64> {
+    let fields = fields_of(register).ok_or(anyhow!("${} has no named fields", register))?;
+    assignments
+        .iter()
+        .try_fold(value, |value, assignment| -> anyhow::Result<u64> {
+            let assignment = assignment.as_ref();
+            let (name, x) = assignment.split_once('=').ok_or(anyhow!(
+                "invalid field assignment {}, expected FIELD=VALUE",
+                assignment
+            ))?;
+            let field = fields
+                .iter()
+                .find(|field| field.name.eq_ignore_ascii_case(name))
+                .ok_or(anyhow!(
+                    "${} has no field {}, it has {}",
+                    register,
+                    name,
+                    fields
+                        .iter()
+                        .map(|field| field.name)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ))?;
+            Ok(field.set(value, field.parse(x)?))
+        })
+}
@@ -0,0 +1,274 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fmt,
+    time::SystemTime,
+};
+
+use nix::unistd::Pid;
+
+use crate::virt_addr::VirtAddr;
+
+// How many released allocations are kept to attribute dangling pointers to.
+pub const DEFAULT_RELEASED_HISTORY: usize = 1024;
+// Return addresses recorded per call, the call site included.
+pub const MAX_BACKTRACE_DEPTH: usize = 16;
+
+const MAP_FAILED: u64 = u64::MAX;
+
+// The allocator entry points heap tracing hooks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum AllocFunction {
+    Malloc,
+    Calloc,
+    Realloc,
+    Free,
+    Mmap,
+    Munmap,
+}
+
+impl AllocFunction {
+    pub const ALL: [AllocFunction; 6] = [
+        AllocFunction::Malloc,
+        AllocFunction::Calloc,
+        AllocFunction::Realloc,
+        AllocFunction::Free,
+        AllocFunction::Mmap,
+        AllocFunction::Munmap,
+    ];
+
+    pub fn symbol(self) -> &'static str {
+        match self {
+            AllocFunction::Malloc => "malloc",
+            AllocFunction::Calloc => "calloc",
+            AllocFunction::Realloc => "realloc",
+            AllocFunction::Free => "free",
+            AllocFunction::Mmap => "mmap",
+            AllocFunction::Munmap => "munmap",
+        }
+    }
+
+    pub fn from_symbol(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|function| function.symbol() == name)
+    }
+}
+
+impl fmt::Display for AllocFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.symbol())
+    }
+}
+
+// A call into the allocator, recorded once it returned.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeapCall {
+    pub function: AllocFunction,
+    pub thread: Pid,
+    pub args: [u64; 6],
+    pub result: u64,
+    // return addresses, the call site first
+    pub backtrace: Vec<VirtAddr>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Allocation {
+    // counts every allocation of the session
+    pub index: usize,
+    pub time: SystemTime,
+    pub address: VirtAddr,
+    pub size: u64,
+    pub function: AllocFunction,
+    pub thread: Pid,
+    pub backtrace: Vec<VirtAddr>,
+    // the call site of the free, realloc or munmap that released it
+    pub released_at: Option<VirtAddr>,
+}
+
+impl Allocation {
+    // Zero sized allocations still own their address.
+    pub fn contains(&self, address: VirtAddr) -> bool {
+        address
+            .offset_from(self.address)
+            .is_some_and(|offset| offset < self.size.max(1))
+    }
+
+    pub fn call_site(&self) -> Option<VirtAddr> {
+        self.backtrace.first().copied()
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HeapStats {
+    pub live_allocations: usize,
+    pub live_bytes: u64,
+    pub peak_bytes: u64,
+    pub allocations: usize,
+    pub releases: usize,
+    // frees of pointers never seen allocated, e.g. because tracing started later
+    pub unknown_releases: usize,
+    pub calls: BTreeMap<AllocFunction, usize>,
+}
+
+// What the allocator handed out and took back while heap tracing was on.
+#[derive(Debug, Clone)]
+pub struct HeapTrace {
+    live: BTreeMap<VirtAddr, Allocation>,
+    released: VecDeque<Allocation>,
+    released_capacity: usize,
+    stats: HeapStats,
+    next_index: usize,
+}
+
+impl Default for HeapTrace {
+    fn default() -> Self {
+        Self::new(DEFAULT_RELEASED_HISTORY)
+    }
+}
+
+impl HeapTrace {
+    pub fn new(released_capacity: usize) -> Self {
+        Self {
+            live: BTreeMap::new(),
+            released: VecDeque::new(),
+            released_capacity,
+            stats: HeapStats::default(),
+            next_index: 0,
+        }
+    }
+
+    pub fn record(&mut self, call: HeapCall) {
+        *self.stats.calls.entry(call.function).or_default() += 1;
+        let call_site = call.backtrace.first().copied();
+
+        match call.function {
+            AllocFunction::Malloc => self.allocate(&call, call.result, call.args[0]),
+            AllocFunction::Calloc => self.allocate(
+                &call,
+                call.result,
+                call.args[0].saturating_mul(call.args[1]),
+            ),
+            AllocFunction::Realloc => {
+                let (old, size, new) = (call.args[0], call.args[1], call.result);
+                // a failed realloc leaves the old block alone, realloc(p, 0) may free it
+                let frees_old = old != 0 && (new != 0 || size == 0);
+                if frees_old && new == old {
+                    // resized in place
+                    self.release(VirtAddr::new(old), call_site, true);
+                    self.allocate(&call, new, size);
+                } else {
+                    // a moved block is copied out of the old one, the allocator holds both
+                    self.allocate(&call, new, size);
+                    if frees_old {
+                        self.release(VirtAddr::new(old), call_site, true);
+                    }
+                }
+            }
+            AllocFunction::Free if call.args[0] != 0 => {
+                self.release(VirtAddr::new(call.args[0]), call_site, true)
+            }
+            AllocFunction::Free => (),
+            AllocFunction::Mmap if call.result != MAP_FAILED => {
+                self.allocate(&call, call.result, call.args[1])
+            }
+            AllocFunction::Mmap => (),
+            // only whole mappings seen being mapped are tracked
+            AllocFunction::Munmap => self.release(VirtAddr::new(call.args[0]), call_site, false),
+        }
+    }
+
+    fn allocate(&mut self, call: &HeapCall, address: u64, size: u64) {
+        if address == 0 {
+            return;
+        }
+        let address = VirtAddr::new(address);
+        // the release went unnoticed, e.g. by a function that isn't hooked
+        if let Some(stale) = self.live.remove(&address) {
+            self.stats.live_bytes -= stale.size;
+        }
+
+        self.live.insert(
+            address,
+            Allocation {
+                index: self.next_index,
+                time: SystemTime::now(),
+                address,
+                size,
+                function: call.function,
+                thread: call.thread,
+                backtrace: call.backtrace.clone(),
+                released_at: None,
+            },
+        );
+        self.next_index += 1;
+        self.stats.allocations += 1;
+        self.stats.live_allocations = self.live.len();
+        self.stats.live_bytes += size;
+        self.stats.peak_bytes = self.stats.peak_bytes.max(self.stats.live_bytes);
+    }
+
+    fn release(&mut self, address: VirtAddr, call_site: Option<VirtAddr>, count_unknown: bool) {
+        let Some(mut allocation) = self.live.remove(&address) else {
+            if count_unknown {
+                self.stats.unknown_releases += 1;
+            }
+            return;
+        };
+        self.stats.releases += 1;
+        self.stats.live_allocations = self.live.len();
+        self.stats.live_bytes -= allocation.size;
+
+        allocation.released_at = call_site;
+        if self.released.len() == self.released_capacity {
+            self.released.pop_front();
+        }
+        if self.released_capacity > 0 {
+            self.released.push_back(allocation);
+        }
+    }
+
+    // Lowest address first.
+    pub fn live(&self) -> impl Iterator<Item = &Allocation> {
+        self.live.values()
+    }
+
+    pub fn stats(&self) -> &HeapStats {
+        &self.stats
+    }
+
+    // The live allocation `address` points into, failing that the latest released one.
+    pub fn find(&self, address: VirtAddr) -> Option<&Allocation> {
+        self.live
+            .range(..=address)
+            .next_back()
+            .map(|(_, allocation)| allocation)
+            .filter(|allocation| allocation.contains(address))
+            .or_else(|| {
+                self.released
+                    .iter()
+                    .rev()
+                    .find(|allocation| allocation.contains(address))
+            })
+    }
+
+    // Live allocations and bytes per call site, most bytes first.
+    pub fn call_sites(&self) -> Vec<(Option<VirtAddr>, usize, u64)> {
+        let mut sites = BTreeMap::<Option<VirtAddr>, (usize, u64)>::new();
+        for allocation in self.live.values() {
+            let (count, bytes) = sites.entry(allocation.call_site()).or_default();
+            *count += 1;
+            *bytes += allocation.size;
+        }
+
+        let mut sites = sites
+            .into_iter()
+            .map(|(site, (count, bytes))| (site, count, bytes))
+            .collect::<Vec<_>>();
+        sites.sort_by(|a, b| b.2.cmp(&a.2).then(a.0.cmp(&b.0)));
+        sites
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::new(self.released_capacity);
+    }
+}
@@ -0,0 +1,83 @@
+use anyhow::anyhow;
+use libc::c_long;
+use nix::{
+    sys::{
+        signal::Signal,
+        wait::{WaitPidFlag, WaitStatus},
+    },
+    unistd::Pid,
+};
+use tracing::{debug, debug_span, warn};
+
+use crate::{
+    arch::{self, Arch},
+    tracer::Tracer,
+};
+
+// Runs syscalls on behalf of a stopped tracee by temporarily patching a syscall instruction at
+// its pc. The original code and registers are kept so they can be restored into the tracee, or
+// into a fork of it. Syscall numbers are the ones of the debugger's own architecture.
+pub(crate) struct SyscallInjector {
+    pid: Pid,
+    arch: &'static dyn Arch,
+    saved_regs: libc::user_regs_struct,
+    saved_code: Vec<u8>,
+}
+
+impl SyscallInjector {
+    pub fn new<T: Tracer>(tracer: &T, pid: Pid) -> anyhow::Result<Self> {
+        let arch = arch::native();
+        let saved_regs = tracer.get_regs(pid)?;
+        let pc = arch.pc(&saved_regs);
+
+        let syscall_instruction = arch.syscall_instruction();
+        let mut saved_code = vec![0; syscall_instruction.len()];
+        tracer.read_memory(pid, pc, &mut saved_code)?;
+        tracer.write_memory(pid, pc, syscall_instruction)?;
+
+        Ok(Self {
+            pid,
+            arch,
+            saved_regs,
+            saved_code,
+        })
+    }
+
+    pub fn call<T: Tracer>(&self, tracer: &T, number: c_long, args: &[u64]) -> anyhow::Result<i64> {
+        let span = debug_span!(
+            "injecting syscall",
+            pid = tracing::field::display(&self.pid),
+            number = number,
+        );
+        let _entered = span.enter();
+
+        let mut regs = self.saved_regs;
+        self.arch.prepare_syscall(&mut regs, number as u64, args);
+        tracer.set_regs(self.pid, regs)?;
+
+        loop {
+            tracer.step(self.pid, None)?;
+            match tracer.wait(self.pid, WaitPidFlag::__WALL)? {
+                WaitStatus::Stopped(_, Signal::SIGTRAP) => break,
+                WaitStatus::PtraceEvent(_, _, event) => {
+                    debug!(event = event, "ptrace event during injected syscall")
+                }
+                WaitStatus::Stopped(_, signal) => {
+                    warn!(signal = %signal, "discarding signal delivered during injected syscall")
+                }
+                status => Err(anyhow!(
+                    "debuggee stopped unexpectedly during injected syscall: {:?}",
+                    status
+                ))?,
+            }
+        }
+
+        Ok(self.arch.syscall_return_value(&tracer.get_regs(self.pid)?))
+    }
+
+    pub fn restore<T: Tracer>(&self, tracer: &T, pid: Pid) -> anyhow::Result<()> {
+        tracer.write_memory(pid, self.arch.pc(&self.saved_regs), &self.saved_code)?;
+        tracer.set_regs(pid, self.saved_regs)?;
+        Ok(())
+    }
+}
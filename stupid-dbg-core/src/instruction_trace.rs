@@ -0,0 +1,85 @@
+use std::fmt;
+
+use nix::unistd::Pid;
+
+use crate::{
+    register::{Register, RegisterValue},
+    tracepoint::{TraceBuffer, TraceRecord},
+    virt_addr::VirtAddr,
+};
+
+pub const DEFAULT_INSTRUCTION_TRACE_CAPACITY: usize = 65536;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct InstructionRecord {
+    // counts every instruction of the recording, dropped ones included
+    pub index: usize,
+    pub thread: Pid,
+    // of the instruction before it executed, with the registers at that point
+    pub pc: VirtAddr,
+    pub registers: Vec<(Register, RegisterValue)>,
+}
+
+// An instruction on a single line, the way trace dumps and trace files have them:
+//
+//     insn=3 thread=4242 pc=0x1001 rax=0x0000000000000000
+impl fmt::Display for InstructionRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "insn={} thread={} pc={}",
+            self.index, self.thread, self.pc
+        )?;
+        for (register, value) in &self.registers {
+            write!(f, " {}={}", register.name(), value)?;
+        }
+        Ok(())
+    }
+}
+
+impl TraceRecord for InstructionRecord {
+    fn index(&self) -> usize {
+        self.index
+    }
+
+    fn set_index(&mut self, index: usize) {
+        self.index = index;
+    }
+}
+
+// The instructions executed while recording, with the registers asked for, kept in a trace
+// buffer of their own.
+#[derive(Debug, Clone)]
+pub struct InstructionTrace {
+    registers: Vec<Register>,
+    records: TraceBuffer<InstructionRecord>,
+}
+
+impl Default for InstructionTrace {
+    fn default() -> Self {
+        Self::new(DEFAULT_INSTRUCTION_TRACE_CAPACITY, Vec::new())
+    }
+}
+
+impl InstructionTrace {
+    pub fn new(capacity: usize, registers: Vec<Register>) -> Self {
+        Self {
+            registers,
+            records: TraceBuffer::new(capacity),
+        }
+    }
+
+    // Recorded with every instruction besides the pc.
+    pub fn registers(&self) -> &[Register] {
+        &self.registers
+    }
+
+    pub fn records(&self) -> &TraceBuffer<InstructionRecord> {
+        &self.records
+    }
+
+    // The index is filled in by the buffer, records are numbered in the order they're given.
+    pub fn record(&mut self, record: InstructionRecord) {
+        self.records.record(record);
+    }
+}
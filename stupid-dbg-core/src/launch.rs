@@ -0,0 +1,207 @@
+use std::{
+    collections::BTreeMap,
+    env,
+    ffi::{CString, OsString},
+    fs::{File, OpenOptions},
+    os::{fd::OwnedFd, unix::ffi::OsStringExt},
+    path::PathBuf,
+};
+
+use anyhow::anyhow;
+use nix::{fcntl::OFlag, sys::ptrace::Options, unistd::pipe2};
+use nonempty::NonEmpty;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Stdio {
+    Inherit,
+    Null,
+    File(PathBuf),
+    // a pipe the debugger reads from, see `Debuggee::take_captured_output`, not for stdin
+    Capture,
+}
+
+// Read ends of the pipes captured stdout and stderr of the debuggee go to.
+#[derive(Debug, Default)]
+pub struct CapturedOutput {
+    pub stdout: Option<File>,
+    pub stderr: Option<File>,
+}
+
+#[derive(Debug, Clone)]
+pub struct LaunchSpec {
+    args: NonEmpty<String>,
+    clear_env: bool,
+    env: BTreeMap<String, Option<String>>,
+    cwd: Option<PathBuf>,
+    stdin: Stdio,
+    stdout: Stdio,
+    stderr: Stdio,
+    disable_aslr: bool,
+    stop_at_entry: bool,
+    ptrace_options: Options,
+}
+
+impl From<NonEmpty<String>> for LaunchSpec {
+    fn from(args: NonEmpty<String>) -> Self {
+        Self::new(args)
+    }
+}
+
+impl LaunchSpec {
+    pub fn new(args: NonEmpty<String>) -> Self {
+        Self {
+            args,
+            clear_env: false,
+            env: BTreeMap::new(),
+            cwd: None,
+            stdin: Stdio::Inherit,
+            stdout: Stdio::Inherit,
+            stderr: Stdio::Inherit,
+            disable_aslr: false,
+            stop_at_entry: false,
+            ptrace_options: Options::empty(),
+        }
+    }
+
+    pub fn env<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> Self {
+        self.env.insert(key.into(), Some(value.into()));
+        self
+    }
+
+    pub fn env_remove<K: Into<String>>(mut self, key: K) -> Self {
+        self.env.insert(key.into(), None);
+        self
+    }
+
+    // Start from an empty environment instead of inheriting the debugger's.
+    pub fn env_clear(mut self) -> Self {
+        self.clear_env = true;
+        self.env.clear();
+        self
+    }
+
+    pub fn cwd<P: Into<PathBuf>>(mut self, cwd: P) -> Self {
+        self.cwd = Some(cwd.into());
+        self
+    }
+
+    pub fn stdin(mut self, stdin: Stdio) -> Self {
+        self.stdin = stdin;
+        self
+    }
+
+    pub fn stdout(mut self, stdout: Stdio) -> Self {
+        self.stdout = stdout;
+        self
+    }
+
+    pub fn stderr(mut self, stderr: Stdio) -> Self {
+        self.stderr = stderr;
+        self
+    }
+
+    pub fn disable_aslr(mut self, disable_aslr: bool) -> Self {
+        self.disable_aslr = disable_aslr;
+        self
+    }
+
+    pub fn stop_at_entry(mut self, stop_at_entry: bool) -> Self {
+        self.stop_at_entry = stop_at_entry;
+        self
+    }
+
+    // Extra options on top of the ones the debugger always sets.
+    pub fn ptrace_options(mut self, ptrace_options: Options) -> Self {
+        self.ptrace_options = ptrace_options;
+        self
+    }
+
+    pub fn args(&self) -> &NonEmpty<String> {
+        &self.args
+    }
+
+    pub fn cwd_path(&self) -> Option<&PathBuf> {
+        self.cwd.as_ref()
+    }
+
+    pub fn is_aslr_disabled(&self) -> bool {
+        self.disable_aslr
+    }
+
+    pub fn should_stop_at_entry(&self) -> bool {
+        self.stop_at_entry
+    }
+
+    pub fn extra_ptrace_options(&self) -> Options {
+        self.ptrace_options
+    }
+
+    pub(crate) fn c_args(&self) -> anyhow::Result<Vec<CString>> {
+        self.args
+            .iter()
+            .map(|arg| {
+                CString::new(arg.clone())
+                    .map_err(|_| anyhow!("argument contains a nul byte: {:?}", arg))
+            })
+            .collect()
+    }
+
+    pub(crate) fn c_env(&self) -> anyhow::Result<Vec<CString>> {
+        let mut vars = if self.clear_env {
+            BTreeMap::new()
+        } else {
+            env::vars_os()
+                .map(|(key, value)| (key, Some(value)))
+                .collect::<BTreeMap<_, _>>()
+        };
+        for (key, value) in &self.env {
+            vars.insert(OsString::from(key), value.as_ref().map(OsString::from));
+        }
+
+        vars.into_iter()
+            .filter_map(|(key, value)| value.map(|value| (key, value)))
+            .map(|(key, value)| {
+                let mut var = key.into_vec();
+                var.push(b'=');
+                var.extend(value.into_vec());
+                CString::new(var).map_err(|_| anyhow!("environment variable contains a nul byte"))
+            })
+            .collect()
+    }
+
+    // Descriptors to install as stdin, stdout and stderr of the child, None to inherit, and the
+    // debugger's ends of the captured ones.
+    pub(crate) fn open_stdio(&self) -> anyhow::Result<([Option<OwnedFd>; 3], CapturedOutput)> {
+        fn open(stdio: &Stdio, write: bool) -> anyhow::Result<(Option<OwnedFd>, Option<File>)> {
+            let file = match stdio {
+                Stdio::Inherit => return Ok((None, None)),
+                Stdio::Capture if write => {
+                    let (read_end, write_end) = pipe2(OFlag::O_CLOEXEC)
+                        .map_err(|err| anyhow!("unable to create pipe: {}", err))?;
+                    return Ok((Some(write_end), Some(File::from(read_end))));
+                }
+                Stdio::Capture => Err(anyhow!("stdin can't be captured"))?,
+                Stdio::Null => OpenOptions::new()
+                    .read(!write)
+                    .write(write)
+                    .open("/dev/null")?,
+                Stdio::File(path) if write => File::create(path)
+                    .map_err(|err| anyhow!("unable to create {}: {}", path.display(), err))?,
+                Stdio::File(path) => File::open(path)
+                    .map_err(|err| anyhow!("unable to open {}: {}", path.display(), err))?,
+            };
+            Ok((Some(file.into()), None))
+        }
+
+        let (stdin, _) = open(&self.stdin, false)?;
+        let (stdout, captured_stdout) = open(&self.stdout, true)?;
+        let (stderr, captured_stderr) = open(&self.stderr, true)?;
+        Ok((
+            [stdin, stdout, stderr],
+            CapturedOutput {
+                stdout: captured_stdout,
+                stderr: captured_stderr,
+            },
+        ))
+    }
+}
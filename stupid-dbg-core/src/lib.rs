@@ -0,0 +1,53 @@
+#![feature(iter_intersperse)]
+
+pub mod anti_debug;
+pub mod arch;
+#[cfg(feature = "async")]
+pub mod async_debuggee;
+pub(crate) mod aux;
+pub mod auxv;
+pub mod breakpoint;
+pub mod c_type;
+pub mod cancel;
+pub(crate) mod checkpoint;
+pub mod checksec;
+pub(crate) mod core_dump;
+pub mod coverage;
+pub mod crash;
+pub mod debug_register;
+pub mod debuggee;
+pub mod disassemble;
+pub mod elf;
+pub mod expression;
+pub mod file_descriptor;
+pub mod format;
+pub mod fp_control;
+pub mod heap;
+pub(crate) mod inject;
+pub mod instruction_trace;
+pub mod launch;
+pub mod line_table;
+pub mod mapped_file;
+pub(crate) mod memory;
+pub(crate) mod memory_cache;
+pub mod memory_map;
+pub mod namespace;
+pub mod plain_data;
+pub mod pretty_printer;
+pub mod provenance;
+pub mod register;
+pub mod session;
+pub mod session_state;
+pub mod source_path;
+pub mod stop_log;
+pub mod stop_reason;
+pub mod symbol_cache;
+pub mod symbol_index;
+pub mod symbols;
+pub mod tracepoint;
+pub mod tracer;
+pub mod trampoline;
+pub mod unit_parser;
+pub mod verdict;
+pub mod virt_addr;
+pub mod watchpoint;
@@ -0,0 +1,691 @@
+use std::{fmt, path::PathBuf, sync::OnceLock};
+
+use anyhow::anyhow;
+use tracing::{debug, warn};
+
+use crate::{
+    aux::box_err, elf::ElfFile, memory_map::MemoryMap, namespace::ProcessRoot, unit_parser,
+    virt_addr::VirtAddr,
+};
+
+const DW_LNS_COPY: u8 = 1;
+const DW_LNS_ADVANCE_PC: u8 = 2;
+const DW_LNS_ADVANCE_LINE: u8 = 3;
+const DW_LNS_SET_FILE: u8 = 4;
+const DW_LNS_NEGATE_STMT: u8 = 6;
+const DW_LNS_CONST_ADD_PC: u8 = 8;
+const DW_LNS_FIXED_ADVANCE_PC: u8 = 9;
+
+const DW_LNE_END_SEQUENCE: u8 = 1;
+const DW_LNE_SET_ADDRESS: u8 = 2;
+const DW_LNE_DEFINE_FILE: u8 = 3;
+
+const DW_LNCT_PATH: u64 = 1;
+const DW_LNCT_DIRECTORY_INDEX: u64 = 2;
+
+const DW_FORM_BLOCK2: u64 = 0x03;
+const DW_FORM_BLOCK4: u64 = 0x04;
+const DW_FORM_DATA2: u64 = 0x05;
+const DW_FORM_DATA4: u64 = 0x06;
+const DW_FORM_DATA8: u64 = 0x07;
+const DW_FORM_STRING: u64 = 0x08;
+const DW_FORM_BLOCK: u64 = 0x09;
+const DW_FORM_BLOCK1: u64 = 0x0a;
+const DW_FORM_DATA1: u64 = 0x0b;
+const DW_FORM_STRP: u64 = 0x0e;
+const DW_FORM_UDATA: u64 = 0x0f;
+const DW_FORM_DATA16: u64 = 0x1e;
+const DW_FORM_LINE_STRP: u64 = 0x1f;
+
+// A row of a line number program: the instructions from `address` up to the next row are code
+// of `line`. End of sequence rows only mark where the code of the previous one ends.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineRow {
+    // where it is in the running process, with the load bias applied
+    pub address: VirtAddr,
+    pub file: PathBuf,
+    pub line: u64,
+    // a recommended breakpoint location, as opposed to e.g. the middle of an expression
+    pub is_stmt: bool,
+    pub end_sequence: bool,
+}
+
+impl LineRow {
+    pub fn location(&self) -> SourceLocation {
+        SourceLocation {
+            file: self.file.clone(),
+            line: self.line,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SourceLocation {
+    pub file: PathBuf,
+    pub line: u64,
+}
+
+impl fmt::Display for SourceLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.file.display(), self.line)
+    }
+}
+
+// Splits `main.rs:42` into the file and the line. None for anything else, including names like
+// `foo::bar`.
+pub fn parse_file_line(input: &str) -> Option<(&str, u64)> {
+    let (file, line) = input.trim().rsplit_once(':')?;
+    if file.is_empty() || file.ends_with(':') {
+        return None;
+    }
+    Some((file, line.parse().ok()?))
+}
+
+// DWARF line tables of the modules mapped into the debuggee. A module's table is only parsed
+// the first time it's needed, when an address in it is looked up or when a line is searched for,
+// which needs all of them.
+#[derive(Debug, Clone, Default)]
+pub struct LineTable {
+    // sorted by where they're mapped
+    modules: Vec<ModuleLines>,
+}
+
+#[derive(Debug, Clone)]
+struct ModuleLines {
+    name: String,
+    start: VirtAddr,
+    end: VirtAddr,
+    // the file, kept mapped until it's parsed, and its load bias
+    source: Option<(ElfFile, u64)>,
+    // sorted by address
+    rows: OnceLock<Vec<LineRow>>,
+}
+
+impl ModuleLines {
+    fn rows(&self) -> &[LineRow] {
+        self.rows.get_or_init(|| {
+            let Some((elf, bias)) = &self.source else {
+                return Vec::new();
+            };
+            match parse_elf(elf, *bias, &self.name) {
+                Ok(rows) => {
+                    debug!(module = %self.name, rows = rows.len(), "line table loaded");
+                    rows
+                }
+                Err(err) => {
+                    warn!(error = box_err(err), module = %self.name, "unable to load line table");
+                    Vec::new()
+                }
+            }
+        })
+    }
+}
+
+impl LineTable {
+    // Modules without line tables are left out, the ones that can't be read with a warning. The
+    // ones that can't be parsed are warned about once they're needed.
+    pub fn load(memory_map: &MemoryMap, root: &ProcessRoot) -> Self {
+        let mut modules = Vec::new();
+        for (module, base) in memory_map.modules() {
+            let elf = match ElfFile::read(root.module_path(memory_map, module)) {
+                Ok(elf) => elf,
+                Err(err) => {
+                    warn!(error = box_err(err), module = %module, "unable to load line table");
+                    continue;
+                }
+            };
+            if elf.section(".debug_line").is_none() {
+                continue;
+            }
+            let bias = if elf.is_position_independent() {
+                base.as_u64().wrapping_sub(elf.link_base())
+            } else {
+                0
+            };
+            let end = memory_map
+                .regions()
+                .iter()
+                .filter(|region| region.path.as_deref() == Some(module))
+                .map(|region| region.end)
+                .max()
+                .unwrap_or(base);
+
+            modules.push(ModuleLines {
+                name: module.to_string(),
+                start: base,
+                end,
+                source: Some((elf, bias)),
+                rows: OnceLock::new(),
+            });
+        }
+        modules.sort_by_key(|module| module.start);
+
+        Self { modules }
+    }
+
+    // Empty if the file has no .debug_line section.
+    pub fn from_elf(elf: &ElfFile, bias: u64) -> anyhow::Result<Self> {
+        Ok(Self::from_rows(parse_elf(elf, bias, "")?))
+    }
+
+    // Every line number program of `debug_line`, the string sections are the ones DWARF 5
+    // headers refer to.
+    pub fn parse(
+        debug_line: &[u8],
+        line_strings: &[u8],
+        strings: &[u8],
+        bias: u64,
+    ) -> anyhow::Result<Self> {
+        let strings = Strings {
+            line_strings,
+            strings,
+        };
+        Ok(Self::from_rows(parse_units(
+            debug_line, &strings, bias, "",
+        )?))
+    }
+
+    pub fn from_rows(mut rows: Vec<LineRow>) -> Self {
+        sort_rows(&mut rows);
+        Self {
+            modules: vec![ModuleLines {
+                name: String::new(),
+                start: VirtAddr::new(0),
+                end: VirtAddr::new(u64::MAX),
+                source: None,
+                rows: OnceLock::from(rows),
+            }],
+        }
+    }
+
+    // Every row of every module by address, parsing the modules that haven't been yet.
+    pub fn rows(&self) -> impl Iterator<Item = &LineRow> {
+        self.modules.iter().flat_map(|module| module.rows())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows().next().is_none()
+    }
+
+    // The row whose code `address` is in.
+    pub fn lookup(&self, address: VirtAddr) -> Option<&LineRow> {
+        let module = self
+            .modules
+            .iter()
+            .find(|module| (module.start..module.end).contains(&address))?;
+        let rows = module.rows();
+        let index = rows.partition_point(|row| row.address <= address);
+        let row = rows.get(index.checked_sub(1)?)?;
+        (!row.end_sequence).then_some(row)
+    }
+
+    // The lowest address of `line` in `file`, or of the first line after it with code if there's
+    // none on it, like a blank line or a comment. `file` only has to match the end of the path
+    // recorded for it, e.g. `main.rs` or `src/main.rs`.
+    pub fn find_line(&self, file: &str, line: u64) -> anyhow::Result<&LineRow> {
+        let rows = self
+            .rows()
+            .filter(|row| row.is_stmt && !row.end_sequence && row.file.ends_with(file))
+            .collect::<Vec<_>>();
+        if rows.is_empty() {
+            Err(anyhow!("no line table entries for {}", file))?;
+        }
+
+        let nearest = rows
+            .iter()
+            .map(|row| row.line)
+            .filter(|candidate| *candidate >= line)
+            .min()
+            .ok_or(anyhow!("no code at or after line {} of {}", line, file))?;
+        Ok(rows
+            .into_iter()
+            .filter(|row| row.line == nearest)
+            .min_by_key(|row| row.address)
+            .expect("a row has the nearest line"))
+    }
+}
+
+fn sort_rows(rows: &mut [LineRow]) {
+    // where one sequence ends and another starts, the start has to come last
+    rows.sort_by_key(|row| (row.address, !row.end_sequence));
+}
+
+// Sorted rows of the .debug_line section of `elf`, none if it has none.
+fn parse_elf(elf: &ElfFile, bias: u64, module: &str) -> anyhow::Result<Vec<LineRow>> {
+    let section = |name: &str| {
+        elf.section(name)
+            .and_then(|section| elf.section_data(section))
+            .unwrap_or_default()
+    };
+    let strings = Strings {
+        line_strings: section(".debug_line_str"),
+        strings: section(".debug_str"),
+    };
+    let mut rows = parse_units(section(".debug_line"), &strings, bias, module)?;
+    sort_rows(&mut rows);
+    Ok(rows)
+}
+
+// Rows of every unit of `debug_line`, unsorted, see `unit_parser::parse_units`.
+fn parse_units(
+    debug_line: &[u8],
+    strings: &Strings,
+    bias: u64,
+    module: &str,
+) -> anyhow::Result<Vec<LineRow>> {
+    let units = split_units(debug_line)?;
+    unit_parser::parse_units(module, debug_line.len(), &units, |unit, rows| {
+        parse_unit(unit, strings, bias, rows)
+    })
+}
+
+// A unit of the section, after its length.
+struct Unit<'a> {
+    bytes: &'a [u8],
+    dwarf64: bool,
+}
+
+fn split_units(debug_line: &[u8]) -> anyhow::Result<Vec<Unit<'_>>> {
+    let mut units = Vec::new();
+    let mut reader = Reader::new(debug_line);
+    while !reader.is_empty() {
+        let (length, dwarf64) = match reader.u32()? {
+            0xffff_ffff => (reader.u64()?, true),
+            length => (length as u64, false),
+        };
+        units.push(Unit {
+            bytes: reader.take(length as usize)?,
+            dwarf64,
+        });
+    }
+    Ok(units)
+}
+
+struct Strings<'a> {
+    line_strings: &'a [u8],
+    strings: &'a [u8],
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Header {
+    version: u16,
+    dwarf64: bool,
+    min_instruction_length: u8,
+    default_is_stmt: bool,
+    line_base: i8,
+    line_range: u8,
+    opcode_base: u8,
+}
+
+// One unit of the section: its header, then the program up to the end of the unit.
+fn parse_unit(
+    unit: &Unit,
+    strings: &Strings,
+    bias: u64,
+    rows: &mut Vec<LineRow>,
+) -> anyhow::Result<()> {
+    let dwarf64 = unit.dwarf64;
+    let mut unit = Reader::new(unit.bytes);
+
+    let version = unit.u16()?;
+    if !(2..=5).contains(&version) {
+        Err(anyhow!(
+            "DWARF version {} line tables are not supported",
+            version
+        ))?;
+    }
+    if version >= 5 {
+        // address and segment selector size, DW_LNE_set_address has its own length anyway
+        unit.skip(2)?;
+    }
+    let header_length = unit.offset(dwarf64)?;
+    let mut program = unit.clone();
+    program.skip(header_length as usize)?;
+
+    let min_instruction_length = unit.u8()?;
+    if version >= 4 {
+        let _max_ops_per_instruction = unit.u8()?;
+    }
+    let header = Header {
+        version,
+        dwarf64,
+        min_instruction_length,
+        default_is_stmt: unit.u8()? != 0,
+        line_base: unit.u8()? as i8,
+        line_range: unit.u8()?,
+        opcode_base: unit.u8()?,
+    };
+    if header.line_range == 0 {
+        Err(anyhow!("line table with a line range of 0"))?;
+    }
+    let opcode_lengths = (1..header.opcode_base)
+        .map(|_| unit.u8())
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let mut files = if version >= 5 {
+        v5_files(&mut unit, &header, strings)?
+    } else {
+        v4_files(&mut unit)?
+    };
+
+    run_program(
+        &mut program,
+        &header,
+        &opcode_lengths,
+        &mut files,
+        bias,
+        rows,
+    )
+}
+
+// Include directories and file names of DWARF 2 to 4, both indexed from 1.
+fn v4_files(unit: &mut Reader) -> anyhow::Result<Vec<PathBuf>> {
+    let mut directories = vec![PathBuf::new()];
+    loop {
+        let directory = unit.c_string()?;
+        if directory.is_empty() {
+            break;
+        }
+        directories.push(PathBuf::from(directory));
+    }
+
+    let mut files = vec![PathBuf::new()];
+    loop {
+        let name = unit.c_string()?;
+        if name.is_empty() {
+            break;
+        }
+        let directory = unit.uleb128()? as usize;
+        let _modified = unit.uleb128()?;
+        let _length = unit.uleb128()?;
+        files.push(join_directory(&directories, directory, &name));
+    }
+    Ok(files)
+}
+
+// DWARF 5 describes the entries of both tables with a list of content types and forms. Both
+// are indexed from 0.
+fn v5_files(unit: &mut Reader, header: &Header, strings: &Strings) -> anyhow::Result<Vec<PathBuf>> {
+    let entries = |unit: &mut Reader| -> anyhow::Result<Vec<(Option<String>, usize)>> {
+        let formats = (0..unit.u8()?)
+            .map(|_| Ok((unit.uleb128()?, unit.uleb128()?)))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        (0..unit.uleb128()?)
+            .map(|_| {
+                let mut path = None;
+                let mut directory = 0;
+                for (content, form) in &formats {
+                    let value = read_form(unit, *form, header, strings)?;
+                    match (content, value) {
+                        (&DW_LNCT_PATH, FormValue::String(string)) => path = Some(string),
+                        (&DW_LNCT_DIRECTORY_INDEX, FormValue::Number(index)) => {
+                            directory = index as usize
+                        }
+                        _ => (),
+                    }
+                }
+                Ok((path, directory))
+            })
+            .collect()
+    };
+
+    let directories = entries(unit)?
+        .into_iter()
+        .map(|(path, _)| PathBuf::from(path.unwrap_or_default()))
+        .collect::<Vec<_>>();
+    Ok(entries(unit)?
+        .into_iter()
+        .map(|(path, directory)| join_directory(&directories, directory, &path.unwrap_or_default()))
+        .collect())
+}
+
+fn join_directory(directories: &[PathBuf], directory: usize, name: &str) -> PathBuf {
+    match directories.get(directory) {
+        Some(directory) => directory.join(name),
+        None => PathBuf::from(name),
+    }
+}
+
+enum FormValue {
+    String(String),
+    Number(u64),
+    Other,
+}
+
+fn read_form(
+    unit: &mut Reader,
+    form: u64,
+    header: &Header,
+    strings: &Strings,
+) -> anyhow::Result<FormValue> {
+    let string_at = |section: &[u8], offset: u64| {
+        section
+            .get(offset as usize..)
+            .map(|bytes| FormValue::String(Reader::new(bytes).c_string().unwrap_or_default()))
+            .ok_or(anyhow!("string offset {:#x} out of bounds", offset))
+    };
+
+    Ok(match form {
+        DW_FORM_STRING => FormValue::String(unit.c_string()?),
+        DW_FORM_LINE_STRP => string_at(strings.line_strings, unit.offset(header.dwarf64)?)?,
+        DW_FORM_STRP => string_at(strings.strings, unit.offset(header.dwarf64)?)?,
+        DW_FORM_UDATA => FormValue::Number(unit.uleb128()?),
+        DW_FORM_DATA1 => FormValue::Number(unit.u8()? as u64),
+        DW_FORM_DATA2 => FormValue::Number(unit.u16()? as u64),
+        DW_FORM_DATA4 => FormValue::Number(unit.u32()? as u64),
+        DW_FORM_DATA8 => FormValue::Number(unit.u64()?),
+        DW_FORM_DATA16 => {
+            unit.skip(16)?;
+            FormValue::Other
+        }
+        DW_FORM_BLOCK | DW_FORM_BLOCK1 | DW_FORM_BLOCK2 | DW_FORM_BLOCK4 => {
+            let length = match form {
+                DW_FORM_BLOCK1 => unit.u8()? as u64,
+                DW_FORM_BLOCK2 => unit.u16()? as u64,
+                DW_FORM_BLOCK4 => unit.u32()? as u64,
+                _ => unit.uleb128()?,
+            };
+            unit.skip(length as usize)?;
+            FormValue::Other
+        }
+        form => Err(anyhow!("unsupported form {:#x} in line table header", form))?,
+    })
+}
+
+// The registers of the line number program nothing here can do without, columns, basic blocks
+// and the like are left out.
+#[derive(Debug, Clone, Copy)]
+struct Registers {
+    address: u64,
+    file: usize,
+    line: u64,
+    is_stmt: bool,
+}
+
+impl Registers {
+    fn new(header: &Header) -> Self {
+        Self {
+            address: 0,
+            file: if header.version >= 5 { 0 } else { 1 },
+            line: 1,
+            is_stmt: header.default_is_stmt,
+        }
+    }
+
+    fn row(&self, files: &[PathBuf], bias: u64, end_sequence: bool) -> LineRow {
+        LineRow {
+            address: VirtAddr::new(self.address.wrapping_add(bias)),
+            file: files.get(self.file).cloned().unwrap_or_default(),
+            line: self.line,
+            is_stmt: self.is_stmt,
+            end_sequence,
+        }
+    }
+}
+
+fn run_program(
+    program: &mut Reader,
+    header: &Header,
+    opcode_lengths: &[u8],
+    files: &mut Vec<PathBuf>,
+    bias: u64,
+    rows: &mut Vec<LineRow>,
+) -> anyhow::Result<()> {
+    let min_length = header.min_instruction_length as u64;
+    let mut registers = Registers::new(header);
+
+    while !program.is_empty() {
+        let opcode = program.u8()?;
+        if opcode >= header.opcode_base {
+            let adjusted = opcode - header.opcode_base;
+            registers.address += (adjusted / header.line_range) as u64 * min_length;
+            registers.line = registers.line.wrapping_add_signed(
+                header.line_base as i64 + (adjusted % header.line_range) as i64,
+            );
+            rows.push(registers.row(files, bias, false));
+            continue;
+        }
+
+        match opcode {
+            0 => {
+                let length = program.uleb128()? as usize;
+                let mut extended = Reader::new(program.take(length)?);
+                match extended.u8()? {
+                    DW_LNE_END_SEQUENCE => {
+                        rows.push(registers.row(files, bias, true));
+                        registers = Registers::new(header);
+                    }
+                    DW_LNE_SET_ADDRESS => {
+                        registers.address = match length - 1 {
+                            4 => extended.u32()? as u64,
+                            _ => extended.u64()?,
+                        };
+                    }
+                    DW_LNE_DEFINE_FILE => {
+                        // relative to an include directory, which is lost by now
+                        files.push(PathBuf::from(extended.c_string()?));
+                    }
+                    // e.g. discriminators
+                    _ => (),
+                }
+            }
+            DW_LNS_COPY => rows.push(registers.row(files, bias, false)),
+            DW_LNS_ADVANCE_PC => registers.address += program.uleb128()? * min_length,
+            DW_LNS_ADVANCE_LINE => {
+                registers.line = registers.line.wrapping_add_signed(program.sleb128()?)
+            }
+            DW_LNS_SET_FILE => registers.file = program.uleb128()? as usize,
+            DW_LNS_NEGATE_STMT => registers.is_stmt = !registers.is_stmt,
+            DW_LNS_CONST_ADD_PC => {
+                registers.address +=
+                    ((255 - header.opcode_base) / header.line_range) as u64 * min_length
+            }
+            DW_LNS_FIXED_ADVANCE_PC => registers.address += program.u16()? as u64,
+            // the rest only take ULEB128 arguments, as many as the header says
+            opcode => {
+                for _ in 0..opcode_lengths[opcode as usize - 1] {
+                    program.uleb128()?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Clone)]
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    fn take(&mut self, len: usize) -> anyhow::Result<&'a [u8]> {
+        if self.bytes.len() < len {
+            Err(anyhow!("truncated line table"))?;
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn skip(&mut self, len: usize) -> anyhow::Result<()> {
+        self.take(len).map(|_| ())
+    }
+
+    fn u8(&mut self) -> anyhow::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> anyhow::Result<u16> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> anyhow::Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> anyhow::Result<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    // A section offset, 8 bytes in 64-bit DWARF.
+    fn offset(&mut self, dwarf64: bool) -> anyhow::Result<u64> {
+        if dwarf64 {
+            self.u64()
+        } else {
+            self.u32().map(u64::from)
+        }
+    }
+
+    fn uleb128(&mut self) -> anyhow::Result<u64> {
+        let mut value = 0u64;
+        let mut shift = 0;
+        loop {
+            let byte = self.u8()?;
+            if shift < 64 {
+                value |= ((byte & 0x7f) as u64) << shift;
+            }
+            shift += 7;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+    }
+
+    fn sleb128(&mut self) -> anyhow::Result<i64> {
+        let mut value = 0i64;
+        let mut shift = 0;
+        loop {
+            let byte = self.u8()?;
+            if shift < 64 {
+                value |= ((byte & 0x7f) as i64) << shift;
+            }
+            shift += 7;
+            if byte & 0x80 == 0 {
+                if shift < 64 && byte & 0x40 != 0 {
+                    value |= -1 << shift;
+                }
+                return Ok(value);
+            }
+        }
+    }
+
+    fn c_string(&mut self) -> anyhow::Result<String> {
+        let end = self
+            .bytes
+            .iter()
+            .position(|byte| *byte == 0)
+            .ok_or(anyhow!("unterminated string in line table"))?;
+        let string = String::from_utf8_lossy(&self.bytes[..end]).into_owned();
+        self.bytes = &self.bytes[end + 1..];
+        Ok(string)
+    }
+}
@@ -0,0 +1,63 @@
+use std::{ffi::c_void, fs::File, num::NonZeroUsize, path::Path, ptr::NonNull, slice};
+
+use anyhow::anyhow;
+use nix::sys::mman::{mmap, munmap, MapFlags, ProtFlags};
+
+// A whole file mapped read-only into the debugger. Its pages are read in as they're touched and
+// can be dropped by the kernel again, instead of the file taking up the heap. Linkers write a
+// new file rather than truncating the old one, a truncated file would fault on the pages that
+// are gone.
+#[derive(Debug)]
+pub struct MappedFile {
+    address: NonNull<c_void>,
+    len: usize,
+}
+
+// the mapping is read-only and owned by nothing else
+unsafe impl Send for MappedFile {}
+unsafe impl Sync for MappedFile {}
+
+impl MappedFile {
+    pub fn open<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path)
+            .map_err(|err| anyhow!("unable to open {}: {}", path.display(), err))?;
+        let len = file
+            .metadata()
+            .map_err(|err| anyhow!("unable to stat {}: {}", path.display(), err))?
+            .len() as usize;
+
+        // an empty mapping isn't allowed
+        let Some(length) = NonZeroUsize::new(len) else {
+            return Ok(Self {
+                address: NonNull::dangling(),
+                len: 0,
+            });
+        };
+        let address = unsafe {
+            mmap(
+                None,
+                length,
+                ProtFlags::PROT_READ,
+                MapFlags::MAP_PRIVATE,
+                &file,
+                0,
+            )
+        }
+        .map_err(|err| anyhow!("unable to map {}: {}", path.display(), err))?;
+
+        Ok(Self { address, len })
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.address.as_ptr() as *const u8, self.len) }
+    }
+}
+
+impl Drop for MappedFile {
+    fn drop(&mut self) {
+        if self.len > 0 {
+            _ = unsafe { munmap(self.address, self.len) };
+        }
+    }
+}
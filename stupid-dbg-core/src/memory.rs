@@ -0,0 +1,59 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{IoSlice, IoSliceMut},
+    os::unix::fs::FileExt,
+};
+
+use anyhow::anyhow;
+use nix::{
+    sys::uio::{process_vm_readv, process_vm_writev, RemoteIoVec},
+    unistd::Pid,
+};
+use tracing::debug;
+
+// process_vm_readv and process_vm_writev take a single syscall for any length, but only get at
+// memory the debuggee could access itself. Whatever they leave, e.g. pages without the
+// permission or past the end of a mapping, goes through /proc/<pid>/mem, which ignores page
+// protections like ptrace does.
+
+pub(crate) fn read_memory(pid: Pid, addr: u64, buf: &mut [u8]) -> anyhow::Result<()> {
+    let remote = [RemoteIoVec {
+        base: addr as usize,
+        len: buf.len(),
+    }];
+    let done = process_vm_readv(pid, &mut [IoSliceMut::new(buf)], &remote).unwrap_or_else(|err| {
+        debug!(error = %err, address = format_args!("{:#x}", addr), "process_vm_readv failed");
+        0
+    });
+    if done == buf.len() {
+        return Ok(());
+    }
+
+    let addr = addr + done as u64;
+    let mem = File::open(format!("/proc/{}/mem", pid))
+        .map_err(|err| anyhow!("unable to open debuggee memory: {}", err))?;
+    mem.read_exact_at(&mut buf[done..], addr)
+        .map_err(|err| anyhow!("unable to read memory at {:#x}: {}", addr, err))
+}
+
+pub(crate) fn write_memory(pid: Pid, addr: u64, data: &[u8]) -> anyhow::Result<()> {
+    let remote = [RemoteIoVec {
+        base: addr as usize,
+        len: data.len(),
+    }];
+    let done = process_vm_writev(pid, &[IoSlice::new(data)], &remote).unwrap_or_else(|err| {
+        debug!(error = %err, address = format_args!("{:#x}", addr), "process_vm_writev failed");
+        0
+    });
+    if done == data.len() {
+        return Ok(());
+    }
+
+    let addr = addr + done as u64;
+    let mem = OpenOptions::new()
+        .write(true)
+        .open(format!("/proc/{}/mem", pid))
+        .map_err(|err| anyhow!("unable to open debuggee memory: {}", err))?;
+    mem.write_all_at(&data[done..], addr)
+        .map_err(|err| anyhow!("unable to write memory at {:#x}: {}", addr, err))
+}
@@ -0,0 +1,191 @@
+use std::{
+    cell::RefCell,
+    collections::{btree_map::Entry, BTreeMap},
+};
+
+use nix::{
+    sys::{
+        ptrace::Options,
+        signal::Signal,
+        wait::{WaitPidFlag, WaitStatus},
+    },
+    unistd::Pid,
+};
+
+use crate::tracer::Tracer;
+
+const PAGE_SIZE: u64 = 4096;
+// Larger reads, like memory dumps, would only push out what the cache is for.
+const MAX_CACHED_READ: usize = 16 * PAGE_SIZE as usize;
+
+// keyed by process and page address
+type Pages = BTreeMap<(Pid, u64), Box<[u8]>>;
+
+// Wraps the tracer of a debuggee to keep the pages it read from memory until the debuggee could
+// have changed them: every write, and every call that lets a thread run or reports it ran, flushes
+// all of them. Only whole pages are cached, a read touching a page that can't be read whole goes
+// to the tracer as it is.
+#[derive(Debug)]
+pub(crate) struct MemoryCache<T: Tracer> {
+    tracer: T,
+    pages: RefCell<Pages>,
+}
+
+impl<T: Tracer> MemoryCache<T> {
+    pub(crate) fn new(tracer: T) -> Self {
+        Self {
+            tracer,
+            pages: RefCell::new(BTreeMap::new()),
+        }
+    }
+
+    pub(crate) fn inner(&self) -> &T {
+        &self.tracer
+    }
+
+    pub(crate) fn flush(&self) {
+        self.pages.borrow_mut().clear();
+    }
+
+    // Bypasses the cache, for reads while the debuggee runs.
+    pub(crate) fn read_memory_uncached(
+        &self,
+        pid: Pid,
+        addr: u64,
+        buf: &mut [u8],
+    ) -> anyhow::Result<()> {
+        self.tracer.read_memory(pid, addr, buf)
+    }
+
+    fn read_cached(&self, pid: Pid, addr: u64, buf: &mut [u8]) -> Option<()> {
+        let end = addr.checked_add(buf.len() as u64)?;
+        let mut pages = self.pages.borrow_mut();
+        let mut page = addr - addr % PAGE_SIZE;
+        while page < end {
+            let data = match pages.entry((pid, page)) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => {
+                    let mut data = vec![0u8; PAGE_SIZE as usize].into_boxed_slice();
+                    self.tracer.read_memory(pid, page, &mut data).ok()?;
+                    entry.insert(data)
+                }
+            };
+
+            let start = addr.max(page);
+            let stop = end.min(page + PAGE_SIZE);
+            buf[(start - addr) as usize..(stop - addr) as usize]
+                .copy_from_slice(&data[(start - page) as usize..(stop - page) as usize]);
+            page += PAGE_SIZE;
+        }
+        Some(())
+    }
+}
+
+impl<T: Tracer> Tracer for MemoryCache<T> {
+    fn attach(&self, tid: Pid) -> nix::Result<()> {
+        self.tracer.attach(tid)
+    }
+
+    fn seize(&self, tid: Pid, options: Options) -> nix::Result<()> {
+        self.tracer.seize(tid, options)
+    }
+
+    fn interrupt(&self, tid: Pid) -> nix::Result<()> {
+        self.tracer.interrupt(tid)
+    }
+
+    fn listen(&self, tid: Pid) -> nix::Result<()> {
+        self.flush();
+        self.tracer.listen(tid)
+    }
+
+    fn detach(&self, tid: Pid, signal: Option<Signal>) -> nix::Result<()> {
+        self.flush();
+        self.tracer.detach(tid, signal)
+    }
+
+    fn set_options(&self, tid: Pid, options: Options) -> nix::Result<()> {
+        self.tracer.set_options(tid, options)
+    }
+
+    fn cont(&self, tid: Pid, signal: Option<Signal>) -> nix::Result<()> {
+        self.flush();
+        self.tracer.cont(tid, signal)
+    }
+
+    fn step(&self, tid: Pid, signal: Option<Signal>) -> nix::Result<()> {
+        self.flush();
+        self.tracer.step(tid, signal)
+    }
+
+    fn syscall(&self, tid: Pid, signal: Option<Signal>) -> nix::Result<()> {
+        self.flush();
+        self.tracer.syscall(tid, signal)
+    }
+
+    fn kill(&self, pid: Pid, signal: Signal) -> nix::Result<()> {
+        self.flush();
+        self.tracer.kill(pid, signal)
+    }
+
+    // Whatever is reported ran after the pages were read, if they were read while it ran.
+    fn wait(&self, tid: Pid, flags: WaitPidFlag) -> nix::Result<WaitStatus> {
+        let wait_status = self.tracer.wait(tid, flags);
+        if !matches!(wait_status, Ok(WaitStatus::StillAlive)) {
+            self.flush();
+        }
+        wait_status
+    }
+
+    fn get_event(&self, tid: Pid) -> nix::Result<libc::c_long> {
+        self.tracer.get_event(tid)
+    }
+
+    fn get_siginfo(&self, tid: Pid) -> nix::Result<libc::siginfo_t> {
+        self.tracer.get_siginfo(tid)
+    }
+
+    fn get_regs(&self, tid: Pid) -> nix::Result<libc::user_regs_struct> {
+        self.tracer.get_regs(tid)
+    }
+
+    fn set_regs(&self, tid: Pid, regs: libc::user_regs_struct) -> nix::Result<()> {
+        self.tracer.set_regs(tid, regs)
+    }
+
+    fn get_fpregs(&self, tid: Pid) -> nix::Result<libc::user_fpregs_struct> {
+        self.tracer.get_fpregs(tid)
+    }
+
+    fn set_fpregs(&self, tid: Pid, fpregs: libc::user_fpregs_struct) -> nix::Result<()> {
+        self.tracer.set_fpregs(tid, fpregs)
+    }
+
+    fn get_regset(&self, tid: Pid, note_type: i32, buf: &mut [u8]) -> nix::Result<usize> {
+        self.tracer.get_regset(tid, note_type, buf)
+    }
+
+    fn set_regset(&self, tid: Pid, note_type: i32, data: &[u8]) -> nix::Result<()> {
+        self.tracer.set_regset(tid, note_type, data)
+    }
+
+    fn read_user(&self, tid: Pid, offset: usize) -> nix::Result<libc::c_long> {
+        self.tracer.read_user(tid, offset)
+    }
+
+    fn write_user(&self, tid: Pid, offset: usize, data: libc::c_long) -> nix::Result<()> {
+        self.tracer.write_user(tid, offset, data)
+    }
+
+    fn read_memory(&self, pid: Pid, addr: u64, buf: &mut [u8]) -> anyhow::Result<()> {
+        if buf.len() <= MAX_CACHED_READ && self.read_cached(pid, addr, buf).is_some() {
+            return Ok(());
+        }
+        self.tracer.read_memory(pid, addr, buf)
+    }
+
+    fn write_memory(&self, pid: Pid, addr: u64, data: &[u8]) -> anyhow::Result<()> {
+        self.flush();
+        self.tracer.write_memory(pid, addr, data)
+    }
+}
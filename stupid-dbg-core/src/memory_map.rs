@@ -0,0 +1,291 @@
+use std::{collections::BTreeMap, fmt, fs, str::FromStr};
+
+use anyhow::anyhow;
+use nix::unistd::Pid;
+
+use crate::virt_addr::VirtAddr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Permissions {
+    pub read: bool,
+    pub write: bool,
+    pub execute: bool,
+    pub shared: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryRegion {
+    pub start: VirtAddr,
+    pub end: VirtAddr,
+    pub permissions: Permissions,
+    pub offset: u64,
+    pub device: String,
+    pub inode: u64,
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RegionKind {
+    // the main stack, or a thread's stack found through its stack pointer
+    Stack(Option<Pid>),
+    Heap,
+    // executable mappings of a file
+    Text(String),
+    // everything else mapped from a file
+    Data(String),
+    Vdso,
+    Anonymous,
+    // other pseudo mappings like [vvar] or [vsyscall]
+    Special(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryMap {
+    regions: Vec<MemoryRegion>,
+}
+
+impl FromStr for Permissions {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let flags = s.as_bytes();
+        if flags.len() != 4 {
+            return Err(anyhow!("invalid permission string: {}", s));
+        }
+
+        Ok(Self {
+            read: flags[0] == b'r',
+            write: flags[1] == b'w',
+            execute: flags[2] == b'x',
+            shared: flags[3] == b's',
+        })
+    }
+}
+
+impl Permissions {
+    // The PROT_* flags mprotect takes.
+    pub fn prot(&self) -> i32 {
+        let mut prot = libc::PROT_NONE;
+        if self.read {
+            prot |= libc::PROT_READ;
+        }
+        if self.write {
+            prot |= libc::PROT_WRITE;
+        }
+        if self.execute {
+            prot |= libc::PROT_EXEC;
+        }
+        prot
+    }
+}
+
+impl fmt::Display for Permissions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let flag = |set: bool, c: char| if set { c } else { '-' };
+        write!(
+            f,
+            "{}{}{}{}",
+            flag(self.read, 'r'),
+            flag(self.write, 'w'),
+            flag(self.execute, 'x'),
+            if self.shared { 's' } else { 'p' }
+        )
+    }
+}
+
+impl RegionKind {
+    pub fn is_stack(&self) -> bool {
+        matches!(self, RegionKind::Stack(_))
+    }
+}
+
+impl fmt::Display for RegionKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RegionKind::Stack(Some(tid)) => write!(f, "stack of thread {}", tid),
+            RegionKind::Stack(None) => write!(f, "stack"),
+            RegionKind::Heap => write!(f, "heap"),
+            RegionKind::Text(module) => write!(f, "text of {}", module),
+            RegionKind::Data(module) => write!(f, "data of {}", module),
+            RegionKind::Vdso => write!(f, "vdso"),
+            RegionKind::Anonymous => write!(f, "anonymous"),
+            RegionKind::Special(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+impl FromStr for MemoryRegion {
+    type Err = anyhow::Error;
+
+    // <start>-<end> <perms> <offset> <dev> <inode>    <path>
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let malformed = || anyhow!("malformed memory map entry: {}", line);
+
+        let mut fields = line.splitn(6, ' ');
+        let mut next_field = || fields.next().ok_or_else(malformed);
+
+        let (start, end) = next_field()?.split_once('-').ok_or_else(malformed)?;
+        let start = VirtAddr::new(u64::from_str_radix(start, 16)?);
+        let end = VirtAddr::new(u64::from_str_radix(end, 16)?);
+        let permissions = next_field()?.parse()?;
+        let offset = u64::from_str_radix(next_field()?, 16)?;
+        let device = next_field()?.to_string();
+        let inode = next_field()?.parse()?;
+        let path = fields
+            .next()
+            .map(str::trim)
+            .filter(|path| !path.is_empty())
+            .map(str::to_string);
+
+        Ok(Self {
+            start,
+            end,
+            permissions,
+            offset,
+            device,
+            inode,
+            path,
+        })
+    }
+}
+
+impl MemoryRegion {
+    pub fn size(&self) -> u64 {
+        self.end.offset_from(self.start).unwrap_or(0)
+    }
+
+    pub fn contains(&self, addr: VirtAddr) -> bool {
+        (self.start..self.end).contains(&addr)
+    }
+
+    fn is_guard_page(&self) -> bool {
+        self.path.is_none()
+            && !self.permissions.read
+            && !self.permissions.write
+            && !self.permissions.execute
+    }
+
+    // Thread stacks are plain anonymous mappings to the kernel. They are recognized by the guard
+    // page right below them, and matched to threads by their stack pointers.
+    fn kind(
+        &self,
+        below: Option<&MemoryRegion>,
+        stack_pointers: &BTreeMap<Pid, VirtAddr>,
+    ) -> RegionKind {
+        let thread = stack_pointers
+            .iter()
+            .find(|(_, stack_pointer)| self.contains(**stack_pointer))
+            .map(|(tid, _)| *tid);
+        let guarded = below.is_some_and(|below| below.end == self.start && below.is_guard_page());
+
+        match self.path.as_deref() {
+            Some("[stack]") => RegionKind::Stack(thread),
+            None if guarded && self.permissions.write && thread.is_some() => {
+                RegionKind::Stack(thread)
+            }
+            Some("[heap]") => RegionKind::Heap,
+            Some("[vdso]") => RegionKind::Vdso,
+            Some(path) if path.starts_with('/') && self.permissions.execute => {
+                RegionKind::Text(path.to_string())
+            }
+            Some(path) if path.starts_with('/') => RegionKind::Data(path.to_string()),
+            Some(name) => RegionKind::Special(name.to_string()),
+            None => RegionKind::Anonymous,
+        }
+    }
+}
+
+impl FromStr for MemoryMap {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let regions = s
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(MemoryRegion::from_str)
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        Ok(Self { regions })
+    }
+}
+
+impl MemoryMap {
+    pub fn read_from_procfs(pid: Pid) -> anyhow::Result<Self> {
+        fs::read_to_string(format!("/proc/{}/maps", pid))
+            .map_err(|err| anyhow!("unable to read memory map of {}: {}", pid, err))?
+            .parse()
+    }
+
+    pub fn regions(&self) -> &[MemoryRegion] {
+        &self.regions
+    }
+
+    pub fn region_containing(&self, addr: VirtAddr) -> Option<&MemoryRegion> {
+        self.regions.iter().find(|region| region.contains(addr))
+    }
+
+    // The regions `len` bytes at `start` are in, None if any of them isn't mapped.
+    pub fn regions_spanning(&self, start: VirtAddr, len: u64) -> Option<Vec<&MemoryRegion>> {
+        let end = start + len;
+        let mut regions = Vec::new();
+        let mut at = start;
+        while at < end {
+            let region = self.region_containing(at)?;
+            regions.push(region);
+            at = region.end;
+        }
+        Some(regions)
+    }
+
+    // Every region with what it holds.
+    pub fn classify(
+        &self,
+        stack_pointers: &BTreeMap<Pid, VirtAddr>,
+    ) -> Vec<(&MemoryRegion, RegionKind)> {
+        self.regions
+            .iter()
+            .enumerate()
+            .map(|(index, region)| {
+                let below = index.checked_sub(1).map(|below| &self.regions[below]);
+                (region, region.kind(below, stack_pointers))
+            })
+            .collect()
+    }
+
+    pub fn kind_of(
+        &self,
+        addr: VirtAddr,
+        stack_pointers: &BTreeMap<Pid, VirtAddr>,
+    ) -> Option<RegionKind> {
+        let index = self
+            .regions
+            .iter()
+            .position(|region| region.contains(addr))?;
+        let below = index.checked_sub(1).map(|below| &self.regions[below]);
+        Some(self.regions[index].kind(below, stack_pointers))
+    }
+
+    // The lowest address the file at `path` is mapped at.
+    pub fn module_base(&self, path: &str) -> Option<VirtAddr> {
+        self.regions
+            .iter()
+            .filter(|region| region.path.as_deref() == Some(path))
+            .map(|region| region.start)
+            .min()
+    }
+
+    // Every mapped file with the address it's mapped at. Anonymous and pseudo mappings like
+    // [heap] or [vdso] are left out.
+    pub fn modules(&self) -> BTreeMap<&str, VirtAddr> {
+        let mut modules = BTreeMap::new();
+        for region in &self.regions {
+            if let Some(path) = region.path.as_deref().filter(|path| path.starts_with('/')) {
+                modules
+                    .entry(path)
+                    .and_modify(|base: &mut VirtAddr| *base = (*base).min(region.start))
+                    .or_insert(region.start);
+            }
+        }
+        modules
+    }
+}
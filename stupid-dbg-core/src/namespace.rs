@@ -0,0 +1,113 @@
+use std::{
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::anyhow;
+use nix::unistd::Pid;
+use tracing::debug;
+
+use crate::{aux::box_err, memory_map::MemoryMap};
+
+// How the files a process sees can be reached from the debugger. A process in another mount
+// namespace, e.g. in a container, has paths like /usr/lib/libc.so.6 that mean something else
+// here, those are read through /proc/<pid>/root instead.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcessRoot {
+    pid: Pid,
+    foreign: bool,
+}
+
+impl ProcessRoot {
+    // Namespaces that can't be compared are taken to be the debugger's own.
+    pub fn of(pid: Pid) -> Self {
+        let foreign = match (
+            fs::read_link("/proc/self/ns/mnt"),
+            fs::read_link(format!("/proc/{}/ns/mnt", pid)),
+        ) {
+            (Ok(own), Ok(theirs)) => own != theirs,
+            (Err(err), _) | (_, Err(err)) => {
+                debug!(error = box_err(err), pid = %pid, "unable to compare mount namespaces");
+                false
+            }
+        };
+
+        Self { pid, foreign }
+    }
+
+    pub fn pid(&self) -> Pid {
+        self.pid
+    }
+
+    // Whether the process lives in another mount namespace than the debugger.
+    pub fn is_foreign(&self) -> bool {
+        self.foreign
+    }
+
+    // Where a path of the process is from the debugger's point of view.
+    pub fn host_path<P: AsRef<Path>>(&self, path: P) -> PathBuf {
+        if self.foreign {
+            translate(
+                Path::new(&format!("/proc/{}/root", self.pid)),
+                path.as_ref(),
+            )
+        } else {
+            path.as_ref().to_path_buf()
+        }
+    }
+
+    // A path the file mapped as `module` can be read at. Files that are gone from the process'
+    // root, like deleted or overmounted ones, are still there in map_files as long as they stay
+    // mapped.
+    pub fn module_path(&self, memory_map: &MemoryMap, module: &str) -> PathBuf {
+        let path = self.host_path(module);
+        if !self.foreign || path.exists() {
+            return path;
+        }
+
+        memory_map
+            .regions()
+            .iter()
+            .find(|region| region.path.as_deref() == Some(module))
+            .map(|region| {
+                PathBuf::from(format!(
+                    "/proc/{}/map_files/{:x}-{:x}",
+                    self.pid,
+                    region.start.as_u64(),
+                    region.end.as_u64()
+                ))
+            })
+            .unwrap_or(path)
+    }
+}
+
+// `path` as if `root` were /, `..` can't climb out of it.
+pub fn translate(root: &Path, path: &Path) -> PathBuf {
+    let mut components = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(component) => components.push(component),
+            Component::ParentDir => {
+                components.pop();
+            }
+            Component::RootDir | Component::CurDir | Component::Prefix(_) => {}
+        }
+    }
+    components
+        .into_iter()
+        .fold(root.to_path_buf(), |path, component| path.join(component))
+}
+
+// The pid of a process inside its innermost pid namespace, as its own getpid() returns it.
+pub fn namespace_pid(pid: Pid) -> anyhow::Result<Pid> {
+    let status = fs::read_to_string(format!("/proc/{}/status", pid))?;
+    let line = status
+        .lines()
+        .find_map(|line| line.strip_prefix("NSpid:"))
+        .ok_or(anyhow!("no NSpid in status of {}", pid))?;
+    let innermost = line
+        .split_whitespace()
+        .last()
+        .ok_or(anyhow!("empty NSpid in status of {}", pid))?;
+    Ok(Pid::from_raw(innermost.parse()?))
+}
@@ -0,0 +1,57 @@
+// Plain data made from the bytes of debuggee memory, in the byte order of the debuggee, which
+// is the one of the debugger too. Pointers depend on the debuggee, `Debuggee::read_pointer`
+// reads them.
+pub trait FromBytes: Sized {
+    const SIZE: usize;
+
+    // None unless `bytes` is SIZE long.
+    fn from_bytes(bytes: &[u8]) -> Option<Self>;
+}
+
+// The other way around, written to debuggee memory as is.
+pub trait ToBytes {
+    fn to_bytes(&self) -> Vec<u8>;
+}
+
+macro_rules! impl_plain_data {
+    ($($ty:ty),*) => {
+        $(
+            impl FromBytes for $ty {
+                const SIZE: usize = size_of::<$ty>();
+
+                fn from_bytes(bytes: &[u8]) -> Option<Self> {
+                    Some(<$ty>::from_ne_bytes(bytes.try_into().ok()?))
+                }
+            }
+
+            impl ToBytes for $ty {
+                fn to_bytes(&self) -> Vec<u8> {
+                    self.to_ne_bytes().to_vec()
+                }
+            }
+        )*
+    };
+}
+
+impl_plain_data!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128, f32, f64);
+
+impl<T: FromBytes, const N: usize> FromBytes for [T; N] {
+    const SIZE: usize = T::SIZE * N;
+
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != Self::SIZE {
+            return None;
+        }
+        let elements = bytes
+            .chunks_exact(T::SIZE)
+            .map(T::from_bytes)
+            .collect::<Option<Vec<_>>>()?;
+        elements.try_into().ok()
+    }
+}
+
+impl<T: ToBytes, const N: usize> ToBytes for [T; N] {
+    fn to_bytes(&self) -> Vec<u8> {
+        self.iter().flat_map(ToBytes::to_bytes).collect()
+    }
+}
@@ -0,0 +1,401 @@
+use std::fmt;
+
+use anyhow::anyhow;
+use tracing::warn;
+
+use crate::{aux::box_err, expression::EvalContext, memory_map::MemoryMap};
+
+// Longer strings and containers are cut off, like `print elements` in gdb.
+const MAX_ELEMENTS: usize = 200;
+const MAX_STRING_LENGTH: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StdLib {
+    // GNU libstdc++ with the C++11 ABI
+    LibStdCxx,
+    // LLVM libc++
+    LibCxx,
+}
+
+impl StdLib {
+    // Picks the standard library mapped into the process, None if there's neither or both.
+    pub fn detect(memory_map: &MemoryMap) -> Option<Self> {
+        let modules = memory_map.modules();
+        let mapped = |name: &str| {
+            modules.keys().any(|path| {
+                path.rsplit('/')
+                    .next()
+                    .is_some_and(|file| file.starts_with(name))
+            })
+        };
+
+        match (mapped("libstdc++.so"), mapped("libc++.so")) {
+            (true, false) => Some(StdLib::LibStdCxx),
+            (false, true) => Some(StdLib::LibCxx),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for StdLib {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StdLib::LibStdCxx => write!(f, "libstdc++"),
+            StdLib::LibCxx => write!(f, "libc++"),
+        }
+    }
+}
+
+// Without debug info the element size has to be given for containers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StdType {
+    String,
+    Vector { element_size: usize },
+    // element_size covers the whole key-value pair
+    Map { element_size: usize },
+    UniquePtr,
+    SharedPtr,
+}
+
+impl StdType {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            StdType::String => "std::string",
+            StdType::Vector { .. } => "std::vector",
+            StdType::Map { .. } => "std::map",
+            StdType::UniquePtr => "std::unique_ptr",
+            StdType::SharedPtr => "std::shared_ptr",
+        }
+    }
+
+    // Size of the object itself, for dumping it raw.
+    fn object_size(&self, lib: StdLib, word: usize) -> usize {
+        match (self, lib) {
+            (StdType::String, StdLib::LibStdCxx) => 2 * word + 16,
+            (StdType::String, StdLib::LibCxx) => 3 * word,
+            (StdType::Vector { .. }, _) => 3 * word,
+            (StdType::Map { .. }, StdLib::LibStdCxx) => 6 * word,
+            (StdType::Map { .. }, StdLib::LibCxx) => 3 * word,
+            (StdType::UniquePtr, _) => word,
+            (StdType::SharedPtr, _) => 2 * word,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PrettyValue {
+    String(String),
+    Vector {
+        elements: Vec<Vec<u8>>,
+        length: usize,
+        capacity: usize,
+    },
+    Map {
+        elements: Vec<Vec<u8>>,
+        length: usize,
+    },
+    UniquePtr(u64),
+    SharedPtr {
+        pointer: u64,
+        use_count: u64,
+        weak_count: u64,
+    },
+    // the words of an object that couldn't be decoded
+    Raw(Vec<u64>),
+}
+
+impl fmt::Display for PrettyValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let elements = |elements: &[Vec<u8>], length: usize| {
+            let mut list = elements
+                .iter()
+                .map(|element| format_element(element))
+                .collect::<Vec<_>>();
+            if length > elements.len() {
+                list.push("...".to_string());
+            }
+            list.join(", ")
+        };
+
+        match self {
+            PrettyValue::String(s) => write!(f, "\"{}\"", s.escape_debug()),
+            PrettyValue::Vector {
+                elements: items,
+                length,
+                capacity,
+            } => write!(
+                f,
+                "std::vector of length {}, capacity {} = {{{}}}",
+                length,
+                capacity,
+                elements(items, *length)
+            ),
+            PrettyValue::Map {
+                elements: items,
+                length,
+            } => write!(
+                f,
+                "std::map with {} elements = {{{}}}",
+                length,
+                elements(items, *length)
+            ),
+            PrettyValue::UniquePtr(0) => write!(f, "std::unique_ptr = nullptr"),
+            PrettyValue::UniquePtr(pointer) => write!(f, "std::unique_ptr = {:#x}", pointer),
+            PrettyValue::SharedPtr { pointer: 0, .. } => write!(f, "std::shared_ptr = nullptr"),
+            PrettyValue::SharedPtr {
+                pointer,
+                use_count,
+                weak_count,
+            } => write!(
+                f,
+                "std::shared_ptr = {:#x} (use count {}, weak count {})",
+                pointer, use_count, weak_count
+            ),
+            PrettyValue::Raw(words) => write!(
+                f,
+                "{{{}}}",
+                words
+                    .iter()
+                    .map(|word| format!("{:#x}", word))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        }
+    }
+}
+
+// Elements of integer sizes show as little endian integers, anything else as bytes.
+fn format_element(bytes: &[u8]) -> String {
+    match bytes.len() {
+        1 | 2 | 4 | 8 => {
+            let mut word = [0u8; 8];
+            word[..bytes.len()].copy_from_slice(bytes);
+            format!("{:#x}", u64::from_le_bytes(word))
+        }
+        _ => bytes
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<Vec<_>>()
+            .join(" "),
+    }
+}
+
+struct Reader<'a, C: ?Sized> {
+    context: &'a C,
+    word: usize,
+}
+
+impl<C: EvalContext + ?Sized> Reader<'_, C> {
+    fn word(&self, address: u64) -> anyhow::Result<u64> {
+        let bytes = self.context.read_memory(address, self.word)?;
+        let mut word = [0u8; 8];
+        word[..self.word].copy_from_slice(&bytes);
+        Ok(u64::from_le_bytes(word))
+    }
+
+    fn words(&self, address: u64, count: usize) -> anyhow::Result<Vec<u64>> {
+        (0..count)
+            .map(|index| self.word(address + (index * self.word) as u64))
+            .collect()
+    }
+
+    fn string(&self, address: u64, length: u64) -> anyhow::Result<String> {
+        let bytes = self
+            .context
+            .read_memory(address, (length as usize).min(MAX_STRING_LENGTH))?;
+        Ok(String::from_utf8_lossy(&bytes).into_owned())
+    }
+
+    fn elements(&self, addresses: &[u64], element_size: usize) -> anyhow::Result<Vec<Vec<u8>>> {
+        addresses
+            .iter()
+            .map(|address| self.context.read_memory(*address, element_size))
+            .collect()
+    }
+}
+
+// Decodes the object at `address`. Fails if the memory doesn't look like the given type.
+pub fn pretty_print<C: EvalContext + ?Sized>(
+    context: &C,
+    lib: StdLib,
+    ty: StdType,
+    address: u64,
+) -> anyhow::Result<PrettyValue> {
+    let reader = Reader {
+        context,
+        word: context.pointer_size(),
+    };
+    let word = reader.word as u64;
+
+    match (ty, lib) {
+        // pointer, length, then either the characters or the capacity
+        (StdType::String, StdLib::LibStdCxx) => {
+            let [data, length] = reader.words(address, 2)?[..] else {
+                unreachable!()
+            };
+            let capacity = if data == address + 2 * word {
+                15
+            } else {
+                reader.word(address + 2 * word)?
+            };
+            if length > capacity {
+                Err(anyhow!("length {} exceeds capacity {}", length, capacity))?;
+            }
+            Ok(PrettyValue::String(reader.string(data, length)?))
+        }
+        // the lowest bit of the first byte tells long strings, which are capacity, length and
+        // pointer, from short ones which keep the length shifted by one in the first byte
+        (StdType::String, StdLib::LibCxx) => {
+            let first = context.read_memory(address, 1)?[0];
+            if first & 1 == 0 {
+                let length = (first >> 1) as u64;
+                if length > 3 * word - 2 {
+                    Err(anyhow!("short string of length {}", length))?;
+                }
+                return Ok(PrettyValue::String(reader.string(address + 1, length)?));
+            }
+            let [capacity, length, data] = reader.words(address, 3)?[..] else {
+                unreachable!()
+            };
+            if length > capacity & !1 {
+                Err(anyhow!(
+                    "length {} exceeds capacity {}",
+                    length,
+                    capacity & !1
+                ))?;
+            }
+            Ok(PrettyValue::String(reader.string(data, length)?))
+        }
+        // begin, end and end of storage in both
+        (StdType::Vector { element_size }, _) => {
+            if element_size == 0 {
+                Err(anyhow!("element size must not be zero"))?;
+            }
+            let [begin, end, storage_end] = reader.words(address, 3)?[..] else {
+                unreachable!()
+            };
+            if !(begin <= end && end <= storage_end) {
+                Err(anyhow!("begin, end and capacity are out of order"))?;
+            }
+            let length = ((end - begin) / element_size as u64) as usize;
+            let capacity = ((storage_end - begin) / element_size as u64) as usize;
+            let addresses = (0..length.min(MAX_ELEMENTS))
+                .map(|index| begin + (index * element_size) as u64)
+                .collect::<Vec<_>>();
+            Ok(PrettyValue::Vector {
+                elements: reader.elements(&addresses, element_size)?,
+                length,
+                capacity,
+            })
+        }
+        // comparator, header node of color, parent (the root), leftmost and rightmost, then the
+        // count. Nodes are color, parent, left and right followed by the value.
+        (StdType::Map { element_size }, StdLib::LibStdCxx) => {
+            let header = address + word;
+            let length = reader.word(header + 4 * word)? as usize;
+            let root = reader.word(header + word)?;
+            let nodes = in_order(&reader, root, 2 * word, 3 * word, length)?;
+            let addresses = nodes.iter().map(|node| node + 4 * word).collect::<Vec<_>>();
+            Ok(PrettyValue::Map {
+                elements: reader.elements(&addresses, element_size)?,
+                length,
+            })
+        }
+        // leftmost node, the end node whose left child is the root, then the count. Nodes are
+        // left, right, parent and color followed by the value.
+        (StdType::Map { element_size }, StdLib::LibCxx) => {
+            let root = reader.word(address + word)?;
+            let length = reader.word(address + 2 * word)? as usize;
+            let nodes = in_order(&reader, root, 0, word, length)?;
+            let addresses = nodes.iter().map(|node| node + 4 * word).collect::<Vec<_>>();
+            Ok(PrettyValue::Map {
+                elements: reader.elements(&addresses, element_size)?,
+                length,
+            })
+        }
+        // with the default deleter it's just the pointer
+        (StdType::UniquePtr, _) => Ok(PrettyValue::UniquePtr(reader.word(address)?)),
+        // the pointer and the control block, whose counts follow its vtable pointer. libc++
+        // counts owners beyond the first.
+        (StdType::SharedPtr, lib) => {
+            let [pointer, control] = reader.words(address, 2)?[..] else {
+                unreachable!()
+            };
+            if control == 0 {
+                return Ok(PrettyValue::SharedPtr {
+                    pointer,
+                    use_count: 0,
+                    weak_count: 0,
+                });
+            }
+            let (use_count, weak_count) = match lib {
+                StdLib::LibStdCxx => {
+                    let counts = context.read_memory(control + word, 8)?;
+                    (
+                        u32::from_le_bytes(counts[..4].try_into().unwrap()) as u64,
+                        u32::from_le_bytes(counts[4..].try_into().unwrap()) as u64,
+                    )
+                }
+                StdLib::LibCxx => {
+                    let [shared, weak] = reader.words(control + word, 2)?[..] else {
+                        unreachable!()
+                    };
+                    (shared.wrapping_add(1), weak.wrapping_add(1))
+                }
+            };
+            Ok(PrettyValue::SharedPtr {
+                pointer,
+                use_count,
+                weak_count,
+            })
+        }
+    }
+}
+
+// Like pretty_print, but shows the words of the object when it can't be decoded.
+pub fn pretty_print_or_raw<C: EvalContext + ?Sized>(
+    context: &C,
+    lib: StdLib,
+    ty: StdType,
+    address: u64,
+) -> anyhow::Result<PrettyValue> {
+    pretty_print(context, lib, ty, address).or_else(|err| {
+        warn!(
+            error = box_err(err),
+            "unable to decode {:#x} as {:?} of {}, showing raw members", address, ty, lib
+        );
+        let word = context.pointer_size();
+        let reader = Reader { context, word };
+        Ok(PrettyValue::Raw(
+            reader.words(address, ty.object_size(lib, word) / word)?,
+        ))
+    })
+}
+
+// Walks a binary tree in order without parent pointers, stopping after `limit` nodes so a
+// corrupted tree can't loop forever.
+fn in_order<C: EvalContext + ?Sized>(
+    reader: &Reader<C>,
+    root: u64,
+    left_offset: u64,
+    right_offset: u64,
+    limit: usize,
+) -> anyhow::Result<Vec<u64>> {
+    let limit = limit.min(MAX_ELEMENTS);
+    let mut nodes = Vec::new();
+    let mut stack = Vec::new();
+    let mut node = root;
+    while nodes.len() < limit && (node != 0 || !stack.is_empty()) {
+        while node != 0 {
+            if stack.len() > limit {
+                Err(anyhow!("tree is deeper than it has elements"))?;
+            }
+            stack.push(node);
+            node = reader.word(node + left_offset)?;
+        }
+        let next = stack.pop().unwrap();
+        nodes.push(next);
+        node = reader.word(next + right_offset)?;
+    }
+    Ok(nodes)
+}
@@ -0,0 +1,91 @@
+use std::{collections::BTreeMap, fmt};
+
+use nix::unistd::Pid;
+
+use crate::{
+    format::{self, MIN_STRING_RUN},
+    memory_map::{MemoryMap, RegionKind},
+    symbols::SymbolTable,
+    virt_addr::VirtAddr,
+};
+
+// How much of a string a pointer points to is shown.
+const MAX_STRING_PREVIEW: usize = 64;
+
+// Where a value that looks like a pointer points, e.g. `text of /usr/lib/libc.so.6 <malloc+0x10>`
+// or `data of /usr/lib/libfoo.so "hello"`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Provenance {
+    pub region: RegionKind,
+    // `name+0x10` of the symbol it points into
+    pub symbol: Option<String>,
+    // the string it points to, for pointers into data
+    pub string: Option<String>,
+}
+
+impl Provenance {
+    // None for unmapped addresses, which is what most integers are. `read` reads up to as many
+    // bytes as asked for from the debuggee.
+    pub fn of<F>(
+        address: VirtAddr,
+        memory_map: &MemoryMap,
+        stack_pointers: &BTreeMap<Pid, VirtAddr>,
+        symbols: &SymbolTable,
+        read: F,
+    ) -> Option<Self>
+    where
+        F: FnOnce(VirtAddr, usize) -> anyhow::Result<Vec<u8>>,
+    {
+        let region = memory_map.kind_of(address, stack_pointers)?;
+        let symbol = symbols
+            .lookup(address)
+            .map(|(symbol, offset)| match offset {
+                0 => symbol.display_name().to_string(),
+                offset => format!("{}+{:#x}", symbol.display_name(), offset),
+            });
+        let string = match region {
+            RegionKind::Text(_) | RegionKind::Vdso => None,
+            _ => {
+                // reads stay within the region, the next one may be unreadable
+                let end = memory_map.region_containing(address)?.end;
+                let len = end.offset_from(address)?.min(MAX_STRING_PREVIEW as u64);
+                read(address, len as usize)
+                    .ok()
+                    .and_then(|bytes| string_at_start(&bytes))
+            }
+        };
+
+        Some(Self {
+            region,
+            symbol,
+            string,
+        })
+    }
+}
+
+// The printable run `bytes` start with, if it's long enough and ends where the bytes or the
+// string do.
+fn string_at_start(bytes: &[u8]) -> Option<String> {
+    let run = format::printable_runs(bytes, MIN_STRING_RUN)
+        .into_iter()
+        .next()
+        .filter(|run| run.start == 0)?;
+    match bytes.get(run.end) {
+        None => Some(format!("{}...", format::format_c_string(&bytes[run]))),
+        Some(0) => Some(format::format_c_string(&bytes[run])),
+        Some(_) => None,
+    }
+}
+
+impl fmt::Display for Provenance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.region)?;
+        if let Some(symbol) = &self.symbol {
+            write!(f, " <{}>", symbol)?;
+        }
+        if let Some(string) = &self.string {
+            write!(f, " {}", string)?;
+        }
+        Ok(())
+    }
+}
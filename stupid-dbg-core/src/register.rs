@@ -0,0 +1,1084 @@
+use std::{
+    arch::x86_64::__cpuid_count, cmp::PartialEq, collections::BTreeMap, fmt::Display, iter,
+    mem::MaybeUninit, ops::Range,
+};
+
+use anyhow::anyhow;
+use f128::f128;
+use helper_proc_macros::define_amd64_registers;
+use lazy_static::lazy_static;
+use nix::unistd::Pid;
+use tracing::debug;
+
+use crate::{
+    arch,
+    aux::{as_u8_slice, read_any_from_u8_pointer},
+    fp_control,
+    tracer::{PtraceTracer, Tracer},
+};
+
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RegisterKind {
+    GeneralPurpose,
+    SubGeneralPurpose,
+    FloatingPoint,
+    Debug,
+    // vector registers kept in the XSAVE area only
+    ExtendedState,
+}
+
+// Registers shown together
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RegisterGroup {
+    GeneralPurpose,
+    FloatingPoint,
+    Vector,
+    Debug,
+}
+
+// How the bytes of a vector register are shown: as lanes of a number type, or all of them as
+// one hex number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LaneFormat {
+    F32,
+    F64,
+    U8,
+    U16,
+    U32,
+    U64,
+    Hex,
+}
+
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RegisterRepr {
+    UInt,
+    LongDouble,
+    Vector,
+}
+
+define_amd64_registers! {
+    // gpr_64(<name>, <dwarf_id>)
+    gpr_64(rax, 0);
+    gpr_64(rdx, 1);
+    gpr_64(rcx, 2);
+    gpr_64(rbx, 3);
+    gpr_64(rsi, 4);
+    gpr_64(rdi, 5);
+    gpr_64(rbp, 6);
+    gpr_64(rsp, 7);
+    gpr_64(r8, 8);
+    gpr_64(r9, 9);
+    gpr_64(r10, 10);
+    gpr_64(r11, 11);
+    gpr_64(r12, 12);
+    gpr_64(r13, 13);
+    gpr_64(r14, 14);
+    gpr_64(r15, 15);
+    gpr_64(rip, 16);
+    gpr_64(eflags, 49);
+    gpr_64(es, 50);
+    gpr_64(cs, 51);
+    gpr_64(ss, 52);
+    gpr_64(ds, 53);
+    gpr_64(fs, 54);
+    gpr_64(gs, 55);
+    // segment bases for thread local storage
+    gpr_64(fs_base, 58);
+    gpr_64(gs_base, 59);
+    gpr_64(orig_rax);
+
+    // gpr_32(<name>, <base_name>)
+    gpr_32(eax, rax);
+    gpr_32(ebx, rbx);
+    gpr_32(ecx, rcx);
+    gpr_32(edx, rdx);
+    gpr_32(esi, rsi);
+    gpr_32(edi, rdi);
+    gpr_32(ebp, rbp);
+    gpr_32(esp, rsp);
+    gpr_32(r8d, r8);
+    gpr_32(r9d, r9);
+    gpr_32(r10d, r10);
+    gpr_32(r11d, r11);
+    gpr_32(r12d, r12);
+    gpr_32(r13d, r13);
+    gpr_32(r14d, r14);
+    gpr_32(r15d, r15);
+
+    // gpr_16(<name>, <base_name>)
+    gpr_16(ax, rax);
+    gpr_16(bx, rbx);
+    gpr_16(cx, rcx);
+    gpr_16(dx, rdx);
+    gpr_16(si, rsi);
+    gpr_16(di, rdi);
+    gpr_16(bp, rbp);
+    gpr_16(sp, rsp);
+    gpr_16(r8w, r8);
+    gpr_16(r9w, r9);
+    gpr_16(r10w, r10);
+    gpr_16(r11w, r11);
+    gpr_16(r12w, r12);
+    gpr_16(r13w, r13);
+    gpr_16(r14w, r14);
+    gpr_16(r15w, r15);
+
+    // gpr_8l(<name>, <base_name>)
+    gpr_8l(al, rax);
+    gpr_8l(bl, rbx);
+    gpr_8l(cl, rcx);
+    gpr_8l(dl, rdx);
+    gpr_8l(sil, rsi);
+    gpr_8l(dil, rdi);
+    gpr_8l(bpl, rbp);
+    gpr_8l(spl, rsp);
+    gpr_8l(r8b, r8);
+    gpr_8l(r9b, r9);
+    gpr_8l(r10b, r10);
+    gpr_8l(r11b, r11);
+    gpr_8l(r12b, r12);
+    gpr_8l(r13b, r13);
+    gpr_8l(r14b, r14);
+    gpr_8l(r15b, r15);
+
+    // gpr_8h(<name>, <base_name>)
+    gpr_8h(ah, rax);
+    gpr_8h(bh, rbx);
+    gpr_8h(ch, rcx);
+    gpr_8h(dd, rdx);
+
+    // fpr(<name>, <field_name_in_user_fpregs_struct>,<dwarf_id>)
+    fpr(fcw, cwd, 65);
+    fpr(fsw, swd, 66);
+    fpr(ftw, ftw);
+    fpr(fop, fop);
+    fpr(frip, rip);
+    fpr(frdp, rdp);
+    fpr(mxcsr, mxcsr, 64);
+    fpr(mxcsrmask, mxcr_mask);
+
+    // fp_st(<id>)
+    fp_st(0);
+    fp_st(1);
+    fp_st(2);
+    fp_st(3);
+    fp_st(4);
+    fp_st(5);
+    fp_st(6);
+    fp_st(7);
+
+    // fp_mm(<id>)
+    fp_mm(0);
+    fp_mm(1);
+    fp_mm(2);
+    fp_mm(3);
+    fp_mm(4);
+    fp_mm(5);
+    fp_mm(6);
+    fp_mm(7);
+
+    // fp_xmm(<id>)
+    fp_xmm(0);
+    fp_xmm(1);
+    fp_xmm(2);
+    fp_xmm(3);
+    fp_xmm(4);
+    fp_xmm(5);
+    fp_xmm(6);
+    fp_xmm(7);
+    fp_xmm(8);
+    fp_xmm(9);
+    fp_xmm(10);
+    fp_xmm(11);
+    fp_xmm(12);
+    fp_xmm(13);
+    fp_xmm(14);
+    fp_xmm(15);
+
+    // xstate_xmm(<id>), the ones past xmm15 come with AVX-512
+    xstate_xmm(16);
+    xstate_xmm(17);
+    xstate_xmm(18);
+    xstate_xmm(19);
+    xstate_xmm(20);
+    xstate_xmm(21);
+    xstate_xmm(22);
+    xstate_xmm(23);
+    xstate_xmm(24);
+    xstate_xmm(25);
+    xstate_xmm(26);
+    xstate_xmm(27);
+    xstate_xmm(28);
+    xstate_xmm(29);
+    xstate_xmm(30);
+    xstate_xmm(31);
+
+    // xstate_ymm(<id>)
+    xstate_ymm(0);
+    xstate_ymm(1);
+    xstate_ymm(2);
+    xstate_ymm(3);
+    xstate_ymm(4);
+    xstate_ymm(5);
+    xstate_ymm(6);
+    xstate_ymm(7);
+    xstate_ymm(8);
+    xstate_ymm(9);
+    xstate_ymm(10);
+    xstate_ymm(11);
+    xstate_ymm(12);
+    xstate_ymm(13);
+    xstate_ymm(14);
+    xstate_ymm(15);
+    xstate_ymm(16);
+    xstate_ymm(17);
+    xstate_ymm(18);
+    xstate_ymm(19);
+    xstate_ymm(20);
+    xstate_ymm(21);
+    xstate_ymm(22);
+    xstate_ymm(23);
+    xstate_ymm(24);
+    xstate_ymm(25);
+    xstate_ymm(26);
+    xstate_ymm(27);
+    xstate_ymm(28);
+    xstate_ymm(29);
+    xstate_ymm(30);
+    xstate_ymm(31);
+
+    // xstate_zmm(<id>)
+    xstate_zmm(0);
+    xstate_zmm(1);
+    xstate_zmm(2);
+    xstate_zmm(3);
+    xstate_zmm(4);
+    xstate_zmm(5);
+    xstate_zmm(6);
+    xstate_zmm(7);
+    xstate_zmm(8);
+    xstate_zmm(9);
+    xstate_zmm(10);
+    xstate_zmm(11);
+    xstate_zmm(12);
+    xstate_zmm(13);
+    xstate_zmm(14);
+    xstate_zmm(15);
+    xstate_zmm(16);
+    xstate_zmm(17);
+    xstate_zmm(18);
+    xstate_zmm(19);
+    xstate_zmm(20);
+    xstate_zmm(21);
+    xstate_zmm(22);
+    xstate_zmm(23);
+    xstate_zmm(24);
+    xstate_zmm(25);
+    xstate_zmm(26);
+    xstate_zmm(27);
+    xstate_zmm(28);
+    xstate_zmm(29);
+    xstate_zmm(30);
+    xstate_zmm(31);
+
+    // dr(<id>)
+    dr(0);
+    dr(1);
+    dr(2);
+    dr(3);
+    dr(4);
+    dr(5);
+    dr(6);
+    dr(7);
+}
+
+lazy_static! {
+    static ref NAME_TO_REGISTER_MAP: BTreeMap<&'static str, Register> = Register::all_registers()
+        .into_iter()
+        .map(|reg| (reg.name(), reg))
+        .collect();
+    static ref DWARF_ID_TO_REGISTER_MAP: BTreeMap<usize, Register> = Register::all_registers()
+        .into_iter()
+        .filter_map(|reg| reg.dwarf_id().map(|id| (id, reg)))
+        .collect();
+}
+
+impl Register {
+    pub fn lookup_by_name(name: &str) -> Option<Register> {
+        NAME_TO_REGISTER_MAP.get(name).copied()
+    }
+
+    pub fn lookup_by_dwarf_id(dwarf_id: usize) -> Option<Register> {
+        DWARF_ID_TO_REGISTER_MAP.get(&dwarf_id).copied()
+    }
+
+    // None for the parts of general purpose registers, e.g. eax, they are only shown by name.
+    pub fn group(&self) -> Option<RegisterGroup> {
+        match (self.kind(), self.repr()) {
+            (RegisterKind::GeneralPurpose, _) => Some(RegisterGroup::GeneralPurpose),
+            (RegisterKind::SubGeneralPurpose, _) => None,
+            (RegisterKind::Debug, _) => Some(RegisterGroup::Debug),
+            (_, RegisterRepr::Vector) => Some(RegisterGroup::Vector),
+            (_, _) => Some(RegisterGroup::FloatingPoint),
+        }
+    }
+
+    // The named fields of fcw, fsw, ftw and mxcsr, e.g. the rounding mode, None for the others.
+    pub fn decode(&self, value: RegisterValue) -> Option<fp_control::Decoded> {
+        fp_control::decode(self.name(), value.as_u64()?)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum RegisterValue {
+    U8(u8),
+    U16(u16),
+    U32(u32),
+    U64(u64),
+    I8(i8),
+    I16(i16),
+    I32(i32),
+    I64(i64),
+    F128(f128),
+    Byte64([u8; 8]),
+    Byte128([u8; 16]),
+    Byte256([u8; 32]),
+    Byte512([u8; 64]),
+}
+
+impl Display for RegisterValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fn pp_u8_vec(vec: &[u8]) -> String {
+            let inner: String = vec
+                .iter()
+                .map(|x| format!("{:#02x}", x))
+                .intersperse(",".to_string())
+                .collect();
+            format!("[{}]", inner)
+        }
+
+        match self {
+            RegisterValue::U8(x) => write!(f, "{:#04x}", x),
+            RegisterValue::U16(x) => write!(f, "{:#06x}", x),
+            RegisterValue::U32(x) => write!(f, "{:#010x}", x),
+            RegisterValue::U64(x) => write!(f, "{:#018x}", x),
+            RegisterValue::I8(x) => write!(f, "{:#04x}", x),
+            RegisterValue::I16(x) => write!(f, "{:#06x}", x),
+            RegisterValue::I32(x) => write!(f, "{:#010x}", x),
+            RegisterValue::I64(x) => write!(f, "{:#018x}", x),
+            RegisterValue::F128(x) => write!(f, "{}", x),
+            RegisterValue::Byte64(x) => write!(f, "{}", pp_u8_vec(x)),
+            RegisterValue::Byte128(x) => write!(f, "{}", pp_u8_vec(x)),
+            RegisterValue::Byte256(x) => write!(f, "{}", pp_u8_vec(x)),
+            RegisterValue::Byte512(x) => write!(f, "{}", pp_u8_vec(x)),
+        }
+    }
+}
+
+impl RegisterValue {
+    pub fn byte_width(&self) -> usize {
+        match self {
+            RegisterValue::U8(x) => size_of_val(x),
+            RegisterValue::U16(x) => size_of_val(x),
+            RegisterValue::U32(x) => size_of_val(x),
+            RegisterValue::U64(x) => size_of_val(x),
+            RegisterValue::I8(x) => size_of_val(x),
+            RegisterValue::I16(x) => size_of_val(x),
+            RegisterValue::I32(x) => size_of_val(x),
+            RegisterValue::I64(x) => size_of_val(x),
+            RegisterValue::F128(x) => size_of_val(x),
+            RegisterValue::Byte64(x) => size_of_val(x),
+            RegisterValue::Byte128(x) => size_of_val(x),
+            RegisterValue::Byte256(x) => size_of_val(x),
+            RegisterValue::Byte512(x) => size_of_val(x),
+        }
+    }
+
+    unsafe fn as_u8_ptr(&self) -> *const u8 {
+        match self {
+            RegisterValue::U8(x) => x,
+            RegisterValue::U16(x) => (x as *const u16).cast(),
+            RegisterValue::U32(x) => (x as *const u32).cast(),
+            RegisterValue::U64(x) => (x as *const u64).cast(),
+            RegisterValue::I8(x) => (x as *const i8).cast(),
+            RegisterValue::I16(x) => (x as *const i16).cast(),
+            RegisterValue::I32(x) => (x as *const i32).cast(),
+            RegisterValue::I64(x) => (x as *const i64).cast(),
+            RegisterValue::F128(x) => (x as *const f128).cast(),
+            RegisterValue::Byte64(x) => (x as *const [u8; 8]).cast(),
+            RegisterValue::Byte128(x) => (x as *const [u8; 16]).cast(),
+            RegisterValue::Byte256(x) => (x as *const [u8; 32]).cast(),
+            RegisterValue::Byte512(x) => (x as *const [u8; 64]).cast(),
+        }
+    }
+
+    // The bytes of an MMX or vector register.
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            RegisterValue::Byte64(x) => Some(x),
+            RegisterValue::Byte128(x) => Some(x),
+            RegisterValue::Byte256(x) => Some(x),
+            RegisterValue::Byte512(x) => Some(x),
+            _ => None,
+        }
+    }
+
+    // An MMX or vector register as `format` shows it, lanes go from the lowest, e.g.
+    // `{1.0, 2.5, 0.0, 0.0}` for xmm0 as f32.
+    pub fn format_lanes(&self, format: LaneFormat) -> Option<String> {
+        fn lanes<const N: usize>(bytes: &[u8], f: impl Fn([u8; N]) -> String) -> String {
+            let lanes = bytes
+                .chunks_exact(N)
+                .map(|lane| f(lane.try_into().unwrap()))
+                .collect::<Vec<_>>();
+            format!("{{{}}}", lanes.join(", "))
+        }
+
+        let bytes = self.as_bytes()?;
+        Some(match format {
+            LaneFormat::F32 => lanes(bytes, |lane| format!("{:?}", f32::from_le_bytes(lane))),
+            LaneFormat::F64 => lanes(bytes, |lane| format!("{:?}", f64::from_le_bytes(lane))),
+            LaneFormat::U8 => lanes(bytes, |lane: [u8; 1]| lane[0].to_string()),
+            LaneFormat::U16 => lanes(bytes, |lane| u16::from_le_bytes(lane).to_string()),
+            LaneFormat::U32 => lanes(bytes, |lane| u32::from_le_bytes(lane).to_string()),
+            LaneFormat::U64 => lanes(bytes, |lane| u64::from_le_bytes(lane).to_string()),
+            LaneFormat::Hex => {
+                let digits = bytes.iter().rev().map(|byte| format!("{:02x}", byte));
+                format!("0x{}", digits.collect::<String>())
+            }
+        })
+    }
+
+    // The bits of an integer register, zero extended. MMX registers count as 64-bit integers,
+    // x87 and vector ones aren't integers.
+    pub fn as_u64(&self) -> Option<u64> {
+        match *self {
+            RegisterValue::U8(x) => Some(x.into()),
+            RegisterValue::U16(x) => Some(x.into()),
+            RegisterValue::U32(x) => Some(x.into()),
+            RegisterValue::U64(x) => Some(x),
+            RegisterValue::I8(x) => Some((x as u8).into()),
+            RegisterValue::I16(x) => Some((x as u16).into()),
+            RegisterValue::I32(x) => Some((x as u32).into()),
+            RegisterValue::I64(x) => Some(x as u64),
+            RegisterValue::Byte64(x) => Some(u64::from_le_bytes(x)),
+            RegisterValue::F128(_)
+            | RegisterValue::Byte128(_)
+            | RegisterValue::Byte256(_)
+            | RegisterValue::Byte512(_) => None,
+        }
+    }
+
+    // Like `as_u64`, but signed registers are sign extended.
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            RegisterValue::I8(x) => Some(x.into()),
+            RegisterValue::I16(x) => Some(x.into()),
+            RegisterValue::I32(x) => Some(x.into()),
+            RegisterValue::I64(x) => Some(x),
+            _ => self.as_u64().map(|x| x as i64),
+        }
+    }
+
+    pub fn as_usize_address(&self) -> Option<usize> {
+        self.as_u64()?.try_into().ok()
+    }
+
+    pub fn is_signed(&self) -> bool {
+        matches!(
+            self,
+            RegisterValue::I8(_)
+                | RegisterValue::I16(_)
+                | RegisterValue::I32(_)
+                | RegisterValue::I64(_)
+        )
+    }
+
+    // The unsigned integer of `width` bytes holding the low bytes of `bits`.
+    pub fn from_bits(bits: u64, width: usize) -> Option<Self> {
+        Some(match width {
+            1 => RegisterValue::U8(bits as u8),
+            2 => RegisterValue::U16(bits as u16),
+            4 => RegisterValue::U32(bits as u32),
+            8 => RegisterValue::U64(bits),
+            _ => return None,
+        })
+    }
+
+    fn from_signed_bits(bits: i64, width: usize) -> Option<Self> {
+        Some(match width {
+            1 => RegisterValue::I8(bits as i8),
+            2 => RegisterValue::I16(bits as i16),
+            4 => RegisterValue::I32(bits as i32),
+            8 => RegisterValue::I64(bits),
+            _ => return None,
+        })
+    }
+
+    // Widens an integer to `width` bytes, filling the new bytes with zeroes. The result is
+    // signed if the value is. None for narrower widths and non-integers.
+    pub fn zero_extend(&self, width: usize) -> Option<Self> {
+        if width < self.byte_width() {
+            return None;
+        }
+        if self.is_signed() {
+            Self::from_signed_bits(self.as_u64()? as i64, width)
+        } else {
+            Self::from_bits(self.as_u64()?, width)
+        }
+    }
+
+    // Like `zero_extend`, but the new bytes are copies of the sign bit, signed or not.
+    pub fn sign_extend(&self, width: usize) -> Option<Self> {
+        let byte_width = self.byte_width();
+        if width < byte_width {
+            return None;
+        }
+        let shift = 64 - byte_width as u32 * 8;
+        let x = ((self.as_u64()? << shift) as i64) >> shift;
+        if self.is_signed() {
+            Self::from_signed_bits(x, width)
+        } else {
+            Self::from_bits(x as u64, width)
+        }
+    }
+
+    // The number an integer register holds, by its signedness.
+    fn integer(&self) -> Option<i128> {
+        if self.is_signed() {
+            self.as_i64().map(i128::from)
+        } else {
+            self.as_u64().map(i128::from)
+        }
+    }
+}
+
+macro_rules! impl_integer_conversions {
+    ($($int:ty => $variant:ident),* $(,)?) => {
+        $(
+            impl From<$int> for RegisterValue {
+                fn from(x: $int) -> Self {
+                    RegisterValue::$variant(x)
+                }
+            }
+
+            // Fails if the number in the register doesn't fit.
+            impl TryFrom<RegisterValue> for $int {
+                type Error = anyhow::Error;
+
+                fn try_from(value: RegisterValue) -> Result<Self, Self::Error> {
+                    let x = value
+                        .integer()
+                        .ok_or(anyhow!("{} is not an integer", value))?;
+                    <$int>::try_from(x)
+                        .map_err(|_| anyhow!("{} doesn't fit in {}", value, stringify!($int)))
+                }
+            }
+        )*
+    };
+}
+
+impl_integer_conversions! {
+    u8 => U8,
+    u16 => U16,
+    u32 => U32,
+    u64 => U64,
+    i8 => I8,
+    i16 => I16,
+    i32 => I32,
+    i64 => I64,
+}
+
+impl Register {
+    unsafe fn get_ptr_in_user_struct(&self, user: &libc::user) -> *const u8 {
+        let offset = self.offset_in_user_struct();
+        let ptr: *const libc::user = user;
+        let ptr = ptr.cast::<u8>();
+        let ptr = unsafe { ptr.offset(offset.try_into().unwrap()) };
+        return ptr;
+    }
+
+    unsafe fn get_mut_ptr_in_user_struct(&self, user: &mut libc::user) -> *mut u8 {
+        let offset = self.offset_in_user_struct();
+        let ptr: *mut libc::user = user;
+        let ptr = ptr.cast::<u8>();
+        let ptr = unsafe { ptr.offset(offset.try_into().unwrap()) };
+        return ptr;
+    }
+
+    pub fn read_from_user_struct(&self, user: &libc::user) -> anyhow::Result<RegisterValue> {
+        if self.kind() == RegisterKind::ExtendedState {
+            return Err(anyhow!("register {:?} is not in the user struct", self));
+        }
+        let byte_width = self.byte_width();
+        let repr = self.repr();
+
+        let ptr = unsafe { self.get_ptr_in_user_struct(user) };
+
+        let val = match (repr, byte_width) {
+            (RegisterRepr::UInt, 1) => {
+                RegisterValue::U8(unsafe { read_any_from_u8_pointer(ptr, 1) })
+            }
+            (RegisterRepr::UInt, 2) => {
+                RegisterValue::U16(unsafe { read_any_from_u8_pointer(ptr, 2) })
+            }
+            (RegisterRepr::UInt, 4) => {
+                RegisterValue::U32(unsafe { read_any_from_u8_pointer(ptr, 4) })
+            }
+            (RegisterRepr::UInt, 8) => {
+                RegisterValue::U64(unsafe { read_any_from_u8_pointer(ptr, 8) })
+            }
+            (RegisterRepr::LongDouble, byte_width) => {
+                RegisterValue::F128(unsafe { read_any_from_u8_pointer(ptr, byte_width) })
+            }
+            (RegisterRepr::Vector, byte_width) => {
+                if byte_width <= 8 {
+                    RegisterValue::Byte64(unsafe { read_any_from_u8_pointer(ptr, byte_width) })
+                } else if byte_width <= 16 {
+                    RegisterValue::Byte128(unsafe { read_any_from_u8_pointer(ptr, byte_width) })
+                } else {
+                    unreachable!("register {:?}: vector is longer than 128 bit", self)
+                }
+            }
+            (repr, byte_width) => {
+                unreachable!(
+                    "register {:?}: unhandled repr/byte width combination: {:?}, {:?}, ",
+                    self, repr, byte_width,
+                )
+            }
+        };
+
+        Ok(val)
+    }
+
+    unsafe fn copy_to_user_struct(
+        &self,
+        from_ptr: *const u8,
+        to_user: &mut libc::user,
+        value_byte_width: usize,
+    ) -> anyhow::Result<()> {
+        if self.kind() == RegisterKind::ExtendedState {
+            return Err(anyhow!("register {:?} is not in the user struct", self));
+        }
+        let byte_width = self.byte_width();
+        if self.byte_width() < value_byte_width {
+            return Err(anyhow!("register {:?}: value doesn't fit in the register, value width: {}, register width: {}", self, value_byte_width, byte_width));
+        }
+        let ptr = self.get_mut_ptr_in_user_struct(to_user);
+        let zeroed = iter::repeat(0u8).take(byte_width).collect::<Vec<u8>>();
+        let zeroed_ptr = zeroed.as_ptr();
+        zeroed_ptr.copy_to(ptr, byte_width);
+        from_ptr.copy_to(ptr, value_byte_width);
+        Ok(())
+    }
+
+    pub fn write_to_user_struct(
+        &self,
+        user: &mut libc::user,
+        value: RegisterValue,
+    ) -> anyhow::Result<()> {
+        let value_byte_width = value.byte_width();
+        unsafe {
+            let value_ptr = value.as_u8_ptr();
+            self.copy_to_user_struct(value_ptr, user, value_byte_width)
+        }
+    }
+
+    pub unsafe fn write_any_to_user_struct<T>(
+        &self,
+        user: &mut libc::user,
+        value: &T,
+    ) -> anyhow::Result<()>
+    where
+        T: Sized,
+    {
+        let value_byte_width = size_of_val(value);
+        let value_ptr = (value as *const T).cast::<u8>();
+        self.copy_to_user_struct(value_ptr, user, value_byte_width)
+    }
+}
+
+// Enough for everything up to AVX-512, in case cpuid tells less.
+const XSTATE_MIN_SIZE: usize = 4096;
+// The first 512 bytes of the XSAVE area have the layout of FXSAVE, that is user_fpregs_struct.
+const XSTATE_LEGACY_SIZE: usize = 512;
+const NT_X86_XSTATE: i32 = 0x202;
+// XSTATE_BV of the XSAVE header, a component whose bit is clear is in its initial state, which
+// is all zeroes for the vector registers
+const XSTATE_BV_OFFSET: usize = 512;
+// x87 and SSE, that is the FXSAVE part
+const XSTATE_LEGACY_COMPONENTS: u64 = 0b11;
+// XSAVE state components with the parts of the vector registers FXSAVE doesn't have
+const XSTATE_YMM_HI128: u32 = 2; // bits 128-255 of ymm0-15
+const XSTATE_ZMM_HI256: u32 = 6; // bits 256-511 of zmm0-15
+const XSTATE_HI16_ZMM: u32 = 7; // all of zmm16-31
+
+lazy_static! {
+    // PTRACE_SETREGSET only takes the whole XSAVE area, as large as the features enabled in XCR0
+    // make it, AMX tile data included.
+    static ref XSTATE_SIZE: usize = {
+        #[allow(unused_unsafe)]
+        let leaf = unsafe { __cpuid_count(0xd, 0) };
+        (leaf.ebx as usize).max(XSTATE_MIN_SIZE)
+    };
+    // Where the components are in the XSAVE area, PTRACE_GETREGSET always has the standard
+    // format whose layout cpuid tells. Components the CPU doesn't have are left out.
+    static ref XSTATE_COMPONENTS: BTreeMap<u32, Range<usize>> =
+        [XSTATE_YMM_HI128, XSTATE_ZMM_HI256, XSTATE_HI16_ZMM]
+            .into_iter()
+            .filter_map(|component| {
+                #[allow(unused_unsafe)]
+                let leaf = unsafe { __cpuid_count(0xd, component) };
+                let range = leaf.ebx as usize..(leaf.ebx + leaf.eax) as usize;
+                (leaf.eax != 0).then_some((component, range))
+            })
+            .collect();
+}
+
+// Where 16 bytes of a vector register are kept: in an xmm register of the FXSAVE part, or at an
+// offset into an XSAVE component.
+enum VectorLane {
+    Xmm(usize),
+    Component(u32, usize),
+}
+
+impl VectorLane {
+    fn of(index: usize, lane: usize) -> Self {
+        match (index, lane) {
+            (0..16, 0) => VectorLane::Xmm(index),
+            (0..16, 1) => VectorLane::Component(XSTATE_YMM_HI128, index * 16),
+            (0..16, lane) => VectorLane::Component(XSTATE_ZMM_HI256, index * 32 + (lane - 2) * 16),
+            (index, lane) => VectorLane::Component(XSTATE_HI16_ZMM, (index - 16) * 64 + lane * 16),
+        }
+    }
+}
+
+// What was written to the registers since they were read, each part is written back with a
+// call of its own.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DirtyRegisters {
+    pub general_purpose: bool,
+    pub floating_point: bool,
+    // a bit per debug register, they're written one at a time
+    pub debug: u8,
+    // the XSAVE area, the floating point registers go along
+    pub extended: bool,
+}
+
+impl DirtyRegisters {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    fn mark(&mut self, register: Register) {
+        match register.kind() {
+            RegisterKind::GeneralPurpose | RegisterKind::SubGeneralPurpose => {
+                self.general_purpose = true
+            }
+            RegisterKind::FloatingPoint => self.floating_point = true,
+            RegisterKind::ExtendedState => self.extended = true,
+            RegisterKind::Debug => {
+                let index = Register::all_debug_registers()
+                    .iter()
+                    .position(|debug_register| *debug_register == register)
+                    .unwrap();
+                self.debug |= 1 << index;
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Registers {
+    user: libc::user,
+    xstate: Option<Vec<u8>>,
+    dirty: DirtyRegisters,
+}
+
+impl Registers {
+    pub fn read_register(&self, register: Register) -> anyhow::Result<RegisterValue> {
+        if register.kind() == RegisterKind::ExtendedState {
+            return self.read_vector(register);
+        }
+        register.read_from_user_struct(&self.user)
+    }
+
+    pub fn write_register(
+        &mut self,
+        register: Register,
+        value: RegisterValue,
+    ) -> anyhow::Result<()> {
+        if register.kind() == RegisterKind::ExtendedState {
+            self.write_vector(register, value)?;
+        } else {
+            register.write_to_user_struct(&mut self.user, value)?;
+        }
+        self.dirty.mark(register);
+        Ok(())
+    }
+
+    // Whether the CPU and the kernel have what `register` is kept in, only the ones in the XSAVE
+    // area may be missing.
+    pub fn is_available(&self, register: Register) -> bool {
+        register.kind() != RegisterKind::ExtendedState
+            || self.vector_lanes(register).all(|lane| match lane {
+                VectorLane::Xmm(_) => true,
+                VectorLane::Component(component, offset) => {
+                    self.xstate_lane(component, offset).is_some()
+                }
+            })
+    }
+
+    // The registers whose values differ from `previous`, as the register, the old value and the
+    // new one. Parts of other registers are left out, like eax, or xmm0 when there's ymm0.
+    pub fn changes_since(
+        &self,
+        previous: &Registers,
+    ) -> Vec<(Register, RegisterValue, RegisterValue)> {
+        let is_shown = |register: Register| {
+            let index = register.vector_index();
+            register.group().is_some()
+                && self.is_available(register)
+                && !Register::all_registers().into_iter().any(|wider| {
+                    index.is_some()
+                        && wider.vector_index() == index
+                        && wider.byte_width() > register.byte_width()
+                        && self.is_available(wider)
+                })
+        };
+        Register::all_registers()
+            .into_iter()
+            .filter(|register| is_shown(*register))
+            .filter_map(|register| {
+                let old = previous.read_register(register).ok()?;
+                let new = self.read_register(register).ok()?;
+                (old != new).then_some((register, old, new))
+            })
+            .collect()
+    }
+
+    fn vector_lanes(&self, register: Register) -> impl Iterator<Item = VectorLane> {
+        let index = register.vector_index().unwrap();
+        (0..register.byte_width() / 16).map(move |lane| VectorLane::of(index, lane))
+    }
+
+    // The 16 bytes of `xstate` at `offset` into `component`, None without the component.
+    fn xstate_lane(&self, component: u32, offset: usize) -> Option<Range<usize>> {
+        let xstate = self.xstate.as_ref()?;
+        let start = XSTATE_COMPONENTS.get(&component)?.start + offset;
+        (start + 16 <= xstate.len()).then_some(start..start + 16)
+    }
+
+    fn xstate_bv(&self) -> u64 {
+        self.xstate
+            .as_ref()
+            .and_then(|xstate| xstate.get(XSTATE_BV_OFFSET..XSTATE_BV_OFFSET + 8))
+            .map_or(0, |bytes| u64::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn read_vector(&self, register: Register) -> anyhow::Result<RegisterValue> {
+        if !self.is_available(register) {
+            Err(anyhow!("${} is not supported by the CPU", register.name()))?;
+        }
+
+        let mut bytes = [0u8; 64];
+        let lanes = bytes[..register.byte_width()].chunks_mut(16);
+        for (lane, lane_bytes) in iter::zip(self.vector_lanes(register), lanes) {
+            match lane {
+                VectorLane::Xmm(xmm) => {
+                    for (word, word_bytes) in iter::zip(
+                        &self.user.i387.xmm_space[xmm * 4..][..4],
+                        lane_bytes.chunks_mut(4),
+                    ) {
+                        word_bytes.copy_from_slice(&word.to_ne_bytes());
+                    }
+                }
+                // left zeroed in the initial state
+                VectorLane::Component(component, _) if self.xstate_bv() & (1 << component) == 0 => {
+                }
+                VectorLane::Component(component, offset) => {
+                    let range = self.xstate_lane(component, offset).unwrap();
+                    lane_bytes.copy_from_slice(&self.xstate.as_ref().unwrap()[range]);
+                }
+            }
+        }
+
+        Ok(match register.byte_width() {
+            16 => RegisterValue::Byte128(bytes[..16].try_into().unwrap()),
+            32 => RegisterValue::Byte256(bytes[..32].try_into().unwrap()),
+            _ => RegisterValue::Byte512(bytes),
+        })
+    }
+
+    // Narrower values are zero extended like in the user struct.
+    fn write_vector(&mut self, register: Register, value: RegisterValue) -> anyhow::Result<()> {
+        if !self.is_available(register) {
+            Err(anyhow!("${} is not supported by the CPU", register.name()))?;
+        }
+        if value.byte_width() > register.byte_width() {
+            Err(anyhow!("register {:?}: value doesn't fit in the register, value width: {}, register width: {}", register, value.byte_width(), register.byte_width()))?;
+        }
+
+        let mut bytes = [0u8; 64];
+        bytes[..value.byte_width()].copy_from_slice(unsafe {
+            std::slice::from_raw_parts(value.as_u8_ptr(), value.byte_width())
+        });
+        let lanes = self.vector_lanes(register).collect::<Vec<_>>();
+        for (lane, lane_bytes) in iter::zip(lanes, bytes.chunks(16)) {
+            match lane {
+                VectorLane::Xmm(xmm) => {
+                    for (word, word_bytes) in iter::zip(
+                        &mut self.user.i387.xmm_space[xmm * 4..][..4],
+                        lane_bytes.chunks(4),
+                    ) {
+                        *word = u32::from_ne_bytes(word_bytes.try_into().unwrap());
+                    }
+                }
+                VectorLane::Component(component, offset) => {
+                    let range = self.xstate_lane(component, offset).unwrap();
+                    let xstate = self.xstate.as_mut().unwrap();
+                    xstate[range].copy_from_slice(lane_bytes);
+                    xstate[XSTATE_BV_OFFSET] |= 1 << component;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub unsafe fn write_register_any<T: Sized>(
+        &mut self,
+        register: Register,
+        value: &T,
+    ) -> anyhow::Result<()> {
+        register.write_any_to_user_struct(&mut self.user, value)?;
+        self.dirty.mark(register);
+        Ok(())
+    }
+
+    pub fn dirty(&self) -> DirtyRegisters {
+        self.dirty
+    }
+
+    // Writes back the parts that were written to, and only those: PTRACE_SETREGS and
+    // PTRACE_SETFPREGS for the general purpose and floating point ones, a PTRACE_POKEUSER per
+    // debug register and PTRACE_SETREGSET for the XSAVE area.
+    pub fn flush<T: Tracer>(&mut self, tracer: &T, pid: Pid) -> anyhow::Result<()> {
+        if self.dirty.general_purpose {
+            debug!("writing general purpose registers");
+            tracer.set_regs(pid, self.user.regs)?;
+            self.dirty.general_purpose = false;
+        }
+        if let (true, Some(xstate)) = (self.dirty.extended, self.xstate.as_mut()) {
+            debug!("writing extended state register set");
+            // with whatever was written to the floating point registers
+            xstate[..XSTATE_LEGACY_SIZE].copy_from_slice(unsafe { as_u8_slice(&self.user.i387) });
+            let xstate_bv = &mut xstate[XSTATE_BV_OFFSET..][..8];
+            let bits = u64::from_le_bytes((&*xstate_bv).try_into().unwrap());
+            xstate_bv.copy_from_slice(&(bits | XSTATE_LEGACY_COMPONENTS).to_le_bytes());
+            tracer.set_regset(pid, NT_X86_XSTATE, xstate)?;
+            self.dirty.extended = false;
+            self.dirty.floating_point = false;
+        }
+        if self.dirty.floating_point {
+            debug!("writing floating point registers");
+            tracer.set_fpregs(pid, self.user.i387)?;
+            self.dirty.floating_point = false;
+        }
+        for (index, register) in Register::all_debug_registers().into_iter().enumerate() {
+            if self.dirty.debug & (1 << index) == 0 {
+                continue;
+            }
+            debug!("writing debug register {:?}", register);
+            tracer.write_user(
+                pid,
+                register.offset_in_user_struct(),
+                self.user.u_debugreg[index] as libc::c_long,
+            )?;
+            self.dirty.debug &= !(1 << index);
+        }
+        Ok(())
+    }
+
+    pub fn user_regs(&self) -> &libc::user_regs_struct {
+        &self.user.regs
+    }
+
+    pub fn user_fpregs(&self) -> &libc::user_fpregs_struct {
+        &self.user.i387
+    }
+
+    pub fn debug_registers(&self) -> [u64; 8] {
+        self.user.u_debugreg
+    }
+
+    // The raw XSAVE area, None if the CPU or the kernel doesn't support it.
+    pub fn xstate(&self) -> Option<&[u8]> {
+        self.xstate.as_deref()
+    }
+
+    pub fn read_with_ptrace(pid: Pid) -> anyhow::Result<Self> {
+        Self::read_with_tracer(&PtraceTracer, pid)
+    }
+
+    pub fn read_with_tracer<T: Tracer>(tracer: &T, pid: Pid) -> anyhow::Result<Self> {
+        let mut debug_registers = [0; 8];
+        for (idx, reg) in iter::zip(0usize..=8, Register::all_debug_registers()) {
+            let offset = reg.offset_in_user_struct();
+            debug!("reading debug register {:?}", reg);
+            let reg_val = tracer.read_user(pid, offset)?;
+            debug_registers[idx] = reg_val as u64;
+        }
+
+        Self::read_with_debug_registers(tracer, pid, debug_registers)
+    }
+
+    // Debug registers have no register set on x86 and take a PTRACE_PEEKUSER each. Since they
+    // only change when the debugger writes them, callers that know their values can skip reading
+    // them on every stop.
+    pub fn read_with_debug_registers<T: Tracer>(
+        tracer: &T,
+        pid: Pid,
+        debug_registers: [u64; 8],
+    ) -> anyhow::Result<Self> {
+        let mut user = unsafe { MaybeUninit::<libc::user>::zeroed().assume_init() };
+        user.u_debugreg = debug_registers;
+
+        debug!("reading general purpose register set");
+        let mut prstatus = [0u8; size_of::<libc::user_regs_struct>()];
+        let len = tracer.get_regset(pid, libc::NT_PRSTATUS, &mut prstatus)?;
+        user.regs = match len {
+            len if len == prstatus.len() => unsafe {
+                read_any_from_u8_pointer(prstatus.as_ptr(), len)
+            },
+            arch::I386_REGS_SIZE => arch::regs_from_i386(&prstatus[..len]),
+            len => Err(anyhow!("short general purpose register set: {} bytes", len))?,
+        };
+
+        debug!("reading extended state register set");
+        let mut xstate = vec![0u8; *XSTATE_SIZE];
+        let xstate = match tracer.get_regset(pid, NT_X86_XSTATE, &mut xstate) {
+            Ok(len) if len >= XSTATE_LEGACY_SIZE => {
+                xstate.truncate(len);
+                user.i387 = unsafe {
+                    read_any_from_u8_pointer(xstate.as_ptr(), size_of::<libc::user_fpregs_struct>())
+                };
+                Some(xstate)
+            }
+            _ => {
+                // NT_PRFPREG of a 32-bit process has the fsave layout, PTRACE_GETFPREGS always
+                // has the fxsave one
+                debug!("no extended state, reading floating point registers");
+                user.i387 = tracer.get_fpregs(pid)?;
+                None
+            }
+        };
+
+        Ok(Self {
+            user,
+            xstate,
+            dirty: DirtyRegisters::default(),
+        })
+    }
+}
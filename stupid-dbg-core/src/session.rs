@@ -0,0 +1,185 @@
+use std::{collections::VecDeque, ops::ControlFlow};
+
+use nix::{sys::signal::Signal, unistd::Pid};
+use tracing::debug_span;
+
+use crate::{
+    debuggee::{self, Debuggee, ProcessState, ThreadEvent},
+    stop_reason::{SignalInfo, StopReason},
+    virt_addr::VirtAddr,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DebugEvent {
+    BreakpointHit {
+        thread: Pid,
+        breakpoint_id: usize,
+        address: VirtAddr,
+    },
+    SignalReceived {
+        thread: Pid,
+        signal: Signal,
+        info: SignalInfo,
+    },
+    // Stops that are neither breakpoints nor signals, like finished steps or syscall stops.
+    Stopped {
+        thread: Pid,
+        reason: StopReason,
+    },
+    ThreadCreated {
+        thread: Pid,
+    },
+    ThreadExited {
+        thread: Pid,
+    },
+    Exited {
+        status_code: Option<i32>,
+    },
+    Terminated {
+        signal: Signal,
+    },
+}
+
+pub type EventCallback = Box<dyn FnMut(&mut Debuggee, &DebugEvent) -> ControlFlow<()>>;
+
+// Drives a debuggee without a REPL. Events can either be pulled one by one with `next_event` or
+// `events`, or pushed to callbacks registered with `on_event` by calling `run`.
+//
+// Thread creation and exit don't stop the debuggee, they are delivered together with the next stop.
+pub struct DebugSession {
+    debuggee: Debuggee,
+    callbacks: Vec<EventCallback>,
+    pending_events: VecDeque<DebugEvent>,
+}
+
+pub struct Events<'a> {
+    session: &'a mut DebugSession,
+}
+
+impl DebugSession {
+    pub fn new(config: debuggee::Config) -> anyhow::Result<Self> {
+        Debuggee::new(config).map(Self::from_debuggee)
+    }
+
+    pub fn from_debuggee(debuggee: Debuggee) -> Self {
+        Self {
+            debuggee,
+            callbacks: Vec::new(),
+            pending_events: VecDeque::new(),
+        }
+    }
+
+    pub fn debuggee(&self) -> &Debuggee {
+        &self.debuggee
+    }
+
+    pub fn debuggee_mut(&mut self) -> &mut Debuggee {
+        &mut self.debuggee
+    }
+
+    pub fn into_debuggee(self) -> Debuggee {
+        self.debuggee
+    }
+
+    pub fn on_event<F>(&mut self, callback: F)
+    where
+        F: FnMut(&mut Debuggee, &DebugEvent) -> ControlFlow<()> + 'static,
+    {
+        self.callbacks.push(Box::new(callback));
+    }
+
+    pub(crate) fn collect_events(&mut self) {
+        self.pending_events
+            .extend(
+                self.debuggee
+                    .take_thread_events()
+                    .into_iter()
+                    .map(|event| match event {
+                        ThreadEvent::Created(thread) => DebugEvent::ThreadCreated { thread },
+                        ThreadEvent::Exited(thread) => DebugEvent::ThreadExited { thread },
+                    }),
+            );
+
+        let thread = self.debuggee.current_thread();
+        let event = match self.debuggee.process_state() {
+            ProcessState::Stopped(StopReason::Breakpoint { id }) => self
+                .debuggee
+                .breakpoints()
+                .get(&id)
+                .map(|breakpoint| DebugEvent::BreakpointHit {
+                    thread,
+                    breakpoint_id: id,
+                    address: breakpoint.address(),
+                }),
+            ProcessState::Stopped(StopReason::Signal(info)) => Some(DebugEvent::SignalReceived {
+                thread,
+                signal: info.signal,
+                info,
+            }),
+            ProcessState::Stopped(StopReason::Initial) | ProcessState::Running => None,
+            ProcessState::Stopped(reason) => Some(DebugEvent::Stopped { thread, reason }),
+            ProcessState::Exited(status_code) => Some(DebugEvent::Exited { status_code }),
+            ProcessState::Terminated(signal) => Some(DebugEvent::Terminated { signal }),
+        };
+        self.pending_events.extend(event);
+    }
+
+    pub(crate) fn pop_pending_event(&mut self) -> Option<DebugEvent> {
+        self.pending_events.pop_front()
+    }
+
+    // Resumes the debuggee whenever there is no pending event. Returns None once the debuggee is
+    // gone and every event has been delivered.
+    pub fn next_event(&mut self) -> anyhow::Result<Option<DebugEvent>> {
+        let span = debug_span!(
+            "waiting for next debug event",
+            pid = tracing::field::display(&self.debuggee.pid()),
+        );
+        let _entered = span.enter();
+
+        while self.pending_events.is_empty() {
+            if !self.debuggee.process_state().is_alive() {
+                return Ok(None);
+            }
+
+            // a signal reported as an event is delivered, a fault would be hit over and over
+            // again otherwise
+            self.debuggee
+                .resume_with_signal(self.debuggee.stop_signal())?;
+            self.debuggee.update_process_state(true)?;
+            // tracepoints and breakpoints whose condition is false are resumed from right away
+            if !self.debuggee.process_stop()? {
+                continue;
+            }
+            self.collect_events();
+        }
+
+        Ok(self.pop_pending_event())
+    }
+
+    pub fn events(&mut self) -> Events<'_> {
+        Events { session: self }
+    }
+
+    // Dispatches events to every registered callback until one of them breaks or the debuggee
+    // is gone. The debuggee is left stopped at the event that caused the break.
+    pub fn run(&mut self) -> anyhow::Result<ControlFlow<()>> {
+        while let Some(event) = self.next_event()? {
+            for callback in &mut self.callbacks {
+                if callback(&mut self.debuggee, &event).is_break() {
+                    return Ok(ControlFlow::Break(()));
+                }
+            }
+        }
+
+        Ok(ControlFlow::Continue(()))
+    }
+}
+
+impl Iterator for Events<'_> {
+    type Item = anyhow::Result<DebugEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.session.next_event().transpose()
+    }
+}
@@ -0,0 +1,229 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::{
+    anti_debug::AntiDebugConfig, aux::box_err, debug_register::WatchKind, debuggee::Debuggee,
+    memory_map::MemoryMap, virt_addr::VirtAddr,
+};
+
+// Addresses inside file backed mappings are stored relative to the module, so they can be
+// resolved again after the debuggee is restarted with a different layout.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BreakpointLocation {
+    Address(VirtAddr),
+    ModuleOffset { module: String, offset: u64 },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BreakpointSpec {
+    pub location: BreakpointLocation,
+    #[serde(default)]
+    pub condition: Option<String>,
+    #[serde(default = "enabled_by_default")]
+    pub enabled: bool,
+    // in a debug register rather than patched into the code
+    #[serde(default)]
+    pub hardware: bool,
+    #[serde(default)]
+    pub groups: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WatchpointSpec {
+    pub location: BreakpointLocation,
+    pub size: usize,
+    pub kind: WatchKind,
+    // set with `mwatch`, by write protecting pages
+    #[serde(default)]
+    pub page_protection: bool,
+}
+
+// What the `set` commands change in the debuggee.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub anti_debug: AntiDebugConfig,
+    pub auto_hardware_breakpoints: bool,
+    pub step_into_handlers: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionState {
+    pub breakpoints: Vec<BreakpointSpec>,
+    #[serde(default)]
+    pub watchpoints: Vec<WatchpointSpec>,
+    #[serde(default)]
+    pub settings: Settings,
+    // the source search path and the environment of `run` belong to the debugger, which fills
+    // them in
+    #[serde(default)]
+    pub source_directories: Vec<PathBuf>,
+    #[serde(default)]
+    pub environment: BTreeMap<String, Option<String>>,
+}
+
+fn enabled_by_default() -> bool {
+    true
+}
+
+impl BreakpointLocation {
+    pub fn from_address(memory_map: &MemoryMap, address: VirtAddr) -> Self {
+        memory_map
+            .region_containing(address)
+            .and_then(|region| region.path.as_ref())
+            .filter(|path| path.starts_with('/'))
+            .and_then(|path| {
+                memory_map
+                    .module_base(path)
+                    .map(|base| BreakpointLocation::ModuleOffset {
+                        module: path.clone(),
+                        offset: address.offset_from(base).unwrap_or(0),
+                    })
+            })
+            .unwrap_or(BreakpointLocation::Address(address))
+    }
+
+    pub fn resolve(&self, memory_map: &MemoryMap) -> anyhow::Result<VirtAddr> {
+        match self {
+            BreakpointLocation::Address(address) => Ok(*address),
+            BreakpointLocation::ModuleOffset { module, offset } => memory_map
+                .module_base(module)
+                .map(|base| base + *offset)
+                .ok_or(anyhow!("module {} is not loaded", module)),
+        }
+    }
+}
+
+impl SessionState {
+    pub fn capture(debuggee: &Debuggee) -> anyhow::Result<Self> {
+        let memory_map = debuggee.memory_map()?;
+
+        let software = debuggee
+            .breakpoints()
+            .values()
+            .map(|breakpoint| (breakpoint.id(), (breakpoint.address(), false)));
+        let hardware = debuggee
+            .hardware_breakpoints()
+            .iter()
+            .map(|(id, address)| (*id, (*address, true)));
+        let breakpoints = software
+            .chain(hardware)
+            .collect::<BTreeMap<_, _>>()
+            .into_iter()
+            .map(|(id, (address, hardware))| BreakpointSpec {
+                location: BreakpointLocation::from_address(&memory_map, address),
+                condition: debuggee.breakpoint_conditions().get(&id).cloned(),
+                enabled: debuggee.is_breakpoint_enabled(id),
+                hardware,
+                groups: debuggee
+                    .breakpoint_groups()
+                    .iter()
+                    .filter(|(_, ids)| ids.contains(&id))
+                    .map(|(group, _)| group.clone())
+                    .collect(),
+            })
+            .collect();
+
+        let watchpoints = debuggee
+            .watchpoints()
+            .values()
+            .map(|watchpoint| WatchpointSpec {
+                location: BreakpointLocation::from_address(&memory_map, watchpoint.address()),
+                size: watchpoint.size(),
+                kind: watchpoint.kind(),
+                page_protection: watchpoint.is_page_protected(),
+            })
+            .collect();
+
+        Ok(Self {
+            breakpoints,
+            watchpoints,
+            settings: Settings {
+                anti_debug: debuggee.anti_debug(),
+                auto_hardware_breakpoints: debuggee.auto_hardware_breakpoints(),
+                step_into_handlers: debuggee.step_into_handlers(),
+            },
+            source_directories: Vec::new(),
+            environment: BTreeMap::new(),
+        })
+    }
+
+    // Best effort: entries that can't be restored are reported and skipped.
+    pub fn apply(&self, debuggee: &mut Debuggee) -> anyhow::Result<()> {
+        let memory_map = debuggee.memory_map()?;
+
+        if let Err(err) = debuggee.set_anti_debug(self.settings.anti_debug) {
+            warn!(error = box_err(err), "unable to restore anti-debug setting");
+        }
+        debuggee.set_auto_hardware_breakpoints(self.settings.auto_hardware_breakpoints);
+        debuggee.set_step_into_handlers(self.settings.step_into_handlers);
+
+        for spec in &self.breakpoints {
+            let result = spec
+                .location
+                .resolve(&memory_map)
+                .and_then(|address| {
+                    if spec.hardware {
+                        debuggee.set_hardware_breakpoint(address)
+                    } else {
+                        debuggee.set_breakpoint(address)
+                    }
+                })
+                .and_then(|id| {
+                    debuggee.set_breakpoint_condition(id, spec.condition.clone())?;
+                    for group in &spec.groups {
+                        debuggee.add_breakpoint_to_group(id, group)?;
+                    }
+                    debuggee.set_breakpoint_enabled(id, spec.enabled)?;
+                    Ok(id)
+                });
+            match result {
+                Ok(id) => debug!(breakpoint = id, location = ?spec.location, "breakpoint restored"),
+                Err(err) => warn!(
+                    error = box_err(err),
+                    location = ?spec.location,
+                    "unable to restore breakpoint",
+                ),
+            }
+        }
+
+        for spec in &self.watchpoints {
+            let result = spec.location.resolve(&memory_map).and_then(|address| {
+                if spec.page_protection {
+                    debuggee.set_page_watchpoint(address, spec.size)
+                } else {
+                    debuggee.set_watchpoint(address, spec.size, spec.kind)
+                }
+            });
+            match result {
+                Ok(id) => debug!(watchpoint = id, location = ?spec.location, "watchpoint restored"),
+                Err(err) => warn!(
+                    error = box_err(err),
+                    location = ?spec.location,
+                    "unable to restore watchpoint",
+                ),
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> anyhow::Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(path, json).map_err(|err| anyhow!("unable to write session file: {}", err))
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let json = fs::read_to_string(path)
+            .map_err(|err| anyhow!("unable to read session file: {}", err))?;
+        Ok(serde_json::from_str(&json)?)
+    }
+}
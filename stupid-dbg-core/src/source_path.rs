@@ -0,0 +1,76 @@
+use std::path::{Component, Path, PathBuf};
+
+use crate::namespace::ProcessRoot;
+
+// Directories searched for source files whose recorded paths don't exist on this machine, e.g.
+// when the binary was built elsewhere.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SourcePath {
+    directories: Vec<PathBuf>,
+}
+
+impl SourcePath {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn directories(&self) -> &[PathBuf] {
+        &self.directories
+    }
+
+    // Adding a directory again moves it to the end.
+    pub fn add<P: Into<PathBuf>>(&mut self, directory: P) {
+        let directory = directory.into();
+        self.directories.retain(|existing| *existing != directory);
+        self.directories.push(directory);
+    }
+
+    pub fn clear(&mut self) {
+        self.directories.clear();
+    }
+
+    // The recorded path is used as is if it exists, relative ones being taken from the
+    // compilation directory. Otherwise every directory is tried with shorter and shorter tails
+    // of the recorded path below it, down to just the file name.
+    pub fn resolve(&self, recorded: &Path, compilation_dir: Option<&Path>) -> Option<PathBuf> {
+        let recorded = match compilation_dir {
+            Some(compilation_dir) if recorded.is_relative() => compilation_dir.join(recorded),
+            _ => recorded.to_path_buf(),
+        };
+        if recorded.exists() {
+            return Some(recorded);
+        }
+
+        let components = recorded
+            .components()
+            .filter(|component| matches!(component, Component::Normal(_)))
+            .collect::<Vec<_>>();
+        let tails = (0..components.len())
+            .map(|skip| components[skip..].iter().collect::<PathBuf>())
+            .collect::<Vec<_>>();
+        self.directories
+            .iter()
+            .flat_map(|directory| tails.iter().map(move |tail| directory.join(tail)))
+            .find(|candidate| candidate.exists())
+    }
+
+    // Like `resolve`, for paths recorded by a process that may see another file system than
+    // the debugger, e.g. one built inside the container it runs in.
+    pub fn resolve_in(
+        &self,
+        root: &ProcessRoot,
+        recorded: &Path,
+        compilation_dir: Option<&Path>,
+    ) -> Option<PathBuf> {
+        let in_root = match compilation_dir {
+            Some(compilation_dir) if recorded.is_relative() => {
+                root.host_path(compilation_dir.join(recorded))
+            }
+            _ => root.host_path(recorded),
+        };
+        if in_root.exists() {
+            return Some(in_root);
+        }
+        self.resolve(recorded, compilation_dir)
+    }
+}
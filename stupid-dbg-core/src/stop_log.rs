@@ -0,0 +1,93 @@
+use std::{collections::VecDeque, time::SystemTime};
+
+use nix::unistd::Pid;
+
+use crate::{stop_reason::StopReason, virt_addr::VirtAddr};
+
+pub const DEFAULT_STOP_LOG_CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StopRecord {
+    // counts every stop of the session, dropped ones included
+    pub index: usize,
+    pub time: SystemTime,
+    pub thread: Pid,
+    pub reason: StopReason,
+    pub pc: VirtAddr,
+}
+
+// The most recent stops of a session. Once it's full the oldest one is dropped for every new
+// one.
+#[derive(Debug, Clone)]
+pub struct StopLog {
+    capacity: usize,
+    records: VecDeque<StopRecord>,
+    next_index: usize,
+}
+
+impl Default for StopLog {
+    fn default() -> Self {
+        Self::new(DEFAULT_STOP_LOG_CAPACITY)
+    }
+}
+
+impl StopLog {
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "stop log capacity must not be zero");
+
+        Self {
+            capacity,
+            records: VecDeque::with_capacity(capacity),
+            next_index: 0,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn set_capacity(&mut self, capacity: usize) {
+        assert!(capacity > 0, "stop log capacity must not be zero");
+
+        self.capacity = capacity;
+        while self.records.len() > capacity {
+            self.records.pop_front();
+        }
+    }
+
+    pub fn record(&mut self, thread: Pid, reason: StopReason, pc: VirtAddr) {
+        if self.records.len() == self.capacity {
+            self.records.pop_front();
+        }
+        self.records.push_back(StopRecord {
+            index: self.next_index,
+            time: SystemTime::now(),
+            thread,
+            reason,
+            pc,
+        });
+        self.next_index += 1;
+    }
+
+    // Oldest first.
+    pub fn records(&self) -> impl DoubleEndedIterator<Item = &StopRecord> {
+        self.records.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    // How many stops no longer fit.
+    pub fn dropped(&self) -> usize {
+        self.next_index - self.records.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.records.clear();
+    }
+}
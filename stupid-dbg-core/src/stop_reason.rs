@@ -0,0 +1,172 @@
+use std::fmt;
+
+use nix::{sys::signal::Signal, unistd::Pid};
+
+use crate::virt_addr::VirtAddr;
+
+// si_code values of SIGTRAP, see siginfo.h
+const TRAP_TRACE: i32 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PtraceEventKind {
+    Fork,
+    Vfork,
+    Clone,
+    Exec,
+    VforkDone,
+    Exit,
+    Seccomp,
+    Stop,
+    Unknown(i32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SignalInfo {
+    pub signal: Signal,
+    pub code: i32,
+    pub errno: i32,
+    // faulting address for SIGSEGV, SIGBUS, SIGILL, SIGFPE and SIGTRAP
+    pub fault_address: Option<VirtAddr>,
+    // sending process for signals sent with kill, tgkill and sigqueue
+    pub sender: Option<Pid>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StopReason {
+    // right after launching, attaching or restarting from a checkpoint
+    Initial,
+    Breakpoint { id: usize },
+    Watchpoint { id: usize, address: VirtAddr },
+    // the frame watchpoint `id` watched a local of returned, the watchpoint is deleted
+    WatchpointScope { id: usize },
+    StepComplete,
+    SyscallEntry { number: u64 },
+    SyscallExit { number: u64, return_value: i64 },
+    PtraceEvent(PtraceEventKind),
+    Signal(SignalInfo),
+    // stopped by job control, stays stopped until SIGCONT as far as the process is concerned
+    GroupStop { signal: Signal },
+}
+
+impl From<i32> for PtraceEventKind {
+    fn from(event: i32) -> Self {
+        match event {
+            libc::PTRACE_EVENT_FORK => Self::Fork,
+            libc::PTRACE_EVENT_VFORK => Self::Vfork,
+            libc::PTRACE_EVENT_CLONE => Self::Clone,
+            libc::PTRACE_EVENT_EXEC => Self::Exec,
+            libc::PTRACE_EVENT_VFORK_DONE => Self::VforkDone,
+            libc::PTRACE_EVENT_EXIT => Self::Exit,
+            libc::PTRACE_EVENT_SECCOMP => Self::Seccomp,
+            libc::PTRACE_EVENT_STOP => Self::Stop,
+            event => Self::Unknown(event),
+        }
+    }
+}
+
+impl SignalInfo {
+    pub(crate) fn from_siginfo(signal: Signal, info: &libc::siginfo_t) -> Self {
+        // the address is only there if the kernel raised it, the sender's pid is in its place
+        // otherwise
+        let fault_address = match signal {
+            Signal::SIGSEGV
+            | Signal::SIGBUS
+            | Signal::SIGILL
+            | Signal::SIGFPE
+            | Signal::SIGTRAP
+                if info.si_code > 0 =>
+            {
+                Some(VirtAddr::new(unsafe { info.si_addr() } as u64))
+            }
+            _ => None,
+        };
+        // SI_USER, SI_QUEUE, SI_TKILL and friends are all <= 0
+        let sender = if info.si_code <= 0 {
+            Some(Pid::from_raw(unsafe { info.si_pid() }))
+        } else {
+            None
+        };
+
+        Self {
+            signal,
+            code: info.si_code,
+            errno: info.si_errno,
+            fault_address,
+            sender,
+        }
+    }
+
+    // Used when the siginfo of a stop can't be retrieved.
+    pub(crate) fn from_signal(signal: Signal) -> Self {
+        Self {
+            signal,
+            code: 0,
+            errno: 0,
+            fault_address: None,
+            sender: None,
+        }
+    }
+
+    pub(crate) fn is_single_step(&self) -> bool {
+        self.signal == Signal::SIGTRAP && self.code == TRAP_TRACE
+    }
+}
+
+impl StopReason {
+    // The signal the stop is reported as, if any.
+    pub fn signal(&self) -> Option<Signal> {
+        match self {
+            StopReason::Initial
+            | StopReason::SyscallEntry { .. }
+            | StopReason::SyscallExit { .. }
+            | StopReason::PtraceEvent(_) => None,
+            StopReason::Breakpoint { .. }
+            | StopReason::Watchpoint { .. }
+            | StopReason::WatchpointScope { .. }
+            | StopReason::StepComplete => Some(Signal::SIGTRAP),
+            StopReason::Signal(info) => Some(info.signal),
+            StopReason::GroupStop { signal } => Some(*signal),
+        }
+    }
+}
+
+// Signals that stop the whole thread group unless handled.
+pub fn is_stop_signal(signal: Signal) -> bool {
+    matches!(
+        signal,
+        Signal::SIGSTOP | Signal::SIGTSTP | Signal::SIGTTIN | Signal::SIGTTOU
+    )
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StopReason::Initial => write!(f, "initial stop"),
+            StopReason::Breakpoint { id } => write!(f, "breakpoint {}", id),
+            StopReason::Watchpoint { id, address } => {
+                write!(f, "watchpoint {} at {}", id, address)
+            }
+            StopReason::WatchpointScope { id } => {
+                write!(f, "watchpoint {} went out of scope", id)
+            }
+            StopReason::StepComplete => write!(f, "step complete"),
+            StopReason::SyscallEntry { number } => write!(f, "syscall {} entry", number),
+            StopReason::SyscallExit {
+                number,
+                return_value,
+            } => write!(f, "syscall {} exit, returned {}", number, return_value),
+            StopReason::PtraceEvent(kind) => write!(f, "ptrace event {:?}", kind),
+            StopReason::GroupStop { signal } => write!(f, "group stop by {}", signal),
+            StopReason::Signal(info) => {
+                write!(f, "signal {} (code {})", info.signal, info.code)?;
+                if let Some(address) = info.fault_address {
+                    write!(f, " at {}", address)?;
+                }
+                if let Some(sender) = info.sender {
+                    write!(f, " from pid {}", sender)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
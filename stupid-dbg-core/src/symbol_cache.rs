@@ -0,0 +1,150 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::{memory_map::MemoryMap, virt_addr::VirtAddr};
+
+pub const DEFAULT_SYMBOL_CACHE_CAPACITY: usize = 4096;
+
+// Addresses are cached relative to the module they belong to, so the entries stay valid as long
+// as the module stays loaded at the same base.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ModuleOffset {
+    pub module: String,
+    pub offset: u64,
+}
+
+impl ModuleOffset {
+    pub fn from_address(memory_map: &MemoryMap, addr: VirtAddr) -> Option<Self> {
+        let region = memory_map.region_containing(addr)?;
+        let module = region
+            .path
+            .as_deref()
+            .filter(|path| path.starts_with('/'))?;
+        let base = memory_map.module_base(module)?;
+
+        Some(Self {
+            module: module.to_string(),
+            offset: addr.offset_from(base)?,
+        })
+    }
+}
+
+// Least recently used cache for the results of symbolication, like address to symbol or
+// address to line lookups. Misses are worth caching as well, so `V` is usually an Option.
+#[derive(Debug)]
+pub struct SymbolCache<V> {
+    capacity: usize,
+    entries: HashMap<ModuleOffset, (V, u64)>,
+    // last use of each entry, the first one gets evicted
+    recency: BTreeMap<u64, ModuleOffset>,
+    clock: u64,
+    // the modules the entries were computed against
+    modules: BTreeMap<String, VirtAddr>,
+}
+
+impl<V> Default for SymbolCache<V> {
+    fn default() -> Self {
+        Self::new(DEFAULT_SYMBOL_CACHE_CAPACITY)
+    }
+}
+
+impl<V> SymbolCache<V> {
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "symbol cache capacity must not be zero");
+
+        Self {
+            capacity,
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+            clock: 0,
+            modules: BTreeMap::new(),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.recency.clear();
+    }
+
+    // Drops the entries of every module unloaded or moved since the last sync, those of the
+    // others stay valid. Returns whether a module was loaded, unloaded or moved.
+    pub fn sync_modules(&mut self, memory_map: &MemoryMap) -> bool {
+        let modules = memory_map
+            .modules()
+            .into_iter()
+            .map(|(path, base)| (path.to_string(), base))
+            .collect::<BTreeMap<_, _>>();
+        if modules == self.modules {
+            return false;
+        }
+
+        let stale = self
+            .modules
+            .iter()
+            .filter(|(module, base)| modules.get(*module) != Some(base))
+            .map(|(module, _)| module.clone())
+            .collect::<Vec<_>>();
+        for module in stale {
+            self.forget_module(&module);
+        }
+        self.modules = modules;
+        true
+    }
+
+    pub fn forget_module(&mut self, module: &str) {
+        self.entries.retain(|key, _| key.module != module);
+        self.recency.retain(|_, key| key.module != module);
+    }
+
+    pub fn get(&mut self, key: &ModuleOffset) -> Option<&V> {
+        let clock = self.tick();
+        let (value, last_used) = self.entries.get_mut(key)?;
+        let key = self
+            .recency
+            .remove(last_used)
+            .expect("cache entry without recency");
+        self.recency.insert(clock, key);
+        *last_used = clock;
+        Some(value)
+    }
+
+    pub fn insert(&mut self, key: ModuleOffset, value: V) {
+        let clock = self.tick();
+        if let Some((_, last_used)) = self.entries.get(&key) {
+            self.recency.remove(last_used);
+        } else if self.entries.len() >= self.capacity {
+            if let Some((_, evicted)) = self.recency.pop_first() {
+                self.entries.remove(&evicted);
+            }
+        }
+        self.recency.insert(clock, key.clone());
+        self.entries.insert(key, (value, clock));
+    }
+
+    pub fn get_or_insert_with<F>(&mut self, key: ModuleOffset, lookup: F) -> &V
+    where
+        F: FnOnce(&ModuleOffset) -> V,
+    {
+        if !self.entries.contains_key(&key) {
+            let value = lookup(&key);
+            self.insert(key.clone(), value);
+        }
+        self.get(&key).expect("entry was just inserted")
+    }
+
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+}
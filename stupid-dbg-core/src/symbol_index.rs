@@ -0,0 +1,146 @@
+use std::{
+    env, fs,
+    hash::{DefaultHasher, Hash, Hasher},
+    io,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::elf::{ElfFile, ElfSymbol};
+
+// Bumped whenever what gets indexed changes, older entries are treated as missing.
+const INDEX_VERSION: u32 = 1;
+
+// What loading the symbols of a module needs from its file, so a file that hasn't changed
+// doesn't have to be read and parsed again.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexedModule {
+    pub position_independent: bool,
+    pub link_base: u64,
+    pub symbols: Vec<ElfSymbol>,
+}
+
+impl IndexedModule {
+    pub fn of(elf: &ElfFile) -> anyhow::Result<Self> {
+        Ok(Self {
+            position_independent: elf.is_position_independent(),
+            link_base: elf.link_base(),
+            symbols: elf.symbols()?,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct IndexEntry {
+    version: u32,
+    path: PathBuf,
+    size: u64,
+    // nanoseconds since the epoch
+    modified: u64,
+    module: IndexedModule,
+}
+
+// On-disk cache of the symbols of module files, one JSON file per module. Entries are keyed by
+// the path of the file and only used while its size and modification time stay the same.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SymbolIndex {
+    directory: PathBuf,
+}
+
+impl SymbolIndex {
+    pub fn new<P: Into<PathBuf>>(directory: P) -> Self {
+        Self {
+            directory: directory.into(),
+        }
+    }
+
+    // `$XDG_CACHE_HOME/stupid-dbg/symbols`, or `~/.cache/stupid-dbg/symbols`. None if there's
+    // no home directory to put it in.
+    pub fn open_default() -> Option<Self> {
+        let cache_home = env::var_os("XDG_CACHE_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+        Some(Self::new(cache_home.join("stupid-dbg").join("symbols")))
+    }
+
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    // None if the file isn't indexed or changed since.
+    pub fn load(&self, path: &Path) -> Option<IndexedModule> {
+        let (size, modified) = file_stamp(path).ok()?;
+        let json = fs::read_to_string(self.entry_path(path)).ok()?;
+        let entry = serde_json::from_str::<IndexEntry>(&json).ok()?;
+        if entry.version != INDEX_VERSION
+            || entry.path != path
+            || entry.size != size
+            || entry.modified != modified
+        {
+            debug!(path = %path.display(), "stale symbol index entry");
+            return None;
+        }
+
+        Some(entry.module)
+    }
+
+    pub fn store(&self, path: &Path, module: &IndexedModule) -> anyhow::Result<()> {
+        let (size, modified) = file_stamp(path)?;
+        let entry = IndexEntry {
+            version: INDEX_VERSION,
+            path: path.to_path_buf(),
+            size,
+            modified,
+            module: module.clone(),
+        };
+
+        fs::create_dir_all(&self.directory)
+            .map_err(|err| anyhow!("unable to create symbol index directory: {}", err))?;
+        // written aside and renamed, another session may be reading the entry
+        let entry_path = self.entry_path(path);
+        let partial_path = entry_path.with_extension("partial");
+        fs::write(&partial_path, serde_json::to_string(&entry)?)
+            .and_then(|()| fs::rename(&partial_path, &entry_path))
+            .map_err(|err| anyhow!("unable to write symbol index entry: {}", err))
+    }
+
+    // Removes every entry, returns how many there were.
+    pub fn flush(&self) -> anyhow::Result<usize> {
+        let entries = match fs::read_dir(&self.directory) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(0),
+            Err(err) => Err(anyhow!("unable to read symbol index directory: {}", err))?,
+        };
+
+        let mut removed = 0;
+        for entry in entries {
+            let path = entry?.path();
+            match path.extension().and_then(|extension| extension.to_str()) {
+                Some("json") => removed += 1,
+                Some("partial") => (),
+                _ => continue,
+            }
+            fs::remove_file(&path)
+                .map_err(|err| anyhow!("unable to remove {}: {}", path.display(), err))?;
+        }
+
+        Ok(removed)
+    }
+
+    fn entry_path(&self, path: &Path) -> PathBuf {
+        let mut hasher = DefaultHasher::new();
+        path.hash(&mut hasher);
+        self.directory.join(format!("{:016x}.json", hasher.finish()))
+    }
+}
+
+fn file_stamp(path: &Path) -> anyhow::Result<(u64, u64)> {
+    let metadata = fs::metadata(path)?;
+    let modified = metadata.modified()?.duration_since(UNIX_EPOCH)?;
+    Ok((metadata.len(), modified.as_nanos() as u64))
+}
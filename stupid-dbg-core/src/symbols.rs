@@ -0,0 +1,355 @@
+use std::{collections::BTreeMap, path::Path};
+
+use anyhow::anyhow;
+use tracing::{debug, warn};
+
+use crate::{
+    aux::box_err,
+    elf::{ElfFile, SymbolKind},
+    memory_map::MemoryMap,
+    namespace::ProcessRoot,
+    symbol_index::{IndexedModule, SymbolIndex},
+    virt_addr::VirtAddr,
+};
+
+// How many of the closest names are suggested for one that doesn't exist.
+const NEAR_MISS_LIMIT: usize = 5;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    pub name: String,
+    pub demangled: Option<String>,
+    // where it is in the running process, with the load bias applied
+    pub address: VirtAddr,
+    pub size: u64,
+    pub kind: SymbolKind,
+    pub module: String,
+}
+
+impl Symbol {
+    pub fn display_name(&self) -> &str {
+        self.demangled.as_deref().unwrap_or(&self.name)
+    }
+}
+
+// Symbols of every file mapped into the debuggee.
+#[derive(Debug, Clone, Default)]
+pub struct SymbolTable {
+    symbols: Vec<Symbol>,
+    // positions in `symbols` by mangled and demangled name
+    by_name: BTreeMap<String, Vec<usize>>,
+    // positions in `symbols` ordered by address
+    by_address: Vec<usize>,
+    // how far before an address `lookup` has to look for the symbol it's in
+    max_size: u64,
+}
+
+impl SymbolTable {
+    // Modules that can't be read or parsed are skipped with a warning. Symbols keep the module
+    // path the process sees, the file itself is read through `root`.
+    pub fn load(memory_map: &MemoryMap, root: &ProcessRoot) -> Self {
+        Self::load_indexed(memory_map, root, None)
+    }
+
+    // Like `load`, taking the symbols of files that haven't changed from `index` and adding the
+    // others to it.
+    pub fn load_indexed(
+        memory_map: &MemoryMap,
+        root: &ProcessRoot,
+        index: Option<&SymbolIndex>,
+    ) -> Self {
+        let mut symbols = Vec::new();
+        for (module, base) in memory_map.modules() {
+            let indexed = match module_symbols(&root.module_path(memory_map, module), index) {
+                Ok(indexed) => indexed,
+                Err(err) => {
+                    warn!(error = box_err(err), module = %module, "unable to load symbols");
+                    continue;
+                }
+            };
+            let bias = if indexed.position_independent {
+                base.as_u64().wrapping_sub(indexed.link_base)
+            } else {
+                0
+            };
+
+            debug!(module = %module, symbols = indexed.symbols.len(), "symbols loaded");
+            symbols.extend(indexed.symbols.into_iter().map(|symbol| Symbol {
+                demangled: demangle(&symbol.name),
+                name: symbol.name,
+                address: VirtAddr::new(symbol.address.wrapping_add(bias)),
+                size: symbol.size,
+                kind: symbol.kind,
+                module: module.to_string(),
+            }));
+        }
+
+        Self::from_symbols(symbols)
+    }
+
+    pub fn from_symbols(symbols: Vec<Symbol>) -> Self {
+        let mut by_name = BTreeMap::<String, Vec<usize>>::new();
+        for (index, symbol) in symbols.iter().enumerate() {
+            by_name.entry(symbol.name.clone()).or_default().push(index);
+            if let Some(demangled) = symbol.demangled.as_ref().filter(|d| **d != symbol.name) {
+                by_name.entry(demangled.clone()).or_default().push(index);
+            }
+        }
+        let mut by_address = (0..symbols.len()).collect::<Vec<_>>();
+        by_address.sort_by_key(|index| symbols[*index].address.as_u64());
+        let max_size = symbols.iter().map(|symbol| symbol.size).max().unwrap_or(0);
+
+        Self {
+            symbols,
+            by_name,
+            by_address,
+            max_size,
+        }
+    }
+
+    pub fn symbols(&self) -> &[Symbol] {
+        &self.symbols
+    }
+
+    // The symbol `address` is in, with the offset into it.
+    // Symbols can overlap, the one that comes first in the table wins.
+    pub fn lookup(&self, address: VirtAddr) -> Option<(&Symbol, u64)> {
+        let end = self
+            .by_address
+            .partition_point(|index| self.symbols[*index].address.as_u64() <= address.as_u64());
+        self.by_address[..end]
+            .iter()
+            .rev()
+            .filter_map(|index| Some((*index, address.offset_from(self.symbols[*index].address)?)))
+            .take_while(|(_, offset)| *offset < self.max_size)
+            .filter(|(index, offset)| *offset < self.symbols[*index].size)
+            .min_by_key(|(index, _)| *index)
+            .map(|(index, offset)| (&self.symbols[index], offset))
+    }
+
+    // `function+offset (module)` for an address in a symbol, e.g. `main+0x4 (/usr/bin/app)`,
+    // without the offset at the start of it.
+    pub fn symbolize(&self, address: VirtAddr) -> Option<String> {
+        let (symbol, offset) = self.lookup(address)?;
+        Some(match offset {
+            0 => format!("{} ({})", symbol.display_name(), symbol.module),
+            offset => format!(
+                "{}+{:#x} ({})",
+                symbol.display_name(),
+                offset,
+                symbol.module
+            ),
+        })
+    }
+
+    // The symbols called `name`, mangled or demangled, functions first. A name like `malloc`
+    // can be defined by more than one module.
+    pub fn find_by_name(&self, name: &str) -> Vec<&Symbol> {
+        let mut matches = self
+            .by_name
+            .get(name)
+            .into_iter()
+            .flatten()
+            .map(|index| &self.symbols[*index])
+            .collect::<Vec<_>>();
+        matches.sort_by_key(|symbol| symbol.kind != SymbolKind::Function);
+        matches.dedup_by(|a, b| a.address == b.address);
+        matches
+    }
+
+    // The symbol called `name`, the first one if there are more. Failing that the error lists
+    // the names that come closest.
+    pub fn resolve(&self, name: &str) -> anyhow::Result<&Symbol> {
+        if let Some(symbol) = self.find_by_name(name).into_iter().next() {
+            return Ok(symbol);
+        }
+
+        let mut candidates = self
+            .find_fuzzy(name, NEAR_MISS_LIMIT)
+            .into_iter()
+            .map(|(symbol, _)| symbol.display_name())
+            .collect::<Vec<_>>();
+        candidates.dedup();
+        if candidates.is_empty() {
+            Err(anyhow!("no symbol named {}", name))
+        } else {
+            Err(anyhow!(
+                "no symbol named {}, did you mean: {}",
+                name,
+                candidates.join(", ")
+            ))
+        }
+    }
+
+    // Symbols matching `query`, best first. Equal scores go to the shorter name.
+    pub fn find_fuzzy(&self, query: &str, limit: usize) -> Vec<(&Symbol, u32)> {
+        let mut matches = self
+            .symbols
+            .iter()
+            .filter_map(|symbol| {
+                let score = fuzzy_score(query, symbol.display_name())
+                    .max(fuzzy_score(query, &symbol.name))?;
+                Some((symbol, score))
+            })
+            .collect::<Vec<_>>();
+        matches.sort_by(|(a, a_score), (b, b_score)| {
+            b_score
+                .cmp(a_score)
+                .then(a.display_name().len().cmp(&b.display_name().len()))
+                .then(a.display_name().cmp(b.display_name()))
+        });
+        matches.dedup_by(|(a, _), (b, _)| a.address == b.address && a.name == b.name);
+        matches.truncate(limit);
+        matches
+    }
+}
+
+// Failing to index a module only costs the next session the time to read it again.
+fn module_symbols(path: &Path, index: Option<&SymbolIndex>) -> anyhow::Result<IndexedModule> {
+    if let Some(indexed) = index.and_then(|index| index.load(path)) {
+        return Ok(indexed);
+    }
+
+    let indexed = IndexedModule::of(&ElfFile::read(path)?)?;
+    if let Some(index) = index {
+        if let Err(err) = index.store(path, &indexed) {
+            warn!(error = box_err(err), path = %path.display(), "unable to index symbols");
+        }
+    }
+    Ok(indexed)
+}
+
+// How well `query` matches `name`, ignoring case. Whole names beat the last path component,
+// which beats prefixes and then substrings, earlier ones first. Failing all of those the query
+// has to be a subsequence, scored by how many of its characters are consecutive.
+pub fn fuzzy_score(query: &str, name: &str) -> Option<u32> {
+    let query = query.to_lowercase();
+    let name = name.to_lowercase();
+    if query.is_empty() {
+        return None;
+    }
+
+    let last_component = name.rsplit("::").next().unwrap_or(&name);
+    if name == query {
+        return Some(1000);
+    }
+    if last_component == query {
+        return Some(900);
+    }
+    if name.starts_with(&query) || last_component.starts_with(&query) {
+        return Some(800);
+    }
+    if let Some(position) = name.find(&query) {
+        return Some(700 - (position as u32).min(199));
+    }
+
+    let mut name_chars = name.chars();
+    let mut consecutive = 0;
+    let mut previous_matched = false;
+    for c in query.chars() {
+        let mut skipped = false;
+        loop {
+            match name_chars.next() {
+                Some(n) if n == c => break,
+                Some(_) => skipped = true,
+                None => return None,
+            }
+        }
+        if previous_matched && !skipped {
+            consecutive += 1;
+        }
+        previous_matched = true;
+    }
+    Some(100 + (consecutive * 300 / query.chars().count() as u32).min(399))
+}
+
+// Demangles Itanium C++ and legacy Rust names as far as their nested name goes, leaving out
+// parameter types and Rust's hash suffix. None for anything else, including names that are
+// only partly understood.
+pub fn demangle(name: &str) -> Option<String> {
+    let mangled = name.strip_prefix("_Z")?;
+
+    // what follows the name are parameter types, which are left out
+    let components = match mangled.strip_prefix('N') {
+        Some(nested) => {
+            let (components, rest) = source_names(nested);
+            rest.strip_prefix('E')?;
+            components
+        }
+        None => vec![source_name(mangled)?.0],
+    };
+    if components.is_empty() {
+        return None;
+    }
+
+    let mut components = components
+        .into_iter()
+        .map(unescape_rust)
+        .collect::<Vec<_>>();
+    // Rust legacy symbols end with h<16 hex digits>
+    if components.len() > 1
+        && components.last().is_some_and(|last| {
+            last.len() == 17
+                && last.starts_with('h')
+                && last[1..].chars().all(|c| c.is_ascii_hexdigit())
+        })
+    {
+        components.pop();
+    }
+    Some(components.join("::"))
+}
+
+// <length><identifier> repeated.
+fn source_names(mut mangled: &str) -> (Vec<&str>, &str) {
+    let mut components = Vec::new();
+    while let Some((component, rest)) = source_name(mangled) {
+        components.push(component);
+        mangled = rest;
+    }
+    (components, mangled)
+}
+
+fn source_name(mangled: &str) -> Option<(&str, &str)> {
+    let digits = mangled.find(|c: char| !c.is_ascii_digit())?;
+    let length = mangled[..digits].parse::<usize>().ok()?;
+    let rest = &mangled[digits..];
+    if length == 0 || rest.len() < length || !rest.is_char_boundary(length) {
+        return None;
+    }
+    Some(rest.split_at(length))
+}
+
+// Rust legacy names escape characters C++ identifiers can't have.
+fn unescape_rust(component: &str) -> String {
+    // an underscore is put in front of escapes at the start
+    let component = component
+        .strip_prefix('_')
+        .filter(|rest| rest.starts_with('$'))
+        .unwrap_or(component);
+    if !component.contains('$') && !component.contains("..") {
+        return component.to_string();
+    }
+
+    [
+        ("$SP$", "@"),
+        ("$BP$", "*"),
+        ("$RF$", "&"),
+        ("$LT$", "<"),
+        ("$GT$", ">"),
+        ("$LP$", "("),
+        ("$RP$", ")"),
+        ("$C$", ","),
+        ("$u20$", " "),
+        ("$u27$", "'"),
+        ("$u5b$", "["),
+        ("$u5d$", "]"),
+        ("$u7b$", "{"),
+        ("$u7d$", "}"),
+        ("$u7e$", "~"),
+        ("..", "::"),
+    ]
+    .into_iter()
+    .fold(component.to_string(), |component, (escape, replacement)| {
+        component.replace(escape, replacement)
+    })
+}
@@ -0,0 +1,383 @@
+use std::{
+    collections::VecDeque,
+    fmt,
+    fs::OpenOptions,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{self, Receiver, SyncSender, TrySendError},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::anyhow;
+
+use nix::{
+    sys::signal::{SigSet, SigmaskHow, Signal},
+    unistd::Pid,
+};
+
+use crate::{
+    expression::Value,
+    register::{Register, RegisterValue},
+    virt_addr::VirtAddr,
+};
+
+pub const DEFAULT_TRACE_BUFFER_CAPACITY: usize = 4096;
+// Frames waiting for the writer of a trace file.
+pub const DEFAULT_TRACE_FILE_QUEUE: usize = 1024;
+pub const DEFAULT_TRACE_FILE_TIMEOUT: Duration = Duration::from_millis(100);
+
+// What a tracepoint collects every time it's hit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TraceAction {
+    // the general purpose registers of the thread that hit it
+    Registers,
+    // `len` bytes at wherever `address` evaluates to at the time
+    Memory { address: String, len: usize },
+    Expression(String),
+}
+
+impl fmt::Display for TraceAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TraceAction::Registers => write!(f, "registers"),
+            TraceAction::Memory { address, len } => write!(f, "memory {}:{}", address, len),
+            TraceAction::Expression(expression) => write!(f, "{}", expression),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CollectedMemory {
+    pub expression: String,
+    // the address and the bytes read there, what went wrong otherwise
+    pub result: Result<(VirtAddr, Vec<u8>), String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CollectedValue {
+    pub expression: String,
+    pub result: Result<Value, String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceFrame {
+    // counts every frame of the session, dropped ones included
+    pub index: usize,
+    pub time: SystemTime,
+    // the id of the breakpoint the tracepoint is
+    pub tracepoint: usize,
+    pub thread: Pid,
+    pub pc: VirtAddr,
+    pub registers: Vec<(Register, RegisterValue)>,
+    pub memory: Vec<CollectedMemory>,
+    pub values: Vec<CollectedValue>,
+}
+
+// What the trace buffer keeps and the trace file streams, a line each.
+pub trait TraceRecord: fmt::Display {
+    fn index(&self) -> usize;
+    // the buffer numbers the records it's given
+    fn set_index(&mut self, index: usize);
+}
+
+impl TraceRecord for TraceFrame {
+    fn index(&self) -> usize {
+        self.index
+    }
+
+    fn set_index(&mut self, index: usize) {
+        self.index = index;
+    }
+}
+
+// A frame on a single line, the way trace files have them:
+//
+//     1700000000.123 frame=3 tracepoint=1 thread=4242 pc=0x1001 rax=0x0 ... $rsp:0x7ff0=0102 $rax+1=1
+impl fmt::Display for TraceFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let time = self
+            .time
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64();
+        write!(
+            f,
+            "{:.3} frame={} tracepoint={} thread={} pc={}",
+            time, self.index, self.tracepoint, self.thread, self.pc
+        )?;
+        for (register, value) in &self.registers {
+            write!(f, " {}={}", register.name(), value)?;
+        }
+        for memory in &self.memory {
+            match &memory.result {
+                Ok((address, bytes)) => {
+                    write!(f, " {}:{}=", memory.expression, address)?;
+                    for byte in bytes {
+                        write!(f, "{:02x}", byte)?;
+                    }
+                }
+                Err(err) => write!(f, " {}=<{}>", memory.expression, err)?,
+            }
+        }
+        for value in &self.values {
+            match &value.result {
+                Ok(x) => write!(f, " {}={}", value.expression, x)?,
+                Err(err) => write!(f, " {}=<{}>", value.expression, err)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+// Frames collected by tracepoints, or other trace records. Once it's full the oldest one is
+// dropped for every new one, a capacity of 0 keeps none of them.
+#[derive(Debug, Clone)]
+pub struct TraceBuffer<T = TraceFrame> {
+    capacity: usize,
+    frames: VecDeque<T>,
+    next_index: usize,
+}
+
+impl Default for TraceBuffer {
+    fn default() -> Self {
+        Self::new(DEFAULT_TRACE_BUFFER_CAPACITY)
+    }
+}
+
+impl<T: TraceRecord> TraceBuffer<T> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            frames: VecDeque::new(),
+            next_index: 0,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    // Drops the oldest frames that don't fit anymore.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        while self.frames.len() > capacity {
+            self.frames.pop_front();
+        }
+    }
+
+    // The index the next frame recorded gets.
+    pub fn next_index(&self) -> usize {
+        self.next_index
+    }
+
+    // The index is filled in here.
+    pub fn record(&mut self, mut frame: T) {
+        if self.capacity > 0 {
+            if self.frames.len() == self.capacity {
+                self.frames.pop_front();
+            }
+            frame.set_index(self.next_index);
+            self.frames.push_back(frame);
+        }
+        self.next_index += 1;
+    }
+
+    // Oldest first.
+    pub fn frames(&self) -> impl DoubleEndedIterator<Item = &T> {
+        self.frames.iter()
+    }
+
+    pub fn frame(&self, index: usize) -> Option<&T> {
+        let first = self.frames.front()?.index();
+        self.frames.get(index.checked_sub(first)?)
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    // How many frames no longer fit.
+    pub fn dropped(&self) -> usize {
+        self.next_index - self.frames.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.frames.clear();
+    }
+}
+
+// What a trace file does with a frame while its writer is behind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backpressure {
+    // drop the frame right away
+    Drop,
+    // wait for the writer up to the timeout, then drop the frame
+    Block(Duration),
+}
+
+impl fmt::Display for Backpressure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Backpressure::Drop => write!(f, "drop"),
+            Backpressure::Block(timeout) => write!(f, "block {}ms", timeout.as_millis()),
+        }
+    }
+}
+
+// Streams frames to a file, a line each. They're written by a thread of its own so a slow disk
+// holds the debuggee up no longer than the backpressure allows.
+#[derive(Debug)]
+pub struct TraceFile {
+    path: PathBuf,
+    backpressure: Backpressure,
+    sender: Option<SyncSender<String>>,
+    writer: Option<JoinHandle<()>>,
+    written: Arc<AtomicUsize>,
+    dropped: usize,
+    error: Arc<Mutex<Option<String>>>,
+}
+
+impl TraceFile {
+    // Appends to `path`, with room for `queue` frames the writer hasn't got to yet.
+    pub fn create<P: AsRef<Path>>(
+        path: P,
+        queue: usize,
+        backpressure: Backpressure,
+    ) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|err| anyhow!("unable to open {}: {}", path.display(), err))?;
+
+        let (sender, receiver) = mpsc::sync_channel(queue);
+        let written = Arc::new(AtomicUsize::new(0));
+        let error = Arc::new(Mutex::new(None));
+        // the writer inherits the mask, so it doesn't take the SIGCHLD a wait on the debuggee
+        // sleeps on
+        let mut sigchld = SigSet::empty();
+        sigchld.add(Signal::SIGCHLD);
+        let previous_mask = sigchld.thread_swap_mask(SigmaskHow::SIG_BLOCK)?;
+        let writer = {
+            let (written, error) = (written.clone(), error.clone());
+            thread::spawn(move || {
+                if let Err(err) = write_lines(receiver, BufWriter::new(file), &written) {
+                    *error.lock().unwrap_or_else(|err| err.into_inner()) = Some(err.to_string());
+                }
+            })
+        };
+        previous_mask.thread_set_mask()?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            backpressure,
+            sender: Some(sender),
+            writer: Some(writer),
+            written,
+            dropped: 0,
+            error,
+        })
+    }
+
+    // Returns whether the frame made it into the queue.
+    pub fn write<R: TraceRecord>(&mut self, frame: &R) -> bool {
+        let Some(sender) = &self.sender else {
+            return false;
+        };
+        let mut line = frame.to_string();
+        let deadline = match self.backpressure {
+            Backpressure::Drop => None,
+            Backpressure::Block(timeout) => Some(Instant::now() + timeout),
+        };
+        loop {
+            match sender.try_send(line) {
+                Ok(()) => return true,
+                Err(TrySendError::Full(rejected))
+                    if deadline.is_some_and(|deadline| Instant::now() < deadline) =>
+                {
+                    line = rejected;
+                    thread::sleep(Duration::from_millis(1));
+                }
+                // full for too long, or the writer gave up
+                Err(_) => {
+                    self.dropped += 1;
+                    return false;
+                }
+            }
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn backpressure(&self) -> Backpressure {
+        self.backpressure
+    }
+
+    pub fn written(&self) -> usize {
+        self.written.load(Ordering::Relaxed)
+    }
+
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+
+    // Why the writer stopped, e.g. because the disk is full.
+    pub fn error(&self) -> Option<String> {
+        self.error
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .clone()
+    }
+
+    // Waits until the frames in the queue are written.
+    pub fn close(mut self) -> anyhow::Result<()> {
+        self.finish();
+        match self.error() {
+            Some(err) => Err(anyhow!("unable to write {}: {}", self.path.display(), err)),
+            None => Ok(()),
+        }
+    }
+
+    fn finish(&mut self) {
+        self.sender = None;
+        if let Some(writer) = self.writer.take() {
+            _ = writer.join();
+        }
+    }
+}
+
+impl Drop for TraceFile {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+// Flushes whenever the queue runs dry, so the file is current while the debuggee is stopped.
+fn write_lines<W: Write>(
+    receiver: Receiver<String>,
+    mut file: W,
+    written: &AtomicUsize,
+) -> std::io::Result<()> {
+    while let Ok(line) = receiver.recv() {
+        writeln!(file, "{}", line)?;
+        written.fetch_add(1, Ordering::Relaxed);
+        while let Ok(line) = receiver.try_recv() {
+            writeln!(file, "{}", line)?;
+            written.fetch_add(1, Ordering::Relaxed);
+        }
+        file.flush()?;
+    }
+    Ok(())
+}
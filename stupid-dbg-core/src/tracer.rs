@@ -0,0 +1,422 @@
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, VecDeque},
+    fmt,
+};
+
+use anyhow::anyhow;
+use nix::{
+    errno::Errno,
+    sys::{
+        ptrace::{self, Options},
+        signal::{kill, Signal},
+        wait::{waitpid, WaitPidFlag, WaitStatus},
+    },
+    unistd::Pid,
+};
+
+use crate::{
+    arch,
+    aux::{
+        as_u8_slice, ptrace_getfpregs, ptrace_getregset, ptrace_listen, ptrace_setfpregs,
+        ptrace_setregset,
+    },
+    memory,
+};
+
+// Everything the debuggee needs from the operating system to control a traced process. The
+// methods take &self since most of them are issued from read-only paths like memory reads.
+pub trait Tracer {
+    fn attach(&self, tid: Pid) -> nix::Result<()>;
+    // attaches without stopping the thread, group-stops are then reported as PTRACE_EVENT_STOP
+    fn seize(&self, tid: Pid, options: Options) -> nix::Result<()>;
+    fn interrupt(&self, tid: Pid) -> nix::Result<()>;
+    // lets a seized thread in group-stop wait for SIGCONT while still reporting events
+    fn listen(&self, tid: Pid) -> nix::Result<()>;
+    fn detach(&self, tid: Pid, signal: Option<Signal>) -> nix::Result<()>;
+    fn set_options(&self, tid: Pid, options: Options) -> nix::Result<()>;
+    fn cont(&self, tid: Pid, signal: Option<Signal>) -> nix::Result<()>;
+    fn step(&self, tid: Pid, signal: Option<Signal>) -> nix::Result<()>;
+    // continues until the next syscall entry or exit
+    fn syscall(&self, tid: Pid, signal: Option<Signal>) -> nix::Result<()>;
+    fn kill(&self, pid: Pid, signal: Signal) -> nix::Result<()>;
+    fn wait(&self, tid: Pid, flags: WaitPidFlag) -> nix::Result<WaitStatus>;
+    fn get_event(&self, tid: Pid) -> nix::Result<libc::c_long>;
+    fn get_siginfo(&self, tid: Pid) -> nix::Result<libc::siginfo_t>;
+    fn get_regs(&self, tid: Pid) -> nix::Result<libc::user_regs_struct>;
+    fn set_regs(&self, tid: Pid, regs: libc::user_regs_struct) -> nix::Result<()>;
+    fn get_fpregs(&self, tid: Pid) -> nix::Result<libc::user_fpregs_struct>;
+    fn set_fpregs(&self, tid: Pid, fpregs: libc::user_fpregs_struct) -> nix::Result<()>;
+    // PTRACE_GETREGSET, returns the number of bytes written to `buf`
+    fn get_regset(&self, tid: Pid, note_type: i32, buf: &mut [u8]) -> nix::Result<usize>;
+    fn set_regset(&self, tid: Pid, note_type: i32, data: &[u8]) -> nix::Result<()>;
+    fn read_user(&self, tid: Pid, offset: usize) -> nix::Result<libc::c_long>;
+    fn write_user(&self, tid: Pid, offset: usize, data: libc::c_long) -> nix::Result<()>;
+    fn read_memory(&self, pid: Pid, addr: u64, buf: &mut [u8]) -> anyhow::Result<()>;
+    fn write_memory(&self, pid: Pid, addr: u64, data: &[u8]) -> anyhow::Result<()>;
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PtraceTracer;
+
+impl Tracer for PtraceTracer {
+    fn attach(&self, tid: Pid) -> nix::Result<()> {
+        ptrace::attach(tid)
+    }
+
+    fn seize(&self, tid: Pid, options: Options) -> nix::Result<()> {
+        ptrace::seize(tid, options)
+    }
+
+    fn interrupt(&self, tid: Pid) -> nix::Result<()> {
+        ptrace::interrupt(tid)
+    }
+
+    fn listen(&self, tid: Pid) -> nix::Result<()> {
+        ptrace_listen(tid)
+    }
+
+    fn detach(&self, tid: Pid, signal: Option<Signal>) -> nix::Result<()> {
+        ptrace::detach(tid, signal)
+    }
+
+    fn set_options(&self, tid: Pid, options: Options) -> nix::Result<()> {
+        ptrace::setoptions(tid, options)
+    }
+
+    fn cont(&self, tid: Pid, signal: Option<Signal>) -> nix::Result<()> {
+        ptrace::cont(tid, signal)
+    }
+
+    fn step(&self, tid: Pid, signal: Option<Signal>) -> nix::Result<()> {
+        ptrace::step(tid, signal)
+    }
+
+    fn syscall(&self, tid: Pid, signal: Option<Signal>) -> nix::Result<()> {
+        ptrace::syscall(tid, signal)
+    }
+
+    fn kill(&self, pid: Pid, signal: Signal) -> nix::Result<()> {
+        kill(pid, signal)
+    }
+
+    fn wait(&self, tid: Pid, flags: WaitPidFlag) -> nix::Result<WaitStatus> {
+        waitpid(tid, Some(flags))
+    }
+
+    fn get_event(&self, tid: Pid) -> nix::Result<libc::c_long> {
+        ptrace::getevent(tid)
+    }
+
+    fn get_siginfo(&self, tid: Pid) -> nix::Result<libc::siginfo_t> {
+        ptrace::getsiginfo(tid)
+    }
+
+    fn get_regs(&self, tid: Pid) -> nix::Result<libc::user_regs_struct> {
+        ptrace::getregs(tid)
+    }
+
+    fn set_regs(&self, tid: Pid, regs: libc::user_regs_struct) -> nix::Result<()> {
+        ptrace::setregs(tid, regs)
+    }
+
+    fn get_fpregs(&self, tid: Pid) -> nix::Result<libc::user_fpregs_struct> {
+        ptrace_getfpregs(tid)
+    }
+
+    fn set_fpregs(&self, tid: Pid, fpregs: libc::user_fpregs_struct) -> nix::Result<()> {
+        ptrace_setfpregs(tid, &fpregs)
+    }
+
+    fn get_regset(&self, tid: Pid, note_type: i32, buf: &mut [u8]) -> nix::Result<usize> {
+        ptrace_getregset(tid, note_type, buf)
+    }
+
+    fn set_regset(&self, tid: Pid, note_type: i32, data: &[u8]) -> nix::Result<()> {
+        ptrace_setregset(tid, note_type, data)
+    }
+
+    fn read_user(&self, tid: Pid, offset: usize) -> nix::Result<libc::c_long> {
+        ptrace::read_user(tid, offset as *mut libc::c_void)
+    }
+
+    fn write_user(&self, tid: Pid, offset: usize, data: libc::c_long) -> nix::Result<()> {
+        ptrace::write_user(tid, offset as *mut libc::c_void, data)
+    }
+
+    fn read_memory(&self, pid: Pid, addr: u64, buf: &mut [u8]) -> anyhow::Result<()> {
+        memory::read_memory(pid, addr, buf)
+    }
+
+    fn write_memory(&self, pid: Pid, addr: u64, data: &[u8]) -> anyhow::Result<()> {
+        memory::write_memory(pid, addr, data)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TracerCall {
+    Attach(Pid),
+    Seize(Pid, Options),
+    Interrupt(Pid),
+    Listen(Pid),
+    Detach(Pid, Option<Signal>),
+    SetOptions(Pid, Options),
+    Cont(Pid, Option<Signal>),
+    Step(Pid, Option<Signal>),
+    Syscall(Pid, Option<Signal>),
+    Kill(Pid, Signal),
+    // only the pc is kept
+    SetRegs(Pid, u64),
+    SetFpRegs(Pid),
+    SetRegset(Pid, i32),
+    WriteUser(Pid, usize, libc::c_long),
+    WriteMemory(Pid, u64, Vec<u8>),
+}
+
+#[derive(Default)]
+struct ScriptedState {
+    wait_statuses: VecDeque<WaitStatus>,
+    events: VecDeque<libc::c_long>,
+    siginfos: BTreeMap<Pid, libc::siginfo_t>,
+    regs: BTreeMap<Pid, libc::user_regs_struct>,
+    fpregs: BTreeMap<Pid, libc::user_fpregs_struct>,
+    // register sets besides the general purpose one
+    regsets: BTreeMap<(Pid, i32), Vec<u8>>,
+    user: BTreeMap<(Pid, usize), libc::c_long>,
+    memory: BTreeMap<u64, u8>,
+    calls: Vec<TracerCall>,
+}
+
+// A tracer that doesn't touch any real process. Wait statuses are handed out in the order they
+// were scripted, registers are kept per thread and memory is a sparse map of bytes. Every call
+// that would change the tracee is recorded so tests can assert on it.
+#[derive(Default)]
+pub struct ScriptedTracer {
+    state: RefCell<ScriptedState>,
+}
+
+impl fmt::Debug for ScriptedTracer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ScriptedTracer")
+            .field("calls", &self.state.borrow().calls)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ScriptedTracer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push_wait_status(&self, wait_status: WaitStatus) {
+        self.state.borrow_mut().wait_statuses.push_back(wait_status);
+    }
+
+    pub fn push_event(&self, event: libc::c_long) {
+        self.state.borrow_mut().events.push_back(event);
+    }
+
+    pub fn set_siginfo(&self, tid: Pid, siginfo: libc::siginfo_t) {
+        self.state.borrow_mut().siginfos.insert(tid, siginfo);
+    }
+
+    pub fn set_thread_regs(&self, tid: Pid, regs: libc::user_regs_struct) {
+        self.state.borrow_mut().regs.insert(tid, regs);
+    }
+
+    pub fn regs(&self, tid: Pid) -> Option<libc::user_regs_struct> {
+        self.state.borrow().regs.get(&tid).copied()
+    }
+
+    pub fn set_thread_regset(&self, tid: Pid, note_type: i32, data: &[u8]) {
+        self.state
+            .borrow_mut()
+            .regsets
+            .insert((tid, note_type), data.to_vec());
+    }
+
+    pub fn regset(&self, tid: Pid, note_type: i32) -> Option<Vec<u8>> {
+        self.state.borrow().regsets.get(&(tid, note_type)).cloned()
+    }
+
+    pub fn map_memory(&self, addr: u64, data: &[u8]) {
+        let mut state = self.state.borrow_mut();
+        for (offset, byte) in data.iter().enumerate() {
+            state.memory.insert(addr + offset as u64, *byte);
+        }
+    }
+
+    pub fn memory(&self, addr: u64, len: usize) -> Option<Vec<u8>> {
+        let state = self.state.borrow();
+        (addr..addr + len as u64)
+            .map(|addr| state.memory.get(&addr).copied())
+            .collect()
+    }
+
+    pub fn take_calls(&self) -> Vec<TracerCall> {
+        std::mem::take(&mut self.state.borrow_mut().calls)
+    }
+
+    fn record(&self, call: TracerCall) {
+        self.state.borrow_mut().calls.push(call);
+    }
+}
+
+impl Tracer for ScriptedTracer {
+    fn attach(&self, tid: Pid) -> nix::Result<()> {
+        self.record(TracerCall::Attach(tid));
+        Ok(())
+    }
+
+    fn seize(&self, tid: Pid, options: Options) -> nix::Result<()> {
+        self.record(TracerCall::Seize(tid, options));
+        Ok(())
+    }
+
+    fn interrupt(&self, tid: Pid) -> nix::Result<()> {
+        self.record(TracerCall::Interrupt(tid));
+        Ok(())
+    }
+
+    fn listen(&self, tid: Pid) -> nix::Result<()> {
+        self.record(TracerCall::Listen(tid));
+        Ok(())
+    }
+
+    fn detach(&self, tid: Pid, signal: Option<Signal>) -> nix::Result<()> {
+        self.record(TracerCall::Detach(tid, signal));
+        Ok(())
+    }
+
+    fn set_options(&self, tid: Pid, options: Options) -> nix::Result<()> {
+        self.record(TracerCall::SetOptions(tid, options));
+        Ok(())
+    }
+
+    fn cont(&self, tid: Pid, signal: Option<Signal>) -> nix::Result<()> {
+        self.record(TracerCall::Cont(tid, signal));
+        Ok(())
+    }
+
+    fn step(&self, tid: Pid, signal: Option<Signal>) -> nix::Result<()> {
+        self.record(TracerCall::Step(tid, signal));
+        Ok(())
+    }
+
+    fn syscall(&self, tid: Pid, signal: Option<Signal>) -> nix::Result<()> {
+        self.record(TracerCall::Syscall(tid, signal));
+        Ok(())
+    }
+
+    fn kill(&self, pid: Pid, signal: Signal) -> nix::Result<()> {
+        self.record(TracerCall::Kill(pid, signal));
+        Ok(())
+    }
+
+    // Runs out of script as if every thread were gone.
+    fn wait(&self, _tid: Pid, flags: WaitPidFlag) -> nix::Result<WaitStatus> {
+        match self.state.borrow_mut().wait_statuses.pop_front() {
+            Some(wait_status) => Ok(wait_status),
+            None if flags.contains(WaitPidFlag::WNOHANG) => Ok(WaitStatus::StillAlive),
+            None => Err(Errno::ECHILD),
+        }
+    }
+
+    fn get_event(&self, _tid: Pid) -> nix::Result<libc::c_long> {
+        self.state
+            .borrow_mut()
+            .events
+            .pop_front()
+            .ok_or(Errno::ESRCH)
+    }
+
+    fn get_siginfo(&self, tid: Pid) -> nix::Result<libc::siginfo_t> {
+        self.state
+            .borrow()
+            .siginfos
+            .get(&tid)
+            .copied()
+            .ok_or(Errno::ESRCH)
+    }
+
+    fn get_regs(&self, tid: Pid) -> nix::Result<libc::user_regs_struct> {
+        self.regs(tid).ok_or(Errno::ESRCH)
+    }
+
+    fn set_regs(&self, tid: Pid, regs: libc::user_regs_struct) -> nix::Result<()> {
+        self.record(TracerCall::SetRegs(tid, arch::native().pc(&regs)));
+        self.state.borrow_mut().regs.insert(tid, regs);
+        Ok(())
+    }
+
+    // Zeroed until set.
+    fn get_fpregs(&self, tid: Pid) -> nix::Result<libc::user_fpregs_struct> {
+        Ok(self
+            .state
+            .borrow()
+            .fpregs
+            .get(&tid)
+            .copied()
+            .unwrap_or(unsafe { std::mem::zeroed() }))
+    }
+
+    fn set_fpregs(&self, tid: Pid, fpregs: libc::user_fpregs_struct) -> nix::Result<()> {
+        self.record(TracerCall::SetFpRegs(tid));
+        self.state.borrow_mut().fpregs.insert(tid, fpregs);
+        Ok(())
+    }
+
+    // The general purpose set and whatever was set, as on a CPU without XSAVE unless it's set.
+    fn get_regset(&self, tid: Pid, note_type: i32, buf: &mut [u8]) -> nix::Result<usize> {
+        let data = match note_type {
+            libc::NT_PRSTATUS => {
+                let regs = self.get_regs(tid)?;
+                unsafe { as_u8_slice(&regs) }.to_vec()
+            }
+            _ => self.regset(tid, note_type).ok_or(Errno::EINVAL)?,
+        };
+        let len = data.len().min(buf.len());
+        buf[..len].copy_from_slice(&data[..len]);
+        Ok(len)
+    }
+
+    fn set_regset(&self, tid: Pid, note_type: i32, data: &[u8]) -> nix::Result<()> {
+        self.record(TracerCall::SetRegset(tid, note_type));
+        self.set_thread_regset(tid, note_type, data);
+        Ok(())
+    }
+
+    // Everything not written before reads as zero.
+    fn read_user(&self, tid: Pid, offset: usize) -> nix::Result<libc::c_long> {
+        Ok(self
+            .state
+            .borrow()
+            .user
+            .get(&(tid, offset))
+            .copied()
+            .unwrap_or(0))
+    }
+
+    fn write_user(&self, tid: Pid, offset: usize, data: libc::c_long) -> nix::Result<()> {
+        self.record(TracerCall::WriteUser(tid, offset, data));
+        self.state.borrow_mut().user.insert((tid, offset), data);
+        Ok(())
+    }
+
+    fn read_memory(&self, _pid: Pid, addr: u64, buf: &mut [u8]) -> anyhow::Result<()> {
+        let data = self
+            .memory(addr, buf.len())
+            .ok_or(anyhow!("unmapped memory at {:#x}", addr))?;
+        buf.copy_from_slice(&data);
+        Ok(())
+    }
+
+    fn write_memory(&self, pid: Pid, addr: u64, data: &[u8]) -> anyhow::Result<()> {
+        if self.memory(addr, data.len()).is_none() {
+            Err(anyhow!("unmapped memory at {:#x}", addr))?;
+        }
+        self.record(TracerCall::WriteMemory(pid, addr, data.to_vec()));
+        self.map_memory(addr, data);
+        Ok(())
+    }
+}
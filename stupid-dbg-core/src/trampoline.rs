@@ -0,0 +1,85 @@
+use std::ops::Range;
+
+use tracing::{debug, warn};
+
+use crate::{
+    aux::box_err, elf::ElfFile, memory_map::MemoryMap, namespace::ProcessRoot, virt_addr::VirtAddr,
+};
+
+// Sections of the stubs calls into other modules go through.
+const PLT_SECTIONS: [&str; 4] = [".plt", ".plt.got", ".plt.sec", ".iplt"];
+
+// Code a call passes through on its way to a function of another module: the PLT stubs of every
+// module, and all of the dynamic linker, which the stubs jump into to resolve their target the
+// first time.
+#[derive(Debug, Clone, Default)]
+pub struct Trampolines {
+    ranges: Vec<Range<VirtAddr>>,
+}
+
+impl Trampolines {
+    // Those of every module mapped right now. The dynamic linker is the module mapped at
+    // `interpreter_base`, static executables have none.
+    pub fn load(
+        memory_map: &MemoryMap,
+        root: &ProcessRoot,
+        interpreter_base: Option<VirtAddr>,
+    ) -> Self {
+        let mut ranges = Vec::new();
+        for (module, base) in memory_map.modules() {
+            if Some(base) == interpreter_base {
+                debug!(module = %module, "dynamic linker found");
+                ranges.extend(
+                    memory_map
+                        .regions()
+                        .iter()
+                        .filter(|region| {
+                            region.path.as_deref() == Some(module) && region.permissions.execute
+                        })
+                        .map(|region| region.start..region.end),
+                );
+                continue;
+            }
+
+            match ElfFile::read(root.module_path(memory_map, module)) {
+                Ok(elf) => {
+                    let bias = if elf.is_position_independent() {
+                        base.as_u64().wrapping_sub(elf.link_base())
+                    } else {
+                        0
+                    };
+                    ranges.extend(Self::plt_sections(&elf, bias));
+                }
+                Err(err) => {
+                    warn!(error = box_err(err), module = %module, "unable to find PLT sections")
+                }
+            }
+        }
+
+        Self::from_ranges(ranges)
+    }
+
+    pub fn from_ranges(ranges: Vec<Range<VirtAddr>>) -> Self {
+        Self { ranges }
+    }
+
+    // The PLT sections of `elf`, moved by `bias`.
+    pub fn plt_sections(elf: &ElfFile, bias: u64) -> Vec<Range<VirtAddr>> {
+        elf.sections()
+            .iter()
+            .filter(|section| PLT_SECTIONS.contains(&section.name.as_str()) && section.size > 0)
+            .map(|section| {
+                let start = section.address.wrapping_add(bias);
+                VirtAddr::new(start)..VirtAddr::new(start + section.size)
+            })
+            .collect()
+    }
+
+    pub fn ranges(&self) -> &[Range<VirtAddr>] {
+        &self.ranges
+    }
+
+    pub fn contains(&self, addr: VirtAddr) -> bool {
+        self.ranges.iter().any(|range| range.contains(&addr))
+    }
+}
@@ -0,0 +1,68 @@
+use std::{
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+};
+
+use tracing::{debug, info};
+
+// Sections smaller than this are parsed on the calling thread, bigger ones by a worker per CPU,
+// with progress reported past PROGRESS_REPORT_SIZE.
+pub const PARALLEL_PARSE_SIZE: usize = 1 << 20;
+pub const PROGRESS_REPORT_SIZE: usize = 64 << 20;
+
+// What `parse` gets out of every unit of a debug info section `size` bytes long, e.g. the line
+// number programs of .debug_line. Units are independent of each other, so big sections are split
+// between threads taking the next unit left until there's none, and what comes back is in no
+// particular order then. A module's units are meant to be parsed the first time it's looked up
+// rather than when it's mapped, so only the modules a session touches get parsed at all.
+pub fn parse_units<U, T, F>(
+    module: &str,
+    size: usize,
+    units: &[U],
+    parse: F,
+) -> anyhow::Result<Vec<T>>
+where
+    U: Sync,
+    T: Send,
+    F: Fn(&U, &mut Vec<T>) -> anyhow::Result<()> + Sync,
+{
+    let report_progress = size >= PROGRESS_REPORT_SIZE;
+    let next = AtomicUsize::new(0);
+    let done = AtomicUsize::new(0);
+    let work = || -> anyhow::Result<Vec<T>> {
+        let mut parsed = Vec::new();
+        while let Some(unit) = units.get(next.fetch_add(1, Ordering::Relaxed)) {
+            parse(unit, &mut parsed)?;
+            let done = done.fetch_add(1, Ordering::Relaxed) + 1;
+            if report_progress && done * 10 / units.len() != (done - 1) * 10 / units.len() {
+                info!(
+                    module,
+                    units = done,
+                    total = units.len(),
+                    "indexing debug info"
+                );
+            }
+        }
+        Ok(parsed)
+    };
+
+    if size < PARALLEL_PARSE_SIZE {
+        return work();
+    }
+    let threads = thread::available_parallelism().map_or(1, usize::from);
+    debug!(
+        module,
+        units = units.len(),
+        threads,
+        "parsing units in parallel"
+    );
+    let parsed = thread::scope(|scope| {
+        (0..threads)
+            .map(|_| scope.spawn(work))
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|worker| worker.join().expect("unit parser panicked"))
+            .collect::<anyhow::Result<Vec<_>>>()
+    })?;
+    Ok(parsed.into_iter().flatten().collect())
+}
@@ -0,0 +1,129 @@
+use std::{fs, path::Path, time::Duration};
+
+use anyhow::anyhow;
+use nix::unistd::Pid;
+use serde::Serialize;
+
+use crate::{symbols::Symbol, virt_addr::VirtAddr};
+
+// How an unattended run ended, each with an exit code of its own. 1 is left for the debugger
+// failing itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Verdict {
+    // exited on its own, whatever its status
+    Exited,
+    // killed by a signal
+    Crashed,
+    // still running once the timeout passed, killed by the debugger
+    TimedOut,
+}
+
+impl Verdict {
+    pub fn exit_code(self) -> i32 {
+        match self {
+            Verdict::Exited => 0,
+            Verdict::Crashed => 2,
+            Verdict::TimedOut => 3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Frame {
+    // hex, since JSON numbers can't hold every address
+    pub address: String,
+    // `name+0x10`
+    pub function: Option<String>,
+    pub module: Option<String>,
+}
+
+impl Frame {
+    // `symbol` is the one `address` is in and the offset into it.
+    pub fn new(address: VirtAddr, symbol: Option<(Symbol, u64)>) -> Self {
+        Self {
+            address: address.to_string(),
+            function: symbol.as_ref().map(|(symbol, offset)| match offset {
+                0 => symbol.display_name().to_string(),
+                offset => format!("{}+{:#x}", symbol.display_name(), offset),
+            }),
+            module: symbol.map(|(symbol, _)| symbol.module),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ThreadReport {
+    pub tid: i32,
+    // innermost first
+    pub frames: Vec<Frame>,
+    // why there are no frames, e.g. because the thread was running
+    pub error: Option<String>,
+}
+
+impl ThreadReport {
+    // `lookup` finds the symbol of a frame, like `Debuggee::lookup_symbol`.
+    pub fn new(
+        tid: Pid,
+        backtrace: anyhow::Result<Vec<VirtAddr>>,
+        lookup: impl Fn(VirtAddr) -> Option<(Symbol, u64)>,
+    ) -> Self {
+        let (frames, error) = match backtrace {
+            Ok(backtrace) => (
+                backtrace
+                    .into_iter()
+                    .map(|address| Frame::new(address, lookup(address)))
+                    .collect(),
+                None,
+            ),
+            Err(err) => (Vec::new(), Some(err.to_string())),
+        };
+        Self {
+            tid: tid.as_raw(),
+            frames,
+            error,
+        }
+    }
+}
+
+// The one thing verdict mode prints, for crash triage in CI.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct VerdictReport {
+    pub verdict: Verdict,
+    pub pid: i32,
+    // how long the debuggee ran under the debugger
+    pub duration_ms: u64,
+    pub exit_code: Option<i32>,
+    // the signal that killed it
+    pub signal: Option<String>,
+    // the thread the fatal signal hit, and where it hit if it's a fault
+    pub crashed_thread: Option<i32>,
+    pub fault_address: Option<String>,
+    // every thread as it was when the fatal signal hit or the time ran out
+    pub threads: Vec<ThreadReport>,
+}
+
+impl VerdictReport {
+    pub fn new(verdict: Verdict, pid: Pid, duration: Duration) -> Self {
+        Self {
+            verdict,
+            pid: pid.as_raw(),
+            duration_ms: duration.as_millis() as u64,
+            exit_code: None,
+            signal: None,
+            crashed_thread: None,
+            fault_address: None,
+            threads: Vec::new(),
+        }
+    }
+
+    pub fn to_json(&self) -> anyhow::Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        fs::write(path, self.to_json()? + "\n")
+            .map_err(|err| anyhow!("unable to write {}: {}", path.display(), err))
+    }
+}
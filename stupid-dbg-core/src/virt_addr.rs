@@ -0,0 +1,109 @@
+use std::{
+    fmt,
+    ops::{Add, Range, Sub},
+};
+
+use serde::{Deserialize, Serialize};
+
+// An address in the virtual address space of the debuggee, as opposed to a file offset or an
+// address relative to a module.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct VirtAddr(u64);
+
+impl VirtAddr {
+    pub const fn new(addr: u64) -> Self {
+        Self(addr)
+    }
+
+    pub const fn as_u64(self) -> u64 {
+        self.0
+    }
+
+    pub fn checked_add(self, offset: u64) -> Option<Self> {
+        self.0.checked_add(offset).map(Self)
+    }
+
+    pub fn checked_sub(self, offset: u64) -> Option<Self> {
+        self.0.checked_sub(offset).map(Self)
+    }
+
+    pub fn wrapping_sub(self, offset: u64) -> Self {
+        Self(self.0.wrapping_sub(offset))
+    }
+
+    pub fn saturating_add(self, offset: u64) -> Self {
+        Self(self.0.saturating_add(offset))
+    }
+
+    // Distance from `base` up to this address, None if the address is below `base`.
+    pub fn offset_from(self, base: VirtAddr) -> Option<u64> {
+        self.0.checked_sub(base.0)
+    }
+
+    // `align` must be a power of two.
+    pub fn align_down(self, align: u64) -> Self {
+        debug_assert!(align.is_power_of_two());
+        Self(self.0 & !(align - 1))
+    }
+
+    // `align` must be a power of two. None on overflow.
+    pub fn align_up(self, align: u64) -> Option<Self> {
+        debug_assert!(align.is_power_of_two());
+        self.0
+            .checked_add(align - 1)
+            .map(|addr| Self(addr & !(align - 1)))
+    }
+
+    pub fn is_aligned(self, align: u64) -> bool {
+        debug_assert!(align.is_power_of_two());
+        self.0 & (align - 1) == 0
+    }
+
+    // The range of `len` bytes starting at this address, clamped at the top of the address space.
+    pub fn range(self, len: u64) -> Range<VirtAddr> {
+        self..self.saturating_add(len)
+    }
+}
+
+impl From<u64> for VirtAddr {
+    fn from(addr: u64) -> Self {
+        Self(addr)
+    }
+}
+
+impl From<VirtAddr> for u64 {
+    fn from(addr: VirtAddr) -> Self {
+        addr.0
+    }
+}
+
+impl Add<u64> for VirtAddr {
+    type Output = VirtAddr;
+
+    fn add(self, offset: u64) -> Self::Output {
+        Self(self.0 + offset)
+    }
+}
+
+impl Sub<u64> for VirtAddr {
+    type Output = VirtAddr;
+
+    fn sub(self, offset: u64) -> Self::Output {
+        Self(self.0 - offset)
+    }
+}
+
+impl fmt::Display for VirtAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#x}", self.0)
+    }
+}
+
+impl fmt::LowerHex for VirtAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::LowerHex::fmt(&self.0, f)
+    }
+}
@@ -0,0 +1,178 @@
+use std::{fmt, ops::Range};
+
+use nix::unistd::Pid;
+
+use crate::{debug_register::WatchKind, virt_addr::VirtAddr};
+
+// Leaf functions of the x86-64 System V ABI may keep locals this far below the stack pointer.
+pub const STACK_RED_ZONE: u64 = 128;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchStrategy {
+    // the range is split across this many debug registers
+    Hardware { slots: usize },
+    // the current thread is single stepped and the range compared after every instruction
+    Software,
+    // the pages holding the range are write protected and every fault on them checked
+    PageProtection { pages: usize },
+}
+
+impl fmt::Display for WatchStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WatchStrategy::Hardware { slots: 1 } => write!(f, "hardware, 1 debug register"),
+            WatchStrategy::Hardware { slots } => write!(f, "hardware, {} debug registers", slots),
+            WatchStrategy::Software => write!(f, "software, single stepping"),
+            WatchStrategy::PageProtection { pages: 1 } => write!(f, "page protection, 1 page"),
+            WatchStrategy::PageProtection { pages } => {
+                write!(f, "page protection, {} pages", pages)
+            }
+        }
+    }
+}
+
+// The frame a watchpoint on a stack local belongs to. Without an unwinder the frame is told by the
+// stack pointer of its thread: once that is above the watched range, red zone included, whatever
+// frame the local lived in has returned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WatchScope {
+    pub thread: Pid,
+    // the stack pointer when the watchpoint was set
+    pub stack_pointer: VirtAddr,
+}
+
+impl WatchScope {
+    pub fn is_popped(&self, address: VirtAddr, size: usize, stack_pointer: VirtAddr) -> bool {
+        let end = address.saturating_add(size as u64);
+        stack_pointer
+            .checked_sub(STACK_RED_ZONE)
+            .is_some_and(|live| end <= live)
+    }
+}
+
+impl fmt::Display for WatchScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "frame of thread {} below {}",
+            self.thread, self.stack_pointer
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Watchpoint {
+    id: usize,
+    address: VirtAddr,
+    size: usize,
+    kind: WatchKind,
+    strategy: WatchStrategy,
+    scope: Option<WatchScope>,
+    // contents before the last step, software watchpoints only
+    value: Option<Vec<u8>>,
+}
+
+impl Watchpoint {
+    pub(crate) fn new(
+        id: usize,
+        address: VirtAddr,
+        size: usize,
+        kind: WatchKind,
+        strategy: WatchStrategy,
+        scope: Option<WatchScope>,
+    ) -> Self {
+        Self {
+            id,
+            address,
+            size,
+            kind,
+            strategy,
+            scope,
+            value: None,
+        }
+    }
+
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    pub fn address(&self) -> VirtAddr {
+        self.address
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn kind(&self) -> WatchKind {
+        self.kind
+    }
+
+    pub fn strategy(&self) -> WatchStrategy {
+        self.strategy
+    }
+
+    // Set for ranges on the stack of a thread, which are deleted once their frame returns.
+    pub fn scope(&self) -> Option<WatchScope> {
+        self.scope
+    }
+
+    pub fn is_software(&self) -> bool {
+        self.strategy == WatchStrategy::Software
+    }
+
+    pub fn is_page_protected(&self) -> bool {
+        matches!(self.strategy, WatchStrategy::PageProtection { .. })
+    }
+
+    pub fn contains(&self, address: VirtAddr) -> bool {
+        self.address.range(self.size as u64).contains(&address)
+    }
+
+    pub fn pages(&self, page_size: u64) -> Range<VirtAddr> {
+        watched_pages(self.address, self.size, page_size)
+    }
+
+    // Remembers the current contents, returns whether they differ from the previous ones.
+    pub(crate) fn update_value(&mut self, value: Vec<u8>) -> bool {
+        let changed = self.value.as_ref().is_some_and(|old| *old != value);
+        self.value = Some(value);
+        changed
+    }
+}
+
+// Memory a syscall has the kernel write its results to. A write protected page there doesn't
+// fault, the syscall fails with EFAULT instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyscallBuffer {
+    // `len` bytes at `address`
+    Flat { address: VirtAddr, len: u64 },
+    // `count` struct iovec at `iov`, filled in order
+    Vectored { iov: VirtAddr, count: u64 },
+}
+
+// Only syscalls reading into a buffer of the caller are known, any other one writing into a
+// watched page still fails.
+pub fn syscall_buffer(number: libc::c_long, args: &[u64; 6]) -> Option<SyscallBuffer> {
+    match number {
+        libc::SYS_read | libc::SYS_pread64 | libc::SYS_recvfrom => Some(SyscallBuffer::Flat {
+            address: VirtAddr::new(args[1]),
+            len: args[2],
+        }),
+        libc::SYS_getrandom => Some(SyscallBuffer::Flat {
+            address: VirtAddr::new(args[0]),
+            len: args[1],
+        }),
+        libc::SYS_readv | libc::SYS_preadv | libc::SYS_preadv2 => Some(SyscallBuffer::Vectored {
+            iov: VirtAddr::new(args[1]),
+            count: args[2],
+        }),
+        _ => None,
+    }
+}
+
+// The whole pages `size` bytes at `address` are on.
+pub fn watched_pages(address: VirtAddr, size: usize, page_size: u64) -> Range<VirtAddr> {
+    let end = address.saturating_add(size as u64);
+    address.align_down(page_size)..end.align_up(page_size).unwrap_or(end.align_down(page_size))
+}
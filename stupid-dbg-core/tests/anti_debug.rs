@@ -0,0 +1,17 @@
+use stupid_dbg_core::anti_debug::is_status_path;
+
+#[test]
+fn status_paths() {
+    assert!(is_status_path("/proc/self/status"));
+    assert!(is_status_path("/proc/thread-self/status"));
+    assert!(is_status_path("/proc/1234/status"));
+    assert!(is_status_path("/proc/self/task/1235/status"));
+    assert!(is_status_path("/proc/1234/task/1235/status"));
+
+    assert!(!is_status_path("/proc/self/stat"));
+    assert!(!is_status_path("/proc/self/maps"));
+    assert!(!is_status_path("/proc/status"));
+    assert!(!is_status_path("/proc/12a4/status"));
+    assert!(!is_status_path("/proc/self/task/self/status"));
+    assert!(!is_status_path("/home/proc/self/status"));
+}
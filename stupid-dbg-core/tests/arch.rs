@@ -0,0 +1,53 @@
+use stupid_dbg_core::arch::{self, PointerWidth};
+
+fn elf_header(class: u8, machine: u16) -> [u8; 20] {
+    let mut header = [0u8; 20];
+    header[..4].copy_from_slice(b"\x7fELF");
+    header[4] = class;
+    header[18..].copy_from_slice(&machine.to_ne_bytes());
+    header
+}
+
+#[test]
+fn native_arch_from_elf_header() {
+    let arch = arch::from_elf_header(&elf_header(2, 62)).unwrap();
+    assert_eq!(arch.name(), "x86_64");
+    assert_eq!(arch.pointer_width(), PointerWidth::Bits64);
+    assert_eq!(arch.breakpoint_instruction(), &[0xcc]);
+}
+
+#[test]
+fn compat_arch_from_elf_header() {
+    let arch = arch::from_elf_header(&elf_header(1, 3)).unwrap();
+    assert_eq!(arch.name(), "i386");
+    assert_eq!(arch.pointer_width(), PointerWidth::Bits32);
+}
+
+#[test]
+fn foreign_arch_is_rejected() {
+    assert!(arch::from_elf_header(&elf_header(2, 183)).is_err());
+    assert!(arch::from_elf_header(b"#!/bin/sh\n").is_err());
+}
+
+#[test]
+fn call_lengths() {
+    let x86_64 = arch::from_elf_header(&elf_header(2, 62)).unwrap();
+    // call rel32
+    assert_eq!(x86_64.call_length(&[0xe8, 0, 0, 0, 0, 0x90]), Some(5));
+    // call *0x0(%rip)
+    assert_eq!(x86_64.call_length(&[0xff, 0x15, 0, 0, 0, 0]), Some(6));
+    // call *%rax, call *%r11
+    assert_eq!(x86_64.call_length(&[0xff, 0xd0]), Some(2));
+    assert_eq!(x86_64.call_length(&[0x41, 0xff, 0xd3]), Some(3));
+    // notrack call *(%rax,%rbx,8), call *0x8(%rbp)
+    assert_eq!(x86_64.call_length(&[0x3e, 0xff, 0x14, 0xd8]), Some(4));
+    assert_eq!(x86_64.call_length(&[0xff, 0x55, 0x08]), Some(3));
+    // jmp *%rax, and a call cut short
+    assert_eq!(x86_64.call_length(&[0xff, 0xe0]), None);
+    assert_eq!(x86_64.call_length(&[0xe8, 0, 0]), None);
+
+    // 0x41 is inc %ecx
+    let i386 = arch::from_elf_header(&elf_header(1, 3)).unwrap();
+    assert_eq!(i386.call_length(&[0x41, 0xff, 0xd3]), None);
+    assert_eq!(i386.call_length(&[0xff, 0xd3]), Some(2));
+}
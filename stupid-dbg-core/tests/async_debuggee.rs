@@ -0,0 +1,101 @@
+#![cfg(feature = "async")]
+
+use futures_util::StreamExt;
+use nix::sys::signal::Signal;
+use nonempty::nonempty;
+use stupid_dbg_core::{
+    async_debuggee::AsyncDebuggee,
+    debuggee,
+    launch::{LaunchSpec, Stdio},
+    session::DebugEvent,
+    tracepoint::TraceAction,
+    virt_addr::VirtAddr,
+};
+
+mod aux;
+
+#[tokio::test]
+async fn program_exiting_immediately_reports_exit() {
+    let mut debuggee =
+        AsyncDebuggee::new(debuggee::Config::SpawnChild(LaunchSpec::new(nonempty![
+            aux::get_program_exiting_immediately()
+        ])))
+        .unwrap();
+
+    let events = debuggee
+        .events()
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .collect::<anyhow::Result<Vec<_>>>()
+        .unwrap();
+
+    assert_eq!(
+        events.last(),
+        Some(&DebugEvent::Exited {
+            status_code: Some(0)
+        })
+    );
+}
+
+#[tokio::test]
+async fn signals_are_delivered_after_being_reported() {
+    let mut debuggee = AsyncDebuggee::new(debuggee::Config::SpawnChild(
+        LaunchSpec::new(nonempty![
+            "sh".to_string(),
+            "-c".to_string(),
+            "kill -SEGV $$; echo survived".to_string()
+        ])
+        .stdout(Stdio::Null),
+    ))
+    .unwrap();
+
+    let events = debuggee
+        .events()
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .collect::<anyhow::Result<Vec<_>>>()
+        .unwrap();
+
+    assert_eq!(
+        events.last(),
+        Some(&DebugEvent::Terminated {
+            signal: Signal::SIGSEGV
+        })
+    );
+}
+
+#[tokio::test]
+async fn tracepoints_are_collected_without_stopping() {
+    let mut debuggee =
+        AsyncDebuggee::new(debuggee::Config::SpawnChild(LaunchSpec::new(nonempty![
+            aux::get_program_exiting_immediately()
+        ])))
+        .unwrap();
+    let entry = VirtAddr::new(debuggee.debuggee().auxv().unwrap().entry_point().unwrap());
+    let id = debuggee.debuggee_mut().set_breakpoint(entry).unwrap();
+    debuggee
+        .debuggee_mut()
+        .set_tracepoint(id, vec![TraceAction::Registers])
+        .unwrap();
+
+    let events = debuggee
+        .events()
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .collect::<anyhow::Result<Vec<_>>>()
+        .unwrap();
+
+    assert!(!events
+        .iter()
+        .any(|event| matches!(event, DebugEvent::BreakpointHit { .. })));
+    let frames = debuggee
+        .debuggee()
+        .trace_buffer()
+        .frames()
+        .collect::<Vec<_>>();
+    assert_eq!(frames.len(), 1);
+    assert_eq!(frames[0].pc, entry);
+}
@@ -0,0 +1,63 @@
+use stupid_dbg_core::{
+    arch::PointerWidth,
+    auxv::{Auxv, AuxvEntry, AT_BASE, AT_ENTRY},
+};
+
+fn encode(entries: &[(u64, u64)], pointer_width: PointerWidth) -> Vec<u8> {
+    entries
+        .iter()
+        .flat_map(|(key, value)| [*key, *value])
+        .flat_map(|word| match pointer_width {
+            PointerWidth::Bits32 => (word as u32).to_ne_bytes().to_vec(),
+            PointerWidth::Bits64 => word.to_ne_bytes().to_vec(),
+        })
+        .collect()
+}
+
+#[test]
+fn parse_64_bit_auxv() {
+    let bytes = encode(
+        &[
+            (6, 4096),
+            (AT_BASE, 0x7f00_0000_0000),
+            (AT_ENTRY, 0x401000),
+            (0, 0),
+        ],
+        PointerWidth::Bits64,
+    );
+    let auxv = Auxv::parse(&bytes, PointerWidth::Bits64).unwrap();
+
+    assert_eq!(auxv.entries().len(), 3);
+    assert_eq!(
+        auxv.entries()[0],
+        AuxvEntry {
+            key: 6,
+            value: 4096
+        }
+    );
+    assert_eq!(auxv.entries()[0].name(), Some("AT_PAGESZ"));
+    assert_eq!(auxv.entry_point(), Some(0x401000));
+    assert_eq!(auxv.interpreter_base(), Some(0x7f00_0000_0000));
+}
+
+#[test]
+fn parse_32_bit_auxv() {
+    let bytes = encode(
+        &[(AT_BASE, 0), (AT_ENTRY, 0x8049000), (0, 0)],
+        PointerWidth::Bits32,
+    );
+    let auxv = Auxv::parse(&bytes, PointerWidth::Bits32).unwrap();
+
+    assert_eq!(auxv.entry_point(), Some(0x8049000));
+    // static executables have no loader
+    assert_eq!(auxv.interpreter_base(), None);
+}
+
+#[test]
+fn unknown_keys_and_truncated_auxv() {
+    let bytes = encode(&[(0x1234, 1)], PointerWidth::Bits64);
+    let auxv = Auxv::parse(&bytes, PointerWidth::Bits64).unwrap();
+    assert_eq!(auxv.entries()[0].name(), None);
+
+    assert!(Auxv::parse(&bytes[..12], PointerWidth::Bits64).is_err());
+}
@@ -0,0 +1,26 @@
+use nix::unistd::getpid;
+use stupid_dbg_core::{
+    checksec::{check_modules, Hardening, Pie, Relro},
+    elf::ElfFile,
+    memory_map::MemoryMap,
+    namespace::ProcessRoot,
+};
+
+#[test]
+fn hardening_of_the_test_binary() {
+    let hardening = Hardening::of(&ElfFile::read("/proc/self/exe").unwrap()).unwrap();
+    // rustc links position independent executables with a non-executable stack and RELRO
+    assert_eq!(hardening.pie, Pie::Enabled);
+    assert!(hardening.nx);
+    assert_ne!(hardening.relro, Relro::None);
+}
+
+#[test]
+fn every_loaded_module_is_checked() {
+    let memory_map = MemoryMap::read_from_procfs(getpid()).unwrap();
+    let modules = check_modules(&memory_map, &ProcessRoot::of(getpid()));
+    let exe = std::fs::read_link("/proc/self/exe").unwrap();
+    assert!(modules
+        .iter()
+        .any(|(module, hardening)| *module == exe.to_str().unwrap() && hardening.is_ok()));
+}
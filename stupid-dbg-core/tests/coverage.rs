@@ -0,0 +1,53 @@
+use nix::unistd::Pid;
+use stupid_dbg_core::{
+    coverage::Coverage,
+    elf::SymbolKind,
+    symbols::{Symbol, SymbolTable},
+    virt_addr::VirtAddr,
+};
+
+fn function(name: &str, address: u64) -> Symbol {
+    Symbol {
+        name: name.to_string(),
+        demangled: None,
+        address: VirtAddr::new(address),
+        size: 0x10,
+        kind: SymbolKind::Function,
+        module: "/bin/app".to_string(),
+    }
+}
+
+#[test]
+fn hits_are_recorded_once_in_order() {
+    let mut coverage = Coverage::new([0x1000, 0x2000, 0x3000].map(VirtAddr::new));
+    assert!(coverage.record(VirtAddr::new(0x3000), Pid::from_raw(1)));
+    assert!(coverage.record(VirtAddr::new(0x1000), Pid::from_raw(2)));
+    assert!(!coverage.record(VirtAddr::new(0x3000), Pid::from_raw(1)));
+    assert!(!coverage.record(VirtAddr::new(0x4000), Pid::from_raw(1)));
+
+    assert_eq!(coverage.covered_count(), 2);
+    assert_eq!(
+        coverage
+            .covered()
+            .into_iter()
+            .map(|(address, hit)| (address.as_u64(), hit.order, hit.thread.as_raw()))
+            .collect::<Vec<_>>(),
+        vec![(0x3000, 0, 1), (0x1000, 1, 2)]
+    );
+    assert_eq!(
+        coverage.uncovered().collect::<Vec<_>>(),
+        vec![VirtAddr::new(0x2000)]
+    );
+}
+
+#[test]
+fn lcov_has_a_function_record_per_probe() {
+    let symbols = SymbolTable::from_symbols(vec![function("main", 0x1000), function("f", 0x2000)]);
+    let mut coverage = Coverage::new([0x1000, 0x2000].map(VirtAddr::new));
+    coverage.record(VirtAddr::new(0x1000), Pid::from_raw(1));
+
+    assert_eq!(
+        coverage.to_lcov(&symbols),
+        "TN:\nSF:/bin/app\nFN:0,main\nFN:0,f\nFNDA:1,main\nFNDA:0,f\nFNF:2\nFNH:1\nend_of_record\n"
+    );
+}
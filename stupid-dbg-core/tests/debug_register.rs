@@ -0,0 +1,120 @@
+use stupid_dbg_core::{
+    debug_register::{
+        split_range, triggered_slots, DebugRegisterAllocator, DebugRegisterSlot, SlotOwner,
+        WatchKind,
+    },
+    virt_addr::VirtAddr,
+};
+
+fn watch(id: usize, address: u64, size: usize) -> DebugRegisterSlot {
+    DebugRegisterSlot {
+        owner: SlotOwner::Watchpoint(id),
+        address: VirtAddr::new(address),
+        size,
+        kind: WatchKind::Write,
+    }
+}
+
+#[test]
+fn allocate_until_exhausted_and_release() {
+    let mut allocator = DebugRegisterAllocator::new();
+    allocator
+        .allocate(DebugRegisterSlot {
+            owner: SlotOwner::Breakpoint(1),
+            address: VirtAddr::new(0x401000),
+            size: 1,
+            kind: WatchKind::Execute,
+        })
+        .unwrap();
+    for id in 1..=3 {
+        allocator
+            .allocate(watch(id, 0x1000 * id as u64, 8))
+            .unwrap();
+    }
+    assert_eq!(allocator.free_slots(), 0);
+
+    let err = allocator.allocate(watch(4, 0x5000, 8)).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "no free debug registers, in use by breakpoint 1 at 0x401000, \
+         watchpoint 1 at 0x1000 (8 bytes, write), watchpoint 2 at 0x2000 (8 bytes, write), \
+         watchpoint 3 at 0x3000 (8 bytes, write)"
+    );
+
+    assert_eq!(allocator.release(SlotOwner::Watchpoint(2)), vec![2]);
+    assert_eq!(allocator.allocate(watch(4, 0x5000, 8)).unwrap(), 2);
+}
+
+#[test]
+fn reject_unsupported_ranges() {
+    let mut allocator = DebugRegisterAllocator::new();
+    assert!(allocator.allocate(watch(1, 0x1000, 16)).is_err());
+    assert!(allocator.allocate(watch(1, 0x1004, 8)).is_err());
+    assert!(allocator
+        .allocate(DebugRegisterSlot {
+            owner: SlotOwner::Breakpoint(1),
+            address: VirtAddr::new(0x1000),
+            size: 4,
+            kind: WatchKind::Execute,
+        })
+        .is_err());
+    assert_eq!(allocator.free_slots(), 4);
+}
+
+#[test]
+fn encode_dr7() {
+    let mut allocator = DebugRegisterAllocator::new();
+    allocator.allocate(watch(1, 0x1000, 8)).unwrap();
+    allocator
+        .allocate(DebugRegisterSlot {
+            kind: WatchKind::ReadWrite,
+            ..watch(2, 0x2002, 2)
+        })
+        .unwrap();
+
+    let (addresses, dr7) = allocator.encode();
+    assert_eq!(addresses, [0x1000, 0x2002, 0, 0]);
+    // L0, L1, RW0 = write, LEN0 = 8, RW1 = read/write, LEN1 = 2
+    assert_eq!(dr7, (0b0111_1001 << 16) | 0b0101);
+}
+
+#[test]
+fn decode_dr6() {
+    assert_eq!(triggered_slots(0xffff0ff2).collect::<Vec<_>>(), vec![1]);
+    assert_eq!(triggered_slots(0x4000).count(), 0);
+}
+
+#[test]
+fn split_unaligned_ranges() {
+    let pieces = |address, size| {
+        split_range(VirtAddr::new(address), size)
+            .into_iter()
+            .map(|(address, size)| (address.as_u64(), size))
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(pieces(0x1000, 16), vec![(0x1000, 8), (0x1008, 8)]);
+    assert_eq!(
+        pieces(0x1003, 4),
+        vec![(0x1003, 1), (0x1004, 2), (0x1006, 1)]
+    );
+    assert_eq!(pieces(0x1004, 4), vec![(0x1004, 4)]);
+}
+
+#[test]
+fn allocate_all_or_nothing() {
+    let mut allocator = DebugRegisterAllocator::new();
+    allocator.allocate(watch(1, 0x1000, 8)).unwrap();
+    allocator.allocate(watch(2, 0x2000, 8)).unwrap();
+
+    let slots = [
+        watch(3, 0x3000, 8),
+        watch(3, 0x3008, 8),
+        watch(3, 0x3010, 8),
+    ];
+    assert!(allocator.allocate_all(&slots).is_err());
+    assert_eq!(allocator.free_slots(), 2);
+
+    assert_eq!(allocator.allocate_all(&slots[..2]).unwrap(), vec![2, 3]);
+    assert_eq!(allocator.release(SlotOwner::Watchpoint(3)), vec![2, 3]);
+}
@@ -0,0 +1,1267 @@
+use std::{sync::Arc, time::Duration};
+
+use nix::{sys::signal::Signal, unistd::Pid};
+use nonempty::nonempty;
+use stupid_dbg_core::{
+    arch::PointerWidth,
+    debug_register::WatchKind,
+    debuggee::{self, Debuggee, ProcessState, WaitOutcome},
+    elf::{ElfFile, SymbolKind},
+    instruction_trace::InstructionTrace,
+    launch::{LaunchSpec, Stdio},
+    line_table::{LineRow, LineTable},
+    register::{Register, RegisterValue},
+    stop_reason::StopReason,
+    tracepoint::Backpressure,
+    trampoline::Trampolines,
+    virt_addr::VirtAddr,
+};
+
+mod aux;
+
+#[ctor::ctor]
+fn init() {
+    aux::setup_logging();
+}
+
+#[test]
+fn launch_program() {
+    let debuggee = Debuggee::new(debuggee::Config::SpawnChild(LaunchSpec::new(nonempty![
+        aux::get_program_running_endlessly()
+    ])))
+    .unwrap();
+    let pid = debuggee.pid();
+    assert!(aux::is_process_existing(pid))
+}
+
+#[test]
+fn launch_nonexistent_program() {
+    assert!(
+        Debuggee::new(debuggee::Config::SpawnChild(LaunchSpec::new(nonempty![
+            format!("this_program_doesnt_exist",)
+        ])))
+        .is_err()
+    )
+}
+
+#[test]
+fn attach_to_process() {
+    let pid = aux::spawn(nonempty![aux::get_program_running_endlessly()], true);
+    let _debuggee = Debuggee::new(debuggee::Config::Existing(pid)).unwrap();
+    let debuggee_procfs_stat = aux::read_process_stat_from_procfs(pid);
+    assert_eq!(
+        debuggee_procfs_stat.state().unwrap(),
+        procfs::process::ProcState::Tracing
+    )
+}
+
+#[test]
+fn attach_reports_competing_tracer() {
+    let debuggee = Debuggee::new(debuggee::Config::SpawnChild(LaunchSpec::new(nonempty![
+        aux::get_program_running_endlessly()
+    ])))
+    .unwrap();
+
+    let tracer = debuggee::CompetingTracer::find(debuggee.pid()).unwrap();
+    assert!(tracer.is_debugger());
+    assert!(tracer.name.is_some());
+    assert!(!tracer.can_be_stolen_from());
+
+    let err = Debuggee::new(debuggee::Config::Existing(debuggee.pid())).unwrap_err();
+    assert!(err.to_string().contains("traced by this debugger already"));
+    assert!(Debuggee::new(debuggee::Config::Steal(debuggee.pid())).is_err());
+}
+
+#[test]
+fn attach_to_invalid_pid() {
+    assert!(Debuggee::new(debuggee::Config::Existing(Pid::from_raw(-1))).is_err())
+}
+
+#[test]
+fn launch_and_resume_program_running_endlessly() {
+    let mut debuggee = Debuggee::new(debuggee::Config::SpawnChild(LaunchSpec::new(nonempty![
+        aux::get_program_running_endlessly()
+    ])))
+    .unwrap();
+    debuggee.resume().unwrap();
+    let debuggee_procfs_stat = aux::read_process_stat_from_procfs(debuggee.pid());
+    let debuggee_procfs_state = debuggee_procfs_stat.state().unwrap();
+    let expected_states = [
+        procfs::process::ProcState::Running,
+        procfs::process::ProcState::Sleeping,
+    ];
+    assert!(expected_states
+        .into_iter()
+        .any(|expected| debuggee_procfs_state == expected))
+}
+
+#[test]
+fn attach_and_resume_program_running_endlessly() {
+    let pid = aux::spawn(nonempty![aux::get_program_running_endlessly()], true);
+    let mut debuggee = Debuggee::new(debuggee::Config::Existing(pid)).unwrap();
+    debuggee.resume().unwrap();
+    let debuggee_procfs_stat = aux::read_process_stat_from_procfs(pid);
+    let debuggee_procfs_state = debuggee_procfs_stat.state().unwrap();
+    let expected_states = [
+        procfs::process::ProcState::Running,
+        procfs::process::ProcState::Sleeping,
+    ];
+    assert!(expected_states
+        .into_iter()
+        .any(|expected| debuggee_procfs_state == expected))
+}
+
+#[test]
+fn detaching_from_a_running_debuggee_removes_the_library_unload_hook() {
+    use std::os::unix::fs::FileExt;
+
+    // the output has to go somewhere writable for it to keep running
+    let pid = aux::spawn(
+        nonempty![
+            "sh".to_string(),
+            "-c".to_string(),
+            format!("exec {} > /dev/null", aux::get_program_running_endlessly())
+        ],
+        true,
+    );
+    // until the loader is done with libc
+    std::thread::sleep(Duration::from_millis(100));
+    let mut debuggee = Debuggee::new(debuggee::Config::Existing(pid)).unwrap();
+    // unloads are only followed while there are breakpoints, from the first resume after the
+    // loader is done
+    let write = debuggee.resolve_symbol("write").unwrap().address;
+    debuggee.set_breakpoint(write).unwrap();
+    debuggee.resume_with_signal(None).unwrap();
+    debuggee.wait_for_stop(None).unwrap();
+    let r_brk = debuggee.resolve_symbol("_dl_debug_state").unwrap().address;
+    let original = debuggee.read_memory(r_brk, 1).unwrap()[0];
+    debuggee.resume_with_signal(None).unwrap();
+
+    let mem = std::fs::File::open(format!("/proc/{}/mem", pid)).unwrap();
+    let read_r_brk = || {
+        let mut code = [0u8];
+        mem.read_exact_at(&mut code, r_brk.as_u64()).unwrap();
+        code[0]
+    };
+    assert_ne!(read_r_brk(), original);
+    drop(debuggee);
+    assert_eq!(read_r_brk(), original);
+
+    nix::sys::signal::kill(pid, Signal::SIGKILL).unwrap();
+}
+
+#[test]
+fn launch_and_resume_program_exiting_immediately() {
+    let mut debuggee = Debuggee::new(debuggee::Config::SpawnChild(LaunchSpec::new(nonempty![
+        aux::get_program_exiting_immediately()
+    ])))
+    .unwrap();
+    debuggee.resume().unwrap();
+    debuggee.update_process_state(true).unwrap();
+    assert!(debuggee.resume().is_err())
+}
+
+#[test]
+fn read_and_write_memory() {
+    let mut debuggee = Debuggee::new(debuggee::Config::SpawnChild(LaunchSpec::new(nonempty![
+        aux::get_program_running_endlessly()
+    ])))
+    .unwrap();
+    let rip = VirtAddr::new(debuggee.registers().unwrap().user_regs().rip);
+
+    let original = debuggee.read_memory(rip, 16).unwrap();
+    assert_eq!(original.len(), 16);
+
+    debuggee.write_memory(rip, &[0x90; 4]).unwrap();
+    let patched = debuggee.read_memory(rip, 16).unwrap();
+    assert_eq!(&patched[..4], &[0x90; 4]);
+    assert_eq!(&patched[4..], &original[4..]);
+
+    debuggee.write_memory(rip, &original).unwrap();
+    let value = unsafe { debuggee.read_value::<u64>(rip) }.unwrap();
+    assert_eq!(value.to_ne_bytes(), original[..8]);
+    assert_eq!(debuggee.read_object::<u64>(rip).unwrap(), value);
+
+    debuggee.write_object(rip, &[0xcc_u8, 0x90]).unwrap();
+    assert_eq!(debuggee.read_object::<[u8; 2]>(rip).unwrap(), [0xcc, 0x90]);
+    assert!(debuggee.read_object::<u64>(VirtAddr::new(0)).is_err());
+}
+
+#[test]
+fn vector_registers_in_the_xsave_area() {
+    let mut debuggee = Debuggee::new(debuggee::Config::SpawnChild(LaunchSpec::new(nonempty![
+        aux::get_program_running_endlessly()
+    ])))
+    .unwrap();
+
+    for (name, width) in [("ymm1", 32), ("zmm2", 64), ("zmm17", 64)] {
+        let register = Register::lookup_by_name(name).unwrap();
+        if !debuggee.registers().unwrap().is_available(register) {
+            continue;
+        }
+        let bytes = (1..=width as u8).collect::<Vec<_>>();
+        let value = match width {
+            32 => RegisterValue::Byte256(bytes.clone().try_into().unwrap()),
+            _ => RegisterValue::Byte512(bytes.clone().try_into().unwrap()),
+        };
+        debuggee.write_register(register, value).unwrap();
+        // read back from the debuggee
+        assert_eq!(
+            debuggee
+                .registers()
+                .unwrap()
+                .read_register(register)
+                .unwrap(),
+            value
+        );
+
+        let low = Register::lookup_by_name(&format!("xmm{}", &name[3..])).unwrap();
+        assert_eq!(
+            debuggee.registers().unwrap().read_register(low).unwrap(),
+            RegisterValue::Byte128(bytes[..16].try_into().unwrap())
+        );
+    }
+}
+
+#[test]
+fn registers_changed_by_a_step() {
+    let mut debuggee = Debuggee::new(debuggee::Config::SpawnChild(LaunchSpec::new(nonempty![
+        aux::get_program_running_endlessly()
+    ])))
+    .unwrap();
+    assert!(debuggee.previous_registers().is_none());
+
+    let rip = debuggee.registers().unwrap().user_regs().rip;
+    debuggee.remember_registers();
+    debuggee.step_instruction().unwrap();
+    debuggee.wait_for_stop(None).unwrap();
+
+    let changes = debuggee
+        .registers()
+        .unwrap()
+        .changes_since(debuggee.previous_registers().unwrap());
+    let (_, old, new) = changes
+        .iter()
+        .find(|(register, _, _)| *register == Register::Rip)
+        .unwrap();
+    assert_eq!(*old, RegisterValue::U64(rip));
+    assert_ne!(old, new);
+    assert!(changes
+        .iter()
+        .all(|(register, old, new)| { register.group().is_some() && old != new }));
+}
+
+#[test]
+fn resolve_symbols_of_mapped_modules() {
+    let debuggee = Debuggee::new(debuggee::Config::SpawnChild(LaunchSpec::new(nonempty![
+        aux::get_program_running_endlessly()
+    ])))
+    .unwrap();
+
+    let symbol_table = debuggee.symbol_table().unwrap();
+    let function = symbol_table
+        .symbols()
+        .iter()
+        .find(|symbol| symbol.kind == SymbolKind::Function && symbol.size > 0)
+        .unwrap();
+    let resolved = debuggee.resolve_symbol(&function.name).unwrap();
+    assert_eq!(resolved.name, function.name);
+    assert_eq!(
+        symbol_table.lookup(resolved.address).unwrap().0.address,
+        resolved.address
+    );
+    assert!(debuggee.resolve_symbol("no_such_symbol_anywhere").is_err());
+}
+
+#[test]
+fn symbol_table_is_reloaded_once_the_mappings_change() {
+    let mut debuggee = Debuggee::new(debuggee::Config::SpawnChild(LaunchSpec::new(nonempty![
+        aux::get_program_running_endlessly()
+    ])))
+    .unwrap();
+
+    let symbol_table = debuggee.symbol_table().unwrap();
+    assert!(Arc::ptr_eq(
+        &symbol_table,
+        &debuggee.symbol_table().unwrap()
+    ));
+
+    // the libraries are only mapped by the time the entry point is reached
+    let entry = VirtAddr::new(debuggee.auxv().unwrap().entry_point().unwrap());
+    debuggee.run_until(entry, false).unwrap();
+    let reloaded = debuggee.symbol_table().unwrap();
+    assert!(!Arc::ptr_eq(&symbol_table, &reloaded));
+    assert!(reloaded.symbols().len() > symbol_table.symbols().len());
+}
+
+#[test]
+fn cached_lookups_match_the_symbol_table() {
+    let debuggee = Debuggee::new(debuggee::Config::SpawnChild(LaunchSpec::new(nonempty![
+        aux::get_program_running_endlessly()
+    ])))
+    .unwrap();
+    let memory_map = debuggee.memory_map().unwrap();
+    let symbol_table = debuggee.symbol_table().unwrap();
+    let function = symbol_table
+        .symbols()
+        .iter()
+        .find(|symbol| symbol.kind == SymbolKind::Function && symbol.size > 1)
+        .unwrap();
+
+    let address = VirtAddr::new(function.address.as_u64() + 1);
+    let expected = symbol_table
+        .lookup(address)
+        .map(|(symbol, offset)| (symbol.clone(), offset));
+    // the second one is answered from the cache
+    for _ in 0..2 {
+        assert_eq!(debuggee.lookup_symbol(&memory_map, address), expected);
+    }
+    let rsp = VirtAddr::new(debuggee.registers().unwrap().user_regs().rsp);
+    assert_eq!(debuggee.lookup_symbol(&memory_map, rsp), None);
+}
+
+#[test]
+fn large_reads_match_the_memory_file() {
+    use std::os::unix::fs::FileExt;
+
+    let debuggee = Debuggee::new(debuggee::Config::SpawnChild(LaunchSpec::new(nonempty![
+        aux::get_program_running_endlessly()
+    ])))
+    .unwrap();
+    let rsp = VirtAddr::new(debuggee.registers().unwrap().user_regs().rsp);
+    let memory_map = debuggee.memory_map().unwrap();
+    let stack = memory_map.region_containing(rsp).unwrap();
+
+    let bytes = debuggee
+        .read_memory(stack.start, stack.size() as usize)
+        .unwrap();
+    let mut expected = vec![0u8; bytes.len()];
+    std::fs::File::open(format!("/proc/{}/mem", debuggee.pid()))
+        .unwrap()
+        .read_exact_at(&mut expected, stack.start.as_u64())
+        .unwrap();
+    assert!(bytes == expected);
+
+    // reading past the end of the stack fails either way
+    assert!(debuggee.read_memory(stack.end - 8, 16).is_err());
+}
+
+#[test]
+fn find_memory_finds_the_environment() {
+    let debuggee = Debuggee::new(debuggee::Config::SpawnChild(
+        LaunchSpec::new(nonempty![aux::get_program_running_endlessly()])
+            .env("STUPID_DBG_NEEDLE", "in a haystack"),
+    ))
+    .unwrap();
+    let pattern = b"STUPID_DBG_NEEDLE=in a haystack";
+    let rsp = VirtAddr::new(debuggee.registers().unwrap().user_regs().rsp);
+    let memory_map = debuggee.memory_map().unwrap();
+    let stack = memory_map.region_containing(rsp).unwrap();
+
+    let found = debuggee
+        .find_memory(stack.start, stack.end, pattern)
+        .unwrap();
+    assert_eq!(found.len(), 1);
+    assert_eq!(
+        debuggee.read_memory(found[0], pattern.len()).unwrap(),
+        pattern
+    );
+
+    // the holes in between are skipped
+    let everywhere = debuggee
+        .find_memory(VirtAddr::new(0), VirtAddr::new(u64::MAX), pattern)
+        .unwrap();
+    assert!(everywhere.contains(&found[0]));
+    assert!(debuggee
+        .find_memory(found[0] + 1, stack.end, pattern)
+        .unwrap()
+        .is_empty());
+    assert!(debuggee.find_memory(stack.start, stack.end, b"").is_err());
+}
+
+// p_type, p_offset, p_vaddr, p_filesz, p_memsz of every program header of a core file
+fn core_program_headers(bytes: &[u8]) -> Vec<(u32, usize, u64, usize, u64)> {
+    let u32_at = |offset: usize| u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+    let u64_at = |offset: usize| u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap());
+    let e_phoff = u64_at(32) as usize;
+    let e_phnum = u16::from_le_bytes(bytes[56..58].try_into().unwrap()) as usize;
+    (0..e_phnum)
+        .map(|index| e_phoff + index * 56)
+        .map(|header| {
+            (
+                u32_at(header),
+                u64_at(header + 8) as usize,
+                u64_at(header + 16),
+                u64_at(header + 32) as usize,
+                u64_at(header + 40),
+            )
+        })
+        .collect()
+}
+
+// pr_pid of every NT_PRSTATUS in the PT_NOTE, which comes first
+fn core_prstatus_pids(bytes: &[u8]) -> Vec<i32> {
+    let u32_at = |offset: usize| u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+    let (p_type, offset, _, file_size, _) = core_program_headers(bytes)[0];
+    assert_eq!(p_type, 4);
+    let mut note = offset;
+    let mut pids = Vec::new();
+    while note < offset + file_size {
+        let (name_size, desc_size) = (u32_at(note) as usize, u32_at(note + 4) as usize);
+        let desc = note + 12 + name_size.next_multiple_of(4);
+        if u32_at(note + 8) == 1 {
+            pids.push(u32_at(desc + 32) as i32);
+        }
+        note = desc + desc_size.next_multiple_of(4);
+    }
+    pids
+}
+
+#[test]
+fn core_dump_round_trips() {
+    let mut debuggee = Debuggee::new(debuggee::Config::SpawnChild(LaunchSpec::new(nonempty![
+        aux::get_program_running_endlessly()
+    ])))
+    .unwrap();
+    let rip = VirtAddr::new(debuggee.registers().unwrap().user_regs().rip);
+    let path = std::env::temp_dir().join(format!("stupid-dbg-core-{}", std::process::id()));
+
+    debuggee.generate_core_dump(&path).unwrap();
+    let bytes = std::fs::read(&path).unwrap();
+    _ = std::fs::remove_file(&path);
+
+    // ET_CORE
+    assert_eq!(ElfFile::parse(bytes.clone()).unwrap().e_type(), 4);
+    assert_eq!(core_prstatus_pids(&bytes), vec![debuggee.pid().as_raw()]);
+
+    // a PT_LOAD for every mapping, with its contents
+    let headers = core_program_headers(&bytes);
+    let memory_map = debuggee.memory_map().unwrap();
+    let loads = headers[1..]
+        .iter()
+        .map(|(p_type, _, vaddr, _, memsz)| {
+            assert_eq!(*p_type, 1);
+            (*vaddr, *memsz)
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        loads,
+        memory_map
+            .regions()
+            .iter()
+            .map(|region| (region.start.as_u64(), region.size()))
+            .collect::<Vec<_>>()
+    );
+    let (_, offset, vaddr, _, _) = headers[1..]
+        .iter()
+        .find(|(_, _, vaddr, _, memsz)| (*vaddr..vaddr + memsz).contains(&rip.as_u64()))
+        .unwrap();
+    let code = offset + (rip.as_u64() - vaddr) as usize;
+    assert_eq!(
+        bytes[code..code + 16],
+        debuggee.read_memory(rip, 16).unwrap()
+    );
+}
+
+// run by core_dumps_stop_the_running_threads in a debuggee of its own
+#[test]
+#[ignore]
+fn spinning_thread() {
+    std::thread::spawn(|| loop {
+        std::hint::spin_loop();
+    });
+    loop {
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
+#[test]
+fn core_dumps_stop_the_running_threads() {
+    let mut debuggee = Debuggee::new(debuggee::Config::SpawnChild(
+        LaunchSpec::new(nonempty![
+            std::env::current_exe().unwrap().display().to_string(),
+            "spinning_thread".to_string(),
+            "--ignored".to_string(),
+            "--exact".to_string(),
+            "--test-threads=1".to_string()
+        ])
+        .stdout(Stdio::Null),
+    ))
+    .unwrap();
+    debuggee.resume().unwrap();
+    assert!(matches!(
+        debuggee
+            .wait_for_stop(Some(Duration::from_millis(300)))
+            .unwrap(),
+        WaitOutcome::TimedOut
+    ));
+    debuggee.interrupt().unwrap();
+    assert!(matches!(
+        debuggee.wait_for_stop(None).unwrap(),
+        WaitOutcome::StateChanged(ProcessState::Stopped(_))
+    ));
+    let threads = debuggee
+        .threads()
+        .iter()
+        .map(|tid| tid.as_raw())
+        .collect::<Vec<_>>();
+    assert!(threads.len() >= 2);
+    let path = std::env::temp_dir().join(format!("stupid-dbg-threads-{}", std::process::id()));
+
+    debuggee.generate_core_dump(&path).unwrap();
+    let bytes = std::fs::read(&path).unwrap();
+    _ = std::fs::remove_file(&path);
+
+    let mut pids = core_prstatus_pids(&bytes);
+    pids.sort();
+    assert_eq!(pids, threads);
+    // the threads it stopped go on afterwards
+    debuggee.resume_with_signal(None).unwrap();
+    assert!(matches!(
+        debuggee
+            .wait_for_stop(Some(Duration::from_millis(50)))
+            .unwrap(),
+        WaitOutcome::TimedOut
+    ));
+    // running threads cannot be detached from, which would leave the drop waiting on them
+    nix::sys::signal::kill(debuggee.pid(), Signal::SIGKILL).unwrap();
+    assert!(matches!(
+        debuggee.wait_for_stop(None).unwrap(),
+        WaitOutcome::StateChanged(ProcessState::Terminated(Signal::SIGKILL))
+    ));
+}
+
+#[test]
+fn dump_memory_to_a_file() {
+    let debuggee = Debuggee::new(debuggee::Config::SpawnChild(LaunchSpec::new(nonempty![
+        aux::get_program_running_endlessly()
+    ])))
+    .unwrap();
+    let rip = VirtAddr::new(debuggee.registers().unwrap().user_regs().rip);
+    let path = std::env::temp_dir().join(format!("stupid-dbg-dump-{}.bin", std::process::id()));
+
+    debuggee.dump_memory(rip, 64, &path).unwrap();
+    assert_eq!(
+        std::fs::read(&path).unwrap(),
+        debuggee.read_memory(rip, 64).unwrap()
+    );
+    _ = std::fs::remove_file(&path);
+
+    assert!(debuggee.dump_memory(VirtAddr::new(0), 64, &path).is_err());
+    _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn restore_memory_from_a_file() {
+    let mut debuggee = Debuggee::new(debuggee::Config::SpawnChild(LaunchSpec::new(nonempty![
+        aux::get_program_running_endlessly()
+    ])))
+    .unwrap();
+    let regs = debuggee.registers().unwrap().user_regs();
+    let (rip, rsp) = (VirtAddr::new(regs.rip), VirtAddr::new(regs.rsp));
+    let path = std::env::temp_dir().join(format!("stupid-dbg-restore-{}.bin", std::process::id()));
+    std::fs::write(&path, [0x90; 4]).unwrap();
+
+    assert_eq!(debuggee.restore_memory(&path, rsp, false).unwrap(), 4);
+    assert_eq!(debuggee.read_memory(rsp, 4).unwrap(), [0x90; 4]);
+
+    // code is mapped read-only
+    assert!(debuggee.restore_memory(&path, rip, false).is_err());
+    debuggee.restore_memory(&path, rip, true).unwrap();
+    assert_eq!(debuggee.read_memory(rip, 4).unwrap(), [0x90; 4]);
+
+    assert!(debuggee
+        .restore_memory(&path, VirtAddr::new(0), true)
+        .is_err());
+    _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn breakpoints_are_invisible_to_memory_reads() {
+    let mut debuggee = Debuggee::new(debuggee::Config::SpawnChild(LaunchSpec::new(nonempty![
+        aux::get_program_running_endlessly()
+    ])))
+    .unwrap();
+    let rip = VirtAddr::new(debuggee.registers().unwrap().user_regs().rip);
+
+    let original = debuggee.read_memory(rip, 8).unwrap();
+    debuggee.set_breakpoint(rip + 1).unwrap();
+    assert_eq!(debuggee.read_memory(rip, 8).unwrap(), original);
+}
+
+#[test]
+fn breakpoint_addresses_are_validated() {
+    let mut debuggee = Debuggee::new(debuggee::Config::SpawnChild(LaunchSpec::new(nonempty![
+        aux::get_program_running_endlessly()
+    ])))
+    .unwrap();
+    let regs = debuggee.registers().unwrap().user_regs();
+    let rip = VirtAddr::new(regs.rip);
+
+    debuggee.check_breakpoint_address(rip).unwrap();
+    assert!(debuggee
+        .check_breakpoint_address(VirtAddr::new(regs.rsp))
+        .is_err());
+    assert!(debuggee.check_breakpoint_address(VirtAddr::new(0)).is_err());
+
+    let id = debuggee.set_breakpoint(rip + 1).unwrap();
+    assert!(debuggee.revalidate_breakpoints().unwrap().is_empty());
+    // nothing changed since
+    assert!(debuggee.revalidate_breakpoints().unwrap().is_empty());
+    debuggee.remove_breakpoint(id).unwrap();
+}
+
+#[test]
+fn disabled_breakpoints_stay_disabled_across_continues() {
+    let mut debuggee = Debuggee::new(debuggee::Config::SpawnChild(
+        LaunchSpec::new(nonempty![aux::get_program_running_endlessly()]).stdout(Stdio::Null),
+    ))
+    .unwrap();
+    // libc is only mapped by the time the entry point is reached
+    let entry = VirtAddr::new(debuggee.auxv().unwrap().entry_point().unwrap());
+    debuggee.run_until(entry, false).unwrap();
+    // the temporary breakpoint of run_until had the id the next one gets
+    debuggee.stop_log_mut().clear();
+    // the program writes all the time, every continue runs into the breakpoint
+    let write = debuggee.resolve_symbol("write").unwrap().address;
+    let id = debuggee.set_breakpoint(write).unwrap();
+
+    let rip = |debuggee: &Debuggee| VirtAddr::new(debuggee.registers().unwrap().user_regs().rip);
+    let hits = |debuggee: &Debuggee| {
+        debuggee
+            .stop_log()
+            .records()
+            .filter(|record| record.reason == StopReason::Breakpoint { id })
+            .count()
+    };
+    // the SIGSTOP an interrupt stopped it with isn't passed on
+    let continue_to_hit = |debuggee: &mut Debuggee| {
+        debuggee.resume_with_signal(None).unwrap();
+        assert!(matches!(
+            debuggee.wait_for_stop(None).unwrap(),
+            WaitOutcome::StateChanged(ProcessState::Stopped(StopReason::Breakpoint { id: hit }))
+                if hit == id
+        ));
+        assert_eq!(rip(debuggee), write);
+    };
+
+    for expected in 1..=2 {
+        continue_to_hit(&mut debuggee);
+        assert_eq!(hits(&debuggee), expected);
+    }
+
+    debuggee.set_breakpoint_enabled(id, false).unwrap();
+    assert!(!debuggee.breakpoints()[&id].is_armed());
+    for _ in 0..2 {
+        debuggee.resume_with_signal(None).unwrap();
+        assert!(matches!(
+            debuggee
+                .wait_for_stop(Some(Duration::from_millis(50)))
+                .unwrap(),
+            WaitOutcome::TimedOut
+        ));
+        debuggee.interrupt().unwrap();
+        assert!(matches!(
+            debuggee.wait_for_stop(None).unwrap(),
+            WaitOutcome::StateChanged(ProcessState::Stopped(reason))
+                if !matches!(reason, StopReason::Breakpoint { .. })
+        ));
+        assert!(!debuggee.is_breakpoint_enabled(id));
+        assert!(!debuggee.breakpoints()[&id].is_armed());
+    }
+    assert_eq!(hits(&debuggee), 2);
+
+    debuggee.set_breakpoint_enabled(id, true).unwrap();
+    assert!(debuggee.breakpoints()[&id].is_armed());
+    continue_to_hit(&mut debuggee);
+    assert_eq!(hits(&debuggee), 3);
+
+    debuggee.remove_breakpoint(id).unwrap();
+    assert!(!debuggee.is_breakpoint_enabled(id));
+    assert!(debuggee.set_breakpoint_enabled(id, true).is_err());
+}
+
+#[test]
+fn breakpoint_conditions_are_parsed_when_set() {
+    let mut debuggee = Debuggee::new(debuggee::Config::SpawnChild(LaunchSpec::new(nonempty![
+        aux::get_program_running_endlessly()
+    ])))
+    .unwrap();
+    let rip = VirtAddr::new(debuggee.registers().unwrap().user_regs().rip);
+    let id = debuggee.set_breakpoint(rip + 1).unwrap();
+
+    assert!(debuggee
+        .set_breakpoint_condition(id, Some("$rax ==".to_string()))
+        .is_err());
+    assert!(debuggee.breakpoint_conditions().is_empty());
+    assert!(debuggee
+        .set_breakpoint_condition(id + 1, Some("1".to_string()))
+        .is_err());
+
+    debuggee
+        .set_breakpoint_condition(id, Some("$rax == 5".to_string()))
+        .unwrap();
+    assert_eq!(debuggee.breakpoint_conditions()[&id], "$rax == 5");
+    debuggee.set_breakpoint_condition(id, None).unwrap();
+    assert!(debuggee.breakpoint_conditions().is_empty());
+
+    debuggee
+        .set_breakpoint_condition(id, Some("$rdi != 0".to_string()))
+        .unwrap();
+    debuggee.remove_breakpoint(id).unwrap();
+    assert!(debuggee.breakpoint_conditions().is_empty());
+}
+
+#[test]
+fn stack_watchpoints_are_scoped() {
+    let mut debuggee = Debuggee::new(debuggee::Config::SpawnChild(LaunchSpec::new(nonempty![
+        aux::get_program_running_endlessly()
+    ])))
+    .unwrap();
+    let regs = *debuggee.registers().unwrap().user_regs();
+
+    let id = debuggee
+        .set_watchpoint(VirtAddr::new(regs.rsp), 8, WatchKind::Write)
+        .unwrap();
+    let scope = debuggee.watchpoints()[&id].scope().unwrap();
+    assert_eq!(scope.thread, debuggee.pid());
+    assert_eq!(scope.stack_pointer, VirtAddr::new(regs.rsp));
+
+    let id = debuggee
+        .set_watchpoint(VirtAddr::new(regs.rip), 1, WatchKind::Write)
+        .unwrap();
+    assert_eq!(debuggee.watchpoints()[&id].scope(), None);
+}
+
+#[test]
+fn page_watchpoints_catch_reads_into_them() {
+    let file = concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml");
+    let mut debuggee = Debuggee::new(debuggee::Config::SpawnChild(
+        LaunchSpec::new(nonempty![
+            "cat".to_string(),
+            "-A".to_string(),
+            file.to_string()
+        ])
+        .stdout(Stdio::Null),
+    ))
+    .unwrap();
+    let entry = VirtAddr::new(debuggee.auxv().unwrap().entry_point().unwrap());
+    debuggee.run_until(entry, false).unwrap();
+    let read = debuggee.resolve_symbol("read").unwrap().address;
+    let id = debuggee.set_breakpoint(read).unwrap();
+    debuggee.resume().unwrap();
+    debuggee.wait_for_stop(None).unwrap();
+    debuggee.remove_breakpoint(id).unwrap();
+
+    // the kernel writes the buffer, which doesn't fault but would fail the read with EFAULT
+    let buffer = VirtAddr::new(debuggee.registers().unwrap().user_regs().rsi);
+    let id = debuggee.set_page_watchpoint(buffer, 16).unwrap();
+    debuggee.resume().unwrap();
+    assert!(matches!(
+        debuggee.wait_for_stop(None).unwrap(),
+        WaitOutcome::StateChanged(ProcessState::Stopped(StopReason::Watchpoint { id: hit, address }))
+            if hit == id && address == buffer
+    ));
+    assert!(debuggee.registers().unwrap().user_regs().rax as i64 > 0);
+
+    // the rest of the file is read into it again, the final read of nothing isn't a hit
+    loop {
+        debuggee.resume().unwrap();
+        match debuggee.wait_for_stop(None).unwrap() {
+            WaitOutcome::StateChanged(ProcessState::Stopped(StopReason::Watchpoint {
+                id: hit,
+                ..
+            })) if hit == id => continue,
+            outcome => {
+                assert!(matches!(
+                    outcome,
+                    WaitOutcome::StateChanged(ProcessState::Exited(Some(0)))
+                ));
+                break;
+            }
+        }
+    }
+}
+
+#[test]
+fn launch_and_stop_at_entry() {
+    let debuggee = Debuggee::new(debuggee::Config::SpawnChild(
+        LaunchSpec::new(nonempty![aux::get_program_running_endlessly()]).stop_at_entry(true),
+    ))
+    .unwrap();
+    assert!(debuggee.breakpoints().is_empty());
+    assert!(debuggee.hit_breakpoint().is_none());
+
+    let rip = VirtAddr::new(debuggee.registers().unwrap().user_regs().rip);
+    let memory_map = debuggee.memory_map().unwrap();
+    let exe = std::fs::read_link(format!("/proc/{}/exe", debuggee.pid())).unwrap();
+    assert_eq!(
+        memory_map.region_containing(rip).unwrap().path.as_deref(),
+        exe.to_str()
+    );
+}
+
+#[test]
+fn resuming_steps_over_the_breakpoint_at_the_pc() {
+    let mut debuggee = Debuggee::new(debuggee::Config::SpawnChild(
+        LaunchSpec::new(nonempty![aux::get_program_running_endlessly()])
+            .stop_at_entry(true)
+            .stdout(Stdio::Null),
+    ))
+    .unwrap();
+    let entry = VirtAddr::new(debuggee.registers().unwrap().user_regs().rip);
+    let entry_id = debuggee.set_breakpoint(entry).unwrap();
+    let write = debuggee.resolve_symbol("write").unwrap().address;
+    let write_id = debuggee.set_breakpoint(write).unwrap();
+
+    // the entry point only runs once, trapping on it again would mean it wasn't stepped over
+    for _ in 0..2 {
+        debuggee.resume().unwrap();
+        assert!(matches!(
+            debuggee.wait_for_stop(None).unwrap(),
+            WaitOutcome::StateChanged(ProcessState::Stopped(StopReason::Breakpoint { id }))
+                if id == write_id
+        ));
+        assert_eq!(
+            VirtAddr::new(debuggee.registers().unwrap().user_regs().rip),
+            write
+        );
+        assert!(debuggee.breakpoints()[&entry_id].is_armed());
+        assert!(debuggee.breakpoints()[&write_id].is_armed());
+    }
+}
+
+#[test]
+fn step_line_runs_to_the_next_line() {
+    let mut debuggee = Debuggee::new(debuggee::Config::SpawnChild(
+        LaunchSpec::new(nonempty![aux::get_program_running_endlessly()]).stop_at_entry(true),
+    ))
+    .unwrap();
+    let rip = VirtAddr::new(debuggee.registers().unwrap().user_regs().rip);
+    let row = |address: VirtAddr, line: u64, end_sequence: bool| LineRow {
+        address,
+        file: "entry.c".into(),
+        line,
+        is_stmt: true,
+        end_sequence,
+    };
+
+    assert!(debuggee
+        .step_line(&LineTable::default(), &Trampolines::default(), false)
+        .is_err());
+
+    // the first instruction is all of line 1
+    let lines = LineTable::from_rows(vec![
+        row(rip, 1, false),
+        row(rip + 1, 2, false),
+        row(rip + 0x1000, 2, true),
+    ]);
+    let outcome = debuggee
+        .step_line(&lines, &Trampolines::default(), false)
+        .unwrap();
+    assert!(matches!(
+        outcome,
+        WaitOutcome::StateChanged(debuggee::ProcessState::Stopped(StopReason::StepComplete))
+    ));
+    let pc = VirtAddr::new(debuggee.registers().unwrap().user_regs().rip);
+    assert_eq!(lines.lookup(pc).unwrap().line, 2);
+}
+
+#[test]
+fn dynamic_linker_is_a_trampoline() {
+    let debuggee = Debuggee::new(debuggee::Config::SpawnChild(LaunchSpec::new(nonempty![
+        aux::get_program_running_endlessly()
+    ])))
+    .unwrap();
+    let rip = VirtAddr::new(debuggee.registers().unwrap().user_regs().rip);
+
+    // right after execve it's the dynamic linker that runs
+    let trampolines = debuggee.trampolines().unwrap();
+    assert!(trampolines.contains(rip));
+}
+
+#[test]
+fn stepping_over_calls_steps_anything_else() {
+    let mut debuggee = Debuggee::new(debuggee::Config::SpawnChild(
+        LaunchSpec::new(nonempty![aux::get_program_running_endlessly()]).stop_at_entry(true),
+    ))
+    .unwrap();
+    let rip = debuggee.registers().unwrap().user_regs().rip;
+
+    // the entry point sets up the call of __libc_start_main first
+    assert!(matches!(
+        debuggee.step_over_call().unwrap(),
+        WaitOutcome::StateChanged(debuggee::ProcessState::Stopped(StopReason::StepComplete))
+    ));
+    assert_ne!(debuggee.registers().unwrap().user_regs().rip, rip);
+    assert!(debuggee.breakpoints().is_empty());
+}
+
+#[test]
+fn finishing_the_entry_point_fails() {
+    let mut debuggee = Debuggee::new(debuggee::Config::SpawnChild(
+        LaunchSpec::new(nonempty![aux::get_program_running_endlessly()]).stop_at_entry(true),
+    ))
+    .unwrap();
+
+    // nothing called it, what's on top of the stack is argc
+    assert!(debuggee.finish().is_err());
+    assert!(debuggee.breakpoints().is_empty());
+    assert!(matches!(
+        debuggee.process_state(),
+        debuggee::ProcessState::Stopped(StopReason::Initial)
+    ));
+}
+
+#[test]
+fn run_until_stops_at_the_target() {
+    let launch = || {
+        Debuggee::new(debuggee::Config::SpawnChild(
+            LaunchSpec::new(nonempty![aux::get_program_running_endlessly()]).stop_at_entry(true),
+        ))
+        .unwrap()
+    };
+    let rip = |debuggee: &Debuggee| debuggee.registers().unwrap().user_regs().rip;
+
+    // where the entry point goes after two instructions, relative to it as the load address
+    // changes. Dropping a debuggee waits for a child to exit, which has to be this one.
+    let offset = {
+        let mut debuggee = launch();
+        let entry = rip(&debuggee);
+        for _ in 0..2 {
+            debuggee.step_instruction().unwrap();
+            debuggee.wait_for_stop(None).unwrap();
+        }
+        rip(&debuggee) - entry
+    };
+
+    let mut debuggee = launch();
+    let target = VirtAddr::new(rip(&debuggee) + offset);
+    assert!(matches!(
+        debuggee.run_until(target, true).unwrap(),
+        WaitOutcome::StateChanged(debuggee::ProcessState::Stopped(StopReason::StepComplete))
+    ));
+    assert_eq!(VirtAddr::new(rip(&debuggee)), target);
+    assert!(debuggee.breakpoints().is_empty());
+}
+
+#[test]
+fn restarting_a_checkpoint_goes_back_to_where_it_was_taken() {
+    let mut debuggee = Debuggee::new(debuggee::Config::SpawnChild(
+        LaunchSpec::new(nonempty![aux::get_program_running_endlessly()]).stop_at_entry(true),
+    ))
+    .unwrap();
+    let rip = |debuggee: &Debuggee| debuggee.registers().unwrap().user_regs().rip;
+    let entry = rip(&debuggee);
+
+    let id = debuggee.checkpoint().unwrap();
+    assert_eq!(debuggee.checkpoint_pc(id).unwrap(), VirtAddr::new(entry));
+    for _ in 0..3 {
+        debuggee.step_instruction().unwrap();
+        debuggee.wait_for_stop(None).unwrap();
+    }
+    assert_ne!(rip(&debuggee), entry);
+
+    // the checkpoint is forked again, so it can be restarted more than once
+    let pid = debuggee.pid();
+    debuggee.restart_checkpoint(id).unwrap();
+    assert_ne!(debuggee.pid(), pid);
+    assert_eq!(rip(&debuggee), entry);
+    assert!(debuggee.checkpoints().contains_key(&id));
+
+    debuggee.delete_checkpoint(id).unwrap();
+    assert!(debuggee.checkpoints().is_empty());
+    assert!(debuggee.checkpoint_pc(id).is_err());
+    assert!(debuggee.delete_checkpoint(id).is_err());
+}
+
+#[test]
+fn restarting_a_checkpoint_applies_the_breakpoints_there_are_by_then() {
+    let mut debuggee = Debuggee::new(debuggee::Config::SpawnChild(
+        LaunchSpec::new(nonempty![aux::get_program_running_endlessly()]).stdout(Stdio::Null),
+    ))
+    .unwrap();
+    // libc is only mapped by the time the entry point is reached
+    let entry = VirtAddr::new(debuggee.auxv().unwrap().entry_point().unwrap());
+    debuggee.run_until(entry, false).unwrap();
+    let write = debuggee.resolve_symbol("write").unwrap().address;
+    let start_main = debuggee
+        .resolve_symbol("__libc_start_main")
+        .unwrap()
+        .address;
+
+    let deleted = debuggee.set_breakpoint(write).unwrap();
+    let id = debuggee.checkpoint().unwrap();
+    debuggee.remove_breakpoint(deleted).unwrap();
+    let added = debuggee.set_breakpoint(start_main).unwrap();
+    debuggee.restart_checkpoint(id).unwrap();
+    assert!(debuggee.breakpoints()[&added].is_armed());
+
+    debuggee.resume().unwrap();
+    assert!(matches!(
+        debuggee.wait_for_stop(None).unwrap(),
+        WaitOutcome::StateChanged(ProcessState::Stopped(StopReason::Breakpoint { id }))
+            if id == added
+    ));
+    // the deleted one would trap as soon as it writes
+    debuggee.remove_breakpoint(added).unwrap();
+    debuggee.resume().unwrap();
+    assert!(matches!(
+        debuggee
+            .wait_for_stop(Some(Duration::from_millis(50)))
+            .unwrap(),
+        WaitOutcome::TimedOut
+    ));
+}
+
+#[test]
+fn recording_runs_into_breakpoints() {
+    let launch = || {
+        Debuggee::new(debuggee::Config::SpawnChild(
+            LaunchSpec::new(nonempty![aux::get_program_running_endlessly()]).stop_at_entry(true),
+        ))
+        .unwrap()
+    };
+    let rip = |debuggee: &Debuggee| debuggee.registers().unwrap().user_regs().rip;
+
+    // see run_until_stops_at_the_target
+    let offset = {
+        let mut debuggee = launch();
+        let entry = rip(&debuggee);
+        for _ in 0..3 {
+            debuggee.step_instruction().unwrap();
+            debuggee.wait_for_stop(None).unwrap();
+        }
+        rip(&debuggee) - entry
+    };
+
+    let mut debuggee = launch();
+    let entry = rip(&debuggee);
+    let id = debuggee
+        .set_breakpoint(VirtAddr::new(entry + offset))
+        .unwrap();
+    let rsp = Register::lookup_by_name("rsp").unwrap();
+    let path =
+        std::env::temp_dir().join(format!("stupid-dbg-recording-{}.txt", std::process::id()));
+    _ = std::fs::remove_file(&path);
+    debuggee
+        .open_trace_file(&path, Backpressure::Block(Duration::from_secs(1)))
+        .unwrap();
+    debuggee.start_recording(InstructionTrace::new(16, vec![rsp]));
+    assert!(matches!(
+        debuggee.run_recording().unwrap(),
+        WaitOutcome::StateChanged(debuggee::ProcessState::Stopped(StopReason::Breakpoint { id: hit })) if hit == id
+    ));
+    assert_eq!(rip(&debuggee), entry + offset);
+
+    let records = debuggee.instruction_trace().records();
+    assert_eq!(records.len(), 3);
+    let first = records.frames().next().unwrap();
+    assert_eq!(first.pc, VirtAddr::new(entry));
+    assert_eq!(first.registers[0].0, rsp);
+    debuggee.stop_recording();
+    assert!(!debuggee.is_recording_instructions());
+
+    // every instruction is streamed to the trace file as well
+    debuggee.close_trace_file().unwrap();
+    let text = std::fs::read_to_string(&path).unwrap();
+    _ = std::fs::remove_file(&path);
+    assert_eq!(text.lines().count(), 3);
+    assert!(text.starts_with(&format!(
+        "insn=0 thread={} pc={}",
+        debuggee.pid(),
+        VirtAddr::new(entry)
+    )));
+}
+
+#[test]
+fn signals_sent_with_kill_have_no_fault_address() {
+    let mut debuggee = Debuggee::new(debuggee::Config::SpawnChild(LaunchSpec::new(nonempty![
+        "sh".to_string(),
+        "-c".to_string(),
+        "kill -SEGV $$".to_string()
+    ])))
+    .unwrap();
+    debuggee.resume().unwrap();
+    let WaitOutcome::StateChanged(ProcessState::Stopped(StopReason::Signal(info))) =
+        debuggee.wait_for_stop(None).unwrap()
+    else {
+        panic!("not stopped by a signal");
+    };
+
+    assert_eq!(info.signal, Signal::SIGSEGV);
+    assert_eq!(info.fault_address, None);
+    assert_eq!(info.sender, Some(debuggee.pid()));
+}
+
+#[test]
+fn launch_with_environment_and_cwd() {
+    let cwd = std::env::temp_dir();
+    let debuggee = Debuggee::new(debuggee::Config::SpawnChild(
+        LaunchSpec::new(nonempty![aux::get_program_running_endlessly()])
+            .env("STUPID_DBG_TEST_VAR", "42")
+            .cwd(&cwd)
+            .stdout(Stdio::Null),
+    ))
+    .unwrap();
+    let environ = std::fs::read(format!("/proc/{}/environ", debuggee.pid())).unwrap();
+    assert!(environ
+        .split(|byte| *byte == 0)
+        .any(|var| var == b"STUPID_DBG_TEST_VAR=42"));
+    assert_eq!(
+        std::fs::read_link(format!("/proc/{}/cwd", debuggee.pid())).unwrap(),
+        cwd.canonicalize().unwrap()
+    );
+}
+
+#[test]
+fn launch_with_captured_stdout() {
+    let mut debuggee = Debuggee::new(debuggee::Config::SpawnChild(
+        LaunchSpec::new(nonempty!["echo".to_string(), "hello".to_string()]).stdout(Stdio::Capture),
+    ))
+    .unwrap();
+    let stdout = debuggee.take_captured_output().stdout.unwrap();
+    assert!(debuggee.take_captured_output().stdout.is_none());
+
+    debuggee.resume().unwrap();
+    assert_eq!(std::io::read_to_string(stdout).unwrap(), "hello\n");
+}
+
+#[test]
+fn capturing_stdin_is_rejected() {
+    assert!(Debuggee::new(debuggee::Config::SpawnChild(
+        LaunchSpec::new(nonempty![aux::get_program_exiting_immediately()]).stdin(Stdio::Capture),
+    ))
+    .is_err());
+}
+
+#[test]
+fn read_environment_and_command_line() {
+    let program = aux::get_program_running_endlessly();
+    let debuggee = Debuggee::new(debuggee::Config::SpawnChild(
+        LaunchSpec::new(nonempty![program.clone(), "a b".to_string()])
+            .env("STUPID_DBG_TEST_VAR", "x=y")
+            .stdout(Stdio::Null),
+    ))
+    .unwrap();
+
+    assert_eq!(
+        debuggee.command_line().unwrap(),
+        vec![program, "a b".to_string()]
+    );
+    assert!(debuggee
+        .environment()
+        .unwrap()
+        .contains(&("STUPID_DBG_TEST_VAR".to_string(), "x=y".to_string())));
+}
+
+#[test]
+fn launch_reports_initial_stop() {
+    let debuggee = Debuggee::new(debuggee::Config::SpawnChild(LaunchSpec::new(nonempty![
+        aux::get_program_running_endlessly()
+    ])))
+    .unwrap();
+    assert!(matches!(
+        debuggee.process_state(),
+        debuggee::ProcessState::Stopped(StopReason::Initial)
+    ));
+}
+
+#[test]
+fn native_process_has_64_bit_pointers() {
+    let debuggee = Debuggee::new(debuggee::Config::SpawnChild(LaunchSpec::new(nonempty![
+        aux::get_program_running_endlessly()
+    ])))
+    .unwrap();
+    assert_eq!(debuggee.pointer_width(), PointerWidth::Bits64);
+
+    let rsp = VirtAddr::new(debuggee.registers().unwrap().user_regs().rsp);
+    let argc = debuggee.read_pointer(rsp).unwrap();
+    assert_eq!(argc, VirtAddr::new(1));
+}
+
+#[test]
+fn entry_point_is_in_the_executable() {
+    let debuggee = Debuggee::new(debuggee::Config::SpawnChild(LaunchSpec::new(nonempty![
+        aux::get_program_running_endlessly()
+    ])))
+    .unwrap();
+    let entry = VirtAddr::new(debuggee.auxv().unwrap().entry_point().unwrap());
+    let load_bias = debuggee.load_bias().unwrap();
+
+    let memory_map = debuggee.memory_map().unwrap();
+    let exe = std::fs::read_link(format!("/proc/{}/exe", debuggee.pid())).unwrap();
+    let base = memory_map.module_base(exe.to_str().unwrap()).unwrap();
+    assert_eq!(
+        memory_map.region_containing(entry).unwrap().path.as_deref(),
+        exe.to_str()
+    );
+    // the lowest segment of an executable is linked at zero if it's position independent
+    assert!(load_bias == 0 || load_bias == base.as_u64());
+}
+
+#[test]
+fn wait_for_stop_times_out_and_can_be_cancelled() {
+    let mut debuggee = Debuggee::new(debuggee::Config::SpawnChild(LaunchSpec::new(nonempty![
+        aux::get_program_running_endlessly()
+    ])))
+    .unwrap();
+    debuggee.resume().unwrap();
+
+    assert!(matches!(
+        debuggee
+            .wait_for_stop(Some(std::time::Duration::from_millis(50)))
+            .unwrap(),
+        WaitOutcome::TimedOut
+    ));
+
+    let token = debuggee.cancellation_token();
+    let canceller = std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        token.cancel();
+    });
+    assert!(matches!(
+        debuggee.wait_for_stop(None).unwrap(),
+        WaitOutcome::Cancelled
+    ));
+    canceller.join().unwrap();
+    assert!(!debuggee.cancellation_token().is_cancelled());
+}
+
+#[test]
+fn ptrace_permission_diagnosis() {
+    let permissions = debuggee::PtracePermissions {
+        ptrace_scope: Some(1),
+        has_cap_sys_ptrace: false,
+        uid: 1000,
+        gid: 1000,
+        target_uids: Some([1000; 4]),
+        target_gids: Some([1000; 4]),
+        target_tracer: None,
+        target_is_descendant: false,
+    };
+    let reasons = permissions.diagnose();
+    assert_eq!(reasons.len(), 1);
+    assert!(reasons[0].starts_with("Yama ptrace_scope=1"));
+
+    let reasons = debuggee::PtracePermissions {
+        target_uids: Some([0; 4]),
+        target_tracer: Some(debuggee::CompetingTracer {
+            pid: Pid::from_raw(1),
+            name: Some("strace".to_string()),
+        }),
+        target_is_descendant: true,
+        ..permissions.clone()
+    }
+    .diagnose();
+    assert_eq!(reasons.len(), 2);
+    assert!(reasons[0].contains("traced by pid 1 (strace)"));
+    assert!(reasons[1].contains("uid 0"));
+
+    let reasons = debuggee::PtracePermissions {
+        has_cap_sys_ptrace: true,
+        ..permissions
+    }
+    .diagnose();
+    assert!(reasons[0].contains("SELinux"));
+}
@@ -0,0 +1,38 @@
+#![cfg(target_arch = "x86_64")]
+
+use stupid_dbg_core::{
+    arch::{I386, X86_64},
+    disassemble::disassemble,
+    virt_addr::VirtAddr,
+};
+
+#[test]
+fn instructions_in_att_syntax() {
+    // push %rbp; mov %rsp,%rbp; an invalid opcode; the first byte of a mov cut short
+    let bytes = [0x55, 0x48, 0x89, 0xe5, 0x06, 0x48];
+    let instructions = disassemble(&X86_64, VirtAddr::new(0x1000), &bytes, 16).unwrap();
+    assert_eq!(
+        instructions
+            .iter()
+            .map(|instruction| (instruction.address.as_u64(), instruction.text.as_str()))
+            .collect::<Vec<_>>(),
+        vec![
+            (0x1000, "push %rbp"),
+            (0x1001, "mov %rsp,%rbp"),
+            (0x1004, "(bad)"),
+        ]
+    );
+    assert_eq!(instructions[1].bytes, vec![0x48, 0x89, 0xe5]);
+
+    assert_eq!(
+        disassemble(&X86_64, VirtAddr::new(0x1000), &bytes, 1)
+            .unwrap()
+            .len(),
+        1
+    );
+    // push %es is only invalid in 64-bit code
+    assert_eq!(
+        disassemble(&I386, VirtAddr::new(0x1004), &bytes[4..], 1).unwrap()[0].text,
+        "push %es"
+    );
+}
@@ -0,0 +1,221 @@
+use std::collections::BTreeMap;
+
+use anyhow::anyhow;
+use stupid_dbg_core::{
+    c_type::{BaseType, CType},
+    expression::{
+        evaluate, evaluate_address, parse_assignment, parse_type, split_assignment, BinaryOp,
+        ConvenienceVariables, EvalContext, Expr, Value, WithConvenienceVariables,
+    },
+};
+
+struct Context {
+    registers: BTreeMap<&'static str, i64>,
+    memory: BTreeMap<u64, u8>,
+}
+
+impl Context {
+    fn new() -> Self {
+        let mut memory = BTreeMap::new();
+        for (offset, byte) in 0x1122334455667788u64.to_le_bytes().iter().enumerate() {
+            memory.insert(0x7ff0 + offset as u64, *byte);
+        }
+
+        Self {
+            registers: BTreeMap::from([("rip", 0x401000), ("rsp", 0x7ff0)]),
+            memory,
+        }
+    }
+}
+
+impl EvalContext for Context {
+    fn register(&self, name: &str) -> anyhow::Result<Value> {
+        self.registers
+            .get(name)
+            .map(|x| Value::Int(*x))
+            .ok_or(anyhow!("no register named ${}", name))
+    }
+
+    fn read_memory(&self, address: u64, len: usize) -> anyhow::Result<Vec<u8>> {
+        (address..address + len as u64)
+            .map(|address| {
+                self.memory
+                    .get(&address)
+                    .copied()
+                    .ok_or(anyhow!("unmapped memory at {:#x}", address))
+            })
+            .collect()
+    }
+}
+
+fn eval(input: &str) -> anyhow::Result<Value> {
+    evaluate(input, &Context::new())
+}
+
+#[test]
+fn literals_and_precedence() {
+    assert_eq!(eval("1 + 2 * 3").unwrap(), Value::Int(7));
+    assert_eq!(eval("(1 + 2) * 3").unwrap(), Value::Int(9));
+    assert_eq!(eval("0x10 | 0b1 << 2").unwrap(), Value::Int(0x14));
+    assert_eq!(eval("10 - 4 - 3").unwrap(), Value::Int(3));
+    assert_eq!(eval("-7 % 3").unwrap(), Value::Int(-1));
+    assert_eq!(eval("~0").unwrap(), Value::Int(-1));
+    assert_eq!(eval("0xffffffffffffffff").unwrap(), Value::Int(-1));
+    assert_eq!(eval("1_000").unwrap(), Value::Int(1000));
+}
+
+#[test]
+fn comparisons_and_logic() {
+    assert_eq!(eval("1 < 2 && 2 <= 2").unwrap(), Value::Int(1));
+    assert_eq!(eval("1 == 2 || !1").unwrap(), Value::Int(0));
+    assert_eq!(eval("3 != 3").unwrap(), Value::Int(0));
+    // the right hand side isn't evaluated
+    assert_eq!(eval("0 && *0").unwrap(), Value::Int(0));
+}
+
+#[test]
+fn floats() {
+    assert_eq!(eval("1.5 * 2").unwrap(), Value::Float(3.0));
+    assert_eq!(eval("1e3 + 0.5").unwrap(), Value::Float(1000.5));
+    assert_eq!(eval("2.5 > 2").unwrap(), Value::Int(1));
+    assert!(eval("1.5 & 1").is_err());
+}
+
+#[test]
+fn registers_and_memory() {
+    assert_eq!(eval("$rip + 8").unwrap(), Value::Int(0x401008));
+    assert_eq!(eval("*$rsp").unwrap(), Value::Int(0x1122334455667788));
+    assert_eq!(eval("*($rsp) & 0xff").unwrap(), Value::Int(0x88));
+    assert!(eval("$nope").is_err());
+    assert!(eval("*0x1234").is_err());
+}
+
+#[test]
+fn casts_to_builtin_types() {
+    assert_eq!(eval("(char)0x1ff").unwrap(), Value::Int(-1));
+    assert_eq!(eval("(unsigned short)-1").unwrap(), Value::Int(0xffff));
+    assert_eq!(eval("(long)-1").unwrap(), Value::Int(-1));
+    assert_eq!(eval("(int)2.9").unwrap(), Value::Int(2));
+    assert_eq!(eval("(double)3 / 2").unwrap(), Value::Float(1.5));
+    assert_eq!(eval("(bool)5").unwrap(), Value::Int(1));
+    assert_eq!(eval("(struct Foo *)$rsp").unwrap(), Value::Int(0x7ff0));
+    assert!(eval("(int *)1.5").is_err());
+    assert!(eval("(int)").is_err());
+    assert!(eval("(long long long)1").is_err());
+}
+
+#[test]
+fn dereferencing_typed_pointers() {
+    assert_eq!(eval("*(char *)$rsp").unwrap(), Value::Int(-0x78));
+    assert_eq!(eval("*(unsigned char *)$rsp").unwrap(), Value::Int(0x88));
+    assert_eq!(eval("*(uint16_t *)($rsp + 2)").unwrap(), Value::Int(0x5566));
+    assert_eq!(eval("*(int *)$rsp").unwrap(), Value::Int(0x55667788));
+    // untyped after arithmetic, so it's a pointer sized word again
+    assert_eq!(
+        eval("*((int *)$rsp + 0)").unwrap(),
+        Value::Int(0x1122334455667788)
+    );
+
+    let err = eval("*(struct Foo *)$rsp").unwrap_err();
+    assert!(err.to_string().contains("no debug info for struct Foo"));
+    assert!(eval("*(void *)$rsp").is_err());
+}
+
+#[test]
+fn type_names() {
+    assert_eq!(
+        parse_type("unsigned long **").unwrap(),
+        CType {
+            base: BaseType::Int {
+                size: 8,
+                signed: false
+            },
+            pointers: 2
+        }
+    );
+    assert_eq!(parse_type("const short int").unwrap().to_string(), "short");
+    assert_eq!(
+        parse_type("struct Foo*").unwrap().to_string(),
+        "struct Foo *"
+    );
+    assert_eq!(parse_type("uint8_t").unwrap().to_string(), "unsigned char");
+    assert!(parse_type("MyStruct").is_err());
+    assert!(parse_type("int )").is_err());
+}
+
+#[test]
+fn variables_need_debug_info() {
+    assert_eq!(
+        Expr::parse("a.b + 1").unwrap(),
+        Expr::Binary(
+            BinaryOp::Add,
+            Box::new(Expr::Member(
+                Box::new(Expr::Variable("a".to_string())),
+                "b".to_string()
+            )),
+            Box::new(Expr::Literal(Value::Int(1))),
+        )
+    );
+    assert!(eval("counter").is_err());
+}
+
+#[test]
+fn malformed_expressions() {
+    assert!(eval("1 +").is_err());
+    assert!(eval("(1").is_err());
+    assert!(eval("1 2").is_err());
+    assert!(eval("1 / 0").is_err());
+    assert!(eval("0x").is_err());
+    assert!(eval("1 @ 2").is_err());
+}
+
+#[test]
+fn convenience_variables() {
+    assert_eq!(parse_assignment("$buf = $rsp"), Some(("buf", " $rsp")));
+    assert_eq!(parse_assignment("$buf == 1"), None);
+    assert_eq!(parse_assignment("$ = 1"), None);
+    assert_eq!(parse_assignment("buf = 1"), None);
+    assert_eq!(
+        split_assignment("*($rsp + 8) = 1 <= 2"),
+        Some(("*($rsp + 8)", " 1 <= 2"))
+    );
+    assert_eq!(split_assignment("$rax != 1"), None);
+
+    let context = Context::new();
+    let mut variables = ConvenienceVariables::new();
+    variables.set("buf", eval("$rsp").unwrap()).unwrap();
+    // registers aren't shadowed
+    assert!(variables.set("rip", Value::Int(0)).is_err());
+
+    let context = WithConvenienceVariables {
+        context: &context,
+        variables: &variables,
+    };
+    assert_eq!(
+        evaluate("*$buf", &context).unwrap(),
+        Value::Int(0x1122334455667788)
+    );
+    assert_eq!(evaluate("$buf + 8", &context).unwrap(), Value::Int(0x7ff8));
+    assert_eq!(evaluate("$rip", &context).unwrap(), Value::Int(0x401000));
+    assert!(evaluate("$unset", &context).is_err());
+}
+
+#[test]
+fn addresses() {
+    let context = Context::new();
+    assert_eq!(
+        evaluate_address("0x401000", &context).unwrap().as_u64(),
+        0x401000
+    );
+    assert_eq!(
+        evaluate_address("$rip + 8", &context).unwrap().as_u64(),
+        0x401008
+    );
+    assert_eq!(
+        evaluate_address("*$rsp", &context).unwrap().as_u64(),
+        0x1122334455667788
+    );
+    assert!(evaluate_address("1.5", &context).is_err());
+    // needs symbols
+    assert!(evaluate_address("main+0x20", &context).is_err());
+}
@@ -0,0 +1,49 @@
+use std::{fs::File, os::fd::AsRawFd, path::PathBuf};
+
+use nix::{fcntl::OFlag, unistd::Pid};
+use stupid_dbg_core::file_descriptor::{FdInfo, FdTarget, FileDescriptor};
+
+#[test]
+fn parse_fd_targets() {
+    assert_eq!(
+        FdTarget::from_link("/dev/pts/3"),
+        FdTarget::File(PathBuf::from("/dev/pts/3"))
+    );
+    assert_eq!(
+        FdTarget::from_link("socket:[12345]"),
+        FdTarget::Socket(12345)
+    );
+    assert_eq!(FdTarget::from_link("pipe:[42]"), FdTarget::Pipe(42));
+    assert_eq!(
+        FdTarget::from_link("anon_inode:[eventpoll]"),
+        FdTarget::AnonInode("[eventpoll]".to_string())
+    );
+    assert_eq!(
+        FdTarget::from_link("net:[4026531840]"),
+        FdTarget::Other("net:[4026531840]".to_string())
+    );
+}
+
+#[test]
+fn parse_fdinfo() {
+    let info = "pos:\t1024\nflags:\t02004002\nmnt_id:\t25\nino:\t7\n"
+        .parse::<FdInfo>()
+        .unwrap();
+
+    assert_eq!(info.position, 1024);
+    assert!(info.flags.contains(OFlag::O_RDWR | OFlag::O_NONBLOCK));
+    assert_eq!(info.describe_flags(), "O_RDWR|O_NONBLOCK|O_CLOEXEC");
+
+    assert!("flags:\t0\n".parse::<FdInfo>().is_err());
+}
+
+#[test]
+fn list_own_file_descriptors() {
+    let file = File::open("/proc/self/stat").unwrap();
+    let fds = FileDescriptor::read_from_procfs(Pid::this()).unwrap();
+
+    let fd = fds.iter().find(|fd| fd.fd == file.as_raw_fd()).unwrap();
+    assert!(matches!(&fd.target, FdTarget::File(path) if path.ends_with("stat")));
+    assert_eq!(fd.info.unwrap().position, 0);
+    assert!(fds.windows(2).all(|pair| pair[0].fd < pair[1].fd));
+}
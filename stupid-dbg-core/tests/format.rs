@@ -0,0 +1,74 @@
+use stupid_dbg_core::format::{
+    format_c_string, format_char, format_unit, hexdump_line, printable_runs, strings_in, Format,
+    Letter,
+};
+
+#[test]
+fn escapes() {
+    assert_eq!(format_char(65), "65 'A'");
+    assert_eq!(format_char(10), "10 '\\n'");
+    assert_eq!(format_char(0x27), "39 '\\''");
+    assert_eq!(format_char(200), "-56 '\\310'");
+    assert_eq!(
+        format_c_string(b"tab\there \"quoted\"\0"),
+        "\"tab\\there \\\"quoted\\\"\\0\""
+    );
+    assert_eq!(format_c_string("héllo".as_bytes()), "\"héllo\"");
+    assert_eq!(format_c_string(&[b'a', 0xff, b'b']), "\"a\\377b\"");
+}
+
+#[test]
+fn printable_runs_in_bytes() {
+    let bytes = b"\x01\x02hello\x00\xffab\x00w\xc3\xb6rld!";
+    assert_eq!(printable_runs(bytes, 4), vec![2..7, 12..19]);
+    assert_eq!(strings_in(bytes), vec!["\"hello\"", "\"wörld!\""]);
+    assert!(strings_in(&0x0102_0304_8090_a0b0u64.to_le_bytes()).is_empty());
+}
+
+#[test]
+fn format_suffixes() {
+    assert_eq!(
+        "32bx".parse::<Format>().unwrap(),
+        Format {
+            count: Some(32),
+            unit: Some(1),
+            letter: Some(Letter::Hex),
+        }
+    );
+    assert_eq!(
+        "s".parse::<Format>().unwrap(),
+        Format {
+            count: None,
+            unit: None,
+            letter: Some(Letter::String),
+        }
+    );
+    assert_eq!("xg".parse::<Format>().unwrap().unit, Some(8));
+    assert_eq!(
+        "16i".parse::<Format>().unwrap().letter,
+        Some(Letter::Instruction)
+    );
+    assert!("4bh".parse::<Format>().is_err());
+    assert!("q".parse::<Format>().is_err());
+}
+
+#[test]
+fn units() {
+    assert_eq!(format_unit(&[0xff, 0xff], Letter::Hex), "0xffff");
+    assert_eq!(format_unit(&[0xff, 0xff], Letter::Decimal), "-1");
+    assert_eq!(format_unit(&[0xff, 0xff], Letter::Unsigned), "65535");
+    assert_eq!(format_unit(&[0x01], Letter::Hex), "0x01");
+    assert_eq!(format_unit(b"A", Letter::Char), "65 'A'");
+}
+
+#[test]
+fn hexdump_lines() {
+    assert_eq!(
+        hexdump_line(b"\x7fELF\x02\x01\x01\0\0\0\0\0\0\0\0\0"),
+        "7f 45 4c 46 02 01 01 00  00 00 00 00 00 00 00 00  |.ELF............|"
+    );
+    assert_eq!(
+        hexdump_line(b"hi\n"),
+        "68 69 0a                                          |hi.|"
+    );
+}
@@ -0,0 +1,41 @@
+use stupid_dbg_core::fp_control::{decode, encode};
+
+#[test]
+fn default_control_words_are_decoded() {
+    assert_eq!(
+        decode("fcw", 0x037f).unwrap().to_string(),
+        "mask=IE|DE|ZE|OE|UE|PE pc=extended rc=nearest"
+    );
+    assert_eq!(
+        decode("mxcsr", 0x1f80).unwrap().to_string(),
+        "flags=none daz=0 mask=IE|DE|ZE|OE|UE|PE rc=nearest fz=0"
+    );
+    // top of stack at 7 with a divide by zero pending
+    assert_eq!(
+        decode("fsw", 0x3804).unwrap().to_string(),
+        "flags=ZE sf=0 es=0 c0=0 c1=0 c2=0 top=7 c3=0 b=0"
+    );
+    assert_eq!(
+        decode("ftw", 0x80).unwrap().0[7],
+        ("r7", "valid".to_string())
+    );
+    assert!(decode("rax", 0).is_none());
+}
+
+#[test]
+fn fields_are_encoded() {
+    assert_eq!(encode("mxcsr", 0x1f80, &["rc=zero"]).unwrap(), 0x7f80);
+    assert_eq!(
+        encode("mxcsr", 0x1f80, &["mask=none", "flags=ZE|pe", "fz=1"]).unwrap(),
+        0x8024
+    );
+    assert_eq!(
+        encode("fcw", 0x037f, &["pc=double", "rc=up"]).unwrap(),
+        0x0a7f
+    );
+
+    assert!(encode("fcw", 0x037f, &["rc=sideways"]).is_err());
+    assert!(encode("fcw", 0x037f, &["daz=1"]).is_err());
+    assert!(encode("fsw", 0, &["top=8"]).is_err());
+    assert!(encode("rax", 0, &["rc=up"]).is_err());
+}
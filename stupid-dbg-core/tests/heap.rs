@@ -0,0 +1,73 @@
+use nix::unistd::Pid;
+use stupid_dbg_core::{
+    heap::{AllocFunction, HeapCall, HeapTrace},
+    virt_addr::VirtAddr,
+};
+
+fn call(function: AllocFunction, args: &[u64], result: u64, call_site: u64) -> HeapCall {
+    let mut all_args = [0; 6];
+    all_args[..args.len()].copy_from_slice(args);
+    HeapCall {
+        function,
+        thread: Pid::from_raw(1),
+        args: all_args,
+        result,
+        backtrace: vec![VirtAddr::new(call_site)],
+    }
+}
+
+#[test]
+fn allocations_are_attributed() {
+    let mut heap_trace = HeapTrace::default();
+    heap_trace.record(call(AllocFunction::Malloc, &[32], 0x5000, 0x1100));
+    heap_trace.record(call(AllocFunction::Calloc, &[4, 8], 0x6000, 0x1200));
+    heap_trace.record(call(AllocFunction::Malloc, &[16], 0x7000, 0x1100));
+
+    let found = heap_trace.find(VirtAddr::new(0x5010)).unwrap();
+    assert_eq!(found.address, VirtAddr::new(0x5000));
+    assert_eq!(found.call_site(), Some(VirtAddr::new(0x1100)));
+    assert!(heap_trace.find(VirtAddr::new(0x5020)).is_none());
+    assert_eq!(heap_trace.find(VirtAddr::new(0x601f)).unwrap().size, 32);
+
+    assert_eq!(
+        heap_trace.call_sites(),
+        vec![
+            (Some(VirtAddr::new(0x1100)), 2, 48),
+            (Some(VirtAddr::new(0x1200)), 1, 32),
+        ]
+    );
+}
+
+#[test]
+fn releases_are_remembered() {
+    let mut heap_trace = HeapTrace::default();
+    heap_trace.record(call(AllocFunction::Malloc, &[32], 0x5000, 0x1100));
+    heap_trace.record(call(AllocFunction::Realloc, &[0x5000, 64], 0x8000, 0x1200));
+    heap_trace.record(call(AllocFunction::Free, &[0x9000], 0, 0x1300));
+
+    let stats = heap_trace.stats();
+    assert_eq!(stats.live_allocations, 1);
+    assert_eq!(stats.live_bytes, 64);
+    assert_eq!(stats.peak_bytes, 96);
+    assert_eq!(stats.releases, 1);
+    assert_eq!(stats.unknown_releases, 1);
+    assert_eq!(stats.calls[&AllocFunction::Realloc], 1);
+
+    // a dangling pointer still leads to the block it pointed into
+    let dangling = heap_trace.find(VirtAddr::new(0x5008)).unwrap();
+    assert_eq!(dangling.released_at, Some(VirtAddr::new(0x1200)));
+
+    // a failed realloc keeps the block
+    heap_trace.record(call(AllocFunction::Realloc, &[0x8000, 1 << 40], 0, 0x1400));
+    assert!(heap_trace
+        .find(VirtAddr::new(0x8000))
+        .unwrap()
+        .released_at
+        .is_none());
+
+    // failed mappings and foreign unmaps are left out
+    heap_trace.record(call(AllocFunction::Mmap, &[0, 4096], u64::MAX, 0x1500));
+    heap_trace.record(call(AllocFunction::Munmap, &[0xa000, 4096], 0, 0x1600));
+    assert_eq!(heap_trace.stats().allocations, 2);
+    assert_eq!(heap_trace.stats().unknown_releases, 1);
+}
@@ -0,0 +1,68 @@
+use std::{fs, time::Duration};
+
+use nix::unistd::Pid;
+use stupid_dbg_core::{
+    instruction_trace::{InstructionRecord, InstructionTrace},
+    register::{Register, RegisterValue},
+    tracepoint::{Backpressure, TraceFile},
+    virt_addr::VirtAddr,
+};
+
+fn record(pc: u64, registers: Vec<(Register, RegisterValue)>) -> InstructionRecord {
+    InstructionRecord {
+        index: 0,
+        thread: Pid::from_raw(42),
+        pc: VirtAddr::new(pc),
+        registers,
+    }
+}
+
+#[test]
+fn oldest_instructions_are_dropped() {
+    let mut trace = InstructionTrace::new(2, Vec::new());
+    for pc in [0x1000, 0x1004, 0x1008] {
+        trace.record(record(pc, Vec::new()));
+    }
+
+    let records = trace.records();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records.dropped(), 1);
+    assert_eq!(
+        records
+            .frames()
+            .map(|record| (record.index, record.pc.as_u64()))
+            .collect::<Vec<_>>(),
+        vec![(1, 0x1004), (2, 0x1008)]
+    );
+}
+
+#[test]
+fn instructions_stream_to_the_trace_file() {
+    let rax = Register::lookup_by_name("rax").unwrap();
+    let mut trace = InstructionTrace::new(4, vec![rax]);
+    for (pc, value) in [(0x1000, 1), (0x1003, 2)] {
+        trace.record(record(pc, vec![(rax, RegisterValue::U64(value))]));
+    }
+    assert_eq!(
+        trace.records().frames().next().unwrap().to_string(),
+        "insn=0 thread=42 pc=0x1000 rax=0x0000000000000001"
+    );
+
+    let path = std::env::temp_dir().join(format!(
+        "stupid-dbg-instructions-{}.txt",
+        std::process::id()
+    ));
+    _ = fs::remove_file(&path);
+    let mut trace_file =
+        TraceFile::create(&path, 4, Backpressure::Block(Duration::from_secs(1))).unwrap();
+    for record in trace.records().frames() {
+        assert!(trace_file.write(record));
+    }
+    trace_file.close().unwrap();
+
+    let text = fs::read_to_string(&path).unwrap();
+    let lines = text.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[1].starts_with("insn=1 thread=42 pc=0x1003"));
+    _ = fs::remove_file(&path);
+}
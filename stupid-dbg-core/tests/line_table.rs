@@ -0,0 +1,111 @@
+use std::path::Path;
+
+use stupid_dbg_core::{
+    elf::ElfFile,
+    line_table::{parse_file_line, LineTable},
+    virt_addr::VirtAddr,
+};
+
+// A DWARF 4 unit for /src/main.c: line 10 at 0x1000, 11 at 0x1004 and 13 at 0x100a, up to
+// 0x100e.
+fn debug_line() -> Vec<u8> {
+    let mut header = vec![
+        1,    // minimum_instruction_length
+        1,    // maximum_operations_per_instruction
+        1,    // default_is_stmt
+        0xfb, // line_base, -5
+        14,   // line_range
+        13,   // opcode_base
+    ];
+    header.extend([0, 1, 1, 1, 1, 0, 0, 0, 1, 0, 0, 1]);
+    header.extend(b"/src\0\0");
+    header.extend(b"main.c\0\x01\x00\x00\0");
+
+    let mut program = vec![0, 9, 2];
+    program.extend(0x1000u64.to_le_bytes());
+    // advance_line to 10, copy, special opcodes for address + 4 and line + 1 then address + 6
+    // and line + 2, advance_pc by 4 and end_sequence
+    program.extend([3, 9, 1, 75, 104, 2, 4, 0, 1, 1]);
+
+    let mut unit = 4u16.to_le_bytes().to_vec();
+    unit.extend((header.len() as u32).to_le_bytes());
+    unit.extend(header);
+    unit.extend(program);
+
+    let mut section = (unit.len() as u32).to_le_bytes().to_vec();
+    section.extend(unit);
+    section
+}
+
+#[test]
+fn rows_of_a_line_program() {
+    let table = LineTable::parse(&debug_line(), &[], &[], 0).unwrap();
+    let rows = table
+        .rows()
+        .map(|row| (row.address.as_u64(), row.line, row.end_sequence))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        rows,
+        vec![
+            (0x1000, 10, false),
+            (0x1004, 11, false),
+            (0x100a, 13, false),
+            (0x100e, 13, true),
+        ]
+    );
+    let first = table.rows().next().unwrap();
+    assert_eq!(first.file, Path::new("/src/main.c"));
+    assert_eq!(first.location().to_string(), "/src/main.c:10");
+}
+
+#[test]
+fn big_sections_are_parsed_in_parallel() {
+    let unit = debug_line();
+    // past the size parsing is split between threads at
+    let copies = (1 << 20) / unit.len() + 1;
+    let table = LineTable::parse(&unit.repeat(copies), &[], &[], 0).unwrap();
+    assert_eq!(table.rows().count(), 4 * copies);
+    assert_eq!(table.lookup(VirtAddr::new(0x1005)).unwrap().line, 11);
+}
+
+#[test]
+fn lookup_by_address() {
+    let table = LineTable::parse(&debug_line(), &[], &[], 0x5000).unwrap();
+    assert_eq!(table.lookup(VirtAddr::new(0x6005)).unwrap().line, 11);
+    assert_eq!(table.lookup(VirtAddr::new(0x6000)).unwrap().line, 10);
+    assert!(table.lookup(VirtAddr::new(0x5fff)).is_none());
+    assert!(table.lookup(VirtAddr::new(0x600e)).is_none());
+}
+
+#[test]
+fn find_line_takes_the_next_line_with_code() {
+    let table = LineTable::parse(&debug_line(), &[], &[], 0).unwrap();
+    assert_eq!(
+        table.find_line("main.c", 10).unwrap().address,
+        VirtAddr::new(0x1000)
+    );
+    let row = table.find_line("src/main.c", 12).unwrap();
+    assert_eq!((row.line, row.address), (13, VirtAddr::new(0x100a)));
+    assert!(table.find_line("main.c", 14).is_err());
+    assert!(table.find_line("ain.c", 10).is_err());
+    assert!(table.find_line("other.c", 10).is_err());
+}
+
+#[test]
+fn file_line_specs() {
+    assert_eq!(parse_file_line("main.rs:42"), Some(("main.rs", 42)));
+    assert_eq!(parse_file_line("src/a b.c:7"), Some(("src/a b.c", 7)));
+    assert_eq!(parse_file_line("my_crate::foo"), None);
+    assert_eq!(parse_file_line("main.rs"), None);
+    assert_eq!(parse_file_line(":42"), None);
+}
+
+#[test]
+fn line_table_of_the_test_binary() {
+    let elf = ElfFile::read("/proc/self/exe").unwrap();
+    let table = LineTable::from_elf(&elf, 0).unwrap();
+    let line = line!();
+    let row = table.find_line("tests/line_table.rs", line as u64).unwrap();
+    assert!(row.line >= line as u64);
+    assert!(table.lookup(row.address).is_some());
+}
@@ -0,0 +1,22 @@
+use stupid_dbg_core::mapped_file::MappedFile;
+
+#[test]
+fn mapped_and_read_files_agree() {
+    let mapped = MappedFile::open("/proc/self/exe").unwrap();
+    assert_eq!(mapped.bytes(), std::fs::read("/proc/self/exe").unwrap());
+}
+
+#[test]
+fn empty_files_map_to_no_bytes() {
+    let path = std::env::temp_dir().join(format!("stupid-dbg-empty-{}", std::process::id()));
+    std::fs::write(&path, []).unwrap();
+    let mapped = MappedFile::open(&path);
+    _ = std::fs::remove_file(&path);
+    assert!(mapped.unwrap().bytes().is_empty());
+}
+
+#[test]
+fn missing_files_are_reported() {
+    let err = MappedFile::open("/nonexistent").unwrap_err();
+    assert!(err.to_string().contains("/nonexistent"));
+}
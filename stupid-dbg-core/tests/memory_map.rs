@@ -0,0 +1,152 @@
+use std::collections::BTreeMap;
+
+use nix::unistd::Pid;
+use stupid_dbg_core::{
+    memory_map::{MemoryMap, MemoryRegion, Permissions, RegionKind},
+    virt_addr::VirtAddr,
+};
+
+#[test]
+fn parse_memory_map() {
+    let maps = "\
+55d0c0a3b000-55d0c0a3d000 r--p 00000000 fd:01 1234                       /usr/bin/cat
+7ffd5a1e5000-7ffd5a206000 rw-p 00000000 00:00 0                          [stack]
+7f3a00000000-7f3a00021000 rw-s 00001000 00:05 42                         /dev/shm/with space
+7f3a00021000-7f3a04000000 ---p 00000000 00:00 0
+";
+
+    let memory_map = maps.parse::<MemoryMap>().unwrap();
+    let regions = memory_map.regions();
+
+    assert_eq!(regions.len(), 4);
+    assert_eq!(
+        regions[0],
+        MemoryRegion {
+            start: VirtAddr::new(0x55d0c0a3b000),
+            end: VirtAddr::new(0x55d0c0a3d000),
+            permissions: Permissions {
+                read: true,
+                write: false,
+                execute: false,
+                shared: false,
+            },
+            offset: 0,
+            device: "fd:01".to_string(),
+            inode: 1234,
+            path: Some("/usr/bin/cat".to_string()),
+        }
+    );
+    assert_eq!(regions[1].path.as_deref(), Some("[stack]"));
+    assert_eq!(regions[2].path.as_deref(), Some("/dev/shm/with space"));
+    assert_eq!(regions[2].offset, 0x1000);
+    assert!(regions[2].permissions.shared);
+    assert_eq!(regions[3].path, None);
+    assert_eq!(regions[3].size(), 0x7f3a04000000 - 0x7f3a00021000);
+}
+
+#[test]
+fn parse_malformed_memory_map() {
+    assert!("55d0c0a3b000 r--p 00000000 fd:01 1234"
+        .parse::<MemoryMap>()
+        .is_err());
+    assert!("55d0c0a3b000-55d0c0a3d000 r--p"
+        .parse::<MemoryMap>()
+        .is_err());
+}
+
+#[test]
+fn permissions_as_prot() {
+    let prot = |s: &str| s.parse::<Permissions>().unwrap().prot();
+    assert_eq!(prot("rw-p"), libc::PROT_READ | libc::PROT_WRITE);
+    assert_eq!(prot("r-xp"), libc::PROT_READ | libc::PROT_EXEC);
+    assert_eq!(prot("---p"), libc::PROT_NONE);
+}
+
+#[test]
+fn classify_regions() {
+    let maps = "\
+55d0c0a3b000-55d0c0a3c000 r--p 00000000 fd:01 1234 /usr/bin/cat
+55d0c0a3c000-55d0c0a3d000 r-xp 00001000 fd:01 1234 /usr/bin/cat
+55d0c1000000-55d0c1021000 rw-p 00000000 00:00 0 [heap]
+7f3a00000000-7f3a00001000 ---p 00000000 00:00 0
+7f3a00001000-7f3a00801000 rw-p 00000000 00:00 0
+7f3a00801000-7f3a00802000 rw-p 00000000 00:00 0
+7ffd5a1e5000-7ffd5a206000 rw-p 00000000 00:00 0 [stack]
+7ffd5a3f0000-7ffd5a3f2000 r-xp 00000000 00:00 0 [vdso]
+7ffd5a3f2000-7ffd5a3f4000 r--p 00000000 00:00 0 [vvar]
+";
+    let memory_map = maps.parse::<MemoryMap>().unwrap();
+    let main = Pid::from_raw(100);
+    let thread = Pid::from_raw(101);
+    let stack_pointers = BTreeMap::from([
+        (main, VirtAddr::new(0x7ffd5a205f00)),
+        (thread, VirtAddr::new(0x7f3a00800f00)),
+    ]);
+
+    let kinds = memory_map
+        .classify(&stack_pointers)
+        .into_iter()
+        .map(|(_, kind)| kind)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        kinds,
+        vec![
+            RegionKind::Data("/usr/bin/cat".to_string()),
+            RegionKind::Text("/usr/bin/cat".to_string()),
+            RegionKind::Heap,
+            RegionKind::Anonymous,
+            RegionKind::Stack(Some(thread)),
+            RegionKind::Anonymous,
+            RegionKind::Stack(Some(main)),
+            RegionKind::Vdso,
+            RegionKind::Special("[vvar]".to_string()),
+        ]
+    );
+
+    // a stack pointer moved into the heap doesn't make it a stack
+    let stack_pointers = BTreeMap::from([(main, VirtAddr::new(0x55d0c1000100))]);
+    assert_eq!(
+        memory_map.kind_of(VirtAddr::new(0x55d0c1000100), &stack_pointers),
+        Some(RegionKind::Heap)
+    );
+    assert_eq!(
+        memory_map.kind_of(VirtAddr::new(0x7f3a00801100), &stack_pointers),
+        Some(RegionKind::Anonymous)
+    );
+    assert_eq!(
+        memory_map.kind_of(VirtAddr::new(0x1000), &stack_pointers),
+        None
+    );
+    assert_eq!(
+        RegionKind::Stack(Some(thread)).to_string(),
+        "stack of thread 101"
+    );
+}
+
+#[test]
+fn regions_spanning_a_range() {
+    let maps = "\
+55d0c0a3b000-55d0c0a3c000 r--p 00000000 fd:01 1234 /usr/bin/cat
+55d0c0a3c000-55d0c0a3d000 r-xp 00001000 fd:01 1234 /usr/bin/cat
+55d0c1000000-55d0c1021000 rw-p 00000000 00:00 0 [heap]
+";
+    let memory_map = maps.parse::<MemoryMap>().unwrap();
+    let starts = |start: u64, len: u64| {
+        memory_map
+            .regions_spanning(VirtAddr::new(start), len)
+            .map(|regions| {
+                regions
+                    .iter()
+                    .map(|region| region.start.as_u64())
+                    .collect::<Vec<_>>()
+            })
+    };
+
+    assert_eq!(
+        starts(0x55d0c0a3bff0, 0x20),
+        Some(vec![0x55d0c0a3b000, 0x55d0c0a3c000])
+    );
+    assert_eq!(starts(0x55d0c1000000, 0x21000), Some(vec![0x55d0c1000000]));
+    assert_eq!(starts(0x55d0c0a3cff0, 0x20), None);
+    assert_eq!(starts(0x55d0c1000000, 0), Some(vec![]));
+}
@@ -0,0 +1,29 @@
+use std::path::{Path, PathBuf};
+
+use nix::unistd::{getpid, Pid};
+use stupid_dbg_core::namespace::{namespace_pid, translate, ProcessRoot};
+
+#[test]
+fn translating_paths() {
+    let root = Path::new("/proc/42/root");
+    assert_eq!(
+        translate(root, Path::new("/usr/lib/libc.so.6")),
+        PathBuf::from("/proc/42/root/usr/lib/libc.so.6")
+    );
+    assert_eq!(
+        translate(root, Path::new("/app/../../etc/./passwd")),
+        PathBuf::from("/proc/42/root/etc/passwd")
+    );
+    assert_eq!(translate(root, Path::new("/")), root.to_path_buf());
+}
+
+#[test]
+fn own_root() {
+    let root = ProcessRoot::of(getpid());
+    assert!(!root.is_foreign());
+    assert_eq!(
+        root.host_path("/usr/lib/libc.so.6"),
+        PathBuf::from("/usr/lib/libc.so.6")
+    );
+    assert_ne!(namespace_pid(getpid()).unwrap(), Pid::from_raw(0));
+}
@@ -0,0 +1,27 @@
+use stupid_dbg_core::plain_data::{FromBytes, ToBytes};
+
+#[test]
+fn numbers_from_and_to_bytes() {
+    assert_eq!(u32::SIZE, 4);
+    assert_eq!(
+        u32::from_bytes(&0xdeadbeefu32.to_ne_bytes()),
+        Some(0xdeadbeef)
+    );
+    assert_eq!(i16::from_bytes(&[0xff, 0xff]), Some(-1));
+    assert_eq!(f64::from_bytes(&1.5f64.to_bytes()), Some(1.5));
+    assert_eq!(u64::from_bytes(&[0; 4]), None);
+    assert_eq!(0x0102u16.to_bytes(), 0x0102u16.to_ne_bytes());
+}
+
+#[test]
+fn arrays_from_and_to_bytes() {
+    assert_eq!(<[u16; 3]>::SIZE, 6);
+    let bytes = [1u16, 2, 3].to_bytes();
+    assert_eq!(bytes.len(), 6);
+    assert_eq!(<[u16; 3]>::from_bytes(&bytes), Some([1, 2, 3]));
+    assert_eq!(<[u16; 3]>::from_bytes(&bytes[..4]), None);
+    assert_eq!(
+        <[[u8; 2]; 2]>::from_bytes(&[1, 2, 3, 4]),
+        Some([[1, 2], [3, 4]])
+    );
+}
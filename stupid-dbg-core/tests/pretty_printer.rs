@@ -0,0 +1,180 @@
+use std::collections::BTreeMap;
+
+use anyhow::anyhow;
+use stupid_dbg_core::{
+    expression::{EvalContext, Value},
+    memory_map::MemoryMap,
+    pretty_printer::{pretty_print, pretty_print_or_raw, PrettyValue, StdLib, StdType},
+};
+
+#[derive(Default)]
+struct Memory {
+    bytes: BTreeMap<u64, u8>,
+}
+
+impl Memory {
+    fn write(&mut self, address: u64, bytes: &[u8]) {
+        for (offset, byte) in bytes.iter().enumerate() {
+            self.bytes.insert(address + offset as u64, *byte);
+        }
+    }
+
+    fn write_words(&mut self, address: u64, words: &[u64]) {
+        for (index, word) in words.iter().enumerate() {
+            self.write(address + 8 * index as u64, &word.to_le_bytes());
+        }
+    }
+}
+
+impl EvalContext for Memory {
+    fn register(&self, name: &str) -> anyhow::Result<Value> {
+        Err(anyhow!("no register named ${}", name))
+    }
+
+    fn read_memory(&self, address: u64, len: usize) -> anyhow::Result<Vec<u8>> {
+        (address..address + len as u64)
+            .map(|address| {
+                self.bytes
+                    .get(&address)
+                    .copied()
+                    .ok_or(anyhow!("unmapped memory at {:#x}", address))
+            })
+            .collect()
+    }
+}
+
+#[test]
+fn detect_standard_library() {
+    let memory_map: MemoryMap = "\
+00400000-00401000 r-xp 00000000 08:01 1 /usr/bin/app
+7f0000000000-7f0000001000 r-xp 00000000 08:01 2 /usr/lib/libstdc++.so.6.0.33
+"
+    .parse()
+    .unwrap();
+    assert_eq!(StdLib::detect(&memory_map), Some(StdLib::LibStdCxx));
+
+    let memory_map: MemoryMap = "00400000-00401000 r-xp 00000000 08:01 1 /usr/lib/libc++.so.1\n"
+        .parse()
+        .unwrap();
+    assert_eq!(StdLib::detect(&memory_map), Some(StdLib::LibCxx));
+}
+
+#[test]
+fn strings() {
+    let mut memory = Memory::default();
+    // libstdc++, short string kept in the object
+    memory.write_words(0x1000, &[0x1010, 5]);
+    memory.write(0x1010, b"hello\0");
+    // libstdc++, long string on the heap
+    memory.write_words(0x2000, &[0x3000, 20, 32]);
+    memory.write(0x3000, b"a string on the heap");
+    // libc++, short string
+    memory.write(0x4000, &[2 << 1]);
+    memory.write(0x4001, b"hi");
+    // libc++, long string
+    memory.write_words(0x5000, &[33, 20, 0x3000]);
+
+    let string = |lib, address| {
+        pretty_print(&memory, lib, StdType::String, address)
+            .unwrap()
+            .to_string()
+    };
+    assert_eq!(string(StdLib::LibStdCxx, 0x1000), "\"hello\"");
+    assert_eq!(
+        string(StdLib::LibStdCxx, 0x2000),
+        "\"a string on the heap\""
+    );
+    assert_eq!(string(StdLib::LibCxx, 0x4000), "\"hi\"");
+    assert_eq!(string(StdLib::LibCxx, 0x5000), "\"a string on the heap\"");
+}
+
+#[test]
+fn vectors_and_smart_pointers() {
+    let mut memory = Memory::default();
+    memory.write_words(0x1000, &[0x2000, 0x200c, 0x2010]);
+    memory.write(0x2000, &[1, 0, 0, 0, 2, 0, 0, 0, 3, 0, 0, 0]);
+    assert_eq!(
+        pretty_print(
+            &memory,
+            StdLib::LibCxx,
+            StdType::Vector { element_size: 4 },
+            0x1000
+        )
+        .unwrap()
+        .to_string(),
+        "std::vector of length 3, capacity 4 = {0x1, 0x2, 0x3}"
+    );
+
+    // control blocks start with a vtable pointer
+    memory.write_words(0x3000, &[0x5000, 0x4000]);
+    memory.write_words(0x4000, &[0, (1 << 32) | 2, 0]);
+    assert_eq!(
+        pretty_print(&memory, StdLib::LibStdCxx, StdType::SharedPtr, 0x3000).unwrap(),
+        PrettyValue::SharedPtr {
+            pointer: 0x5000,
+            use_count: 2,
+            weak_count: 1,
+        }
+    );
+    memory.write_words(0x4000, &[0, 1, 0]);
+    assert_eq!(
+        pretty_print(&memory, StdLib::LibCxx, StdType::SharedPtr, 0x3000).unwrap(),
+        PrettyValue::SharedPtr {
+            pointer: 0x5000,
+            use_count: 2,
+            weak_count: 1,
+        }
+    );
+}
+
+#[test]
+fn maps() {
+    // libstdc++, 2 at the root with 1 and 3 as children
+    let mut memory = Memory::default();
+    memory.write_words(0x1000, &[0, 0, 0x2000, 0x3000, 0x4000, 3]);
+    memory.write_words(0x2000, &[1, 0x1008, 0x3000, 0x4000, 2]);
+    memory.write_words(0x3000, &[0, 0x2000, 0, 0, 1]);
+    memory.write_words(0x4000, &[0, 0x2000, 0, 0, 3]);
+    assert_eq!(
+        pretty_print(
+            &memory,
+            StdLib::LibStdCxx,
+            StdType::Map { element_size: 8 },
+            0x1000
+        )
+        .unwrap()
+        .to_string(),
+        "std::map with 3 elements = {0x1, 0x2, 0x3}"
+    );
+
+    // libc++, same tree with left, right, parent and color first
+    let mut memory = Memory::default();
+    memory.write_words(0x1000, &[0x3000, 0x2000, 3]);
+    memory.write_words(0x2000, &[0x3000, 0x4000, 0x1008, 1, 2]);
+    memory.write_words(0x3000, &[0, 0, 0x2000, 0, 1]);
+    memory.write_words(0x4000, &[0, 0, 0x2000, 0, 3]);
+    assert_eq!(
+        pretty_print(
+            &memory,
+            StdLib::LibCxx,
+            StdType::Map { element_size: 8 },
+            0x1000
+        )
+        .unwrap()
+        .to_string(),
+        "std::map with 3 elements = {0x1, 0x2, 0x3}"
+    );
+}
+
+#[test]
+fn undecodable_objects_show_raw_members() {
+    let mut memory = Memory::default();
+    // end before begin
+    memory.write_words(0x1000, &[0x2010, 0x2000, 0x2020]);
+    let vector = StdType::Vector { element_size: 8 };
+    assert!(pretty_print(&memory, StdLib::LibStdCxx, vector, 0x1000).is_err());
+    assert_eq!(
+        pretty_print_or_raw(&memory, StdLib::LibStdCxx, vector, 0x1000).unwrap(),
+        PrettyValue::Raw(vec![0x2010, 0x2000, 0x2020])
+    );
+}
@@ -0,0 +1,89 @@
+use std::collections::BTreeMap;
+
+use anyhow::anyhow;
+use nix::unistd::Pid;
+use stupid_dbg_core::{
+    elf::SymbolKind,
+    memory_map::{MemoryMap, RegionKind},
+    provenance::Provenance,
+    symbols::{Symbol, SymbolTable},
+    virt_addr::VirtAddr,
+};
+
+const MAPS: &str = "\
+555555554000-555555556000 r-xp 00000000 fd:01 1234                       /bin/app
+555555556000-555555557000 r--p 00002000 fd:01 1234                       /bin/app
+7ffc00000000-7ffc00021000 rw-p 00000000 00:00 0                          [stack]
+";
+
+fn symbol(name: &str, address: u64, kind: SymbolKind) -> Symbol {
+    Symbol {
+        name: name.to_string(),
+        demangled: None,
+        address: VirtAddr::new(address),
+        size: 0x20,
+        kind,
+        module: "/bin/app".to_string(),
+    }
+}
+
+fn provenance_of(address: u64, memory: &[u8]) -> Option<Provenance> {
+    let memory_map = MAPS.parse::<MemoryMap>().unwrap();
+    let stack_pointers = BTreeMap::from([(Pid::from_raw(1234), VirtAddr::new(0x7ffc00010000))]);
+    let symbols = SymbolTable::from_symbols(vec![
+        symbol("main", 0x555555555000, SymbolKind::Function),
+        symbol("greeting", 0x555555556010, SymbolKind::Object),
+    ]);
+    Provenance::of(
+        VirtAddr::new(address),
+        &memory_map,
+        &stack_pointers,
+        &symbols,
+        |_, len| match memory {
+            [] => Err(anyhow!("unreadable")),
+            memory => Ok(memory[..len.min(memory.len())].to_vec()),
+        },
+    )
+}
+
+#[test]
+fn pointers_are_described_by_what_they_point_to() {
+    assert_eq!(
+        provenance_of(0x555555555004, b"\x55\x48\x89\xe5")
+            .unwrap()
+            .to_string(),
+        "text of /bin/app <main+0x4>"
+    );
+    assert_eq!(
+        provenance_of(0x555555556010, b"hello\0world")
+            .unwrap()
+            .to_string(),
+        "data of /bin/app <greeting> \"hello\""
+    );
+
+    let stack = provenance_of(0x7ffc00010008, b"\x01\x02").unwrap();
+    assert_eq!(stack.region, RegionKind::Stack(Some(Pid::from_raw(1234))));
+    assert_eq!(stack.string, None);
+    assert_eq!(stack.to_string(), "stack of thread 1234");
+
+    assert_eq!(provenance_of(0x10, b"hello\0"), None);
+}
+
+#[test]
+fn only_whole_strings_are_shown() {
+    // too short, or followed by something that isn't text
+    assert_eq!(
+        provenance_of(0x7ffc00010000, b"abc\0").unwrap().string,
+        None
+    );
+    assert_eq!(
+        provenance_of(0x7ffc00010000, b"abcd\x01").unwrap().string,
+        None
+    );
+    // cut off by the preview length or the end of the region
+    assert_eq!(
+        provenance_of(0x7ffc00020ffc, b"abcdefgh").unwrap().string,
+        Some("\"abcd\"...".to_string())
+    );
+    assert_eq!(provenance_of(0x7ffc00010000, b"").unwrap().string, None);
+}
@@ -0,0 +1,243 @@
+use std::{iter, mem::MaybeUninit};
+
+use stupid_dbg_core::register::{
+    LaneFormat, Register, RegisterGroup, RegisterKind, RegisterRepr, RegisterValue,
+};
+
+fn assert_read_register_value(
+    register: Register,
+    expected_value: RegisterValue,
+    user: &libc::user,
+) {
+    let actual_value = register.read_from_user_struct(user).unwrap();
+    if actual_value.ne(&expected_value) {
+        panic!(
+            "register: {:?}: unexpected register value, expected: {:?}, actual: {:?}",
+            register, expected_value, actual_value
+        )
+    }
+}
+
+fn write_and_check_register_value(register: Register, value: RegisterValue, user: &mut libc::user) {
+    register.write_to_user_struct(user, value).unwrap();
+    assert_read_register_value(register, value, user);
+}
+
+unsafe fn write_any_and_check_register_value<T: Sized>(
+    register: Register,
+    value: T,
+    expected_value: RegisterValue,
+    user: &mut libc::user,
+) {
+    register.write_any_to_user_struct(user, &value).unwrap();
+    assert_read_register_value(register, expected_value, user);
+}
+
+#[test]
+fn read_and_write_registers() {
+    let mut user = unsafe { MaybeUninit::<libc::user>::zeroed().assume_init() };
+    assert_read_register_value(Register::Rax, RegisterValue::U64(0), &user);
+    assert_read_register_value(Register::R15d, RegisterValue::U32(0), &user);
+    assert_read_register_value(
+        Register::Xmm0,
+        RegisterValue::Byte128(
+            iter::repeat(0u8)
+                .take(16)
+                .collect::<Vec<u8>>()
+                .try_into()
+                .unwrap(),
+        ),
+        &user,
+    );
+
+    write_and_check_register_value(Register::Rax, RegisterValue::U64(1), &mut user);
+    write_and_check_register_value(Register::R15d, RegisterValue::U32(2), &mut user);
+    write_and_check_register_value(
+        Register::Xmm0,
+        RegisterValue::Byte128(
+            iter::repeat(42u8)
+                .take(16)
+                .collect::<Vec<u8>>()
+                .try_into()
+                .unwrap(),
+        ),
+        &mut user,
+    );
+
+    unsafe {
+        write_any_and_check_register_value(
+            Register::R15d,
+            69u8,
+            RegisterValue::U32(69u32),
+            &mut user,
+        );
+        write_any_and_check_register_value(
+            Register::R15d,
+            69u16,
+            RegisterValue::U32(69u32),
+            &mut user,
+        );
+        write_any_and_check_register_value(
+            Register::R15d,
+            69u32,
+            RegisterValue::U32(69u32),
+            &mut user,
+        );
+    }
+}
+
+#[test]
+fn segment_bases() {
+    let mut user = unsafe { MaybeUninit::<libc::user>::zeroed().assume_init() };
+    user.regs.fs_base = 0x7f00_0000_0740;
+
+    let fs_base = Register::lookup_by_name("fs_base").unwrap();
+    assert_eq!(fs_base, Register::FsBase);
+    assert_eq!(Register::lookup_by_dwarf_id(58), Some(Register::FsBase));
+    assert_eq!(Register::lookup_by_dwarf_id(59), Some(Register::GsBase));
+    assert_read_register_value(fs_base, RegisterValue::U64(0x7f00_0000_0740), &user);
+
+    write_and_check_register_value(Register::GsBase, RegisterValue::U64(0x1000), &mut user);
+    assert_eq!(user.regs.gs_base, 0x1000);
+}
+
+#[test]
+fn registers_in_the_xsave_area() {
+    let zmm31 = Register::lookup_by_name("zmm31").unwrap();
+    assert_eq!(zmm31.kind(), RegisterKind::ExtendedState);
+    assert_eq!(zmm31.repr(), RegisterRepr::Vector);
+    assert_eq!(zmm31.byte_width(), 64);
+    assert_eq!(zmm31.vector_index(), Some(31));
+    assert_eq!(Register::Ymm0.byte_width(), 32);
+    assert_eq!(Register::lookup_by_dwarf_id(67), Some(Register::Xmm16));
+    assert_eq!(Register::Xmm0.vector_index(), Some(0));
+    assert_eq!(Register::Rax.vector_index(), None);
+
+    let user = unsafe { MaybeUninit::<libc::user>::zeroed().assume_init() };
+    assert!(zmm31.read_from_user_struct(&user).is_err());
+    assert_eq!(RegisterValue::Byte512([0; 64]).as_u64(), None);
+}
+
+#[test]
+fn register_values_convert_to_integers() {
+    assert_eq!(RegisterValue::U8(0xff).as_u64(), Some(0xff));
+    assert_eq!(RegisterValue::U8(0xff).as_i64(), Some(0xff));
+    assert_eq!(RegisterValue::I8(-1).as_u64(), Some(0xff));
+    assert_eq!(RegisterValue::I8(-1).as_i64(), Some(-1));
+    assert_eq!(RegisterValue::U64(u64::MAX).as_i64(), Some(-1));
+    assert_eq!(
+        RegisterValue::Byte64([1, 0, 0, 0, 0, 0, 0, 0]).as_u64(),
+        Some(1)
+    );
+    assert_eq!(RegisterValue::Byte128([0; 16]).as_u64(), None);
+    assert_eq!(
+        RegisterValue::U64(0x7ffc_0000).as_usize_address(),
+        Some(0x7ffc_0000)
+    );
+
+    assert_eq!(RegisterValue::from(7u16), RegisterValue::U16(7));
+    assert_eq!(RegisterValue::from(-7i32), RegisterValue::I32(-7));
+    assert_eq!(
+        RegisterValue::from_bits(0x1234, 1),
+        Some(RegisterValue::U8(0x34))
+    );
+    assert_eq!(RegisterValue::from_bits(0x1234, 3), None);
+}
+
+#[test]
+fn register_values_extend_and_narrow() {
+    assert_eq!(
+        RegisterValue::U8(0x80).zero_extend(4),
+        Some(RegisterValue::U32(0x80))
+    );
+    assert_eq!(
+        RegisterValue::U8(0x80).sign_extend(4),
+        Some(RegisterValue::U32(0xffff_ff80))
+    );
+    assert_eq!(
+        RegisterValue::I16(-2).zero_extend(8),
+        Some(RegisterValue::I64(0xfffe))
+    );
+    assert_eq!(
+        RegisterValue::I16(-2).sign_extend(8),
+        Some(RegisterValue::I64(-2))
+    );
+    assert_eq!(RegisterValue::U32(1).zero_extend(2), None);
+
+    assert_eq!(u8::try_from(RegisterValue::U64(0xff)).unwrap(), 0xff);
+    assert!(u8::try_from(RegisterValue::U64(0x100)).is_err());
+    assert_eq!(i8::try_from(RegisterValue::I64(-128)).unwrap(), -128);
+    assert!(u32::try_from(RegisterValue::I32(-1)).is_err());
+    assert!(u64::try_from(RegisterValue::Byte128([0; 16])).is_err());
+}
+
+#[test]
+fn register_groups() {
+    let group_of = |name| Register::lookup_by_name(name).unwrap().group();
+    assert_eq!(group_of("rip"), Some(RegisterGroup::GeneralPurpose));
+    assert_eq!(group_of("fs_base"), Some(RegisterGroup::GeneralPurpose));
+    assert_eq!(group_of("eax"), None);
+    assert_eq!(group_of("st0"), Some(RegisterGroup::FloatingPoint));
+    assert_eq!(group_of("mxcsr"), Some(RegisterGroup::FloatingPoint));
+    assert_eq!(group_of("xmm0"), Some(RegisterGroup::Vector));
+    assert_eq!(group_of("zmm31"), Some(RegisterGroup::Vector));
+    assert_eq!(group_of("dr7"), Some(RegisterGroup::Debug));
+}
+
+#[test]
+fn control_registers_are_decoded() {
+    let fcw = Register::lookup_by_name("fcw").unwrap();
+    assert_eq!(
+        fcw.decode(RegisterValue::U16(0x0b7f)).unwrap().to_string(),
+        "mask=IE|DE|ZE|OE|UE|PE pc=extended rc=up"
+    );
+    let fsw = Register::lookup_by_name("fsw").unwrap();
+    assert_eq!(
+        fsw.decode(RegisterValue::U16(0x4100)).unwrap().to_string(),
+        "flags=none sf=0 es=0 c0=1 c1=0 c2=0 top=0 c3=1 b=0"
+    );
+    let mxcsr = Register::lookup_by_name("mxcsr").unwrap();
+    assert_eq!(
+        mxcsr
+            .decode(RegisterValue::U32(0x9fc0))
+            .unwrap()
+            .to_string(),
+        "flags=none daz=1 mask=IE|DE|ZE|OE|UE|PE rc=nearest fz=1"
+    );
+    assert!(Register::Rax.decode(RegisterValue::U64(0)).is_none());
+}
+
+#[test]
+fn vector_registers_as_lanes() {
+    let mut bytes = [0u8; 16];
+    bytes[..4].copy_from_slice(&1f32.to_le_bytes());
+    bytes[4..8].copy_from_slice(&2.5f32.to_le_bytes());
+    bytes[15] = 0x80;
+    let xmm = RegisterValue::Byte128(bytes);
+
+    assert_eq!(
+        xmm.format_lanes(LaneFormat::F32).unwrap(),
+        "{1.0, 2.5, 0.0, -0.0}"
+    );
+    assert_eq!(
+        xmm.format_lanes(LaneFormat::U64).unwrap(),
+        "{4620693218747482112, 9223372036854775808}"
+    );
+    assert_eq!(
+        xmm.format_lanes(LaneFormat::U16).unwrap(),
+        "{0, 16256, 0, 16416, 0, 0, 0, 32768}"
+    );
+    assert_eq!(
+        xmm.format_lanes(LaneFormat::Hex).unwrap(),
+        "0x8000000000000000402000003f800000"
+    );
+    assert_eq!(
+        RegisterValue::Byte64([1, 2, 3, 4, 5, 6, 7, 8])
+            .format_lanes(LaneFormat::U8)
+            .unwrap(),
+        "{1, 2, 3, 4, 5, 6, 7, 8}"
+    );
+    assert!(RegisterValue::U64(1)
+        .format_lanes(LaneFormat::F64)
+        .is_none());
+}
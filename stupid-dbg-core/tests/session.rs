@@ -0,0 +1,92 @@
+use nix::sys::signal::Signal;
+use nonempty::nonempty;
+use stupid_dbg_core::{
+    debuggee,
+    launch::{LaunchSpec, Stdio},
+    session::{DebugEvent, DebugSession},
+    virt_addr::VirtAddr,
+};
+
+mod aux;
+
+#[test]
+fn program_exiting_immediately_reports_exit() {
+    let mut session = DebugSession::new(debuggee::Config::SpawnChild(LaunchSpec::new(nonempty![
+        aux::get_program_exiting_immediately()
+    ])))
+    .unwrap();
+
+    let events = session
+        .events()
+        .collect::<anyhow::Result<Vec<_>>>()
+        .unwrap();
+
+    assert_eq!(
+        events.last(),
+        Some(&DebugEvent::Exited {
+            status_code: Some(0)
+        })
+    );
+    assert!(session.next_event().unwrap().is_none());
+}
+
+#[test]
+fn signals_are_delivered_after_being_reported() {
+    let mut session = DebugSession::new(debuggee::Config::SpawnChild(
+        LaunchSpec::new(nonempty![
+            "sh".to_string(),
+            "-c".to_string(),
+            "kill -SEGV $$; echo survived".to_string()
+        ])
+        .stdout(Stdio::Null),
+    ))
+    .unwrap();
+
+    let events = session
+        .events()
+        .collect::<anyhow::Result<Vec<_>>>()
+        .unwrap();
+
+    assert!(events.iter().any(|event| matches!(
+        event,
+        DebugEvent::SignalReceived {
+            signal: Signal::SIGSEGV,
+            ..
+        }
+    )));
+    assert_eq!(
+        events.last(),
+        Some(&DebugEvent::Terminated {
+            signal: Signal::SIGSEGV
+        })
+    );
+}
+
+#[test]
+fn false_breakpoint_conditions_are_not_reported() {
+    let mut session = DebugSession::new(debuggee::Config::SpawnChild(LaunchSpec::new(nonempty![
+        aux::get_program_exiting_immediately()
+    ])))
+    .unwrap();
+    let debuggee = session.debuggee_mut();
+    let entry = VirtAddr::new(debuggee.auxv().unwrap().entry_point().unwrap());
+    let id = debuggee.set_breakpoint(entry).unwrap();
+    debuggee
+        .set_breakpoint_condition(id, Some("0".to_string()))
+        .unwrap();
+
+    let events = session
+        .events()
+        .collect::<anyhow::Result<Vec<_>>>()
+        .unwrap();
+
+    assert!(!events
+        .iter()
+        .any(|event| matches!(event, DebugEvent::BreakpointHit { .. })));
+    assert_eq!(
+        events.last(),
+        Some(&DebugEvent::Exited {
+            status_code: Some(0)
+        })
+    );
+}
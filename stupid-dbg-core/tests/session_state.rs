@@ -0,0 +1,151 @@
+use nonempty::nonempty;
+use stupid_dbg_core::{
+    anti_debug::AntiDebugConfig,
+    debug_register::WatchKind,
+    debuggee::{self, Debuggee},
+    launch::LaunchSpec,
+    memory_map::MemoryMap,
+    session_state::{BreakpointLocation, BreakpointSpec, SessionState, Settings, WatchpointSpec},
+    virt_addr::VirtAddr,
+};
+
+mod aux;
+
+const MAPS_BEFORE: &str = "\
+55d0c0a3b000-55d0c0a3d000 r--p 00000000 fd:01 1234                       /usr/bin/cat
+55d0c0a3d000-55d0c0a42000 r-xp 00002000 fd:01 1234                       /usr/bin/cat
+7ffd5a1e5000-7ffd5a206000 rw-p 00000000 00:00 0                          [stack]
+";
+
+const MAPS_AFTER: &str = "\
+5611aa000000-5611aa002000 r--p 00000000 fd:01 1234                       /usr/bin/cat
+5611aa002000-5611aa007000 r-xp 00002000 fd:01 1234                       /usr/bin/cat
+7ffe00000000-7ffe00021000 rw-p 00000000 00:00 0                          [stack]
+";
+
+#[test]
+fn breakpoint_locations_survive_relocation() {
+    let before = MAPS_BEFORE.parse::<MemoryMap>().unwrap();
+    let after = MAPS_AFTER.parse::<MemoryMap>().unwrap();
+
+    let location = BreakpointLocation::from_address(&before, VirtAddr::new(0x55d0c0a3d123));
+    assert_eq!(
+        location,
+        BreakpointLocation::ModuleOffset {
+            module: "/usr/bin/cat".to_string(),
+            offset: 0x2123,
+        }
+    );
+    assert_eq!(
+        location.resolve(&after).unwrap(),
+        VirtAddr::new(0x5611aa002123)
+    );
+
+    let location = BreakpointLocation::from_address(&before, VirtAddr::new(0x7ffd5a1e5010));
+    assert_eq!(
+        location,
+        BreakpointLocation::Address(VirtAddr::new(0x7ffd5a1e5010))
+    );
+}
+
+#[test]
+fn session_state_round_trip() {
+    let state = SessionState {
+        breakpoints: vec![
+            BreakpointSpec {
+                location: BreakpointLocation::Address(VirtAddr::new(0x401000)),
+                condition: None,
+                enabled: false,
+                hardware: true,
+                groups: Vec::new(),
+            },
+            BreakpointSpec {
+                location: BreakpointLocation::ModuleOffset {
+                    module: "/usr/bin/cat".to_string(),
+                    offset: 0x2123,
+                },
+                condition: Some("$rdi == 3".to_string()),
+                enabled: true,
+                hardware: false,
+                groups: vec!["io".to_string(), "read".to_string()],
+            },
+        ],
+        watchpoints: vec![WatchpointSpec {
+            location: BreakpointLocation::ModuleOffset {
+                module: "/usr/bin/cat".to_string(),
+                offset: 0x8010,
+            },
+            size: 8,
+            kind: WatchKind::ReadWrite,
+            page_protection: false,
+        }],
+        settings: Settings {
+            anti_debug: AntiDebugConfig {
+                detect: true,
+                spoof_traceme: true,
+            },
+            auto_hardware_breakpoints: true,
+            step_into_handlers: true,
+        },
+        source_directories: vec!["/src/cat".into()],
+        environment: [
+            ("LANG".to_string(), Some("C".to_string())),
+            ("LD_PRELOAD".to_string(), None),
+        ]
+        .into(),
+    };
+
+    let path = std::env::temp_dir().join(format!("stupid-dbg-session-{}.json", std::process::id()));
+    state.save(&path).unwrap();
+    let loaded = SessionState::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(loaded, state);
+}
+
+#[test]
+fn session_files_without_conditions_still_load() {
+    let json = r#"{"breakpoints":[{"location":{"address":4198400}}]}"#;
+    let state = serde_json::from_str::<SessionState>(json).unwrap();
+    assert_eq!(state.breakpoints[0].condition, None);
+    assert!(state.breakpoints[0].enabled);
+    assert!(state.watchpoints.is_empty());
+    assert_eq!(state.settings, Settings::default());
+}
+
+#[test]
+fn session_state_is_restored_into_a_new_debuggee() {
+    let launch = || {
+        Debuggee::new(debuggee::Config::SpawnChild(LaunchSpec::new(nonempty![
+            aux::get_program_running_endlessly()
+        ])))
+        .unwrap()
+    };
+    let mut debuggee = launch();
+    let regs = *debuggee.registers().unwrap().user_regs();
+    let (rip, rsp) = (VirtAddr::new(regs.rip), VirtAddr::new(regs.rsp));
+
+    let software = debuggee.set_breakpoint(rip).unwrap();
+    debuggee.add_breakpoint_to_group(software, "entry").unwrap();
+    debuggee.set_breakpoint_enabled(software, false).unwrap();
+    let hardware = debuggee.set_hardware_breakpoint(rip + 1).unwrap();
+    debuggee
+        .set_breakpoint_condition(hardware, Some("$rax == 0".to_string()))
+        .unwrap();
+    debuggee.set_watchpoint(rsp, 8, WatchKind::Write).unwrap();
+    debuggee.set_step_into_handlers(true);
+
+    let state = SessionState::capture(&debuggee).unwrap();
+    assert_eq!(state.breakpoints.len(), 2);
+    assert_eq!(state.breakpoints[0].groups, vec!["entry".to_string()]);
+    assert!(!state.breakpoints[0].enabled);
+    assert!(state.breakpoints[1].hardware);
+    assert_eq!(state.watchpoints.len(), 1);
+    assert!(state.settings.step_into_handlers);
+
+    // dropping a debuggee waits for any child, so only one is kept alive at a time
+    drop(debuggee);
+    let mut restarted = launch();
+    state.apply(&mut restarted).unwrap();
+    assert_eq!(SessionState::capture(&restarted).unwrap(), state);
+}
@@ -0,0 +1,49 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use stupid_dbg_core::source_path::SourcePath;
+
+#[test]
+fn directories_keep_order_without_duplicates() {
+    let mut source_path = SourcePath::new();
+    source_path.add("/a");
+    source_path.add("/b");
+    source_path.add("/a");
+    assert_eq!(
+        source_path.directories(),
+        [PathBuf::from("/b"), PathBuf::from("/a")]
+    );
+}
+
+#[test]
+fn resolve_moved_sources() {
+    let root = std::env::temp_dir().join(format!("stupid-dbg-sources-{}", std::process::id()));
+    fs::create_dir_all(root.join("checkout/src")).unwrap();
+    fs::write(root.join("checkout/src/main.c"), "int main() {}\n").unwrap();
+    fs::write(root.join("util.c"), "\n").unwrap();
+
+    let mut source_path = SourcePath::new();
+    let recorded = Path::new("/build/src/main.c");
+    assert_eq!(source_path.resolve(recorded, None), None);
+
+    source_path.add(root.join("checkout"));
+    source_path.add(&root);
+    // the recorded path below a directory wins over just the file name
+    assert_eq!(
+        source_path.resolve(Path::new("src/main.c"), Some(Path::new("/build"))),
+        Some(root.join("checkout/src/main.c"))
+    );
+    assert_eq!(
+        source_path.resolve(Path::new("/build/lib/util.c"), None),
+        Some(root.join("util.c"))
+    );
+    // existing paths are used as they are
+    assert_eq!(
+        source_path.resolve(Path::new("util.c"), Some(&root)),
+        Some(root.join("util.c"))
+    );
+
+    fs::remove_dir_all(&root).unwrap();
+}
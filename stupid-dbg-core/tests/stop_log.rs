@@ -0,0 +1,28 @@
+use nix::unistd::Pid;
+use stupid_dbg_core::{stop_log::StopLog, stop_reason::StopReason, virt_addr::VirtAddr};
+
+#[test]
+fn oldest_stops_are_dropped() {
+    let mut stop_log = StopLog::new(2);
+    for pc in [0x1000, 0x2000, 0x3000] {
+        stop_log.record(
+            Pid::from_raw(1),
+            StopReason::StepComplete,
+            VirtAddr::new(pc),
+        );
+    }
+
+    assert_eq!(stop_log.len(), 2);
+    assert_eq!(stop_log.dropped(), 1);
+    assert_eq!(
+        stop_log
+            .records()
+            .map(|record| (record.index, record.pc.as_u64()))
+            .collect::<Vec<_>>(),
+        vec![(1, 0x2000), (2, 0x3000)]
+    );
+
+    stop_log.set_capacity(1);
+    assert_eq!(stop_log.records().next().unwrap().index, 2);
+    assert_eq!(stop_log.dropped(), 2);
+}
@@ -0,0 +1,43 @@
+use nix::{sys::signal::Signal, unistd::Pid};
+use stupid_dbg_core::{
+    stop_reason::{PtraceEventKind, SignalInfo, StopReason},
+    virt_addr::VirtAddr,
+};
+
+#[test]
+fn ptrace_event_kinds() {
+    assert_eq!(
+        PtraceEventKind::from(libc::PTRACE_EVENT_EXEC),
+        PtraceEventKind::Exec
+    );
+    assert_eq!(PtraceEventKind::from(42), PtraceEventKind::Unknown(42));
+}
+
+#[test]
+fn stop_reason_signals() {
+    assert_eq!(StopReason::Initial.signal(), None);
+    assert_eq!(
+        StopReason::Breakpoint { id: 1 }.signal(),
+        Some(Signal::SIGTRAP)
+    );
+    assert_eq!(StopReason::SyscallEntry { number: 0 }.signal(), None);
+
+    let segfault = StopReason::Signal(SignalInfo {
+        signal: Signal::SIGSEGV,
+        code: 1,
+        errno: 0,
+        fault_address: Some(VirtAddr::new(0xdead)),
+        sender: None,
+    });
+    assert_eq!(segfault.signal(), Some(Signal::SIGSEGV));
+    assert_eq!(segfault.to_string(), "signal SIGSEGV (code 1) at 0xdead");
+
+    let killed = StopReason::Signal(SignalInfo {
+        signal: Signal::SIGUSR1,
+        code: 0,
+        errno: 0,
+        fault_address: None,
+        sender: Some(Pid::from_raw(1)),
+    });
+    assert_eq!(killed.to_string(), "signal SIGUSR1 (code 0) from pid 1");
+}
@@ -0,0 +1,94 @@
+use stupid_dbg_core::{
+    memory_map::MemoryMap,
+    symbol_cache::{ModuleOffset, SymbolCache},
+    virt_addr::VirtAddr,
+};
+
+const MAPS: &str = "\
+55d0c0a3b000-55d0c0a3d000 r--p 00000000 fd:01 1234                       /usr/bin/cat
+55d0c0a3d000-55d0c0a42000 r-xp 00002000 fd:01 1234                       /usr/bin/cat
+7ffd5a1e5000-7ffd5a206000 rw-p 00000000 00:00 0                          [stack]
+";
+
+fn key(offset: u64) -> ModuleOffset {
+    ModuleOffset {
+        module: "/usr/bin/cat".to_string(),
+        offset,
+    }
+}
+
+#[test]
+fn module_offset_from_address() {
+    let memory_map = MAPS.parse::<MemoryMap>().unwrap();
+
+    assert_eq!(
+        ModuleOffset::from_address(&memory_map, VirtAddr::new(0x55d0c0a3e010)),
+        Some(key(0x3010))
+    );
+    assert_eq!(
+        ModuleOffset::from_address(&memory_map, VirtAddr::new(0x7ffd5a1e5010)),
+        None
+    );
+}
+
+#[test]
+fn least_recently_used_entry_is_evicted() {
+    let mut cache = SymbolCache::new(2);
+    cache.insert(key(1), "one");
+    cache.insert(key(2), "two");
+    assert_eq!(cache.get(&key(1)), Some(&"one"));
+
+    cache.insert(key(3), "three");
+
+    assert_eq!(cache.len(), 2);
+    assert_eq!(cache.get(&key(2)), None);
+    assert_eq!(cache.get(&key(1)), Some(&"one"));
+    assert_eq!(cache.get(&key(3)), Some(&"three"));
+}
+
+#[test]
+fn lookup_runs_once_per_key() {
+    let mut cache = SymbolCache::new(8);
+    let mut lookups = 0;
+
+    for _ in 0..3 {
+        cache.get_or_insert_with(key(0x10), |_| {
+            lookups += 1;
+            None::<String>
+        });
+    }
+
+    assert_eq!(lookups, 1);
+}
+
+#[test]
+fn module_changes_invalidate_entries() {
+    let mut cache = SymbolCache::new(8);
+    let memory_map = MAPS.parse::<MemoryMap>().unwrap();
+    assert!(cache.sync_modules(&memory_map));
+    cache.insert(key(0x10), "main");
+
+    assert!(!cache.sync_modules(&memory_map));
+    assert_eq!(cache.len(), 1);
+
+    let maps = format!(
+        "{}7f3a00000000-7f3a00021000 r-xp 00000000 fd:01 42                         /usr/lib/libc.so.6\n",
+        MAPS
+    );
+    // loading a library leaves the others alone
+    assert!(cache.sync_modules(&maps.parse::<MemoryMap>().unwrap()));
+    assert_eq!(cache.len(), 1);
+
+    let libc = ModuleOffset {
+        module: "/usr/lib/libc.so.6".to_string(),
+        offset: 0x20,
+    };
+    cache.insert(libc.clone(), "malloc");
+    assert!(cache.sync_modules(&memory_map));
+    assert_eq!(cache.get(&libc), None);
+    assert_eq!(cache.get(&key(0x10)), Some(&"main"));
+
+    let moved = MAPS.replace("55d0c0a", "55d0c1a");
+    assert!(cache.sync_modules(&moved.parse::<MemoryMap>().unwrap()));
+    assert!(cache.is_empty());
+}
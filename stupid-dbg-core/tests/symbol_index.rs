@@ -0,0 +1,31 @@
+use std::fs;
+
+use stupid_dbg_core::{
+    elf::ElfFile,
+    symbol_index::{IndexedModule, SymbolIndex},
+};
+
+#[test]
+fn entries_last_until_the_file_changes() {
+    let root = std::env::temp_dir().join(format!("stupid-dbg-symbol-index-{}", std::process::id()));
+    let index = SymbolIndex::new(root.join("index"));
+    let module = root.join("libfoo.so");
+    fs::create_dir_all(&root).unwrap();
+    fs::write(&module, "not much of a library").unwrap();
+
+    let indexed = IndexedModule::of(&ElfFile::read("/proc/self/exe").unwrap()).unwrap();
+    assert!(!indexed.symbols.is_empty());
+    assert_eq!(index.load(&module), None);
+    index.store(&module, &indexed).unwrap();
+    assert_eq!(index.load(&module), Some(indexed.clone()));
+
+    fs::write(&module, "a rebuilt library").unwrap();
+    assert_eq!(index.load(&module), None);
+
+    index.store(&module, &indexed).unwrap();
+    assert_eq!(index.flush().unwrap(), 1);
+    assert_eq!(index.load(&module), None);
+    assert_eq!(index.flush().unwrap(), 0);
+
+    fs::remove_dir_all(&root).unwrap();
+}
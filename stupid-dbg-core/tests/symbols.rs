@@ -0,0 +1,174 @@
+use stupid_dbg_core::{
+    elf::{ElfFile, SymbolKind},
+    symbols::{demangle, fuzzy_score, Symbol, SymbolTable},
+    virt_addr::VirtAddr,
+};
+
+fn symbol(name: &str, address: u64) -> Symbol {
+    Symbol {
+        name: name.to_string(),
+        demangled: demangle(name),
+        address: VirtAddr::new(address),
+        size: 16,
+        kind: SymbolKind::Function,
+        module: "/usr/bin/app".to_string(),
+    }
+}
+
+#[test]
+fn demangling() {
+    assert_eq!(demangle("_ZN3foo3barE").as_deref(), Some("foo::bar"));
+    assert_eq!(demangle("_Z3fooi").as_deref(), Some("foo"));
+    assert_eq!(
+        demangle("_ZN4core3fmt5write17h0123456789abcdefE").as_deref(),
+        Some("core::fmt::write")
+    );
+    assert_eq!(
+        demangle("_ZN49_$LT$alloc..vec..Vec$LT$T$GT$$u20$as$u20$Drop$GT$4drop17h0123456789abcdefE")
+            .as_deref(),
+        Some("<alloc::vec::Vec<T> as Drop>::drop")
+    );
+    assert_eq!(demangle("main"), None);
+    assert_eq!(demangle("_ZN3foo"), None);
+}
+
+#[test]
+fn fuzzy_scores() {
+    let exact = fuzzy_score("parse", "parse").unwrap();
+    let component = fuzzy_score("parse", "config::parse").unwrap();
+    let prefix = fuzzy_score("parse", "parse_args").unwrap();
+    let substring = fuzzy_score("parse", "reparse").unwrap();
+    let subsequence = fuzzy_score("prs", "parse").unwrap();
+    assert!(exact > component);
+    assert!(component > prefix);
+    assert!(prefix > substring);
+    assert!(substring > subsequence);
+    assert_eq!(fuzzy_score("PARSE", "parse"), Some(exact));
+    assert_eq!(fuzzy_score("xyz", "parse"), None);
+}
+
+#[test]
+fn find_ranks_matches() {
+    let table = SymbolTable::from_symbols(vec![
+        symbol("reparse_all", 0x3000),
+        symbol("_ZN6config5parseE", 0x1000),
+        symbol("parse_args", 0x2000),
+        symbol("unrelated", 0x4000),
+    ]);
+    let names = table
+        .find_fuzzy("parse", 10)
+        .into_iter()
+        .map(|(symbol, _)| symbol.display_name().to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec!["config::parse", "parse_args", "reparse_all"]);
+    assert_eq!(table.find_fuzzy("parse", 1).len(), 1);
+}
+
+#[test]
+fn symbols_of_the_test_binary() {
+    let elf = ElfFile::read("/proc/self/exe").unwrap();
+    let symbols = elf.symbols().unwrap();
+    assert!(symbols
+        .iter()
+        .any(|symbol| symbol.kind == SymbolKind::Function && symbol.address != 0));
+}
+
+#[test]
+fn mapped_and_read_files_agree() {
+    let mapped = ElfFile::read("/proc/self/exe").unwrap();
+    let read = ElfFile::parse(std::fs::read("/proc/self/exe").unwrap()).unwrap();
+    assert_eq!(mapped.symbols().unwrap(), read.symbols().unwrap());
+    let text = mapped.section(".text").unwrap().clone();
+    assert_eq!(mapped.section_data(&text), read.section_data(&text));
+    // clones share the mapping, which outlives the original
+    let clone = mapped.clone();
+    drop(mapped);
+    assert_eq!(clone.section_data(&text), read.section_data(&text));
+    assert!(ElfFile::read("/dev/null").is_err());
+}
+
+#[test]
+fn lookup_by_address() {
+    let table = SymbolTable::from_symbols(vec![symbol("main", 0x1000), symbol("helper", 0x1010)]);
+    let (found, offset) = table.lookup(VirtAddr::new(0x1014)).unwrap();
+    assert_eq!(found.name, "helper");
+    assert_eq!(offset, 4);
+    assert!(table.lookup(VirtAddr::new(0x1020)).is_none());
+}
+
+#[test]
+fn lookup_prefers_the_first_of_overlapping_symbols() {
+    let mut outer = symbol("outer", 0x1000);
+    outer.size = 0x100;
+    let mut empty = symbol("label", 0x1040);
+    empty.size = 0;
+    let table = SymbolTable::from_symbols(vec![
+        symbol("inner", 0x1080),
+        outer,
+        empty,
+        symbol("after", 0x2000),
+    ]);
+
+    assert_eq!(table.lookup(VirtAddr::new(0x1084)).unwrap().0.name, "inner");
+    let (found, offset) = table.lookup(VirtAddr::new(0x1040)).unwrap();
+    assert_eq!((found.name.as_str(), offset), ("outer", 0x40));
+    assert_eq!(table.lookup(VirtAddr::new(0x2008)).unwrap().0.name, "after");
+    assert!(table.lookup(VirtAddr::new(0xfff)).is_none());
+    assert!(table.lookup(VirtAddr::new(0x1100)).is_none());
+}
+
+#[test]
+fn symbolize_addresses() {
+    let table = SymbolTable::from_symbols(vec![
+        symbol("main", 0x1000),
+        symbol("_ZN6config5parseE", 0x2000),
+    ]);
+    assert_eq!(
+        table.symbolize(VirtAddr::new(0x1000)).unwrap(),
+        "main (/usr/bin/app)"
+    );
+    assert_eq!(
+        table.symbolize(VirtAddr::new(0x200c)).unwrap(),
+        "config::parse+0xc (/usr/bin/app)"
+    );
+    assert!(table.symbolize(VirtAddr::new(0x1800)).is_none());
+}
+
+#[test]
+fn resolve_by_name() {
+    let mut object = symbol("parse_args", 0x3000);
+    object.kind = SymbolKind::Object;
+    let table = SymbolTable::from_symbols(vec![
+        symbol("main", 0x1000),
+        symbol("_ZN6config5parseE", 0x2000),
+        object,
+        symbol("parse_args", 0x4000),
+    ]);
+
+    assert_eq!(
+        table.resolve("main").unwrap().address,
+        VirtAddr::new(0x1000)
+    );
+    assert_eq!(
+        table.resolve("config::parse").unwrap().address,
+        VirtAddr::new(0x2000)
+    );
+    assert_eq!(
+        table.resolve("_ZN6config5parseE").unwrap().address,
+        VirtAddr::new(0x2000)
+    );
+    // functions win over variables of the same name
+    assert_eq!(table.find_by_name("parse_args").len(), 2);
+    assert_eq!(
+        table.resolve("parse_args").unwrap().address,
+        VirtAddr::new(0x4000)
+    );
+
+    let err = table.resolve("parse").unwrap_err().to_string();
+    assert!(err.contains("did you mean"), "{}", err);
+    assert!(err.contains("config::parse"), "{}", err);
+    assert_eq!(
+        table.resolve("zzz").unwrap_err().to_string(),
+        "no symbol named zzz"
+    );
+}
@@ -0,0 +1,102 @@
+use std::{
+    fs,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use nix::unistd::Pid;
+use stupid_dbg_core::{
+    expression::Value,
+    tracepoint::{
+        Backpressure, CollectedMemory, CollectedValue, TraceBuffer, TraceFile, TraceFrame,
+    },
+    virt_addr::VirtAddr,
+};
+
+fn frame(tracepoint: usize) -> TraceFrame {
+    TraceFrame {
+        index: 0,
+        time: UNIX_EPOCH + Duration::from_millis(1_500),
+        tracepoint,
+        thread: Pid::from_raw(4242),
+        pc: VirtAddr::new(0x1001),
+        registers: Vec::new(),
+        memory: vec![
+            CollectedMemory {
+                expression: "$rsp".to_string(),
+                result: Ok((VirtAddr::new(0x7ff0), vec![0x01, 0xab])),
+            },
+            CollectedMemory {
+                expression: "0".to_string(),
+                result: Err("unmapped".to_string()),
+            },
+        ],
+        values: vec![CollectedValue {
+            expression: "$rax + 1".to_string(),
+            result: Ok(Value::Int(2)),
+        }],
+    }
+}
+
+#[test]
+fn frames_are_formatted_on_a_line() {
+    assert_eq!(
+        frame(1).to_string(),
+        "1.500 frame=0 tracepoint=1 thread=4242 pc=0x1001 $rsp:0x7ff0=01ab 0=<unmapped> $rax + 1=2"
+    );
+}
+
+#[test]
+fn buffer_keeps_the_latest_frames_within_its_capacity() {
+    let mut buffer = TraceBuffer::new(2);
+    for tracepoint in 0..3 {
+        buffer.record(frame(tracepoint));
+    }
+    assert_eq!(
+        buffer.frames().map(|frame| frame.index).collect::<Vec<_>>(),
+        vec![1, 2]
+    );
+    assert_eq!(buffer.dropped(), 1);
+
+    buffer.set_capacity(1);
+    assert_eq!(
+        buffer.frames().map(|frame| frame.index).collect::<Vec<_>>(),
+        vec![2]
+    );
+
+    // frames still count while none are kept
+    buffer.set_capacity(0);
+    buffer.record(frame(3));
+    assert!(buffer.is_empty());
+    assert_eq!(buffer.next_index(), 4);
+    assert_eq!(buffer.dropped(), 4);
+}
+
+#[test]
+fn trace_file_gets_a_line_per_frame() {
+    let path = std::env::temp_dir().join(format!("stupid-dbg-trace-{}.txt", std::process::id()));
+    _ = fs::remove_file(&path);
+
+    let mut trace_file =
+        TraceFile::create(&path, 4, Backpressure::Block(Duration::from_secs(1))).unwrap();
+    assert!(trace_file.write(&frame(1)));
+    assert!(trace_file.write(&TraceFrame {
+        index: 1,
+        ..frame(2)
+    }));
+    assert_eq!(trace_file.dropped(), 0);
+    trace_file.close().unwrap();
+
+    let text = fs::read_to_string(&path).unwrap();
+    let lines = text.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].contains("frame=0 tracepoint=1"));
+    assert!(lines[1].contains("frame=1 tracepoint=2"));
+    _ = fs::remove_file(&path);
+}
+
+#[test]
+fn failing_trace_file_reports_why() {
+    let mut trace_file = TraceFile::create("/dev/full", 4, Backpressure::Drop).unwrap();
+    trace_file.write(&frame(1));
+    assert!(trace_file.close().is_err());
+}
@@ -0,0 +1,1123 @@
+use std::time::Duration;
+
+use nix::{
+    sys::{signal::Signal, wait::WaitStatus},
+    unistd::Pid,
+};
+use stupid_dbg_core::{
+    anti_debug::{AntiDebugAttempt, AntiDebugConfig},
+    breakpoint::BreakpointMechanism,
+    coverage::Coverage,
+    crash::CrashReport,
+    debug_register::WatchKind,
+    debuggee::{Debuggee, FollowFork, ProcessState, WaitOutcome},
+    elf::SymbolKind,
+    expression::{self, Lvalue, Value},
+    heap::AllocFunction,
+    register::{Register, RegisterValue, Registers},
+    stop_reason::StopReason,
+    symbols::{Symbol, SymbolTable},
+    tracepoint::{Backpressure, TraceAction},
+    tracer::{ScriptedTracer, Tracer, TracerCall},
+    virt_addr::VirtAddr,
+    watchpoint::WatchStrategy,
+};
+
+const PID: Pid = Pid::from_raw(4242);
+
+fn debug_register_offset(index: usize) -> usize {
+    std::mem::offset_of!(libc::user, u_debugreg) + index * 8
+}
+
+fn regs_at(rip: u64) -> libc::user_regs_struct {
+    let mut regs: libc::user_regs_struct = unsafe { std::mem::zeroed() };
+    regs.rip = rip;
+    regs
+}
+
+fn scripted_debuggee() -> Debuggee<ScriptedTracer> {
+    let tracer = ScriptedTracer::new();
+    tracer.push_wait_status(WaitStatus::Stopped(PID, Signal::SIGSTOP));
+    tracer.set_thread_regs(PID, regs_at(0x1000));
+    tracer.map_memory(0x1000, &[0x90; 16]);
+
+    let debuggee = Debuggee::from_tracer(tracer, PID).unwrap();
+    debuggee.tracer().take_calls();
+    debuggee
+}
+
+#[test]
+fn initial_stop() {
+    let debuggee = scripted_debuggee();
+    assert!(matches!(
+        debuggee.process_state(),
+        ProcessState::Stopped(StopReason::Initial)
+    ));
+    assert_eq!(debuggee.registers().unwrap().user_regs().rip, 0x1000);
+}
+
+#[test]
+fn breakpoint_hit_and_step_over() {
+    let mut debuggee = scripted_debuggee();
+    let id = debuggee.set_breakpoint(VirtAddr::new(0x1001)).unwrap();
+    assert_eq!(
+        debuggee.tracer().memory(0x1000, 2).unwrap(),
+        vec![0x90, 0xcc]
+    );
+    assert_eq!(
+        debuggee.read_memory(VirtAddr::new(0x1000), 2).unwrap(),
+        vec![0x90, 0x90]
+    );
+
+    // the int3 has been executed
+    debuggee.tracer().set_thread_regs(PID, regs_at(0x1002));
+    debuggee
+        .tracer()
+        .push_wait_status(WaitStatus::Stopped(PID, Signal::SIGTRAP));
+    debuggee.resume().unwrap();
+    debuggee.update_process_state(true).unwrap();
+
+    assert!(matches!(
+        debuggee.process_state(),
+        ProcessState::Stopped(StopReason::Breakpoint { id: hit }) if hit == id
+    ));
+    assert_eq!(debuggee.tracer().get_regs(PID).unwrap().rip, 0x1001);
+
+    debuggee
+        .tracer()
+        .push_wait_status(WaitStatus::Stopped(PID, Signal::SIGTRAP));
+    debuggee.tracer().take_calls();
+    debuggee.resume().unwrap();
+    assert_eq!(
+        debuggee.tracer().take_calls(),
+        vec![
+            TracerCall::WriteMemory(PID, 0x1001, vec![0x90]),
+            TracerCall::Step(PID, None),
+            TracerCall::WriteMemory(PID, 0x1001, vec![0xcc]),
+            TracerCall::Cont(PID, None),
+        ]
+    );
+}
+
+#[test]
+fn syscall_entry_stop() {
+    let mut debuggee = scripted_debuggee();
+    let mut regs = regs_at(0x1000);
+    regs.orig_rax = libc::SYS_getpid as u64;
+    regs.rax = -(libc::ENOSYS as i64) as u64;
+    debuggee.tracer().set_thread_regs(PID, regs);
+    debuggee
+        .tracer()
+        .push_wait_status(WaitStatus::PtraceSyscall(PID));
+
+    debuggee.resume().unwrap();
+    debuggee.update_process_state(true).unwrap();
+
+    assert!(matches!(
+        debuggee.process_state(),
+        ProcessState::Stopped(StopReason::SyscallEntry { number }) if number == libc::SYS_getpid as u64
+    ));
+}
+
+#[test]
+fn breakpoints_are_patched_once_per_page() {
+    let mut debuggee = scripted_debuggee();
+    debuggee.tracer().map_memory(0x2ff0, &[0x90; 32]);
+
+    let ids = debuggee
+        .set_breakpoints(&[
+            VirtAddr::new(0x1008),
+            VirtAddr::new(0x1002),
+            VirtAddr::new(0x3004),
+        ])
+        .unwrap();
+
+    assert_eq!(ids.len(), 3);
+    assert_eq!(
+        debuggee.tracer().take_calls(),
+        vec![
+            TracerCall::WriteMemory(PID, 0x1002, vec![0xcc, 0x90, 0x90, 0x90, 0x90, 0x90, 0xcc]),
+            TracerCall::WriteMemory(PID, 0x3004, vec![0xcc]),
+        ]
+    );
+    assert_eq!(
+        debuggee.read_memory(VirtAddr::new(0x1000), 16).unwrap(),
+        vec![0x90; 16]
+    );
+    assert!(debuggee
+        .set_breakpoints(&[VirtAddr::new(0x1004), VirtAddr::new(0x1002)])
+        .is_err());
+}
+
+#[test]
+fn failed_page_restores_the_pages_before_it() {
+    let mut debuggee = scripted_debuggee();
+    let before = debuggee.tracer().memory(0x1000, 16).unwrap();
+
+    // nothing is mapped at 0x7000
+    assert!(debuggee
+        .set_breakpoints(&[VirtAddr::new(0x1002), VirtAddr::new(0x7000)])
+        .is_err());
+
+    assert_eq!(debuggee.tracer().memory(0x1000, 16).unwrap(), before);
+    assert!(debuggee.breakpoints().is_empty());
+    assert!(debuggee.set_breakpoints(&[VirtAddr::new(0x1002)]).is_ok());
+}
+
+#[test]
+fn watchpoint_programs_debug_registers_and_reports_hit() {
+    let mut debuggee = scripted_debuggee();
+    let id = debuggee
+        .set_watchpoint(VirtAddr::new(0x2000), 8, WatchKind::Write)
+        .unwrap();
+
+    let dr7 = (0b1001 << 16) | 1;
+    assert_eq!(
+        debuggee.tracer().take_calls(),
+        vec![
+            TracerCall::WriteUser(PID, debug_register_offset(7), 0),
+            TracerCall::WriteUser(PID, debug_register_offset(0), 0x2000),
+            TracerCall::WriteUser(PID, debug_register_offset(1), 0),
+            TracerCall::WriteUser(PID, debug_register_offset(2), 0),
+            TracerCall::WriteUser(PID, debug_register_offset(3), 0),
+            TracerCall::WriteUser(PID, debug_register_offset(7), dr7),
+        ]
+    );
+    assert_eq!(
+        debuggee.registers().unwrap().debug_registers()[7],
+        dr7 as u64
+    );
+
+    // DR6 reports slot 0
+    debuggee
+        .tracer()
+        .write_user(PID, debug_register_offset(6), 1)
+        .unwrap();
+    debuggee
+        .tracer()
+        .push_wait_status(WaitStatus::Stopped(PID, Signal::SIGTRAP));
+    debuggee.resume().unwrap();
+    debuggee.update_process_state(true).unwrap();
+
+    assert!(matches!(
+        debuggee.process_state(),
+        ProcessState::Stopped(StopReason::Watchpoint { id: hit, address })
+            if hit == id && address == VirtAddr::new(0x2000)
+    ));
+    assert_eq!(
+        debuggee
+            .tracer()
+            .read_user(PID, debug_register_offset(6))
+            .unwrap(),
+        0
+    );
+
+    debuggee.remove_watchpoint(id).unwrap();
+    assert!(debuggee.watchpoints().is_empty());
+    assert_eq!(debuggee.debug_register_allocator().free_slots(), 4);
+}
+
+#[test]
+fn large_watchpoints_are_split_or_single_stepped() {
+    let mut debuggee = scripted_debuggee();
+    debuggee.tracer().map_memory(0x2000, &[0; 64]);
+
+    let split = debuggee
+        .set_watchpoint(VirtAddr::new(0x2000), 16, WatchKind::Write)
+        .unwrap();
+    assert_eq!(
+        debuggee.watchpoints()[&split].strategy(),
+        WatchStrategy::Hardware { slots: 2 }
+    );
+
+    let stepped = debuggee
+        .set_watchpoint(VirtAddr::new(0x2010), 48, WatchKind::Write)
+        .unwrap();
+    assert_eq!(
+        debuggee.watchpoints()[&stepped].strategy(),
+        WatchStrategy::Software
+    );
+    assert!(debuggee
+        .set_watchpoint(VirtAddr::new(0x2010), 48, WatchKind::ReadWrite)
+        .is_err());
+
+    let mut siginfo: libc::siginfo_t = unsafe { std::mem::zeroed() };
+    siginfo.si_signo = libc::SIGTRAP;
+    siginfo.si_code = 2; // TRAP_TRACE
+    debuggee.tracer().set_siginfo(PID, siginfo);
+    debuggee.tracer().take_calls();
+
+    // nothing changed, keep stepping
+    debuggee.resume().unwrap();
+    debuggee
+        .tracer()
+        .push_wait_status(WaitStatus::Stopped(PID, Signal::SIGTRAP));
+    debuggee.update_process_state(false).unwrap();
+    assert!(matches!(debuggee.process_state(), ProcessState::Running));
+
+    debuggee.tracer().map_memory(0x2020, &[1]);
+    debuggee
+        .tracer()
+        .push_wait_status(WaitStatus::Stopped(PID, Signal::SIGTRAP));
+    debuggee.update_process_state(false).unwrap();
+    assert!(matches!(
+        debuggee.process_state(),
+        ProcessState::Stopped(StopReason::Watchpoint { id, address })
+            if id == stepped && address == VirtAddr::new(0x2010)
+    ));
+    assert_eq!(
+        debuggee.tracer().take_calls(),
+        vec![TracerCall::Step(PID, None), TracerCall::Step(PID, None)]
+    );
+}
+
+#[test]
+fn memory_reads_are_cached_until_resumed_or_written() {
+    let mut debuggee = scripted_debuggee();
+    debuggee.tracer().map_memory(0x3000, &[0; 4096]);
+    assert_eq!(
+        debuggee.read_memory(VirtAddr::new(0x3010), 2).unwrap(),
+        vec![0, 0]
+    );
+
+    // changed behind the back of the debuggee, the cached page still has the old bytes
+    debuggee.tracer().map_memory(0x3010, &[1, 1]);
+    assert_eq!(
+        debuggee.read_memory(VirtAddr::new(0x3010), 2).unwrap(),
+        vec![0, 0]
+    );
+
+    debuggee.write_memory(VirtAddr::new(0x3000), &[2]).unwrap();
+    assert_eq!(
+        debuggee.read_memory(VirtAddr::new(0x3010), 2).unwrap(),
+        vec![1, 1]
+    );
+
+    debuggee.tracer().map_memory(0x3010, &[3, 3]);
+    debuggee.resume().unwrap();
+    debuggee
+        .tracer()
+        .push_wait_status(WaitStatus::Stopped(PID, Signal::SIGSTOP));
+    debuggee.update_process_state(false).unwrap();
+    assert_eq!(
+        debuggee.read_memory(VirtAddr::new(0x3010), 2).unwrap(),
+        vec![3, 3]
+    );
+
+    // pages that can't be read whole aren't cached
+    assert_eq!(
+        debuggee.read_memory(VirtAddr::new(0x1000), 2).unwrap(),
+        vec![0x90, 0x90]
+    );
+}
+
+#[test]
+fn assign_registers_and_memory() {
+    let mut debuggee = scripted_debuggee();
+
+    let lvalue = expression::Expr::parse("$rax")
+        .unwrap()
+        .lvalue(&debuggee)
+        .unwrap();
+    assert_eq!(lvalue, Lvalue::Register("rax".to_string()));
+    expression::assign(&mut debuggee, &lvalue, Value::Int(-1)).unwrap();
+    assert_eq!(debuggee.tracer().get_regs(PID).unwrap().rax, u64::MAX);
+
+    // only the low half changes, and what doesn't fit is dropped
+    let eax = Lvalue::Register("eax".to_string());
+    expression::assign(&mut debuggee, &eax, Value::Int(0x1_2345_6789)).unwrap();
+    assert_eq!(
+        debuggee.registers().unwrap().user_regs().rax,
+        0xffff_ffff_2345_6789
+    );
+
+    let lvalue = expression::Expr::parse("*0x1008")
+        .unwrap()
+        .lvalue(&debuggee)
+        .unwrap();
+    assert_eq!(
+        lvalue,
+        Lvalue::Memory {
+            address: 0x1008,
+            size: 8
+        }
+    );
+    expression::assign(&mut debuggee, &lvalue, Value::Int(0x0102)).unwrap();
+    assert_eq!(
+        debuggee.read_memory(VirtAddr::new(0x1008), 8).unwrap(),
+        vec![0x02, 0x01, 0, 0, 0, 0, 0, 0]
+    );
+
+    assert!(expression::Expr::parse("1 + 2")
+        .unwrap()
+        .lvalue(&debuggee)
+        .is_err());
+    assert!(expression::Expr::parse("counter")
+        .unwrap()
+        .lvalue(&debuggee)
+        .is_err());
+}
+
+#[test]
+fn assign_through_casts_uses_the_pointee_width() {
+    let mut debuggee = scripted_debuggee();
+
+    let lvalue = expression::Expr::parse("*(uint16_t *)0x1008")
+        .unwrap()
+        .lvalue(&debuggee)
+        .unwrap();
+    assert_eq!(
+        lvalue,
+        Lvalue::Memory {
+            address: 0x1008,
+            size: 2
+        }
+    );
+    expression::assign(&mut debuggee, &lvalue, Value::Int(0xabcd)).unwrap();
+    assert_eq!(
+        debuggee.read_memory(VirtAddr::new(0x1008), 3).unwrap(),
+        vec![0xcd, 0xab, 0x90]
+    );
+
+    // an integer stored into a float is converted like in C
+    let lvalue = expression::Expr::parse("*(float *)0x1008")
+        .unwrap()
+        .lvalue(&debuggee)
+        .unwrap();
+    assert_eq!(
+        lvalue,
+        Lvalue::FloatMemory {
+            address: 0x1008,
+            size: 4
+        }
+    );
+    expression::assign(&mut debuggee, &lvalue, Value::Int(2)).unwrap();
+    assert_eq!(
+        debuggee.read_memory(VirtAddr::new(0x1008), 4).unwrap(),
+        2.0f32.to_le_bytes().to_vec()
+    );
+    assert_eq!(
+        expression::evaluate("*(float *)0x1008", &debuggee).unwrap(),
+        Value::Float(2.0)
+    );
+
+    assert!(expression::Expr::parse("*(struct Foo *)0x1008")
+        .unwrap()
+        .lvalue(&debuggee)
+        .is_err());
+}
+
+#[test]
+fn jump_moves_the_pc_without_running() {
+    let mut debuggee = scripted_debuggee();
+    debuggee.jump(VirtAddr::new(0x1008), true).unwrap();
+    assert_eq!(
+        debuggee.tracer().take_calls(),
+        vec![TracerCall::SetRegs(PID, 0x1008)]
+    );
+    assert_eq!(debuggee.registers().unwrap().user_regs().rip, 0x1008);
+
+    // a running thread's pc can't be changed
+    debuggee.resume().unwrap();
+    assert!(debuggee.jump(VirtAddr::new(0x1000), true).is_err());
+}
+
+#[test]
+fn stops_are_logged() {
+    let mut debuggee = scripted_debuggee();
+    let id = debuggee.set_breakpoint(VirtAddr::new(0x1001)).unwrap();
+    debuggee.tracer().set_thread_regs(PID, regs_at(0x1002));
+    debuggee
+        .tracer()
+        .push_wait_status(WaitStatus::Stopped(PID, Signal::SIGTRAP));
+    debuggee.resume().unwrap();
+    debuggee.update_process_state(true).unwrap();
+
+    let records = debuggee.stop_log().records().collect::<Vec<_>>();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0].pc, VirtAddr::new(0x1000));
+    assert_eq!(records[1].index, 1);
+    assert_eq!(records[1].thread, PID);
+    assert_eq!(records[1].reason, StopReason::Breakpoint { id });
+    // where the breakpoint is, not past the int3
+    assert_eq!(records[1].pc, VirtAddr::new(0x1001));
+}
+
+#[test]
+fn traceme_is_spoofed() {
+    let mut debuggee = scripted_debuggee();
+    debuggee
+        .set_anti_debug(AntiDebugConfig {
+            detect: true,
+            spoof_traceme: true,
+        })
+        .unwrap();
+    debuggee.resume().unwrap();
+    assert!(debuggee
+        .tracer()
+        .take_calls()
+        .contains(&TracerCall::Syscall(PID, None)));
+
+    let mut regs = regs_at(0x1002);
+    regs.orig_rax = libc::SYS_ptrace as u64;
+    regs.rdi = libc::PTRACE_TRACEME as u64;
+    regs.rax = -(libc::ENOSYS as i64) as u64;
+    debuggee.tracer().set_thread_regs(PID, regs);
+    debuggee
+        .tracer()
+        .push_wait_status(WaitStatus::PtraceSyscall(PID));
+    debuggee.update_process_state(false).unwrap();
+    assert_eq!(
+        debuggee.anti_debug_attempts(),
+        &[AntiDebugAttempt::TraceMe {
+            thread: PID,
+            spoofed: true
+        }]
+    );
+
+    // the kernel refuses, the debuggee gets to see success
+    regs.rax = -(libc::EPERM as i64) as u64;
+    debuggee.tracer().set_thread_regs(PID, regs);
+    debuggee
+        .tracer()
+        .push_wait_status(WaitStatus::PtraceSyscall(PID));
+    debuggee.update_process_state(false).unwrap();
+    assert_eq!(debuggee.tracer().get_regs(PID).unwrap().rax, 0);
+    assert!(matches!(debuggee.process_state(), ProcessState::Running));
+}
+
+#[test]
+fn fast_breakpoints_fall_back_to_software() {
+    let mut debuggee = scripted_debuggee();
+    for offset in 0..4 {
+        let (_, mechanism) = debuggee
+            .set_breakpoint_auto(VirtAddr::new(0x1000 + offset), true)
+            .unwrap();
+        assert_eq!(mechanism, BreakpointMechanism::Hardware);
+    }
+    // the code is left alone
+    assert_eq!(debuggee.tracer().memory(0x1000, 4).unwrap(), vec![0x90; 4]);
+
+    let (id, mechanism) = debuggee
+        .set_breakpoint_auto(VirtAddr::new(0x1004), true)
+        .unwrap();
+    assert_eq!(mechanism, BreakpointMechanism::Software);
+    assert!(debuggee.breakpoints().contains_key(&id));
+    assert_eq!(debuggee.tracer().memory(0x1004, 1).unwrap(), vec![0xcc]);
+
+    assert!(debuggee
+        .set_breakpoint_auto(VirtAddr::new(0x1000), false)
+        .is_err());
+    // nothing makes it worth a debug register without automatic promotion
+    debuggee.remove_breakpoint(1).unwrap();
+    let (_, mechanism) = debuggee
+        .set_breakpoint_auto(VirtAddr::new(0x1000), false)
+        .unwrap();
+    assert_eq!(mechanism, BreakpointMechanism::Software);
+}
+
+#[test]
+fn breakpoint_groups() {
+    let mut debuggee = scripted_debuggee();
+    let io = debuggee.set_breakpoint(VirtAddr::new(0x1001)).unwrap();
+    let (fast, _) = debuggee
+        .set_breakpoint_auto(VirtAddr::new(0x1002), true)
+        .unwrap();
+    let other = debuggee.set_breakpoint(VirtAddr::new(0x1003)).unwrap();
+    debuggee.add_breakpoint_to_group(io, "io").unwrap();
+    debuggee.add_breakpoint_to_group(fast, "io").unwrap();
+    debuggee.add_breakpoint_to_group(other, "tmp").unwrap();
+    assert!(debuggee.add_breakpoint_to_group(42, "io").is_err());
+    assert!(debuggee.breakpoint_group("nope").is_err());
+
+    for id in debuggee.breakpoint_group("io").unwrap() {
+        debuggee.set_breakpoint_enabled(id, false).unwrap();
+    }
+    assert!(!debuggee.is_breakpoint_enabled(io));
+    assert!(!debuggee.is_breakpoint_enabled(fast));
+    assert!(debuggee.is_breakpoint_enabled(other));
+    assert_eq!(debuggee.tracer().memory(0x1001, 1).unwrap(), vec![0x90]);
+    assert_eq!(debuggee.debug_register_allocator().free_slots(), 4);
+
+    debuggee.set_breakpoint_enabled(io, true).unwrap();
+    assert_eq!(debuggee.tracer().memory(0x1001, 1).unwrap(), vec![0xcc]);
+
+    for id in debuggee.breakpoint_group("tmp").unwrap() {
+        debuggee.remove_breakpoint(id).unwrap();
+    }
+    assert!(!debuggee.breakpoint_groups().contains_key("tmp"));
+    debuggee.remove_breakpoint_from_group(fast, "io").unwrap();
+    assert_eq!(debuggee.breakpoint_group("io").unwrap(), vec![io]);
+}
+
+#[test]
+fn tracepoints_collect_without_stopping() {
+    let mut debuggee = scripted_debuggee();
+    let id = debuggee.set_breakpoint(VirtAddr::new(0x1001)).unwrap();
+    debuggee
+        .set_tracepoint(
+            id,
+            vec![
+                TraceAction::Registers,
+                TraceAction::Memory {
+                    address: "$rip - 1".to_string(),
+                    len: 4,
+                },
+                TraceAction::Memory {
+                    address: "0x9000".to_string(),
+                    len: 1,
+                },
+                TraceAction::Expression("$rip + 1".to_string()),
+            ],
+        )
+        .unwrap();
+    assert!(debuggee.set_tracepoint(42, vec![]).is_err());
+
+    // the hit, then the step over the breakpoint
+    debuggee.tracer().set_thread_regs(PID, regs_at(0x1002));
+    debuggee
+        .tracer()
+        .push_wait_status(WaitStatus::Stopped(PID, Signal::SIGTRAP));
+    debuggee
+        .tracer()
+        .push_wait_status(WaitStatus::Stopped(PID, Signal::SIGTRAP));
+    debuggee.resume().unwrap();
+    assert!(matches!(
+        debuggee.wait_for_stop(Some(Duration::ZERO)).unwrap(),
+        WaitOutcome::TimedOut
+    ));
+    assert!(matches!(debuggee.process_state(), ProcessState::Running));
+
+    let frames = debuggee.trace_buffer().frames().collect::<Vec<_>>();
+    assert_eq!(frames.len(), 1);
+    assert_eq!(frames[0].tracepoint, id);
+    assert_eq!(frames[0].pc, VirtAddr::new(0x1001));
+    assert!(frames[0]
+        .registers
+        .iter()
+        .any(|(register, _)| register.name() == "rip"));
+    assert_eq!(
+        frames[0].memory[0].result,
+        Ok((VirtAddr::new(0x1000), vec![0x90; 4]))
+    );
+    assert!(frames[0].memory[1].result.is_err());
+    assert_eq!(frames[0].values[0].result, Ok(Value::Int(0x1002)));
+
+    debuggee.remove_breakpoint(id).unwrap();
+    assert!(debuggee.tracepoints().is_empty());
+}
+
+#[test]
+fn trace_frames_stream_to_a_file_without_the_buffer() {
+    let path = std::env::temp_dir().join(format!(
+        "stupid-dbg-tracer-frames-{}.txt",
+        std::process::id()
+    ));
+    _ = std::fs::remove_file(&path);
+
+    let mut debuggee = scripted_debuggee();
+    let id = debuggee.set_breakpoint(VirtAddr::new(0x1001)).unwrap();
+    debuggee
+        .set_tracepoint(id, vec![TraceAction::Expression("$rip".to_string())])
+        .unwrap();
+    debuggee.trace_buffer_mut().set_capacity(0);
+    debuggee
+        .open_trace_file(&path, Backpressure::Block(Duration::from_secs(1)))
+        .unwrap();
+
+    debuggee.tracer().set_thread_regs(PID, regs_at(0x1002));
+    debuggee
+        .tracer()
+        .push_wait_status(WaitStatus::Stopped(PID, Signal::SIGTRAP));
+    debuggee
+        .tracer()
+        .push_wait_status(WaitStatus::Stopped(PID, Signal::SIGTRAP));
+    debuggee.resume().unwrap();
+    assert!(matches!(
+        debuggee.wait_for_stop(Some(Duration::ZERO)).unwrap(),
+        WaitOutcome::TimedOut
+    ));
+
+    assert!(debuggee.trace_buffer().is_empty());
+    assert_eq!(debuggee.trace_buffer().dropped(), 1);
+    debuggee.close_trace_file().unwrap();
+    assert!(debuggee.trace_file().is_none());
+
+    let text = std::fs::read_to_string(&path).unwrap();
+    assert_eq!(text.lines().count(), 1);
+    assert!(text.contains(&format!("frame=0 tracepoint={} thread=4242 pc=0x1001", id)));
+    _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn steps_survive_signal_handlers() {
+    let mut debuggee = scripted_debuggee();
+    let mut regs = regs_at(0x1000);
+    regs.rsp = 0x7000;
+    debuggee.tracer().set_thread_regs(PID, regs);
+
+    // the handler runs first and returns to the instruction, which is stepped then
+    debuggee.step_instruction().unwrap();
+    regs.rip = 0x1001;
+    debuggee.tracer().set_thread_regs(PID, regs);
+    for signal in [Signal::SIGALRM, Signal::SIGTRAP, Signal::SIGTRAP] {
+        debuggee
+            .tracer()
+            .push_wait_status(WaitStatus::Stopped(PID, signal));
+    }
+    debuggee.update_process_state(true).unwrap();
+
+    assert!(matches!(
+        debuggee.process_state(),
+        ProcessState::Stopped(StopReason::StepComplete)
+    ));
+    let resumptions = debuggee
+        .tracer()
+        .take_calls()
+        .into_iter()
+        .filter(|call| matches!(call, TracerCall::Step(..) | TracerCall::Cont(..)))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        resumptions,
+        vec![
+            TracerCall::Step(PID, None),
+            TracerCall::Cont(PID, Some(Signal::SIGALRM)),
+            TracerCall::Step(PID, None),
+        ]
+    );
+    assert_eq!(debuggee.tracer().memory(0x1000, 1).unwrap(), vec![0x90]);
+
+    // or the step ends at the first instruction of the handler
+    debuggee.set_step_into_handlers(true);
+    debuggee.step_instruction().unwrap();
+    for signal in [Signal::SIGALRM, Signal::SIGTRAP] {
+        debuggee
+            .tracer()
+            .push_wait_status(WaitStatus::Stopped(PID, signal));
+    }
+    debuggee.update_process_state(true).unwrap();
+
+    assert!(matches!(
+        debuggee.process_state(),
+        ProcessState::Stopped(StopReason::StepComplete)
+    ));
+    assert!(debuggee
+        .tracer()
+        .take_calls()
+        .contains(&TracerCall::Step(PID, Some(Signal::SIGALRM))));
+}
+
+#[test]
+fn group_stops_wait_for_sigcont() {
+    let mut debuggee = scripted_debuggee();
+
+    // a stop signal sent to the debuggee takes effect once it's resumed
+    debuggee
+        .tracer()
+        .set_siginfo(PID, unsafe { std::mem::zeroed() });
+    debuggee
+        .tracer()
+        .push_wait_status(WaitStatus::Stopped(PID, Signal::SIGTSTP));
+    debuggee.resume().unwrap();
+    debuggee.update_process_state(true).unwrap();
+    debuggee.tracer().take_calls();
+    debuggee.resume().unwrap();
+    assert!(debuggee
+        .tracer()
+        .take_calls()
+        .contains(&TracerCall::Cont(PID, Some(Signal::SIGTSTP))));
+
+    debuggee.tracer().push_wait_status(WaitStatus::PtraceEvent(
+        PID,
+        Signal::SIGTSTP,
+        libc::PTRACE_EVENT_STOP,
+    ));
+    debuggee.update_process_state(true).unwrap();
+    assert!(matches!(
+        debuggee.process_state(),
+        ProcessState::Stopped(StopReason::GroupStop {
+            signal: Signal::SIGTSTP
+        })
+    ));
+    assert!(debuggee.is_group_stopped());
+
+    // resuming leaves it stopped until SIGCONT
+    debuggee.resume().unwrap();
+    let calls = debuggee.tracer().take_calls();
+    assert!(calls.contains(&TracerCall::Listen(PID)));
+    assert!(!calls
+        .iter()
+        .any(|call| matches!(call, TracerCall::Cont(..))));
+
+    debuggee.tracer().push_wait_status(WaitStatus::PtraceEvent(
+        PID,
+        Signal::SIGTRAP,
+        libc::PTRACE_EVENT_STOP,
+    ));
+    debuggee
+        .tracer()
+        .push_wait_status(WaitStatus::Stopped(PID, Signal::SIGCONT));
+    debuggee.update_process_state(true).unwrap();
+    assert!(matches!(
+        debuggee.process_state(),
+        ProcessState::Stopped(StopReason::Signal(info)) if info.signal == Signal::SIGCONT
+    ));
+    assert!(!debuggee.is_group_stopped());
+    assert!(debuggee
+        .tracer()
+        .take_calls()
+        .contains(&TracerCall::Cont(PID, None)));
+}
+
+#[test]
+fn heap_tracing_records_allocator_calls() {
+    let mut debuggee = scripted_debuggee();
+    debuggee.tracer().map_memory(0x2000, &[0x90; 16]);
+    // the return address pushed by the call
+    debuggee
+        .tracer()
+        .map_memory(0x7000, &0x1005u64.to_ne_bytes());
+    let symbol = |name: &str, address: u64| Symbol {
+        name: name.to_string(),
+        demangled: None,
+        address: VirtAddr::new(address),
+        size: 8,
+        kind: SymbolKind::Function,
+        module: "libc.so.6".to_string(),
+    };
+    let symbols = SymbolTable::from_symbols(vec![symbol("malloc", 0x2000), symbol("free", 0x2008)]);
+
+    assert_eq!(
+        debuggee.start_heap_trace(&symbols).unwrap(),
+        vec![
+            (AllocFunction::Malloc, VirtAddr::new(0x2000)),
+            (AllocFunction::Free, VirtAddr::new(0x2008)),
+        ]
+    );
+    // hooked only while the debuggee runs
+    assert_eq!(debuggee.tracer().memory(0x2000, 1).unwrap(), vec![0x90]);
+    debuggee.resume().unwrap();
+    assert_eq!(debuggee.tracer().memory(0x2000, 1).unwrap(), vec![0xcc]);
+
+    let call = |debuggee: &mut Debuggee<ScriptedTracer>, entry: u64, arg: u64, result: u64| {
+        let mut regs = regs_at(entry + 1);
+        regs.rsp = 0x7000;
+        regs.rdi = arg;
+        debuggee.tracer().set_thread_regs(PID, regs);
+        // the hit and the step over the hook
+        for _ in 0..2 {
+            debuggee
+                .tracer()
+                .push_wait_status(WaitStatus::Stopped(PID, Signal::SIGTRAP));
+        }
+        debuggee.update_process_state(false).unwrap();
+        assert_eq!(debuggee.tracer().memory(0x1005, 1).unwrap(), vec![0xcc]);
+
+        let mut regs = regs_at(0x1006);
+        regs.rsp = 0x7008;
+        regs.rax = result;
+        debuggee.tracer().set_thread_regs(PID, regs);
+        debuggee
+            .tracer()
+            .push_wait_status(WaitStatus::Stopped(PID, Signal::SIGTRAP));
+        debuggee.update_process_state(false).unwrap();
+        assert!(matches!(debuggee.process_state(), ProcessState::Running));
+        assert_eq!(debuggee.tracer().regs(PID).unwrap().rip, 0x1005);
+        assert_eq!(debuggee.tracer().memory(0x1005, 1).unwrap(), vec![0x90]);
+    };
+
+    call(&mut debuggee, 0x2000, 24, 0x5000);
+    let allocation = debuggee.heap_trace().find(VirtAddr::new(0x5010)).unwrap();
+    assert_eq!(allocation.size, 24);
+    assert_eq!(allocation.backtrace, vec![VirtAddr::new(0x1005)]);
+
+    call(&mut debuggee, 0x2008, 0x5000, 0);
+    assert_eq!(debuggee.heap_trace().stats().live_allocations, 0);
+    assert_eq!(
+        debuggee
+            .heap_trace()
+            .find(VirtAddr::new(0x5000))
+            .unwrap()
+            .released_at,
+        Some(VirtAddr::new(0x1005))
+    );
+
+    // any stop takes the hooks out again
+    debuggee
+        .tracer()
+        .push_wait_status(WaitStatus::Stopped(PID, Signal::SIGINT));
+    debuggee.update_process_state(false).unwrap();
+    assert_eq!(debuggee.tracer().memory(0x2000, 1).unwrap(), vec![0x90]);
+    debuggee.stop_heap_trace().unwrap();
+    assert!(!debuggee.is_heap_tracing());
+}
+
+#[test]
+fn coverage_probes_fire_once() {
+    let mut debuggee = scripted_debuggee();
+    assert_eq!(
+        debuggee
+            .start_coverage([0x1004, 0x1008].map(VirtAddr::new))
+            .unwrap(),
+        2
+    );
+    assert_eq!(debuggee.tracer().memory(0x1004, 1).unwrap(), vec![0x90]);
+    debuggee.resume().unwrap();
+    assert_eq!(debuggee.tracer().memory(0x1004, 1).unwrap(), vec![0xcc]);
+    assert_eq!(debuggee.tracer().memory(0x1008, 1).unwrap(), vec![0xcc]);
+
+    debuggee.tracer().set_thread_regs(PID, regs_at(0x1005));
+    debuggee
+        .tracer()
+        .push_wait_status(WaitStatus::Stopped(PID, Signal::SIGTRAP));
+    debuggee.update_process_state(false).unwrap();
+    assert!(matches!(debuggee.process_state(), ProcessState::Running));
+    assert_eq!(debuggee.tracer().regs(PID).unwrap().rip, 0x1004);
+    assert_eq!(debuggee.tracer().memory(0x1004, 1).unwrap(), vec![0x90]);
+    assert!(debuggee
+        .coverage()
+        .is_some_and(|coverage| coverage.is_covered(VirtAddr::new(0x1004))));
+
+    debuggee
+        .tracer()
+        .push_wait_status(WaitStatus::Stopped(PID, Signal::SIGINT));
+    debuggee.update_process_state(false).unwrap();
+    assert_eq!(debuggee.tracer().memory(0x1008, 1).unwrap(), vec![0x90]);
+    // a probe that was hit stays out
+    debuggee.resume().unwrap();
+    assert_eq!(debuggee.tracer().memory(0x1004, 1).unwrap(), vec![0x90]);
+    assert_eq!(debuggee.tracer().memory(0x1008, 1).unwrap(), vec![0xcc]);
+
+    debuggee
+        .tracer()
+        .push_wait_status(WaitStatus::Stopped(PID, Signal::SIGINT));
+    debuggee.update_process_state(false).unwrap();
+    debuggee.stop_coverage().unwrap();
+    assert!(!debuggee.is_collecting_coverage());
+    assert_eq!(debuggee.coverage().map(Coverage::covered_count), Some(1));
+}
+
+#[test]
+fn floating_point_registers_are_written_back() {
+    let mut debuggee = scripted_debuggee();
+    let mxcsr = Register::lookup_by_name("mxcsr").unwrap();
+    debuggee
+        .write_register(mxcsr, RegisterValue::U32(0x7f80))
+        .unwrap();
+    assert_eq!(
+        debuggee.tracer().take_calls(),
+        vec![TracerCall::SetFpRegs(PID)]
+    );
+    assert_eq!(
+        debuggee.registers().unwrap().read_register(mxcsr).unwrap(),
+        RegisterValue::U32(0x7f80)
+    );
+    assert!(debuggee
+        .write_register(
+            Register::lookup_by_name("dr7").unwrap(),
+            RegisterValue::U64(0)
+        )
+        .is_err());
+}
+
+#[test]
+fn only_dirty_registers_are_written_back() {
+    let mut debuggee = scripted_debuggee();
+    let registers = debuggee.registers_mut().unwrap();
+    registers
+        .write_register(
+            Register::lookup_by_name("rax").unwrap(),
+            RegisterValue::U64(42),
+        )
+        .unwrap();
+    assert!(registers.dirty().general_purpose);
+    assert!(!registers.dirty().floating_point);
+    assert!(debuggee.tracer().take_calls().is_empty());
+
+    debuggee.resume().unwrap();
+    assert_eq!(
+        debuggee.tracer().take_calls(),
+        vec![
+            TracerCall::SetRegs(PID, 0x1000),
+            TracerCall::Cont(PID, None)
+        ]
+    );
+    assert_eq!(debuggee.tracer().get_regs(PID).unwrap().rax, 42);
+
+    let tracer = ScriptedTracer::new();
+    tracer.set_thread_regs(PID, regs_at(0x1000));
+    let mut registers = Registers::read_with_tracer(&tracer, PID).unwrap();
+    registers
+        .write_register(
+            Register::lookup_by_name("dr7").unwrap(),
+            RegisterValue::U64(1),
+        )
+        .unwrap();
+    registers.flush(&tracer, PID).unwrap();
+    assert_eq!(
+        tracer.take_calls(),
+        vec![TracerCall::WriteUser(PID, debug_register_offset(7), 1)]
+    );
+    assert!(registers.dirty().is_empty());
+}
+
+#[test]
+fn debug_registers_written_by_hand_are_written_back() {
+    let mut debuggee = scripted_debuggee();
+    debuggee
+        .registers_mut()
+        .unwrap()
+        .write_register(
+            Register::lookup_by_name("dr0").unwrap(),
+            RegisterValue::U64(0x2000),
+        )
+        .unwrap();
+
+    debuggee.resume().unwrap();
+    assert_eq!(
+        debuggee.tracer().take_calls(),
+        vec![
+            TracerCall::WriteUser(PID, debug_register_offset(0), 0x2000),
+            TracerCall::Cont(PID, None)
+        ]
+    );
+    assert!(debuggee.registers().unwrap().dirty().is_empty());
+}
+
+#[test]
+fn fork_child_is_released_without_breakpoints() {
+    let child = Pid::from_raw(4343);
+    let mut debuggee = scripted_debuggee();
+    debuggee.set_breakpoint(VirtAddr::new(0x1001)).unwrap();
+    debuggee.resume().unwrap();
+    debuggee.tracer().take_calls();
+
+    debuggee.tracer().push_wait_status(WaitStatus::PtraceEvent(
+        PID,
+        Signal::SIGTRAP,
+        libc::PTRACE_EVENT_FORK,
+    ));
+    debuggee.tracer().push_event(child.as_raw().into());
+    debuggee
+        .tracer()
+        .push_wait_status(WaitStatus::Stopped(child, Signal::SIGSTOP));
+    debuggee.update_process_state(false).unwrap();
+
+    assert!(matches!(debuggee.process_state(), ProcessState::Running));
+    assert_eq!(
+        debuggee.tracer().take_calls(),
+        vec![
+            TracerCall::WriteMemory(child, 0x1001, vec![0x90]),
+            TracerCall::Detach(child, None),
+            TracerCall::Cont(PID, None),
+        ]
+    );
+}
+
+#[test]
+fn fork_child_sharing_memory_is_left_alone() {
+    let child = Pid::from_raw(4343);
+    let mut debuggee = scripted_debuggee();
+    debuggee.set_breakpoint(VirtAddr::new(0x1001)).unwrap();
+    debuggee.resume().unwrap();
+    debuggee.tracer().take_calls();
+
+    let mut regs = regs_at(0x1004);
+    regs.orig_rax = libc::SYS_clone as u64;
+    regs.rdi = (libc::CLONE_VM | libc::SIGCHLD) as u64;
+    debuggee.tracer().set_thread_regs(PID, regs);
+    debuggee.tracer().push_wait_status(WaitStatus::PtraceEvent(
+        PID,
+        Signal::SIGTRAP,
+        libc::PTRACE_EVENT_FORK,
+    ));
+    debuggee.tracer().push_event(child.as_raw().into());
+    debuggee
+        .tracer()
+        .push_wait_status(WaitStatus::Stopped(child, Signal::SIGSTOP));
+    debuggee.update_process_state(false).unwrap();
+
+    // writing the code back there would disarm the breakpoint in the debuggee
+    assert_eq!(
+        debuggee.tracer().take_calls(),
+        vec![TracerCall::Detach(child, None), TracerCall::Cont(PID, None)]
+    );
+    assert!(debuggee
+        .breakpoints()
+        .values()
+        .all(|breakpoint| breakpoint.is_armed()));
+}
+
+#[test]
+fn fork_child_keeps_breakpoints_when_following_both() {
+    let child = Pid::from_raw(4343);
+    let mut debuggee = scripted_debuggee();
+    debuggee.set_follow_fork(FollowFork::Both);
+    let id = debuggee.set_breakpoint(VirtAddr::new(0x1001)).unwrap();
+    debuggee
+        .set_breakpoint_condition(id, Some("$rax == 1".to_string()))
+        .unwrap();
+    debuggee.resume().unwrap();
+    debuggee.tracer().take_calls();
+
+    debuggee.tracer().push_wait_status(WaitStatus::PtraceEvent(
+        PID,
+        Signal::SIGTRAP,
+        libc::PTRACE_EVENT_FORK,
+    ));
+    debuggee.tracer().push_event(child.as_raw().into());
+    debuggee
+        .tracer()
+        .push_wait_status(WaitStatus::Stopped(child, Signal::SIGSTOP));
+    debuggee.update_process_state(false).unwrap();
+
+    assert_eq!(
+        debuggee.tracer().take_calls(),
+        vec![TracerCall::Cont(PID, None)]
+    );
+    let mut children = debuggee.take_fork_children();
+    assert_eq!(children.len(), 1);
+    assert_eq!(children[0].pid(), child);
+
+    let tracer = ScriptedTracer::new();
+    tracer.set_thread_regs(child, regs_at(0x1004));
+    tracer.map_memory(0x1000, &[0x90, 0xcc, 0x90, 0x90]);
+    let forked = Debuggee::from_fork_child(tracer, children.remove(0)).unwrap();
+    assert_eq!(forked.pid(), child);
+    assert!(matches!(
+        forked.process_state(),
+        ProcessState::Stopped(StopReason::Initial)
+    ));
+    assert_eq!(forked.breakpoints()[&id].address(), VirtAddr::new(0x1001));
+    assert!(forked.breakpoints()[&id].is_armed());
+    assert_eq!(forked.breakpoint_conditions()[&id], "$rax == 1");
+}
+
+#[test]
+fn crash_reports_capture_the_faulting_thread() {
+    let mut debuggee = scripted_debuggee();
+    let mut regs = regs_at(0x1004);
+    regs.rax = 0xdead;
+    debuggee.tracer().set_thread_regs(PID, regs);
+    debuggee.resume().unwrap();
+    debuggee
+        .tracer()
+        .push_wait_status(WaitStatus::Stopped(PID, Signal::SIGSEGV));
+    debuggee.update_process_state(true).unwrap();
+
+    let ProcessState::Stopped(StopReason::Signal(info)) = debuggee.process_state() else {
+        panic!("unexpected state {:?}", debuggee.process_state());
+    };
+    let report = CrashReport::capture(&debuggee, info).unwrap();
+    assert_eq!(report.thread, PID);
+    assert_eq!(report.pc, VirtAddr::new(0x1004));
+    let instruction = report.instruction.unwrap();
+    assert_eq!(instruction.bytes, vec![0x90]);
+    assert_eq!(instruction.text, "nop");
+    assert!(report
+        .registers
+        .contains(&(Register::Rax, RegisterValue::U64(0xdead))));
+    assert_eq!(report.threads.len(), 1);
+    assert_eq!(report.threads[0].frames[0].address, "0x1004");
+    assert_eq!(report.fault_region, None);
+}
@@ -0,0 +1,30 @@
+use stupid_dbg_core::{elf::ElfFile, trampoline::Trampolines, virt_addr::VirtAddr};
+
+#[test]
+fn trampolines_contain_their_ranges() {
+    let trampolines = Trampolines::from_ranges(vec![
+        VirtAddr::new(0x1000)..VirtAddr::new(0x1040),
+        VirtAddr::new(0x7000)..VirtAddr::new(0x8000),
+    ]);
+    assert!(trampolines.contains(VirtAddr::new(0x1000)));
+    assert!(trampolines.contains(VirtAddr::new(0x7fff)));
+    assert!(!trampolines.contains(VirtAddr::new(0x1040)));
+    assert!(!trampolines.contains(VirtAddr::new(0x2000)));
+    assert!(!Trampolines::default().contains(VirtAddr::new(0x1000)));
+}
+
+#[test]
+fn plt_sections_are_moved_by_the_bias() {
+    let elf = ElfFile::read("/proc/self/exe").unwrap();
+    let plt = elf
+        .sections()
+        .iter()
+        .filter(|section| section.name.starts_with(".plt") && section.size > 0)
+        .map(|section| section.address + 0x1000..section.address + 0x1000 + section.size)
+        .collect::<Vec<_>>();
+    let ranges = Trampolines::plt_sections(&elf, 0x1000)
+        .into_iter()
+        .map(|range| range.start.as_u64()..range.end.as_u64())
+        .collect::<Vec<_>>();
+    assert_eq!(ranges, plt);
+}
@@ -0,0 +1,38 @@
+use anyhow::anyhow;
+use stupid_dbg_core::unit_parser::{parse_units, PARALLEL_PARSE_SIZE};
+
+fn squares(unit: &u64, parsed: &mut Vec<u64>) -> anyhow::Result<()> {
+    parsed.push(unit * unit);
+    Ok(())
+}
+
+#[test]
+fn small_sections_are_parsed_in_order() {
+    let units = (0..100).collect::<Vec<u64>>();
+    let squared = units.iter().map(|unit| unit * unit).collect::<Vec<_>>();
+    assert_eq!(
+        parse_units("small", 1024, &units, squares).unwrap(),
+        squared
+    );
+}
+
+#[test]
+fn big_sections_parse_every_unit_once() {
+    let units = (0..10_000).collect::<Vec<u64>>();
+    let squared = units.iter().map(|unit| unit * unit).collect::<Vec<_>>();
+    let mut parsed = parse_units("big", PARALLEL_PARSE_SIZE, &units, squares).unwrap();
+    parsed.sort();
+    assert_eq!(parsed, squared);
+}
+
+#[test]
+fn a_unit_that_fails_fails_the_section() {
+    let units = (0..10_000).collect::<Vec<u64>>();
+    for size in [0, PARALLEL_PARSE_SIZE] {
+        let parsed = parse_units("broken", size, &units, |unit, parsed| match unit {
+            4321 => Err(anyhow!("truncated unit")),
+            _ => squares(unit, parsed),
+        });
+        assert!(parsed.is_err());
+    }
+}
@@ -0,0 +1,27 @@
+use stupid_dbg_core::virt_addr::VirtAddr;
+
+#[test]
+fn checked_arithmetic() {
+    let addr = VirtAddr::new(0x1000);
+    assert_eq!(addr.checked_add(0x10), Some(VirtAddr::new(0x1010)));
+    assert_eq!(VirtAddr::new(u64::MAX).checked_add(1), None);
+    assert_eq!(addr.checked_sub(0x1001), None);
+    assert_eq!(VirtAddr::new(0x1010).offset_from(addr), Some(0x10));
+    assert_eq!(addr.offset_from(VirtAddr::new(0x1010)), None);
+}
+
+#[test]
+fn alignment() {
+    let addr = VirtAddr::new(0x1234);
+    assert_eq!(addr.align_down(0x1000), VirtAddr::new(0x1000));
+    assert_eq!(addr.align_up(0x1000), Some(VirtAddr::new(0x2000)));
+    assert!(VirtAddr::new(0x2000).is_aligned(0x1000));
+    assert!(!addr.is_aligned(8));
+    assert_eq!(VirtAddr::new(u64::MAX).align_up(0x1000), None);
+}
+
+#[test]
+fn display_in_hex() {
+    assert_eq!(VirtAddr::new(0xdead).to_string(), "0xdead");
+    assert_eq!(format!("{:x}", VirtAddr::new(0xdead)), "dead");
+}
@@ -0,0 +1,76 @@
+use nix::unistd::Pid;
+use stupid_dbg_core::{
+    virt_addr::VirtAddr,
+    watchpoint::{syscall_buffer, watched_pages, SyscallBuffer, WatchScope, WatchStrategy},
+};
+
+#[test]
+fn pages_of_watched_ranges() {
+    assert_eq!(
+        watched_pages(VirtAddr::new(0x1000), 0x1000, 0x1000),
+        VirtAddr::new(0x1000)..VirtAddr::new(0x2000)
+    );
+    assert_eq!(
+        watched_pages(VirtAddr::new(0x1ff8), 16, 0x1000),
+        VirtAddr::new(0x1000)..VirtAddr::new(0x3000)
+    );
+    assert_eq!(
+        watched_pages(VirtAddr::new(0x10010), 0x100000, 0x1000),
+        VirtAddr::new(0x10000)..VirtAddr::new(0x111000)
+    );
+}
+
+#[test]
+fn buffers_of_syscalls() {
+    let args = [3, 0x1000, 64, 0, 0, 0];
+    assert_eq!(
+        syscall_buffer(libc::SYS_read, &args),
+        Some(SyscallBuffer::Flat {
+            address: VirtAddr::new(0x1000),
+            len: 64
+        })
+    );
+    assert_eq!(
+        syscall_buffer(libc::SYS_readv, &args),
+        Some(SyscallBuffer::Vectored {
+            iov: VirtAddr::new(0x1000),
+            count: 64
+        })
+    );
+    assert_eq!(
+        syscall_buffer(libc::SYS_getrandom, &[0x2000, 16, 0, 0, 0, 0]),
+        Some(SyscallBuffer::Flat {
+            address: VirtAddr::new(0x2000),
+            len: 16
+        })
+    );
+    // writes from the buffer, rather than into it
+    assert_eq!(syscall_buffer(libc::SYS_write, &args), None);
+}
+
+#[test]
+fn strategies() {
+    assert_eq!(
+        WatchStrategy::PageProtection { pages: 1 }.to_string(),
+        "page protection, 1 page"
+    );
+    assert_eq!(
+        WatchStrategy::PageProtection { pages: 257 }.to_string(),
+        "page protection, 257 pages"
+    );
+}
+
+#[test]
+fn scopes() {
+    let scope = WatchScope {
+        thread: Pid::from_raw(4242),
+        stack_pointer: VirtAddr::new(0x7fff_0000),
+    };
+    let local = VirtAddr::new(0x7fff_0010);
+
+    assert!(!scope.is_popped(local, 8, VirtAddr::new(0x7fff_0000)));
+    // still within the red zone
+    assert!(!scope.is_popped(local, 8, VirtAddr::new(0x7fff_0090)));
+    assert!(scope.is_popped(local, 8, VirtAddr::new(0x7fff_0098)));
+    assert!(!scope.is_popped(VirtAddr::new(0x10), 8, VirtAddr::new(0x20)));
+}
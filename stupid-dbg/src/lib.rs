@@ -1,6 +0,0 @@
-#![feature(iter_intersperse)]
-
-pub(crate) mod aux;
-pub mod debuggee;
-pub mod debugger;
-pub mod register;